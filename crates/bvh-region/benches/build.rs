@@ -0,0 +1,51 @@
+use std::hint::black_box;
+
+use bvh_region::Bvh;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use geometry::aabb::Aabb;
+use glam::Vec3;
+
+const ENTITY_COUNTS: &[usize] = &[1_000, 10_000];
+
+/// Player sized bounding boxes spread over a 1000 by 1000 block area
+fn random_entities(count: usize) -> Vec<Aabb> {
+    (0..count)
+        .map(|_| {
+            let feet = Vec3::new(
+                rand::random::<f32>() * 1000.0,
+                rand::random::<f32>() * 64.0,
+                rand::random::<f32>() * 1000.0,
+            );
+            Aabb::create(feet, 0.6, 1.8)
+        })
+        .collect()
+}
+
+fn build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+
+    for &count in ENTITY_COUNTS {
+        let elements = random_entities(count);
+
+        group.bench_with_input(
+            BenchmarkId::new("single_threaded", count),
+            &elements,
+            |b, elements| {
+                b.iter(|| Bvh::build_with_threads(black_box(elements.clone()), |x| *x, 1));
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("parallel", count),
+            &elements,
+            |b, elements| {
+                b.iter(|| Bvh::build(black_box(elements.clone()), |x| *x));
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, build);
+criterion_main!(benches);
@@ -1,6 +1,7 @@
 use std::fmt::Debug;
 
 use geometry::aabb::Aabb;
+use rayon::prelude::*;
 
 use crate::{
     Bvh, ELEMENTS_TO_ACTIVATE_LEAF, VOLUME_TO_ACTIVATE_LEAF, node::BvhNode, sort_by_largest_axis,
@@ -32,9 +33,21 @@ impl<T> Bvh<T>
 where
     T: Debug + Send + Copy + Sync,
 {
+    /// Builds the tree using every thread of the rayon thread pool. The top levels of the tree are
+    /// split between threads until each thread has a subtree of its own.
+    pub fn build(elements: Vec<T>, get_aabb: (impl GetAabb<T> + Sync)) -> Self {
+        Self::build_with_threads(elements, get_aabb, utils::thread_count_pow2())
+    }
+
+    /// Builds the tree using at most `max_threads` threads, which is rounded down to a power of
+    /// two. One thread builds the tree without rayon.
     #[tracing::instrument(skip_all, fields(elements_len = elements.len()))]
-    pub fn build(mut elements: Vec<T>, get_aabb: (impl GetAabb<T> + Sync)) -> Self {
-        let max_threads = utils::thread_count_pow2();
+    pub fn build_with_threads(
+        mut elements: Vec<T>,
+        get_aabb: (impl GetAabb<T> + Sync),
+        max_threads: usize,
+    ) -> Self {
+        let max_threads = utils::floor_pow2(max_threads.max(1));
 
         let len = elements.len();
 
@@ -80,8 +93,19 @@ pub fn build_in<T>(
 where
     T: Send + Copy + Sync + Debug,
 {
-    // aabb that encompasses all elements
-    let aabb: Aabb = elements.iter().map(get_aabb).collect();
+    // aabb that encompasses all elements. The top levels contain most of the elements, so they are
+    // split between threads as well.
+    let aabb: Aabb = if max_threads > 1 {
+        elements.par_iter().map(get_aabb).reduce(
+            || Aabb::NULL,
+            |mut a, b| {
+                a.expand_to_fit(&b);
+                a
+            },
+        )
+    } else {
+        elements.iter().map(get_aabb).collect()
+    };
     let volume = aabb.volume();
 
     if elements.len() <= ELEMENTS_TO_ACTIVATE_LEAF || volume <= VOLUME_TO_ACTIVATE_LEAF {
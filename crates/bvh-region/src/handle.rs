@@ -0,0 +1,214 @@
+use geometry::{aabb::Aabb, ray::Ray};
+use glam::Vec3;
+use ordered_float::NotNan;
+
+use crate::Bvh;
+
+/// A stable reference to an element of a [`HandleBvh`]. Handles stay valid when the tree is
+/// rebuilt and become invalid once their element is removed, even if the slot is reused.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BvhHandle {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Slot<T> {
+    value: Option<(T, Aabb)>,
+    generation: u32,
+}
+
+/// A [`Bvh`] whose elements are inserted, moved and removed one at a time through
+/// [`BvhHandle`]s.
+///
+/// Queries prune the tree with the bounding boxes from the last [`HandleBvh::rebuild`], so
+/// inserted and moved elements are only reliably found once it is called. This lets many changes
+/// be made before paying for a single rebuild. Removed elements are never returned, even before
+/// the rebuild.
+#[derive(Debug, Clone)]
+pub struct HandleBvh<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    tree: Bvh<BvhHandle>,
+    dirty: bool,
+}
+
+impl<T> Default for HandleBvh<T> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            tree: Bvh::default(),
+            dirty: false,
+        }
+    }
+}
+
+impl<T> HandleBvh<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, element: T, aabb: Aabb) -> BvhHandle {
+        self.dirty = true;
+
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.value = Some((element, aabb));
+
+            return BvhHandle {
+                index,
+                generation: slot.generation,
+            };
+        }
+
+        let index = u32::try_from(self.slots.len()).expect("too many elements in bvh");
+        self.slots.push(Slot {
+            value: Some((element, aabb)),
+            generation: 0,
+        });
+
+        BvhHandle {
+            index,
+            generation: 0,
+        }
+    }
+
+    /// Removes the element of the handle, or returns `None` if it was already removed
+    pub fn remove(&mut self, handle: BvhHandle) -> Option<T> {
+        let slot = self.slot_mut(handle)?;
+        let (element, _) = slot.value.take()?;
+
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        self.dirty = true;
+
+        Some(element)
+    }
+
+    /// Moves the element of the handle to `aabb`. Returns false if the element was removed.
+    pub fn update(&mut self, handle: BvhHandle, aabb: Aabb) -> bool {
+        let Some((_, current)) = self.slot_mut(handle).and_then(|slot| slot.value.as_mut()) else {
+            return false;
+        };
+
+        *current = aabb;
+        self.dirty = true;
+        true
+    }
+
+    #[must_use]
+    pub fn get(&self, handle: BvhHandle) -> Option<&T> {
+        self.value(handle).map(|(element, _)| element)
+    }
+
+    #[must_use]
+    pub fn get_mut(&mut self, handle: BvhHandle) -> Option<&mut T> {
+        self.slot_mut(handle)?
+            .value
+            .as_mut()
+            .map(|(element, _)| element)
+    }
+
+    #[must_use]
+    pub fn aabb(&self, handle: BvhHandle) -> Option<Aabb> {
+        self.value(handle).map(|&(_, aabb)| aabb)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether elements were changed since the last [`HandleBvh::rebuild`]
+    #[must_use]
+    pub const fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Rebuilds the tree if any element was inserted, moved or removed
+    pub fn rebuild(&mut self)
+    where
+        T: Sync,
+    {
+        if !self.dirty {
+            return;
+        }
+
+        let handles = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.value.is_some())
+            .map(|(index, slot)| BvhHandle {
+                index: index as u32,
+                generation: slot.generation,
+            })
+            .collect();
+
+        let slots = &self.slots;
+        self.tree = Bvh::build(handles, |handle: &BvhHandle| {
+            slots[handle.index as usize]
+                .value
+                .as_ref()
+                .map_or(Aabb::NULL, |&(_, aabb)| aabb)
+        });
+        self.dirty = false;
+    }
+
+    /// The elements which collide with `target`
+    pub fn range(&self, target: Aabb) -> impl Iterator<Item = (BvhHandle, &T)> + '_ {
+        self.tree
+            .range_filtered(target, self.get_aabb(), |&handle| self.contains(handle))
+            .filter_map(|&handle| Some((handle, self.get(handle)?)))
+    }
+
+    /// The closest element to `target` and the distance squared to it
+    #[must_use]
+    pub fn get_closest(&self, target: Vec3) -> Option<(BvhHandle, &T, f64)> {
+        let (&handle, dist2) =
+            self.tree
+                .get_closest_filtered(target, self.get_aabb(), |&handle| self.contains(handle))?;
+
+        Some((handle, self.get(handle)?, dist2))
+    }
+
+    /// The closest element hit by the ray and the intersection distance (t) along the ray
+    #[must_use]
+    pub fn first_ray_collision(&self, ray: Ray) -> Option<(BvhHandle, &T, NotNan<f32>)> {
+        let (&handle, t) = self.tree.first_ray_collision(ray, self.get_aabb())?;
+        Some((handle, self.get(handle)?, t))
+    }
+
+    fn contains(&self, handle: BvhHandle) -> bool {
+        self.value(handle).is_some()
+    }
+
+    /// The current bounding box of each element, used for the final test against each element.
+    /// Nodes are still pruned with the boxes from the last rebuild. Removed elements have an empty
+    /// bounding box so that they never match.
+    fn get_aabb(&self) -> impl Fn(&BvhHandle) -> Aabb + '_ {
+        |&handle| self.aabb(handle).unwrap_or(Aabb::NULL)
+    }
+
+    fn value(&self, handle: BvhHandle) -> Option<&(T, Aabb)> {
+        let slot = self.slots.get(handle.index as usize)?;
+
+        if slot.generation != handle.generation {
+            return None;
+        }
+
+        slot.value.as_ref()
+    }
+
+    fn slot_mut(&mut self, handle: BvhHandle) -> Option<&mut Slot<T>> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        (slot.generation == handle.generation).then_some(slot)
+    }
+}
@@ -17,9 +17,12 @@ mod node;
 use node::BvhNode;
 
 mod build;
+mod handle;
 mod query;
 mod utils;
 
+pub use handle::{BvhHandle, HandleBvh};
+
 #[cfg(feature = "plot")]
 pub mod plot;
 
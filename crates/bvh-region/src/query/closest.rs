@@ -10,6 +10,20 @@ use crate::{Bvh, Node, utils::NodeOrd};
 impl<T: Debug> Bvh<T> {
     /// Returns the closest element to the target and the distance squared to it.
     pub fn get_closest(&self, target: Vec3, get_aabb: impl Fn(&T) -> Aabb) -> Option<(&T, f64)> {
+        self.get_closest_filtered(target, get_aabb, |_| true)
+    }
+
+    /// Returns the closest element to the target for which `filter` returns true and the distance
+    /// squared to it.
+    ///
+    /// The filter is evaluated while the tree is traversed, so elements which are rejected do not
+    /// stop the search from pruning nodes which are further away than the closest accepted element.
+    pub fn get_closest_filtered(
+        &self,
+        target: Vec3,
+        get_aabb: impl Fn(&T) -> Aabb,
+        filter: impl Fn(&T) -> bool,
+    ) -> Option<(&T, f64)> {
         let mut min_dist2 = f64::INFINITY;
         let mut min_node = None;
 
@@ -20,6 +34,7 @@ impl<T: Debug> Bvh<T> {
             Node::Leaf(leaf) => {
                 return leaf
                     .iter()
+                    .filter(|elem| filter(elem))
                     .map(|elem| {
                         let aabb = get_aabb(elem);
                         let dist2 = aabb.dist2(target);
@@ -61,6 +76,7 @@ impl<T: Debug> Bvh<T> {
                     Node::Leaf(leaf) => {
                         let Some((elem, dist2)) = leaf
                             .iter()
+                            .filter(|elem| filter(elem))
                             .map(|elem| {
                                 let aabb = get_aabb(elem);
                                 let dist2 = aabb.dist2(target);
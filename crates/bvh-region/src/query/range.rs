@@ -1,7 +1,11 @@
 use std::fmt::Debug;
 
 use arrayvec::ArrayVec;
-use geometry::aabb::Aabb;
+use geometry::{
+    aabb::Aabb,
+    plane::{Plane, is_inside_planes},
+};
+use glam::Vec3;
 
 use crate::{Bvh, Node, utils::GetAabb};
 
@@ -11,28 +15,80 @@ impl<T: Debug> Bvh<T> {
         target: Aabb,
         get_aabb: impl GetAabb<T> + 'a,
     ) -> impl Iterator<Item = &'a T> + 'a {
-        CollisionIter::new(self, target, get_aabb)
+        self.range_filtered(target, get_aabb, |_| true)
+    }
+
+    /// Returns the elements which collide with the target and for which `filter` returns true.
+    ///
+    /// The filter is evaluated before the bounding box of an element is looked up, so rejected
+    /// elements are cheap to skip.
+    pub fn range_filtered<'a>(
+        &'a self,
+        target: Aabb,
+        get_aabb: impl GetAabb<T> + 'a,
+        filter: impl Fn(&T) -> bool + 'a,
+    ) -> impl Iterator<Item = &'a T> + 'a {
+        CollisionIter::new(self, move |aabb| aabb.collides(&target), get_aabb, filter)
+    }
+
+    /// Returns the elements whose bounding box is at most `radius` away from `center`, such as
+    /// the entities hit by an explosion
+    pub fn range_sphere<'a>(
+        &'a self,
+        center: Vec3,
+        radius: f32,
+        get_aabb: impl GetAabb<T> + 'a,
+    ) -> impl Iterator<Item = &'a T> + 'a {
+        let radius2 = f64::from(radius) * f64::from(radius);
+        CollisionIter::new(
+            self,
+            move |aabb| aabb.dist2(center) <= radius2,
+            get_aabb,
+            |_| true,
+        )
+    }
+
+    /// Returns the elements whose bounding box is inside every plane, such as the planes of a
+    /// view frustum. Elements near the corners of the planes may be returned even though they are
+    /// outside.
+    pub fn range_frustum<'a>(
+        &'a self,
+        planes: &'a [Plane],
+        get_aabb: impl GetAabb<T> + 'a,
+    ) -> impl Iterator<Item = &'a T> + 'a {
+        CollisionIter::new(
+            self,
+            move |aabb| is_inside_planes(aabb, planes),
+            get_aabb,
+            |_| true,
+        )
     }
 }
 
-pub struct CollisionIter<'a, T, F> {
+/// Iterates over the elements whose bounding box passes `test`. Nodes which do not pass `test`
+/// are skipped along with all of their elements, so `test` must pass for a node if it passes for
+/// any box inside of it.
+pub struct CollisionIter<'a, T, S, F, P> {
     bvh: &'a Bvh<T>,
-    target: Aabb,
+    test: S,
     get_aabb: F,
+    filter: P,
     stack: ArrayVec<Node<'a, T>, 64>,
     current_leaf: Option<(&'a [T], usize)>,
 }
 
-impl<'a, T, F> CollisionIter<'a, T, F>
+impl<'a, T, S, F, P> CollisionIter<'a, T, S, F, P>
 where
+    S: Fn(&Aabb) -> bool,
     F: GetAabb<T>,
+    P: Fn(&T) -> bool,
 {
-    fn new(bvh: &'a Bvh<T>, target: Aabb, get_aabb: F) -> Self {
+    fn new(bvh: &'a Bvh<T>, test: S, get_aabb: F, filter: P) -> Self {
         let mut stack = ArrayVec::new();
         // Initialize stack with root if it collides
         match bvh.root() {
             Node::Internal(root) => {
-                if root.aabb.collides(&target) {
+                if test(&root.aabb) {
                     stack.push(Node::Internal(root));
                 }
             }
@@ -44,17 +100,20 @@ where
 
         Self {
             bvh,
-            target,
+            test,
             get_aabb,
+            filter,
             stack,
             current_leaf: None,
         }
     }
 }
 
-impl<'a, T, F> Iterator for CollisionIter<'a, T, F>
+impl<'a, T, S, F, P> Iterator for CollisionIter<'a, T, S, F, P>
 where
+    S: Fn(&Aabb) -> bool,
     F: GetAabb<T>,
+    P: Fn(&T) -> bool,
 {
     type Item = &'a T;
 
@@ -66,8 +125,12 @@ where
                     let elem = &leaf[*index];
                     *index += 1;
 
+                    if !(self.filter)(elem) {
+                        continue;
+                    }
+
                     let elem_aabb = (self.get_aabb)(elem);
-                    if elem_aabb.collides(&self.target) {
+                    if (self.test)(&elem_aabb) {
                         return Some(elem);
                     }
                     // If not colliding, continue to next element in leaf
@@ -86,7 +149,7 @@ where
                     for child in internal.children(self.bvh) {
                         match child {
                             Node::Internal(child_node) => {
-                                if child_node.aabb.collides(&self.target) {
+                                if (self.test)(&child_node.aabb) {
                                     self.stack.push(Node::Internal(child_node));
                                 }
                             }
@@ -9,13 +9,29 @@ impl<T: Debug> Bvh<T> {
     /// Returns the closest element hit by the ray and the intersection distance (t) along the ray.
     ///
     /// If no element is hit, returns `None`.
-    #[allow(clippy::excessive_nesting)]
     pub fn first_ray_collision(
         &self,
         ray: Ray,
         get_aabb: impl Fn(&T) -> Aabb,
     ) -> Option<(&T, NotNan<f32>)> {
-        let mut closest_t = NotNan::new(f32::INFINITY).unwrap();
+        self.first_ray_collision_within(ray, f32::INFINITY, get_aabb)
+    }
+
+    /// Returns the closest element hit by the ray before `max_t` and the intersection distance (t)
+    /// along the ray. Nodes which start after `max_t` are never visited.
+    ///
+    /// If no element is hit, returns `None`.
+    #[expect(
+        clippy::excessive_nesting,
+        reason = "the traversal loop matches on the node kind of each child"
+    )]
+    pub fn first_ray_collision_within(
+        &self,
+        ray: Ray,
+        max_t: f32,
+        get_aabb: impl Fn(&T) -> Aabb,
+    ) -> Option<(&T, NotNan<f32>)> {
+        let mut closest_t = NotNan::new(max_t).ok()?;
         let mut closest_elem = None;
 
         let root = self.root();
@@ -37,7 +53,7 @@ impl<T: Debug> Bvh<T> {
 
                 // Check if the ray hits the root node's AABB
                 if let Some(t) = internal.aabb.intersect_ray(&ray) {
-                    if t.into_inner() >= 0.0 {
+                    if t < closest_t && t.into_inner() >= 0.0 {
                         heap.push(Reverse(NodeOrd {
                             node: internal,
                             by: t,
@@ -84,4 +100,34 @@ impl<T: Debug> Bvh<T> {
 
         closest_elem.map(|elem| (elem, closest_t))
     }
+
+    /// Whether the ray hits any element before `max_t`, such as for checking if there is a line of
+    /// sight. This stops at the first hit instead of searching for the closest one.
+    pub fn any_ray_collision(&self, ray: Ray, max_t: f32, get_aabb: impl Fn(&T) -> Aabb) -> bool {
+        let hits = |aabb: Aabb| {
+            aabb.intersect_ray(&ray)
+                .is_some_and(|t| t.into_inner() >= 0.0 && t.into_inner() < max_t)
+        };
+
+        let mut stack: Vec<Node<'_, T>> = vec![self.root()];
+
+        while let Some(node) = stack.pop() {
+            match node {
+                Node::Internal(internal) => {
+                    if !hits(internal.aabb) {
+                        continue;
+                    }
+
+                    stack.extend(internal.children(self));
+                }
+                Node::Leaf(elems) => {
+                    if elems.iter().any(|elem| hits(get_aabb(elem))) {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        false
+    }
 }
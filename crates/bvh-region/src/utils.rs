@@ -5,17 +5,19 @@ use crate::node::BvhNode;
 
 /// get number of threads that is pow of 2
 pub fn thread_count_pow2() -> usize {
-    let max_threads_tentative = rayon::current_num_threads();
-    // let max
-
     // does not make sense to not have a power of two
-    let mut max_threads = max_threads_tentative.next_power_of_two();
+    floor_pow2(rayon::current_num_threads())
+}
+
+/// The largest power of two which is at most `value`, which must not be 0
+pub const fn floor_pow2(value: usize) -> usize {
+    let mut pow2 = value.next_power_of_two();
 
-    if max_threads != max_threads_tentative {
-        max_threads >>= 1;
+    if pow2 != value {
+        pow2 >>= 1;
     }
 
-    max_threads
+    pow2
 }
 
 pub trait GetAabb<T>: Fn(&T) -> Aabb {}
@@ -1,9 +1,10 @@
 use std::collections::HashSet;
 
-use approx::assert_relative_eq;
-use bvh_region::Bvh;
+use approx::{assert_relative_eq, relative_eq};
+use bvh_region::{Bvh, HandleBvh};
 use geometry::{
     aabb::{Aabb, OrderedAabb},
+    plane::Plane,
     ray::Ray,
 };
 use glam::Vec3;
@@ -62,6 +63,110 @@ fn test_multiple_aabbs_along_ray() {
     assert!(dist < NotNan::new(2.0).unwrap());
 }
 
+#[test]
+fn test_range_sphere_and_frustum() {
+    // A 10 by 10 grid of unit boxes
+    let elements: Vec<Aabb> = (0..100)
+        .map(|i| {
+            let min = Vec3::new((i % 10) as f32 * 2.0, 0.0, (i / 10) as f32 * 2.0);
+            Aabb::new(min, min + Vec3::ONE)
+        })
+        .collect();
+
+    let bvh = Bvh::build(elements.clone(), copied);
+
+    let center = Vec3::new(9.5, 0.5, 9.5);
+    let radius = 3.0;
+
+    let sphere: HashSet<_> = bvh
+        .range_sphere(center, radius, copied)
+        .map(|aabb| OrderedAabb::try_from(*aabb).unwrap())
+        .collect();
+    let brute: HashSet<_> = elements
+        .iter()
+        .filter(|aabb| aabb.dist2(center) <= f64::from(radius * radius))
+        .map(|aabb| OrderedAabb::try_from(*aabb).unwrap())
+        .collect();
+    assert!(!brute.is_empty());
+    assert_eq!(sphere, brute);
+
+    // Everything with x between 4 and 7
+    let planes = [
+        Plane::from_point_normal(Vec3::new(4.0, 0.0, 0.0), Vec3::X),
+        Plane::from_point_normal(Vec3::new(7.0, 0.0, 0.0), Vec3::NEG_X),
+    ];
+
+    let frustum: Vec<_> = bvh.range_frustum(&planes, copied).collect();
+    assert_eq!(frustum.len(), 20);
+    assert!(
+        frustum
+            .iter()
+            .all(|aabb| [4.0, 6.0].iter().any(|&x| relative_eq!(aabb.min.x, x)))
+    );
+}
+
+#[test]
+fn test_handles() {
+    let mut bvh = HandleBvh::new();
+
+    let a = bvh.insert("a", Aabb::new(Vec3::ZERO, Vec3::ONE));
+    let b = bvh.insert("b", Aabb::new(Vec3::splat(10.0), Vec3::splat(11.0)));
+    bvh.rebuild();
+
+    let (closest, element, _) = bvh.get_closest(Vec3::splat(9.0)).unwrap();
+    assert_eq!((closest, *element), (b, "b"));
+
+    // Moving `b` is only visible to queries after a rebuild
+    assert!(bvh.update(b, Aabb::new(Vec3::splat(-11.0), Vec3::splat(-10.0))));
+    bvh.rebuild();
+
+    let (closest, ..) = bvh.get_closest(Vec3::splat(9.0)).unwrap();
+    assert_eq!(closest, a);
+
+    // Removed elements are not returned even before a rebuild
+    assert_eq!(bvh.remove(a), Some("a"));
+    assert_eq!(bvh.range(Aabb::new(Vec3::ZERO, Vec3::ONE)).count(), 0);
+    assert_eq!(bvh.remove(a), None);
+    assert!(!bvh.update(a, Aabb::new(Vec3::ZERO, Vec3::ONE)));
+
+    // The slot of `a` is reused, but the old handle stays invalid
+    let c = bvh.insert("c", Aabb::new(Vec3::ZERO, Vec3::ONE));
+    bvh.rebuild();
+
+    assert_eq!(bvh.get(a), None);
+    assert_eq!(bvh.get(c), Some(&"c"));
+    assert_eq!(bvh.len(), 2);
+
+    let in_range: Vec<_> = bvh.range(Aabb::new(Vec3::ZERO, Vec3::ONE)).collect();
+    assert_eq!(in_range, vec![(c, &"c")]);
+}
+
+#[test]
+fn test_ray_collision_within() {
+    // Enough elements that the tree has internal nodes
+    let elements: Vec<Aabb> = (0..100)
+        .map(|i| {
+            let x = (i as f32).mul_add(2.0, 1.0);
+            Aabb::new(Vec3::new(x, -0.5, -0.5), Vec3::new(x + 1.0, 0.5, 0.5))
+        })
+        .collect();
+
+    let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+    let bvh = Bvh::build(elements.clone(), copied);
+
+    let (closest, dist) = bvh.first_ray_collision_within(ray, 10.0, copied).unwrap();
+    assert_eq!(closest, &elements[0]);
+    assert_relative_eq!(dist.into_inner(), 1.0);
+
+    assert_eq!(bvh.first_ray_collision_within(ray, 0.5, copied), None);
+    assert!(bvh.any_ray_collision(ray, 1.5, copied));
+    assert!(!bvh.any_ray_collision(ray, 0.5, copied));
+
+    // Pointing away from every element
+    let away = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+    assert!(!bvh.any_ray_collision(away, f32::INFINITY, copied));
+}
+
 proptest! {
     #[test]
     fn test_rays_origin_inside_aabb(
@@ -124,6 +229,47 @@ fn test_empty_get_closest() {
     assert_eq!(bvh.get_closest(target, copied), None);
 }
 
+#[test]
+fn test_get_closest_filtered() {
+    // Many rejected elements are close to the target and one accepted element is far away
+    let elements: Vec<(usize, Aabb)> = (0..100)
+        .map(|i| {
+            let offset = i as f32;
+            let min = Vec3::new(offset, 0.0, 0.0);
+            (i, Aabb::new(min, min + Vec3::ONE))
+        })
+        .collect();
+
+    let bvh = Bvh::build(elements, |(_, aabb)| *aabb);
+    let target = Vec3::new(0.5, 0.5, 0.5);
+
+    let (closest, _) = bvh
+        .get_closest_filtered(target, |(_, aabb)| *aabb, |(i, _)| *i == 99)
+        .unwrap();
+    assert_eq!(closest.0, 99);
+
+    let (closest, dist2) = bvh
+        .get_closest_filtered(target, |(_, aabb)| *aabb, |(i, _)| i % 2 == 1)
+        .unwrap();
+    assert_eq!(closest.0, 1);
+    assert_relative_eq!(dist2, 0.25);
+
+    assert_eq!(
+        bvh.get_closest_filtered(target, |(_, aabb)| *aabb, |_| false),
+        None
+    );
+
+    let in_range: HashSet<usize> = bvh
+        .range_filtered(
+            Aabb::new(Vec3::ZERO, Vec3::new(10.0, 1.0, 1.0)),
+            |(_, aabb)| *aabb,
+            |(i, _)| i % 2 == 0,
+        )
+        .map(|(i, _)| *i)
+        .collect();
+    assert_eq!(in_range, HashSet::from([0, 2, 4, 6, 8, 10]));
+}
+
 proptest! {
     #[test]
     fn test_get_closest_correctness(
@@ -0,0 +1,61 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+
+/// A plane which splits space into an inside, which its normal points towards, and an outside
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Plane {
+    pub normal: Vec3,
+    /// The signed distance of the plane from the origin along the normal
+    pub distance: f32,
+}
+
+impl Plane {
+    #[must_use]
+    pub const fn new(normal: Vec3, distance: f32) -> Self {
+        Self { normal, distance }
+    }
+
+    /// The plane through `point` whose inside is in the direction of `normal`
+    #[must_use]
+    pub fn from_point_normal(point: Vec3, normal: Vec3) -> Self {
+        let normal = normal.normalize();
+        Self::new(normal, -normal.dot(point))
+    }
+
+    /// The distance of `point` from the plane, which is negative on the outside
+    #[must_use]
+    pub fn signed_distance(&self, point: Vec3) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+
+    /// Whether the whole of `aabb` is on the outside of the plane
+    #[must_use]
+    pub fn is_outside(&self, aabb: &Aabb) -> bool {
+        // The corner which is furthest towards the inside
+        let corner = Vec3::select(self.normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+        self.signed_distance(corner) < 0.0
+    }
+}
+
+/// Whether `aabb` may be inside every plane. Boxes near the corners of the planes may be counted
+/// as inside even though they are not.
+#[must_use]
+pub fn is_inside_planes(aabb: &Aabb, planes: &[Plane]) -> bool {
+    !planes.iter().any(|plane| plane.is_outside(aabb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_outside() {
+        let plane = Plane::from_point_normal(Vec3::new(0.0, 5.0, 0.0), Vec3::Y);
+
+        assert!(plane.is_outside(&Aabb::new(Vec3::ZERO, Vec3::splat(4.0))));
+        assert!(!plane.is_outside(&Aabb::new(Vec3::ZERO, Vec3::splat(6.0))));
+        assert!(!plane.is_outside(&Aabb::new(Vec3::splat(6.0), Vec3::splat(7.0))));
+    }
+}
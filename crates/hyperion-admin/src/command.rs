@@ -0,0 +1,22 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion_clap::{CommandPermission, CommandResult, MinecraftCommand};
+
+use crate::open_panel;
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "admin")]
+#[command_permission(group = "Admin")]
+pub struct AdminCommand;
+
+impl MinecraftCommand for AdminCommand {
+    type State = SystemState<Commands<'static, 'static>>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let mut commands = state.get(world);
+
+        commands.queue(move |world: &mut World| open_panel(world, caller));
+
+        Ok(())
+    }
+}
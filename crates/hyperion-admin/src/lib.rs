@@ -0,0 +1,418 @@
+//! An inventory menu for operators, opened with `/admin`.
+//!
+//! The menu lists the online players with their heads. Clicking a head shows actions to teleport
+//! to, kick or ban the player. The bottom row shows the TPS and the [`AdminToggles`], which game
+//! modes can extend with their own switches:
+//!
+//! ```ignore
+//! app.world_mut()
+//!     .resource_mut::<AdminToggles>()
+//!     .add(AdminToggle {
+//!         name: "PvP".to_owned(),
+//!         icon: ItemKind::IronSword,
+//!         get: |world| world.resource::<PvpEnabled>().0,
+//!         set: |world, enabled| world.resource_mut::<PvpEnabled>().0 = enabled,
+//!     });
+//! ```
+
+mod command;
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use hyperion::{
+    ItemKind, ItemStack,
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{
+        PendingTeleportation, Position, Uuid, entity_kind::EntityKind, kick::Kick, packet_state,
+        skin::PlayerSkin,
+    },
+    tick_health::TickHealth,
+    tick_rate::TickRate,
+    valence_protocol::packets::play::open_screen_s2c::WindowType,
+};
+use hyperion_clap::MinecraftCommand;
+use hyperion_gui::{Gui, GuiClick};
+use hyperion_inventory::{Inventory, OpenInventory};
+use hyperion_item::builder::{HeadBuilder, ItemBuilder};
+use hyperion_permission::Group;
+use tracing::error;
+
+use crate::command::AdminCommand;
+
+const TITLE: &str = "Admin Panel";
+
+/// The number of player heads on each page of the player list
+const PLAYERS_PER_PAGE: usize = 45;
+
+const PREVIOUS_PAGE_SLOT: usize = 45;
+const TPS_SLOT: usize = 46;
+/// The toggles fill the bottom row between the TPS and the next page button
+const TOGGLE_SLOTS: std::ops::Range<usize> = 48..53;
+const NEXT_PAGE_SLOT: usize = 53;
+
+const TARGET_SLOT: usize = 13;
+const TELEPORT_SLOT: usize = 29;
+const KICK_SLOT: usize = 31;
+const BAN_SLOT: usize = 33;
+const BACK_SLOT: usize = 49;
+
+/// A setting which operators can switch on and off from the admin panel
+#[derive(Clone, Debug)]
+pub struct AdminToggle {
+    pub name: String,
+    pub icon: ItemKind,
+    pub get: fn(&World) -> bool,
+    pub set: fn(&mut World, bool),
+}
+
+/// The toggles shown in the bottom row of the admin panel. Only the first five are shown.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct AdminToggles(Vec<AdminToggle>);
+
+impl AdminToggles {
+    pub fn add(&mut self, toggle: AdminToggle) {
+        self.0.push(toggle);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum View {
+    Players { page: usize },
+    Player { target: Entity },
+}
+
+/// An open admin panel, which is on the entity with its [`Gui`]
+#[derive(Component, Copy, Clone, Debug)]
+struct AdminMenu {
+    viewer: Entity,
+    view: View,
+}
+
+struct OnlinePlayer {
+    entity: Entity,
+    name: String,
+    uuid: uuid::Uuid,
+    skin: Option<PlayerSkin>,
+}
+
+fn online_players(world: &mut World) -> Vec<OnlinePlayer> {
+    let mut query = world
+        .query_filtered::<(Entity, &Name, &Uuid, Option<&PlayerSkin>), With<packet_state::Play>>();
+
+    let mut players = query
+        .iter(world)
+        .map(|(entity, name, uuid, skin)| OnlinePlayer {
+            entity,
+            name: name.to_string(),
+            uuid: uuid.0,
+            skin: skin.cloned(),
+        })
+        .collect::<Vec<_>>();
+
+    players.sort_by_cached_key(|player| player.name.to_lowercase());
+    players
+}
+
+/// A player head showing the skin of `player`
+fn player_head(player: &OnlinePlayer) -> ItemStack {
+    let mut head = HeadBuilder::new(player.uuid).owner(player.name.clone());
+
+    if let Some(skin) = &player.skin {
+        head = head.skin(skin);
+    }
+
+    head.name(format!("§e{}", player.name)).build()
+}
+
+fn button(kind: ItemKind, name: impl Into<String>) -> ItemStack {
+    ItemBuilder::new(kind).name(name).build()
+}
+
+fn tps_item(world: &World) -> ItemStack {
+    let average = world
+        .resource::<TickHealth>()
+        .average(Duration::from_secs(60));
+
+    let name = average.map_or_else(
+        || "§6TPS: -".to_owned(),
+        |average| format!("§6TPS: {:.2} §7({:.2} ms)", average.tps, average.mspt),
+    );
+
+    button(ItemKind::Clock, name)
+}
+
+fn toggle_item(world: &World, toggle: &AdminToggle) -> ItemStack {
+    let state = if (toggle.get)(world) {
+        "§aon"
+    } else {
+        "§coff"
+    };
+
+    button(toggle.icon, format!("§6{}: {state}", toggle.name))
+}
+
+/// The items in each slot of the panel
+fn render(world: &mut World, view: View) -> Vec<(usize, ItemStack)> {
+    let players = online_players(world);
+    let mut items = Vec::new();
+
+    match view {
+        View::Players { page } => {
+            let start = page * PLAYERS_PER_PAGE;
+
+            items.extend(
+                players
+                    .iter()
+                    .skip(start)
+                    .take(PLAYERS_PER_PAGE)
+                    .map(player_head)
+                    .enumerate(),
+            );
+
+            if page > 0 {
+                items.push((
+                    PREVIOUS_PAGE_SLOT,
+                    button(ItemKind::Arrow, "§ePrevious page"),
+                ));
+            }
+
+            if players.len() > start + PLAYERS_PER_PAGE {
+                items.push((NEXT_PAGE_SLOT, button(ItemKind::Arrow, "§eNext page")));
+            }
+
+            items.push((TPS_SLOT, tps_item(world)));
+
+            let toggles = world.resource::<AdminToggles>();
+            items.extend(
+                TOGGLE_SLOTS
+                    .zip(&toggles.0)
+                    .map(|(slot, toggle)| (slot, toggle_item(world, toggle))),
+            );
+        }
+        View::Player { target } => {
+            if let Some(player) = players.iter().find(|player| player.entity == target) {
+                items.push((TARGET_SLOT, player_head(player)));
+            }
+
+            items.push((TELEPORT_SLOT, button(ItemKind::EnderPearl, "§aTeleport")));
+            items.push((KICK_SLOT, button(ItemKind::IronDoor, "§eKick")));
+            items.push((BAN_SLOT, button(ItemKind::Barrier, "§cBan")));
+            items.push((BACK_SLOT, button(ItemKind::Arrow, "§eBack")));
+        }
+    }
+
+    items
+}
+
+/// Shows `view` in the panel on `menu_entity`
+fn show(world: &mut World, menu_entity: Entity, view: View) {
+    let Some(inventory_entity) = world.get::<Gui>(menu_entity).map(Gui::inventory) else {
+        return;
+    };
+
+    let items = render(world, view);
+
+    if let Some(mut menu) = world.get_mut::<AdminMenu>(menu_entity) {
+        menu.view = view;
+    }
+
+    let Some(mut inventory) = world.get_mut::<Inventory>(inventory_entity) else {
+        return;
+    };
+
+    inventory.clear();
+    for (slot, item) in items {
+        let _ = inventory.set(u16::try_from(slot).unwrap(), item);
+    }
+}
+
+fn notify(world: &World, player: Entity, msg: String) {
+    let Some(&connection_id) = world.get::<ConnectionId>(player) else {
+        return;
+    };
+
+    if let Err(e) = world
+        .resource::<Compose>()
+        .unicast(&agnostic::chat(msg), connection_id)
+    {
+        error!("failed to send admin message: {e}");
+    }
+}
+
+fn close(world: &mut World, menu_entity: Entity) {
+    if let Some(inventory) = world.get::<Gui>(menu_entity).map(Gui::inventory) {
+        world.despawn(inventory);
+    }
+
+    world.despawn(menu_entity);
+}
+
+/// Opens the admin panel for `viewer`, replacing any panel they already have open
+pub fn open_panel(world: &mut World, viewer: Entity) {
+    let mut menus = world.query::<(Entity, &AdminMenu)>();
+    let open = menus
+        .iter(world)
+        .filter(|(_, menu)| menu.viewer == viewer)
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+
+    for menu_entity in open {
+        close(world, menu_entity);
+    }
+
+    let inventory = Inventory::new(54, TITLE.to_owned(), WindowType::Generic9x6, true);
+    let gui = Gui::new(inventory, world, viewer.to_bits());
+
+    let view = View::Players { page: 0 };
+    let menu_entity = world
+        .spawn((EntityKind::Gui, gui, AdminMenu { viewer, view }))
+        .observe(on_click)
+        .id();
+
+    show(world, menu_entity, view);
+
+    if let Some(gui) = world.get::<Gui>(menu_entity).cloned() {
+        gui.open(world, viewer);
+    }
+}
+
+fn on_click(trigger: Trigger<'_, GuiClick>, mut commands: Commands<'_, '_>) {
+    let menu_entity = trigger.target();
+    let click = *trigger.event();
+
+    commands.queue(move |world: &mut World| handle_click(world, menu_entity, click));
+}
+
+fn handle_click(world: &mut World, menu_entity: Entity, click: GuiClick) {
+    let Some(&AdminMenu { viewer, view }) = world.get::<AdminMenu>(menu_entity) else {
+        return;
+    };
+
+    if click.player != viewer {
+        return;
+    }
+
+    match view {
+        View::Players { page } => match click.slot {
+            PREVIOUS_PAGE_SLOT if page > 0 => {
+                show(world, menu_entity, View::Players { page: page - 1 });
+            }
+            NEXT_PAGE_SLOT => {
+                let players = online_players(world).len();
+                if players > (page + 1) * PLAYERS_PER_PAGE {
+                    show(world, menu_entity, View::Players { page: page + 1 });
+                }
+            }
+            TPS_SLOT => show(world, menu_entity, view),
+            slot if TOGGLE_SLOTS.contains(&slot) => {
+                let index = slot - TOGGLE_SLOTS.start;
+                let Some(toggle) = world.resource::<AdminToggles>().0.get(index).cloned() else {
+                    return;
+                };
+
+                let enabled = !(toggle.get)(world);
+                (toggle.set)(world, enabled);
+
+                show(world, menu_entity, view);
+            }
+            slot if slot < PLAYERS_PER_PAGE => {
+                let players = online_players(world);
+                let Some(target) = players.get(page * PLAYERS_PER_PAGE + slot) else {
+                    return;
+                };
+
+                show(world, menu_entity, View::Player {
+                    target: target.entity,
+                });
+            }
+            _ => {}
+        },
+        View::Player { target } => {
+            let name = world
+                .get::<Name>(target)
+                .map(ToString::to_string)
+                .filter(|_| world.get::<packet_state::Play>(target).is_some());
+
+            let Some(name) = name else {
+                notify(
+                    world,
+                    viewer,
+                    "§cThat player is no longer online".to_owned(),
+                );
+                show(world, menu_entity, View::Players { page: 0 });
+                return;
+            };
+
+            match click.slot {
+                TELEPORT_SLOT => {
+                    let Some(destination) =
+                        world.get::<Position>(target).map(|position| **position)
+                    else {
+                        return;
+                    };
+
+                    if let Some(mut position) = world.get_mut::<Position>(viewer) {
+                        **position = destination;
+                    }
+
+                    world
+                        .entity_mut(viewer)
+                        .insert(PendingTeleportation::new(destination));
+                    world.entity_mut(viewer).remove::<OpenInventory>();
+                    notify(world, viewer, format!("§aTeleported to {name}"));
+                }
+                KICK_SLOT => {
+                    world.trigger_targets(Kick::new("Kicked by an operator"), target);
+                    notify(world, viewer, format!("§aKicked {name}"));
+                    show(world, menu_entity, View::Players { page: 0 });
+                }
+                BAN_SLOT => {
+                    // Players in the banned group are kicked by the permission plugin
+                    world.entity_mut(target).insert(Group::Banned);
+                    notify(world, viewer, format!("§aBanned {name}"));
+                    show(world, menu_entity, View::Players { page: 0 });
+                }
+                BACK_SLOT => show(world, menu_entity, View::Players { page: 0 }),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Closes the panel once its viewer closes the inventory or leaves
+fn close_panels(
+    trigger: Trigger<'_, OnRemove, OpenInventory>,
+    menus: Query<'_, '_, (Entity, &AdminMenu)>,
+    mut commands: Commands<'_, '_>,
+) {
+    for (menu_entity, menu) in &menus {
+        if menu.viewer == trigger.target() {
+            commands.queue(move |world: &mut World| close(world, menu_entity));
+        }
+    }
+}
+
+fn freeze_toggle() -> AdminToggle {
+    AdminToggle {
+        name: "Freeze game".to_owned(),
+        icon: ItemKind::Ice,
+        get: |world| world.resource::<TickRate>().is_frozen(),
+        set: |world, frozen| world.resource_mut::<TickRate>().set_frozen(frozen),
+    }
+}
+
+/// Adds `/admin`, which opens the admin panel
+pub struct AdminPlugin;
+
+impl Plugin for AdminPlugin {
+    fn build(&self, app: &mut App) {
+        let mut toggles = AdminToggles::default();
+        toggles.add(freeze_toggle());
+
+        app.insert_resource(toggles);
+        app.add_observer(close_panels);
+
+        AdminCommand::register(app.world_mut());
+    }
+}
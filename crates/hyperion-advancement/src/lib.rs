@@ -0,0 +1,610 @@
+//! Custom advancement trees and toast notifications.
+//!
+//! Advancements are defined in the [`Advancements`] resource and sent to every player when they
+//! join. Granting an advancement to a player shows its toast and saves their
+//! [`AdvancementProgress`] in the [`LocalDb`], so progress is kept across restarts.
+//!
+//! ```ignore
+//! advancements.define("bedwars:root", Advancement::root(
+//!     AdvancementDisplay::new("Bedwars", "Protect your bed", ItemKind::RedBed)
+//!         .with_background("minecraft:textures/block/red_wool.png"),
+//! ));
+//! advancements.define("bedwars:first_kill", Advancement::child(
+//!     "bedwars:root",
+//!     AdvancementDisplay::new("First Blood", "Kill a player", ItemKind::IronSword)
+//!         .with_position(1.0, 0.0),
+//! ));
+//!
+//! commands.entity(player).grant_advancement("bedwars:first_kill");
+//! commands.entity(player).toast("Diamond generator upgraded", ItemKind::Diamond);
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use hyperion::{
+    ItemKind,
+    net::{Compose, ConnectionId},
+    simulation::{Uuid, packet_state},
+    storage::{Bucket, LocalDb},
+    timings::timed,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use valence_protocol::ItemStack;
+use valence_text::{IntoText, Text};
+
+mod packet;
+
+pub use packet::AdvancementUpdateS2c;
+
+/// The name of the criterion of advancements created with [`Advancement::root`] and
+/// [`Advancement::child`]
+pub const DEFAULT_CRITERION: &str = "done";
+
+/// The id of the advancement which [`Toast`] shows
+const TOAST_ID: &str = "hyperion:toast";
+
+/// How an advancement is framed in its toast and in the advancements screen
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum AdvancementFrame {
+    #[default]
+    Task = 0,
+    Challenge = 1,
+    Goal = 2,
+}
+
+/// How an advancement is shown to players. Advancements without a display are never shown but
+/// can still be used as parents.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdvancementDisplay {
+    pub title: Text,
+    pub description: Text,
+    pub icon: ItemStack,
+    pub frame: AdvancementFrame,
+    /// The background texture of the tab, which is only used for root advancements
+    pub background: Option<String>,
+    pub show_toast: bool,
+    /// Whether the advancement is hidden until it is completed
+    pub hidden: bool,
+    /// The position in the advancements screen
+    pub x: f32,
+    pub y: f32,
+}
+
+impl AdvancementDisplay {
+    #[must_use]
+    pub fn new(
+        title: impl IntoText<'static>,
+        description: impl IntoText<'static>,
+        icon: ItemKind,
+    ) -> Self {
+        Self {
+            title: title.into_text(),
+            description: description.into_text(),
+            icon: ItemStack::new(icon, 1, None),
+            frame: AdvancementFrame::default(),
+            background: None,
+            show_toast: true,
+            hidden: false,
+            x: 0.0,
+            y: 0.0,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_frame(mut self, frame: AdvancementFrame) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    #[must_use]
+    pub fn with_background(mut self, texture: impl Into<String>) -> Self {
+        self.background = Some(texture.into());
+        self
+    }
+
+    #[must_use]
+    pub const fn with_position(mut self, x: f32, y: f32) -> Self {
+        self.x = x;
+        self.y = y;
+        self
+    }
+
+    #[must_use]
+    pub const fn hidden(mut self) -> Self {
+        self.hidden = true;
+        self
+    }
+
+    #[must_use]
+    pub const fn without_toast(mut self) -> Self {
+        self.show_toast = false;
+        self
+    }
+}
+
+/// An advancement in a tree of advancements
+#[derive(Clone, Debug, PartialEq)]
+pub struct Advancement {
+    /// Advancements without a parent start a new tab
+    pub parent: Option<String>,
+    pub display: Option<AdvancementDisplay>,
+    /// Every criterion must be completed to complete the advancement
+    pub criteria: Vec<String>,
+}
+
+impl Advancement {
+    /// An advancement which starts a new tab and has a single criterion
+    #[must_use]
+    pub fn root(display: AdvancementDisplay) -> Self {
+        Self {
+            parent: None,
+            display: Some(display),
+            criteria: vec![DEFAULT_CRITERION.to_owned()],
+        }
+    }
+
+    /// An advancement below `parent` which has a single criterion
+    #[must_use]
+    pub fn child(parent: impl Into<String>, display: AdvancementDisplay) -> Self {
+        Self {
+            parent: Some(parent.into()),
+            display: Some(display),
+            criteria: vec![DEFAULT_CRITERION.to_owned()],
+        }
+    }
+
+    #[must_use]
+    pub fn with_criteria(mut self, criteria: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.criteria = criteria.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+/// The advancements which are sent to players, keyed by their id such as `bedwars:first_kill`.
+///
+/// Players who have already joined are sent the new advancements when this resource changes.
+#[derive(Resource, Default, Debug)]
+pub struct Advancements {
+    advancements: BTreeMap<String, Advancement>,
+}
+
+impl Advancements {
+    /// Adds an advancement, replacing the advancement with the same id
+    pub fn define(&mut self, id: impl Into<String>, advancement: Advancement) {
+        self.advancements.insert(id.into(), advancement);
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<Advancement> {
+        self.advancements.remove(id)
+    }
+
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&Advancement> {
+        self.advancements.get(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Advancement)> {
+        self.advancements
+            .iter()
+            .map(|(id, advancement)| (id.as_str(), advancement))
+    }
+}
+
+/// The criteria a player has completed and when they completed them, in milliseconds since the
+/// unix epoch
+#[derive(
+    Component,
+    Serialize,
+    Deserialize,
+    Default,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq
+)]
+pub struct AdvancementProgress {
+    criteria: HashMap<String, HashMap<String, i64>>,
+}
+
+impl AdvancementProgress {
+    /// Whether the player has completed the criterion of an advancement
+    #[must_use]
+    pub fn has_criterion(&self, advancement: &str, criterion: &str) -> bool {
+        self.criteria
+            .get(advancement)
+            .is_some_and(|criteria| criteria.contains_key(criterion))
+    }
+
+    /// Whether the player has completed every criterion of an advancement
+    #[must_use]
+    pub fn is_done(&self, id: &str, advancement: &Advancement) -> bool {
+        advancement
+            .criteria
+            .iter()
+            .all(|criterion| self.has_criterion(id, criterion))
+    }
+
+    /// Completes criteria at `time` and returns whether any of them were not already completed
+    pub fn grant<'a>(
+        &mut self,
+        advancement: &str,
+        criteria: impl IntoIterator<Item = &'a str>,
+        time: i64,
+    ) -> bool {
+        let completed = self.criteria.entry(advancement.to_owned()).or_default();
+
+        let mut changed = false;
+        for criterion in criteria {
+            if !completed.contains_key(criterion) {
+                completed.insert(criterion.to_owned(), time);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Removes the progress of an advancement and returns whether there was any
+    pub fn revoke(&mut self, advancement: &str) -> bool {
+        self.criteria
+            .remove(advancement)
+            .is_some_and(|criteria| !criteria.is_empty())
+    }
+
+    /// The progress of an advancement as sent to the client
+    fn packet_progress<'a>(
+        &self,
+        id: &'a str,
+        advancement: &'a Advancement,
+    ) -> (&'a str, Vec<(&'a str, Option<i64>)>) {
+        let completed = self.criteria.get(id);
+        let criteria = advancement
+            .criteria
+            .iter()
+            .map(|criterion| {
+                let time = completed
+                    .and_then(|completed| completed.get(criterion))
+                    .copied();
+                (criterion.as_str(), time)
+            })
+            .collect();
+        (id, criteria)
+    }
+}
+
+/// Where the progress of players is saved, keyed by their uuid
+#[derive(Resource, Clone)]
+pub struct AdvancementStorage(pub Bucket<AdvancementProgress>);
+
+/// Completes every criterion of an advancement for the target player. Completing an advancement
+/// shows its toast unless the display disables it.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct GrantAdvancement {
+    pub advancement: String,
+    /// The criteria to complete, or every criterion if this is `None`
+    pub criteria: Option<Vec<String>>,
+}
+
+/// Removes all progress of an advancement for the target player
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct RevokeAdvancement {
+    pub advancement: String,
+}
+
+/// Shows a toast to the target player without defining an advancement. This is done by adding a
+/// hidden advancement, completing it and then removing it again.
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct Toast {
+    pub title: Text,
+    pub icon: ItemStack,
+    pub frame: AdvancementFrame,
+}
+
+pub trait AdvancementExt {
+    /// Completes every criterion of an advancement by triggering [`GrantAdvancement`]
+    fn grant_advancement(&mut self, advancement: impl Into<String>) -> &mut Self;
+
+    /// Removes all progress of an advancement by triggering [`RevokeAdvancement`]
+    fn revoke_advancement(&mut self, advancement: impl Into<String>) -> &mut Self;
+
+    /// Shows a toast by triggering [`Toast`]
+    fn toast(&mut self, title: impl IntoText<'static>, icon: ItemKind) -> &mut Self;
+}
+
+impl AdvancementExt for EntityCommands<'_> {
+    fn grant_advancement(&mut self, advancement: impl Into<String>) -> &mut Self {
+        self.trigger(GrantAdvancement {
+            advancement: advancement.into(),
+            criteria: None,
+        })
+    }
+
+    fn revoke_advancement(&mut self, advancement: impl Into<String>) -> &mut Self {
+        self.trigger(RevokeAdvancement {
+            advancement: advancement.into(),
+        })
+    }
+
+    fn toast(&mut self, title: impl IntoText<'static>, icon: ItemKind) -> &mut Self {
+        self.trigger(Toast {
+            title: title.into_text(),
+            icon: ItemStack::new(icon, 1, None),
+            frame: AdvancementFrame::Goal,
+        })
+    }
+}
+
+/// The current time in the form advancement progress is sent in
+fn now_millis() -> i64 {
+    i64::try_from(hyperion::storage::now_millis()).unwrap_or(i64::MAX)
+}
+
+/// Every advancement and the progress of a player, which replaces the advancements they have
+fn full_update<'a>(
+    advancements: &'a Advancements,
+    progress: &AdvancementProgress,
+) -> AdvancementUpdateS2c<'a> {
+    AdvancementUpdateS2c {
+        reset: true,
+        added: advancements.iter().collect(),
+        removed: Vec::new(),
+        progress: advancements
+            .iter()
+            .map(|(id, advancement)| progress.packet_progress(id, advancement))
+            .collect(),
+    }
+}
+
+fn save(storage: &AdvancementStorage, uuid: &Uuid, progress: &AdvancementProgress) {
+    if let Err(e) = storage.0.insert(&uuid.0.to_string(), progress) {
+        error!("failed to save advancement progress: {e}");
+    }
+}
+
+fn load_progress(
+    trigger: Trigger<'_, OnAdd, Uuid>,
+    query: Query<'_, '_, &Uuid, With<ConnectionId>>,
+    storage: Res<'_, AdvancementStorage>,
+    mut commands: Commands<'_, '_>,
+) {
+    let Ok(uuid) = query.get(trigger.target()) else {
+        return;
+    };
+
+    let progress = storage
+        .0
+        .get(&uuid.0.to_string())
+        .unwrap_or_else(|e| {
+            error!("failed to load advancement progress: {e}");
+            None
+        })
+        .unwrap_or_default();
+
+    commands.entity(trigger.target()).insert(progress);
+}
+
+fn send_advancements(
+    trigger: Trigger<'_, OnAdd, packet_state::Play>,
+    query: Query<'_, '_, (&ConnectionId, &AdvancementProgress)>,
+    advancements: Res<'_, Advancements>,
+    compose: Res<'_, Compose>,
+) {
+    let (&connection_id, progress) = match query.get(trigger.target()) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("failed to send advancements: query failed: {e}");
+            return;
+        }
+    };
+
+    if let Err(e) = compose.unicast(&full_update(&advancements, progress), connection_id) {
+        error!("failed to send advancements: {e}");
+    }
+}
+
+fn resend_advancements(
+    advancements: Res<'_, Advancements>,
+    query: Query<'_, '_, (&ConnectionId, &AdvancementProgress), With<packet_state::Play>>,
+    compose: Res<'_, Compose>,
+) {
+    for (&connection_id, progress) in &query {
+        if let Err(e) = compose.unicast(&full_update(&advancements, progress), connection_id) {
+            error!("failed to send advancements: {e}");
+        }
+    }
+}
+
+fn grant_advancement(
+    trigger: Trigger<'_, GrantAdvancement>,
+    mut query: Query<'_, '_, (&ConnectionId, &Uuid, &mut AdvancementProgress)>,
+    advancements: Res<'_, Advancements>,
+    storage: Res<'_, AdvancementStorage>,
+    compose: Res<'_, Compose>,
+) {
+    let event = trigger.event();
+    let Some(advancement) = advancements.get(&event.advancement) else {
+        error!(
+            "failed to grant advancement: {} is not defined",
+            event.advancement
+        );
+        return;
+    };
+
+    let (&connection_id, uuid, mut progress) = match query.get_mut(trigger.target()) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("failed to grant advancement: query failed: {e}");
+            return;
+        }
+    };
+
+    let criteria = event.criteria.as_ref().unwrap_or(&advancement.criteria);
+    let criteria = criteria
+        .iter()
+        .filter(|criterion| advancement.criteria.contains(*criterion))
+        .map(String::as_str);
+
+    if !progress.grant(&event.advancement, criteria, now_millis()) {
+        return;
+    }
+
+    save(&storage, uuid, &progress);
+
+    let packet = AdvancementUpdateS2c {
+        reset: false,
+        added: Vec::new(),
+        removed: Vec::new(),
+        progress: vec![progress.packet_progress(&event.advancement, advancement)],
+    };
+    if let Err(e) = compose.unicast(&packet, connection_id) {
+        error!("failed to send advancement progress: {e}");
+    }
+}
+
+fn revoke_advancement(
+    trigger: Trigger<'_, RevokeAdvancement>,
+    mut query: Query<'_, '_, (&ConnectionId, &Uuid, &mut AdvancementProgress)>,
+    advancements: Res<'_, Advancements>,
+    storage: Res<'_, AdvancementStorage>,
+    compose: Res<'_, Compose>,
+) {
+    let event = trigger.event();
+    let (&connection_id, uuid, mut progress) = match query.get_mut(trigger.target()) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("failed to revoke advancement: query failed: {e}");
+            return;
+        }
+    };
+
+    if !progress.revoke(&event.advancement) {
+        return;
+    }
+
+    save(&storage, uuid, &progress);
+
+    let Some(advancement) = advancements.get(&event.advancement) else {
+        return;
+    };
+    let packet = AdvancementUpdateS2c {
+        reset: false,
+        added: Vec::new(),
+        removed: Vec::new(),
+        progress: vec![progress.packet_progress(&event.advancement, advancement)],
+    };
+    if let Err(e) = compose.unicast(&packet, connection_id) {
+        error!("failed to send advancement progress: {e}");
+    }
+}
+
+fn show_toast(
+    trigger: Trigger<'_, Toast>,
+    query: Query<'_, '_, &ConnectionId>,
+    compose: Res<'_, Compose>,
+) {
+    let Ok(&connection_id) = query.get(trigger.target()) else {
+        error!("failed to show toast: player is missing ConnectionId");
+        return;
+    };
+
+    let toast = trigger.event();
+    let advancement = Advancement {
+        parent: None,
+        display: Some(AdvancementDisplay {
+            title: toast.title.clone(),
+            description: Text::default(),
+            icon: toast.icon.clone(),
+            frame: toast.frame,
+            background: None,
+            show_toast: true,
+            hidden: true,
+            x: 0.0,
+            y: 0.0,
+        }),
+        criteria: vec![DEFAULT_CRITERION.to_owned()],
+    };
+
+    // The toast is shown when the advancement is completed, so it can be removed straight away
+    let show = AdvancementUpdateS2c {
+        reset: false,
+        added: vec![(TOAST_ID, &advancement)],
+        removed: Vec::new(),
+        progress: vec![(TOAST_ID, vec![(DEFAULT_CRITERION, Some(now_millis()))])],
+    };
+    let remove = AdvancementUpdateS2c {
+        reset: false,
+        added: Vec::new(),
+        removed: vec![TOAST_ID],
+        progress: Vec::new(),
+    };
+
+    for packet in [show, remove] {
+        if let Err(e) = compose.unicast(&packet, connection_id) {
+            error!("failed to show toast: {e}");
+            return;
+        }
+    }
+}
+
+pub struct AdvancementPlugin;
+
+impl Plugin for AdvancementPlugin {
+    fn build(&self, app: &mut App) {
+        let bucket = app
+            .world()
+            .resource::<LocalDb>()
+            .bucket("hyperion-advancement")
+            .expect("failed to open advancement progress");
+
+        app.insert_resource(AdvancementStorage(bucket));
+        app.init_resource::<Advancements>();
+        app.add_observer(load_progress);
+        app.add_observer(send_advancements);
+        app.add_observer(grant_advancement);
+        app.add_observer(revoke_advancement);
+        app.add_observer(show_toast);
+        app.add_systems(
+            FixedUpdate,
+            timed(resend_advancements)
+                .run_if(resource_changed::<Advancements>.and(not(resource_added::<Advancements>))),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress() {
+        let advancement = Advancement::root(AdvancementDisplay::new(
+            "Root",
+            "The root advancement",
+            ItemKind::Stone,
+        ))
+        .with_criteria(["a", "b"]);
+
+        let mut progress = AdvancementProgress::default();
+        assert!(!progress.is_done("test:root", &advancement));
+
+        assert!(progress.grant("test:root", ["a"], 1));
+        assert!(!progress.grant("test:root", ["a"], 2));
+        assert!(progress.has_criterion("test:root", "a"));
+        assert!(!progress.is_done("test:root", &advancement));
+
+        assert!(progress.grant("test:root", ["a", "b"], 3));
+        assert!(progress.is_done("test:root", &advancement));
+        assert_eq!(
+            progress.packet_progress("test:root", &advancement),
+            ("test:root", vec![("a", Some(1)), ("b", Some(3))])
+        );
+
+        assert!(progress.revoke("test:root"));
+        assert!(!progress.revoke("test:root"));
+        assert_eq!(
+            progress.packet_progress("test:root", &advancement),
+            ("test:root", vec![("a", None), ("b", None)])
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use std::io::Write;
+
+use valence_protocol::{Encode, Packet, VarInt};
+
+use crate::Advancement;
+
+/// The advancement display has a background texture
+const HAS_BACKGROUND: i32 = 0x01;
+
+/// Completing the advancement shows a toast
+const SHOW_TOAST: i32 = 0x02;
+
+/// The advancement is hidden in the advancements screen until it is completed
+const HIDDEN: i32 = 0x04;
+
+/// Adds, removes and updates the progress of advancements
+#[derive(Clone, Debug, Packet)]
+pub struct AdvancementUpdateS2c<'a> {
+    /// Removes every advancement before the others are added. Toasts are not shown when this is
+    /// set.
+    pub reset: bool,
+    pub added: Vec<(&'a str, &'a Advancement)>,
+    pub removed: Vec<&'a str>,
+    /// The time each criterion was completed at in milliseconds since the unix epoch, or `None`
+    /// if it has not been completed
+    pub progress: Vec<(&'a str, Vec<(&'a str, Option<i64>)>)>,
+}
+
+fn encode_len(len: usize, w: impl Write) -> anyhow::Result<()> {
+    VarInt(i32::try_from(len)?).encode(w)
+}
+
+impl Encode for AdvancementUpdateS2c<'_> {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        self.reset.encode(&mut w)?;
+
+        encode_len(self.added.len(), &mut w)?;
+        for (id, advancement) in &self.added {
+            id.encode(&mut w)?;
+            encode_advancement(advancement, &mut w)?;
+        }
+
+        encode_len(self.removed.len(), &mut w)?;
+        for id in &self.removed {
+            id.encode(&mut w)?;
+        }
+
+        encode_len(self.progress.len(), &mut w)?;
+        for (id, criteria) in &self.progress {
+            id.encode(&mut w)?;
+            encode_len(criteria.len(), &mut w)?;
+            for (criterion, completed) in criteria {
+                criterion.encode(&mut w)?;
+                completed.encode(&mut w)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn encode_advancement(advancement: &Advancement, mut w: impl Write) -> anyhow::Result<()> {
+    advancement.parent.as_deref().encode(&mut w)?;
+
+    advancement.display.is_some().encode(&mut w)?;
+    if let Some(display) = &advancement.display {
+        display.title.encode(&mut w)?;
+        display.description.encode(&mut w)?;
+        display.icon.encode(&mut w)?;
+        VarInt(display.frame as i32).encode(&mut w)?;
+
+        let mut flags = 0;
+        if display.background.is_some() {
+            flags |= HAS_BACKGROUND;
+        }
+        if display.show_toast {
+            flags |= SHOW_TOAST;
+        }
+        if display.hidden {
+            flags |= HIDDEN;
+        }
+        flags.encode(&mut w)?;
+
+        if let Some(background) = &display.background {
+            background.as_str().encode(&mut w)?;
+        }
+        display.x.encode(&mut w)?;
+        display.y.encode(&mut w)?;
+    }
+
+    // Criteria do not have any data sent to the client
+    encode_len(advancement.criteria.len(), &mut w)?;
+    for criterion in &advancement.criteria {
+        criterion.as_str().encode(&mut w)?;
+    }
+
+    // Every criterion is required, so each one is in its own group
+    encode_len(advancement.criteria.len(), &mut w)?;
+    for criterion in &advancement.criteria {
+        encode_len(1, &mut w)?;
+        criterion.as_str().encode(&mut w)?;
+    }
+
+    // Sends telemetry data
+    false.encode(w)
+}
@@ -0,0 +1,134 @@
+use std::time::{Duration, SystemTime};
+
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    command_channel::CommandChannel,
+    net::{Compose, ConnectionId, DataBundle, agnostic},
+};
+use hyperion_clap::{
+    CommandFailed, CommandPermission, CommandResult, MinecraftCommand,
+    hyperion_command::CommandOutcome,
+};
+use tracing::error;
+
+use crate::{AuditEntry, AuditLog, export_audit_log};
+
+/// The number of commands on each page of `/auditlog view`
+const PAGE_SIZE: u64 = 10;
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "auditlog")]
+#[command_permission(group = "Admin")]
+pub enum AuditLogCommand {
+    /// Lists the latest commands, newest first, optionally only those sent by `player`
+    View {
+        page: Option<u64>,
+        player: Option<String>,
+    },
+    /// Writes every recorded command to a JSON file
+    Export,
+}
+
+fn describe(entry: &AuditEntry) -> String {
+    let time = SystemTime::UNIX_EPOCH + Duration::from_millis(entry.time);
+    let outcome = match entry.outcome {
+        CommandOutcome::Executed => "§a✔",
+        CommandOutcome::Failed => "§c✘ failed",
+        CommandOutcome::UnknownCommand => "§c✘ unknown",
+        CommandOutcome::InvalidArguments => "§c✘ invalid",
+        CommandOutcome::PermissionDenied => "§c✘ denied",
+    };
+
+    format!(
+        "§7{} §6{}§r /{} {outcome}",
+        humantime::format_rfc3339_seconds(time),
+        entry.name,
+        entry.command
+    )
+}
+
+impl MinecraftCommand for AuditLogCommand {
+    type State = SystemState<(
+        Query<'static, 'static, &'static ConnectionId>,
+        Res<'static, AuditLog>,
+        Res<'static, Compose>,
+        Res<'static, CommandChannel>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (query, log, compose, command_channel) = state.get(world);
+
+        let &connection_id = match query.get(caller) {
+            Ok(connection_id) => connection_id,
+            Err(e) => {
+                error!("auditlog command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let mut result = Ok(());
+        let lines = match self {
+            Self::View { page, player } => {
+                let page = page.unwrap_or(1).max(1);
+
+                // The number of pages is only known when every entry is shown, since counting
+                // the commands of one player would read the whole log
+                let (page, header) = if player.is_some() {
+                    (page, format!("§6Audit log page {page}"))
+                } else {
+                    let pages = log.len().div_ceil(PAGE_SIZE).max(1);
+                    let page = page.min(pages);
+                    (page, format!("§6Audit log page {page}/{pages}"))
+                };
+
+                let skip = (page - 1).saturating_mul(PAGE_SIZE);
+                match log.latest(skip, PAGE_SIZE, player.as_deref()) {
+                    Ok(entries) => {
+                        let mut lines = vec![header];
+                        lines.extend(entries.iter().map(describe));
+
+                        if lines.len() == 1 {
+                            lines.push("§7No commands have been recorded".to_owned());
+                        }
+
+                        lines
+                    }
+                    Err(e) => {
+                        result = Err(CommandFailed);
+                        vec![format!("§cFailed to read the audit log: {e}")]
+                    }
+                }
+            }
+            Self::Export => {
+                let command_channel = command_channel.clone();
+
+                export_audit_log(world, move |result| {
+                    let msg = match result {
+                        Ok(path) => format!("§aAudit log exported to {}", path.display()),
+                        Err(e) => format!("§cFailed to export the audit log: {e}"),
+                    };
+
+                    command_channel.push(move |world: &mut World| {
+                        let compose = world.resource::<Compose>();
+                        if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+                            error!("failed to send audit message: {e}");
+                        }
+                    });
+                });
+
+                vec!["§7Exporting the audit log...".to_owned()]
+            }
+        };
+
+        let mut bundle = DataBundle::new(&compose);
+        for line in lines {
+            bundle.add_packet(&agnostic::chat(line)).unwrap();
+        }
+        if let Err(e) = bundle.unicast(connection_id) {
+            error!("failed to send audit log: {e}");
+        }
+
+        result
+    }
+}
@@ -0,0 +1,488 @@
+//! Records every command sent by players, so that moderators can see who ran what during events.
+//!
+//! Commands are saved in their own [`LocalDb`] with whether they were run, and can be read in chat
+//! with `/auditlog view` or exported to JSON with `/auditlog export`.
+
+mod command;
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use bevy::prelude::*;
+use hyperion::{
+    runtime::AsyncRuntime,
+    simulation::Uuid,
+    storage::{Bucket, LocalDb, now_millis},
+    timings::timed,
+};
+use hyperion_clap::{
+    MinecraftCommand,
+    hyperion_command::{CommandExecuted, CommandOutcome},
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::command::AuditLogCommand;
+
+/// A command sent by a player
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// When the command was sent, in milliseconds since the Unix epoch
+    pub time: u64,
+    pub player: uuid::Uuid,
+    /// The name of the player when the command was sent
+    pub name: String,
+    /// The command without the leading slash, including its arguments
+    pub command: String,
+    pub outcome: CommandOutcome,
+}
+
+/// Every command sent by players in the retention window, keyed by a sequence number so that
+/// entries are ordered by when they were recorded.
+///
+/// Entries are only kept in the bucket, so reading a page reads just the entries on it.
+#[derive(Resource)]
+pub struct AuditLog {
+    bucket: Bucket<AuditEntry>,
+    /// The id of the oldest entry which has not been removed
+    first_id: u64,
+    next_id: u64,
+    retention: Duration,
+    max_entries: u64,
+    /// Every minute before this one has been pruned
+    pruned_until: u64,
+}
+
+/// The number of entries read at once when looking for the commands of one player
+const SCAN_CHUNK: u64 = 256;
+
+const MILLIS_PER_MINUTE: u64 = 60_000;
+
+fn entry_key(id: u64) -> String {
+    format!("{id:020}")
+}
+
+impl AuditLog {
+    /// Opens the log saved in `bucket`. Once there are more than `max_entries` entries, the
+    /// oldest are removed even if they are in the retention window.
+    pub fn new(
+        bucket: Bucket<AuditEntry>,
+        retention: Duration,
+        max_entries: u64,
+    ) -> anyhow::Result<Self> {
+        let first_id = match bucket.first_key()? {
+            Some(key) => key.parse()?,
+            None => 0,
+        };
+        let next_id = match bucket.last_key()? {
+            Some(key) => key.parse::<u64>()? + 1,
+            None => 0,
+        };
+
+        Ok(Self {
+            bucket,
+            first_id,
+            next_id,
+            retention,
+            max_entries,
+            pruned_until: 0,
+        })
+    }
+
+    /// The number of entries in the log
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.next_id - self.first_id
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn record(&mut self, entry: &AuditEntry) -> anyhow::Result<()> {
+        let insert = [(entry_key(self.next_id), entry.clone())];
+        let next_id = self.next_id + 1;
+
+        // The oldest entries which no longer fit in `max_entries`
+        let first_id = next_id.saturating_sub(self.max_entries).max(self.first_id);
+        let remove = (self.first_id..first_id).map(entry_key).collect::<Vec<_>>();
+
+        self.bucket.write_many(&insert, &remove)?;
+        self.first_id = first_id;
+        self.next_id = next_id;
+
+        Ok(())
+    }
+
+    /// Removes the entries which are older than the retention window. This only does work once
+    /// a minute.
+    pub fn prune(&mut self, now: u64) -> anyhow::Result<()> {
+        let retention = u64::try_from(self.retention.as_millis()).unwrap_or(u64::MAX);
+        let cutoff_minute = now.saturating_sub(retention) / MILLIS_PER_MINUTE;
+
+        if cutoff_minute <= self.pruned_until {
+            return Ok(());
+        }
+
+        while self.first_id < self.next_id {
+            let end = (self.first_id + SCAN_CHUNK).min(self.next_id);
+            let chunk = self
+                .bucket
+                .range(&entry_key(self.first_id), &entry_key(end))?;
+
+            let expired = chunk
+                .iter()
+                .take_while(|(_, entry)| entry.time / MILLIS_PER_MINUTE < cutoff_minute)
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>();
+            let done = expired.len() < chunk.len();
+
+            self.bucket.write_many(&[], &expired)?;
+            self.first_id += expired.len() as u64;
+
+            if done {
+                break;
+            }
+        }
+
+        self.pruned_until = cutoff_minute;
+
+        Ok(())
+    }
+
+    /// Skips the `skip` newest entries sent by `player`, or by anyone if it is `None`, and
+    /// returns up to `count` of the entries after them, newest first. Only the entries which are
+    /// skipped or returned are read.
+    pub fn latest(
+        &self,
+        skip: u64,
+        count: u64,
+        player: Option<&str>,
+    ) -> anyhow::Result<Vec<AuditEntry>> {
+        let Some(player) = player else {
+            let end = self.next_id.saturating_sub(skip).max(self.first_id);
+            let start = end.saturating_sub(count).max(self.first_id);
+
+            let mut entries = self.bucket.range(&entry_key(start), &entry_key(end))?;
+            entries.reverse();
+
+            return Ok(entries.into_iter().map(|(_, entry)| entry).collect());
+        };
+
+        let mut skip = skip;
+        let mut found = Vec::new();
+        let mut end = self.next_id;
+
+        while end > self.first_id && (found.len() as u64) < count {
+            let start = end.saturating_sub(SCAN_CHUNK).max(self.first_id);
+            let chunk = self.bucket.range(&entry_key(start), &entry_key(end))?;
+
+            for (_, entry) in chunk.into_iter().rev() {
+                if !entry.name.eq_ignore_ascii_case(player) {
+                    continue;
+                }
+
+                if skip > 0 {
+                    skip -= 1;
+                } else if (found.len() as u64) < count {
+                    found.push(entry);
+                }
+            }
+
+            end = start;
+        }
+
+        Ok(found)
+    }
+
+    /// Every entry, ordered from oldest to newest
+    pub fn entries(&self) -> anyhow::Result<Vec<AuditEntry>> {
+        self.snapshot().entries()
+    }
+
+    /// The entries which are in the log now. Entries recorded afterwards are not part of the
+    /// snapshot.
+    #[must_use]
+    pub fn snapshot(&self) -> AuditSnapshot {
+        AuditSnapshot {
+            bucket: self.bucket.clone(),
+            first_id: self.first_id,
+            next_id: self.next_id,
+        }
+    }
+}
+
+/// The entries of an [`AuditLog`] at one point in time. The entries are only read when they are
+/// needed, so a snapshot can be taken during a tick and read on another thread.
+#[derive(Clone)]
+pub struct AuditSnapshot {
+    bucket: Bucket<AuditEntry>,
+    first_id: u64,
+    next_id: u64,
+}
+
+impl AuditSnapshot {
+    /// Every entry, ordered from oldest to newest. Entries which were pruned after the snapshot
+    /// was taken are skipped.
+    pub fn entries(&self) -> anyhow::Result<Vec<AuditEntry>> {
+        Ok(self
+            .bucket
+            .range(&entry_key(self.first_id), &entry_key(self.next_id))?
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .collect())
+    }
+
+    /// Writes every entry to a JSON file in `directory` named after the current time and returns
+    /// its path
+    pub fn export(&self, directory: &Path) -> anyhow::Result<PathBuf> {
+        let entries = self.entries()?;
+
+        std::fs::create_dir_all(directory)?;
+
+        let name = format!(
+            "audit-{}.json",
+            humantime::format_rfc3339_seconds(SystemTime::now())
+        )
+        .replace(':', "-");
+        let path = directory.join(name);
+
+        std::fs::write(&path, serde_json::to_vec_pretty(&entries)?)?;
+
+        Ok(path)
+    }
+}
+
+/// Where `/auditlog export` writes its files
+#[derive(Resource, Clone, Debug)]
+pub struct AuditExportDirectory(pub PathBuf);
+
+/// Exports the audit log as it is now to the [`AuditExportDirectory`] in the background, calling
+/// `on_done` with the path of the file once it has been written
+pub fn export_audit_log(
+    world: &World,
+    on_done: impl FnOnce(anyhow::Result<PathBuf>) + Send + 'static,
+) {
+    let snapshot = world.resource::<AuditLog>().snapshot();
+    let directory = world.resource::<AuditExportDirectory>().0.clone();
+    let runtime = world.resource::<AsyncRuntime>();
+
+    runtime.spawn_blocking(move || on_done(snapshot.export(&directory)));
+}
+
+fn record_commands(
+    mut events: EventReader<'_, '_, CommandExecuted>,
+    players: Query<'_, '_, (&Uuid, &Name)>,
+    mut log: ResMut<'_, AuditLog>,
+) {
+    for event in events.read() {
+        let Ok((uuid, name)) = players.get(event.sender) else {
+            continue;
+        };
+
+        let entry = AuditEntry {
+            time: now_millis(),
+            player: uuid.0,
+            name: name.to_string(),
+            command: event.command.clone(),
+            outcome: event.outcome,
+        };
+
+        if let Err(e) = log.record(&entry) {
+            error!("failed to record command {:?}: {e}", entry.command);
+        }
+    }
+}
+
+fn prune_audit_log(mut log: ResMut<'_, AuditLog>) {
+    if let Err(e) = log.prune(now_millis()) {
+        error!("failed to prune audit log: {e}");
+    }
+}
+
+/// The space in the database reserved for each entry, which leaves room for long commands and
+/// for the pages of the database itself
+const ENTRY_SPACE: u64 = 1024;
+
+/// The space in the database reserved for everything other than the entries
+const BASE_SPACE: u64 = 1024 * 1024;
+
+/// The largest size in bytes which a database holding `max_entries` entries can grow to
+fn map_size(max_entries: u64) -> usize {
+    let size = max_entries
+        .saturating_mul(ENTRY_SPACE)
+        .saturating_add(BASE_SPACE)
+        .checked_next_multiple_of(4096)
+        .unwrap_or(u64::MAX);
+    usize::try_from(size).unwrap_or(usize::MAX)
+}
+
+/// Adds `/auditlog` and records the commands sent by players. Commands older than `retention`
+/// are removed, and so are the oldest commands once there are more than `max_entries`. Exports
+/// are written to `export_directory`.
+///
+/// Commands are saved in a separate database at `path` which is sized for `max_entries`, so a
+/// full audit log cannot stop other plugins from saving to the shared [`LocalDb`].
+pub struct AuditPlugin {
+    pub retention: Duration,
+    pub max_entries: u64,
+    pub export_directory: PathBuf,
+    /// The directory of the database the commands are saved in
+    pub path: PathBuf,
+}
+
+impl Default for AuditPlugin {
+    fn default() -> Self {
+        Self {
+            retention: Duration::from_secs(30 * 24 * 60 * 60),
+            max_entries: 1_000_000,
+            export_directory: PathBuf::from("audit"),
+            path: PathBuf::from("db").join("audit.mdb"),
+        }
+    }
+}
+
+impl Plugin for AuditPlugin {
+    fn build(&self, app: &mut App) {
+        let bucket = LocalDb::open_with_map_size(&self.path, map_size(self.max_entries))
+            .and_then(|db| db.bucket("hyperion-audit"))
+            .expect("failed to open audit log");
+        let log = AuditLog::new(bucket, self.retention, self.max_entries)
+            .expect("failed to load audit log");
+
+        app.insert_resource(log);
+        app.insert_resource(AuditExportDirectory(self.export_directory.clone()));
+        app.add_systems(
+            FixedUpdate,
+            (timed(record_commands), timed(prune_audit_log)),
+        );
+
+        AuditLogCommand::register(app.world_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion::storage::TempLocalDb;
+
+    use super::*;
+
+    #[test]
+    fn test_entries_are_ordered() {
+        let db = TempLocalDb::new().unwrap();
+        let bucket = db.bucket("hyperion-audit").unwrap();
+
+        let entry = |command: &str| AuditEntry {
+            time: 0,
+            player: uuid::Uuid::nil(),
+            name: "moderator".to_owned(),
+            command: command.to_owned(),
+            outcome: CommandOutcome::Executed,
+        };
+
+        let mut log = AuditLog::new(bucket.clone(), Duration::MAX, 100).unwrap();
+        for i in 0..12 {
+            log.record(&entry(&format!("kick player{i}"))).unwrap();
+        }
+
+        // Ids continue after a restart
+        let mut log = AuditLog::new(bucket, Duration::MAX, 100).unwrap();
+        log.record(&entry("ban griefer")).unwrap();
+
+        let commands = log
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.command)
+            .collect::<Vec<_>>();
+        assert_eq!(commands.len(), 13);
+        assert_eq!(commands[2], "kick player2");
+        assert_eq!(commands[12], "ban griefer");
+
+        let latest = |skip, count, player| {
+            log.latest(skip, count, player)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.command)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(latest(1, 2, None), ["kick player11", "kick player10"]);
+        assert_eq!(latest(12, 5, None), ["kick player0"]);
+        assert_eq!(latest(0, 5, Some("Moderator")).len(), 5);
+        assert!(latest(0, 5, Some("griefer")).is_empty());
+    }
+
+    #[test]
+    fn test_old_entries_are_pruned() {
+        let db = TempLocalDb::new().unwrap();
+        let bucket = db.bucket("hyperion-audit").unwrap();
+
+        let entry = |time| AuditEntry {
+            time,
+            player: uuid::Uuid::nil(),
+            name: "moderator".to_owned(),
+            command: "kick griefer".to_owned(),
+            outcome: CommandOutcome::Executed,
+        };
+
+        let mut log = AuditLog::new(bucket.clone(), Duration::from_secs(60 * 60), 4).unwrap();
+        for minute in 0..5 {
+            log.record(&entry(minute * MILLIS_PER_MINUTE)).unwrap();
+        }
+
+        // The oldest entry does not fit in `max_entries`
+        assert_eq!(log.len(), 4);
+        assert_eq!(log.entries().unwrap()[0].time, MILLIS_PER_MINUTE);
+
+        // Only entries from minutes before the cutoff are removed
+        log.prune(63 * MILLIS_PER_MINUTE + 1).unwrap();
+        assert_eq!(log.len(), 2);
+
+        let log = AuditLog::new(bucket, Duration::from_secs(60 * 60), 4).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(
+            log.latest(0, 1, None).unwrap()[0].time,
+            4 * MILLIS_PER_MINUTE
+        );
+    }
+
+    #[test]
+    fn test_map_size() {
+        assert_eq!(map_size(0), 1024 * 1024);
+        assert_eq!(map_size(1_000_000), 1024 * 1024 + 1_024_000_000);
+        assert_eq!(map_size(3) % 4096, 0);
+        assert_eq!(map_size(u64::MAX), usize::MAX);
+    }
+
+    #[test]
+    fn test_export_snapshot() {
+        let db = TempLocalDb::new().unwrap();
+        let bucket = db.bucket("hyperion-audit").unwrap();
+        let directory = tempfile::tempdir().unwrap();
+
+        let entry = |command: &str| AuditEntry {
+            time: 0,
+            player: uuid::Uuid::nil(),
+            name: "moderator".to_owned(),
+            command: command.to_owned(),
+            outcome: CommandOutcome::Executed,
+        };
+
+        let mut log = AuditLog::new(bucket, Duration::MAX, 100).unwrap();
+        log.record(&entry("kick griefer")).unwrap();
+        log.record(&entry("ban griefer")).unwrap();
+
+        // Commands recorded while the export is running are not exported
+        let snapshot = log.snapshot();
+        log.record(&entry("auditlog export")).unwrap();
+
+        let path = snapshot.export(&directory.path().join("audit")).unwrap();
+        let exported: Vec<AuditEntry> =
+            serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+
+        assert_eq!(exported, [entry("kick griefer"), entry("ban griefer")]);
+    }
+}
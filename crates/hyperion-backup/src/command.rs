@@ -0,0 +1,109 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    command_channel::CommandChannel,
+    net::{Compose, ConnectionId, DataBundle, agnostic},
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+use crate::{BackupDirectory, create_backup};
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "backup")]
+#[command_permission(group = "Admin")]
+pub enum BackupCommand {
+    /// Backs up the world, named after the current time by default
+    Create {
+        name: Option<String>,
+    },
+    List,
+    /// Restores a backup when the server next starts
+    Restore {
+        name: String,
+    },
+}
+
+impl MinecraftCommand for BackupCommand {
+    type State = SystemState<(
+        Query<'static, 'static, &'static ConnectionId>,
+        Res<'static, BackupDirectory>,
+        Res<'static, Compose>,
+        Res<'static, CommandChannel>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (query, directory, compose, command_channel) = state.get(world);
+
+        let &connection_id = match query.get(caller) {
+            Ok(connection_id) => connection_id,
+            Err(e) => {
+                error!("backup command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let mut result = Ok(());
+        let lines = match self {
+            Self::Create { name } => {
+                let command_channel = command_channel.clone();
+
+                create_backup(world, name, move |result| {
+                    let msg = match result {
+                        Ok(archive) => format!("§aBackup written to {}", archive.display()),
+                        Err(e) => format!("§cFailed to create backup: {e}"),
+                    };
+
+                    command_channel.push(move |world: &mut World| {
+                        let compose = world.resource::<Compose>();
+                        if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+                            error!("failed to send backup message: {e}");
+                        }
+                    });
+                });
+
+                vec!["§7Creating backup...".to_owned()]
+            }
+            Self::List => match directory.list() {
+                Ok(names) if names.is_empty() => vec!["§7There are no backups".to_owned()],
+                Ok(names) => {
+                    let pending = directory.pending_restore();
+
+                    names
+                        .into_iter()
+                        .map(|name| {
+                            if pending.as_deref() == Some(name.as_str()) {
+                                format!("§6{name} §7(restored on next start)")
+                            } else {
+                                format!("§6{name}")
+                            }
+                        })
+                        .collect()
+                }
+                Err(e) => {
+                    result = Err(CommandFailed);
+                    vec![format!("§cFailed to list backups: {e}")]
+                }
+            },
+            Self::Restore { name } => match directory.schedule_restore(&name) {
+                Ok(()) => vec![format!(
+                    "§aBackup {name} will be restored when the server next starts"
+                )],
+                Err(e) => {
+                    result = Err(CommandFailed);
+                    vec![format!("§cFailed to restore backup: {e}")]
+                }
+            },
+        };
+
+        let mut bundle = DataBundle::new(&compose);
+        for line in lines {
+            bundle.add_packet(&agnostic::chat(line)).unwrap();
+        }
+        if let Err(e) = bundle.unicast(connection_id) {
+            error!("failed to send backup list: {e}");
+        }
+
+        result
+    }
+}
@@ -0,0 +1,402 @@
+//! Backing up the world to archives, which are restored when the server next starts.
+//!
+//! A backup is a `.tar.gz` archive of the save directory the world was loaded from together with
+//! [`CHUNKS_ENTRY`], which stores every chunk which was changed since it was loaded.
+
+mod command;
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context, bail};
+use bevy::prelude::*;
+use hyperion::{
+    BlockState,
+    glam::{I16Vec2, IVec3},
+    runtime::AsyncRuntime,
+    simulation::blocks::{Blocks, chunk::START_Y},
+};
+use hyperion_clap::MinecraftCommand;
+use tracing::{error, info, warn};
+
+use crate::command::BackupCommand;
+
+/// The archive entry which stores the changed chunks
+pub const CHUNKS_ENTRY: &str = "hyperion-chunks.bin";
+
+/// The file in the backup directory which names the backup to restore on the next start
+const PENDING_RESTORE: &str = "pending-restore";
+
+const ARCHIVE_EXTENSION: &str = ".tar.gz";
+
+/// The blocks of a chunk, ordered by Y, then Z, then X
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ChunkSnapshot {
+    position: I16Vec2,
+    states: Vec<u16>,
+}
+
+impl ChunkSnapshot {
+    fn capture(blocks: &Blocks, position: I16Vec2) -> Option<Self> {
+        let column = blocks.get_loaded_chunk(position)?;
+        let height = column.data.sections.len() * 16;
+        let max_y = START_Y + i16::try_from(height).ok()? - 1;
+
+        let mut states = vec![0; height * 256];
+        for (block, state) in column.blocks_in_range(START_Y, max_y) {
+            let index = Self::index(block);
+            states[index] = state.to_raw();
+        }
+
+        Some(Self { position, states })
+    }
+
+    fn index(block: IVec3) -> usize {
+        let x = block.x & 15;
+        let z = block.z & 15;
+        let y = block.y - i32::from(START_Y);
+        usize::try_from(x + z * 16 + y * 256).unwrap()
+    }
+
+    fn blocks(&self) -> impl Iterator<Item = (IVec3, BlockState)> + '_ {
+        let origin = IVec3::new(
+            i32::from(self.position.x) * 16,
+            i32::from(START_Y),
+            i32::from(self.position.y) * 16,
+        );
+
+        self.states
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, &raw)| {
+                let index = i32::try_from(index).ok()?;
+                let offset = IVec3::new(index % 16, index / 256, index / 16 % 16);
+                Some((origin + offset, BlockState::from_raw(raw)?))
+            })
+    }
+}
+
+/// Encodes chunks as their position and the number of blocks followed by the blocks, all in
+/// little endian
+fn encode_chunks(chunks: &[ChunkSnapshot]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    for chunk in chunks {
+        bytes.extend_from_slice(&chunk.position.x.to_le_bytes());
+        bytes.extend_from_slice(&chunk.position.y.to_le_bytes());
+        bytes.extend_from_slice(&u32::try_from(chunk.states.len()).unwrap().to_le_bytes());
+
+        for state in &chunk.states {
+            bytes.extend_from_slice(&state.to_le_bytes());
+        }
+    }
+
+    bytes
+}
+
+fn decode_chunks(mut bytes: &[u8]) -> anyhow::Result<Vec<ChunkSnapshot>> {
+    fn take<const N: usize>(bytes: &mut &[u8]) -> anyhow::Result<[u8; N]> {
+        let Some((taken, rest)) = bytes.split_first_chunk::<N>() else {
+            bail!("unexpected end of chunk data");
+        };
+        *bytes = rest;
+        Ok(*taken)
+    }
+
+    let mut chunks = Vec::new();
+
+    while !bytes.is_empty() {
+        let x = i16::from_le_bytes(take(&mut bytes)?);
+        let z = i16::from_le_bytes(take(&mut bytes)?);
+        let len = u32::from_le_bytes(take(&mut bytes)?);
+
+        let states = (0..len)
+            .map(|_| Ok(u16::from_le_bytes(take(&mut bytes)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        chunks.push(ChunkSnapshot {
+            position: I16Vec2::new(x, z),
+            states,
+        });
+    }
+
+    Ok(chunks)
+}
+
+/// Where backups are stored
+#[derive(Resource, Clone, Debug)]
+pub struct BackupDirectory(pub PathBuf);
+
+impl BackupDirectory {
+    /// Returns the path of the archive of the backup `name`
+    fn archive(&self, name: &str) -> anyhow::Result<PathBuf> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            && !name.starts_with('.');
+
+        if !valid {
+            bail!("invalid backup name {name:?}");
+        }
+
+        Ok(self.0.join(format!("{name}{ARCHIVE_EXTENSION}")))
+    }
+
+    /// Returns the names of every backup in order
+    pub fn list(&self) -> anyhow::Result<Vec<String>> {
+        if !self.0.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut names = std::fs::read_dir(&self.0)?
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                Some(name.strip_suffix(ARCHIVE_EXTENSION)?.to_owned())
+            })
+            .collect::<Vec<_>>();
+
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns the backup which is restored on the next start
+    #[must_use]
+    pub fn pending_restore(&self) -> Option<String> {
+        let name = std::fs::read_to_string(self.0.join(PENDING_RESTORE)).ok()?;
+        Some(name.trim().to_owned())
+    }
+
+    /// Restores the backup `name` when the server next starts
+    pub fn schedule_restore(&self, name: &str) -> anyhow::Result<()> {
+        let archive = self.archive(name)?;
+        if !archive.exists() {
+            bail!("backup {name} does not exist");
+        }
+
+        std::fs::write(self.0.join(PENDING_RESTORE), name)?;
+        Ok(())
+    }
+}
+
+/// Returns the default name of a backup, which is the current time
+fn timestamp_name() -> String {
+    humantime::format_rfc3339_seconds(SystemTime::now())
+        .to_string()
+        .trim_end_matches('Z')
+        .replace('T', "_")
+        .replace(':', "-")
+}
+
+/// Writes the archive of a backup. The archive is written to a temporary file first, so an
+/// archive which exists is always complete.
+fn write_archive(
+    archive: &Path,
+    save_directory: Option<&Path>,
+    chunks: &[ChunkSnapshot],
+) -> anyhow::Result<()> {
+    let partial = archive.with_extension("part");
+
+    {
+        let file = BufWriter::new(File::create(&partial)?);
+        let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+
+        if let Some(save_directory) = save_directory {
+            builder
+                .append_dir_all(".", save_directory)
+                .with_context(|| format!("failed to archive {save_directory:?}"))?;
+        }
+
+        let bytes = encode_chunks(chunks);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, CHUNKS_ENTRY, bytes.as_slice())?;
+
+        builder.into_inner()?.finish()?.into_inner()?;
+    }
+
+    std::fs::rename(&partial, archive)?;
+    Ok(())
+}
+
+fn read_archive_chunks(archive: &Path) -> anyhow::Result<Vec<ChunkSnapshot>> {
+    let reader = flate2::read::GzDecoder::new(BufReader::new(File::open(archive)?));
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.as_os_str() != CHUNKS_ENTRY {
+            continue;
+        }
+
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        return decode_chunks(&bytes);
+    }
+
+    bail!("archive does not contain {CHUNKS_ENTRY}")
+}
+
+/// Creates the backup `name` in the background, calling `on_done` with the path of the archive
+/// once it has been written
+pub fn create_backup(
+    world: &World,
+    name: Option<String>,
+    on_done: impl FnOnce(anyhow::Result<PathBuf>) + Send + 'static,
+) {
+    let directory = world.resource::<BackupDirectory>().clone();
+    let blocks = world.resource::<Blocks>();
+    let runtime = world.resource::<AsyncRuntime>();
+
+    let name = name.unwrap_or_else(timestamp_name);
+    let archive = match directory.archive(&name) {
+        Ok(archive) => archive,
+        Err(e) => {
+            on_done(Err(e));
+            return;
+        }
+    };
+
+    // Chunks are captured immediately so the backup matches the world at this tick
+    let chunks = blocks
+        .modified_chunks()
+        .filter_map(|position| ChunkSnapshot::capture(blocks, position))
+        .collect::<Vec<_>>();
+    let save_directory = blocks.save_directory().map(Path::to_path_buf);
+
+    runtime.spawn_blocking(move || {
+        let result = std::fs::create_dir_all(&directory.0)
+            .map_err(anyhow::Error::from)
+            .and_then(|()| write_archive(&archive, save_directory.as_deref(), &chunks))
+            .map(|()| archive);
+
+        on_done(result);
+    });
+}
+
+/// Applies the changed chunks of the backup which was scheduled to be restored
+fn restore_pending_backup(world: &mut World) {
+    let directory = world.resource::<BackupDirectory>().clone();
+    let Some(name) = directory.pending_restore() else {
+        return;
+    };
+
+    if let Err(e) = std::fs::remove_file(directory.0.join(PENDING_RESTORE)) {
+        error!("failed to remove pending restore of backup {name}: {e}");
+        return;
+    }
+
+    let chunks = match directory
+        .archive(&name)
+        .and_then(|archive| read_archive_chunks(&archive))
+    {
+        Ok(chunks) => chunks,
+        Err(e) => {
+            error!("failed to restore backup {name}: {e}");
+            return;
+        }
+    };
+
+    let runtime = world.resource::<AsyncRuntime>().clone();
+    let mut blocks = world.resource_mut::<Blocks>();
+
+    let mut skipped = 0;
+    for chunk in &chunks {
+        blocks.block_and_load(chunk.position, &runtime);
+
+        for (position, state) in chunk.blocks() {
+            if blocks.set_block(position, state).is_err() {
+                skipped += 1;
+            }
+        }
+    }
+
+    if skipped > 0 {
+        warn!("{skipped} blocks of backup {name} could not be restored");
+    }
+
+    info!("restored {} chunks from backup {name}", chunks.len());
+}
+
+/// Adds `/backup`. Backups are stored in `directory`.
+pub struct BackupPlugin {
+    pub directory: PathBuf,
+}
+
+impl Default for BackupPlugin {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("backups"),
+        }
+    }
+}
+
+impl Plugin for BackupPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BackupDirectory(self.directory.clone()));
+        app.add_systems(Startup, restore_pending_backup);
+
+        BackupCommand::register(app.world_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_roundtrip() {
+        let chunks = vec![
+            ChunkSnapshot {
+                position: I16Vec2::new(-1, 2),
+                states: vec![BlockState::STONE.to_raw(); 4096],
+            },
+            ChunkSnapshot {
+                position: I16Vec2::new(0, 0),
+                states: vec![0, 1, 2],
+            },
+        ];
+
+        let bytes = encode_chunks(&chunks);
+        assert_eq!(decode_chunks(&bytes).unwrap(), chunks);
+        assert!(decode_chunks(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_positions() {
+        let chunk = ChunkSnapshot {
+            position: I16Vec2::new(-1, 2),
+            states: vec![BlockState::STONE.to_raw(); 4096],
+        };
+
+        for (position, _) in chunk.blocks() {
+            let index = ChunkSnapshot::index(position);
+            assert_eq!(
+                chunk.blocks().nth(index).map(|(position, _)| position),
+                Some(position)
+            );
+        }
+
+        let first = chunk.blocks().next().unwrap().0;
+        assert_eq!(first, IVec3::new(-16, i32::from(START_Y), 32));
+    }
+
+    #[test]
+    fn test_archive_name() {
+        let directory = BackupDirectory(PathBuf::from("backups"));
+
+        assert_eq!(
+            directory.archive("2024-01-01_00-00-00").unwrap(),
+            PathBuf::from("backups/2024-01-01_00-00-00.tar.gz")
+        );
+        assert!(directory.archive("../save").is_err());
+        assert!(directory.archive("").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    net::{Compose, ConnectionId, DataBundle, agnostic},
+    simulation::{Position, blocks::Blocks},
+    storage::now_millis,
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+use crate::{BlockAction, BlockLog, BlockLogEntry};
+
+/// The most changes shown by `/blocklog lookup`
+const MAX_LOOKUP_LINES: usize = 10;
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "blocklog")]
+#[command_permission(group = "Admin")]
+pub enum BlockLogCommand {
+    /// Lists the latest block changes within `radius` blocks of you
+    Lookup {
+        radius: u16,
+        /// How far back to look, such as `30m` or `2h`. Defaults to the whole log.
+        #[arg(value_parser = humantime::parse_duration)]
+        time: Option<Duration>,
+    },
+    /// Undoes the blocks placed and broken by a player
+    Rollback {
+        player: String,
+        /// How far back to undo, such as `30m` or `2h`. Defaults to the whole log.
+        #[arg(value_parser = humantime::parse_duration)]
+        time: Option<Duration>,
+    },
+}
+
+/// The milliseconds since the Unix epoch `time` ago, or the start of the log if `time` is `None`
+fn since(time: Option<Duration>) -> u64 {
+    time.map_or(0, |time| {
+        now_millis().saturating_sub(u64::try_from(time.as_millis()).unwrap_or(u64::MAX))
+    })
+}
+
+fn describe(entry: &BlockLogEntry, now: u64) -> String {
+    let age = Duration::from_secs(now.saturating_sub(entry.time) / 1000);
+    let block = match entry.action {
+        BlockAction::Break => entry.old_block(),
+        BlockAction::Place | BlockAction::Interact => entry.new_block(),
+    };
+    let position = entry.position;
+
+    format!(
+        "§7{} ago §6{}§r {} {} at ({}, {}, {})",
+        humantime::format_duration(age),
+        entry.name,
+        entry.action.verb(),
+        block.to_kind().to_str(),
+        position.x,
+        position.y,
+        position.z
+    )
+}
+
+impl MinecraftCommand for BlockLogCommand {
+    type State = SystemState<(
+        Query<'static, 'static, (&'static ConnectionId, &'static Position)>,
+        Res<'static, BlockLog>,
+        Res<'static, Compose>,
+        Commands<'static, 'static>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (query, log, compose, mut commands) = state.get(world);
+
+        let (&connection_id, position) = match query.get(caller) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("blocklog command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let mut result = Ok(());
+        let lines = match self {
+            Self::Lookup { radius, time } => {
+                let center = position.floor().as_ivec3();
+                let radius = i32::from(radius);
+                let now = now_millis();
+
+                let mut lines = log
+                    .since(since(time))
+                    .rev()
+                    .filter(|entry| (entry.position - center).abs().max_element() <= radius)
+                    .take(MAX_LOOKUP_LINES)
+                    .map(|entry| describe(entry, now))
+                    .collect::<Vec<_>>();
+
+                if lines.is_empty() {
+                    lines.push("§7No blocks were changed nearby".to_owned());
+                }
+
+                lines
+            }
+            Self::Rollback { player, time } => {
+                // Changes are undone from newest to oldest, so each block ends up as it was
+                // before the player's first change to it
+                let changes = log
+                    .since(since(time))
+                    .rev()
+                    .filter(|entry| entry.action != BlockAction::Interact)
+                    .filter(|entry| entry.name.eq_ignore_ascii_case(&player))
+                    .map(|entry| (entry.position, entry.old_block()))
+                    .collect::<Vec<_>>();
+
+                if changes.is_empty() {
+                    result = Err(CommandFailed);
+                    vec![format!("§c{player} has no block changes to roll back")]
+                } else {
+                    let msg = format!("§aRolled back {} block changes by {player}", changes.len());
+
+                    commands.queue(move |world: &mut World| {
+                        let mut blocks = world.resource_mut::<Blocks>();
+
+                        for (position, block) in changes {
+                            if let Err(e) = blocks.set_block(position, block) {
+                                error!("failed to roll back block at {position}: {e:?}");
+                            }
+                        }
+                    });
+
+                    vec![msg]
+                }
+            }
+        };
+
+        let mut bundle = DataBundle::new(&compose);
+        for line in lines {
+            bundle.add_packet(&agnostic::chat(line)).unwrap();
+        }
+        if let Err(e) = bundle.unicast(connection_id) {
+            error!("failed to send block log: {e}");
+        }
+
+        result
+    }
+}
@@ -0,0 +1,187 @@
+//! Records who placed, broke and used which blocks so that operators can look up and roll back
+//! griefing.
+//!
+//! Changes are saved in their own [`LocalDb`] at [`BlockLogPlugin::path`] so that they do not fill
+//! up the database shared by other plugins, and are removed once they are older than
+//! [`BlockLogPlugin::retention`].
+
+mod command;
+mod log;
+
+use std::{path::PathBuf, time::Duration};
+
+use bevy::prelude::*;
+use hyperion::{
+    BlockState,
+    glam::IVec3,
+    simulation::{
+        Uuid,
+        blocks::Blocks,
+        event::{CancelEvents, Cancellable, DestroyBlock, PlaceBlock, ToggleDoor},
+    },
+    storage::{LocalDb, now_millis},
+    timings::timed,
+    valence_protocol::block::{PropName, PropValue},
+};
+use hyperion_clap::MinecraftCommand;
+pub use log::{BlockAction, BlockLog, BlockLogEntry};
+use tracing::error;
+
+use crate::command::BlockLogCommand;
+
+/// Records the block changes of [`PlaceBlock`], [`DestroyBlock`] and [`ToggleDoor`] events which
+/// were not cancelled. The block before the change is read from [`Blocks`], so systems which
+/// apply these events must run after this set.
+#[derive(SystemSet, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RecordBlockChanges;
+
+/// The door with its `open` property flipped, or `None` if the block cannot be opened
+fn toggled(door: BlockState) -> Option<BlockState> {
+    let open = match door.get(PropName::Open)? {
+        PropValue::True => PropValue::False,
+        PropValue::False => PropValue::True,
+        _ => return None,
+    };
+
+    Some(door.set(PropName::Open, open))
+}
+
+fn record_block_changes(
+    mut place_events: EventReader<'_, '_, Cancellable<PlaceBlock>>,
+    mut destroy_events: EventReader<'_, '_, Cancellable<DestroyBlock>>,
+    mut door_events: EventReader<'_, '_, Cancellable<ToggleDoor>>,
+    blocks: Res<'_, Blocks>,
+    players: Query<'_, '_, (&Uuid, &Name)>,
+    mut log: ResMut<'_, BlockLog>,
+) {
+    let time = now_millis();
+    let mut entries = Vec::new();
+
+    let mut record =
+        |player: Entity, action: BlockAction, position: IVec3, old: BlockState, new: BlockState| {
+            let Ok((uuid, name)) = players.get(player) else {
+                return;
+            };
+
+            entries.push(BlockLogEntry {
+                time,
+                player: uuid.0,
+                name: name.to_string(),
+                action,
+                position,
+                old: old.to_raw(),
+                new: new.to_raw(),
+            });
+        };
+
+    for event in place_events.read() {
+        if event.is_cancelled() {
+            continue;
+        }
+
+        let old = blocks.get_block(event.position).unwrap_or(BlockState::AIR);
+        record(
+            event.from,
+            BlockAction::Place,
+            event.position,
+            old,
+            event.block,
+        );
+    }
+
+    for event in destroy_events.read() {
+        if event.is_cancelled() {
+            continue;
+        }
+
+        let Some(old) = blocks.get_block(event.position) else {
+            continue;
+        };
+
+        if old.is_air() {
+            continue;
+        }
+
+        record(
+            event.from,
+            BlockAction::Break,
+            event.position,
+            old,
+            BlockState::AIR,
+        );
+    }
+
+    for event in door_events.read() {
+        if event.is_cancelled() {
+            continue;
+        }
+
+        let Some((old, new)) = blocks
+            .get_block(event.position)
+            .and_then(|old| Some((old, toggled(old)?)))
+        else {
+            continue;
+        };
+
+        record(event.from, BlockAction::Interact, event.position, old, new);
+    }
+
+    if entries.is_empty() {
+        return;
+    }
+
+    if let Err(e) = log.record(entries) {
+        error!("failed to record block changes: {e}");
+    }
+}
+
+fn prune_block_log(mut log: ResMut<'_, BlockLog>) {
+    if let Err(e) = log.prune(now_millis()) {
+        error!("failed to prune block log: {e}");
+    }
+}
+
+/// Adds `/blocklog` and records block changes. Changes older than `retention` are removed, and
+/// so are the oldest changes once there are more than `max_entries`.
+pub struct BlockLogPlugin {
+    pub retention: Duration,
+    pub max_entries: usize,
+    /// The directory of the database the changes are saved in
+    pub path: PathBuf,
+    /// The largest size in bytes which the database can grow to. It must have room for
+    /// `max_entries` changes, which take about 200 bytes each.
+    pub map_size: usize,
+}
+
+impl Default for BlockLogPlugin {
+    fn default() -> Self {
+        Self {
+            retention: Duration::from_secs(7 * 24 * 60 * 60),
+            max_entries: 2_000_000,
+            path: PathBuf::from("db").join("blocklog.mdb"),
+            map_size: 1024 * 1024 * 1024, // 1GiB
+        }
+    }
+}
+
+impl Plugin for BlockLogPlugin {
+    fn build(&self, app: &mut App) {
+        let bucket = LocalDb::open_with_map_size(&self.path, self.map_size)
+            .and_then(|db| db.bucket("hyperion-blocklog"))
+            .expect("failed to open block log");
+        let log = BlockLog::load(bucket, self.retention, self.max_entries)
+            .expect("failed to load block log");
+
+        app.insert_resource(log);
+        app.configure_sets(FixedUpdate, RecordBlockChanges.after(CancelEvents));
+        app.add_systems(
+            FixedUpdate,
+            (
+                timed(record_block_changes).in_set(RecordBlockChanges),
+                timed(prune_block_log),
+            ),
+        );
+
+        BlockLogCommand::register(app.world_mut());
+    }
+}
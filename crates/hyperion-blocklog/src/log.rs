@@ -0,0 +1,308 @@
+use std::{sync::mpsc, time::Duration};
+
+use bevy::prelude::*;
+use hyperion::{
+    BlockState,
+    glam::IVec3,
+    storage::{Bucket, now_millis},
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+const MILLIS_PER_MINUTE: u64 = 60_000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockAction {
+    Place,
+    Break,
+    /// Opening or closing a door, trapdoor or gate
+    Interact,
+}
+
+impl BlockAction {
+    /// The past tense of the action, such as `placed`
+    #[must_use]
+    pub const fn verb(self) -> &'static str {
+        match self {
+            Self::Place => "placed",
+            Self::Break => "broke",
+            Self::Interact => "used",
+        }
+    }
+}
+
+/// A block change made by a player
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockLogEntry {
+    /// When the change was made, in milliseconds since the Unix epoch
+    pub time: u64,
+    pub player: Uuid,
+    /// The name of the player when the change was made
+    pub name: String,
+    pub action: BlockAction,
+    pub position: IVec3,
+    /// The raw block state before the change
+    pub old: u16,
+    /// The raw block state after the change
+    pub new: u16,
+}
+
+impl BlockLogEntry {
+    #[must_use]
+    pub fn old_block(&self) -> BlockState {
+        BlockState::from_raw(self.old).unwrap_or(BlockState::AIR)
+    }
+
+    #[must_use]
+    pub fn new_block(&self) -> BlockState {
+        BlockState::from_raw(self.new).unwrap_or(BlockState::AIR)
+    }
+}
+
+/// A write which the writer thread makes to the bucket
+enum Write {
+    Append(Vec<(String, BlockLogEntry)>),
+    Remove(Vec<String>),
+    /// Sent back once every earlier write is saved
+    Flush(mpsc::SyncSender<()>),
+}
+
+/// Saves the writes it receives until the [`BlockLog`] is dropped. Writes which are queued
+/// together are saved in one transaction, so a slow disk does not hold up the tick.
+fn spawn_writer(bucket: Bucket<BlockLogEntry>) -> anyhow::Result<mpsc::Sender<Write>> {
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::Builder::new()
+        .name("blocklog-writer".to_owned())
+        .spawn(move || {
+            while let Ok(write) = rx.recv() {
+                let mut insert = Vec::new();
+                let mut remove = Vec::new();
+                let mut flushes = Vec::new();
+
+                for write in std::iter::once(write).chain(rx.try_iter()) {
+                    match write {
+                        Write::Append(entries) => insert.extend(entries),
+                        Write::Remove(keys) => remove.extend(keys),
+                        Write::Flush(done) => flushes.push(done),
+                    }
+                }
+
+                if let Err(e) = bucket.write_many(&insert, &remove) {
+                    error!("failed to save block log: {e}");
+                }
+
+                // The caller may have stopped waiting
+                for done in flushes {
+                    done.send(()).ok();
+                }
+            }
+        })?;
+
+    Ok(tx)
+}
+
+/// Every block change made in the retention window, ordered by time.
+///
+/// Entries are kept in memory for lookups and saved in a [`Bucket`] with one key for each entry,
+/// so recording a change never rewrites older changes. Keys are consecutive numbers which sort
+/// by time. Saving happens on a separate thread.
+#[derive(Resource)]
+pub struct BlockLog {
+    entries: Vec<BlockLogEntry>,
+    /// The key of the first entry. The key of every later entry is one more than the one before.
+    first_key: u64,
+    writer: mpsc::Sender<Write>,
+    retention: Duration,
+    max_entries: usize,
+    /// Every minute before this one has been pruned
+    pruned_until: u64,
+}
+
+/// The bucket key of an entry. Keys are padded so that they sort numerically.
+fn entry_key(key: u64) -> String {
+    format!("{key:020}")
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+impl BlockLog {
+    /// Loads the entries saved in `bucket`. Once there are more than `max_entries` entries, the
+    /// oldest are removed even if they are in the retention window.
+    pub fn load(
+        bucket: Bucket<BlockLogEntry>,
+        retention: Duration,
+        max_entries: usize,
+    ) -> anyhow::Result<Self> {
+        let saved = bucket.entries()?;
+
+        let first_key = match saved.first() {
+            Some((key, _)) => key.parse()?,
+            None => 0,
+        };
+
+        Ok(Self {
+            entries: saved.into_iter().map(|(_, entry)| entry).collect(),
+            first_key,
+            writer: spawn_writer(bucket)?,
+            retention,
+            max_entries,
+            pruned_until: 0,
+        })
+    }
+
+    #[must_use]
+    pub const fn retention(&self) -> Duration {
+        self.retention
+    }
+
+    /// Every entry, ordered by time
+    #[must_use]
+    pub fn entries(&self) -> &[BlockLogEntry] {
+        &self.entries
+    }
+
+    /// The entries made at or after `since`, in milliseconds since the Unix epoch
+    pub fn since(&self, since: u64) -> impl DoubleEndedIterator<Item = &BlockLogEntry> + '_ {
+        let start = self.entries.partition_point(|entry| entry.time < since);
+        self.entries[start..].iter()
+    }
+
+    fn send(&self, write: Write) -> anyhow::Result<()> {
+        self.writer
+            .send(write)
+            .map_err(|_| anyhow::anyhow!("the block log writer has stopped"))
+    }
+
+    /// Adds `entries`, which must not be older than the entries already in the log. The entries
+    /// are saved in the background.
+    pub fn record(&mut self, entries: Vec<BlockLogEntry>) -> anyhow::Result<()> {
+        let next_key = self.first_key + self.entries.len() as u64;
+
+        let saved = (next_key..)
+            .zip(&entries)
+            .map(|(key, entry)| (entry_key(key), entry.clone()))
+            .collect();
+
+        self.send(Write::Append(saved))?;
+        self.entries.extend(entries);
+
+        if self.entries.len() > self.max_entries {
+            self.remove_oldest(self.entries.len() - self.max_entries)?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the `count` oldest entries
+    fn remove_oldest(&mut self, count: usize) -> anyhow::Result<()> {
+        let end = self.first_key + count as u64;
+        self.send(Write::Remove(
+            (self.first_key..end).map(entry_key).collect(),
+        ))?;
+
+        self.entries.drain(..count);
+        self.first_key = end;
+
+        Ok(())
+    }
+
+    /// Removes the entries which are older than the retention window. This only does work once
+    /// a minute.
+    pub fn prune(&mut self, now: u64) -> anyhow::Result<()> {
+        let cutoff = now.saturating_sub(millis(self.retention));
+        let cutoff_minute = cutoff / MILLIS_PER_MINUTE;
+
+        if cutoff_minute <= self.pruned_until {
+            return Ok(());
+        }
+
+        let expired = self
+            .entries
+            .partition_point(|entry| entry.time / MILLIS_PER_MINUTE < cutoff_minute);
+        if expired > 0 {
+            self.remove_oldest(expired)?;
+        }
+
+        self.pruned_until = cutoff_minute;
+
+        Ok(())
+    }
+
+    /// Blocks until every change recorded so far is saved
+    pub fn flush(&self) -> anyhow::Result<()> {
+        let (done, saved) = mpsc::sync_channel(1);
+        self.send(Write::Flush(done))?;
+        saved.recv()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion::storage::TempLocalDb;
+
+    use super::*;
+
+    fn entry(time: u64) -> BlockLogEntry {
+        BlockLogEntry {
+            time,
+            player: Uuid::nil(),
+            name: "griefer".to_owned(),
+            action: BlockAction::Break,
+            position: IVec3::new(1, 2, 3),
+            old: BlockState::STONE.to_raw(),
+            new: BlockState::AIR.to_raw(),
+        }
+    }
+
+    #[test]
+    fn test_record_and_prune() {
+        let db = TempLocalDb::new().unwrap();
+        let bucket = db.bucket("hyperion-blocklog").unwrap();
+
+        let retention = Duration::from_secs(600);
+        let mut log = BlockLog::load(bucket.clone(), retention, 100).unwrap();
+
+        let minute = MILLIS_PER_MINUTE;
+        log.record(vec![entry(minute), entry(minute + 1), entry(minute * 5)])
+            .unwrap();
+        log.record(vec![entry(minute * 20)]).unwrap();
+        log.flush().unwrap();
+
+        assert_eq!(log.since(minute * 5).count(), 2);
+        assert_eq!(bucket.keys().unwrap().len(), 4);
+
+        // Everything before minute 12 is expired
+        log.prune(minute * 22).unwrap();
+        assert_eq!(log.entries(), &[entry(minute * 20)]);
+        log.flush().unwrap();
+
+        let reloaded = BlockLog::load(bucket.clone(), retention, 100).unwrap();
+        assert_eq!(reloaded.entries(), log.entries());
+
+        // Recording after a reload continues after the last key
+        drop(log);
+        let mut log = reloaded;
+        log.record(vec![entry(minute * 21)]).unwrap();
+        log.flush().unwrap();
+        assert_eq!(bucket.keys().unwrap(), vec![entry_key(3), entry_key(4)]);
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let db = TempLocalDb::new().unwrap();
+        let bucket = db.bucket("hyperion-blocklog").unwrap();
+
+        let mut log = BlockLog::load(bucket.clone(), Duration::from_secs(600), 2).unwrap();
+        log.record(vec![entry(1), entry(2), entry(3)]).unwrap();
+        log.flush().unwrap();
+
+        assert_eq!(log.entries(), &[entry(2), entry(3)]);
+        assert_eq!(bucket.keys().unwrap(), vec![entry_key(1), entry_key(2)]);
+    }
+}
@@ -0,0 +1,370 @@
+//! Vanilla bow mechanics.
+//!
+//! The power of a shot depends on how long the bow was drawn for, and scales both the speed of
+//! the arrow and the damage it deals. The Power, Punch and Flame enchantments on the bow are
+//! applied to the arrow when it is fired.
+//!
+//! Arrow hits are sent as [`event::AttackEntity`] events, which game modes handle after
+//! [`event::CancelEvents`].
+
+use bevy::prelude::*;
+use hyperion::{
+    ItemKind, ItemStack,
+    glam::Vec3,
+    net::{Channel, Compose},
+    simulation::{
+        Owner, Pitch, Position, Uuid, Velocity, Yaw,
+        entity_kind::EntityKind,
+        event, get_direction_from_rotation,
+        metadata::living_entity::{ArrowsInEntity, HandStates},
+        packet_state,
+        util::enchantment_level,
+    },
+    timings::timed,
+};
+use hyperion_inventory::PlayerInventory;
+use tracing::{debug, error};
+use valence_protocol::ident;
+
+/// Shots with less power than this are not fired, which is the case when the bow is drawn for
+/// less than 3 ticks
+pub const MIN_POWER: f32 = 0.1;
+
+/// The speed of a fully drawn arrow in blocks per tick
+pub const MAX_ARROW_SPEED: f32 = 3.0;
+
+/// The damage of an arrow per block per tick of speed without the Power enchantment
+pub const BASE_ARROW_DAMAGE: f32 = 2.0;
+
+/// The number of ticks a Flame arrow sets its target on fire for
+pub const FLAME_TICKS: u16 = 100;
+
+/// The tick at which a player started drawing their bow
+#[derive(Component, Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BowCharging {
+    pub start_tick: Option<i64>,
+}
+
+impl BowCharging {
+    #[must_use]
+    pub const fn start(tick: i64) -> Self {
+        Self {
+            start_tick: Some(tick),
+        }
+    }
+
+    /// The number of ticks the bow has been drawn for, or `None` if the bow is not being drawn
+    #[must_use]
+    pub fn draw_ticks(&self, tick: i64) -> Option<i64> {
+        self.start_tick.map(|start| (tick - start).max(0))
+    }
+
+    pub const fn reset(&mut self) {
+        self.start_tick = None;
+    }
+}
+
+/// The power of a shot from a bow drawn for `draw_ticks`, between 0 and 1. This matches vanilla,
+/// in which the bow is fully drawn after 20 ticks.
+#[must_use]
+#[expect(
+    clippy::cast_precision_loss,
+    reason = "the number of ticks is clamped to 20"
+)]
+pub fn bow_power(draw_ticks: i64) -> f32 {
+    let seconds = draw_ticks.clamp(0, 20) as f32 / 20.0;
+    (seconds.mul_add(seconds, seconds * 2.0) / 3.0).min(1.0)
+}
+
+/// An arrow fired from a bow
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct Arrow {
+    /// The damage per block per tick of speed
+    pub damage: f32,
+    /// Critical arrows are fired from fully drawn bows and deal extra damage
+    pub critical: bool,
+    /// The level of Punch on the bow
+    pub punch: u8,
+    /// Whether the bow had Flame
+    pub flame: bool,
+}
+
+impl Arrow {
+    /// Creates an arrow fired from `bow` with the given power
+    #[must_use]
+    pub fn from_bow(bow: &ItemStack, power: f32) -> Self {
+        let power_level = enchantment_level(bow, "minecraft:power");
+
+        let damage = if power_level > 0 {
+            f32::from(power_level).mul_add(0.5, BASE_ARROW_DAMAGE + 0.5)
+        } else {
+            BASE_ARROW_DAMAGE
+        };
+
+        Self {
+            damage,
+            critical: power >= 1.0,
+            punch: enchantment_level(bow, "minecraft:punch"),
+            flame: enchantment_level(bow, "minecraft:flame") > 0,
+        }
+    }
+
+    /// The damage dealt by the arrow when it hits an entity at `speed`, without the random
+    /// bonus of critical arrows
+    #[must_use]
+    pub fn damage_at(&self, speed: f32) -> f32 {
+        (speed * self.damage).ceil().max(0.0)
+    }
+
+    /// The damage dealt by the arrow when it hits an entity at `speed`. Critical arrows deal up
+    /// to `damage / 2 + 1` extra damage.
+    #[must_use]
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "the damage is a small positive whole number"
+    )]
+    pub fn roll_damage(&self, speed: f32) -> f32 {
+        let damage = self.damage_at(speed);
+
+        if !self.critical {
+            return damage;
+        }
+
+        let bonus = fastrand::u32(0..(damage as u32) / 2 + 2);
+        damage + bonus as f32
+    }
+}
+
+fn initialize_player(
+    trigger: Trigger<'_, OnAdd, packet_state::Play>,
+    mut commands: Commands<'_, '_>,
+) {
+    commands
+        .entity(trigger.target())
+        .insert(BowCharging::default());
+}
+
+fn handle_bow_use(
+    mut events: EventReader<'_, '_, event::ItemInteract>,
+    compose: Res<'_, Compose>,
+    query: Query<'_, '_, &PlayerInventory>,
+    mut commands: Commands<'_, '_>,
+) {
+    let tick = compose.global().tick;
+
+    for event in events.read() {
+        let inventory = match query.get(event.entity) {
+            Ok(inventory) => inventory,
+            Err(e) => {
+                error!("failed to handle bow use: query failed: {e}");
+                continue;
+            }
+        };
+
+        let cursor = inventory.get_cursor();
+        if cursor.stack.item != ItemKind::Bow {
+            continue;
+        }
+
+        commands
+            .entity(event.entity)
+            .insert((BowCharging::start(tick), HandStates::new(1)));
+    }
+}
+
+/// Removes one arrow from the inventory. Returns false if the inventory has no arrows.
+fn consume_arrow(inventory: &mut PlayerInventory) -> bool {
+    let Some((slot, stack)) = inventory
+        .items()
+        .find(|(_, stack)| stack.item == ItemKind::Arrow && stack.count >= 1)
+    else {
+        return false;
+    };
+
+    let stack = if stack.count == 1 {
+        ItemStack::EMPTY
+    } else {
+        ItemStack::new(stack.item, stack.count - 1, stack.nbt.clone())
+    };
+
+    inventory.set(slot, stack).unwrap();
+    true
+}
+
+fn handle_bow_release(
+    mut events: EventReader<'_, '_, event::ReleaseUseItem>,
+    compose: Res<'_, Compose>,
+    mut query: Query<
+        '_,
+        '_,
+        (
+            &mut PlayerInventory,
+            &Position,
+            &Yaw,
+            &Pitch,
+            &mut BowCharging,
+        ),
+    >,
+    mut commands: Commands<'_, '_>,
+) {
+    let tick = compose.global().tick;
+
+    for event in events.read() {
+        let (mut inventory, position, yaw, pitch, mut bow_charging) =
+            match query.get_mut(event.from) {
+                Ok(data) => data,
+                Err(e) => {
+                    error!("failed to handle bow release: query failed: {e}");
+                    continue;
+                }
+            };
+
+        let Some(draw_ticks) = bow_charging.draw_ticks(tick) else {
+            continue;
+        };
+
+        bow_charging.reset();
+
+        let bow = inventory.get_cursor().stack.clone();
+        if bow.item != ItemKind::Bow {
+            continue;
+        }
+
+        let power = bow_power(draw_ticks);
+        if power < MIN_POWER {
+            continue;
+        }
+
+        if !consume_arrow(&mut inventory) {
+            continue;
+        }
+
+        debug!(
+            "Player {:?} fired an arrow with power {power} after {draw_ticks} ticks",
+            event.from
+        );
+
+        let direction = get_direction_from_rotation(**yaw, **pitch);
+        let velocity = direction * (power * MAX_ARROW_SPEED);
+
+        let spawn_pos = Vec3::new(position.x, position.y + 1.62, position.z) + direction * 0.5;
+
+        commands.spawn((
+            Uuid::new_v4(),
+            Position::new(spawn_pos.x, spawn_pos.y, spawn_pos.z),
+            Velocity::new(velocity.x, velocity.y, velocity.z),
+            Pitch::new(**pitch),
+            Yaw::new(**yaw),
+            Owner::new(event.from),
+            Arrow::from_bow(&bow, power),
+            EntityKind::Arrow,
+            Channel,
+        ));
+    }
+}
+
+fn arrow_entity_hit(
+    mut events: EventReader<'_, '_, event::ProjectileEntityEvent>,
+    arrow_query: Query<'_, '_, (&Velocity, &Owner, &Arrow)>,
+    mut player_query: Query<'_, '_, &mut ArrowsInEntity>,
+    mut commands: Commands<'_, '_>,
+    mut writer: EventWriter<'_, event::Cancellable<event::AttackEntity>>,
+) {
+    for event in events.read() {
+        // Other projectiles are handled elsewhere
+        let Ok((velocity, owner, arrow)) = arrow_query.get(event.projectile) else {
+            continue;
+        };
+
+        let mut arrows = match player_query.get_mut(event.client) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("arrow entity hit failed: player query failed: {e}");
+                continue;
+            }
+        };
+
+        let damage = arrow.roll_damage(velocity.0.length());
+
+        if damage == 0.0 && owner.entity == event.client {
+            continue;
+        }
+
+        arrows.0 += 1;
+
+        commands.entity(event.projectile).despawn();
+
+        writer.write(event::Cancellable::new(event::AttackEntity {
+            origin: owner.entity,
+            target: event.client,
+            direction: velocity.0.normalize(),
+            damage,
+            sound: ident!("entity.arrow.hit_player"),
+            particles: None,
+            extra_knockback: f32::from(arrow.punch) * 0.6,
+            fire_ticks: if arrow.flame { FLAME_TICKS } else { 0 },
+        }));
+    }
+}
+
+fn arrow_block_hit(
+    mut events: EventReader<'_, '_, event::ProjectileBlockEvent>,
+    mut query: Query<'_, '_, (&mut Position, &mut Velocity), With<Arrow>>,
+) {
+    for event in events.read() {
+        let Ok((mut position, mut velocity)) = query.get_mut(event.projectile) else {
+            continue;
+        };
+
+        velocity.0 = Vec3::ZERO;
+        **position = event.collision.point;
+    }
+}
+
+pub struct BowPlugin;
+
+impl Plugin for BowPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(initialize_player);
+        app.add_systems(
+            FixedUpdate,
+            (
+                (timed(handle_bow_use), timed(handle_bow_release)).chain(),
+                timed(arrow_entity_hit).before(event::CancelEvents),
+                timed(arrow_block_hit),
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bow_power() {
+        assert!(bow_power(0).abs() < f32::EPSILON);
+        assert!(bow_power(2) < MIN_POWER);
+        assert!(bow_power(3) >= MIN_POWER);
+        assert!((bow_power(10) - 1.25 / 3.0).abs() < 1e-6);
+        assert!((bow_power(20) - 1.0).abs() < f32::EPSILON);
+        assert!((bow_power(100) - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_arrow_damage() {
+        let arrow = Arrow::from_bow(&ItemStack::new(ItemKind::Bow, 1, None), 1.0);
+        assert!(arrow.critical);
+
+        // A fully drawn arrow without Power deals 6 damage before the critical bonus
+        assert!((arrow.damage_at(MAX_ARROW_SPEED) - 6.0).abs() < f32::EPSILON);
+
+        let damage = arrow.roll_damage(MAX_ARROW_SPEED);
+        assert!((6.0..=10.0).contains(&damage));
+
+        let arrow = Arrow::from_bow(&ItemStack::new(ItemKind::Bow, 1, None), 0.5);
+        assert!(!arrow.critical);
+        assert!((arrow.roll_damage(1.5) - 3.0).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,546 @@
+//! Bridges chat to a Discord channel.
+//!
+//! Chat messages, joins, leaves and deaths are posted to the channel through a webhook, or
+//! through a bot if no webhook is set. The bot polls the channel for new messages, which are
+//! pushed back into the world through the [`CommandChannel`] and broadcast to every player.
+//!
+//! Both run on the [`AsyncRuntime`] and are configured by the
+//! [`BridgeConfig`](hyperion::config::BridgeConfig) in `config.toml`.
+
+use std::time::Duration;
+
+use anyhow::{Context, bail};
+use bevy::prelude::*;
+use hyperion::{
+    command_channel::CommandChannel,
+    config::{BridgeConfig, Config},
+    net::{Compose, agnostic},
+    runtime::AsyncRuntime,
+    simulation::{
+        event::{ChatSent, PlayerDeath},
+        packet_state,
+        player_list::{Nickname, profile_name},
+    },
+    timings::timed,
+};
+use reqwest::{
+    StatusCode,
+    header::{AUTHORIZATION, CONTENT_TYPE},
+};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+const API_URL: &str = "https://discord.com/api/v10";
+
+/// The longest message relayed from Discord, in characters
+const MAX_RELAYED_LEN: usize = 256;
+
+/// The most messages waiting to be posted. Messages are dropped while the queue is full, such as
+/// while Discord is down, so that they do not pile up in memory.
+const QUEUE_CAPACITY: usize = 256;
+
+/// The longest post Discord accepts, in characters. Queued messages are combined into posts of up
+/// to this length.
+const MAX_POST_LEN: usize = 2000;
+
+/// A message posted to Discord
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Outgoing {
+    /// The player who sent the message, or `None` for notices such as joins
+    author: Option<String>,
+    content: String,
+}
+
+impl Outgoing {
+    /// The message as a line of a post without an author
+    fn line(&self) -> String {
+        match &self.author {
+            Some(author) => format!("**{}**: {}", escape_markdown(author), self.content),
+            None => self.content.clone(),
+        }
+    }
+}
+
+/// Combines `messages` into as few posts as possible. A single message is posted as it is, so a
+/// webhook can show it under the name of its author.
+fn combine(messages: Vec<Outgoing>) -> Vec<Outgoing> {
+    if messages.len() <= 1 {
+        return messages;
+    }
+
+    let mut posts = Vec::new();
+    let mut content = String::new();
+
+    for line in messages.iter().map(Outgoing::line) {
+        let len = content.chars().count() + 1 + line.chars().count();
+        if !content.is_empty() && len > MAX_POST_LEN {
+            posts.push(Outgoing {
+                author: None,
+                content: std::mem::take(&mut content),
+            });
+        }
+
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        content.push_str(&line);
+    }
+
+    posts.push(Outgoing {
+        author: None,
+        content,
+    });
+
+    posts
+}
+
+/// Where messages are posted to
+#[derive(Clone, Debug)]
+enum Destination {
+    Webhook(String),
+    Bot { token: String, channel_id: String },
+}
+
+impl Destination {
+    fn from_config(config: &BridgeConfig) -> Option<Self> {
+        if let Some(url) = &config.webhook_url {
+            return Some(Self::Webhook(url.clone()));
+        }
+
+        match (&config.bot_token, &config.channel_id) {
+            (Some(token), Some(channel_id)) => Some(Self::Bot {
+                token: token.clone(),
+                channel_id: channel_id.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Queues messages to be posted to Discord. This is only present if a webhook or bot is set.
+#[derive(Resource, Clone, Debug)]
+pub struct Bridge {
+    tx: mpsc::Sender<Outgoing>,
+}
+
+impl Bridge {
+    /// Posts a notice, such as an announcement, to the channel
+    pub fn notice(&self, content: impl Into<String>) {
+        self.send(Outgoing {
+            author: None,
+            content: content.into(),
+        });
+    }
+
+    /// Posts a chat message under the name of the player who sent it
+    pub fn chat(&self, author: impl Into<String>, content: impl Into<String>) {
+        self.send(Outgoing {
+            author: Some(author.into()),
+            content: content.into(),
+        });
+    }
+
+    fn send(&self, message: Outgoing) {
+        match self.tx.try_send(message) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                warn!("failed to post to discord: too many messages are waiting to be posted");
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                warn!("failed to post to discord: the bridge is not running");
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Author {
+    username: String,
+    global_name: Option<String>,
+    #[serde(default)]
+    bot: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct Message {
+    id: String,
+    content: String,
+    author: Author,
+    webhook_id: Option<String>,
+}
+
+impl Message {
+    fn snowflake(&self) -> u64 {
+        self.id.parse().unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct RateLimit {
+    retry_after: f64,
+}
+
+/// Escapes the characters which Discord uses for formatting
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Removes formatting codes and line breaks, which players could not type themselves
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .filter(|&c| c != '§')
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .take(MAX_RELAYED_LEN)
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+/// The chat line shown to players for a Discord message, or `None` if it is not relayed
+fn relayed_line(message: &Message) -> Option<String> {
+    if message.author.bot || message.webhook_id.is_some() {
+        return None;
+    }
+
+    let content = sanitize(&message.content);
+    if content.is_empty() {
+        return None;
+    }
+
+    let author = message
+        .author
+        .global_name
+        .as_deref()
+        .unwrap_or(&message.author.username);
+
+    Some(format!("§9[Discord] §7{}§8: §f{content}", sanitize(author)))
+}
+
+/// Posts `message`, returning how long to wait before retrying if Discord is rate limiting
+async fn post(
+    client: &reqwest::Client,
+    destination: &Destination,
+    message: &Outgoing,
+) -> anyhow::Result<Option<Duration>> {
+    let no_mentions = serde_json::json!({ "parse": [] });
+
+    let request = match destination {
+        Destination::Webhook(url) => {
+            let mut body = serde_json::json!({
+                "content": message.content,
+                "allowed_mentions": no_mentions,
+            });
+            if let Some(author) = &message.author {
+                body["username"] = author.as_str().into();
+            }
+            client.post(url).body(body.to_string())
+        }
+        Destination::Bot { token, channel_id } => {
+            let body = serde_json::json!({
+                "content": message.line(),
+                "allowed_mentions": no_mentions,
+            });
+            client
+                .post(format!("{API_URL}/channels/{channel_id}/messages"))
+                .header(AUTHORIZATION, format!("Bot {token}"))
+                .body(body.to_string())
+        }
+    };
+
+    let response = request
+        .header(CONTENT_TYPE, "application/json")
+        .send()
+        .await?;
+    let status = response.status();
+    let body = response.text().await?;
+
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let limit = serde_json::from_str::<RateLimit>(&body)
+            .with_context(|| format!("failed to parse rate limit: {body:?}"))?;
+        return Ok(Some(Duration::from_secs_f64(limit.retry_after)));
+    }
+
+    if !status.is_success() {
+        bail!("discord returned {status}: {body}");
+    }
+
+    Ok(None)
+}
+
+/// Posts queued messages in order until the [`Bridge`] is dropped. Messages which are queued
+/// while a post is sent are combined into the next post.
+async fn forward(
+    client: reqwest::Client,
+    destination: Destination,
+    mut rx: mpsc::Receiver<Outgoing>,
+) {
+    while let Some(message) = rx.recv().await {
+        let mut messages = vec![message];
+        while let Ok(message) = rx.try_recv() {
+            messages.push(message);
+        }
+
+        for message in combine(messages) {
+            loop {
+                match post(&client, &destination, &message).await {
+                    Ok(None) => break,
+                    Ok(Some(retry_after)) => tokio::time::sleep(retry_after).await,
+                    Err(e) => {
+                        error!("failed to post to discord: {e}");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The messages sent in the channel after `after`, or the latest message if `after` is `None`
+async fn fetch(
+    client: &reqwest::Client,
+    token: &str,
+    channel_id: &str,
+    after: Option<u64>,
+) -> anyhow::Result<Vec<Message>> {
+    let query = match after {
+        Some(after) => format!("limit=50&after={after}"),
+        None => "limit=1".to_owned(),
+    };
+
+    let response = client
+        .get(format!("{API_URL}/channels/{channel_id}/messages?{query}"))
+        .header(AUTHORIZATION, format!("Bot {token}"))
+        .send()
+        .await?;
+    let status = response.status();
+    let body = response.text().await?;
+
+    if !status.is_success() {
+        bail!("discord returned {status}: {body}");
+    }
+
+    serde_json::from_str(&body).with_context(|| format!("failed to parse messages: {body:?}"))
+}
+
+/// Polls the channel and broadcasts new messages to every player
+async fn relay(
+    client: reqwest::Client,
+    token: String,
+    channel_id: String,
+    poll: Duration,
+    command_channel: CommandChannel,
+) {
+    let mut interval = tokio::time::interval(poll);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    // Messages sent before the server started are skipped
+    let mut last = None;
+
+    loop {
+        interval.tick().await;
+
+        let mut messages = match fetch(&client, &token, &channel_id, last).await {
+            Ok(messages) => messages,
+            Err(e) => {
+                warn!("failed to read discord messages: {e}");
+                continue;
+            }
+        };
+
+        messages.sort_unstable_by_key(Message::snowflake);
+
+        let Some(after) = last else {
+            last = Some(messages.last().map_or(0, Message::snowflake));
+            continue;
+        };
+
+        last = Some(messages.last().map_or(after, Message::snowflake));
+
+        for line in messages.iter().filter_map(relayed_line) {
+            command_channel.push(move |world: &mut World| {
+                let compose = world.resource::<Compose>();
+                if let Err(e) = compose.broadcast(&agnostic::chat(line)).send() {
+                    error!("failed to relay discord message: {e}");
+                }
+            });
+        }
+    }
+}
+
+fn start_bridge(
+    config: Res<'_, Config>,
+    runtime: Res<'_, AsyncRuntime>,
+    command_channel: Res<'_, CommandChannel>,
+    mut commands: Commands<'_, '_>,
+) {
+    let config = &config.bridge;
+    let client = reqwest::Client::new();
+
+    let Some(destination) = Destination::from_config(config) else {
+        info!("no discord webhook or bot is set, so chat is not bridged");
+        return;
+    };
+
+    let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+    runtime.spawn(forward(client.clone(), destination, rx));
+    commands.insert_resource(Bridge { tx });
+
+    if let (Some(token), Some(channel_id)) = (&config.bot_token, &config.channel_id) {
+        runtime.spawn(relay(
+            client,
+            token.clone(),
+            channel_id.clone(),
+            Duration::from_secs(config.poll_secs.max(1)),
+            command_channel.clone(),
+        ));
+    }
+}
+
+fn forward_chat(
+    mut events: EventReader<'_, '_, ChatSent>,
+    query: Query<'_, '_, (&Name, Option<&Nickname>)>,
+    bridge: Res<'_, Bridge>,
+) {
+    for event in events.read() {
+        let Ok((name, nickname)) = query.get(event.sender) else {
+            continue;
+        };
+
+        bridge.chat(profile_name(name, nickname), event.message.as_str());
+    }
+}
+
+fn forward_join(
+    trigger: Trigger<'_, OnAdd, packet_state::Play>,
+    query: Query<'_, '_, (&Name, Option<&Nickname>)>,
+    bridge: Option<Res<'_, Bridge>>,
+) {
+    let (Some(bridge), Ok((name, nickname))) = (bridge, query.get(trigger.target())) else {
+        return;
+    };
+
+    let name = escape_markdown(profile_name(name, nickname));
+    bridge.notice(format!("**{name}** joined the server"));
+}
+
+fn forward_leave(
+    trigger: Trigger<'_, OnRemove, packet_state::Play>,
+    query: Query<'_, '_, (&Name, Option<&Nickname>)>,
+    bridge: Option<Res<'_, Bridge>>,
+) {
+    let (Some(bridge), Ok((name, nickname))) = (bridge, query.get(trigger.target())) else {
+        return;
+    };
+
+    let name = escape_markdown(profile_name(name, nickname));
+    bridge.notice(format!("**{name}** left the server"));
+}
+
+fn forward_deaths(
+    mut events: EventReader<'_, '_, PlayerDeath>,
+    query: Query<'_, '_, (&Name, Option<&Nickname>)>,
+    bridge: Res<'_, Bridge>,
+) {
+    let display_name = |entity| {
+        query
+            .get(entity)
+            .ok()
+            .map(|(name, nickname)| escape_markdown(profile_name(name, nickname)))
+    };
+
+    for event in events.read() {
+        let Some(victim) = display_name(event.victim) else {
+            continue;
+        };
+
+        match event.killer.and_then(display_name) {
+            Some(killer) => bridge.notice(format!("**{victim}** was slain by **{killer}**")),
+            None => bridge.notice(format!("**{victim}** died")),
+        }
+    }
+}
+
+/// Bridges chat to the Discord channel set in the `bridge` section of `config.toml`
+pub struct BridgePlugin;
+
+impl Plugin for BridgePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(forward_join);
+        app.add_observer(forward_leave);
+        app.add_systems(Startup, start_bridge);
+        app.add_systems(
+            FixedUpdate,
+            (timed(forward_chat), timed(forward_deaths)).run_if(resource_exists::<Bridge>),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(content: &str, bot: bool) -> Message {
+        Message {
+            id: "1".to_owned(),
+            content: content.to_owned(),
+            author: Author {
+                username: "steve".to_owned(),
+                global_name: Some("Steve".to_owned()),
+                bot,
+            },
+            webhook_id: None,
+        }
+    }
+
+    #[test]
+    fn test_escape_markdown() {
+        assert_eq!(escape_markdown("Some_Player"), "Some\\_Player");
+        assert_eq!(escape_markdown("**bold**"), "\\*\\*bold\\*\\*");
+    }
+
+    #[test]
+    fn test_combine() {
+        let chat = Outgoing {
+            author: Some("Some_Player".to_owned()),
+            content: "hi".to_owned(),
+        };
+        let notice = Outgoing {
+            author: None,
+            content: "**Steve** joined the server".to_owned(),
+        };
+
+        assert_eq!(combine(vec![chat.clone()]), vec![chat.clone()]);
+        assert_eq!(combine(vec![chat.clone(), notice.clone()]), vec![
+            Outgoing {
+                author: None,
+                content: "**Some\\_Player**: hi\n**Steve** joined the server".to_owned(),
+            }
+        ]);
+
+        let long = Outgoing {
+            author: None,
+            content: "a".repeat(1500),
+        };
+        assert_eq!(combine(vec![long.clone(), long.clone(), notice]).len(), 2);
+    }
+
+    #[test]
+    fn test_relayed_line() {
+        assert_eq!(
+            relayed_line(&message("§khello\nworld", false)).as_deref(),
+            Some("§9[Discord] §7Steve§8: §fkhello world")
+        );
+        assert_eq!(relayed_line(&message("hello", true)), None);
+        assert_eq!(relayed_line(&message("  ", false)), None);
+    }
+}
@@ -8,7 +8,7 @@ use hyperion::{
 };
 pub use hyperion_clap_macros::CommandPermission;
 pub use hyperion_command;
-use hyperion_command::{CommandHandler, CommandRegistry, ExecutableCommand};
+use hyperion_command::{CommandHandler, CommandOutcome, CommandRegistry, ExecutableCommand};
 use hyperion_permission::Group;
 use hyperion_utils::ApplyWorld;
 use tracing::error;
@@ -26,7 +26,7 @@ struct GenericExecutableCommand<Command: MinecraftCommand> {
 }
 
 impl<Command: MinecraftCommand> ExecutableCommand for GenericExecutableCommand<Command> {
-    fn execute(&mut self, world: &World, packet: &play::CommandExecution) {
+    fn execute(&mut self, world: &World, packet: &play::CommandExecution) -> CommandOutcome {
         let compose = world.resource::<Compose>();
         let input = packet.command.split_whitespace();
 
@@ -34,17 +34,24 @@ impl<Command: MinecraftCommand> ExecutableCommand for GenericExecutableCommand<C
             Ok(elem) => {
                 let Some(group) = world.entity(packet.sender()).get::<Group>() else {
                     error!("failed to execute command: player is missing Group component");
-                    return;
+                    return CommandOutcome::PermissionDenied;
                 };
 
                 if Command::has_required_permission(*group) {
-                    elem.execute(world, &mut self.state, packet.sender());
+                    match elem.execute(world, &mut self.state, packet.sender()) {
+                        Ok(()) => CommandOutcome::Executed,
+                        Err(CommandFailed) => CommandOutcome::Failed,
+                    }
                 } else {
                     let chat = agnostic::chat("§cYou do not have permission to use this command!");
 
                     let mut bundle = DataBundle::new(compose);
                     bundle.add_packet(&chat).unwrap();
-                    bundle.unicast(packet.connection_id()).unwrap();
+                    if let Err(e) = bundle.unicast(packet.connection_id()) {
+                        error!("failed to send command usage: {e}");
+                    }
+
+                    CommandOutcome::PermissionDenied
                 }
             }
             Err(e) => {
@@ -58,9 +65,13 @@ impl<Command: MinecraftCommand> ExecutableCommand for GenericExecutableCommand<C
                 let msg = format!("{prefix}{e}");
 
                 let msg = agnostic::chat(msg);
-                compose.unicast(&msg, packet.connection_id()).unwrap();
+                if let Err(e) = compose.unicast(&msg, packet.connection_id()) {
+                    error!("failed to send command error: {e}");
+                }
 
                 tracing::warn!("could not parse command {e}");
+
+                CommandOutcome::InvalidArguments
             }
         }
     }
@@ -72,6 +83,15 @@ impl<Command: MinecraftCommand> ApplyWorld for GenericExecutableCommand<Command>
     }
 }
 
+/// Returned by [`MinecraftCommand::execute`] when a command did not do what was asked, such as
+/// when a player it names is not online. The command tells the player why before returning it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CommandFailed;
+
+/// The result of [`MinecraftCommand::execute`], which is recorded in
+/// [`CommandExecuted`](hyperion_command::CommandExecuted)
+pub type CommandResult = Result<(), CommandFailed>;
+
 pub trait MinecraftCommand: Parser + CommandPermission + 'static {
     /// Command state passed to [`MinecraftCommand::execute`]. This can be any type, but it may be
     /// useful to store a [`bevy::ecs::system::SystemState`] to access types implementing
@@ -79,7 +99,9 @@ pub trait MinecraftCommand: Parser + CommandPermission + 'static {
     /// trait, and it is applied with the [`ApplyWorld`] trait once every `FixedMain`.
     type State: FromWorld + ApplyWorld + Send + Sync + 'static;
 
-    fn execute(self, world: &World, state: &mut Self::State, caller: Entity);
+    /// Runs the command sent by `caller`. Returns [`CommandFailed`] if it did not do what was
+    /// asked.
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult;
 
     fn pre_register(_world: &World) {}
 
@@ -185,9 +207,9 @@ pub trait MinecraftCommand: Parser + CommandPermission + 'static {
                     matches,
                 };
 
-                compose
-                    .unicast(&packet, completion.connection_id())
-                    .unwrap();
+                if let Err(e) = compose.unicast(&packet, completion.connection_id()) {
+                    error!("failed to send command suggestions: {e}");
+                }
 
                 // todo: send possible matches to player
                 return;
@@ -222,9 +244,9 @@ pub trait MinecraftCommand: Parser + CommandPermission + 'static {
                 matches,
             };
 
-            compose
-                .unicast(&packet, completion.connection_id())
-                .unwrap();
+            if let Err(e) = compose.unicast(&packet, completion.connection_id()) {
+                error!("failed to send command suggestions: {e}");
+            }
         };
 
         let handler = CommandHandler {
@@ -296,13 +318,13 @@ pub enum PermissionCommand {
 impl MinecraftCommand for PermissionCommand {
     type State = SystemState<Commands<'static, 'static>>;
 
-    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) {
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
         let mut commands = state.get(world);
         let compose = world.resource::<Compose>();
         let ign_map = world.resource::<IgnMap>();
         let Some(&connection_id) = world.entity(caller).get::<ConnectionId>() else {
             error!("permission command failed: caller is missing ConnectionId component");
-            return;
+            return Err(CommandFailed);
         };
         match self {
             Self::Set(cmd) => {
@@ -310,8 +332,10 @@ impl MinecraftCommand for PermissionCommand {
                 let Some(&entity) = ign_map.get(cmd.player.as_str()) else {
                     let msg = format!("§c{} not found", cmd.player);
                     let chat = hyperion::net::agnostic::chat(msg);
-                    compose.unicast(&chat, connection_id).unwrap();
-                    return;
+                    if let Err(e) = compose.unicast(&chat, connection_id) {
+                        error!("failed to send command error: {e}");
+                    }
+                    return Err(CommandFailed);
                 };
 
                 commands.entity(entity).insert(cmd.group);
@@ -321,26 +345,34 @@ impl MinecraftCommand for PermissionCommand {
                     cmd.player, cmd.group
                 );
                 let chat = hyperion::net::agnostic::chat(msg);
-                compose.unicast(&chat, connection_id).unwrap();
+                if let Err(e) = compose.unicast(&chat, connection_id) {
+                    error!("failed to send command error: {e}");
+                }
             }
             Self::Get(cmd) => {
                 let Some(&entity) = ign_map.get(cmd.player.as_str()) else {
                     let msg = format!("§c{} not found", cmd.player);
                     let chat = hyperion::net::agnostic::chat(msg);
-                    compose.unicast(&chat, connection_id).unwrap();
-                    return;
+                    if let Err(e) = compose.unicast(&chat, connection_id) {
+                        error!("failed to send command error: {e}");
+                    }
+                    return Err(CommandFailed);
                 };
 
                 let Some(group) = world.entity(entity).get::<Group>() else {
                     error!("permission command failed: player is missing Group component");
-                    return;
+                    return Err(CommandFailed);
                 };
 
                 let msg = format!("§b{}§r's group is §e{:?}", cmd.player, group);
                 let chat = hyperion::net::agnostic::chat(msg);
-                compose.unicast(&chat, connection_id).unwrap();
+                if let Err(e) = compose.unicast(&chat, connection_id) {
+                    error!("failed to send command error: {e}");
+                }
             }
         }
+
+        Ok(())
     }
 }
 
@@ -0,0 +1,315 @@
+//! Tags players who are in a fight with another player.
+//!
+//! Attacking a player or being attacked by one adds a [`CombatTag`] which lasts for the
+//! configured duration after the last hit. Tagged players who log out are killed, and tagged
+//! players cannot enter regions which deny [`Flag::Pvp`], so fights cannot be escaped by leaving
+//! or by running into a safe zone.
+//!
+//! A player who logs out is gone before they can be killed, so the kill is saved in the
+//! [`LocalDb`] and carried out when they join again: their inventory is cleared and they are shown
+//! the death screen.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use hyperion::{
+    glam::Vec3,
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{
+        MovementTracking, PendingTeleportation, Position, Uuid,
+        event::{AttackEntity, CancelEvents, Cancellable, PlayerDeath},
+        metadata::living_entity::Health,
+        packet_state,
+    },
+    storage::{Bucket, LocalDb},
+    tick_rate::TickRate,
+    timings::timed,
+    valence_protocol::{VarInt, packets::play, text::IntoText},
+};
+use hyperion_inventory::PlayerInventory;
+use hyperion_protect::{Flag, Regions};
+use hyperion_utils::EntityExt;
+use tracing::error;
+
+/// Marks a player who has recently attacked or been attacked by another player
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CombatTag {
+    /// The tick when the tag expires
+    pub until: i64,
+    /// The last player this player fought, who is credited with the kill if they log out
+    pub opponent: Entity,
+}
+
+/// How long a [`CombatTag`] lasts after the last hit. It is converted to ticks at the current
+/// [`TickRate`] when a player is tagged.
+#[derive(Resource, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CombatTagDuration(pub Duration);
+
+/// The players who logged out while tagged and have not been killed for it yet, keyed by UUID.
+/// The value is the name of the player they were fighting.
+#[derive(Resource, Clone)]
+pub struct CombatLoggers(Bucket<String>);
+
+/// Whether moving from `previous` to `current` enters a region which denies PvP
+fn enters_safe_zone(regions: &Regions, previous: Vec3, current: Vec3) -> bool {
+    regions.is_allowed_at_position(previous, Flag::Pvp)
+        && !regions.is_allowed_at_position(current, Flag::Pvp)
+}
+
+fn tag_attacks(
+    mut events: EventReader<'_, '_, Cancellable<AttackEntity>>,
+    players: Query<'_, '_, (&ConnectionId, Option<&CombatTag>)>,
+    duration: Res<'_, CombatTagDuration>,
+    tick_rate: Res<'_, TickRate>,
+    compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
+) {
+    let until = compose.global().tick + tick_rate.ticks_in(duration.0);
+
+    for event in events.read() {
+        if event.is_cancelled() {
+            continue;
+        }
+
+        // Only fights between players are tagged
+        if !players.contains(event.origin) || !players.contains(event.target) {
+            continue;
+        }
+
+        for (player, opponent) in [(event.origin, event.target), (event.target, event.origin)] {
+            let Ok((&connection_id, tag)) = players.get(player) else {
+                continue;
+            };
+
+            if tag.is_none() {
+                if let Err(e) = compose.unicast(
+                    &agnostic::chat("§cYou are in combat, do not log out"),
+                    connection_id,
+                ) {
+                    error!("failed to send combat tag message: {e}");
+                }
+            }
+
+            commands
+                .entity(player)
+                .insert(CombatTag { until, opponent });
+        }
+    }
+}
+
+fn expire_tags(
+    query: Query<'_, '_, (Entity, &ConnectionId, &CombatTag)>,
+    compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
+) {
+    let tick = compose.global().tick;
+
+    for (player, &connection_id, tag) in &query {
+        if tag.until > tick {
+            continue;
+        }
+
+        commands.entity(player).remove::<CombatTag>();
+        if let Err(e) = compose.unicast(
+            &agnostic::chat("§aYou are no longer in combat"),
+            connection_id,
+        ) {
+            error!("failed to send combat tag message: {e}");
+        }
+    }
+}
+
+fn untag_dead(mut events: EventReader<'_, '_, PlayerDeath>, mut commands: Commands<'_, '_>) {
+    for event in events.read() {
+        if let Ok(mut entity) = commands.get_entity(event.victim) {
+            entity.remove::<CombatTag>();
+        }
+    }
+}
+
+/// Moves tagged players back if they walk into a region which denies PvP
+fn block_safe_zones(
+    regions: Res<'_, Regions>,
+    mut query: Query<
+        '_,
+        '_,
+        (Entity, &mut Position, &MovementTracking, &ConnectionId),
+        (
+            With<CombatTag>,
+            Changed<Position>,
+            Without<PendingTeleportation>,
+        ),
+    >,
+    compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
+) {
+    for (player, mut position, tracking, &connection_id) in &mut query {
+        let previous = tracking.last_tick_position;
+
+        if !enters_safe_zone(&regions, previous, **position) {
+            continue;
+        }
+
+        **position = previous;
+        commands
+            .entity(player)
+            .insert(PendingTeleportation::new(previous));
+
+        let msg = agnostic::chat("§cYou cannot enter a safe zone while in combat");
+        if let Err(e) = compose.unicast(&msg, connection_id) {
+            error!("failed to send safe zone message: {e}");
+        }
+    }
+}
+
+/// Kills tagged players who log out. The entity of the player is about to be despawned, so the
+/// death itself is carried out by [`punish_combat_loggers`] when the player joins again.
+fn kill_combat_loggers(
+    trigger: Trigger<'_, OnRemove, ConnectionId>,
+    query: Query<'_, '_, (&Name, &Uuid, &CombatTag)>,
+    names: Query<'_, '_, &Name>,
+    loggers: Res<'_, CombatLoggers>,
+    compose: Res<'_, Compose>,
+) {
+    let player = trigger.target();
+    let Ok((name, uuid, tag)) = query.get(player) else {
+        return;
+    };
+
+    let opponent = names
+        .get(tag.opponent)
+        .map_or_else(|_| "another player".to_owned(), ToString::to_string);
+
+    if let Err(e) = loggers.0.insert(&uuid.0.to_string(), &opponent) {
+        error!("failed to save the combat log of {name}: {e}");
+    }
+
+    if let Err(e) = compose
+        .broadcast(&agnostic::chat(format!(
+            "§c{name} logged out during combat and was killed"
+        )))
+        .send()
+    {
+        error!("failed to send combat log message: {e}");
+    }
+}
+
+/// Kills players who logged out during combat when they join again
+fn punish_combat_loggers(
+    mut query: Query<
+        '_,
+        '_,
+        (
+            Entity,
+            &Uuid,
+            &ConnectionId,
+            &mut PlayerInventory,
+            Option<&mut Health>,
+        ),
+        Added<packet_state::Play>,
+    >,
+    loggers: Res<'_, CombatLoggers>,
+    compose: Res<'_, Compose>,
+    mut deaths: EventWriter<'_, PlayerDeath>,
+) {
+    for (player, uuid, &connection_id, mut inventory, health) in &mut query {
+        let key = uuid.0.to_string();
+        let opponent = match loggers.0.get(&key) {
+            Ok(Some(opponent)) => opponent,
+            Ok(None) => continue,
+            Err(e) => {
+                error!("failed to read combat log: {e}");
+                continue;
+            }
+        };
+
+        if let Err(e) = loggers.0.remove(&key) {
+            error!("failed to remove combat log: {e}");
+            continue;
+        }
+
+        inventory.clear();
+
+        if let Some(mut health) = health {
+            health.damage(f32::MAX);
+        }
+
+        // The opponent may have left or died since, so nobody is credited with the kill
+        deaths.write(PlayerDeath {
+            victim: player,
+            killer: None,
+        });
+
+        let pkt_death_screen = play::DeathMessageS2c {
+            player_id: VarInt(player.minecraft_id()),
+            message: format!("You logged out while fighting {opponent}").into_cow_text(),
+        };
+        if let Err(e) = compose.unicast(&pkt_death_screen, connection_id) {
+            error!("failed to send combat logger death screen: {e}");
+        }
+    }
+}
+
+/// Tags players in fights with other players for `duration` after the last hit
+pub struct CombatTagPlugin {
+    pub duration: Duration,
+}
+
+impl Default for CombatTagPlugin {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(15),
+        }
+    }
+}
+
+impl Plugin for CombatTagPlugin {
+    fn build(&self, app: &mut App) {
+        let loggers = app
+            .world()
+            .resource::<LocalDb>()
+            .bucket("hyperion-combat-tag")
+            .expect("failed to open combat loggers");
+
+        app.insert_resource(CombatTagDuration(self.duration));
+        app.insert_resource(CombatLoggers(loggers));
+        app.add_observer(kill_combat_loggers);
+        app.add_systems(
+            FixedUpdate,
+            (
+                timed(tag_attacks).after(CancelEvents),
+                timed(expire_tags),
+                timed(untag_dead),
+                timed(punish_combat_loggers),
+                // Player movement is handled before CancelEvents
+                timed(block_safe_zones).after(CancelEvents),
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion::glam::IVec3;
+    use hyperion_protect::Region;
+
+    use super::*;
+
+    #[test]
+    fn test_enters_safe_zone() {
+        let mut spawn = Region::new(IVec3::new(0, 0, 0), IVec3::new(10, 10, 10));
+        spawn.flags.set(Flag::Pvp, false);
+
+        let mut regions = Regions::default();
+        regions.insert("spawn", spawn);
+
+        let outside = Vec3::new(-0.5, 5.0, 5.0);
+        let inside = Vec3::new(0.5, 5.0, 5.0);
+
+        assert!(enters_safe_zone(&regions, outside, inside));
+        // Players may leave a safe zone and move around in one
+        assert!(!enters_safe_zone(&regions, inside, outside));
+        assert!(!enters_safe_zone(&regions, inside, inside));
+        assert!(!enters_safe_zone(&regions, outside, outside));
+    }
+}
@@ -5,10 +5,32 @@ use derive_more::{Deref, DerefMut};
 use hyperion::simulation::packet::play;
 use hyperion_utils::ApplyWorld;
 use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+/// Whether a command sent by a player was run
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CommandOutcome {
+    Executed,
+    /// The command was run but did not do what was asked, such as when a player it names is not
+    /// online
+    Failed,
+    UnknownCommand,
+    InvalidArguments,
+    PermissionDenied,
+}
+
+/// Sent after a player sends a command, whether or not it was run
+#[derive(Event, Clone, Debug)]
+pub struct CommandExecuted {
+    pub sender: Entity,
+    /// The command without the leading slash, including its arguments
+    pub command: String,
+    pub outcome: CommandOutcome,
+}
 
 pub trait ExecutableCommand: ApplyWorld {
     /// Executes a command triggered by a player
-    fn execute(&mut self, world: &World, execution: &play::CommandExecution);
+    fn execute(&mut self, world: &World, execution: &play::CommandExecution) -> CommandOutcome;
 }
 
 pub struct CommandHandler {
@@ -19,6 +41,8 @@ pub struct CommandHandler {
 
 pub struct CommandRegistryInner {
     pub(crate) commands: IndexMap<String, CommandHandler>,
+    /// Commands executed since [`CommandExecuted`] events were last sent
+    pub(crate) executed: Vec<CommandExecuted>,
 }
 
 impl CommandRegistryInner {
@@ -60,6 +84,8 @@ impl Plugin for CommandComponentPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(CommandRegistry(Mutex::new(CommandRegistryInner {
             commands: IndexMap::default(),
+            executed: Vec::new(),
         })));
+        app.add_event::<CommandExecuted>();
     }
 }
@@ -5,7 +5,9 @@ use bevy::prelude::*;
 mod component;
 mod system;
 
-pub use component::{CommandHandler, CommandRegistry, ExecutableCommand};
+pub use component::{
+    CommandExecuted, CommandHandler, CommandOutcome, CommandRegistry, ExecutableCommand,
+};
 
 pub struct CommandPlugin;
 
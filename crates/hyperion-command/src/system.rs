@@ -5,10 +5,11 @@ use hyperion::{
     ingress,
     net::{Compose, agnostic},
     simulation::packet::play,
+    timings::timed,
 };
-use tracing::{debug, warn};
+use tracing::{debug, error, warn};
 
-use crate::component::CommandRegistry;
+use crate::component::{CommandExecuted, CommandOutcome, CommandRegistry};
 
 /// Executes commands sent by the client.
 ///
@@ -65,23 +66,41 @@ fn execute_commands(
 
             let chat = agnostic::chat(msg);
 
-            compose.unicast(&chat, packet.connection_id()).unwrap();
+            if let Err(e) = compose.unicast(&chat, packet.connection_id()) {
+                error!("failed to send command error: {e}");
+            }
+
+            registry.executed.push(CommandExecuted {
+                sender: packet.sender(),
+                command: packet.command.to_string(),
+                outcome: CommandOutcome::UnknownCommand,
+            });
 
             continue;
         };
 
         debug!("executing command {first_word}");
 
-        command.executable.execute(world, packet);
+        let outcome = command.executable.execute(world, packet);
+
+        registry.executed.push(CommandExecuted {
+            sender: packet.sender(),
+            command: packet.command.to_string(),
+            outcome,
+        });
     }
 }
 
 fn apply_deferred_changes(world: &mut World) {
     let mut registry = world.resource_mut::<CommandRegistry>();
+    let registry = registry.get_mut().unwrap();
 
     // TODO: There should be some sort of error if the apply callback tries to access the
     // CommandRegistry
-    let mut commands = std::mem::take(&mut registry.get_mut().unwrap().commands);
+    let mut commands = std::mem::take(&mut registry.commands);
+    let executed = std::mem::take(&mut registry.executed);
+
+    world.send_event_batch(executed);
 
     for (_, command) in &mut commands {
         command.executable.apply(world);
@@ -149,7 +168,11 @@ impl Plugin for CommandSystemPlugin {
         // but they avoid lock contention on the CommandRegistry.
         app.add_systems(
             FixedUpdate,
-            (execute_commands, apply_deferred_changes, complete_commands)
+            (
+                timed(execute_commands),
+                timed(apply_deferred_changes),
+                timed(complete_commands),
+            )
                 .chain()
                 .after(ingress::decode::play),
         );
@@ -0,0 +1,93 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::net::{Compose, ConnectionId, DataBundle, agnostic};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+use crate::{CosmeticSlot, Cosmetics, CosmeticsExt, EquippedCosmetics};
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "cosmetic")]
+#[command_permission(group = "Normal")]
+pub enum CosmeticCommand {
+    /// Lists the cosmetics and which ones you have equipped
+    List,
+    /// Equips a cosmetic, replacing the one in the same slot
+    Equip { id: String },
+    /// Unequips the cosmetic in a slot
+    Unequip { slot: CosmeticSlot },
+}
+
+impl MinecraftCommand for CosmeticCommand {
+    type State = SystemState<(
+        Query<'static, 'static, (&'static ConnectionId, &'static EquippedCosmetics)>,
+        Res<'static, Cosmetics>,
+        Res<'static, Compose>,
+        Commands<'static, 'static>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (query, cosmetics, compose, mut commands) = state.get(world);
+
+        let (&connection_id, equipped) = match query.get(caller) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("cosmetic command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let mut result = Ok(());
+        let lines = match self {
+            Self::List => {
+                let mut lines = vec!["§6Cosmetics:".to_owned()];
+                lines.extend(cosmetics.sorted().into_iter().map(|(id, cosmetic)| {
+                    let slot = cosmetic.effect.slot();
+                    let marker = if equipped.get(slot) == Some(id) {
+                        " §a(equipped)"
+                    } else {
+                        ""
+                    };
+
+                    format!("§7- §e{id}§7: {} ({}){marker}", cosmetic.name, slot.name())
+                }));
+
+                if lines.len() == 1 {
+                    lines.push("§7No cosmetics are available".to_owned());
+                }
+
+                lines
+            }
+            Self::Equip { id } => match cosmetics.get(&id) {
+                Some(cosmetic) => {
+                    let msg = format!("§aEquipped {}", cosmetic.name);
+                    commands.entity(caller).equip_cosmetic(id);
+                    vec![msg]
+                }
+                None => {
+                    result = Err(CommandFailed);
+                    vec![format!("§cNo cosmetic is named {id}")]
+                }
+            },
+            Self::Unequip { slot } => {
+                if equipped.get(slot).is_some() {
+                    commands.entity(caller).unequip_cosmetic(slot);
+                    vec![format!("§aUnequipped your {}", slot.name())]
+                } else {
+                    result = Err(CommandFailed);
+                    vec![format!("§cYou have no {} equipped", slot.name())]
+                }
+            }
+        };
+
+        let mut bundle = DataBundle::new(&compose);
+        for line in lines {
+            bundle.add_packet(&agnostic::chat(line)).unwrap();
+        }
+        if let Err(e) = bundle.unicast(connection_id) {
+            error!("failed to send cosmetics list: {e}");
+        }
+
+        result
+    }
+}
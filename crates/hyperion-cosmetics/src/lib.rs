@@ -0,0 +1,448 @@
+//! Cosmetics which players can equip: particle trails, kill effects and chat prefixes.
+//!
+//! Cosmetics are defined in the [`Cosmetics`] resource. Each player has one slot per
+//! [`CosmeticSlot`], and their [`EquippedCosmetics`] are saved in the [`LocalDb`] so they are kept
+//! across restarts.
+//!
+//! ```ignore
+//! cosmetics.define("flame_trail", Cosmetic::new("Flame Trail", CosmeticEffect::Trail {
+//!     particle: Particle::Flame,
+//!     count: 2,
+//! }));
+//! cosmetics.define("vip", Cosmetic::new("VIP", CosmeticEffect::ChatPrefix("§6[VIP] ".to_owned())));
+//!
+//! commands.entity(player).equip_cosmetic("flame_trail");
+//! ```
+
+mod command;
+
+use std::collections::{BTreeMap, HashMap};
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use clap::ValueEnum;
+use hyperion::{
+    Particle,
+    glam::Vec3,
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{ChatPrefix, Position, Uuid, event::PlayerDeath},
+    storage::{Bucket, LocalDb},
+    timings::timed,
+};
+use hyperion_clap::MinecraftCommand;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::command::CosmeticCommand;
+
+/// The slots which cosmetics are equipped in. A player has at most one cosmetic in each slot.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    ValueEnum
+)]
+#[serde(rename_all = "snake_case")]
+pub enum CosmeticSlot {
+    Trail,
+    KillEffect,
+    ChatPrefix,
+}
+
+impl CosmeticSlot {
+    /// The name shown to players
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Trail => "trail",
+            Self::KillEffect => "kill effect",
+            Self::ChatPrefix => "chat prefix",
+        }
+    }
+}
+
+/// What a cosmetic does while it is equipped
+#[derive(Clone, Debug)]
+pub enum CosmeticEffect {
+    /// Particles shown at the feet of the player while they move
+    Trail { particle: Particle, count: i32 },
+    /// Particles shown where a player killed by the wearer died
+    KillEffect { particle: Particle, count: i32 },
+    /// Text shown before the name of the player in chat
+    ChatPrefix(String),
+}
+
+impl CosmeticEffect {
+    #[must_use]
+    pub const fn slot(&self) -> CosmeticSlot {
+        match self {
+            Self::Trail { .. } => CosmeticSlot::Trail,
+            Self::KillEffect { .. } => CosmeticSlot::KillEffect,
+            Self::ChatPrefix(_) => CosmeticSlot::ChatPrefix,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Cosmetic {
+    /// The name shown to players
+    pub name: String,
+    pub effect: CosmeticEffect,
+}
+
+impl Cosmetic {
+    #[must_use]
+    pub fn new(name: impl Into<String>, effect: CosmeticEffect) -> Self {
+        Self {
+            name: name.into(),
+            effect,
+        }
+    }
+}
+
+/// Every cosmetic which players can equip, keyed by id
+#[derive(Resource, Default, Debug)]
+pub struct Cosmetics {
+    cosmetics: HashMap<String, Cosmetic>,
+}
+
+impl Cosmetics {
+    /// Defines a cosmetic, replacing any cosmetic with the same id
+    pub fn define(&mut self, id: impl Into<String>, cosmetic: Cosmetic) {
+        self.cosmetics.insert(id.into(), cosmetic);
+    }
+
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&Cosmetic> {
+        self.cosmetics.get(id)
+    }
+
+    /// The cosmetics sorted by id
+    #[must_use]
+    pub fn sorted(&self) -> Vec<(&str, &Cosmetic)> {
+        let mut cosmetics = self
+            .cosmetics
+            .iter()
+            .map(|(id, cosmetic)| (id.as_str(), cosmetic))
+            .collect::<Vec<_>>();
+        cosmetics.sort_unstable_by_key(|(id, _)| *id);
+        cosmetics
+    }
+}
+
+/// The ids of the cosmetics a player has equipped
+#[derive(
+    Component,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize
+)]
+pub struct EquippedCosmetics(BTreeMap<CosmeticSlot, String>);
+
+impl EquippedCosmetics {
+    #[must_use]
+    pub fn get(&self, slot: CosmeticSlot) -> Option<&str> {
+        self.0.get(&slot).map(String::as_str)
+    }
+
+    /// Equips `id` in `slot`, returning the id of the cosmetic it replaced
+    pub fn equip(&mut self, slot: CosmeticSlot, id: impl Into<String>) -> Option<String> {
+        self.0.insert(slot, id.into())
+    }
+
+    /// Unequips the cosmetic in `slot`, returning its id
+    pub fn unequip(&mut self, slot: CosmeticSlot) -> Option<String> {
+        self.0.remove(&slot)
+    }
+
+    /// The cosmetic equipped in `slot`, if it is still defined
+    #[must_use]
+    pub fn cosmetic<'a>(
+        &self,
+        slot: CosmeticSlot,
+        cosmetics: &'a Cosmetics,
+    ) -> Option<&'a Cosmetic> {
+        cosmetics.get(self.get(slot)?)
+    }
+}
+
+/// Where the equipped cosmetics of players are saved, keyed by their uuid
+#[derive(Resource, Clone)]
+pub struct CosmeticStorage(pub Bucket<EquippedCosmetics>);
+
+/// Equips a cosmetic for the target player, replacing the cosmetic in the same slot
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct EquipCosmetic {
+    pub cosmetic: String,
+}
+
+/// Unequips the cosmetic in a slot for the target player
+#[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UnequipCosmetic {
+    pub slot: CosmeticSlot,
+}
+
+pub trait CosmeticsExt {
+    /// Equips a cosmetic by triggering [`EquipCosmetic`]
+    fn equip_cosmetic(&mut self, cosmetic: impl Into<String>) -> &mut Self;
+
+    /// Unequips the cosmetic in `slot` by triggering [`UnequipCosmetic`]
+    fn unequip_cosmetic(&mut self, slot: CosmeticSlot) -> &mut Self;
+}
+
+impl CosmeticsExt for EntityCommands<'_> {
+    fn equip_cosmetic(&mut self, cosmetic: impl Into<String>) -> &mut Self {
+        self.trigger(EquipCosmetic {
+            cosmetic: cosmetic.into(),
+        })
+    }
+
+    fn unequip_cosmetic(&mut self, slot: CosmeticSlot) -> &mut Self {
+        self.trigger(UnequipCosmetic { slot })
+    }
+}
+
+fn save(storage: &CosmeticStorage, uuid: &Uuid, equipped: &EquippedCosmetics) {
+    if let Err(e) = storage.0.insert(&uuid.0.to_string(), equipped) {
+        error!("failed to save equipped cosmetics: {e}");
+    }
+}
+
+/// Adds or removes the [`ChatPrefix`] of a player to match their equipped prefix
+fn sync_chat_prefix(
+    commands: &mut Commands<'_, '_>,
+    player: Entity,
+    equipped: &EquippedCosmetics,
+    cosmetics: &Cosmetics,
+) {
+    match equipped
+        .cosmetic(CosmeticSlot::ChatPrefix, cosmetics)
+        .map(|cosmetic| &cosmetic.effect)
+    {
+        Some(CosmeticEffect::ChatPrefix(prefix)) => {
+            commands.entity(player).insert(ChatPrefix(prefix.clone()));
+        }
+        _ => {
+            commands.entity(player).remove::<ChatPrefix>();
+        }
+    }
+}
+
+fn load_cosmetics(
+    trigger: Trigger<'_, OnAdd, Uuid>,
+    query: Query<'_, '_, &Uuid, With<ConnectionId>>,
+    storage: Res<'_, CosmeticStorage>,
+    cosmetics: Res<'_, Cosmetics>,
+    mut commands: Commands<'_, '_>,
+) {
+    let Ok(uuid) = query.get(trigger.target()) else {
+        return;
+    };
+
+    let equipped = storage
+        .0
+        .get(&uuid.0.to_string())
+        .unwrap_or_else(|e| {
+            error!("failed to load equipped cosmetics: {e}");
+            None
+        })
+        .unwrap_or_default();
+
+    sync_chat_prefix(&mut commands, trigger.target(), &equipped, &cosmetics);
+    commands.entity(trigger.target()).insert(equipped);
+}
+
+fn equip_cosmetic(
+    trigger: Trigger<'_, EquipCosmetic>,
+    mut query: Query<'_, '_, (&Uuid, &mut EquippedCosmetics)>,
+    cosmetics: Res<'_, Cosmetics>,
+    storage: Res<'_, CosmeticStorage>,
+    mut commands: Commands<'_, '_>,
+) {
+    let id = &trigger.event().cosmetic;
+    let Some(cosmetic) = cosmetics.get(id) else {
+        error!("failed to equip cosmetic: {id} is not defined");
+        return;
+    };
+
+    let (uuid, mut equipped) = match query.get_mut(trigger.target()) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("failed to equip cosmetic: query failed: {e}");
+            return;
+        }
+    };
+
+    equipped.equip(cosmetic.effect.slot(), id.clone());
+
+    save(&storage, uuid, &equipped);
+    sync_chat_prefix(&mut commands, trigger.target(), &equipped, &cosmetics);
+}
+
+fn unequip_cosmetic(
+    trigger: Trigger<'_, UnequipCosmetic>,
+    mut query: Query<'_, '_, (&Uuid, &mut EquippedCosmetics)>,
+    cosmetics: Res<'_, Cosmetics>,
+    storage: Res<'_, CosmeticStorage>,
+    mut commands: Commands<'_, '_>,
+) {
+    let (uuid, mut equipped) = match query.get_mut(trigger.target()) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("failed to unequip cosmetic: query failed: {e}");
+            return;
+        }
+    };
+
+    if equipped.unequip(trigger.event().slot).is_none() {
+        return;
+    }
+
+    save(&storage, uuid, &equipped);
+    sync_chat_prefix(&mut commands, trigger.target(), &equipped, &cosmetics);
+}
+
+fn show_trails(
+    query: Query<'_, '_, (&Position, &EquippedCosmetics), Changed<Position>>,
+    cosmetics: Res<'_, Cosmetics>,
+    compose: Res<'_, Compose>,
+) {
+    for (position, equipped) in &query {
+        let Some(CosmeticEffect::Trail { particle, count }) = equipped
+            .cosmetic(CosmeticSlot::Trail, &cosmetics)
+            .map(|cosmetic| &cosmetic.effect)
+        else {
+            continue;
+        };
+
+        let particles = agnostic::particle(particle.clone(), **position)
+            .offset(Vec3::new(0.2, 0.0, 0.2))
+            .count(*count)
+            .build();
+
+        if let Err(e) = compose
+            .broadcast_local(&particles, position.to_chunk())
+            .send()
+        {
+            error!("failed to send trail particles: {e}");
+        }
+    }
+}
+
+fn show_kill_effects(
+    mut events: EventReader<'_, '_, PlayerDeath>,
+    killers: Query<'_, '_, &EquippedCosmetics>,
+    victims: Query<'_, '_, &Position>,
+    cosmetics: Res<'_, Cosmetics>,
+    compose: Res<'_, Compose>,
+) {
+    for event in events.read() {
+        let Some(killer) = event.killer else {
+            continue;
+        };
+
+        let Some(CosmeticEffect::KillEffect { particle, count }) = killers
+            .get(killer)
+            .ok()
+            .and_then(|equipped| equipped.cosmetic(CosmeticSlot::KillEffect, &cosmetics))
+            .map(|cosmetic| &cosmetic.effect)
+        else {
+            continue;
+        };
+
+        let Ok(position) = victims.get(event.victim) else {
+            continue;
+        };
+
+        let particles = agnostic::particle(particle.clone(), **position + Vec3::Y)
+            .offset(Vec3::new(0.5, 0.5, 0.5))
+            .speed(0.1)
+            .count(*count)
+            .long_distance(true)
+            .build();
+
+        if let Err(e) = compose
+            .broadcast_local(&particles, position.to_chunk())
+            .send()
+        {
+            error!("failed to send kill effect particles: {e}");
+        }
+    }
+}
+
+/// Adds `/cosmetic` and shows the equipped cosmetics of players
+pub struct CosmeticsPlugin;
+
+impl Plugin for CosmeticsPlugin {
+    fn build(&self, app: &mut App) {
+        let bucket = app
+            .world()
+            .resource::<LocalDb>()
+            .bucket("hyperion-cosmetics")
+            .expect("failed to open equipped cosmetics");
+
+        app.insert_resource(CosmeticStorage(bucket));
+        app.init_resource::<Cosmetics>();
+        app.add_observer(load_cosmetics);
+        app.add_observer(equip_cosmetic);
+        app.add_observer(unequip_cosmetic);
+        app.add_systems(FixedUpdate, (timed(show_trails), timed(show_kill_effects)));
+
+        CosmeticCommand::register(app.world_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equip() {
+        let mut cosmetics = Cosmetics::default();
+        cosmetics.define(
+            "vip",
+            Cosmetic::new("VIP", CosmeticEffect::ChatPrefix("§6[VIP] ".to_owned())),
+        );
+
+        let mut equipped = EquippedCosmetics::default();
+        assert_eq!(equipped.equip(CosmeticSlot::ChatPrefix, "mvp"), None);
+        assert_eq!(
+            equipped.equip(CosmeticSlot::ChatPrefix, "vip"),
+            Some("mvp".to_owned())
+        );
+        assert_eq!(
+            equipped
+                .cosmetic(CosmeticSlot::ChatPrefix, &cosmetics)
+                .map(|cosmetic| cosmetic.name.as_str()),
+            Some("VIP")
+        );
+
+        // Slots are saved by name
+        let json = serde_json::to_string(&equipped).unwrap();
+        assert_eq!(json, r#"{"chat_prefix":"vip"}"#);
+        assert_eq!(
+            serde_json::from_str::<EquippedCosmetics>(&json).unwrap(),
+            equipped
+        );
+
+        assert_eq!(
+            equipped.unequip(CosmeticSlot::ChatPrefix),
+            Some("vip".to_owned())
+        );
+        assert!(
+            equipped
+                .cosmetic(CosmeticSlot::ChatPrefix, &cosmetics)
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{IgnMap, Uuid},
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+use crate::{Bank, EconomyError, format_coins};
+
+type State = SystemState<(
+    Query<'static, 'static, (&'static ConnectionId, &'static Uuid)>,
+    Res<'static, IgnMap>,
+    Res<'static, Compose>,
+    Bank<'static, 'static>,
+)>;
+
+fn reply(compose: &Compose, connection_id: ConnectionId, msg: impl Into<String>) {
+    if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+        error!("failed to send economy message: {e}");
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "balance")]
+#[command_permission(group = "Normal")]
+pub struct BalanceCommand {
+    /// The player to show the balance of. Defaults to you.
+    player: Option<String>,
+}
+
+impl MinecraftCommand for BalanceCommand {
+    type State = State;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (query, ign_map, compose, bank) = state.get(world);
+
+        let (&connection_id, uuid) = match query.get(caller) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("balance command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let (name, uuid) = match &self.player {
+            None => ("Your".to_owned(), uuid.0),
+            Some(player) => {
+                let Some((_, uuid)) = ign_map
+                    .get(player.as_str())
+                    .and_then(|&target| query.get(target).ok())
+                else {
+                    reply(&compose, connection_id, format!("§c{player} not found"));
+                    return Err(CommandFailed);
+                };
+                (format!("{player}'s"), uuid.0)
+            }
+        };
+
+        let (msg, result) = match bank.balance(uuid) {
+            Ok(balance) => (
+                format!("§6{name} balance: §e{}", format_coins(balance)),
+                Ok(()),
+            ),
+            Err(e) => (
+                format!("§cFailed to read the balance: {e}"),
+                Err(CommandFailed),
+            ),
+        };
+
+        reply(&compose, connection_id, msg);
+        result
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "pay")]
+#[command_permission(group = "Normal")]
+pub struct PayCommand {
+    player: String,
+    #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+    amount: u64,
+}
+
+impl MinecraftCommand for PayCommand {
+    type State = State;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (query, ign_map, compose, mut bank) = state.get(world);
+
+        let (&connection_id, uuid) = match query.get(caller) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("pay command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let Some((&target_connection, target_uuid)) = ign_map
+            .get(self.player.as_str())
+            .and_then(|&target| query.get(target).ok())
+        else {
+            reply(
+                &compose,
+                connection_id,
+                format!("§c{} not found", self.player),
+            );
+            return Err(CommandFailed);
+        };
+
+        let coins = format_coins(self.amount);
+
+        match bank.transfer(uuid.0, target_uuid.0, self.amount, "/pay") {
+            Ok((balance, _)) => {
+                reply(
+                    &compose,
+                    connection_id,
+                    format!(
+                        "§aPaid {coins} to {}. Your balance: {}",
+                        self.player,
+                        format_coins(balance)
+                    ),
+                );
+
+                let sender = world
+                    .get::<Name>(caller)
+                    .map_or_else(|| "Someone".to_owned(), ToString::to_string);
+                reply(
+                    &compose,
+                    target_connection,
+                    format!("§a{sender} paid you {coins}"),
+                );
+                Ok(())
+            }
+            Err(EconomyError::SamePlayer) => {
+                reply(&compose, connection_id, "§cYou cannot pay yourself");
+                Err(CommandFailed)
+            }
+            Err(e) => {
+                reply(&compose, connection_id, format!("§cFailed to pay: {e}"));
+                Err(CommandFailed)
+            }
+        }
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "eco")]
+#[command_permission(group = "Admin")]
+pub enum EcoCommand {
+    /// Adds coins to the balance of a player
+    Give {
+        player: String,
+        #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+        amount: u64,
+    },
+    /// Removes coins from the balance of a player
+    Take {
+        player: String,
+        #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+        amount: u64,
+    },
+}
+
+impl MinecraftCommand for EcoCommand {
+    type State = State;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (query, ign_map, compose, mut bank) = state.get(world);
+
+        let &connection_id = match query.get(caller) {
+            Ok((connection_id, _)) => connection_id,
+            Err(e) => {
+                error!("eco command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let (Self::Give { player, amount } | Self::Take { player, amount }) = &self;
+
+        let Some((_, target_uuid)) = ign_map
+            .get(player.as_str())
+            .and_then(|&target| query.get(target).ok())
+        else {
+            reply(&compose, connection_id, format!("§c{player} not found"));
+            return Err(CommandFailed);
+        };
+
+        let result = match &self {
+            Self::Give { .. } => bank.deposit(target_uuid.0, *amount, "/eco give"),
+            Self::Take { .. } => bank.withdraw(target_uuid.0, *amount, "/eco take"),
+        };
+
+        let (msg, result) = match result {
+            Ok(balance) => (
+                format!("§a{player}'s balance is now {}", format_coins(balance)),
+                Ok(()),
+            ),
+            Err(e) => (
+                format!("§cFailed to change {player}'s balance: {e}"),
+                Err(CommandFailed),
+            ),
+        };
+
+        reply(&compose, connection_id, msg);
+        result
+    }
+}
@@ -0,0 +1,296 @@
+//! Per-player balances of coins.
+//!
+//! Balances are saved in the [`LocalDb`] keyed by the uuid of the player, so players keep them
+//! across restarts and can be paid while offline. Plugins change balances through the [`Bank`]
+//! system param, which applies each change atomically and sends a [`Transaction`] event for it:
+//!
+//! ```ignore
+//! fn buy(mut bank: Bank<'_, '_>, ...) {
+//!     match bank.withdraw(uuid, 50, "bought a sword") {
+//!         Ok(balance) => { /* give the sword */ }
+//!         Err(EconomyError::InsufficientFunds { .. }) => { /* tell the player */ }
+//!         Err(e) => error!("failed to buy: {e}"),
+//!     }
+//! }
+//! ```
+
+mod command;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use hyperion::storage::{Bucket, LocalDb};
+use hyperion_clap::MinecraftCommand;
+
+use crate::command::{BalanceCommand, EcoCommand, PayCommand};
+
+/// Formats an amount of coins to show to players
+#[must_use]
+pub fn format_coins(amount: u64) -> String {
+    if amount == 1 {
+        "1 coin".to_owned()
+    } else {
+        format!("{amount} coins")
+    }
+}
+
+/// Why a balance could not be changed
+#[derive(Debug, thiserror::Error)]
+pub enum EconomyError {
+    #[error("cannot afford {} with a balance of {}", format_coins(*.amount), format_coins(*.balance))]
+    InsufficientFunds { balance: u64, amount: u64 },
+    #[error("the balance would be larger than the maximum")]
+    Overflow,
+    #[error("cannot transfer coins to the same player")]
+    SamePlayer,
+    #[error("failed to access balances: {0:#}")]
+    Storage(#[from] anyhow::Error),
+}
+
+/// A change to balances, sent as an event after it is saved
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct Transaction {
+    pub kind: TransactionKind,
+    pub amount: u64,
+    /// Why the balances changed, such as `/pay` or the item bought in a shop
+    pub reason: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TransactionKind {
+    Deposit { player: uuid::Uuid },
+    Withdraw { player: uuid::Uuid },
+    Transfer { from: uuid::Uuid, to: uuid::Uuid },
+}
+
+/// Where balances are saved. Balances are read with [`Economy::balance`] and changed through
+/// [`Bank`].
+#[derive(Resource, Clone)]
+pub struct Economy {
+    balances: Bucket<u64>,
+    /// The balance of players who have never had one
+    starting_balance: u64,
+}
+
+impl Economy {
+    #[must_use]
+    pub const fn new(balances: Bucket<u64>, starting_balance: u64) -> Self {
+        Self {
+            balances,
+            starting_balance,
+        }
+    }
+
+    pub fn balance(&self, player: uuid::Uuid) -> Result<u64, EconomyError> {
+        Ok(self
+            .balances
+            .get(&player.to_string())?
+            .unwrap_or(self.starting_balance))
+    }
+
+    /// Atomically changes the balances of `players`. `f` receives their balances in the same
+    /// order and the change is discarded if it returns an error.
+    fn update(
+        &self,
+        players: &[uuid::Uuid],
+        f: impl FnOnce(&mut [u64]) -> Result<(), EconomyError>,
+    ) -> Result<Vec<u64>, EconomyError> {
+        let keys = players.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let keys = keys.iter().map(String::as_str).collect::<Vec<_>>();
+        let mut balances = Vec::new();
+
+        self.balances.update_many(&keys, |values| {
+            balances = values
+                .iter()
+                .map(|value| value.unwrap_or(self.starting_balance))
+                .collect();
+
+            f(&mut balances)?;
+
+            for (value, &balance) in values.iter_mut().zip(&balances) {
+                *value = Some(balance);
+            }
+
+            Ok(())
+        })?;
+
+        Ok(balances)
+    }
+
+    fn deposit(&self, player: uuid::Uuid, amount: u64) -> Result<u64, EconomyError> {
+        let balances = self.update(&[player], |balances| {
+            balances[0] = balances[0]
+                .checked_add(amount)
+                .ok_or(EconomyError::Overflow)?;
+            Ok(())
+        })?;
+
+        Ok(balances[0])
+    }
+
+    fn withdraw(&self, player: uuid::Uuid, amount: u64) -> Result<u64, EconomyError> {
+        let balances = self.update(&[player], |balances| {
+            balances[0] = checked_withdraw(balances[0], amount)?;
+            Ok(())
+        })?;
+
+        Ok(balances[0])
+    }
+
+    fn transfer(
+        &self,
+        from: uuid::Uuid,
+        to: uuid::Uuid,
+        amount: u64,
+    ) -> Result<(u64, u64), EconomyError> {
+        if from == to {
+            return Err(EconomyError::SamePlayer);
+        }
+
+        let balances = self.update(&[from, to], |balances| {
+            balances[0] = checked_withdraw(balances[0], amount)?;
+            balances[1] = balances[1]
+                .checked_add(amount)
+                .ok_or(EconomyError::Overflow)?;
+            Ok(())
+        })?;
+
+        Ok((balances[0], balances[1]))
+    }
+}
+
+const fn checked_withdraw(balance: u64, amount: u64) -> Result<u64, EconomyError> {
+    match balance.checked_sub(amount) {
+        Some(balance) => Ok(balance),
+        None => Err(EconomyError::InsufficientFunds { balance, amount }),
+    }
+}
+
+/// Changes balances and sends a [`Transaction`] for every change
+#[derive(SystemParam)]
+pub struct Bank<'w, 's> {
+    economy: Res<'w, Economy>,
+    commands: Commands<'w, 's>,
+}
+
+impl Bank<'_, '_> {
+    pub fn balance(&self, player: uuid::Uuid) -> Result<u64, EconomyError> {
+        self.economy.balance(player)
+    }
+
+    /// Adds `amount` to the balance of `player`, returning their new balance
+    pub fn deposit(
+        &mut self,
+        player: uuid::Uuid,
+        amount: u64,
+        reason: impl Into<String>,
+    ) -> Result<u64, EconomyError> {
+        let balance = self.economy.deposit(player, amount)?;
+        self.record(TransactionKind::Deposit { player }, amount, reason);
+        Ok(balance)
+    }
+
+    /// Removes `amount` from the balance of `player`, returning their new balance. Fails with
+    /// [`EconomyError::InsufficientFunds`] if they cannot afford it.
+    pub fn withdraw(
+        &mut self,
+        player: uuid::Uuid,
+        amount: u64,
+        reason: impl Into<String>,
+    ) -> Result<u64, EconomyError> {
+        let balance = self.economy.withdraw(player, amount)?;
+        self.record(TransactionKind::Withdraw { player }, amount, reason);
+        Ok(balance)
+    }
+
+    /// Moves `amount` from the balance of `from` to the balance of `to`, returning their new
+    /// balances. Neither balance changes if `from` cannot afford it.
+    pub fn transfer(
+        &mut self,
+        from: uuid::Uuid,
+        to: uuid::Uuid,
+        amount: u64,
+        reason: impl Into<String>,
+    ) -> Result<(u64, u64), EconomyError> {
+        let balances = self.economy.transfer(from, to, amount)?;
+        self.record(TransactionKind::Transfer { from, to }, amount, reason);
+        Ok(balances)
+    }
+
+    fn record(&mut self, kind: TransactionKind, amount: u64, reason: impl Into<String>) {
+        self.commands.send_event(Transaction {
+            kind,
+            amount,
+            reason: reason.into(),
+        });
+    }
+}
+
+/// Adds `/balance`, `/pay` and `/eco`, and saves balances
+pub struct EconomyPlugin {
+    /// The balance of players who have never had one
+    pub starting_balance: u64,
+}
+
+impl Default for EconomyPlugin {
+    fn default() -> Self {
+        Self {
+            starting_balance: 100,
+        }
+    }
+}
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        let bucket = app
+            .world()
+            .resource::<LocalDb>()
+            .bucket("hyperion-economy")
+            .expect("failed to open balances");
+
+        app.insert_resource(Economy::new(bucket, self.starting_balance));
+        app.add_event::<Transaction>();
+
+        BalanceCommand::register(app.world_mut());
+        PayCommand::register(app.world_mut());
+        EcoCommand::register(app.world_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion::storage::TempLocalDb;
+
+    use super::*;
+
+    #[test]
+    fn test_transactions() {
+        let db = TempLocalDb::new().unwrap();
+        let economy = Economy::new(db.bucket("hyperion-economy").unwrap(), 100);
+
+        let alice = uuid::Uuid::from_u128(1);
+        let bob = uuid::Uuid::from_u128(2);
+
+        assert_eq!(economy.balance(alice).unwrap(), 100);
+        assert_eq!(economy.deposit(alice, 50).unwrap(), 150);
+        assert_eq!(economy.withdraw(alice, 30).unwrap(), 120);
+        assert_eq!(economy.transfer(alice, bob, 20).unwrap(), (100, 120));
+
+        // Failed transactions change no balance
+        assert!(matches!(
+            economy.transfer(alice, bob, 101),
+            Err(EconomyError::InsufficientFunds {
+                balance: 100,
+                amount: 101
+            })
+        ));
+        assert!(matches!(
+            economy.deposit(bob, u64::MAX),
+            Err(EconomyError::Overflow)
+        ));
+        assert!(matches!(
+            economy.transfer(alice, alice, 1),
+            Err(EconomyError::SamePlayer)
+        ));
+        assert_eq!(economy.balance(alice).unwrap(), 100);
+        assert_eq!(economy.balance(bob).unwrap(), 120);
+    }
+}
@@ -0,0 +1,274 @@
+//! Vanilla fishing rod mechanics.
+//!
+//! Using a fishing rod casts a bobber, and using it again retracts the bobber. A bobber which hits
+//! an entity hooks it, and retracting the rod pulls the hooked entity towards the caster. This
+//! is the rod pull used in many pvp game modes.
+
+#![feature(let_chains)]
+
+use bevy::prelude::*;
+use hyperion::{
+    ItemKind,
+    glam::Vec3,
+    net::{Channel, Compose, agnostic},
+    simulation::{
+        Owner, Pitch, Position, Uuid, Velocity, Yaw, entity_kind::EntityKind, event,
+        metadata::fishing_bobber::HookedEntity,
+    },
+    tick_rate::AdvanceTick,
+    timings::timed,
+};
+use hyperion_inventory::PlayerInventory;
+use hyperion_utils::EntityExt;
+use tracing::error;
+use valence_protocol::{Hand, Ident, VarInt, ident};
+
+/// Bobbers further than this many blocks from their owner are removed
+pub const MAX_LINE_LENGTH: f32 = 32.0;
+
+/// The fraction of the distance between the caster and the hooked entity that the entity is
+/// pulled by
+pub const PULL_STRENGTH: f32 = 0.1;
+
+/// The height of the eyes of a standing player, which is where bobbers are cast from
+const EYE_HEIGHT: f32 = 1.62;
+
+/// A player who has cast their fishing rod
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Fishing {
+    pub bobber: Entity,
+}
+
+/// A fishing bobber. The caster is stored in [`Owner`].
+#[derive(Component, Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Bobber {
+    pub hooked: Option<Entity>,
+}
+
+/// The velocity of a bobber cast by a player looking in the given direction. This matches
+/// vanilla without the random spread.
+#[must_use]
+pub fn cast_velocity(yaw: f32, pitch: f32) -> Vec3 {
+    let (yaw_sin, yaw_cos) = yaw.to_radians().sin_cos();
+    let vertical = -pitch.to_radians().tan();
+
+    let direction = Vec3::new(-yaw_sin, vertical.clamp(-5.0, 5.0), yaw_cos);
+    direction * (0.6 / direction.length() + 0.5)
+}
+
+/// The velocity added to a hooked entity at `target` when the rod of a player at `caster` is
+/// retracted
+#[must_use]
+pub fn pull_velocity(caster: Vec3, target: Vec3) -> Vec3 {
+    (caster - target) * PULL_STRENGTH
+}
+
+fn is_holding_rod(inventory: &PlayerInventory, hand: Hand) -> bool {
+    let slot = match hand {
+        Hand::Main => inventory.get_cursor(),
+        Hand::Off => inventory.get_offhand(),
+    };
+
+    slot.stack.item == ItemKind::FishingRod
+}
+
+fn play_sound(compose: &Compose, sound: Ident, position: Vec3) {
+    let sound = agnostic::sound(sound, position).build();
+
+    if let Err(e) = compose
+        .broadcast_local(&sound, Position::from(position).to_chunk())
+        .send()
+    {
+        error!("failed to send fishing sound: {e}");
+    }
+}
+
+fn handle_rod_use(
+    mut events: EventReader<'_, '_, event::ItemInteract>,
+    compose: Res<'_, Compose>,
+    player_query: Query<'_, '_, (&PlayerInventory, &Position, &Yaw, &Pitch, Option<&Fishing>)>,
+    bobber_query: Query<'_, '_, &Bobber>,
+    mut target_query: Query<'_, '_, (&Position, &mut Velocity)>,
+    mut commands: Commands<'_, '_>,
+) {
+    for event in events.read() {
+        let (inventory, position, yaw, pitch, fishing) = match player_query.get(event.entity) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("failed to handle fishing rod use: query failed: {e}");
+                continue;
+            }
+        };
+
+        if !is_holding_rod(inventory, event.hand) {
+            continue;
+        }
+
+        // Retract the bobber if one was already cast
+        if let Some(fishing) = fishing
+            && let Ok(bobber) = bobber_query.get(fishing.bobber)
+        {
+            if let Some(hooked) = bobber.hooked
+                && let Ok((target_pos, mut velocity)) = target_query.get_mut(hooked)
+            {
+                velocity.0 += pull_velocity(**position, **target_pos);
+            }
+
+            commands.entity(fishing.bobber).despawn();
+            commands.entity(event.entity).remove::<Fishing>();
+
+            play_sound(
+                &compose,
+                ident!("entity.fishing_bobber.retrieve"),
+                **position,
+            );
+            continue;
+        }
+
+        let spawn_pos = Vec3::new(position.x, position.y + EYE_HEIGHT, position.z);
+        let velocity = cast_velocity(**yaw, **pitch);
+
+        let bobber = commands
+            .spawn((
+                Uuid::new_v4(),
+                Position::from(spawn_pos),
+                Velocity::new(velocity.x, velocity.y, velocity.z),
+                Pitch::new(**pitch),
+                Yaw::new(**yaw),
+                Owner::new(event.entity),
+                Bobber::default(),
+                EntityKind::FishingBobber,
+                Channel,
+            ))
+            .id();
+
+        commands.entity(event.entity).insert(Fishing { bobber });
+
+        play_sound(&compose, ident!("entity.fishing_bobber.throw"), spawn_pos);
+    }
+}
+
+fn bobber_entity_hit(
+    mut events: EventReader<'_, '_, event::ProjectileEntityEvent>,
+    mut query: Query<'_, '_, (&mut Bobber, &mut Velocity, &mut HookedEntity)>,
+) {
+    for event in events.read() {
+        // Other projectiles are handled elsewhere
+        let Ok((mut bobber, mut velocity, mut hooked_entity)) = query.get_mut(event.projectile)
+        else {
+            continue;
+        };
+
+        bobber.hooked = Some(event.client);
+        velocity.0 = Vec3::ZERO;
+        **hooked_entity = VarInt(event.client.minecraft_id() + 1);
+    }
+}
+
+fn bobber_block_hit(
+    mut events: EventReader<'_, '_, event::ProjectileBlockEvent>,
+    mut query: Query<'_, '_, (&mut Position, &mut Velocity), With<Bobber>>,
+) {
+    for event in events.read() {
+        let Ok((mut position, mut velocity)) = query.get_mut(event.projectile) else {
+            continue;
+        };
+
+        velocity.0 = Vec3::ZERO;
+        **position = event.collision.point;
+    }
+}
+
+/// Removes bobbers whose owner left, stopped holding a rod or moved too far away, and keeps
+/// hooked bobbers on the entity they hooked
+fn update_bobbers(
+    mut bobber_query: Query<
+        '_,
+        '_,
+        (
+            Entity,
+            &Owner,
+            &mut Bobber,
+            &mut Position,
+            &mut HookedEntity,
+        ),
+    >,
+    owner_query: Query<'_, '_, (&PlayerInventory, &Position), Without<Bobber>>,
+    target_query: Query<'_, '_, &Position, Without<Bobber>>,
+    mut commands: Commands<'_, '_>,
+) {
+    for (entity, owner, mut bobber, mut position, mut hooked_entity) in &mut bobber_query {
+        let keep = owner_query
+            .get(owner.entity)
+            .is_ok_and(|(inventory, owner_pos)| {
+                (is_holding_rod(inventory, Hand::Main) || is_holding_rod(inventory, Hand::Off))
+                    && owner_pos.distance(**position) <= MAX_LINE_LENGTH
+            });
+
+        if !keep {
+            commands.entity(entity).despawn();
+            if let Ok(mut owner) = commands.get_entity(owner.entity) {
+                owner.remove::<Fishing>();
+            }
+            continue;
+        }
+
+        let Some(hooked) = bobber.hooked else {
+            continue;
+        };
+
+        if let Ok(target_pos) = target_query.get(hooked) {
+            **position = **target_pos;
+        } else {
+            bobber.hooked = None;
+            **hooked_entity = VarInt(0);
+        }
+    }
+}
+
+pub struct FishingPlugin;
+
+impl Plugin for FishingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                timed(handle_rod_use),
+                timed(bobber_entity_hit),
+                timed(bobber_block_hit),
+                timed(update_bobbers).in_set(AdvanceTick),
+            )
+                .chain(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cast_velocity() {
+        // Looking straight ahead casts the bobber horizontally at 1.1 blocks per tick
+        let velocity = cast_velocity(0.0, 0.0);
+        assert!(velocity.abs_diff_eq(Vec3::new(0.0, 0.0, 1.1), 1e-6));
+
+        let velocity = cast_velocity(90.0, 0.0);
+        assert!(velocity.abs_diff_eq(Vec3::new(-1.1, 0.0, 0.0), 1e-6));
+
+        // Looking up casts the bobber upwards, and the vertical speed is capped when looking
+        // straight up
+        let velocity = cast_velocity(0.0, -45.0);
+        assert!(velocity.y > 0.0);
+        assert!((velocity.y - velocity.z).abs() < 1e-6);
+
+        let velocity = cast_velocity(0.0, -90.0);
+        assert!((velocity.y / velocity.z - 5.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_pull_velocity() {
+        let velocity = pull_velocity(Vec3::ZERO, Vec3::new(10.0, 0.0, -5.0));
+        assert!(velocity.abs_diff_eq(Vec3::new(-1.0, 0.0, 0.5), 1e-6));
+    }
+}
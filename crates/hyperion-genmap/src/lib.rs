@@ -1,20 +1,45 @@
 use bevy::prelude::*;
 use hyperion::{runtime::AsyncRuntime, simulation::blocks::Blocks};
+use hyperion_utils::CacheOptions;
 
-pub struct GenMapPlugin;
+/// The map which is loaded by default
+pub const DEFAULT_MAP: &str = "https://github.com/andrewgazelka/maps/raw/main/GenMap.tar.gz";
+
+/// Loads the map from `source`, which is either a url or a local path of a `.tar.gz` archive, or
+/// a local directory. See [`hyperion_utils::cached_save`].
+pub struct GenMapPlugin {
+    pub source: String,
+    /// The expected SHA-256 of the archive in hex
+    pub sha256: Option<String>,
+    /// Download and unpack the map again even if the cached map is up to date
+    pub force_refresh: bool,
+}
+
+impl Default for GenMapPlugin {
+    fn default() -> Self {
+        Self {
+            source: DEFAULT_MAP.to_owned(),
+            sha256: None,
+            force_refresh: false,
+        }
+    }
+}
 
 impl Plugin for GenMapPlugin {
     fn build(&self, app: &mut App) {
-        const URL: &str = "https://github.com/andrewgazelka/maps/raw/main/GenMap.tar.gz";
-
         let runtime = app
             .world()
             .get_resource::<AsyncRuntime>()
             .expect("AsyncRuntime resource must exist");
-        let f = hyperion_utils::cached_save(app.world(), URL);
+
+        let options = CacheOptions {
+            sha256: self.sha256.clone(),
+            force_refresh: self.force_refresh,
+        };
+        let f = hyperion_utils::cached_save(app.world(), self.source.clone(), options);
 
         let save = runtime.block_on(f).unwrap_or_else(|e| {
-            panic!("failed to download map {URL}: {e}");
+            panic!("failed to load map {}: {e}", self.source);
         });
 
         app.insert_resource(Blocks::new(runtime, &save).unwrap());
@@ -2,7 +2,9 @@ use std::collections::HashMap;
 
 use bevy::prelude::*;
 use hyperion::{
-    simulation::{Uuid, entity_kind::EntityKind},
+    ingress,
+    simulation::{Uuid, entity_kind::EntityKind, packet},
+    timings::timed,
     valence_protocol::packets::play::{
         click_slot_c2s::ClickMode, close_screen_s2c::CloseScreenS2c,
     },
@@ -39,9 +41,8 @@ impl Gui {
     pub fn new(inventory: Inventory, world: &mut World, id: u64) -> Self {
         let uuid = Uuid::new_v4();
 
-        let entity = world
-            .spawn((EntityKind::BlockDisplay, uuid, inventory))
-            .id();
+        // The inventory is only opened by players and never spawned for them
+        let entity = world.spawn((EntityKind::Gui, uuid, inventory)).id();
 
         Self {
             entity,
@@ -50,6 +51,12 @@ impl Gui {
         }
     }
 
+    /// The entity with the [`Inventory`] shown to players who open this gui
+    #[must_use]
+    pub const fn inventory(&self) -> Entity {
+        self.entity
+    }
+
     pub fn add_command(&mut self, slot: usize, on_click: fn(Entity, ClickMode)) {
         self.items.insert(slot, on_click);
     }
@@ -74,3 +81,77 @@ impl Gui {
         todo!()
     }
 }
+
+/// Triggered on the entity with the [`Gui`] when a player clicks one of its slots. Clicks on the
+/// player's own inventory below the gui are not included.
+///
+/// ```ignore
+/// commands.spawn((EntityKind::Gui, gui)).observe(|trigger: Trigger<'_, GuiClick>| {
+///     info!("{} clicked slot {}", trigger.player, trigger.slot);
+/// });
+/// ```
+#[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GuiClick {
+    pub player: Entity,
+    pub slot: usize,
+    pub mode: ClickMode,
+    pub button: i8,
+}
+
+fn dispatch_clicks(
+    mut packets: EventReader<'_, '_, packet::play::ClickSlot>,
+    players: Query<'_, '_, &OpenInventory>,
+    guis: Query<'_, '_, (Entity, &Gui)>,
+    inventories: Query<'_, '_, &Inventory>,
+    mut commands: Commands<'_, '_>,
+) {
+    for packet in packets.read() {
+        let player = packet.sender();
+
+        let Ok(open_inventory) = players.get(player) else {
+            continue;
+        };
+
+        let Some((gui_entity, gui)) = guis
+            .iter()
+            .find(|(_, gui)| gui.entity == open_inventory.entity)
+        else {
+            continue;
+        };
+
+        let Ok(slot) = usize::try_from(packet.slot_idx) else {
+            continue;
+        };
+
+        let in_gui = inventories
+            .get(gui.entity)
+            .is_ok_and(|inventory| slot < inventory.size());
+
+        if !in_gui {
+            continue;
+        }
+
+        if let Some(on_click) = gui.items.get(&slot) {
+            on_click(player, packet.mode);
+        }
+
+        commands.entity(gui_entity).trigger(GuiClick {
+            player,
+            slot,
+            mode: packet.mode,
+            button: packet.button,
+        });
+    }
+}
+
+/// Runs the commands of [`Gui`]s and triggers [`GuiClick`] when players click them
+pub struct GuiPlugin;
+
+impl Plugin for GuiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            timed(dispatch_clicks).after(ingress::decode::play),
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Writable and written books.
+//!
+//! Players can write in writable books and sign them, which turns them into written books. Plugins
+//! can show a book without giving it to the player with [`BookExt::open_book`], which is useful
+//! for rules and tutorials.
+
+use std::borrow::Cow;
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use hyperion::{
+    ItemKind, ItemStack, ingress,
+    net::{Compose, ConnectionId, DataBundle},
+    simulation::packet::play,
+    timings::timed,
+};
+use hyperion_inventory::{InventoryState, PlayerInventory};
+use tracing::{error, warn};
+use valence_protocol::{
+    Hand, VarInt, nbt,
+    packets::play::{OpenWrittenBookS2c, ScreenHandlerSlotUpdateS2c},
+};
+
+use crate::builder::BookBuilder;
+
+/// The most pages a book may have
+pub const MAX_PAGES: usize = 100;
+
+/// The longest title of a written book
+pub const MAX_TITLE_LENGTH: usize = 32;
+
+/// The slot which refers to the offhand in [`play::BookUpdate`]
+const OFFHAND_SLOT: i32 = 40;
+
+/// The number of hotbar slots, which are the slots `0..HOTBAR_SLOTS` in [`play::BookUpdate`]
+const HOTBAR_SLOTS: u16 = 9;
+
+/// Shows a written book with these pages to the target player without giving them the book
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct OpenBook {
+    pub pages: Vec<String>,
+}
+
+pub trait BookExt {
+    /// Shows a book to this player by triggering [`OpenBook`]
+    fn open_book(&mut self, pages: impl IntoIterator<Item = impl Into<String>>) -> &mut Self;
+}
+
+impl BookExt for EntityCommands<'_> {
+    fn open_book(&mut self, pages: impl IntoIterator<Item = impl Into<String>>) -> &mut Self {
+        self.trigger(OpenBook {
+            pages: pages.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+/// Creates a writable book with these pages
+#[must_use]
+pub fn writable_book(pages: impl IntoIterator<Item = impl Into<String>>) -> ItemStack {
+    let pages = pages.into_iter().map(Into::into).collect();
+
+    let mut nbt = nbt::Compound::new();
+    nbt.insert("pages", nbt::Value::List(nbt::List::String(pages)));
+
+    ItemStack::new(ItemKind::WritableBook, 1, Some(nbt))
+}
+
+/// Creates a written book with these pages
+#[must_use]
+pub fn written_book(
+    author: impl Into<String>,
+    title: impl Into<String>,
+    pages: impl IntoIterator<Item = impl Into<String>>,
+) -> ItemStack {
+    pages
+        .into_iter()
+        .fold(BookBuilder::new(author, title), BookBuilder::add_page)
+        .build()
+}
+
+fn open_book(
+    trigger: Trigger<'_, OpenBook>,
+    mut query: Query<'_, '_, (&ConnectionId, &mut PlayerInventory, &InventoryState)>,
+    compose: Res<'_, Compose>,
+) {
+    let (&connection_id, mut inventory, state) = match query.get_mut(trigger.target()) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("failed to open book: query failed: {e}");
+            return;
+        }
+    };
+
+    let book = written_book("", "", trigger.event().pages.iter().cloned());
+    let slot = inventory.get_cursor_index();
+
+    // The client can only open a book which is in its hand, so the book is put in the hand until
+    // the book is opened
+    let mut bundle = DataBundle::new(&compose);
+    bundle
+        .add_packet(&ScreenHandlerSlotUpdateS2c {
+            window_id: 0,
+            state_id: VarInt(state.state_id()),
+            slot_idx: i16::try_from(slot).unwrap(),
+            slot_data: Cow::Borrowed(&book),
+        })
+        .unwrap();
+    bundle
+        .add_packet(&OpenWrittenBookS2c { hand: Hand::Main })
+        .unwrap();
+
+    if let Err(e) = bundle.unicast(connection_id) {
+        error!("failed to open book: {e}");
+    }
+
+    // The item which is actually in the hand is sent again at the end of the tick
+    if let Err(e) = inventory.get_mut(slot) {
+        error!("failed to open book: {e}");
+    }
+}
+
+/// Writes in or signs a writable book
+fn update_books(
+    mut packets: EventReader<'_, '_, play::BookUpdate>,
+    mut query: Query<'_, '_, (&mut PlayerInventory, &Name)>,
+) {
+    for packet in packets.read() {
+        let (mut inventory, name) = match query.get_mut(packet.sender()) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("failed to update book: query failed: {e}");
+                continue;
+            }
+        };
+
+        // Books can only be edited in the main hand or the offhand
+        let slot = if packet.slot.0 == OFFHAND_SLOT {
+            PlayerInventory::OFFHAND_SLOT
+        } else {
+            // Larger slots would overflow when they are converted to an inventory index
+            let Some(slot) = u16::try_from(packet.slot.0)
+                .ok()
+                .filter(|&slot| slot < HOTBAR_SLOTS)
+            else {
+                warn!("invalid book slot {}", packet.slot.0);
+                continue;
+            };
+
+            match inventory.hand_slot_index(slot) {
+                Ok(slot) if slot == inventory.get_cursor_index() => slot,
+                _ => {
+                    warn!("book slot {slot} is not held");
+                    continue;
+                }
+            }
+        };
+
+        let Ok(current) = inventory.get(slot) else {
+            continue;
+        };
+
+        if current.stack.item != ItemKind::WritableBook {
+            warn!("player tried to edit a book which they do not hold");
+            continue;
+        }
+
+        let pages = packet.entries.iter().take(MAX_PAGES).map(|page| page.0);
+
+        let book = match packet.title {
+            Some(title) => {
+                let title = title.0.trim();
+                if title.is_empty() || title.chars().count() > MAX_TITLE_LENGTH {
+                    warn!("invalid book title {title:?}");
+                    continue;
+                }
+
+                written_book(name.as_str(), title, pages)
+            }
+            None => writable_book(pages),
+        };
+
+        if let Err(e) = inventory.set(slot, book) {
+            error!("failed to update book: {e}");
+        }
+    }
+}
+
+pub(crate) fn build(app: &mut App) {
+    app.add_observer(open_book);
+    app.add_systems(
+        FixedUpdate,
+        timed(update_books).after(ingress::decode::play),
+    );
+}
@@ -1,8 +1,10 @@
 use bevy::prelude::*;
-use valence_protocol::{ItemKind, ItemStack, nbt, nbt::Value};
+use valence_protocol::{ItemKind, ItemStack, nbt, nbt::Value, text::Text};
 
 mod book;
+mod head;
 pub use book::BookBuilder;
+pub use head::HeadBuilder;
 
 /// A builder for creating Minecraft items with NBT data
 #[derive(Clone, Debug)]
@@ -145,6 +147,26 @@ impl ItemBuilder {
         self
     }
 
+    /// Sets the lines of text shown below the name of the item
+    pub fn lore<S: Into<String>>(mut self, lines: impl IntoIterator<Item = S>) -> Self {
+        let nbt = self.nbt.get_or_insert_with(nbt::Compound::new);
+
+        let mut display = match nbt.remove("display") {
+            Some(Value::Compound(display)) => display,
+            _ => nbt::Compound::new(),
+        };
+
+        let lines = lines
+            .into_iter()
+            .map(|line| Text::text(line.into()).to_string())
+            .collect();
+
+        display.insert("Lore", Value::List(nbt::list::List::String(lines)));
+
+        nbt.insert("display", Value::Compound(display));
+        self
+    }
+
     pub const fn count(mut self, count: i8) -> Self {
         self.count = count;
         self
@@ -208,4 +230,22 @@ mod tests {
 
         // Add assertions here
     }
+
+    #[test]
+    fn test_lore_is_escaped() {
+        let item = ItemBuilder::new(ItemKind::Paper)
+            .lore([r#"say "hi""#, r"C:\"])
+            .build();
+
+        let Some(Value::Compound(display)) = item.nbt.as_ref().and_then(|nbt| nbt.get("display"))
+        else {
+            panic!("item has no display compound");
+        };
+        let Some(Value::List(nbt::list::List::String(lore))) = display.get("Lore") else {
+            panic!("item has no lore");
+        };
+
+        assert!(lore[0].contains(r#"say \"hi\""#));
+        assert!(lore[1].contains(r"C:\\"));
+    }
 }
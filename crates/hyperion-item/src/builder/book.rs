@@ -1,5 +1,5 @@
 use hyperion::{ItemKind, ItemStack};
-use valence_protocol::nbt;
+use valence_protocol::{nbt, text::Text};
 
 use crate::builder::ItemBuilder;
 
@@ -31,8 +31,7 @@ impl BookBuilder {
     }
 
     pub fn add_page(mut self, page: impl Into<String>) -> Self {
-        let page = page.into();
-        let json = format!(r#"{{"text":"{page}"}}"#);
+        let json = Text::text(page.into()).to_string();
 
         if let Some(nbt) = &mut self.item.nbt {
             if let nbt::Value::List(nbt::List::String(pages)) = nbt.get_mut("pages").unwrap() {
@@ -0,0 +1,120 @@
+use hyperion::{
+    ItemKind, ItemStack, simulation::skin::PlayerSkin, storage::SkinHandler,
+    util::mojang::MojangClient,
+};
+use valence_protocol::{nbt, nbt::Value};
+
+use crate::builder::ItemBuilder;
+
+/// A builder for player heads which show the skin of a player
+///
+/// # Example
+/// ```
+/// use hyperion::{simulation::skin::PlayerSkin, uuid::Uuid};
+/// use hyperion_item::builder::HeadBuilder;
+/// let head = HeadBuilder::new(Uuid::nil())
+///     .owner("Notch")
+///     .skin(&PlayerSkin::EMPTY)
+///     .name("§eNotch")
+///     .build();
+/// ```
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct HeadBuilder {
+    item: ItemBuilder,
+    owner: nbt::Compound<String>,
+}
+
+/// The `SkullOwner` id of a player, which is their UUID split into four ints
+fn skull_owner_id(uuid: uuid::Uuid) -> Vec<i32> {
+    let (most, least) = uuid.as_u64_pair();
+
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
+        reason = "the uuid is split into four ints"
+    )]
+    let id = [most >> 32, most, least >> 32, least].map(|part| part as u32 as i32);
+
+    id.to_vec()
+}
+
+impl HeadBuilder {
+    /// Creates a head owned by the player with `uuid`. Without a [`skin`](Self::skin), the
+    /// client shows the default skin.
+    pub fn new(uuid: uuid::Uuid) -> Self {
+        let mut owner = nbt::Compound::new();
+        owner.insert("Id", Value::IntArray(skull_owner_id(uuid)));
+
+        Self {
+            item: ItemBuilder::new(ItemKind::PlayerHead),
+            owner,
+        }
+    }
+
+    /// Creates a head with the skin of the player with `uuid`, which is read from the skin cache
+    /// or fetched from Mojang on a cache miss
+    pub async fn from_uuid(
+        uuid: uuid::Uuid,
+        mojang: &MojangClient,
+        skins: &SkinHandler,
+    ) -> anyhow::Result<Self> {
+        let head = Self::new(uuid);
+
+        Ok(match PlayerSkin::from_uuid(uuid, mojang, skins).await? {
+            Some(skin) => head.skin(&skin),
+            None => head,
+        })
+    }
+
+    /// Sets the name of the player who owns the head
+    pub fn owner(mut self, name: impl Into<String>) -> Self {
+        self.owner.insert("Name", Value::String(name.into()));
+        self
+    }
+
+    /// Sets the texture shown on the head. Empty skins are ignored.
+    pub fn skin(mut self, skin: &PlayerSkin) -> Self {
+        if skin.textures.is_empty() {
+            return self;
+        }
+
+        let mut texture = nbt::Compound::new();
+        texture.insert("Value", Value::String(skin.textures.clone()));
+        texture.insert("Signature", Value::String(skin.signature.clone()));
+
+        let mut properties = nbt::Compound::new();
+        properties.insert("textures", Value::List(nbt::List::Compound(vec![texture])));
+
+        self.owner.insert("Properties", Value::Compound(properties));
+        self
+    }
+
+    /// Sets a custom name for the item
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.item = self.item.name(name);
+        self
+    }
+
+    #[must_use]
+    pub fn build(mut self) -> ItemStack {
+        self.item
+            .nbt
+            .get_or_insert_with(nbt::Compound::new)
+            .insert("SkullOwner", Value::Compound(self.owner));
+
+        self.item.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skull_owner_id() {
+        let uuid = uuid::Uuid::from_u128(0x0001_0002_0003_0004_ffff_fffe_0000_0000);
+
+        assert_eq!(skull_owner_id(uuid), vec![0x0001_0002, 0x0003_0004, -2, 0]);
+    }
+}
@@ -1,10 +1,11 @@
 use bevy::prelude::*;
 use derive_more::Deref;
-use hyperion::{ingress, simulation::event::InteractEvent};
+use hyperion::{ingress, simulation::event::InteractEvent, timings::timed};
 use hyperion_inventory::PlayerInventory;
 use tracing::error;
 use valence_protocol::nbt;
 
+pub mod book;
 pub mod builder;
 
 pub struct ItemPlugin;
@@ -70,6 +71,10 @@ fn handle_interact(
 impl Plugin for ItemPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<NbtInteractEvent>();
-        app.add_systems(FixedUpdate, handle_interact.after(ingress::decode::play));
+        book::build(app);
+        app.add_systems(
+            FixedUpdate,
+            timed(handle_interact).after(ingress::decode::play),
+        );
     }
 }
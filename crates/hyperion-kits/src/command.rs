@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    net::{Compose, ConnectionId, DataBundle, agnostic},
+    simulation::Uuid,
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use hyperion_inventory::PlayerInventory;
+use hyperion_permission::Group;
+use tracing::error;
+
+use crate::{Kit, KitStorage};
+
+/// Kits cannot have the names of subcommands, since they could not be claimed
+const RESERVED_NAMES: [&str; 4] = ["list", "create", "delete", "help"];
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "kit")]
+#[command_permission(group = "Normal")]
+pub enum KitCommand {
+    /// Lists the kits you may claim
+    List,
+    /// Saves your inventory as a kit. Requires the `Admin` group.
+    Create {
+        name: String,
+        /// The time players have to wait between claims, such as `10m`. Defaults to none.
+        #[arg(value_parser = humantime::parse_duration)]
+        cooldown: Option<Duration>,
+        /// The lowest group which may claim the kit. Defaults to `Normal`.
+        group: Option<Group>,
+    },
+    /// Deletes a kit. Requires the `Admin` group.
+    Delete { name: String },
+    /// Gives you the kit with this name
+    #[command(external_subcommand)]
+    Claim(Vec<String>),
+}
+
+impl MinecraftCommand for KitCommand {
+    type State = SystemState<(
+        Query<
+            'static,
+            'static,
+            (
+                &'static ConnectionId,
+                &'static Uuid,
+                &'static Group,
+                &'static PlayerInventory,
+            ),
+        >,
+        Res<'static, KitStorage>,
+        Res<'static, Compose>,
+        Commands<'static, 'static>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (query, storage, compose, mut commands) = state.get(world);
+
+        let (&connection_id, uuid, &group, inventory) = match query.get(caller) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("kit command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let result = match self {
+            Self::List => match storage.kits.entries() {
+                Ok(kits) => {
+                    let names = kits
+                        .into_iter()
+                        .filter(|(_, kit)| kit.allows(group))
+                        .map(|(name, _)| name)
+                        .collect::<Vec<_>>();
+
+                    if names.is_empty() {
+                        Ok("§7There are no kits".to_owned())
+                    } else {
+                        Ok(format!("§6Kits: §e{}", names.join(", ")))
+                    }
+                }
+                Err(e) => Err(format!("§cFailed to read kits: {e}")),
+            },
+            Self::Create { .. } | Self::Delete { .. } if group != Group::Admin => {
+                Err("§cYou do not have permission to manage kits".to_owned())
+            }
+            Self::Create { name, .. } if RESERVED_NAMES.contains(&name.as_str()) => {
+                Err(format!("§c{name} cannot be the name of a kit"))
+            }
+            Self::Create {
+                name,
+                cooldown,
+                group,
+            } => {
+                let kit = Kit::from_inventory(
+                    inventory,
+                    cooldown.unwrap_or_default(),
+                    group.unwrap_or_default(),
+                );
+
+                match kit.and_then(|kit| storage.kits.insert(&name, &kit)) {
+                    Ok(()) => Ok(format!("§aSaved your inventory as the kit {name}")),
+                    Err(e) => Err(format!("§cFailed to save kit {name}: {e}")),
+                }
+            }
+            Self::Delete { name } => match storage.kits.remove(&name) {
+                Ok(true) => Ok(format!("§aDeleted the kit {name}")),
+                Ok(false) => Err(format!("§cNo kit is named {name}")),
+                Err(e) => Err(format!("§cFailed to delete kit {name}: {e}")),
+            },
+            Self::Claim(args) => {
+                let name = args.join(" ");
+                claim(&storage, &mut commands, caller, uuid.0, group, name)
+            }
+        };
+
+        let (msg, result) = match result {
+            Ok(msg) => (msg, Ok(())),
+            Err(msg) => (msg, Err(CommandFailed)),
+        };
+
+        if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+            error!("failed to send kit message: {e}");
+        }
+
+        result
+    }
+}
+
+/// Gives the kit to `caller` if they may claim it, returning the message to show them. The error
+/// explains why the kit could not be claimed.
+fn claim(
+    storage: &KitStorage,
+    commands: &mut Commands<'_, '_>,
+    caller: Entity,
+    uuid: uuid::Uuid,
+    group: Group,
+    name: String,
+) -> Result<String, String> {
+    let kit = match storage.kits.get(&name) {
+        Ok(Some(kit)) if kit.allows(group) => kit,
+        Ok(_) => return Err(format!("§cNo kit is named {name}")),
+        Err(e) => return Err(format!("§cFailed to read kit {name}: {e}")),
+    };
+
+    match storage.remaining_cooldown(uuid, &name, &kit) {
+        Ok(None) => {}
+        Ok(Some(remaining)) => {
+            let remaining = Duration::from_secs(remaining.as_secs().max(1));
+            return Err(format!(
+                "§cYou can claim {name} again in {}",
+                humantime::format_duration(remaining)
+            ));
+        }
+        Err(e) => return Err(format!("§cFailed to read your kit claims: {e}")),
+    }
+
+    let msg = format!("§aClaimed the kit {name}");
+    let storage = storage.clone();
+
+    commands.queue(move |world: &mut World| {
+        // Another claim of the kit may have been given earlier in the same tick
+        if !matches!(storage.remaining_cooldown(uuid, &name, &kit), Ok(None)) {
+            return;
+        }
+
+        let Some(mut inventory) = world.get_mut::<PlayerInventory>(caller) else {
+            return;
+        };
+
+        let warning = match kit.apply(&mut inventory) {
+            Ok(remaining) => {
+                // The claim is only recorded once the items were given
+                if let Err(e) = storage.claim(uuid, &name) {
+                    error!("failed to record claim of kit {name}: {e}");
+                }
+
+                if remaining.is_empty() {
+                    return;
+                }
+
+                "§cSome items of the kit did not fit in your inventory".to_owned()
+            }
+            Err(e) => {
+                error!("failed to give kit {name}: {e}");
+                format!("§cFailed to give you the kit {name}")
+            }
+        };
+
+        let Some(&connection_id) = world.get::<ConnectionId>(caller) else {
+            return;
+        };
+
+        if let Err(e) = world
+            .resource::<Compose>()
+            .unicast(&agnostic::chat(warning), connection_id)
+        {
+            error!("failed to send kit message: {e}");
+        }
+    });
+
+    Ok(msg)
+}
@@ -0,0 +1,238 @@
+//! Kits of items which players can claim with `/kit <name>`.
+//!
+//! Admins create kits from their own inventory with `/kit create`. Each [`Kit`] has a cooldown
+//! between claims and a [`Group`] which players must be in to claim it. Kits and the times they
+//! were last claimed are saved in the [`LocalDb`].
+
+mod command;
+
+use std::{collections::BTreeMap, time::Duration};
+
+use bevy::prelude::*;
+use hyperion::{
+    ItemStack,
+    storage::{Bucket, LocalDb, now_millis},
+    valence_protocol::{Decode, Encode},
+};
+use hyperion_clap::MinecraftCommand;
+use hyperion_inventory::PlayerInventory;
+use hyperion_permission::Group;
+use serde::{Deserialize, Serialize};
+
+use crate::command::KitCommand;
+
+/// A set of items which players can claim
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Kit {
+    /// The items by inventory slot, each encoded in the same format it is sent to clients in
+    items: Vec<(u16, Vec<u8>)>,
+    /// The time players have to wait between claims, in seconds
+    cooldown: u64,
+    /// The lowest group which may claim the kit
+    pub group: Group,
+}
+
+impl Kit {
+    /// Creates a kit with the items in `inventory`
+    pub fn from_inventory(
+        inventory: &PlayerInventory,
+        cooldown: Duration,
+        group: Group,
+    ) -> anyhow::Result<Self> {
+        let items = inventory
+            .items()
+            .map(|(slot, item)| {
+                let mut bytes = Vec::new();
+                item.encode(&mut bytes)?;
+                Ok((slot, bytes))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            items,
+            cooldown: cooldown.as_secs(),
+            group,
+        })
+    }
+
+    /// The items by the inventory slot they were in when the kit was created
+    pub fn items(&self) -> anyhow::Result<Vec<(u16, ItemStack)>> {
+        self.items
+            .iter()
+            .map(|(slot, bytes)| Ok((*slot, ItemStack::decode(&mut bytes.as_slice())?)))
+            .collect()
+    }
+
+    #[must_use]
+    pub const fn cooldown(&self) -> Duration {
+        Duration::from_secs(self.cooldown)
+    }
+
+    /// Whether players in `group` may claim the kit
+    #[must_use]
+    pub const fn allows(&self, group: Group) -> bool {
+        match (self.group, group) {
+            (Group::Banned, group) => matches!(group, Group::Banned),
+            (required, group) => group as u32 >= required as u32,
+        }
+    }
+
+    /// Gives the items of the kit to `inventory`. Items go in the slot they were in when the kit
+    /// was created, or anywhere else if that slot is taken. Returns the items which did not fit.
+    pub fn apply(&self, inventory: &mut PlayerInventory) -> anyhow::Result<Vec<ItemStack>> {
+        let mut displaced = Vec::new();
+
+        for (slot, item) in self.items()? {
+            let free = inventory
+                .get(slot)
+                .is_ok_and(|current| current.stack.is_empty());
+
+            if free {
+                inventory.set(slot, item)?;
+            } else {
+                displaced.push(item);
+            }
+        }
+
+        // Displaced items are added once every other item is in its slot, so they do not take
+        // the slot of another item of the kit
+        Ok(displaced
+            .into_iter()
+            .filter_map(|item| inventory.try_add_item(item).remaining)
+            .collect())
+    }
+}
+
+/// Where kits and the times players last claimed them are saved
+#[derive(Resource, Clone)]
+pub struct KitStorage {
+    /// Kits keyed by name
+    pub kits: Bucket<Kit>,
+    /// The time each player last claimed each kit in milliseconds since the Unix epoch, keyed by
+    /// the uuid of the player and then the name of the kit
+    pub claims: Bucket<BTreeMap<String, u64>>,
+}
+
+impl KitStorage {
+    /// How long `player` has to wait before claiming `kit` again, or `None` if they may claim it
+    pub fn remaining_cooldown(
+        &self,
+        player: uuid::Uuid,
+        name: &str,
+        kit: &Kit,
+    ) -> anyhow::Result<Option<Duration>> {
+        let Some(claimed) = self
+            .claims
+            .get(&player.to_string())?
+            .and_then(|claims| claims.get(name).copied())
+        else {
+            return Ok(None);
+        };
+
+        let elapsed = Duration::from_millis(now_millis().saturating_sub(claimed));
+
+        Ok(kit
+            .cooldown()
+            .checked_sub(elapsed)
+            .filter(|remaining| !remaining.is_zero()))
+    }
+
+    /// Records that `player` claimed the kit with this name now
+    pub fn claim(&self, player: uuid::Uuid, name: &str) -> anyhow::Result<()> {
+        self.claims.update(
+            &player.to_string(),
+            |claims: Option<BTreeMap<String, u64>>| {
+                let mut claims = claims.unwrap_or_default();
+                claims.insert(name.to_owned(), now_millis());
+                Some(claims)
+            },
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Adds `/kit`
+pub struct KitsPlugin;
+
+impl Plugin for KitsPlugin {
+    fn build(&self, app: &mut App) {
+        let db = app.world().resource::<LocalDb>();
+        let storage = KitStorage {
+            kits: db.bucket("hyperion-kits").expect("failed to open kits"),
+            claims: db
+                .bucket("hyperion-kit-claims")
+                .expect("failed to open kit claims"),
+        };
+
+        app.insert_resource(storage);
+
+        KitCommand::register(app.world_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion::{ItemKind, storage::TempLocalDb};
+
+    use super::*;
+
+    #[test]
+    fn test_kit() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(36, ItemStack::new(ItemKind::IronSword, 1, None))
+            .unwrap();
+        inventory
+            .set(37, ItemStack::new(ItemKind::Bread, 16, None))
+            .unwrap();
+
+        let kit = Kit::from_inventory(&inventory, Duration::from_secs(60), Group::Normal).unwrap();
+        assert!(kit.allows(Group::Admin));
+        assert!(!kit.allows(Group::Banned));
+
+        // Items which are in the way of the kit are kept
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(36, ItemStack::new(ItemKind::Stick, 1, None))
+            .unwrap();
+
+        assert!(kit.apply(&mut inventory).unwrap().is_empty());
+        assert_eq!(inventory.get(36).unwrap().stack.item, ItemKind::Stick);
+        assert_eq!(inventory.get(37).unwrap().stack.item, ItemKind::Bread);
+        assert_eq!(inventory.get(38).unwrap().stack.item, ItemKind::IronSword);
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let db = TempLocalDb::new().unwrap();
+        let storage = KitStorage {
+            kits: db.bucket("hyperion-kits").unwrap(),
+            claims: db.bucket("hyperion-kit-claims").unwrap(),
+        };
+
+        let kit = Kit::from_inventory(
+            &PlayerInventory::default(),
+            Duration::from_secs(60),
+            Group::Normal,
+        )
+        .unwrap();
+        let player = uuid::Uuid::nil();
+
+        assert_eq!(
+            storage.remaining_cooldown(player, "food", &kit).unwrap(),
+            None
+        );
+        storage.claim(player, "food").unwrap();
+        assert!(
+            storage
+                .remaining_cooldown(player, "food", &kit)
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(
+            storage.remaining_cooldown(player, "other", &kit).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+use valence_protocol::VarInt;
+
+use crate::{
+    MapId,
+    color::MapColor,
+    font::{GLYPH_HEIGHT, GLYPH_WIDTH, glyph},
+    packet::{MapPatch, MapUpdateS2c},
+};
+
+/// The width and height of a map in pixels
+pub const MAP_SIZE: usize = 128;
+
+/// The gap between characters drawn with [`MapCanvas::draw_text`]
+const LETTER_SPACING: usize = 1;
+
+/// The part of a canvas which changed since it was last sent, in pixels. Both corners are
+/// inclusive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Dirty {
+    min: (u8, u8),
+    max: (u8, u8),
+}
+
+impl Dirty {
+    const FULL: Self = Self {
+        min: (0, 0),
+        max: (127, 127),
+    };
+}
+
+/// The colors of a filled map. Changes are sent to players at the end of the tick.
+///
+/// Drawing outside of the canvas is ignored, so shapes and text may be partially drawn.
+#[derive(Component, Clone, Debug)]
+pub struct MapCanvas {
+    id: MapId,
+    colors: Box<[MapColor; MAP_SIZE * MAP_SIZE]>,
+    dirty: Option<Dirty>,
+}
+
+impl MapCanvas {
+    /// Creates a transparent canvas
+    #[must_use]
+    pub fn new(id: MapId) -> Self {
+        Self {
+            id,
+            colors: Box::new([MapColor::TRANSPARENT; MAP_SIZE * MAP_SIZE]),
+            dirty: Some(Dirty::FULL),
+        }
+    }
+
+    #[must_use]
+    pub const fn id(&self) -> MapId {
+        self.id
+    }
+
+    #[must_use]
+    pub fn get(&self, x: usize, y: usize) -> Option<MapColor> {
+        if x >= MAP_SIZE || y >= MAP_SIZE {
+            return None;
+        }
+
+        Some(self.colors[y * MAP_SIZE + x])
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: MapColor) {
+        let (Ok(x8), Ok(y8)) = (u8::try_from(x), u8::try_from(y)) else {
+            return;
+        };
+
+        if x >= MAP_SIZE || y >= MAP_SIZE {
+            return;
+        }
+
+        let pixel = &mut self.colors[y * MAP_SIZE + x];
+        if *pixel == color {
+            return;
+        }
+        *pixel = color;
+
+        self.dirty = Some(match self.dirty {
+            Some(Dirty { min, max }) => Dirty {
+                min: (min.0.min(x8), min.1.min(y8)),
+                max: (max.0.max(x8), max.1.max(y8)),
+            },
+            None => Dirty {
+                min: (x8, y8),
+                max: (x8, y8),
+            },
+        });
+    }
+
+    pub fn fill(&mut self, color: MapColor) {
+        self.fill_rect(0, 0, MAP_SIZE, MAP_SIZE, color);
+    }
+
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: MapColor) {
+        for y in y..(y + height).min(MAP_SIZE) {
+            for x in x..(x + width).min(MAP_SIZE) {
+                self.set(x, y, color);
+            }
+        }
+    }
+
+    /// Draws an image of RGBA pixels, row by row, at `(x, y)`. Each pixel is drawn with the
+    /// closest [`MapColor`], and pixels with an alpha below 128 are skipped.
+    pub fn draw_image(&mut self, x: usize, y: usize, width: usize, rgba: &[u8]) {
+        if width == 0 {
+            return;
+        }
+
+        for (i, pixel) in rgba.chunks_exact(4).enumerate() {
+            let &[r, g, b, a] = pixel else {
+                continue;
+            };
+
+            if a < 128 {
+                continue;
+            }
+
+            self.set(x + i % width, y + i / width, MapColor::nearest([r, g, b]));
+        }
+    }
+
+    /// Draws text with its top left corner at `(x, y)` and returns the width of the text in
+    /// pixels. See [`GLYPH_WIDTH`] and [`GLYPH_HEIGHT`].
+    pub fn draw_text(&mut self, x: usize, y: usize, text: &str, color: MapColor) -> usize {
+        let advance = usize::from(GLYPH_WIDTH) + LETTER_SPACING;
+
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i * advance;
+
+            for (row, bits) in glyph(c).into_iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        self.set(left + usize::from(column), y + row, color);
+                    }
+                }
+            }
+        }
+
+        (text.chars().count() * advance).saturating_sub(LETTER_SPACING)
+    }
+
+    /// Marks the whole canvas to be sent again
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty = Some(Dirty::FULL);
+    }
+
+    /// Whether the canvas changed since it was last sent
+    #[must_use]
+    pub const fn is_dirty(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    pub(crate) fn clear_dirty(&mut self) {
+        self.dirty = None;
+    }
+
+    /// The packet which sends the whole canvas
+    pub(crate) fn full_packet(&self, buf: &mut Vec<u8>) -> MapUpdateS2c<'_> {
+        self.packet(Dirty::FULL, buf)
+    }
+
+    /// The packet which sends the part of the canvas which changed, if anything changed
+    pub(crate) fn dirty_packet<'a>(&self, buf: &'a mut Vec<u8>) -> Option<MapUpdateS2c<'a>> {
+        Some(self.packet(self.dirty?, buf))
+    }
+
+    fn packet<'a>(&self, dirty: Dirty, buf: &'a mut Vec<u8>) -> MapUpdateS2c<'a> {
+        let Dirty { min, max } = dirty;
+
+        buf.clear();
+        for y in min.1..=max.1 {
+            let row = usize::from(y) * MAP_SIZE;
+            let columns = &self.colors[row + usize::from(min.0)..=row + usize::from(max.0)];
+            buf.extend(columns.iter().map(|color| color.0));
+        }
+
+        MapUpdateS2c {
+            map_id: VarInt(self.id.0),
+            scale: 0,
+            locked: true,
+            patch: Some(MapPatch {
+                columns: max.0 - min.0 + 1,
+                rows: max.1 - min.1 + 1,
+                x: min.0,
+                z: min.1,
+                colors: buf,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dirty_patch() {
+        let mut canvas = MapCanvas::new(MapId(0));
+        canvas.clear_dirty();
+
+        let mut buf = Vec::new();
+        assert!(canvas.dirty_packet(&mut buf).is_none());
+
+        canvas.set(3, 4, MapColor::RED);
+        canvas.set(5, 2, MapColor::WHITE);
+        // Pixels outside of the canvas are ignored
+        canvas.set(MAP_SIZE, 0, MapColor::WHITE);
+
+        let packet = canvas.dirty_packet(&mut buf).unwrap();
+        let patch = packet.patch.unwrap();
+        assert_eq!((patch.x, patch.z, patch.columns, patch.rows), (3, 2, 3, 3));
+        assert_eq!(patch.colors[2], MapColor::WHITE.0);
+        assert_eq!(patch.colors[2 * 3], MapColor::RED.0);
+    }
+
+    #[test]
+    fn test_draw_text_width() {
+        let mut canvas = MapCanvas::new(MapId(0));
+        assert_eq!(canvas.draw_text(0, 0, "AB", MapColor::BLACK), 11);
+        assert_eq!(canvas.get(0, 1), Some(MapColor::BLACK));
+    }
+}
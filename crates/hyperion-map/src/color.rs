@@ -0,0 +1,169 @@
+/// The brightness of a map color
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Shade {
+    Dark = 0,
+    Normal = 1,
+    Light = 2,
+    Darkest = 3,
+}
+
+impl Shade {
+    const ALL: [Self; 4] = [Self::Dark, Self::Normal, Self::Light, Self::Darkest];
+
+    /// How much the base color is multiplied by, out of 255
+    const fn multiplier(self) -> u32 {
+        match self {
+            Self::Dark => 180,
+            Self::Normal => 220,
+            Self::Light => 255,
+            Self::Darkest => 135,
+        }
+    }
+}
+
+/// The base colors of maps in vanilla. The first color is transparent.
+const BASE_COLORS: [[u8; 3]; 62] = [
+    [0, 0, 0],
+    [127, 178, 56],
+    [247, 233, 163],
+    [199, 199, 199],
+    [255, 0, 0],
+    [160, 160, 255],
+    [167, 167, 167],
+    [0, 124, 0],
+    [255, 255, 255],
+    [164, 168, 184],
+    [151, 109, 77],
+    [112, 112, 112],
+    [64, 64, 255],
+    [143, 119, 72],
+    [255, 252, 245],
+    [216, 127, 51],
+    [178, 76, 216],
+    [102, 153, 216],
+    [229, 229, 51],
+    [127, 204, 25],
+    [242, 127, 165],
+    [76, 76, 76],
+    [153, 153, 153],
+    [76, 127, 153],
+    [127, 63, 178],
+    [51, 76, 178],
+    [102, 76, 51],
+    [102, 127, 51],
+    [153, 51, 51],
+    [25, 25, 25],
+    [250, 238, 77],
+    [92, 219, 213],
+    [74, 128, 255],
+    [0, 217, 58],
+    [129, 86, 49],
+    [112, 2, 0],
+    [209, 177, 161],
+    [159, 82, 36],
+    [149, 87, 108],
+    [112, 108, 138],
+    [186, 133, 36],
+    [103, 117, 53],
+    [160, 77, 78],
+    [57, 41, 35],
+    [135, 107, 98],
+    [87, 92, 92],
+    [122, 73, 88],
+    [76, 62, 92],
+    [76, 50, 35],
+    [76, 82, 42],
+    [142, 60, 46],
+    [37, 22, 16],
+    [189, 48, 49],
+    [148, 63, 97],
+    [92, 25, 29],
+    [22, 126, 134],
+    [58, 142, 140],
+    [86, 44, 62],
+    [20, 180, 133],
+    [100, 100, 100],
+    [216, 175, 147],
+    [127, 167, 150],
+];
+
+/// A color which can be shown on a map. Each color is a base color with a [`Shade`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MapColor(pub u8);
+
+impl MapColor {
+    pub const BLACK: Self = Self::new(29, Shade::Light);
+    pub const BLUE: Self = Self::new(25, Shade::Light);
+    pub const GRAY: Self = Self::new(21, Shade::Light);
+    pub const GREEN: Self = Self::new(27, Shade::Light);
+    pub const LIGHT_GRAY: Self = Self::new(22, Shade::Light);
+    pub const ORANGE: Self = Self::new(15, Shade::Light);
+    pub const RED: Self = Self::new(28, Shade::Light);
+    pub const TRANSPARENT: Self = Self(0);
+    pub const WHITE: Self = Self::new(8, Shade::Light);
+    pub const YELLOW: Self = Self::new(18, Shade::Light);
+
+    /// Creates a color from the index of its base color in the vanilla map palette
+    #[must_use]
+    pub const fn new(base: u8, shade: Shade) -> Self {
+        Self(base * 4 + shade as u8)
+    }
+
+    #[must_use]
+    pub const fn is_transparent(self) -> bool {
+        self.0 / 4 == 0
+    }
+
+    /// The RGB value of this color, or `None` if it is transparent or not a valid color
+    #[must_use]
+    pub fn rgb(self) -> Option<[u8; 3]> {
+        let base = usize::from(self.0 / 4);
+        if base == 0 || base >= BASE_COLORS.len() {
+            return None;
+        }
+
+        let shade = Shade::ALL[usize::from(self.0 % 4)];
+        let [r, g, b] = BASE_COLORS[base];
+
+        #[expect(clippy::cast_possible_truncation, reason = "the result is at most 255")]
+        let scale = |value: u8| (u32::from(value) * shade.multiplier() / 255) as u8;
+
+        Some([scale(r), scale(g), scale(b)])
+    }
+
+    /// The map color which is closest to an RGB value
+    #[must_use]
+    pub fn nearest([r, g, b]: [u8; 3]) -> Self {
+        let distance = |[r2, g2, b2]: [u8; 3]| {
+            let dr = i32::from(r) - i32::from(r2);
+            let dg = i32::from(g) - i32::from(g2);
+            let db = i32::from(b) - i32::from(b2);
+            dr * dr + dg * dg + db * db
+        };
+
+        (4..=u8::MAX)
+            .map(Self)
+            .filter_map(|color| Some((color, distance(color.rgb()?))))
+            .min_by_key(|&(_, distance)| distance)
+            .map_or(Self::BLACK, |(color, _)| color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nearest_is_exact_for_palette_colors() {
+        for color in [MapColor::WHITE, MapColor::RED, MapColor::BLACK] {
+            assert_eq!(MapColor::nearest(color.rgb().unwrap()), color);
+        }
+    }
+
+    #[test]
+    fn test_transparent() {
+        assert!(MapColor::TRANSPARENT.is_transparent());
+        assert_eq!(MapColor::TRANSPARENT.rgb(), None);
+        assert!(!MapColor::WHITE.is_transparent());
+    }
+}
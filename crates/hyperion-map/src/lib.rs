@@ -0,0 +1,139 @@
+//! Drawing on filled maps.
+//!
+//! A [`MapCanvas`] is a 128×128 buffer of [`MapColor`]s. Changed pixels are sent to players at the
+//! end of every tick, so canvases can be redrawn every tick for minimaps or only when needed for
+//! leaderboards and pixel art in item frames. Canvases are shared by every player unless they
+//! have a [`MapViewer`], which is how each player can be shown their own map.
+//!
+//! ```ignore
+//! let id = map_ids.allocate();
+//! let mut canvas = MapCanvas::new(id);
+//! canvas.fill(MapColor::WHITE);
+//! canvas.draw_text(4, 4, "Top kills", MapColor::BLACK);
+//! commands.spawn((canvas, MapViewer(player)));
+//!
+//! inventory.set_hotbar(0, map_item(id))?;
+//! ```
+
+use bevy::prelude::*;
+use hyperion::{
+    ItemKind, ItemStack,
+    net::{Compose, ConnectionId},
+    simulation::packet_state,
+    timings::timed,
+};
+use tracing::error;
+use valence_protocol::nbt;
+
+mod canvas;
+mod color;
+mod font;
+mod packet;
+
+pub use canvas::{MAP_SIZE, MapCanvas};
+pub use color::{MapColor, Shade};
+pub use font::{GLYPH_HEIGHT, GLYPH_WIDTH};
+pub use packet::{MapPatch, MapUpdateS2c};
+
+/// The id of a filled map, which is stored in the `map` tag of the map item
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MapId(pub i32);
+
+/// Allocates unique [`MapId`]s
+#[derive(Resource, Debug, Default)]
+pub struct MapIds {
+    next: i32,
+}
+
+impl MapIds {
+    pub const fn allocate(&mut self) -> MapId {
+        let id = MapId(self.next);
+        self.next += 1;
+        id
+    }
+}
+
+/// Makes a [`MapCanvas`] only visible to one player. Other players see the map as it was drawn
+/// for them, or as empty if it was never drawn for them.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MapViewer(pub Entity);
+
+/// Creates a filled map item which shows the map with this id
+#[must_use]
+pub fn map_item(id: MapId) -> ItemStack {
+    let mut nbt = nbt::Compound::new();
+    nbt.insert("map", nbt::Value::Int(id.0));
+
+    ItemStack::new(ItemKind::FilledMap, 1, Some(nbt))
+}
+
+/// Sends the part of each canvas which changed
+fn sync_canvases(
+    mut canvases: Query<'_, '_, (&mut MapCanvas, Option<&MapViewer>), Changed<MapCanvas>>,
+    viewers: Query<'_, '_, &ConnectionId>,
+    compose: Res<'_, Compose>,
+) {
+    let mut buf = Vec::new();
+
+    for (mut canvas, viewer) in &mut canvases {
+        let Some(packet) = canvas.dirty_packet(&mut buf) else {
+            continue;
+        };
+
+        let result = match viewer {
+            Some(&MapViewer(viewer)) => match viewers.get(viewer) {
+                Ok(&connection_id) => compose.unicast(&packet, connection_id),
+                // The viewer may have left
+                Err(_) => Ok(()),
+            },
+            None => compose.broadcast(&packet).send(),
+        };
+
+        if let Err(e) = result {
+            error!("failed to send map canvas: {e}");
+        }
+
+        canvas.bypass_change_detection().clear_dirty();
+    }
+}
+
+/// Sends every shared canvas to players who join
+fn send_canvases(
+    trigger: Trigger<'_, OnAdd, packet_state::Play>,
+    query: Query<'_, '_, &ConnectionId>,
+    canvases: Query<'_, '_, &MapCanvas, Without<MapViewer>>,
+    compose: Res<'_, Compose>,
+) {
+    let Ok(&connection_id) = query.get(trigger.target()) else {
+        return;
+    };
+
+    let mut buf = Vec::new();
+
+    for canvas in &canvases {
+        if let Err(e) = compose.unicast(&canvas.full_packet(&mut buf), connection_id) {
+            error!("failed to send map canvas: {e}");
+        }
+    }
+}
+
+/// Sends the whole canvas to its new viewer
+fn change_viewer(
+    trigger: Trigger<'_, OnInsert, MapViewer>,
+    mut query: Query<'_, '_, &mut MapCanvas>,
+) {
+    if let Ok(mut canvas) = query.get_mut(trigger.target()) {
+        canvas.mark_all_dirty();
+    }
+}
+
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapIds>();
+        app.add_observer(send_canvases);
+        app.add_observer(change_viewer);
+        app.add_systems(FixedPostUpdate, timed(sync_canvases));
+    }
+}
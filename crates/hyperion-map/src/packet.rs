@@ -0,0 +1,48 @@
+use std::io::Write;
+
+use valence_protocol::{Encode, Packet, VarInt};
+
+/// A rectangle of map colors
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapPatch<'a> {
+    pub columns: u8,
+    pub rows: u8,
+    pub x: u8,
+    pub z: u8,
+    /// The colors of the rectangle, row by row
+    pub colors: &'a [u8],
+}
+
+/// Updates the colors of a filled map. Map icons are not supported.
+#[derive(Clone, Debug, PartialEq, Eq, Packet)]
+pub struct MapUpdateS2c<'a> {
+    pub map_id: VarInt,
+    pub scale: i8,
+    pub locked: bool,
+    pub patch: Option<MapPatch<'a>>,
+}
+
+impl Encode for MapUpdateS2c<'_> {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        self.map_id.encode(&mut w)?;
+        self.scale.encode(&mut w)?;
+        self.locked.encode(&mut w)?;
+
+        // There are no icons
+        false.encode(&mut w)?;
+
+        let Some(patch) = &self.patch else {
+            // Zero columns means that there is no patch
+            return 0_u8.encode(w);
+        };
+
+        patch.columns.encode(&mut w)?;
+        patch.rows.encode(&mut w)?;
+        patch.x.encode(&mut w)?;
+        patch.z.encode(&mut w)?;
+        VarInt(i32::try_from(patch.colors.len())?).encode(&mut w)?;
+        w.write_all(patch.colors)?;
+
+        Ok(())
+    }
+}
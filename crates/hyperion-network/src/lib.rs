@@ -0,0 +1,389 @@
+//! Sharing data between several hyperion servers with Redis.
+//!
+//! - [`Network::get_player_data`] and [`Network::set_player_data`] cache player data for every
+//!   server, such as statistics which should not be loaded again when a player switches server.
+//! - [`Network::publish`] sends a [`NetworkMessage`] to every server, which receive it as a
+//!   [`NetworkEvent`]. Chat messages and announcements from other servers are shown to players.
+//! - [`Network::locate`] finds the server a player is on.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, bail};
+use bevy::prelude::*;
+use futures_util::StreamExt;
+use hyperion::{
+    command_channel::CommandChannel,
+    net::{Compose, agnostic},
+    runtime::AsyncRuntime,
+    simulation::{Uuid, packet_state},
+    tick_rate::{AdvanceTick, TickRate},
+    timings::timed,
+};
+use redis::{AsyncCommands, aio::ConnectionManager};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::{error, warn};
+
+/// The channel messages between servers are published on
+const CHANNEL: &str = "hyperion:events";
+
+/// How long cached player data is kept for
+pub const PLAYER_DATA_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How long the server of a player is known for without being refreshed. This only matters if a
+/// server stops without removing its players.
+const LOCATOR_TTL: Duration = Duration::from_secs(60);
+
+/// How often the servers of players are refreshed
+const LOCATOR_REFRESH_INTERVAL: Duration = Duration::from_secs(20);
+
+/// How long to wait before subscribing to network messages again after the subscription is lost.
+/// The delay doubles every time subscribing fails, up to [`MAX_RESUBSCRIBE_DELAY`].
+const INITIAL_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(30);
+
+/// Removes the server of a player only if it was not replaced by the server they switched to
+const REMOVE_LOCATION: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+";
+
+/// A message sent to every server in the network
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NetworkMessage {
+    /// A chat message which is shown on every server
+    Chat { sender: String, message: String },
+    /// An announcement which is shown on every server
+    Announcement { message: String },
+    /// A message for other plugins, which is not handled by this crate
+    Custom {
+        channel: String,
+        payload: serde_json::Value,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    origin: String,
+    message: NetworkMessage,
+}
+
+/// A message received from another server
+#[derive(Event, Clone, Debug)]
+pub struct NetworkEvent {
+    /// The id of the server which sent the message
+    pub origin: String,
+    pub message: NetworkMessage,
+}
+
+fn player_data_key(uuid: uuid::Uuid, namespace: &str) -> String {
+    format!("hyperion:player:{uuid}:{namespace}")
+}
+
+fn location_key(uuid: uuid::Uuid) -> String {
+    format!("hyperion:locator:{uuid}")
+}
+
+/// A connection to the Redis server shared by every server in the network
+#[derive(Resource, Clone)]
+pub struct Network {
+    server_id: String,
+    connection: ConnectionManager,
+    runtime: AsyncRuntime,
+}
+
+impl Network {
+    /// The id of this server
+    #[must_use]
+    pub fn server_id(&self) -> &str {
+        &self.server_id
+    }
+
+    /// Returns the data of a player stored under `namespace` by any server.
+    pub async fn get_player_data<T: DeserializeOwned>(
+        &self,
+        uuid: uuid::Uuid,
+        namespace: &str,
+    ) -> anyhow::Result<Option<T>> {
+        let mut connection = self.connection.clone();
+        let bytes: Option<Vec<u8>> = connection.get(player_data_key(uuid, namespace)).await?;
+
+        bytes
+            .map(|bytes| serde_json::from_slice(&bytes))
+            .transpose()
+            .context("failed to parse player data")
+    }
+
+    /// Stores the data of a player under `namespace` for [`PLAYER_DATA_TTL`].
+    pub async fn set_player_data<T: Serialize>(
+        &self,
+        uuid: uuid::Uuid,
+        namespace: &str,
+        data: &T,
+    ) -> anyhow::Result<()> {
+        let bytes = serde_json::to_vec(data)?;
+
+        let mut connection = self.connection.clone();
+        connection
+            .set_ex::<_, _, ()>(
+                player_data_key(uuid, namespace),
+                bytes,
+                PLAYER_DATA_TTL.as_secs(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the id of the server the player is on, if they are online.
+    pub async fn locate(&self, uuid: uuid::Uuid) -> anyhow::Result<Option<String>> {
+        let mut connection = self.connection.clone();
+        Ok(connection.get(location_key(uuid)).await?)
+    }
+
+    /// Sends `message` to every other server in the background.
+    pub fn publish(&self, message: NetworkMessage) {
+        let envelope = Envelope {
+            origin: self.server_id.clone(),
+            message,
+        };
+
+        let payload = match serde_json::to_vec(&envelope) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("failed to publish network message: {e}");
+                return;
+            }
+        };
+
+        let mut connection = self.connection.clone();
+        self.runtime.spawn(async move {
+            if let Err(e) = connection.publish::<_, _, ()>(CHANNEL, payload).await {
+                error!("failed to publish network message: {e}");
+            }
+        });
+    }
+
+    fn set_locations(&self, uuids: Vec<uuid::Uuid>) {
+        let server_id = self.server_id.clone();
+        let mut connection = self.connection.clone();
+
+        self.runtime.spawn(async move {
+            let mut pipe = redis::pipe();
+            for uuid in uuids {
+                pipe.set_ex(location_key(uuid), &server_id, LOCATOR_TTL.as_secs())
+                    .ignore();
+            }
+
+            if let Err(e) = pipe.query_async::<()>(&mut connection).await {
+                error!("failed to update player locations: {e}");
+            }
+        });
+    }
+
+    fn remove_location(&self, uuid: uuid::Uuid) {
+        let server_id = self.server_id.clone();
+        let mut connection = self.connection.clone();
+
+        self.runtime.spawn(async move {
+            let result = redis::Script::new(REMOVE_LOCATION)
+                .key(location_key(uuid))
+                .arg(server_id)
+                .invoke_async::<()>(&mut connection)
+                .await;
+
+            if let Err(e) = result {
+                error!("failed to remove player location: {e}");
+            }
+        });
+    }
+}
+
+/// Receives messages from other servers and sends them as [`NetworkEvent`]s until the
+/// subscription is lost, which is always an error
+async fn subscribe(
+    client: &redis::Client,
+    server_id: &str,
+    command_channel: &CommandChannel,
+) -> anyhow::Result<()> {
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(CHANNEL).await?;
+
+    let mut messages = pubsub.on_message();
+    while let Some(message) = messages.next().await {
+        let envelope = match serde_json::from_slice::<Envelope>(message.get_payload_bytes()) {
+            Ok(envelope) => envelope,
+            Err(e) => {
+                warn!("received invalid network message: {e}");
+                continue;
+            }
+        };
+
+        if envelope.origin == server_id {
+            continue;
+        }
+
+        command_channel.push(move |world: &mut World| {
+            world.send_event(NetworkEvent {
+                origin: envelope.origin,
+                message: envelope.message,
+            });
+        });
+    }
+
+    bail!("the connection to redis was closed")
+}
+
+/// Keeps subscribing to network messages, waiting longer between attempts while subscribing fails
+async fn subscribe_forever(
+    client: redis::Client,
+    server_id: String,
+    command_channel: CommandChannel,
+) {
+    let mut delay = INITIAL_RESUBSCRIBE_DELAY;
+
+    loop {
+        let subscribed_at = Instant::now();
+
+        if let Err(e) = subscribe(&client, &server_id, &command_channel).await {
+            error!("stopped receiving network messages: {e}");
+        }
+
+        // A subscription which lasted for a while was working, so the delay starts over
+        if subscribed_at.elapsed() > MAX_RESUBSCRIBE_DELAY {
+            delay = INITIAL_RESUBSCRIBE_DELAY;
+        }
+
+        warn!("subscribing to network messages again in {delay:?}");
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RESUBSCRIBE_DELAY);
+    }
+}
+
+fn add_location(
+    trigger: Trigger<'_, OnAdd, packet_state::Play>,
+    query: Query<'_, '_, &Uuid>,
+    network: Res<'_, Network>,
+) {
+    let Ok(uuid) = query.get(trigger.target()) else {
+        return;
+    };
+
+    network.set_locations(vec![**uuid]);
+}
+
+fn remove_location(
+    trigger: Trigger<'_, OnRemove, packet_state::Play>,
+    query: Query<'_, '_, &Uuid>,
+    network: Res<'_, Network>,
+) {
+    let Ok(uuid) = query.get(trigger.target()) else {
+        return;
+    };
+
+    network.remove_location(**uuid);
+}
+
+fn refresh_locations(
+    compose: Res<'_, Compose>,
+    query: Query<'_, '_, &Uuid, With<packet_state::Play>>,
+    network: Res<'_, Network>,
+    tick_rate: Res<'_, TickRate>,
+) {
+    let interval = tick_rate.ticks_in(LOCATOR_REFRESH_INTERVAL).max(1);
+    if compose.global().tick % interval != 0 {
+        return;
+    }
+
+    let uuids = query.iter().map(|uuid| **uuid).collect::<Vec<_>>();
+    if !uuids.is_empty() {
+        network.set_locations(uuids);
+    }
+}
+
+fn show_network_messages(mut events: EventReader<'_, '_, NetworkEvent>, compose: Res<'_, Compose>) {
+    for event in events.read() {
+        let chat = match &event.message {
+            NetworkMessage::Chat { sender, message } => {
+                agnostic::chat(format!("§8[{}] §7<{sender}> §f{message}", event.origin))
+            }
+            NetworkMessage::Announcement { message } => agnostic::chat(message.clone()),
+            NetworkMessage::Custom { .. } => continue,
+        };
+
+        if let Err(e) = compose.broadcast(&chat).send() {
+            error!("failed to send network message: {e}");
+        }
+    }
+}
+
+/// Connects to the Redis server at `redis_url`. Every server in the network must have a unique
+/// `server_id`.
+pub struct NetworkPlugin {
+    pub redis_url: String,
+    pub server_id: String,
+}
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        let runtime = app.world().resource::<AsyncRuntime>().clone();
+        let command_channel = app.world().resource::<CommandChannel>().clone();
+
+        let client = redis::Client::open(self.redis_url.as_str()).expect("invalid redis url");
+        let connection = runtime
+            .block_on(ConnectionManager::new(client.clone()))
+            .expect("failed to connect to redis");
+
+        runtime.spawn(subscribe_forever(
+            client,
+            self.server_id.clone(),
+            command_channel,
+        ));
+
+        app.insert_resource(Network {
+            server_id: self.server_id.clone(),
+            connection,
+            runtime,
+        });
+        app.add_event::<NetworkEvent>();
+        app.add_observer(add_location);
+        app.add_observer(remove_location);
+        app.add_systems(
+            FixedUpdate,
+            (
+                timed(refresh_locations).in_set(AdvanceTick),
+                timed(show_network_messages),
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_format() {
+        let envelope = Envelope {
+            origin: "lobby-1".to_owned(),
+            message: NetworkMessage::Chat {
+                sender: "Notch".to_owned(),
+                message: "hello".to_owned(),
+            },
+        };
+
+        let json = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "origin": "lobby-1",
+                "message": { "type": "chat", "sender": "Notch", "message": "hello" },
+            })
+        );
+
+        let parsed = serde_json::from_value::<Envelope>(json).unwrap();
+        assert_eq!(parsed.message, envelope.message);
+    }
+}
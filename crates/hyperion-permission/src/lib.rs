@@ -1,13 +1,21 @@
 mod storage;
 
-use bevy::{ecs::world::OnDespawn, prelude::*};
+use std::collections::HashSet;
+
+use bevy::{
+    ecs::{component::Tick, world::OnDespawn},
+    prelude::*,
+};
 use clap::ValueEnum;
 use hyperion::{
+    command_channel::CommandChannel,
     net::{Compose, ConnectionId},
-    simulation::{Uuid, command::get_command_packet},
-    storage::LocalDb,
+    runtime::AsyncRuntime,
+    simulation::{Uuid, command::get_command_packet, event::PlayerLimitReached, kick::KickExt},
+    storage::Storage,
 };
 use num_derive::{FromPrimitive, ToPrimitive};
+use serde::{Deserialize, Serialize};
 use storage::PermissionStorage;
 use tracing::error;
 
@@ -23,7 +31,9 @@ pub struct PermissionPlugin;
     Debug,
     PartialEq,
     ValueEnum,
-    Eq
+    Eq,
+    Serialize,
+    Deserialize
 )]
 #[repr(C)]
 pub enum Group {
@@ -34,28 +44,104 @@ pub enum Group {
     Admin,
 }
 
-// todo:
+impl Group {
+    /// Whether players in this group may join when the server is full
+    #[must_use]
+    pub const fn bypasses_player_limit(self) -> bool {
+        matches!(self, Self::Moderator | Self::Admin)
+    }
+}
+
+/// The players who may join when the server is full. This is loaded when the server starts and
+/// kept up to date as groups change on this server.
+#[derive(Resource, Default, Debug)]
+struct ReservedSlots(HashSet<uuid::Uuid>);
+
+/// The players in [`Group::Banned`], so they can be kicked as soon as they join instead of once
+/// their group is loaded. This is loaded when the server starts and kept up to date as groups
+/// change on this server.
+#[derive(Resource, Default, Debug)]
+struct BannedPlayers(HashSet<uuid::Uuid>);
+
+/// A player whose stored [`Group`] is still being loaded, with the tick their placeholder group
+/// was inserted at. The stored group only replaces the placeholder if the group was not changed
+/// in the meantime.
+#[derive(Component, Debug)]
+struct LoadingGroup(Tick);
 
 fn load_permissions(
     trigger: Trigger<'_, OnAdd, Uuid>,
     query: Query<'_, '_, &Uuid, With<ConnectionId>>,
+    banned: Res<'_, BannedPlayers>,
     permissions: Res<'_, PermissionStorage>,
+    runtime: Res<'_, AsyncRuntime>,
+    command_channel: Res<'_, CommandChannel>,
     mut commands: Commands<'_, '_>,
 ) {
     let Ok(uuid) = query.get(trigger.target()) else {
         return;
     };
 
-    let group = permissions.get(**uuid);
-    commands.entity(trigger.target()).insert(group);
+    let uuid = **uuid;
+    let entity = trigger.target();
+
+    // Players always have a group, so until the stored one is loaded they are normal players, or
+    // kicked right away if they are known to be banned
+    let placeholder = if banned.0.contains(&uuid) {
+        Group::Banned
+    } else {
+        Group::default()
+    };
+
+    commands.queue(move |world: &mut World| {
+        let Ok(mut player) = world.get_entity_mut(entity) else {
+            return;
+        };
+
+        player.insert(placeholder);
+        if let Some(tick) = player.get_ref::<Group>().map(|group| group.last_changed()) {
+            player.insert(LoadingGroup(tick));
+        }
+    });
+
+    let permissions = permissions.clone();
+    let command_channel = command_channel.clone();
+
+    runtime.spawn(async move {
+        let group = permissions.get(uuid).await.unwrap_or_else(|e| {
+            error!("failed to load permissions: {e}");
+            Group::default()
+        });
+
+        command_channel.push(move |world: &mut World| {
+            // The player may have left while the permissions were loading
+            let Ok(mut player) = world.get_entity_mut(entity) else {
+                return;
+            };
+
+            let Some(LoadingGroup(placeholder)) = player.take::<LoadingGroup>() else {
+                return;
+            };
+
+            // A group which was set while loading, such as by an admin, is newer than the stored one
+            let unchanged = player
+                .get_ref::<Group>()
+                .is_some_and(|group| group.last_changed() == placeholder);
+
+            if unchanged {
+                player.insert(group);
+            }
+        });
+    });
 }
 
 fn store_permissions(
     trigger: Trigger<'_, OnDespawn, Group>,
-    query: Query<'_, '_, (&Uuid, &Group)>,
+    query: Query<'_, '_, (&Uuid, &Group, Has<LoadingGroup>)>,
     permissions: Res<'_, PermissionStorage>,
+    runtime: Res<'_, AsyncRuntime>,
 ) {
-    let (uuid, group) = match query.get(trigger.target()) {
+    let (uuid, group, loading) = match query.get(trigger.target()) {
         Ok(data) => data,
         Err(e) => {
             error!("failed to store permissions: query failed: {e}");
@@ -63,7 +149,21 @@ fn store_permissions(
         }
     };
 
-    permissions.set(**uuid, *group).unwrap();
+    // The placeholder group of a player who left before their group was loaded must not replace
+    // the stored one
+    if loading {
+        return;
+    }
+
+    let uuid = **uuid;
+    let group = *group;
+    let permissions = permissions.clone();
+
+    runtime.spawn(async move {
+        if let Err(e) = permissions.set(uuid, group).await {
+            error!("failed to store permissions: {e}");
+        }
+    });
 }
 
 fn initialize_commands(
@@ -77,15 +177,102 @@ fn initialize_commands(
         error!("failed to initialize commands: player is missing ConnectionId");
         return;
     };
-    compose.unicast(&cmd_pkt, connection_id).unwrap();
+    if let Err(e) = compose.unicast(&cmd_pkt, connection_id) {
+        error!("failed to initialize commands: {e}");
+    }
+}
+
+fn update_reserved_slots(
+    trigger: Trigger<'_, OnInsert, Group>,
+    query: Query<'_, '_, (&Uuid, &Group)>,
+    mut reserved: ResMut<'_, ReservedSlots>,
+) {
+    let Ok((uuid, group)) = query.get(trigger.target()) else {
+        return;
+    };
+
+    if group.bypasses_player_limit() {
+        reserved.0.insert(**uuid);
+    } else {
+        reserved.0.remove(&**uuid);
+    }
+}
+
+fn update_banned_players(
+    trigger: Trigger<'_, OnInsert, Group>,
+    query: Query<'_, '_, (&Uuid, &Group)>,
+    mut banned: ResMut<'_, BannedPlayers>,
+) {
+    let Ok((uuid, &group)) = query.get(trigger.target()) else {
+        return;
+    };
+
+    if group == Group::Banned {
+        banned.0.insert(**uuid);
+    } else {
+        banned.0.remove(&**uuid);
+    }
+}
+
+/// Disconnects players once they are in [`Group::Banned`], which also happens when a banned
+/// player joins and their group is loaded
+fn kick_banned(
+    trigger: Trigger<'_, OnInsert, Group>,
+    query: Query<'_, '_, &Group, With<ConnectionId>>,
+    mut commands: Commands<'_, '_>,
+) {
+    let banned = query
+        .get(trigger.target())
+        .is_ok_and(|&group| group == Group::Banned);
+
+    if banned {
+        commands
+            .entity(trigger.target())
+            .kick("§cYou are banned from this server");
+    }
+}
+
+fn bypass_player_limit(
+    mut trigger: Trigger<'_, PlayerLimitReached>,
+    reserved: Res<'_, ReservedSlots>,
+) {
+    if reserved.0.contains(&trigger.event().uuid) {
+        trigger.event_mut().allow = true;
+    }
 }
 
 impl Plugin for PermissionPlugin {
     fn build(&self, app: &mut App) {
-        let storage = storage::PermissionStorage::new(app.world().resource::<LocalDb>()).unwrap();
+        let storage = PermissionStorage::new(app.world().resource::<Storage>().clone());
+
+        let groups = app
+            .world()
+            .resource::<AsyncRuntime>()
+            .block_on(storage.all())
+            .unwrap_or_else(|e| {
+                error!("failed to load the groups of players: {e}");
+                Vec::new()
+            });
+        let reserved = groups
+            .iter()
+            .filter(|(_, group)| group.bypasses_player_limit())
+            .map(|&(uuid, _)| uuid)
+            .collect();
+        let banned = groups
+            .iter()
+            .filter(|&&(_, group)| group == Group::Banned)
+            .map(|&(uuid, _)| uuid)
+            .collect();
+
         app.insert_resource(storage);
+        app.insert_resource(ReservedSlots(reserved));
+        app.insert_resource(BannedPlayers(banned));
         app.add_observer(load_permissions);
         app.add_observer(store_permissions);
         app.add_observer(initialize_commands);
+        app.add_observer(update_reserved_slots);
+        app.add_observer(update_banned_players);
+        app.add_observer(kick_banned);
+        app.add_observer(bypass_player_limit);
     }
 }
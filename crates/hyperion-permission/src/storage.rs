@@ -1,52 +1,57 @@
 use bevy::prelude::*;
-use heed::{Database, Env, byteorder::NativeEndian, types};
-use hyperion::storage::LocalDb;
+use hyperion::storage::Storage;
 use num_traits::{FromPrimitive, ToPrimitive};
 
 use crate::Group;
 
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 pub struct PermissionStorage {
-    env: Env,
-    perms: Database<types::U128<NativeEndian>, types::U8>,
+    storage: Storage,
 }
 
 impl PermissionStorage {
-    pub fn new(db: &LocalDb) -> anyhow::Result<Self> {
-        // We open the default unnamed database
-        let perms = {
-            let mut wtxn = db.write_txn()?;
-            let db = db.create_database(&mut wtxn, Some("uuid-to-perms"))?;
-            wtxn.commit()?;
-            db
-        };
+    const TABLE: &str = "uuid-to-perms";
 
-        Ok(Self {
-            env: (**db).clone(),
-            perms,
-        })
+    pub const fn new(storage: Storage) -> Self {
+        Self { storage }
     }
 
-    pub fn get(&self, uuid: uuid::Uuid) -> Group {
-        let uuid = uuid.as_u128();
-        let rtxn = self.env.read_txn().unwrap();
-        let Some(perms) = self.perms.get(&rtxn, &uuid).unwrap() else {
-            return Group::default();
+    pub async fn get(&self, uuid: uuid::Uuid) -> anyhow::Result<Group> {
+        let key = uuid.as_u128().to_ne_bytes();
+
+        let Some(perms) = self.storage.get(Self::TABLE, &key).await? else {
+            return Ok(Group::default());
         };
 
-        let Some(group) = Group::from_u8(perms) else {
+        let Some(group) = perms.first().copied().and_then(Group::from_u8) else {
             tracing::error!("invalid group {perms:?}");
-            return Group::default();
+            return Ok(Group::default());
         };
 
-        group
+        Ok(group)
+    }
+
+    /// Every player whose group is stored
+    pub async fn all(&self) -> anyhow::Result<Vec<(uuid::Uuid, Group)>> {
+        let entries = self.storage.entries(Self::TABLE).await?;
+
+        let groups = entries
+            .into_iter()
+            .filter_map(|(key, perms)| {
+                let key: [u8; 16] = key.try_into().ok()?;
+                let uuid = uuid::Uuid::from_u128(u128::from_ne_bytes(key));
+                let group = perms.first().copied().and_then(Group::from_u8)?;
+                Some((uuid, group))
+            })
+            .collect();
+
+        Ok(groups)
     }
 
-    pub fn set(&self, uuid: uuid::Uuid, group: Group) -> anyhow::Result<()> {
-        let uuid = uuid.as_u128();
-        let mut wtxn = self.env.write_txn()?;
-        self.perms.put(&mut wtxn, &uuid, &group.to_u8().unwrap())?;
-        wtxn.commit()?;
-        Ok(())
+    pub async fn set(&self, uuid: uuid::Uuid, group: Group) -> anyhow::Result<()> {
+        let key = uuid.as_u128().to_ne_bytes();
+        self.storage
+            .put(Self::TABLE, &key, &[group.to_u8().unwrap()])
+            .await
     }
 }
@@ -0,0 +1,214 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::{Parser, ValueEnum};
+use enumset::EnumSet;
+use hyperion::{
+    net::{Compose, ConnectionId, DataBundle, agnostic},
+    simulation::Position,
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+use crate::{Flag, ProtectionBypass, Region, Regions, Selection};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FlagValue {
+    Allow,
+    Deny,
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "region")]
+#[command_permission(group = "Admin")]
+pub enum RegionCommand {
+    /// Selects your current block position as the first corner
+    Pos1,
+    /// Selects your current block position as the second corner
+    Pos2,
+    /// Defines a region between the selected corners, replacing any region with the same name
+    Define {
+        name: String,
+    },
+    Remove {
+        name: String,
+    },
+    Flag {
+        name: String,
+        flag: Flag,
+        value: FlagValue,
+    },
+    /// Lists the regions at your position
+    Info,
+    List,
+    /// Toggles whether protection applies to you
+    Bypass,
+}
+
+fn describe(name: &str, region: &Region) -> String {
+    let flags = EnumSet::<Flag>::all()
+        .iter()
+        .map(|flag| {
+            let color = if region.flags.get(flag) { "§a" } else { "§c" };
+            let flag = flag.to_possible_value().unwrap();
+            format!("{color}{}§r", flag.get_name())
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let (min, max) = (region.min(), region.max());
+
+    format!(
+        "§6{name}§r ({}, {}, {}) to ({}, {}, {}): {flags}",
+        min.x, min.y, min.z, max.x, max.y, max.z
+    )
+}
+
+impl MinecraftCommand for RegionCommand {
+    type State = SystemState<(
+        Query<
+            'static,
+            'static,
+            (
+                &'static ConnectionId,
+                &'static Position,
+                Option<&'static Selection>,
+                Has<ProtectionBypass>,
+            ),
+        >,
+        Res<'static, Regions>,
+        Res<'static, Compose>,
+        Commands<'static, 'static>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (query, regions, compose, mut commands) = state.get(world);
+
+        let (&connection_id, position, selection, bypass) = match query.get(caller) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("region command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let block = position.floor().as_ivec3();
+        let mut selection = selection.copied().unwrap_or_default();
+
+        let mut result = Ok(());
+        let lines = match self {
+            Self::Pos1 | Self::Pos2 => {
+                let corner = if matches!(self, Self::Pos1) {
+                    selection.first = Some(block);
+                    "First"
+                } else {
+                    selection.second = Some(block);
+                    "Second"
+                };
+
+                commands.entity(caller).insert(selection);
+
+                vec![format!(
+                    "§a{corner} corner set to ({}, {}, {})",
+                    block.x, block.y, block.z
+                )]
+            }
+            Self::Define { name } => {
+                let (Some(first), Some(second)) = (selection.first, selection.second) else {
+                    let msg = agnostic::chat("§cSelect both corners with /region pos1 and pos2");
+                    if let Err(e) = compose.unicast(&msg, connection_id) {
+                        error!("failed to send region message: {e}");
+                    }
+                    return Err(CommandFailed);
+                };
+
+                let region = Region::new(first, second);
+                let replaced = regions.get(&name).is_some();
+                let description = describe(&name, &region);
+
+                commands.queue(move |world: &mut World| {
+                    world.resource_mut::<Regions>().insert(name, region);
+                });
+
+                vec![format!(
+                    "§a{} region {description}",
+                    if replaced { "Replaced" } else { "Defined" }
+                )]
+            }
+            Self::Remove { name } => {
+                if regions.get(&name).is_none() {
+                    result = Err(CommandFailed);
+                    vec![format!("§cRegion {name} does not exist")]
+                } else {
+                    let msg = format!("§aRemoved region {name}");
+                    commands.queue(move |world: &mut World| {
+                        world.resource_mut::<Regions>().remove(&name);
+                    });
+                    vec![msg]
+                }
+            }
+            Self::Flag { name, flag, value } => {
+                let Some(&region) = regions.get(&name) else {
+                    let msg = agnostic::chat(format!("§cRegion {name} does not exist"));
+                    if let Err(e) = compose.unicast(&msg, connection_id) {
+                        error!("failed to send region message: {e}");
+                    }
+                    return Err(CommandFailed);
+                };
+
+                let mut updated = region;
+                updated.flags.set(flag, value == FlagValue::Allow);
+                let description = describe(&name, &updated);
+
+                commands.queue(move |world: &mut World| {
+                    if let Some(region) = world.resource_mut::<Regions>().get_mut(&name) {
+                        region.flags.set(flag, value == FlagValue::Allow);
+                    }
+                });
+
+                vec![format!("§aUpdated region {description}")]
+            }
+            Self::Info => {
+                let mut lines = regions
+                    .at(block)
+                    .map(|(name, region)| describe(name, region))
+                    .collect::<Vec<_>>();
+
+                if lines.is_empty() {
+                    lines.push("§7You are not in any region".to_string());
+                }
+
+                lines
+            }
+            Self::List => {
+                let mut lines = regions
+                    .iter()
+                    .map(|(name, region)| describe(name, region))
+                    .collect::<Vec<_>>();
+
+                if lines.is_empty() {
+                    lines.push("§7No regions have been defined".to_string());
+                }
+
+                lines
+            }
+            Self::Bypass => {
+                if bypass {
+                    commands.entity(caller).remove::<ProtectionBypass>();
+                    vec!["§aProtection now applies to you".to_string()]
+                } else {
+                    commands.entity(caller).insert(ProtectionBypass);
+                    vec!["§aYou now bypass protection".to_string()]
+                }
+            }
+        };
+
+        let mut bundle = DataBundle::new(&compose);
+        for line in lines {
+            bundle.add_packet(&agnostic::chat(line)).unwrap();
+        }
+        if let Err(e) = bundle.unicast(connection_id) {
+            error!("failed to send region list: {e}");
+        }
+
+        result
+    }
+}
@@ -0,0 +1,172 @@
+//! Protects cuboid regions of the world by cancelling events which their flags deny.
+
+mod command;
+mod region;
+
+use bevy::prelude::*;
+use hyperion::{
+    glam::IVec3,
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{
+        MovementTracking, PendingTeleportation, Position,
+        event::{AttackEntity, CancelEvents, Cancellable, DestroyBlock, PlaceBlock, ToggleDoor},
+    },
+    timings::timed,
+};
+use hyperion_clap::MinecraftCommand;
+pub use region::{Flag, Region, RegionFlags, Regions};
+use tracing::error;
+
+use crate::command::RegionCommand;
+
+/// Players with this component are not affected by protection
+#[derive(Component, Copy, Clone, Debug)]
+pub struct ProtectionBypass;
+
+/// The corners selected by a player with `/region pos1` and `/region pos2`
+#[derive(Component, Copy, Clone, Debug, Default)]
+pub struct Selection {
+    pub first: Option<IVec3>,
+    pub second: Option<IVec3>,
+}
+
+fn notify(compose: &Compose, query: &Query<'_, '_, &ConnectionId>, player: Entity, msg: &str) {
+    if let Ok(&connection_id) = query.get(player) {
+        if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+            error!("failed to send protection message: {e}");
+        }
+    }
+}
+
+fn protect_blocks(
+    mut place_events: EventMutator<'_, '_, Cancellable<PlaceBlock>>,
+    mut destroy_events: EventMutator<'_, '_, Cancellable<DestroyBlock>>,
+    mut door_events: EventMutator<'_, '_, Cancellable<ToggleDoor>>,
+    regions: Res<'_, Regions>,
+    bypass_query: Query<'_, '_, (), With<ProtectionBypass>>,
+    connection_query: Query<'_, '_, &ConnectionId>,
+    compose: Res<'_, Compose>,
+) {
+    let is_allowed = |player: Entity, block: IVec3, flag: Flag| {
+        if bypass_query.contains(player) || regions.is_allowed(block, flag) {
+            return true;
+        }
+
+        notify(
+            &compose,
+            &connection_query,
+            player,
+            "§cThis area is protected",
+        );
+        false
+    };
+
+    for event in place_events.read() {
+        if !is_allowed(event.from, event.position, Flag::Build) {
+            event.cancel();
+        }
+    }
+
+    for event in destroy_events.read() {
+        if !is_allowed(event.from, event.position, Flag::Build) {
+            event.cancel();
+        }
+    }
+
+    for event in door_events.read() {
+        if !is_allowed(event.from, event.position, Flag::Interact) {
+            event.cancel();
+        }
+    }
+}
+
+fn protect_pvp(
+    mut events: EventMutator<'_, '_, Cancellable<AttackEntity>>,
+    regions: Res<'_, Regions>,
+    position_query: Query<'_, '_, &Position>,
+    bypass_query: Query<'_, '_, (), With<ProtectionBypass>>,
+    connection_query: Query<'_, '_, &ConnectionId>,
+    compose: Res<'_, Compose>,
+) {
+    for event in events.read() {
+        if bypass_query.contains(event.origin) {
+            continue;
+        }
+
+        let denied = [event.origin, event.target].into_iter().any(|entity| {
+            position_query
+                .get(entity)
+                .is_ok_and(|position| !regions.is_allowed_at_position(**position, Flag::Pvp))
+        });
+
+        if denied {
+            event.cancel();
+            notify(
+                &compose,
+                &connection_query,
+                event.origin,
+                "§cPvP is disabled here",
+            );
+        }
+    }
+}
+
+/// Moves players back if they enter a region which denies [`Flag::Enter`]. Players who were
+/// already inside of the region, such as when the region was defined around them, may move
+/// freely so that they can leave.
+fn protect_enter(
+    regions: Res<'_, Regions>,
+    mut query: Query<
+        '_,
+        '_,
+        (Entity, &mut Position, &MovementTracking, &ConnectionId),
+        (
+            Changed<Position>,
+            Without<PendingTeleportation>,
+            Without<ProtectionBypass>,
+        ),
+    >,
+    compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
+) {
+    for (entity, mut position, tracking, &connection_id) in &mut query {
+        let previous = tracking.last_tick_position;
+        let previous_block = previous.floor().as_ivec3();
+
+        let entered = regions
+            .at(position.floor().as_ivec3())
+            .any(|(_, region)| !region.flags.enter && !region.contains(previous_block));
+
+        if !entered {
+            continue;
+        }
+
+        **position = previous;
+        commands
+            .entity(entity)
+            .insert(PendingTeleportation::new(previous));
+
+        let msg = agnostic::chat("§cYou cannot enter this area");
+        if let Err(e) = compose.unicast(&msg, connection_id) {
+            error!("failed to send region message: {e}");
+        }
+    }
+}
+
+pub struct ProtectPlugin;
+
+impl Plugin for ProtectPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Regions>();
+        app.add_systems(
+            FixedUpdate,
+            (
+                (timed(protect_blocks), timed(protect_pvp)).in_set(CancelEvents),
+                // Player movement is handled before CancelEvents
+                timed(protect_enter).after(CancelEvents),
+            ),
+        );
+
+        RegionCommand::register(app.world_mut());
+    }
+}
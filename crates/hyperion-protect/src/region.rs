@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use clap::ValueEnum;
+use enumset::{EnumSet, EnumSetType};
+use hyperion::glam::{IVec3, Vec3};
+
+/// An action which may be allowed or denied within a [`Region`]
+#[derive(EnumSetType, Debug, ValueEnum)]
+pub enum Flag {
+    /// Placing and destroying blocks
+    Build,
+    /// Attacking other entities. This is denied if either the attacker or the target is in a
+    /// region which denies it.
+    Pvp,
+    /// Interacting with blocks such as doors
+    Interact,
+    /// Moving into the region from outside of it
+    Enter,
+}
+
+/// The flags which are allowed within a region
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RegionFlags {
+    allowed: EnumSet<Flag>,
+}
+
+impl Default for RegionFlags {
+    fn default() -> Self {
+        Self {
+            allowed: Flag::Pvp | Flag::Enter,
+        }
+    }
+}
+
+impl RegionFlags {
+    #[must_use]
+    pub fn get(&self, flag: Flag) -> bool {
+        self.allowed.contains(flag)
+    }
+
+    pub fn set(&mut self, flag: Flag, allowed: bool) {
+        if allowed {
+            self.allowed.insert(flag);
+        } else {
+            self.allowed.remove(flag);
+        }
+    }
+}
+
+/// A cuboid of blocks. Both corners are included in the region.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    min: IVec3,
+    max: IVec3,
+    pub flags: RegionFlags,
+}
+
+impl Region {
+    /// Creates a region with the default flags between two opposite corners, which may be given in
+    /// any order
+    #[must_use]
+    pub fn new(corner: IVec3, opposite: IVec3) -> Self {
+        Self {
+            min: corner.min(opposite),
+            max: corner.max(opposite),
+            flags: RegionFlags::default(),
+        }
+    }
+
+    #[must_use]
+    pub const fn min(&self) -> IVec3 {
+        self.min
+    }
+
+    #[must_use]
+    pub const fn max(&self) -> IVec3 {
+        self.max
+    }
+
+    #[must_use]
+    pub fn contains(&self, block: IVec3) -> bool {
+        block.cmpge(self.min).all() && block.cmple(self.max).all()
+    }
+
+    /// Whether the block containing `position` is part of the region
+    #[must_use]
+    pub fn contains_position(&self, position: Vec3) -> bool {
+        self.contains(position.floor().as_ivec3())
+    }
+}
+
+/// All protected regions, keyed by name
+#[derive(Resource, Default, Debug)]
+pub struct Regions {
+    regions: BTreeMap<String, Region>,
+}
+
+impl Regions {
+    /// Adds a region, returning the region it replaced if one already had the same name
+    pub fn insert(&mut self, name: impl Into<String>, region: Region) -> Option<Region> {
+        self.regions.insert(name.into(), region)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Region> {
+        self.regions.remove(name)
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Region> {
+        self.regions.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Region> {
+        self.regions.get_mut(name)
+    }
+
+    /// All regions, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Region)> {
+        self.regions
+            .iter()
+            .map(|(name, region)| (name.as_str(), region))
+    }
+
+    /// The regions which contain `block`, sorted by name
+    pub fn at(&self, block: IVec3) -> impl Iterator<Item = (&str, &Region)> {
+        self.iter()
+            .filter(move |(_, region)| region.contains(block))
+    }
+
+    /// Whether `flag` is allowed at `block`. This is only allowed if every region containing the
+    /// block allows it, so blocks outside of every region allow everything.
+    #[must_use]
+    pub fn is_allowed(&self, block: IVec3, flag: Flag) -> bool {
+        self.at(block).all(|(_, region)| region.flags.get(flag))
+    }
+
+    /// Same as [`Regions::is_allowed`] for the block containing `position`
+    #[must_use]
+    pub fn is_allowed_at_position(&self, position: Vec3, flag: Flag) -> bool {
+        self.is_allowed(position.floor().as_ivec3(), flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains() {
+        let region = Region::new(IVec3::new(5, 0, -5), IVec3::new(-5, 10, 5));
+
+        assert_eq!(region.min(), IVec3::new(-5, 0, -5));
+        assert_eq!(region.max(), IVec3::new(5, 10, 5));
+
+        assert!(region.contains(IVec3::new(-5, 0, -5)));
+        assert!(region.contains(IVec3::new(5, 10, 5)));
+        assert!(!region.contains(IVec3::new(6, 10, 5)));
+
+        // Positions are rounded down to the block which contains them
+        assert!(region.contains_position(Vec3::new(5.9, 10.5, -4.1)));
+        assert!(!region.contains_position(Vec3::new(-5.1, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let mut regions = Regions::default();
+
+        let mut arena = Region::new(IVec3::ZERO, IVec3::splat(100));
+        arena.flags.set(Flag::Build, true);
+        regions.insert("arena", arena);
+
+        // Spawn is inside of the arena and denies building by default
+        regions.insert("spawn", Region::new(IVec3::ZERO, IVec3::splat(10)));
+
+        assert!(regions.is_allowed(IVec3::splat(50), Flag::Build));
+        assert!(!regions.is_allowed(IVec3::splat(5), Flag::Build));
+        assert!(regions.is_allowed(IVec3::splat(5), Flag::Pvp));
+        assert!(regions.is_allowed(IVec3::splat(-1), Flag::Interact));
+
+        let names = regions
+            .at(IVec3::splat(5))
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["arena", "spawn"]);
+
+        regions.remove("spawn");
+        assert!(regions.is_allowed(IVec3::splat(5), Flag::Build));
+    }
+}
@@ -1,5 +1,7 @@
 use rkyv::{Archive, Deserialize, Serialize, with::InlineAsBox};
 
+use crate::PlayerProfile;
+
 #[derive(Archive, Deserialize, Serialize, Clone, PartialEq, Debug)]
 pub struct PlayerPackets<'a> {
     pub stream: u64,
@@ -25,10 +27,35 @@ pub enum PlayerDisconnectReason<'a> {
     /// If cannot receive packets fast enough
     CouldNotKeepUp,
     LostConnection,
+    /// The player was moved to another server with [`crate::Transfer`]
+    Transferred,
 
     Other(#[rkyv(with = InlineAsBox)] &'a str),
 }
 
+/// Sent instead of [`PlayerConnect`] for a player who was transferred from another server. The
+/// player is already in the play state and has already enabled compression.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct PlayerTransfer<'a> {
+    pub stream: u64,
+    pub profile: PlayerProfile<'a>,
+}
+
+/// Sent when a [`crate::Transfer`] could not be completed. The player remains on the server which
+/// requested the transfer.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct TransferFailed {
+    pub stream: u64,
+}
+
+/// Sent when the proxy dropped [`crate::Priority::Bulk`] packets for a player who could not keep up.
+/// The server should resend the state which it sent in bulk, such as chunks. This is sent at most
+/// once until the player has caught up with the bulk packets sent to them.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct BulkShed {
+    pub stream: u64,
+}
+
 #[derive(Archive, Deserialize, Serialize, Clone, PartialEq, Debug)]
 pub struct RequestSubscribeChannelPackets<'a> {
     #[rkyv(with = InlineAsBox)]
@@ -41,4 +68,7 @@ pub enum ProxyToServerMessage<'a> {
     PlayerDisconnect(PlayerDisconnect<'a>),
     PlayerPackets(PlayerPackets<'a>),
     RequestSubscribeChannelPackets(RequestSubscribeChannelPackets<'a>),
+    PlayerTransfer(PlayerTransfer<'a>),
+    TransferFailed(TransferFailed),
+    BulkShed(BulkShed),
 }
@@ -1,6 +1,6 @@
 use rkyv::{Archive, Deserialize, Serialize, with::InlineAsBox};
 
-use crate::ChunkPosition;
+use crate::{ChunkPosition, PlayerProfile, Priority};
 
 #[derive(Archive, Deserialize, Serialize, Clone, PartialEq)]
 #[rkyv(derive(Debug))]
@@ -22,6 +22,9 @@ pub struct AddChannel<'a> {
 pub struct UpdateChannelPosition {
     pub channel_id: u32,
     pub position: ChunkPosition,
+
+    /// Players within this many chunks of `position` are subscribed to the channel
+    pub radius: i16,
 }
 
 #[derive(Archive, Deserialize, Serialize, Clone, PartialEq)]
@@ -45,6 +48,29 @@ pub struct SubscribeChannelPackets<'a> {
     pub data: &'a [u8],
 }
 
+/// Subscribes a player to a channel or unsubscribes them from it. This is used for channels which
+/// never have their position updated with [`UpdateChannelPositions`], so the proxy does not choose
+/// their subscribers itself. Players who are unsubscribed are sent the unsubscribe packets of the
+/// channel.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[rkyv(derive(Debug))]
+pub struct SetChannelSubscription {
+    pub channel_id: u32,
+    pub stream: u64,
+    pub subscribed: bool,
+}
+
+/// Hides a channel from a player or shows it to them again. A hidden player is unsubscribed from
+/// the channel and sent its unsubscribe packets, and is never subscribed to it until the channel
+/// is shown again. This is used to keep players from seeing entities they are not allowed to see.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[rkyv(derive(Debug))]
+pub struct SetChannelHidden {
+    pub channel_id: u32,
+    pub stream: u64,
+    pub hidden: bool,
+}
+
 #[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq)]
 #[rkyv(derive(Debug))]
 pub struct SetReceiveBroadcasts {
@@ -54,15 +80,18 @@ pub struct SetReceiveBroadcasts {
 #[derive(Archive, Deserialize, Serialize, Clone, PartialEq)]
 pub struct BroadcastGlobal<'a> {
     pub exclude: u64,
+    pub priority: Priority,
 
     #[rkyv(with = InlineAsBox)]
     pub data: &'a [u8],
 }
 
+/// Sent to players who have loaded the chunk at `center`. See [`UpdateChunkSubscriptions`].
 #[derive(Archive, Deserialize, Serialize, Clone, PartialEq)]
 pub struct BroadcastLocal<'a> {
     pub center: ChunkPosition,
     pub exclude: u64,
+    pub priority: Priority,
 
     #[rkyv(with = InlineAsBox)]
     pub data: &'a [u8],
@@ -72,6 +101,7 @@ pub struct BroadcastLocal<'a> {
 pub struct BroadcastChannel<'a> {
     pub channel_id: u32,
     pub exclude: u64,
+    pub priority: Priority,
 
     #[rkyv(with = InlineAsBox)]
     pub data: &'a [u8],
@@ -80,6 +110,7 @@ pub struct BroadcastChannel<'a> {
 #[derive(Archive, Deserialize, Serialize, Clone, PartialEq)]
 pub struct Unicast<'a> {
     pub stream: u64,
+    pub priority: Priority,
 
     #[rkyv(with = InlineAsBox)]
     pub data: &'a [u8],
@@ -93,6 +124,94 @@ pub struct Shutdown {
     pub stream: u64,
 }
 
+/// The status (server list ping) response which the proxy sends to clients. The proxy answers
+/// status requests itself using the most recent status it has received, so status requests never
+/// reach the server.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct UpdateStatus<'a> {
+    /// See <https://wiki.vg/Server_List_Ping#Status_Response>
+    #[rkyv(with = InlineAsBox)]
+    pub json: &'a str,
+}
+
+/// The packets which log players into an empty world while they wait for a slot in the proxy queue,
+/// so that they can be shown their position. This is sent when a proxy connects.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct UpdateWaitingRoom<'a> {
+    /// The compression threshold which `data` is encoded with, or a negative number if `data` is
+    /// not compressed
+    pub compression_threshold: i32,
+
+    /// Play packets starting with `GameJoinS2c`
+    #[rkyv(with = InlineAsBox)]
+    pub data: &'a [u8],
+}
+
+/// Moves a player in the play state to another server.
+///
+/// The proxy connects to `server` if it is not connected to it already and sends
+/// [`crate::PlayerTransfer`] to it. Afterwards, it sends [`crate::PlayerDisconnect`] with
+/// [`crate::PlayerDisconnectReason::Transferred`] to this server. If the other server cannot be
+/// reached, the proxy sends [`crate::TransferFailed`] instead and the player stays on this server.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct Transfer<'a> {
+    pub stream: u64,
+
+    /// The address of the other server, such as `127.0.0.1:35565`
+    #[rkyv(with = InlineAsBox)]
+    pub server: &'a str,
+
+    pub profile: PlayerProfile<'a>,
+
+    /// Packets which are sent to the player once the other server has accepted them, before any
+    /// packet of the other server. They are not sent if the transfer fails.
+    #[rkyv(with = InlineAsBox)]
+    pub on_success: &'a [u8],
+}
+
+/// Describes the server to the proxy so that the proxy can route new players to it. This is sent
+/// when a proxy connects.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct RegisterServer<'a> {
+    /// A name which identifies this server in the network, such as `lobby-1`
+    #[rkyv(with = InlineAsBox)]
+    pub name: &'a str,
+
+    /// The region which the server is in, such as `eu`, or empty if the server has no region
+    #[rkyv(with = InlineAsBox)]
+    pub region: &'a str,
+
+    /// The arena which the server hosts, or empty if the server does not host a specific arena
+    #[rkyv(with = InlineAsBox)]
+    pub arena: &'a str,
+}
+
+/// The number of players on the server, which is sent periodically. The proxy routes new players
+/// to servers based on these counts.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct PlayerCount {
+    pub online: u32,
+    pub max: u32,
+}
+
+/// Changes which chunks a player has loaded, which decides which [`BroadcastLocal`] messages are
+/// sent to the player. Players subscribe to a chunk once it is sent to them and unsubscribe when it
+/// is unloaded.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct UpdateChunkSubscriptions<'a> {
+    pub stream: u64,
+
+    /// Whether the player is unsubscribed from every chunk before the other changes are made, such
+    /// as when they leave the server
+    pub clear: bool,
+
+    #[rkyv(with = InlineAsBox)]
+    pub subscribe: &'a [ChunkPosition],
+
+    #[rkyv(with = InlineAsBox)]
+    pub unsubscribe: &'a [ChunkPosition],
+}
+
 #[derive(Archive, Deserialize, Serialize, Clone, PartialEq)]
 pub enum ServerToProxyMessage<'a> {
     UpdatePlayerPositions(UpdatePlayerPositions),
@@ -106,4 +225,12 @@ pub enum ServerToProxyMessage<'a> {
     Unicast(Unicast<'a>),
     SetReceiveBroadcasts(SetReceiveBroadcasts),
     Shutdown(Shutdown),
+    UpdateStatus(UpdateStatus<'a>),
+    Transfer(Transfer<'a>),
+    RegisterServer(RegisterServer<'a>),
+    PlayerCount(PlayerCount),
+    UpdateChunkSubscriptions(UpdateChunkSubscriptions<'a>),
+    SetChannelSubscription(SetChannelSubscription),
+    UpdateWaitingRoom(UpdateWaitingRoom<'a>),
+    SetChannelHidden(SetChannelHidden),
 }
@@ -1,5 +1,5 @@
 use glam::I16Vec2;
-use rkyv::{Archive, Deserialize, Serialize};
+use rkyv::{Archive, Deserialize, Serialize, with::InlineAsBox};
 
 #[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
 #[rkyv(derive(Debug))]
@@ -29,3 +29,57 @@ impl From<ChunkPosition> for I16Vec2 {
         Self::new(value.x, value.z)
     }
 }
+
+/// The login state of a player which is kept when the player is transferred to another server, so
+/// the other server does not need to log the player in again.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct PlayerProfile<'a> {
+    #[rkyv(with = InlineAsBox)]
+    pub username: &'a str,
+    pub uuid: u128,
+
+    /// The skin textures property, which is empty if the player has no skin
+    #[rkyv(with = InlineAsBox)]
+    pub textures: &'a str,
+
+    #[rkyv(with = InlineAsBox)]
+    pub signature: &'a str,
+}
+
+/// How important a packet is to a player. The proxy writes packets in the order they were sent, and
+/// when a player cannot keep up with them, it drops [`Priority::Bulk`] packets before
+/// disconnecting the player.
+#[derive(
+    Archive,
+    Deserialize,
+    Serialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Debug,
+    Default
+)]
+#[rkyv(derive(Debug, Clone, Copy, PartialEq, Eq))]
+pub enum Priority {
+    /// Packets which the connection depends on, such as keep-alives, teleports and disconnects
+    Critical,
+
+    /// Game state, which is the default
+    #[default]
+    Normal,
+
+    /// Large or cosmetic packets such as chunks and particles, which are dropped first when the
+    /// player falls behind. Anything sent with this priority must be safe to lose.
+    Bulk,
+}
+
+impl From<ArchivedPriority> for Priority {
+    fn from(value: ArchivedPriority) -> Self {
+        match value {
+            ArchivedPriority::Critical => Self::Critical,
+            ArchivedPriority::Normal => Self::Normal,
+            ArchivedPriority::Bulk => Self::Bulk,
+        }
+    }
+}
@@ -1,7 +1,11 @@
-use std::{net::SocketAddr, path::Path};
+use std::{net::SocketAddr, path::Path, sync::Arc};
 
 use bevy::prelude::*;
 use hyperion::runtime::AsyncRuntime;
+use hyperion_proxy::{
+    lanes::DEFAULT_MAX_QUEUED_BYTES, metrics::ProxyMetrics, queue::PlayerLimit, routing::Routing,
+    translation::Translations,
+};
 use tokio::net::TcpListener;
 
 pub struct HyperionProxyPlugin;
@@ -49,6 +53,12 @@ fn update_proxy_address(trigger: Trigger<'_, SetProxyAddress>, runtime: Res<'_,
             Path::new("root_ca.crt"),
             Path::new("proxy.crt"),
             Path::new("proxy_private_key.pem"),
+            Vec::new(),
+            Routing::default(),
+            PlayerLimit::new(None, 0),
+            DEFAULT_MAX_QUEUED_BYTES,
+            Translations::default(),
+            Arc::new(ProxyMetrics::default()),
         )
         .await
         .unwrap();
@@ -0,0 +1,455 @@
+//! Connections to the servers which players can be routed and transferred between.
+//!
+//! New players join the server chosen by the [`RoutingStrategy`] out of the primary server given on
+//! the command line and the shards. When a server sends [`hyperion_proto::Transfer`], the proxy
+//! connects to the other server if needed and moves the player's stream to it. The TLS certificate
+//! of every server must be valid for its host name.
+
+use std::{
+    fmt::Debug,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, bail};
+use arc_swap::ArcSwapOption;
+use bytes::Bytes;
+use hyperion_proto::{
+    PlayerDisconnect, PlayerDisconnectReason, ProxyToServerMessage, TransferFailed,
+};
+use rkyv::util::AlignedVec;
+use rustc_hash::{FxBuildHasher, FxHashMap};
+use rustls::client::ClientConfig;
+use rustls_pki_types::ServerName;
+use tokio::{io::BufReader, net::TcpStream};
+use tokio_rustls::TlsConnector;
+use tracing::{Instrument, error, info, info_span, warn};
+
+use crate::{
+    IngressHandler, ShutdownType,
+    cache::BufferedEgress,
+    data::PlayerHandle,
+    egress::Egress,
+    metrics::ProxyMetrics,
+    routing::RoutingStrategy,
+    server_sender::{ServerSender, launch_server_writer},
+};
+
+/// How long connecting to a server for a transfer may take before the transfer fails
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the proxy checks that it is connected to every shard
+const SHARD_RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How a server describes itself with [`hyperion_proto::RegisterServer`]. Empty fields are unset.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Registration {
+    pub name: String,
+    pub region: String,
+    pub arena: String,
+}
+
+/// A server which the proxy is connected to
+pub struct Backend {
+    id: u64,
+    address: String,
+    server_sender: ServerSender,
+    registration: ArcSwapOption<Registration>,
+    /// The number of players most recently reported by the server
+    online: AtomicU32,
+    /// The maximum number of players most recently reported by the server, or 0 if the server has
+    /// not reported it
+    max: AtomicU32,
+    /// Players routed to the server since it last reported its player count
+    routed: AtomicU32,
+}
+
+impl Debug for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Backend")
+            .field("id", &self.id)
+            .field("address", &self.address)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Backend {
+    #[must_use]
+    pub const fn new(id: u64, address: String, server_sender: ServerSender) -> Self {
+        Self {
+            id,
+            address,
+            server_sender,
+            registration: ArcSwapOption::const_empty(),
+            online: AtomicU32::new(0),
+            max: AtomicU32::new(0),
+            routed: AtomicU32::new(0),
+        }
+    }
+
+    #[must_use]
+    pub const fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[must_use]
+    #[expect(clippy::missing_const_for_fn, reason = "false positive")]
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    #[must_use]
+    pub const fn server_sender(&self) -> &ServerSender {
+        &self.server_sender
+    }
+
+    /// The registration of the server, or `None` if it has not registered yet
+    #[must_use]
+    pub fn registration(&self) -> Option<Arc<Registration>> {
+        self.registration.load_full()
+    }
+
+    pub fn register(&self, registration: Registration) {
+        self.registration.store(Some(Arc::new(registration)));
+    }
+
+    pub fn update_player_count(&self, online: u32, max: u32) {
+        self.online.store(online, Ordering::Relaxed);
+        self.max.store(max, Ordering::Relaxed);
+        self.routed.store(0, Ordering::Relaxed);
+    }
+
+    /// The number of players on the server, including players routed to it since it last reported
+    /// its player count
+    #[must_use]
+    pub fn load(&self) -> u32 {
+        self.online
+            .load(Ordering::Relaxed)
+            .saturating_add(self.routed.load(Ordering::Relaxed))
+    }
+
+    /// Whether the server has reported a maximum number of players which it has reached
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        let max = self.max.load(Ordering::Relaxed);
+        max > 0 && self.load() >= max
+    }
+
+    fn add_routed(&self) {
+        self.routed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The servers which players are routed and transferred to. Connections to servers other than the
+/// primary server are opened by [`Backends::connect_shards`] or on the first transfer to them, and
+/// closed when the proxy reconnects to the primary server.
+pub struct Backends {
+    config: Arc<ClientConfig>,
+    player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher>,
+    metrics: Arc<ProxyMetrics>,
+    shutdown_rx: tokio::sync::watch::Receiver<Option<ShutdownType>>,
+    routing: Arc<dyn RoutingStrategy>,
+    primary: Arc<Backend>,
+    next_id: AtomicU64,
+    connected: Mutex<FxHashMap<String, Arc<Backend>>>,
+    /// Held while connecting so that a server is not connected to twice
+    connecting: tokio::sync::Mutex<()>,
+}
+
+impl Backends {
+    #[must_use]
+    pub(crate) fn new(
+        config: Arc<ClientConfig>,
+        player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher>,
+        metrics: Arc<ProxyMetrics>,
+        shutdown_rx: tokio::sync::watch::Receiver<Option<ShutdownType>>,
+        routing: Arc<dyn RoutingStrategy>,
+        primary: Arc<Backend>,
+    ) -> Self {
+        Self {
+            config,
+            player_registry,
+            metrics,
+            shutdown_rx,
+            routing,
+            next_id: AtomicU64::new(primary.id() + 1),
+            primary,
+            connected: Mutex::default(),
+            connecting: tokio::sync::Mutex::default(),
+        }
+    }
+
+    /// The primary server followed by the other connected servers
+    #[must_use]
+    pub fn all(&self) -> Vec<Arc<Backend>> {
+        let connected = self.connected.lock().unwrap();
+        std::iter::once(self.primary.clone())
+            .chain(connected.values().cloned())
+            .collect()
+    }
+
+    /// Chooses the server for a new player who connected to `server_address`, which is the
+    /// address in the player's handshake
+    #[must_use]
+    pub fn route(&self, server_address: &str) -> Arc<Backend> {
+        let backend = self
+            .routing
+            .route(server_address, &self.all())
+            .unwrap_or_else(|| self.primary.clone());
+        backend.add_routed();
+        backend
+    }
+
+    /// Keeps connections open to the servers at `addresses` so that players can be routed to them,
+    /// reconnecting to servers which are unreachable
+    pub fn connect_shards(self: &Arc<Self>, addresses: Vec<String>) {
+        for address in addresses {
+            let backends = self.clone();
+            let mut shutdown_rx = self.shutdown_rx.clone();
+
+            tokio::spawn(async move {
+                loop {
+                    if let Err(e) = backends.get_or_connect(&address).await {
+                        warn!("failed to connect to shard {address}: {e:?}");
+                    }
+
+                    tokio::select! {
+                        _ = shutdown_rx.wait_for(Option::is_some) => return,
+                        () = tokio::time::sleep(SHARD_RECONNECT_INTERVAL) => {}
+                    }
+                }
+            });
+        }
+    }
+
+    /// Moves `stream` from `source` to the server at `address`. `player_transfer` is the
+    /// [`hyperion_proto::PlayerTransfer`] message which is sent to the other server, and
+    /// `on_success` is sent to the player once they are moved.
+    pub async fn transfer(
+        self: Arc<Self>,
+        source: Arc<Backend>,
+        stream: u64,
+        address: String,
+        player_transfer: AlignedVec,
+        on_success: Bytes,
+    ) {
+        let result = async {
+            let target = self.get_or_connect(&address).await?;
+
+            if target.id() == source.id() {
+                bail!("player is already connected to {address}");
+            }
+
+            Ok(target)
+        }
+        .await;
+
+        let target = match result {
+            Ok(target) => target,
+            Err(e) => {
+                warn!("failed to transfer player {stream} to {address}: {e:?}");
+                send(
+                    &source,
+                    &ProxyToServerMessage::TransferFailed(TransferFailed { stream }),
+                )
+                .await;
+                return;
+            }
+        };
+
+        // The player is moved before the other server is told about them, because packets from
+        // the other server are dropped until then
+        let moved = {
+            let players = self.player_registry.pin();
+            let player = players.get(&stream);
+            if let Some(player) = player {
+                // These are queued after the packets of the previous server and before the
+                // packets of the other server
+                if !on_success.is_empty()
+                    && let Err(e) = player.send(on_success)
+                {
+                    warn!("failed to send transfer packets to player {stream}: {e:?}");
+                }
+
+                player.set_backend(target.clone());
+            }
+            player.is_some()
+        };
+
+        // The previous server has already been told about the disconnect if the player left while
+        // connecting to the other server
+        if !moved {
+            return;
+        }
+
+        if let Err(e) = target.server_sender().send(player_transfer).await {
+            warn!(
+                "failed to transfer player {stream} to {address}: failed to send player transfer \
+                 to server: {e:?}"
+            );
+
+            let stayed = {
+                let players = self.player_registry.pin();
+                let player = players.get(&stream);
+                if let Some(player) = player {
+                    player.set_backend(source.clone());
+                }
+                player.is_some()
+            };
+
+            // A player who left in the meantime was reported to the other server instead
+            let message = if stayed {
+                ProxyToServerMessage::TransferFailed(TransferFailed { stream })
+            } else {
+                ProxyToServerMessage::PlayerDisconnect(PlayerDisconnect {
+                    stream,
+                    reason: PlayerDisconnectReason::LostConnection,
+                })
+            };
+            send(&source, &message).await;
+            return;
+        }
+
+        info!("transferred player {stream} to {address}");
+        send(
+            &source,
+            &ProxyToServerMessage::PlayerDisconnect(PlayerDisconnect {
+                stream,
+                reason: PlayerDisconnectReason::Transferred,
+            }),
+        )
+        .await;
+
+        // A player who left before the player transfer was sent was reported to the other server
+        // before it knew about them
+        if self.player_registry.pin().get(&stream).is_none() {
+            send(
+                &target,
+                &ProxyToServerMessage::PlayerDisconnect(PlayerDisconnect {
+                    stream,
+                    reason: PlayerDisconnectReason::LostConnection,
+                }),
+            )
+            .await;
+        }
+    }
+
+    async fn get_or_connect(self: &Arc<Self>, address: &str) -> anyhow::Result<Arc<Backend>> {
+        if address == self.primary.address() {
+            return Ok(self.primary.clone());
+        }
+
+        if let Some(backend) = self.connected.lock().unwrap().get(address) {
+            return Ok(backend.clone());
+        }
+
+        let _connecting = self.connecting.lock().await;
+
+        // Another task may have connected while this one was waiting
+        if let Some(backend) = self.connected.lock().unwrap().get(address) {
+            return Ok(backend.clone());
+        }
+
+        let backend = self.connect(address).await?;
+        self.connected
+            .lock()
+            .unwrap()
+            .insert(address.to_owned(), backend.clone());
+
+        Ok(backend)
+    }
+
+    async fn connect(self: &Arc<Self>, address: &str) -> anyhow::Result<Arc<Backend>> {
+        let Some((host, _)) = address.rsplit_once(':') else {
+            bail!("server address is missing port");
+        };
+
+        let server_name =
+            ServerName::try_from(host.to_owned()).context("failed to parse server name")?;
+
+        let socket = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+            .await
+            .context("timed out connecting to server")?
+            .context("failed to connect to server")?;
+        socket.set_nodelay(true)?;
+
+        let connector = TlsConnector::from(self.config.clone());
+        let server_stream =
+            tokio::time::timeout(CONNECT_TIMEOUT, connector.connect(server_name, socket))
+                .await
+                .context("timed out connecting to server")?
+                .context("failed to connect to game server")?;
+
+        let (server_read, server_write) = tokio::io::split(server_stream);
+        let server_sender = launch_server_writer(server_write, self.metrics.clone());
+
+        let backend = Arc::new(Backend::new(
+            self.next_id.fetch_add(1, Ordering::Relaxed),
+            address.to_owned(),
+            server_sender,
+        ));
+
+        // Only the status and the waiting room of the primary server are used
+        let (status_sender, _) = tokio::sync::watch::channel(None);
+        let (waiting_room_sender, _) = tokio::sync::watch::channel(None);
+
+        let egress = Egress::new(
+            self.player_registry,
+            backend.clone(),
+            self.clone(),
+            status_sender,
+            waiting_room_sender,
+        );
+        let mut handler = IngressHandler::new(
+            BufReader::new(server_read),
+            BufferedEgress::new(egress),
+            self.metrics.clone(),
+        );
+
+        tokio::spawn({
+            let backends = self.clone();
+            let backend = backend.clone();
+            let mut shutdown_rx = self.shutdown_rx.clone();
+
+            async move {
+                loop {
+                    tokio::select! {
+                        _ = shutdown_rx.wait_for(Option::is_some) => return,
+                        result = handler.handle_next() => {
+                            if let Err(e) = result {
+                                error!("lost connection to {}: {e:?}", backend.address());
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                backends.connected.lock().unwrap().remove(backend.address());
+
+                // Players cannot be moved back without the server which they were on
+                let players = backends.player_registry.pin();
+                for player in players.values() {
+                    if player.is_on(&backend) {
+                        player.shutdown();
+                    }
+                }
+            }
+            .instrument(info_span!("backend_reader_loop", address))
+        });
+
+        info!("🔗 Connected to {address}");
+
+        Ok(backend)
+    }
+}
+
+async fn send(backend: &Backend, message: &ProxyToServerMessage<'_>) {
+    let message = rkyv::to_bytes::<rkyv::rancor::Error>(message).unwrap();
+
+    if let Err(e) = backend.server_sender().send(message).await {
+        warn!("failed to send message to {}: {e}", backend.address());
+    }
+}
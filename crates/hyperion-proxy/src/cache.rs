@@ -3,16 +3,12 @@ use std::collections::HashSet;
 use bvh::{Aabb, Bvh, Data, Point};
 use bytes::Bytes;
 use glam::I16Vec2;
-use hyperion_proto::ArchivedServerToProxyMessage;
-use rustc_hash::FxHashMap;
+use hyperion_proto::{ArchivedServerToProxyMessage, Priority};
+use rustc_hash::{FxHashMap, FxHashSet};
 use tracing::{debug, error};
 
 use crate::egress::Egress;
 
-/// Maximum chunk distance between a packet's center and a player for a local broadcast to be sent to
-/// that player
-const RADIUS: i16 = 16;
-
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 struct Player {
     stream: u64,
@@ -46,12 +42,62 @@ pub struct Channel {
 #[derive(Default)]
 pub struct ChannelManager {
     channels: FxHashMap<u32, Channel>,
+
+    /// Connection ids which must never be subscribed to each channel. This is kept apart from
+    /// [`Channel`] because the server may hide a channel before the channel is added.
+    hidden: FxHashMap<u32, FxHashSet<u64>>,
+}
+
+/// The chunks which each player has loaded, which receive local broadcasts centered on them
+#[derive(Default)]
+struct ChunkSubscriptions {
+    subscribers: FxHashMap<I16Vec2, FxHashSet<u64>>,
+    chunks: FxHashMap<u64, FxHashSet<I16Vec2>>,
+}
+
+impl ChunkSubscriptions {
+    fn subscribe(&mut self, stream: u64, chunk: I16Vec2) {
+        self.subscribers.entry(chunk).or_default().insert(stream);
+        self.chunks.entry(stream).or_default().insert(chunk);
+    }
+
+    fn unsubscribe(&mut self, stream: u64, chunk: I16Vec2) {
+        if let Some(subscribers) = self.subscribers.get_mut(&chunk) {
+            subscribers.remove(&stream);
+
+            if subscribers.is_empty() {
+                self.subscribers.remove(&chunk);
+            }
+        }
+
+        if let Some(chunks) = self.chunks.get_mut(&stream) {
+            chunks.remove(&chunk);
+        }
+    }
+
+    fn clear(&mut self, stream: u64) {
+        for chunk in self.chunks.remove(&stream).unwrap_or_default() {
+            if let Some(subscribers) = self.subscribers.get_mut(&chunk) {
+                subscribers.remove(&stream);
+
+                if subscribers.is_empty() {
+                    self.subscribers.remove(&chunk);
+                }
+            }
+        }
+    }
+
+    fn subscribers(&self, chunk: I16Vec2) -> impl Iterator<Item = u64> + '_ {
+        self.subscribers.get(&chunk).into_iter().flatten().copied()
+    }
 }
 
 /// Buffers egress operations for optimized processing.
 pub struct BufferedEgress {
     /// Manages channels
     channel_manager: ChannelManager,
+    /// Which players receive local broadcasts in each chunk
+    chunk_subscriptions: ChunkSubscriptions,
     /// Reference to the underlying egress handler.
     egress: Egress,
     player_bvh: Bvh<Vec<u64>>,
@@ -63,6 +109,7 @@ impl BufferedEgress {
     pub fn new(egress: Egress) -> Self {
         Self {
             channel_manager: ChannelManager::default(),
+            chunk_subscriptions: ChunkSubscriptions::default(),
             egress,
             player_bvh: Bvh::default(),
         }
@@ -132,11 +179,15 @@ impl BufferedEgress {
                         continue;
                     };
 
+                    let hidden = self.channel_manager.hidden.get(&channel_id);
+
                     let Ok(channel_position) = rkyv::deserialize::<_, !>(&update.position);
                     let channel_position = I16Vec2::from(channel_position);
 
-                    let min = channel_position - I16Vec2::splat(RADIUS);
-                    let max = channel_position + I16Vec2::splat(RADIUS);
+                    let Ok(radius) = rkyv::deserialize::<i16, !>(&update.radius);
+
+                    let min = channel_position - I16Vec2::splat(radius);
+                    let max = channel_position + I16Vec2::splat(radius);
 
                     let aabb = Aabb::new(min, max);
 
@@ -157,7 +208,9 @@ impl BufferedEgress {
                                 continue;
                             };
 
-                            if !player.can_receive_broadcasts() {
+                            if !player.can_receive_broadcasts()
+                                || hidden.is_some_and(|hidden| hidden.contains(&stream))
+                            {
                                 continue;
                             }
 
@@ -200,7 +253,7 @@ impl BufferedEgress {
                         ),
                     )
                     .unwrap();
-                    let server_sender = self.egress.server_sender.clone();
+                    let server_sender = self.egress.backend.server_sender().clone();
                     tokio::spawn(async move {
                         if let Err(e) = server_sender.send(request).await {
                             error!("failed to send request subscribe channel packets: {e}");
@@ -210,6 +263,9 @@ impl BufferedEgress {
             }
             ArchivedServerToProxyMessage::RemoveChannel(packet) => {
                 debug!("removing channel {}", u32::from(packet.channel_id));
+                self.channel_manager
+                    .hidden
+                    .remove(&packet.channel_id.into());
                 let Some(channel) = self
                     .channel_manager
                     .channels
@@ -250,6 +306,7 @@ impl BufferedEgress {
                 channel.pending_connections.clear();
             }
             ArchivedServerToProxyMessage::BroadcastGlobal(packet) => {
+                let priority = Priority::from(packet.priority);
                 let data =
                     Bytes::from(rkyv::deserialize::<_, rkyv::rancor::Error>(&packet.data).unwrap());
                 let Ok(exclude) = rkyv::deserialize::<u64, !>(&packet.exclude);
@@ -257,46 +314,123 @@ impl BufferedEgress {
                 let players = self.egress.player_registry.pin_owned();
 
                 for (&stream, player) in &players {
-                    if !player.can_receive_broadcasts() || stream == exclude {
+                    if !player.can_receive_broadcasts()
+                        || !player.is_on(&self.egress.backend)
+                        || stream == exclude
+                    {
                         continue;
                     }
 
-                    self.egress.unicast(stream, data.clone());
+                    self.egress
+                        .unicast_with_priority(stream, data.clone(), priority);
                 }
             }
             ArchivedServerToProxyMessage::BroadcastLocal(packet) => {
                 let Ok(center_x) = rkyv::deserialize::<i16, !>(&packet.center.x);
                 let Ok(center_z) = rkyv::deserialize::<i16, !>(&packet.center.z);
                 let Ok(player_id_to_exclude) = rkyv::deserialize::<u64, !>(&packet.exclude);
+                let priority = Priority::from(packet.priority);
                 let data =
                     Bytes::from(rkyv::deserialize::<_, rkyv::rancor::Error>(&packet.data).unwrap());
 
                 let position = I16Vec2::new(center_x, center_z);
-                let min = position - I16Vec2::splat(RADIUS);
-                let max = position + I16Vec2::splat(RADIUS);
 
-                let aabb = Aabb::new(min, max);
+                for stream in self.chunk_subscriptions.subscribers(position) {
+                    if stream == player_id_to_exclude {
+                        continue;
+                    }
 
-                let slices = self.player_bvh.get_in(aabb);
+                    self.egress
+                        .unicast_with_priority(stream, data.clone(), priority);
+                }
+            }
+            ArchivedServerToProxyMessage::UpdateChunkSubscriptions(packet) => {
+                let Ok(stream) = rkyv::deserialize::<u64, !>(&packet.stream);
 
-                for slice in slices {
-                    let (_, streams) = self.player_bvh.inner();
+                if packet.clear {
+                    self.chunk_subscriptions.clear(stream);
+                }
 
-                    let start = slice.start as usize;
-                    let end = slice.end as usize;
+                for chunk in packet.unsubscribe.get() {
+                    let Ok(chunk) = rkyv::deserialize::<_, !>(chunk);
+                    self.chunk_subscriptions
+                        .unsubscribe(stream, I16Vec2::from(chunk));
+                }
 
-                    let streams = &streams[start..end];
-                    for &stream in streams {
-                        if stream == player_id_to_exclude {
-                            continue;
-                        }
+                for chunk in packet.subscribe.get() {
+                    let Ok(chunk) = rkyv::deserialize::<_, !>(chunk);
+                    self.chunk_subscriptions
+                        .subscribe(stream, I16Vec2::from(chunk));
+                }
+            }
+            ArchivedServerToProxyMessage::SetChannelSubscription(packet) => {
+                let channel_id = packet.channel_id.into();
+                let Ok(stream) = rkyv::deserialize::<u64, !>(&packet.stream);
+                let Some(channel) = self.channel_manager.channels.get_mut(&channel_id) else {
+                    error!("server sent SetChannelSubscription for a channel that does not exist");
+                    return;
+                };
 
-                        self.egress.unicast(stream, data.clone());
+                if packet.subscribed {
+                    if self
+                        .channel_manager
+                        .hidden
+                        .get(&channel_id)
+                        .is_some_and(|hidden| hidden.contains(&stream))
+                    {
+                        return;
+                    }
+
+                    debug!("subscribing player {stream} to channel {channel_id}");
+                    channel.subscribed_connections.insert(stream);
+                } else if channel.subscribed_connections.remove(&stream) {
+                    debug!("unsubscribing player {stream} from channel {channel_id}");
+
+                    // Players who disconnected are unsubscribed after they are gone
+                    if self.egress.player_registry.pin().contains_key(&stream) {
+                        self.egress
+                            .unicast(stream, channel.unsubscribe_packets.clone());
                     }
                 }
             }
+            ArchivedServerToProxyMessage::SetChannelHidden(packet) => {
+                let channel_id = packet.channel_id.into();
+                let Ok(stream) = rkyv::deserialize::<u64, !>(&packet.stream);
+
+                if !packet.hidden {
+                    if let Some(hidden) = self.channel_manager.hidden.get_mut(&channel_id) {
+                        hidden.remove(&stream);
+
+                        if hidden.is_empty() {
+                            self.channel_manager.hidden.remove(&channel_id);
+                        }
+                    }
+
+                    // The player is subscribed again by the next position update of the channel
+                    return;
+                }
+
+                self.channel_manager
+                    .hidden
+                    .entry(channel_id)
+                    .or_default()
+                    .insert(stream);
+
+                let Some(channel) = self.channel_manager.channels.get_mut(&channel_id) else {
+                    return;
+                };
+
+                channel.pending_connections.remove(&stream);
+
+                if channel.subscribed_connections.remove(&stream) {
+                    debug!("hiding channel {channel_id} from player {stream}");
+                    self.egress
+                        .unicast(stream, channel.unsubscribe_packets.clone());
+                }
+            }
             ArchivedServerToProxyMessage::BroadcastChannel(packet) => {
                 let exclude = u64::from(packet.exclude);
+                let priority = Priority::from(packet.priority);
                 let data =
                     Bytes::from(rkyv::deserialize::<_, rkyv::rancor::Error>(&packet.data).unwrap());
 
@@ -311,13 +445,17 @@ impl BufferedEgress {
                         continue;
                     }
 
-                    self.egress.unicast(stream, data.clone());
+                    self.egress
+                        .unicast_with_priority(stream, data.clone(), priority);
                 }
             }
             ArchivedServerToProxyMessage::Unicast(unicast) => {
                 let data = rkyv::deserialize::<_, rkyv::rancor::Error>(&unicast.data).unwrap();
-                self.egress
-                    .unicast(unicast.stream.into(), Bytes::from(data));
+                self.egress.unicast_with_priority(
+                    unicast.stream.into(),
+                    Bytes::from(data),
+                    Priority::from(unicast.priority),
+                );
             }
             ArchivedServerToProxyMessage::SetReceiveBroadcasts(pkt) => {
                 self.egress.handle_set_receive_broadcasts(pkt);
@@ -325,6 +463,47 @@ impl BufferedEgress {
             ArchivedServerToProxyMessage::Shutdown(pkt) => {
                 self.egress.handle_shutdown(pkt);
             }
+            ArchivedServerToProxyMessage::UpdateStatus(pkt) => {
+                self.egress.handle_update_status(pkt);
+            }
+            ArchivedServerToProxyMessage::UpdateWaitingRoom(pkt) => {
+                self.egress.handle_update_waiting_room(pkt);
+            }
+            ArchivedServerToProxyMessage::Transfer(pkt) => {
+                self.egress.handle_transfer(pkt);
+            }
+            ArchivedServerToProxyMessage::RegisterServer(pkt) => {
+                self.egress.handle_register_server(pkt);
+            }
+            ArchivedServerToProxyMessage::PlayerCount(pkt) => {
+                self.egress.handle_player_count(pkt);
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_subscriptions() {
+        let mut subscriptions = ChunkSubscriptions::default();
+        let chunk = I16Vec2::new(1, -2);
+
+        subscriptions.subscribe(1, chunk);
+        subscriptions.subscribe(2, chunk);
+        subscriptions.subscribe(2, I16Vec2::ZERO);
+
+        let mut subscribers = subscriptions.subscribers(chunk).collect::<Vec<_>>();
+        subscribers.sort_unstable();
+        assert_eq!(subscribers, [1, 2]);
+
+        subscriptions.unsubscribe(1, chunk);
+        assert_eq!(subscriptions.subscribers(chunk).collect::<Vec<_>>(), [2]);
+
+        subscriptions.clear(2);
+        assert_eq!(subscriptions.subscribers(chunk).count(), 0);
+        assert!(subscriptions.subscribers.is_empty());
+    }
+}
@@ -1,9 +1,16 @@
-use std::sync::{atomic, atomic::AtomicBool};
+use std::sync::{Arc, atomic, atomic::AtomicBool};
 
 use anyhow::bail;
+use arc_swap::ArcSwap;
 use bytes::Bytes;
+use hyperion_proto::Priority;
 use slotmap::{KeyData, new_key_type};
 
+use crate::{
+    backend::Backend,
+    lanes::{Lanes, PushError, Pushed},
+};
+
 new_key_type! {
     pub struct PlayerId;
 }
@@ -17,7 +24,7 @@ impl From<u64> for PlayerId {
 
 #[derive(Debug)]
 pub struct PlayerHandle {
-    writer: kanal::AsyncSender<Bytes>,
+    writer: Arc<Lanes>,
 
     /// Whether the player is allowed to send broadcasts.
     ///
@@ -26,20 +33,40 @@ pub struct PlayerHandle {
     /// they will get packets that it deems are invalid because the broadcasts are using the play
     /// state and play IDs.
     can_receive_broadcasts: AtomicBool,
+
+    /// The server which the player is connected to, which changes when the player is transferred
+    backend: ArcSwap<Backend>,
 }
 
 impl PlayerHandle {
     #[must_use]
-    pub const fn new(writer: kanal::AsyncSender<Bytes>) -> Self {
+    pub fn new(writer: Arc<Lanes>, backend: Arc<Backend>) -> Self {
         Self {
             writer,
             can_receive_broadcasts: AtomicBool::new(false),
+            backend: ArcSwap::new(backend),
         }
     }
 
+    #[must_use]
+    pub fn backend(&self) -> Arc<Backend> {
+        self.backend.load_full()
+    }
+
+    /// Whether the player is connected to `backend`. Packets from other servers are ignored
+    /// because they were sent before the player was transferred.
+    #[must_use]
+    pub fn is_on(&self, backend: &Backend) -> bool {
+        self.backend.load().id() == backend.id()
+    }
+
+    pub fn set_backend(&self, backend: Arc<Backend>) {
+        self.backend.store(backend);
+    }
+
+    /// Disconnects the player once the packets which are already queued have been written
     pub fn shutdown(&self) {
-        // Ignore error for if the channel is already closed
-        let _ = self.writer.close();
+        self.writer.close();
     }
 
     pub fn enable_receive_broadcasts(&self) {
@@ -52,13 +79,17 @@ impl PlayerHandle {
     }
 
     pub fn send(&self, bytes: Bytes) -> anyhow::Result<()> {
-        match self.writer.try_send(bytes) {
-            Ok(true) => Ok(()),
+        self.send_with_priority(Priority::Normal, bytes).map(|_| ())
+    }
 
-            Ok(false) => {
-                let is_full = self.writer.is_full();
+    /// Queues a packet for the player. Bulk packets are dropped rather than disconnecting the
+    /// player if the player cannot keep up.
+    pub fn send_with_priority(&self, priority: Priority, bytes: Bytes) -> anyhow::Result<Pushed> {
+        match self.writer.push(priority, bytes) {
+            Ok(pushed) => Ok(pushed),
+            Err(e @ PushError::Full(_)) => {
                 self.shutdown();
-                bail!("failed to send packet to player, channel is full: {is_full}");
+                bail!("failed to send packet to player, channel is full: {e}");
             }
             Err(e) => {
                 self.shutdown();
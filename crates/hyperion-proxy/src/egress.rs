@@ -1,31 +1,57 @@
+use std::sync::Arc;
+
 use bytes::Bytes;
-use hyperion_proto::{ArchivedSetReceiveBroadcasts, ArchivedShutdown};
+use hyperion_proto::{
+    ArchivedPlayerCount, ArchivedRegisterServer, ArchivedSetReceiveBroadcasts, ArchivedShutdown,
+    ArchivedTransfer, ArchivedUpdateStatus, ArchivedUpdateWaitingRoom, BulkShed, PlayerProfile,
+    PlayerTransfer, Priority, ProxyToServerMessage,
+};
 use rustc_hash::FxBuildHasher;
-use tracing::{error, instrument, warn};
+use tracing::{debug, error, info, instrument, warn};
 
-use crate::{data::PlayerHandle, server_sender::ServerSender};
+use crate::{
+    backend::{Backend, Backends, Registration},
+    data::PlayerHandle,
+    lanes::Pushed,
+    queue::{WaitingRoom, WaitingRoomSender},
+    status::{StatusSender, encode_status_response},
+};
 
 #[derive(Clone)]
 pub struct Egress {
     // todo: can we do some type of EntityId and SlotMap
     pub(crate) player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher>,
-    pub(crate) server_sender: ServerSender,
+    /// The server which this egress receives messages from
+    pub(crate) backend: Arc<Backend>,
+    pub(crate) backends: Arc<Backends>,
+    pub(crate) status: StatusSender,
+    pub(crate) waiting_room: WaitingRoomSender,
 }
 
 impl Egress {
     #[must_use]
     pub const fn new(
         player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher>,
-        server_sender: ServerSender,
+        backend: Arc<Backend>,
+        backends: Arc<Backends>,
+        status: StatusSender,
+        waiting_room: WaitingRoomSender,
     ) -> Self {
         Self {
             player_registry,
-            server_sender,
+            backend,
+            backends,
+            status,
+            waiting_room,
         }
     }
 
-    #[instrument(skip_all)]
     pub fn unicast(&self, stream: u64, data: Bytes) {
+        self.unicast_with_priority(stream, data, Priority::Normal);
+    }
+
+    #[instrument(skip_all)]
+    pub fn unicast_with_priority(&self, stream: u64, data: Bytes, priority: Priority) {
         let players = self.player_registry.pin();
 
         let Some(player) = players.get(&stream) else {
@@ -34,13 +60,40 @@ impl Egress {
             return;
         };
 
+        if !player.is_on(&self.backend) {
+            debug!("Ignoring packets for player {stream:?} who was transferred");
+            return;
+        }
+
         // todo: handle error; kick player if cannot send (buffer full)
-        if let Err(e) = player.send(data) {
-            warn!("Failed to send data to player: {:?}", e);
-            player.shutdown();
+        match player.send_with_priority(priority, data) {
+            Ok(Pushed::Queued | Pushed::Shed { first: false }) => {}
+            Ok(Pushed::Shed { first: true }) => self.report_bulk_shed(stream),
+            Err(e) => {
+                warn!("Failed to send data to player: {:?}", e);
+                player.shutdown();
+            }
         }
     }
 
+    /// Tells the server that bulk packets were dropped for a player so that it can send them again
+    fn report_bulk_shed(&self, stream: u64) {
+        debug!("Dropping bulk packets for player {stream:?} who cannot keep up");
+
+        let message =
+            rkyv::to_bytes::<rkyv::rancor::Error>(&ProxyToServerMessage::BulkShed(BulkShed {
+                stream,
+            }))
+            .unwrap();
+
+        let server_sender = self.backend.server_sender().clone();
+        tokio::spawn(async move {
+            if let Err(e) = server_sender.send(message).await {
+                error!("failed to send bulk shed to server: {e}");
+            }
+        });
+    }
+
     #[instrument(skip_all)]
     pub fn handle_set_receive_broadcasts(&self, pkt: &ArchivedSetReceiveBroadcasts) {
         let player_registry = self.player_registry;
@@ -52,7 +105,9 @@ impl Egress {
             return;
         };
 
-        player.enable_receive_broadcasts();
+        if player.is_on(&self.backend) {
+            player.enable_receive_broadcasts();
+        }
     }
 
     #[instrument(skip_all)]
@@ -62,9 +117,81 @@ impl Egress {
         let Ok(stream) = rkyv::deserialize::<u64, !>(&pkt.stream);
 
         if let Some(result) = players.get(&stream) {
-            result.shutdown();
+            // The previous server may disconnect a player who was transferred
+            if result.is_on(&self.backend) {
+                result.shutdown();
+            }
         } else {
             error!("Player not found for stream {stream:?}");
         }
     }
+
+    #[instrument(skip_all)]
+    pub fn handle_update_status(&self, pkt: &ArchivedUpdateStatus<'_>) {
+        let response = encode_status_response(pkt.json.get());
+        self.status.send_replace(Some(response));
+    }
+
+    #[instrument(skip_all)]
+    pub fn handle_update_waiting_room(&self, pkt: &ArchivedUpdateWaitingRoom<'_>) {
+        let Ok(compression_threshold) = rkyv::deserialize::<i32, !>(&pkt.compression_threshold);
+        let room = WaitingRoom::new(compression_threshold, Bytes::copy_from_slice(&pkt.data));
+        self.waiting_room.send_replace(Some(Arc::new(room)));
+    }
+
+    #[instrument(skip_all)]
+    pub fn handle_transfer(&self, pkt: &ArchivedTransfer<'_>) {
+        let Ok(stream) = rkyv::deserialize::<u64, !>(&pkt.stream);
+        let Ok(uuid) = rkyv::deserialize::<u128, !>(&pkt.profile.uuid);
+
+        let players = self.player_registry.pin();
+        if !players
+            .get(&stream)
+            .is_some_and(|player| player.is_on(&self.backend))
+        {
+            error!("Player not found for stream {stream:?}");
+            return;
+        }
+
+        let player_transfer = rkyv::to_bytes::<rkyv::rancor::Error>(
+            &ProxyToServerMessage::PlayerTransfer(PlayerTransfer {
+                stream,
+                profile: PlayerProfile {
+                    username: pkt.profile.username.get(),
+                    uuid,
+                    textures: pkt.profile.textures.get(),
+                    signature: pkt.profile.signature.get(),
+                },
+            }),
+        )
+        .unwrap();
+
+        tokio::spawn(self.backends.clone().transfer(
+            self.backend.clone(),
+            stream,
+            pkt.server.get().to_owned(),
+            player_transfer,
+            Bytes::copy_from_slice(pkt.on_success.get()),
+        ));
+    }
+
+    #[instrument(skip_all)]
+    pub fn handle_register_server(&self, pkt: &ArchivedRegisterServer<'_>) {
+        let registration = Registration {
+            name: pkt.name.get().to_owned(),
+            region: pkt.region.get().to_owned(),
+            arena: pkt.arena.get().to_owned(),
+        };
+
+        info!("{} registered as {registration:?}", self.backend.address());
+
+        self.backend.register(registration);
+    }
+
+    #[instrument(skip_all)]
+    pub fn handle_player_count(&self, pkt: &ArchivedPlayerCount) {
+        let Ok(online) = rkyv::deserialize::<u32, !>(&pkt.online);
+        let Ok(max) = rkyv::deserialize::<u32, !>(&pkt.max);
+        self.backend.update_player_count(online, max);
+    }
 }
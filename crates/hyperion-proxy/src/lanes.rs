@@ -0,0 +1,368 @@
+//! Outgoing packet queues for a player, with one lane per [`Priority`].
+//!
+//! Packets are always written in the order they were queued, because later packets depend on
+//! earlier ones. For example, the client disconnects if the join teleport arrives before the game
+//! join packet, and a block update is lost if the chunk it modifies arrives after it. The lanes
+//! only decide which packets are dropped: when a player cannot keep up, bulk packets are dropped
+//! before the player is disconnected, and the server is told so that it can send them again.
+//!
+//! The bytes queued for a player are limited so that one slow client cannot use up the memory of
+//! the proxy. Bulk packets may use up to half of the limit. Once the limit is reached, queued bulk
+//! packets are dropped to make room, and the player is disconnected if that is not enough.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use arrayvec::ArrayVec;
+use bytes::Bytes;
+use hyperion_proto::Priority;
+use tokio::sync::Notify;
+
+use crate::metrics::ConnectionMetrics;
+
+/// The maximum number of packets written to the player at once
+pub const BATCH_SIZE: usize = 16;
+
+/// The default limit of bytes queued for each player, set to 8 MiB
+pub const DEFAULT_MAX_QUEUED_BYTES: usize = 8 * 1024 * 1024;
+
+/// The maximum number of pending packets in each lane, indexed by [`lane`]. The player is
+/// disconnected if the critical or normal lane is full.
+const CAPACITY: [usize; 3] = [256, 1_024, 256];
+
+/// Bulk packets are dropped once this many normal packets are pending
+const SHED_THRESHOLD: usize = CAPACITY[1] / 2;
+
+const fn lane(priority: Priority) -> usize {
+    match priority {
+        Priority::Critical => 0,
+        Priority::Normal => 1,
+        Priority::Bulk => 2,
+    }
+}
+
+/// The result of [`Lanes::push`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pushed {
+    Queued,
+
+    /// Bulk packets were dropped. `first` is true for the first packets dropped since the player
+    /// last caught up, in which case the server should be told with
+    /// [`hyperion_proto::BulkShed`].
+    Shed {
+        first: bool,
+    },
+}
+
+/// Returned by [`Lanes::push`] when the packet could not be queued
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PushError {
+    Full(Priority),
+    Closed,
+}
+
+impl std::fmt::Display for PushError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Full(priority) => write!(f, "the {priority:?} lane is full"),
+            Self::Closed => f.write_str("the player connection is closed"),
+        }
+    }
+}
+
+impl std::error::Error for PushError {}
+
+#[derive(Default)]
+struct Queues {
+    /// The packets in each lane with the sequence number they were queued with
+    lanes: [VecDeque<(u64, Bytes)>; 3],
+    /// The sequence number of the next packet
+    next_sequence: u64,
+    /// The bytes queued in each lane
+    bytes: [usize; 3],
+    closed: bool,
+
+    /// Whether bulk packets have been dropped since the lanes were last empty
+    shed: bool,
+}
+
+impl Queues {
+    fn is_empty(&self) -> bool {
+        self.lanes.iter().all(VecDeque::is_empty)
+    }
+
+    fn total_bytes(&self) -> usize {
+        self.bytes.iter().sum()
+    }
+
+    /// Drops the queued bulk packets, returning how many were dropped
+    fn shed_bulk(&mut self) -> usize {
+        let index = lane(Priority::Bulk);
+        let dropped = self.lanes[index].len();
+        self.lanes[index].clear();
+        self.bytes[index] = 0;
+        dropped
+    }
+
+    const fn mark_shed(&mut self) -> Pushed {
+        let first = !self.shed;
+        self.shed = true;
+        Pushed::Shed { first }
+    }
+
+    /// Removes the packet which was queued first across all lanes
+    fn pop_oldest(&mut self) -> Option<Bytes> {
+        let index = (0..self.lanes.len())
+            .filter_map(|index| Some((self.lanes[index].front()?.0, index)))
+            .min()?
+            .1;
+
+        let (_, bytes) = self.lanes[index].pop_front()?;
+        self.bytes[index] -= bytes.len();
+        Some(bytes)
+    }
+}
+
+/// The packets waiting to be written to a player.
+pub struct Lanes {
+    queues: Mutex<Queues>,
+    notify: Notify,
+    max_bytes: usize,
+    metrics: Arc<ConnectionMetrics>,
+}
+
+impl std::fmt::Debug for Lanes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let queues = self.queues.lock().unwrap();
+        f.debug_struct("Lanes")
+            .field("pending", &queues.lanes.each_ref().map(VecDeque::len))
+            .field("bytes", &queues.bytes)
+            .field("closed", &queues.closed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Lanes {
+    /// Creates empty lanes which hold at most `max_bytes` and report their depth to `metrics`
+    #[must_use]
+    pub fn new(max_bytes: usize, metrics: Arc<ConnectionMetrics>) -> Self {
+        Self {
+            queues: Mutex::default(),
+            notify: Notify::new(),
+            max_bytes,
+            metrics,
+        }
+    }
+
+    /// Queues a packet to be written to the player
+    pub fn push(&self, priority: Priority, bytes: Bytes) -> Result<Pushed, PushError> {
+        let mut queues = self.queues.lock().unwrap();
+
+        if queues.closed {
+            return Err(PushError::Closed);
+        }
+
+        let index = lane(priority);
+        let len = bytes.len();
+        let normal_pending = queues.lanes[lane(Priority::Normal)].len();
+        let over_limit = queues.total_bytes() + len > self.max_bytes;
+
+        let (pushed, dropped) = match priority {
+            Priority::Bulk => {
+                if queues.lanes[index].len() >= CAPACITY[index]
+                    || normal_pending >= SHED_THRESHOLD
+                    || queues.total_bytes() + len > self.max_bytes / 2
+                {
+                    let dropped = queues.shed_bulk() + 1;
+                    let pushed = queues.mark_shed();
+                    self.record(&queues, dropped);
+                    return Ok(pushed);
+                }
+
+                (Pushed::Queued, 0)
+            }
+            Priority::Critical | Priority::Normal => {
+                // Pending bulk packets would delay game state even further
+                let dropped = if over_limit || normal_pending >= SHED_THRESHOLD {
+                    queues.shed_bulk()
+                } else {
+                    0
+                };
+                let pushed = if dropped > 0 {
+                    queues.mark_shed()
+                } else {
+                    Pushed::Queued
+                };
+
+                if queues.lanes[index].len() >= CAPACITY[index]
+                    || queues.total_bytes() + len > self.max_bytes
+                {
+                    self.record(&queues, dropped);
+                    self.metrics.add_queue_overflow();
+                    return Err(PushError::Full(priority));
+                }
+
+                (pushed, dropped)
+            }
+        };
+
+        let sequence = queues.next_sequence;
+        queues.next_sequence += 1;
+        queues.lanes[index].push_back((sequence, bytes));
+        queues.bytes[index] += len;
+        self.record(&queues, dropped);
+        drop(queues);
+
+        self.notify.notify_one();
+        Ok(pushed)
+    }
+
+    fn record(&self, queues: &Queues, dropped: usize) {
+        self.metrics.set_queued_bytes(queues.total_bytes());
+        if dropped > 0 {
+            self.metrics.add_shed(dropped);
+        }
+    }
+
+    /// Stops accepting packets. Packets which are already queued are still written.
+    pub fn close(&self) {
+        self.queues.lock().unwrap().closed = true;
+        self.notify.notify_one();
+    }
+
+    /// Waits for packets and moves up to [`BATCH_SIZE`] of them into `batch` in the order they were
+    /// queued. Returns `false` once the lanes are closed and every queued packet has been taken.
+    pub async fn next_batch(&self, batch: &mut ArrayVec<Bytes, BATCH_SIZE>) -> bool {
+        loop {
+            {
+                let mut queues = self.queues.lock().unwrap();
+
+                if !queues.is_empty() {
+                    while !batch.is_full()
+                        && let Some(bytes) = queues.pop_oldest()
+                    {
+                        batch.push(bytes);
+                    }
+
+                    if queues.is_empty() {
+                        queues.shed = false;
+                    }
+
+                    self.record(&queues, 0);
+                    return true;
+                }
+
+                if queues.closed {
+                    return false;
+                }
+            }
+
+            self.notify.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::ProxyMetrics;
+
+    fn packet(byte: u8) -> Bytes {
+        Bytes::from(vec![byte])
+    }
+
+    fn lanes(max_bytes: usize) -> Lanes {
+        let metrics = Arc::new(ProxyMetrics::default());
+        Lanes::new(max_bytes, metrics.add_connection(1))
+    }
+
+    #[tokio::test]
+    async fn test_queue_order() {
+        let lanes = lanes(DEFAULT_MAX_QUEUED_BYTES);
+
+        lanes.push(Priority::Normal, packet(1)).unwrap();
+        lanes.push(Priority::Critical, packet(0)).unwrap();
+        for _ in 0..10 {
+            lanes.push(Priority::Bulk, packet(2)).unwrap();
+            lanes.push(Priority::Normal, packet(1)).unwrap();
+        }
+
+        let mut batch = ArrayVec::new();
+        assert!(lanes.next_batch(&mut batch).await);
+
+        let lanes_in_batch = batch.iter().map(|bytes| bytes[0]).collect::<Vec<_>>();
+        assert_eq!(lanes_in_batch[..4], [1, 0, 2, 1]);
+        assert_eq!(lanes_in_batch.len(), BATCH_SIZE);
+
+        batch.clear();
+        assert!(lanes.next_batch(&mut batch).await);
+        assert_eq!(batch.len(), 22 - BATCH_SIZE);
+    }
+
+    #[tokio::test]
+    async fn test_shed_bulk() {
+        let lanes = lanes(DEFAULT_MAX_QUEUED_BYTES);
+
+        for _ in 0..SHED_THRESHOLD {
+            lanes.push(Priority::Normal, packet(1)).unwrap();
+        }
+
+        assert_eq!(
+            lanes.push(Priority::Bulk, packet(2)),
+            Ok(Pushed::Shed { first: true })
+        );
+        assert_eq!(
+            lanes.push(Priority::Bulk, packet(2)),
+            Ok(Pushed::Shed { first: false })
+        );
+
+        for _ in SHED_THRESHOLD..CAPACITY[1] {
+            lanes.push(Priority::Normal, packet(1)).unwrap();
+        }
+        assert_eq!(
+            lanes.push(Priority::Normal, packet(1)),
+            Err(PushError::Full(Priority::Normal))
+        );
+
+        // Packets which were queued before closing are still written
+        lanes.close();
+        let mut batch = ArrayVec::new();
+        while lanes.next_batch(&mut batch).await {
+            batch.clear();
+        }
+        assert_eq!(
+            lanes.push(Priority::Critical, packet(0)),
+            Err(PushError::Closed)
+        );
+    }
+
+    #[test]
+    fn test_byte_limit() {
+        let lanes = lanes(100);
+        let chunk = Bytes::from(vec![0; 40]);
+
+        // Bulk packets may only use half of the limit
+        assert_eq!(
+            lanes.push(Priority::Bulk, chunk.clone()),
+            Ok(Pushed::Queued)
+        );
+        assert_eq!(
+            lanes.push(Priority::Bulk, chunk.clone()),
+            Ok(Pushed::Shed { first: true })
+        );
+
+        // Bulk packets are dropped to make room before the player is disconnected
+        lanes.push(Priority::Bulk, chunk.clone()).unwrap();
+        lanes.push(Priority::Normal, chunk.clone()).unwrap();
+        assert_eq!(
+            lanes.push(Priority::Normal, chunk.clone()),
+            Ok(Pushed::Shed { first: false })
+        );
+        assert_eq!(
+            lanes.push(Priority::Normal, chunk),
+            Err(PushError::Full(Priority::Normal))
+        );
+    }
+}
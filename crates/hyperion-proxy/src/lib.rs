@@ -33,23 +33,34 @@ use tokio_util::net::Listener;
 use tracing::{Instrument, debug, error, info, info_span, instrument, trace, warn};
 
 use crate::{
-    cache::BufferedEgress, data::PlayerHandle, egress::Egress, player::initiate_player_connection,
+    backend::{Backend, Backends},
+    cache::BufferedEgress,
+    data::PlayerHandle,
+    egress::Egress,
+    lanes::Lanes,
+    metrics::ProxyMetrics,
+    player::initiate_player_connection,
+    queue::PlayerLimit,
+    routing::Routing,
     server_sender::launch_server_writer,
+    translation::Translations,
 };
 
 /// 4 KiB
 const DEFAULT_BUFFER_SIZE: usize = 4 * 1024;
 
-/// Maximum number of pending messages in a player's communication channel.
-/// If this limit is exceeded, the player will be disconnected to prevent
-/// memory exhaustion from slow or unresponsive clients.
-const MAX_PLAYER_PENDING_MESSAGES: usize = 1_024;
-
+pub mod backend;
 pub mod cache;
 pub mod data;
 pub mod egress;
+pub mod lanes;
+pub mod metrics;
 pub mod player;
+pub mod queue;
+pub mod routing;
 pub mod server_sender;
+pub mod status;
+pub mod translation;
 pub mod util;
 
 #[tracing::instrument(level = "trace", skip_all)]
@@ -77,7 +88,19 @@ pub async fn run_proxy(
     root_ca_cert_path: &Path,
     proxy_cert_path: &Path,
     proxy_private_key_path: &Path,
+    shards: Vec<String>,
+    routing: Routing,
+    player_limit: PlayerLimit,
+    max_queued_bytes: usize,
+    translations: Translations,
+    metrics: Arc<ProxyMetrics>,
 ) -> anyhow::Result<()> {
+    let player_limit = Arc::new(player_limit);
+    let translations = Arc::new(translations);
+
+    // Servers request transfers to the primary server by its address
+    let server_address = server_name.clone();
+
     // Remove port
     let Some(port_index) = server_name.rfind(':') else {
         anyhow::bail!("server name is missing port");
@@ -160,7 +183,7 @@ pub async fn run_proxy(
                 let server_socket = connect(server_addr.clone()).await;
                 server_socket.set_nodelay(true).unwrap();
 
-                if let Err(e) = connect_to_server_and_run_proxy(&mut listener, server_socket, server_name.clone(), config.clone(), shutdown_rx.clone(), shutdown_tx.clone()).await {
+                if let Err(e) = connect_to_server_and_run_proxy(&mut listener, server_socket, server_address.clone(), server_name.clone(), config.clone(), shutdown_rx.clone(), shutdown_tx.clone(), shards.clone(), routing, player_limit.clone(), max_queued_bytes, translations.clone(), metrics.clone()).await {
                     error!("Error connecting to server: {e:?}");
                 }
 
@@ -174,31 +197,60 @@ pub async fn run_proxy(
 async fn connect_to_server_and_run_proxy(
     listener: &mut impl HyperionListener,
     server_socket: TcpStream,
+    server_address: String,
     server_name: ServerName<'static>,
     config: Arc<ClientConfig>,
     shutdown_rx: tokio::sync::watch::Receiver<Option<ShutdownType>>,
     shutdown_tx: tokio::sync::watch::Sender<Option<ShutdownType>>,
+    shards: Vec<String>,
+    routing: Routing,
+    player_limit: Arc<PlayerLimit>,
+    max_queued_bytes: usize,
+    translations: Arc<Translations>,
+    metrics: Arc<ProxyMetrics>,
 ) -> anyhow::Result<()> {
     info!("🔗 Connected to server, accepting connections");
 
-    let connector = TlsConnector::from(config);
+    let connector = TlsConnector::from(config.clone());
     let server_stream = connector
         .connect(server_name, server_socket)
         .await
         .context("failed to connect to game server")?;
 
     let (server_read, server_write) = tokio::io::split(server_stream);
-    let server_sender = launch_server_writer(server_write);
+    let server_sender = launch_server_writer(server_write, metrics.clone());
 
     let player_registry = papaya::HashMap::default();
     let player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher> =
         Box::leak(Box::new(player_registry));
 
-    let egress = Egress::new(player_registry, server_sender.clone());
+    // The status and the waiting room are reset when reconnecting because they describe the
+    // previous server
+    let (status_sender, status_receiver) = tokio::sync::watch::channel(None);
+    let (waiting_room_sender, waiting_room_receiver) = tokio::sync::watch::channel(None);
+
+    let backend = Arc::new(Backend::new(0, server_address, server_sender));
+    let backends = Arc::new(Backends::new(
+        config,
+        player_registry,
+        metrics.clone(),
+        shutdown_rx.clone(),
+        routing.strategy(),
+        backend.clone(),
+    ));
+    backends.connect_shards(shards);
+
+    let egress = Egress::new(
+        player_registry,
+        backend.clone(),
+        backends.clone(),
+        status_sender,
+        waiting_room_sender,
+    );
 
     let egress = BufferedEgress::new(egress);
 
-    let mut handler = IngressHandler::new(BufReader::new(server_read), egress);
+    let mut handler = IngressHandler::new(BufReader::new(server_read), egress, metrics.clone());
 
     tokio::spawn({
         let mut shutdown_rx = shutdown_rx.clone();
@@ -247,9 +299,13 @@ async fn connect_to_server_and_run_proxy(
 
         let registry = player_registry.pin();
 
-        // todo: re-add bounding but issues if have MASSIVE number of packets
-        let (tx, rx) = kanal::bounded_async(MAX_PLAYER_PENDING_MESSAGES);
-        registry.insert(player_id_on, PlayerHandle::new(tx));
+        // Each lane is bounded so that slow or unresponsive clients cannot exhaust memory
+        let connection_metrics = metrics.add_connection(player_id_on);
+        let lanes = Arc::new(Lanes::new(max_queued_bytes, connection_metrics.clone()));
+        registry.insert(
+            player_id_on,
+            PlayerHandle::new(lanes.clone(), backend.clone()),
+        );
 
         // todo: some SlotMap like thing
         debug!("got player with id {player_id_on:?}");
@@ -258,9 +314,15 @@ async fn connect_to_server_and_run_proxy(
             socket,
             shutdown_rx.clone(),
             player_id_on,
-            rx,
-            server_sender.clone(),
+            lanes,
             player_registry,
+            status_receiver.clone(),
+            waiting_room_receiver.clone(),
+            backends.clone(),
+            player_limit.clone(),
+            translations.clone(),
+            connection_metrics,
+            metrics.clone(),
         );
 
         player_id_on += 1;
@@ -271,6 +333,7 @@ struct IngressHandler<R> {
     server_read: BufReader<R>,
     buffer: Vec<u8>,
     egress: BufferedEgress,
+    metrics: Arc<ProxyMetrics>,
 }
 
 impl<R> Debug for IngressHandler<R> {
@@ -283,11 +346,16 @@ impl<R> IngressHandler<R>
 where
     R: AsyncRead + Unpin,
 {
-    pub fn new(server_read: BufReader<R>, egress: BufferedEgress) -> Self {
+    pub fn new(
+        server_read: BufReader<R>,
+        egress: BufferedEgress,
+        metrics: Arc<ProxyMetrics>,
+    ) -> Self {
         Self {
             server_read,
             egress,
             buffer: Vec::with_capacity(DEFAULT_BUFFER_SIZE),
+            metrics,
         }
     }
 
@@ -300,6 +368,8 @@ where
 
         trace!("Received packet of length {len}");
 
+        self.metrics.server.add_in(size_of::<u64>() + len);
+
         self.handle_next_server_packet(len).await
     }
 
@@ -1,7 +1,14 @@
-use std::{fmt::Debug, net::SocketAddr, path::PathBuf};
+use std::{fmt::Debug, net::SocketAddr, path::PathBuf, sync::Arc};
 
 use clap::Parser;
-use hyperion_proxy::run_proxy;
+use hyperion_proxy::{
+    lanes::DEFAULT_MAX_QUEUED_BYTES,
+    metrics::{ProxyMetrics, serve_metrics},
+    queue::PlayerLimit,
+    routing::Routing,
+    run_proxy,
+    translation::Translations,
+};
 use serde::Deserialize;
 use tokio::net::TcpListener;
 #[cfg(unix)]
@@ -38,6 +45,50 @@ struct Params {
     /// The file path to the proxy private key
     #[clap(long)]
     private_key: PathBuf,
+
+    /// The address to serve bandwidth metrics on in the Prometheus text format, such as
+    /// "127.0.0.1:9100". Metrics are not served if this is not set.
+    #[clap(long)]
+    #[serde(default)]
+    metrics_addr: Option<SocketAddr>,
+
+    /// The maximum number of players connected through the proxy. Players joining while this many
+    /// players are connected wait in a queue. The number of players is unlimited if this is not
+    /// set.
+    #[clap(long)]
+    #[serde(default)]
+    max_players: Option<usize>,
+
+    /// The maximum number of players waiting in the queue. Players joining while the queue is full
+    /// are disconnected.
+    #[clap(long, default_value_t = 1000)]
+    #[serde(default = "default_max_queued")]
+    max_queued: usize,
+
+    /// The maximum number of bytes waiting to be sent to each player. Chunks and other bulk data
+    /// are dropped first when a player falls behind, and the player is disconnected if their
+    /// other packets reach this limit.
+    #[clap(long, default_value_t = DEFAULT_MAX_QUEUED_BYTES)]
+    #[serde(default = "default_max_queued_bytes")]
+    max_queued_bytes: usize,
+
+    /// The addresses of additional game servers which new players are routed to along with
+    /// `server`. Can be given multiple times.
+    #[clap(long = "shard")]
+    #[serde(default)]
+    shards: Vec<String>,
+
+    /// How new players are assigned to game servers when there are shards
+    #[clap(long, value_enum, default_value_t)]
+    #[serde(default)]
+    routing: Routing,
+
+    /// A directory of mapping tables for client versions other than the server version. Clients
+    /// on versions with a mapping table have their packets translated so that they can join. Only
+    /// versions older than 1.20.2 can be translated.
+    #[clap(long)]
+    #[serde(default)]
+    mappings: Option<PathBuf>,
 }
 
 fn default_proxy_addr() -> String {
@@ -48,6 +99,14 @@ fn default_server() -> String {
     "127.0.0.1:35565".to_string()
 }
 
+const fn default_max_queued() -> usize {
+    1000
+}
+
+const fn default_max_queued_bytes() -> usize {
+    DEFAULT_MAX_QUEUED_BYTES
+}
+
 #[derive(Debug)]
 enum ProxyAddress {
     Tcp(SocketAddr),
@@ -135,6 +194,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let server_help = "~ The event server internal address".dimmed();
     info!("👾 Internal server address: tcp://{server_addr} {server_help}");
 
+    let metrics = Arc::new(ProxyMetrics::default());
+
+    if let Some(metrics_addr) = params.metrics_addr {
+        tokio::spawn({
+            let metrics = metrics.clone();
+            async move {
+                if let Err(e) = serve_metrics(metrics_addr, metrics).await {
+                    error!("Failed to serve metrics: {e:?}");
+                }
+            }
+        });
+    }
+
+    let player_limit = PlayerLimit::new(params.max_players, params.max_queued);
+
+    let translations = match &params.mappings {
+        Some(dir) => Translations::load_dir(dir)?,
+        None => Translations::default(),
+    };
+
     let handle = tokio::spawn(async move {
         match &proxy_addr {
             ProxyAddress::Tcp(addr) => {
@@ -147,6 +226,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     &params.root_ca_cert,
                     &params.cert,
                     &params.private_key,
+                    params.shards,
+                    params.routing,
+                    player_limit,
+                    params.max_queued_bytes,
+                    translations,
+                    metrics,
                 )
                 .await
                 .unwrap();
@@ -163,6 +248,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     &params.root_ca_cert,
                     &params.cert,
                     &params.private_key,
+                    params.shards,
+                    params.routing,
+                    player_limit,
+                    params.max_queued_bytes,
+                    translations,
+                    metrics,
                 )
                 .await
                 .unwrap();
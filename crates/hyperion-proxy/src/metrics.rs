@@ -0,0 +1,455 @@
+//! Bandwidth metrics exposed in the Prometheus text format.
+
+use std::{
+    fmt::{Debug, Write},
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use rustc_hash::FxBuildHasher;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+use tracing::{info, warn};
+
+use crate::{
+    queue::{LOGIN_SUCCESS_ID, SET_COMPRESSION_ID},
+    status::read_var_int,
+};
+
+/// Counts the bytes sent and received over a connection, and the size of the packets sent before
+/// and after compression.
+#[derive(Default, Debug)]
+pub struct ByteCounters {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    uncompressed: AtomicU64,
+    compressed: AtomicU64,
+}
+
+impl ByteCounters {
+    pub fn add_in(&self, bytes: usize) {
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_out(&self, bytes: usize) {
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_encoded(&self, uncompressed: u64, compressed: u64) {
+        self.uncompressed.fetch_add(uncompressed, Ordering::Relaxed);
+        self.compressed.fetch_add(compressed, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in.load(Ordering::Relaxed)
+    }
+
+    #[must_use]
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+
+    /// The size of the packets sent before compression
+    #[must_use]
+    pub fn uncompressed(&self) -> u64 {
+        self.uncompressed.load(Ordering::Relaxed)
+    }
+
+    /// The size of the packets sent after compression
+    #[must_use]
+    pub fn compressed(&self) -> u64 {
+        self.compressed.load(Ordering::Relaxed)
+    }
+}
+
+/// Reads the headers of the packets sent to a player to find their size before compression. The
+/// proxy forwards packets without decoding them, so this follows the login packets to find out
+/// when compression is enabled.
+#[derive(Default, Debug)]
+pub struct PacketSizes {
+    compressed: bool,
+    logged_in: bool,
+}
+
+impl PacketSizes {
+    /// The size of the packets in `bytes` before compression. Anything after a malformed or
+    /// incomplete packet is counted at its size in `bytes`.
+    pub fn uncompressed_len(&mut self, mut bytes: &[u8]) -> u64 {
+        let mut total = 0;
+
+        while !bytes.is_empty() {
+            let Some((frame_len, uncompressed)) = self.read_packet(bytes) else {
+                total += bytes.len() as u64;
+                break;
+            };
+
+            total += uncompressed as u64;
+            bytes = &bytes[frame_len..];
+        }
+
+        total
+    }
+
+    /// Reads the packet at the start of `bytes`, returning the length of its frame and its size
+    /// before compression
+    fn read_packet(&mut self, bytes: &[u8]) -> Option<(usize, usize)> {
+        let (packet_len, prefix_len) = read_var_int(bytes).ok()??;
+        let packet_len = usize::try_from(packet_len).ok()?;
+        let packet = bytes.get(prefix_len..prefix_len + packet_len)?;
+
+        let (uncompressed, body) = if self.compressed {
+            // A data length of 0 means that the packet is below the compression threshold
+            let (data_len, data_len_size) = read_var_int(packet).ok()??;
+            match usize::try_from(data_len).ok()? {
+                0 => (packet_len - data_len_size, &packet[data_len_size..]),
+                // Login packets are never large enough to be compressed
+                data_len => (data_len, &[][..]),
+            }
+        } else {
+            (packet_len, packet)
+        };
+
+        if !self.logged_in {
+            self.read_login_packet(body);
+        }
+
+        Some((prefix_len + packet_len, uncompressed))
+    }
+
+    fn read_login_packet(&mut self, body: &[u8]) {
+        let Ok(Some((id, id_len))) = read_var_int(body) else {
+            return;
+        };
+
+        match id {
+            LOGIN_SUCCESS_ID => self.logged_in = true,
+            SET_COMPRESSION_ID => {
+                if let Ok(Some((threshold, _))) = read_var_int(&body[id_len..]) {
+                    self.compressed = threshold >= 0;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Bandwidth and send queue usage of the proxy.
+#[derive(Default, Debug)]
+pub struct ProxyMetrics {
+    /// Bytes exchanged with the game server, including the length prefix of each message
+    pub server: ByteCounters,
+
+    /// Bytes exchanged with all players
+    pub players: ByteCounters,
+
+    /// Bytes exchanged with each connected player
+    connections: papaya::HashMap<u64, Arc<ConnectionMetrics>, FxBuildHasher>,
+
+    /// Bulk packets dropped because players could not keep up
+    shed_packets: AtomicU64,
+
+    /// Players disconnected because their send queue was full
+    queue_overflows: AtomicU64,
+}
+
+/// Bandwidth and send queue usage of a single player connection. Bytes are also added to the
+/// player totals in [`ProxyMetrics`].
+pub struct ConnectionMetrics {
+    metrics: Arc<ProxyMetrics>,
+    counters: ByteCounters,
+
+    /// Bytes waiting to be written to the player
+    queued_bytes: AtomicU64,
+}
+
+impl Debug for ConnectionMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // `metrics` is skipped because it contains this connection
+        f.debug_struct("ConnectionMetrics")
+            .field("counters", &self.counters)
+            .field("queued_bytes", &self.queued_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ConnectionMetrics {
+    pub fn add_in(&self, bytes: usize) {
+        self.counters.add_in(bytes);
+        self.metrics.players.add_in(bytes);
+    }
+
+    pub fn add_out(&self, bytes: usize) {
+        self.counters.add_out(bytes);
+        self.metrics.players.add_out(bytes);
+    }
+
+    /// Counts packets sent to the player, whose size before compression was read by `sizes`
+    pub fn add_packets(&self, sizes: &mut PacketSizes, packets: &[u8]) {
+        let uncompressed = sizes.uncompressed_len(packets);
+        let compressed = packets.len() as u64;
+
+        self.counters.add_encoded(uncompressed, compressed);
+        self.metrics.players.add_encoded(uncompressed, compressed);
+    }
+
+    pub fn set_queued_bytes(&self, bytes: usize) {
+        self.queued_bytes.store(bytes as u64, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn queued_bytes(&self) -> u64 {
+        self.queued_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn add_shed(&self, packets: usize) {
+        self.metrics
+            .shed_packets
+            .fetch_add(packets as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_queue_overflow(&self) {
+        self.metrics.queue_overflows.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl ProxyMetrics {
+    /// Starts tracking a player connection. The connection should be removed with
+    /// [`ProxyMetrics::remove_connection`] when it is closed.
+    pub fn add_connection(self: &Arc<Self>, player_id: u64) -> Arc<ConnectionMetrics> {
+        let connection = Arc::new(ConnectionMetrics {
+            metrics: self.clone(),
+            counters: ByteCounters::default(),
+            queued_bytes: AtomicU64::default(),
+        });
+
+        self.connections.pin().insert(player_id, connection.clone());
+
+        connection
+    }
+
+    pub fn remove_connection(&self, player_id: u64) {
+        self.connections.pin().remove(&player_id);
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let mut metric = |name: &str, kind: &str, help: &str, series: &[(String, u64)]| {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            for (labels, value) in series {
+                if labels.is_empty() {
+                    let _ = writeln!(out, "{name} {value}");
+                } else {
+                    let _ = writeln!(out, "{name}{{{labels}}} {value}");
+                }
+            }
+        };
+
+        let direction = |counters: &ByteCounters, labels: &str| {
+            [
+                (format!("{labels}direction=\"in\""), counters.bytes_in()),
+                (format!("{labels}direction=\"out\""), counters.bytes_out()),
+            ]
+        };
+
+        metric(
+            "hyperion_proxy_server_bytes_total",
+            "counter",
+            "Bytes exchanged with the game server",
+            &direction(&self.server, ""),
+        );
+
+        metric(
+            "hyperion_proxy_player_bytes_total",
+            "counter",
+            "Bytes exchanged with all players",
+            &direction(&self.players, ""),
+        );
+
+        let size = |counters: &ByteCounters, labels: &str| {
+            [
+                (
+                    format!("{labels}size=\"uncompressed\""),
+                    counters.uncompressed(),
+                ),
+                (
+                    format!("{labels}size=\"compressed\""),
+                    counters.compressed(),
+                ),
+            ]
+        };
+
+        metric(
+            "hyperion_proxy_player_packet_bytes_total",
+            "counter",
+            "Size of the packets sent to all players before and after compression",
+            &size(&self.players, ""),
+        );
+
+        let connections = self.connections.pin();
+        let series = connections
+            .iter()
+            .flat_map(|(player_id, connection)| {
+                direction(&connection.counters, &format!("player_id=\"{player_id}\","))
+            })
+            .collect::<Vec<_>>();
+
+        metric(
+            "hyperion_proxy_connection_bytes_total",
+            "counter",
+            "Bytes exchanged with each connected player",
+            &series,
+        );
+
+        let series = connections
+            .iter()
+            .flat_map(|(player_id, connection)| {
+                size(&connection.counters, &format!("player_id=\"{player_id}\","))
+            })
+            .collect::<Vec<_>>();
+
+        metric(
+            "hyperion_proxy_connection_packet_bytes_total",
+            "counter",
+            "Size of the packets sent to each connected player before and after compression",
+            &series,
+        );
+
+        let queued = connections
+            .iter()
+            .map(|(player_id, connection)| {
+                (
+                    format!("player_id=\"{player_id}\""),
+                    connection.queued_bytes(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let total_queued = queued.iter().map(|(_, bytes)| bytes).sum::<u64>();
+
+        metric(
+            "hyperion_proxy_send_queue_bytes",
+            "gauge",
+            "Bytes waiting to be written to all players",
+            &[(String::new(), total_queued)],
+        );
+
+        metric(
+            "hyperion_proxy_connection_send_queue_bytes",
+            "gauge",
+            "Bytes waiting to be written to each connected player",
+            &queued,
+        );
+
+        metric(
+            "hyperion_proxy_shed_packets_total",
+            "counter",
+            "Bulk packets dropped because players could not keep up",
+            &[(String::new(), self.shed_packets.load(Ordering::Relaxed))],
+        );
+
+        metric(
+            "hyperion_proxy_send_queue_overflows_total",
+            "counter",
+            "Players disconnected because their send queue was full",
+            &[(String::new(), self.queue_overflows.load(Ordering::Relaxed))],
+        );
+
+        out
+    }
+}
+
+/// Serves the metrics over HTTP at `addr`. Every request receives the metrics regardless of its
+/// path.
+pub async fn serve_metrics(addr: SocketAddr, metrics: Arc<ProxyMetrics>) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("📊 Serving metrics on http://{addr}");
+
+    loop {
+        let (mut socket, _) = listener.accept().await?;
+        let metrics = metrics.clone();
+
+        tokio::spawn(async move {
+            // The request is not parsed because the same response is sent for every request
+            let mut request = [0; 1024];
+            if let Err(e) = socket.read(&mut request).await {
+                warn!("failed to read metrics request: {e}");
+                return;
+            }
+
+            let body = metrics.render();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: \
+                 {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+
+            if let Err(e) = socket.write_all(response.as_bytes()).await {
+                warn!("failed to write metrics response: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Arc::new(ProxyMetrics::default());
+        metrics.server.add_in(10);
+
+        let connection = metrics.add_connection(1);
+        connection.add_out(5);
+        connection.set_queued_bytes(7);
+        connection.add_shed(2);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("hyperion_proxy_server_bytes_total{direction=\"in\"} 10"));
+        assert!(rendered.contains("hyperion_proxy_player_bytes_total{direction=\"out\"} 5"));
+        assert!(rendered.contains(
+            "hyperion_proxy_connection_bytes_total{player_id=\"1\",direction=\"out\"} 5"
+        ));
+        assert!(rendered.contains("hyperion_proxy_send_queue_bytes 7"));
+        assert!(rendered.contains("hyperion_proxy_shed_packets_total 2"));
+
+        metrics.remove_connection(1);
+        assert!(!metrics.render().contains("player_id=\"1\""));
+    }
+
+    #[test]
+    fn test_packet_sizes() {
+        let mut sizes = PacketSizes::default();
+
+        // Set compression with a threshold of 256, and then login success below the threshold
+        let login = [3, 0x03, 0x80, 0x02, 4, 0, 0x02, 0xAA, 0xBB];
+        assert_eq!(sizes.uncompressed_len(&login), 3 + 3);
+
+        // A compressed packet with a data length of 300, and a play packet with the same id as set
+        // compression which must not change the format
+        let play = [5, 0xAC, 0x02, 0x78, 0x9C, 0x00, 3, 0, 0x03, 0xFF];
+        assert_eq!(sizes.uncompressed_len(&play), 300 + 2);
+
+        let metrics = Arc::new(ProxyMetrics::default());
+        let connection = metrics.add_connection(1);
+        connection.add_packets(&mut sizes, &play);
+
+        let rendered = metrics.render();
+        assert!(
+            rendered
+                .contains("hyperion_proxy_player_packet_bytes_total{size=\"uncompressed\"} 302")
+        );
+        assert!(rendered.contains(
+            "hyperion_proxy_connection_packet_bytes_total{player_id=\"1\",size=\"compressed\"} 10"
+        ));
+    }
+}
@@ -1,6 +1,13 @@
 //! Player connection handling and packet processing.
 
-use std::io::IoSlice;
+use std::{
+    io::IoSlice,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
 
 use arrayvec::ArrayVec;
 use bytes::Bytes;
@@ -16,12 +23,25 @@ use tokio::{
 use tracing::{info, info_span, instrument, warn};
 
 use crate::{
-    ShutdownType, data::PlayerHandle, server_sender::ServerSender, util::AsyncWriteVectoredExt,
+    ShutdownType,
+    backend::Backends,
+    data::PlayerHandle,
+    lanes::{self, Lanes},
+    metrics::{ConnectionMetrics, PacketSizes, ProxyMetrics},
+    queue::{self, PlayerLimit, ServerLogin, WaitingRoomReceiver},
+    server_sender::ServerSender,
+    status::{self, Handshake, StatusReceiver},
+    translation::{self, SERVER_PROTOCOL_VERSION, Translations, Translator},
+    util::AsyncWriteVectoredExt,
 };
 
 /// Default buffer size for reading player packets, set to 8 KiB.
 const DEFAULT_READ_BUFFER_SIZE: usize = 8 * 1024;
 
+/// How long a connection may take to send its handshake, and to finish a status exchange answered
+/// by the proxy
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Initiates a player connection handler, managing both incoming and outgoing packet streams.
 ///
 /// This function sets up two asynchronous tasks:
@@ -34,9 +54,15 @@ pub fn initiate_player_connection(
     socket: impl tokio::io::AsyncRead + AsyncWrite + Send + 'static,
     mut shutdown_signal: tokio::sync::watch::Receiver<Option<ShutdownType>>,
     player_id: u64,
-    incoming_packet_receiver: kanal::AsyncReceiver<Bytes>,
-    server_sender: ServerSender,
+    outgoing_packets: Arc<Lanes>,
     player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher>,
+    status: StatusReceiver,
+    waiting_room: WaitingRoomReceiver,
+    backends: Arc<Backends>,
+    player_limit: Arc<PlayerLimit>,
+    translations: Arc<Translations>,
+    connection_metrics: Arc<ConnectionMetrics>,
+    metrics: Arc<ProxyMetrics>,
 ) -> JoinHandle<()> {
     let span = info_span!("player_connection", player_id);
     let _enter = span.enter();
@@ -47,13 +73,120 @@ pub fn initiate_player_connection(
     let mut socket_reader = Box::pin(socket_reader);
     let mut socket_writer = Box::pin(socket_writer);
 
+    // Whether the server has been told about this connection. Connections whose status request is
+    // answered by the proxy are never seen by the server.
+    let connected = Arc::new(AtomicBool::new(false));
+
+    // Translates packets sent to players on another protocol version, which is only known once the
+    // handshake is read
+    let clientbound = Arc::new(Mutex::new(None));
+
+    // Removes the server's login packets for players who were logged in by the waiting room
+    let server_login = Arc::new(Mutex::new(None));
+
     // Task for handling incoming packets (player -> proxy)
     let mut packet_reader_task = tokio::spawn({
-        let server_sender = server_sender.clone();
+        let connected = connected.clone();
+        let clientbound = clientbound.clone();
+        let server_login = server_login.clone();
+        let connection_metrics = connection_metrics.clone();
         async move {
             let mut read_buffer = Vec::new();
             let player_stream_id = player_id;
 
+            let send = |bytes| {
+                let players = player_registry.pin();
+                let Some(player) = players.get(&player_id) else {
+                    anyhow::bail!("player is no longer registered");
+                };
+                player.send(bytes)
+            };
+
+            let handshake = tokio::time::timeout(
+                STATUS_TIMEOUT,
+                status::try_answer_status(&mut socket_reader, &mut read_buffer, &status, send),
+            )
+            .await;
+
+            match handshake {
+                Ok(Ok(Handshake::Forward)) => {}
+                Ok(Ok(Handshake::Answered)) => return,
+                Ok(Err(e)) => {
+                    warn!("Error reading handshake from player: {e:?}");
+                    return;
+                }
+                Err(_) => {
+                    warn!("Timed out reading handshake from player");
+                    return;
+                }
+            }
+
+            // Held until the connection closes. Status requests which are forwarded to the server
+            // do not take a slot.
+            let login = status::requests_login(&read_buffer);
+
+            // Players on other versions are translated from their handshake onwards
+            let mut serverbound = None;
+            if login
+                && let Some(protocol) = translation::handshake_protocol(&read_buffer)
+                && protocol != SERVER_PROTOCOL_VERSION
+                && let Some(mappings) = translations.get(protocol)
+            {
+                info!("Translating packets of player on {}", mappings.name());
+
+                let (to_server, to_client) = Translator::pair(mappings);
+                serverbound = Some(to_server);
+                *clientbound.lock().unwrap() = Some(to_client);
+            }
+
+            // Translated players would need the waiting room to be translated as well
+            let waiting_room = if serverbound.is_none() {
+                waiting_room.borrow().clone()
+            } else {
+                None
+            };
+
+            let (_slot, mut waiting_room_exit) = if login {
+                match queue::wait_for_slot(
+                    &mut socket_reader,
+                    &mut read_buffer,
+                    &player_limit,
+                    player_id,
+                    waiting_room,
+                    send,
+                )
+                .await
+                {
+                    Ok((slot, exit)) => (Some(slot), exit),
+                    Err(e) => {
+                        warn!("Player left before being admitted: {e:?}");
+                        return;
+                    }
+                }
+            } else {
+                (None, None)
+            };
+
+            if let Some(exit) = &waiting_room_exit {
+                *server_login.lock().unwrap() = Some(exit.server_login());
+            }
+
+            // Status requests which are forwarded are answered by the primary server
+            if login {
+                let server_address = status::server_address(&read_buffer).unwrap_or_default();
+                let backend = backends.route(&server_address);
+
+                let players = player_registry.pin();
+                let Some(player) = players.get(&player_id) else {
+                    return;
+                };
+                player.set_backend(backend);
+            }
+
+            let Some(server_sender) = current_server_sender(player_registry, player_id) else {
+                return;
+            };
+
             let connect = rkyv::to_bytes::<rkyv::rancor::Error>(
                 &ProxyToServerMessage::PlayerConnect(PlayerConnect {
                     stream: player_stream_id,
@@ -66,23 +199,48 @@ pub fn initiate_player_connection(
                 return;
             }
 
+            connected.store(true, Ordering::Relaxed);
+
             let mut arena = Arena::new();
 
             loop {
-                // Ensure the buffer has enough capacity
-                read_buffer.reserve(DEFAULT_READ_BUFFER_SIZE);
+                // Bytes read while inspecting the handshake are forwarded before reading more
+                if read_buffer.is_empty() {
+                    // Ensure the buffer has enough capacity
+                    read_buffer.reserve(DEFAULT_READ_BUFFER_SIZE);
 
-                let bytes_read = match socket_reader.read_buf(&mut read_buffer).await {
-                    Ok(n) => n,
-                    Err(e) => {
-                        warn!("Error reading from player: {e:?}");
+                    let bytes_read = match socket_reader.read_buf(&mut read_buffer).await {
+                        Ok(n) => n,
+                        Err(e) => {
+                            warn!("Error reading from player: {e:?}");
+                            return;
+                        }
+                    };
+
+                    if bytes_read == 0 {
+                        warn!("End of stream reached for player");
                         return;
                     }
-                };
+                }
 
-                if bytes_read == 0 {
-                    warn!("End of stream reached for player");
-                    return;
+                connection_metrics.add_in(read_buffer.len());
+
+                if let Some(translator) = &mut serverbound {
+                    match translator.translate(&read_buffer) {
+                        Ok(translated) => {
+                            read_buffer.clear();
+                            read_buffer.extend_from_slice(&translated);
+                        }
+                        Err(e) => {
+                            warn!("Error translating packets from player: {e:?}");
+                            return;
+                        }
+                    }
+
+                    // The rest of an incomplete packet has not been read yet
+                    if read_buffer.is_empty() {
+                        continue;
+                    }
                 }
 
                 let player_packets = ProxyToServerMessage::PlayerPackets(PlayerPackets {
@@ -98,31 +256,49 @@ pub fn initiate_player_connection(
 
                 read_buffer.clear();
 
+                // The server changes when the player is transferred
+                let Some(server_sender) = current_server_sender(player_registry, player_id) else {
+                    return;
+                };
+
                 if let Err(e) = server_sender.send(aligned_vec).await {
                     warn!("Error forwarding player packets to server: {e:?}");
                     return;
                 }
+
+                // The login of players in the waiting room has just been forwarded
+                if let Some(exit) = waiting_room_exit.take()
+                    && let Err(e) = exit.leave(&mut socket_reader, &mut read_buffer).await
+                {
+                    warn!("Error leaving the waiting room: {e:?}");
+                    return;
+                }
             }
         }
     });
 
     // Task for handling outgoing packets (proxy -> player)
     let mut packet_writer_task = tokio::spawn(async move {
-        while let Ok(outgoing_packet) = incoming_packet_receiver.recv().await {
-            let mut bytes = ArrayVec::<_, 16>::new();
-            bytes.push(outgoing_packet);
-
-            // Try reading more bytes from the channel
-            while bytes.remaining_capacity() > 0 {
-                let Ok(Some(outgoing_packet)) = incoming_packet_receiver.try_recv() else {
-                    break;
-                };
-                bytes.push(outgoing_packet);
+        let mut bytes = ArrayVec::new();
+        let mut packet_sizes = PacketSizes::default();
+
+        // The writer finishes once the player is shut down and the remaining packets are written
+        while outgoing_packets.next_batch(&mut bytes).await {
+            if let Err(e) = remove_server_login(&server_login, &mut bytes) {
+                warn!("Error removing login packets of player in the waiting room: {e:?}");
+                return;
+            }
+
+            if let Err(e) = translate_batch(&clientbound, &mut bytes) {
+                warn!("Error translating packets to player: {e:?}");
+                return;
             }
 
             // Convert the bytes into slices
-            let mut slices = ArrayVec::<_, 16>::new();
+            let mut slices = ArrayVec::<_, { lanes::BATCH_SIZE }>::new();
             for slice in &bytes {
+                connection_metrics.add_out(slice.len());
+                connection_metrics.add_packets(&mut packet_sizes, slice);
                 slices.push(IoSlice::new(slice));
             }
 
@@ -130,6 +306,9 @@ pub fn initiate_player_connection(
                 warn!("Error writing packets to player: {e:?}");
                 return;
             }
+
+            drop(slices);
+            bytes.clear();
         }
     });
 
@@ -138,45 +317,110 @@ pub fn initiate_player_connection(
             shutdown_signal.wait_for(Option::is_some).await.unwrap();
         };
 
-        tokio::select! {
+        let shutdown_signal_received = tokio::select! {
             () = shutdown_received => {
                 info!("Shutting down player connection due to server shutdown");
                 packet_reader_task.abort();
                 packet_writer_task.abort();
+                true
             },
             _ = &mut packet_writer_task => {
                 info!("Player disconnected because writer task finished: {player_id:?}");
                 packet_reader_task.abort();
-
-                let disconnect = rkyv::to_bytes::<rkyv::rancor::Error>(
-                    &ProxyToServerMessage::PlayerDisconnect(PlayerDisconnect {
-                        stream: player_id,
-                        reason: PlayerDisconnectReason::LostConnection,
-                    }),
-                ).unwrap();
-
-                if let Err(e) = server_sender.send(disconnect).await {
-                    warn!("failed to send player disconnect to server: {e}");
-                }
+                false
             },
             _ = &mut packet_reader_task => {
                 info!("Player disconnected because reader task finished: {player_id:?}");
                 packet_writer_task.abort();
+                false
+            }
+        };
 
+        // The player is removed before the disconnect is sent so that a transfer in progress
+        // cannot move the player to another server afterwards
+        let backend = player_registry
+            .pin()
+            .remove(&player_id)
+            .map(PlayerHandle::backend);
 
-                let disconnect = rkyv::to_bytes::<rkyv::rancor::Error>(
-                    &ProxyToServerMessage::PlayerDisconnect(PlayerDisconnect {
-                        stream: player_id,
-                        reason: PlayerDisconnectReason::LostConnection,
-                    })).unwrap();
-
-                if let Err(e) = server_sender.send(disconnect).await {
-                    warn!("failed to send player disconnect to server: {e}");
-                }
-            }
+        if let Some(backend) = backend
+            && connected.load(Ordering::Relaxed)
+            && !shutdown_signal_received
+        {
+            send_disconnect(backend.server_sender(), player_id).await;
         }
 
-        let map_ref = player_registry.pin();
-        map_ref.remove(&player_id);
+        metrics.remove_connection(player_id);
     })
 }
+
+/// Translates the packets in `batch` if the player is on another protocol version
+fn translate_batch(
+    clientbound: &Mutex<Option<Translator>>,
+    batch: &mut ArrayVec<Bytes, { lanes::BATCH_SIZE }>,
+) -> anyhow::Result<()> {
+    let mut clientbound = clientbound.lock().unwrap();
+
+    let Some(translator) = clientbound.as_mut() else {
+        return Ok(());
+    };
+
+    for bytes in batch.iter_mut() {
+        *bytes = translator.translate(bytes)?;
+    }
+
+    drop(clientbound);
+
+    Ok(())
+}
+
+/// Removes the login packets in `batch` which the server sends to a player who was logged in by
+/// the waiting room
+fn remove_server_login(
+    server_login: &Mutex<Option<ServerLogin>>,
+    batch: &mut ArrayVec<Bytes, { lanes::BATCH_SIZE }>,
+) -> anyhow::Result<()> {
+    let mut server_login = server_login.lock().unwrap();
+
+    let Some(login) = server_login.as_mut() else {
+        return Ok(());
+    };
+
+    for bytes in batch.iter_mut() {
+        if login.remove_login_packets(bytes)? {
+            *server_login = None;
+            break;
+        }
+    }
+
+    drop(server_login);
+
+    // Empty slices cannot be written
+    batch.retain(|bytes| !bytes.is_empty());
+
+    Ok(())
+}
+
+/// Returns the sender of the server which the player is connected to
+fn current_server_sender(
+    player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher>,
+    player_id: u64,
+) -> Option<ServerSender> {
+    let players = player_registry.pin();
+    let player = players.get(&player_id)?;
+    Some(player.backend().server_sender().clone())
+}
+
+async fn send_disconnect(server_sender: &ServerSender, player_id: u64) {
+    let disconnect = rkyv::to_bytes::<rkyv::rancor::Error>(
+        &ProxyToServerMessage::PlayerDisconnect(PlayerDisconnect {
+            stream: player_id,
+            reason: PlayerDisconnectReason::LostConnection,
+        }),
+    )
+    .unwrap();
+
+    if let Err(e) = server_sender.send(disconnect).await {
+        warn!("failed to send player disconnect to server: {e}");
+    }
+}
@@ -0,0 +1,776 @@
+//! Limits the number of players connected to the server.
+//!
+//! Players who log in while the server is full wait in a queue and are admitted in the order they
+//! joined as slots free.
+//!
+//! Once the server has sent a [`WaitingRoom`], the proxy logs queued players into the empty world it
+//! describes and shows them their position in the action bar. When they are admitted, their login is
+//! forwarded to the server as usual. The login packets which the server sends back are removed by
+//! [`ServerLogin`] because the player is already in the play state, and the server's join packets
+//! replace the waiting room. The player keeps the username and UUID from their login start packet,
+//! so a server which renames players on login should not be used with a player limit.
+//!
+//! Players who are translated to another protocol version, or who log in before the server has
+//! sent a waiting room, wait in the login state, where vanilla clients show "Logging in...". The
+//! proxy keeps them connected by sending login plugin requests on the [`QUEUE_CHANNEL`] channel,
+//! whose data is the player's position in the queue as a `VarInt`, so that modded clients can show
+//! their position.
+
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Context, bail, ensure};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use libdeflater::Decompressor;
+use tokio::{
+    io::AsyncRead,
+    sync::{Notify, OwnedSemaphorePermit, Semaphore},
+};
+use tracing::info;
+
+use crate::status::{next_frame, read_more, read_var_int, write_var_int};
+
+/// The channel of the login plugin requests sent to queued players
+pub const QUEUE_CHANNEL: &str = "hyperion:queue";
+
+/// How often queued players are sent their position, which must be shorter than the 30 second
+/// read timeout of the client and the 3 seconds for which the action bar is shown
+const QUEUE_UPDATE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a player may take to answer the login plugin requests sent while they were queued
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The longest packet which players in the waiting room may send
+const MAX_WAITING_ROOM_PACKET_LEN: usize = 64 * 1024;
+
+const LOGIN_DISCONNECT_ID: i32 = 0x00;
+pub(crate) const LOGIN_SUCCESS_ID: i32 = 0x02;
+pub(crate) const SET_COMPRESSION_ID: i32 = 0x03;
+const LOGIN_PLUGIN_REQUEST_ID: i32 = 0x04;
+const LOGIN_START_ID: i32 = 0x00;
+const LOGIN_PLUGIN_RESPONSE_ID: i32 = 0x02;
+const PLAY_DISCONNECT_ID: i32 = 0x1A;
+const ACTION_BAR_ID: i32 = 0x46;
+const CLIENT_SETTINGS_ID: i32 = 0x08;
+
+/// The empty world which queued players are logged into. See
+/// [`hyperion_proto::UpdateWaitingRoom`].
+#[derive(Debug)]
+pub struct WaitingRoom {
+    compression_threshold: i32,
+    packets: Bytes,
+}
+
+impl WaitingRoom {
+    #[must_use]
+    pub const fn new(compression_threshold: i32, packets: Bytes) -> Self {
+        Self {
+            compression_threshold,
+            packets,
+        }
+    }
+}
+
+/// Sends the most recent waiting room, or `None` if the server has not sent one yet
+pub type WaitingRoomSender = tokio::sync::watch::Sender<Option<Arc<WaitingRoom>>>;
+
+/// Receives the most recent waiting room, or `None` if the server has not sent one yet
+pub type WaitingRoomReceiver = tokio::sync::watch::Receiver<Option<Arc<WaitingRoom>>>;
+
+/// The maximum number of players and queued players.
+pub struct PlayerLimit {
+    /// `None` if the number of players is unlimited
+    slots: Option<Arc<Semaphore>>,
+    max_queued: usize,
+    /// The ids of queued players in the order they are admitted
+    queue: Mutex<VecDeque<u64>>,
+}
+
+/// A slot held by a connected player, which is freed when dropped
+#[derive(Debug)]
+pub struct Slot {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl PlayerLimit {
+    /// Creates a limit of `max_players` connected players, or an unlimited number if `None`. At
+    /// most `max_queued` players wait for a slot, and players joining while the queue is full are
+    /// disconnected.
+    #[must_use]
+    pub fn new(max_players: Option<usize>, max_queued: usize) -> Self {
+        Self {
+            slots: max_players.map(|max_players| Arc::new(Semaphore::new(max_players))),
+            max_queued,
+            queue: Mutex::default(),
+        }
+    }
+
+    /// The number of players waiting for a slot
+    #[must_use]
+    pub fn queued(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    fn try_admit(&self) -> Option<Slot> {
+        // Players cannot skip the queue
+        if self.queued() > 0 {
+            return None;
+        }
+
+        self.slots.as_ref().map_or_else(
+            || Some(Slot { _permit: None }),
+            |slots| {
+                slots.clone().try_acquire_owned().ok().map(|permit| Slot {
+                    _permit: Some(permit),
+                })
+            },
+        )
+    }
+
+    fn enqueue(&self, player_id: u64) -> Option<QueueEntry<'_>> {
+        {
+            let mut queue = self.queue.lock().unwrap();
+
+            if queue.len() >= self.max_queued {
+                return None;
+            }
+
+            queue.push_back(player_id);
+        }
+
+        Some(QueueEntry {
+            limit: self,
+            player_id,
+        })
+    }
+}
+
+/// A queued player, who is removed from the queue when dropped
+struct QueueEntry<'a> {
+    limit: &'a PlayerLimit,
+    player_id: u64,
+}
+
+impl QueueEntry<'_> {
+    /// The position in the queue starting at 1
+    fn position(&self) -> usize {
+        let queue = self.limit.queue.lock().unwrap();
+        queue
+            .iter()
+            .position(|&player_id| player_id == self.player_id)
+            .map_or(queue.len(), |index| index + 1)
+    }
+
+    async fn wait(&self) -> Slot {
+        let slots = self
+            .limit
+            .slots
+            .clone()
+            .expect("players are only queued if the number of players is limited");
+
+        // The semaphore is fair, so players are admitted in the order they were queued
+        let permit = slots
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+
+        Slot {
+            _permit: Some(permit),
+        }
+    }
+}
+
+impl Drop for QueueEntry<'_> {
+    fn drop(&mut self) {
+        let mut queue = self.limit.queue.lock().unwrap();
+        queue.retain(|&player_id| player_id != self.player_id);
+    }
+}
+
+/// Encodes a packet without compressing it. If `compression_threshold` is not negative, the
+/// packet uses the format of compressed connections.
+fn encode_frame(compression_threshold: i32, id: i32, body: &[u8]) -> Bytes {
+    let mut data = BytesMut::with_capacity(body.len() + 6);
+    if compression_threshold >= 0 {
+        // A data length of 0 marks the packet as not compressed
+        write_var_int(&mut data, 0);
+    }
+    write_var_int(&mut data, id);
+    data.put_slice(body);
+
+    let data_len = i32::try_from(data.len()).unwrap_or(i32::MAX);
+
+    let mut packet = BytesMut::with_capacity(data.len() + 5);
+    write_var_int(&mut packet, data_len);
+    packet.put_slice(&data);
+
+    packet.freeze()
+}
+
+fn encode_packet(id: i32, body: &[u8]) -> Bytes {
+    encode_frame(-1, id, body)
+}
+
+fn put_string(buf: &mut BytesMut, string: &str) {
+    write_var_int(buf, i32::try_from(string.len()).unwrap_or(i32::MAX));
+    buf.put_slice(string.as_bytes());
+}
+
+fn encode_login_disconnect(reason: &str) -> Bytes {
+    let mut body = BytesMut::new();
+    put_string(&mut body, &format!(r#"{{"text":"{reason}"}}"#));
+
+    encode_packet(LOGIN_DISCONNECT_ID, &body)
+}
+
+fn encode_queue_position(message_id: i32, position: usize) -> Bytes {
+    let mut body = BytesMut::new();
+    write_var_int(&mut body, message_id);
+    put_string(&mut body, QUEUE_CHANNEL);
+    write_var_int(&mut body, i32::try_from(position).unwrap_or(i32::MAX));
+
+    encode_packet(LOGIN_PLUGIN_REQUEST_ID, &body)
+}
+
+fn encode_action_bar_position(compression_threshold: i32, position: usize) -> Bytes {
+    let mut body = BytesMut::new();
+    put_string(
+        &mut body,
+        &format!(r#"{{"text":"Position in queue: {position}","color":"yellow"}}"#),
+    );
+
+    encode_frame(compression_threshold, ACTION_BAR_ID, &body)
+}
+
+/// Encodes the packets which log a player into the waiting room
+fn encode_waiting_room_login(room: &WaitingRoom, username: &str, uuid: u128) -> Bytes {
+    let mut packets = BytesMut::new();
+
+    let mut body = BytesMut::new();
+    write_var_int(&mut body, room.compression_threshold);
+    packets.put(encode_packet(SET_COMPRESSION_ID, &body));
+
+    let mut body = BytesMut::new();
+    body.put_u128(uuid);
+    put_string(&mut body, username);
+    // The properties, which the player does not need to see themselves
+    write_var_int(&mut body, 0);
+    packets.put(encode_frame(
+        room.compression_threshold,
+        LOGIN_SUCCESS_ID,
+        &body,
+    ));
+
+    packets.put_slice(&room.packets);
+    packets.freeze()
+}
+
+/// The username and UUID in the login start packet at the start of `buf`, or `None` if the
+/// packet does not contain a UUID
+fn login_start_profile(buf: &[u8]) -> Option<(&str, u128)> {
+    let frame = next_frame(buf).ok()??;
+
+    if frame.id != LOGIN_START_ID {
+        return None;
+    }
+
+    let (username_len, prefix_len) = read_var_int(frame.body).ok()??;
+    let username_end = prefix_len + usize::try_from(username_len).ok()?;
+    let username = std::str::from_utf8(frame.body.get(prefix_len..username_end)?).ok()?;
+
+    let (&has_uuid, uuid) = frame.body.get(username_end..)?.split_first()?;
+    if has_uuid != 1 {
+        return None;
+    }
+
+    let uuid = u128::from_be_bytes(uuid.get(..size_of::<u128>())?.try_into().ok()?);
+
+    Some((username, uuid))
+}
+
+/// Reads until `read_buffer` contains `count` complete packets, returning their total length
+async fn read_frames(
+    reader: &mut (impl AsyncRead + Unpin),
+    read_buffer: &mut Vec<u8>,
+    count: usize,
+) -> anyhow::Result<usize> {
+    loop {
+        let mut len = 0;
+        let mut frames = 0;
+
+        while frames < count {
+            let Some(frame) = next_frame(&read_buffer[len..])? else {
+                break;
+            };
+            len += frame.len;
+            frames += 1;
+        }
+
+        if frames == count {
+            return Ok(len);
+        }
+
+        if read_more(reader, read_buffer).await? == 0 {
+            bail!("connection closed while logging in");
+        }
+    }
+}
+
+/// Removes the responses to queue position requests after `start` in `read_buffer`, returning the
+/// number of responses removed
+fn drain_responses(read_buffer: &mut Vec<u8>, start: usize) -> anyhow::Result<usize> {
+    let mut responses = 0;
+
+    while let Some(frame) = next_frame(&read_buffer[start..])? {
+        if frame.id != LOGIN_PLUGIN_RESPONSE_ID {
+            bail!("unexpected packet with id {} while queued", frame.id);
+        }
+
+        let frame_len = frame.len;
+        read_buffer.drain(start..start + frame_len);
+        responses += 1;
+    }
+
+    Ok(responses)
+}
+
+/// Removes the complete packets which a player in the waiting room sent after `start` in
+/// `read_buffer`. The last client settings packet is kept in `settings` so that the server receives
+/// the player's settings.
+fn drain_waiting_room_packets(
+    read_buffer: &mut Vec<u8>,
+    start: usize,
+    compression_threshold: i32,
+    settings: &mut Option<Bytes>,
+) -> anyhow::Result<()> {
+    let mut position = start;
+
+    while let Some((packet_len, prefix_len)) = read_var_int(&read_buffer[position..])? {
+        let packet_len = usize::try_from(packet_len).context("packet length is negative")?;
+        ensure!(
+            packet_len <= MAX_WAITING_ROOM_PACKET_LEN,
+            "packet is too large to be sent in the waiting room ({packet_len} bytes)"
+        );
+
+        let end = position + prefix_len + packet_len;
+        let Some(packet) = read_buffer.get(position + prefix_len..end) else {
+            break;
+        };
+
+        let data = if compression_threshold >= 0 {
+            match read_var_int(packet)?.context("packet is missing its data length")? {
+                (0, data_len_size) => &packet[data_len_size..],
+                // Compressed packets are never client settings
+                _ => &[],
+            }
+        } else {
+            packet
+        };
+
+        if let Some((id, _)) = read_var_int(data)?
+            && id == CLIENT_SETTINGS_ID
+        {
+            *settings = Some(Bytes::copy_from_slice(&read_buffer[position..end]));
+        }
+
+        position = end;
+    }
+
+    read_buffer.drain(start..position);
+
+    Ok(())
+}
+
+/// The data of a packet sent by the server, decompressing it if needed
+fn packet_data(packet: &[u8], compression: bool) -> anyhow::Result<Cow<'_, [u8]>> {
+    if !compression {
+        return Ok(Cow::Borrowed(packet));
+    }
+
+    let (data_len, data_len_size) =
+        read_var_int(packet)?.context("packet is missing its data length")?;
+    let compressed = &packet[data_len_size..];
+
+    if data_len == 0 {
+        return Ok(Cow::Borrowed(compressed));
+    }
+
+    let data_len = usize::try_from(data_len).context("data length is negative")?;
+    let mut data = vec![0; data_len];
+    let written = Decompressor::new()
+        .zlib_decompress(compressed, &mut data)
+        .context("failed to decompress packet")?;
+    ensure!(
+        written == data_len,
+        "decompressed packet has the wrong length"
+    );
+
+    Ok(Cow::Owned(data))
+}
+
+/// Removes the login packets which the server sends to a player who was logged in by the waiting
+/// room, since the player is already in the play state.
+pub struct ServerLogin {
+    /// The compression threshold of the player's connection
+    compression_threshold: i32,
+    /// Whether the server has enabled compression
+    compression: bool,
+    logged_in: Arc<Notify>,
+}
+
+impl ServerLogin {
+    /// Removes the login packets at the start of `bytes`. Returns `true` once the server has
+    /// finished logging in the player and the rest of the packets should be written to the player.
+    ///
+    /// A login disconnect is turned into a play disconnect so that the player is shown the reason.
+    pub fn remove_login_packets(&mut self, bytes: &mut Bytes) -> anyhow::Result<bool> {
+        while !bytes.is_empty() {
+            let (packet_len, prefix_len) =
+                read_var_int(bytes)?.context("login packet is split across messages")?;
+            let packet_len = usize::try_from(packet_len).context("packet length is negative")?;
+            let end = prefix_len + packet_len;
+
+            let packet = bytes
+                .get(prefix_len..end)
+                .context("login packet is split across messages")?;
+            let data = packet_data(packet, self.compression)?;
+            let (id, id_len) = read_var_int(&data)?.context("packet is missing its id")?;
+            let body = &data[id_len..];
+
+            match id {
+                SET_COMPRESSION_ID if !self.compression => {
+                    let (threshold, _) =
+                        read_var_int(body)?.context("set compression is missing threshold")?;
+                    self.compression = threshold >= 0;
+                }
+                LOGIN_SUCCESS_ID => {
+                    bytes.advance(end);
+                    self.logged_in.notify_one();
+                    return Ok(true);
+                }
+                LOGIN_DISCONNECT_ID => {
+                    let disconnect =
+                        encode_frame(self.compression_threshold, PLAY_DISCONNECT_ID, body);
+
+                    let mut rest = BytesMut::from(disconnect);
+                    rest.put_slice(&bytes[end..]);
+                    *bytes = rest.freeze();
+
+                    self.logged_in.notify_one();
+                    return Ok(true);
+                }
+                id => bail!("unexpected packet with id {id} while the server logs in the player"),
+            }
+
+            bytes.advance(end);
+        }
+
+        Ok(false)
+    }
+}
+
+/// A player who was logged in by the waiting room and whose login was forwarded to the server
+pub struct WaitingRoomExit {
+    compression_threshold: i32,
+    /// The last client settings packet sent in the waiting room
+    settings: Option<Bytes>,
+    /// Bytes read after the login start packet which do not form a complete packet yet
+    pending: Vec<u8>,
+    logged_in: Arc<Notify>,
+}
+
+impl WaitingRoomExit {
+    /// Removes the server's login packets, which must happen before the player's login is
+    /// forwarded to the server
+    #[must_use]
+    pub fn server_login(&self) -> ServerLogin {
+        ServerLogin {
+            compression_threshold: self.compression_threshold,
+            compression: false,
+            logged_in: self.logged_in.clone(),
+        }
+    }
+
+    /// Drops the packets which the player sent to the waiting room until the server has logged
+    /// them in. Afterwards, `read_buffer` contains the bytes which must be forwarded to the server.
+    pub async fn leave(
+        self,
+        reader: &mut (impl AsyncRead + Unpin),
+        read_buffer: &mut Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let Self {
+            compression_threshold,
+            mut settings,
+            mut pending,
+            logged_in,
+        } = self;
+
+        loop {
+            drain_waiting_room_packets(&mut pending, 0, compression_threshold, &mut settings)?;
+
+            tokio::select! {
+                () = logged_in.notified() => break,
+                bytes_read = read_more(reader, &mut pending) => {
+                    if bytes_read? == 0 {
+                        bail!("connection closed while the server logs in the player");
+                    }
+                }
+            }
+        }
+
+        // Packets which were read before the server's join packets were sent to the player belong
+        // to the waiting room
+        drain_waiting_room_packets(&mut pending, 0, compression_threshold, &mut settings)?;
+
+        read_buffer.clear();
+        if let Some(settings) = settings {
+            read_buffer.extend_from_slice(&settings);
+        }
+        read_buffer.extend_from_slice(&pending);
+
+        Ok(())
+    }
+}
+
+/// Waits for a slot for a player whose login handshake is in `read_buffer`. Players who must wait
+/// are sent their queue position until a slot is free. If `waiting_room` is given, players who
+/// must wait are logged into it.
+///
+/// When this returns, `read_buffer` contains the unmodified bytes of the handshake and the login
+/// start packet, which must be forwarded to the server. A [`WaitingRoomExit`] is returned if the
+/// player was logged into the waiting room.
+pub async fn wait_for_slot(
+    reader: &mut (impl AsyncRead + Unpin),
+    read_buffer: &mut Vec<u8>,
+    limit: &PlayerLimit,
+    player_id: u64,
+    waiting_room: Option<Arc<WaitingRoom>>,
+    mut send: impl FnMut(Bytes) -> anyhow::Result<()>,
+) -> anyhow::Result<(Slot, Option<WaitingRoomExit>)> {
+    if let Some(slot) = limit.try_admit() {
+        return Ok((slot, None));
+    }
+
+    let Some(entry) = limit.enqueue(player_id) else {
+        send(encode_login_disconnect("The server is full"))?;
+        bail!("the server and the queue are full");
+    };
+
+    // Packets sent while queued are only told apart from the packets which are forwarded to the
+    // server once the login start packet has been read
+    let forwarded_len = read_frames(reader, read_buffer, 2).await?;
+
+    info!(
+        "Server is full, queued player {player_id} at position {}",
+        entry.position()
+    );
+
+    let handshake_len = next_frame(read_buffer)?
+        .context("handshake is missing")?
+        .len;
+
+    let waiting_room = match (
+        waiting_room,
+        login_start_profile(&read_buffer[handshake_len..]),
+    ) {
+        (Some(room), Some((username, uuid))) => {
+            send(encode_waiting_room_login(&room, username, uuid))?;
+            Some(room)
+        }
+        // Players without a UUID cannot be logged in by the proxy because the server chooses
+        // their UUID
+        _ => None,
+    };
+
+    let mut settings = None;
+    let mut message_id = 0;
+    let mut pending_responses = 0_usize;
+    let mut interval = tokio::time::interval(QUEUE_UPDATE_INTERVAL);
+
+    let slot = {
+        let wait = entry.wait();
+        tokio::pin!(wait);
+
+        loop {
+            tokio::select! {
+                slot = &mut wait => break slot,
+                _ = interval.tick() => {
+                    if let Some(room) = &waiting_room {
+                        send(encode_action_bar_position(
+                            room.compression_threshold,
+                            entry.position(),
+                        ))?;
+                    } else {
+                        send(encode_queue_position(message_id, entry.position()))?;
+                        message_id = message_id.wrapping_add(1);
+                        pending_responses += 1;
+                    }
+                }
+                bytes_read = read_more(reader, read_buffer) => {
+                    if bytes_read? == 0 {
+                        bail!("connection closed while queued");
+                    }
+
+                    if let Some(room) = &waiting_room {
+                        drain_waiting_room_packets(
+                            read_buffer,
+                            forwarded_len,
+                            room.compression_threshold,
+                            &mut settings,
+                        )?;
+                    } else {
+                        let responses = drain_responses(read_buffer, forwarded_len)?;
+                        pending_responses = pending_responses.saturating_sub(responses);
+                    }
+                }
+            }
+        }
+    };
+
+    drop(entry);
+
+    info!("Admitted player {player_id} from the queue");
+
+    if let Some(room) = waiting_room {
+        let exit = WaitingRoomExit {
+            compression_threshold: room.compression_threshold,
+            settings,
+            pending: read_buffer.split_off(forwarded_len),
+            logged_in: Arc::new(Notify::new()),
+        };
+
+        return Ok((slot, Some(exit)));
+    }
+
+    // The server must not receive responses to requests which it did not send
+    tokio::time::timeout(RESPONSE_TIMEOUT, async {
+        while pending_responses > 0 || read_buffer.len() > forwarded_len {
+            if read_more(reader, read_buffer).await? == 0 {
+                bail!("connection closed while leaving the queue");
+            }
+
+            let responses = drain_responses(read_buffer, forwarded_len)?;
+            pending_responses = pending_responses.saturating_sub(responses);
+        }
+
+        anyhow::Ok(())
+    })
+    .await
+    .context("timed out waiting for queue position responses")??;
+
+    Ok((slot, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_order() {
+        let limit = PlayerLimit::new(Some(1), 2);
+
+        let slot = limit.try_admit().unwrap();
+        assert!(limit.try_admit().is_none());
+
+        let first = limit.enqueue(1).unwrap();
+        let second = limit.enqueue(2).unwrap();
+        assert!(limit.enqueue(3).is_none());
+        assert_eq!(first.position(), 1);
+        assert_eq!(second.position(), 2);
+
+        drop(first);
+        assert_eq!(second.position(), 1);
+
+        drop(slot);
+        drop(second);
+        assert!(limit.try_admit().is_some());
+    }
+
+    fn login_start(username: &str, uuid: Option<u128>) -> Vec<u8> {
+        let mut body = BytesMut::new();
+        put_string(&mut body, username);
+        body.put_u8(u8::from(uuid.is_some()));
+        if let Some(uuid) = uuid {
+            body.put_u128(uuid);
+        }
+
+        encode_packet(LOGIN_START_ID, &body).to_vec()
+    }
+
+    #[test]
+    fn test_login_start_profile() {
+        assert_eq!(
+            login_start_profile(&login_start("Steve", Some(42))),
+            Some(("Steve", 42))
+        );
+        assert_eq!(login_start_profile(&login_start("Steve", None)), None);
+    }
+
+    #[test]
+    fn test_drain_waiting_room_packets() {
+        let settings = encode_frame(256, CLIENT_SETTINGS_ID, b"settings");
+        let movement = encode_frame(256, 0x14, b"movement");
+
+        let mut read_buffer = b"login".to_vec();
+        read_buffer.extend_from_slice(&settings);
+        read_buffer.extend_from_slice(&movement);
+        // The start of a packet which has not been read entirely
+        read_buffer.extend_from_slice(&movement[..3]);
+
+        let mut kept = None;
+        drain_waiting_room_packets(&mut read_buffer, 5, 256, &mut kept).unwrap();
+
+        assert_eq!(kept, Some(settings));
+        assert_eq!(read_buffer[..5], *b"login");
+        assert_eq!(read_buffer[5..], movement[..3]);
+    }
+
+    #[test]
+    fn test_remove_login_packets() {
+        let exit = WaitingRoomExit {
+            compression_threshold: 256,
+            settings: None,
+            pending: Vec::new(),
+            logged_in: Arc::new(Notify::new()),
+        };
+        let mut login = exit.server_login();
+
+        let join = encode_frame(256, 0x28, b"join");
+
+        let mut body = BytesMut::new();
+        write_var_int(&mut body, 256);
+        let mut bytes = encode_packet(SET_COMPRESSION_ID, &body);
+        assert!(!login.remove_login_packets(&mut bytes).unwrap());
+        assert!(bytes.is_empty());
+
+        let mut bytes = BytesMut::from(encode_frame(256, LOGIN_SUCCESS_ID, b"success"));
+        bytes.put_slice(&join);
+        let mut bytes = bytes.freeze();
+        assert!(login.remove_login_packets(&mut bytes).unwrap());
+        assert_eq!(bytes, join);
+    }
+
+    #[test]
+    fn test_login_disconnect_is_shown() {
+        let mut login = ServerLogin {
+            compression_threshold: 256,
+            compression: false,
+            logged_in: Arc::new(Notify::new()),
+        };
+
+        let mut bytes = encode_packet(LOGIN_DISCONNECT_ID, b"reason");
+        assert!(login.remove_login_packets(&mut bytes).unwrap());
+        assert_eq!(bytes, encode_frame(256, PLAY_DISCONNECT_ID, b"reason"));
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let limit = PlayerLimit::new(None, 0);
+        let slots = (0..100).map(|_| limit.try_admit()).collect::<Vec<_>>();
+        assert!(slots.iter().all(Option::is_some));
+    }
+}
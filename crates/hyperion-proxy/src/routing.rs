@@ -0,0 +1,147 @@
+//! Strategies for choosing which server a new player joins.
+
+use std::sync::Arc;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::backend::{Backend, Registration};
+
+/// Chooses the server for new players.
+pub trait RoutingStrategy: Send + Sync {
+    /// Chooses one of `backends` for a player who connected to `server_address`, which is the
+    /// address in the player's handshake such as `eu.play.example.com`. The primary server is
+    /// used if this returns `None`.
+    fn route(&self, server_address: &str, backends: &[Arc<Backend>]) -> Option<Arc<Backend>>;
+}
+
+/// Routes players to the server with the fewest players which is not full.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LeastLoaded;
+
+impl RoutingStrategy for LeastLoaded {
+    fn route(&self, _server_address: &str, backends: &[Arc<Backend>]) -> Option<Arc<Backend>> {
+        least_loaded(backends.iter())
+    }
+}
+
+/// Routes players to the least loaded server in the region named by the first label of the address
+/// they connected to, so players joining `eu.play.example.com` join a server registered with
+/// region `eu`. Players are routed to the least loaded server in any region if no server matches.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Region;
+
+impl RoutingStrategy for Region {
+    fn route(&self, server_address: &str, backends: &[Arc<Backend>]) -> Option<Arc<Backend>> {
+        route_by_label(server_address, backends, |registration| {
+            registration.region.as_str()
+        })
+    }
+}
+
+/// Routes players to the least loaded server hosting the arena named by the first label of the
+/// address they connected to, so players joining `skywars.play.example.com` join a server
+/// registered with arena `skywars`. Players are routed to the least loaded server if no server
+/// hosts the arena.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Arena;
+
+impl RoutingStrategy for Arena {
+    fn route(&self, server_address: &str, backends: &[Arc<Backend>]) -> Option<Arc<Backend>> {
+        route_by_label(server_address, backends, |registration| {
+            registration.arena.as_str()
+        })
+    }
+}
+
+/// The built-in routing strategies, which can be chosen on the command line.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Routing {
+    #[default]
+    LeastLoaded,
+    Region,
+    Arena,
+}
+
+impl Routing {
+    #[must_use]
+    pub fn strategy(self) -> Arc<dyn RoutingStrategy> {
+        match self {
+            Self::LeastLoaded => Arc::new(LeastLoaded),
+            Self::Region => Arc::new(Region),
+            Self::Arena => Arc::new(Arena),
+        }
+    }
+}
+
+fn least_loaded<'a>(backends: impl Iterator<Item = &'a Arc<Backend>>) -> Option<Arc<Backend>> {
+    backends
+        .filter(|backend| !backend.is_full())
+        .min_by_key(|backend| backend.load())
+        .cloned()
+}
+
+/// The first label of a server address, ignoring the data which modded clients append after a
+/// null byte
+fn first_label(server_address: &str) -> &str {
+    let host = server_address.split('\0').next().unwrap_or_default();
+    host.split('.').next().unwrap_or_default()
+}
+
+fn route_by_label(
+    server_address: &str,
+    backends: &[Arc<Backend>],
+    field: impl Fn(&Registration) -> &str,
+) -> Option<Arc<Backend>> {
+    let label = first_label(server_address);
+
+    let matching = backends.iter().filter(|backend| {
+        backend
+            .registration()
+            .is_some_and(|registration| !label.is_empty() && field(&registration) == label)
+    });
+
+    least_loaded(matching).or_else(|| least_loaded(backends.iter()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(id: u64, online: u32, max: u32, region: &str) -> Arc<Backend> {
+        let (server_sender, _) = kanal::bounded_async(1);
+        let backend = Backend::new(id, format!("127.0.0.{id}:35565"), server_sender);
+        backend.update_player_count(online, max);
+        backend.register(Registration {
+            name: format!("shard-{id}"),
+            region: region.to_owned(),
+            arena: String::new(),
+        });
+        Arc::new(backend)
+    }
+
+    #[test]
+    fn test_least_loaded() {
+        let backends = [
+            backend(1, 50, 100, ""),
+            backend(2, 10, 10, ""),
+            backend(3, 20, 100, ""),
+        ];
+        let chosen = LeastLoaded.route("localhost", &backends).unwrap();
+        assert_eq!(chosen.id(), 3);
+    }
+
+    #[test]
+    fn test_region() {
+        let backends = [backend(1, 50, 100, "eu"), backend(2, 10, 100, "us")];
+
+        let chosen = Region.route("eu.play.example.com", &backends).unwrap();
+        assert_eq!(chosen.id(), 1);
+
+        let chosen = Region
+            .route("asia.play.example.com\0FML\0", &backends)
+            .unwrap();
+        assert_eq!(chosen.id(), 2);
+    }
+}
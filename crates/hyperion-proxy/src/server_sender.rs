@@ -1,16 +1,19 @@
-use std::io::IoSlice;
+use std::{io::IoSlice, sync::Arc};
 
 use rkyv::util::AlignedVec;
 use tokio::io::AsyncWrite;
 use tracing::{Instrument, trace_span, warn};
 
-use crate::util::AsyncWriteVectoredExt;
+use crate::{metrics::ProxyMetrics, util::AsyncWriteVectoredExt};
 
 pub type ServerSender = kanal::AsyncSender<AlignedVec>;
 
 // todo: probably makes sense for caller to encode bytes
 #[must_use]
-pub fn launch_server_writer(mut write: impl AsyncWrite + Unpin + Send + 'static) -> ServerSender {
+pub fn launch_server_writer(
+    mut write: impl AsyncWrite + Unpin + Send + 'static,
+    metrics: Arc<ProxyMetrics>,
+) -> ServerSender {
     let (tx, rx) = kanal::bounded_async::<AlignedVec>(32_768);
 
     tokio::spawn(
@@ -34,6 +37,8 @@ pub fn launch_server_writer(mut write: impl AsyncWrite + Unpin + Send + 'static)
                 }
 
                 for (message, length) in messages.iter().zip(lengths.iter()) {
+                    metrics.server.add_out(length.len() + message.len());
+
                     let len = IoSlice::new(length);
                     let msg = IoSlice::new(message);
 
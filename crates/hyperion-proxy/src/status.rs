@@ -0,0 +1,351 @@
+//! Answers status (server list ping) requests in the proxy using the status most recently sent by
+//! the server, so that status requests never reach the server.
+//!
+//! See <https://wiki.vg/Server_List_Ping>.
+
+use anyhow::{Context, bail};
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Sends the most recent status response packet, or `None` if the server has not sent a status
+/// yet.
+pub type StatusSender = tokio::sync::watch::Sender<Option<Bytes>>;
+
+/// Receives the most recent status response packet, or `None` if the server has not sent a status
+/// yet.
+pub type StatusReceiver = tokio::sync::watch::Receiver<Option<Bytes>>;
+
+/// The maximum length of a packet which is inspected by the proxy. A handshake packet with the
+/// longest allowed server address is shorter than this.
+const MAX_INSPECTED_PACKET_LEN: usize = 1024;
+
+const HANDSHAKE_ID: i32 = 0x00;
+const STATUS_REQUEST_ID: i32 = 0x00;
+const PING_REQUEST_ID: i32 = 0x01;
+const STATUS_RESPONSE_ID: i32 = 0x00;
+
+/// The `next_state` of a handshake requesting the status
+const NEXT_STATE_STATUS: i32 = 1;
+
+/// The `next_state` of a handshake requesting to log in
+const NEXT_STATE_LOGIN: i32 = 2;
+
+/// What the proxy did with a connection after inspecting its handshake
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Handshake {
+    /// The connection should be forwarded to the server
+    Forward,
+    /// The status request was answered by the proxy and the connection should be closed
+    Answered,
+}
+
+/// Reads a `VarInt` from the start of `buf`, returning the value and its length in bytes. Returns
+/// `None` if `buf` ends before the `VarInt` does.
+pub(crate) fn read_var_int(buf: &[u8]) -> anyhow::Result<Option<(i32, usize)>> {
+    let mut value = 0_i32;
+
+    for (i, &byte) in buf.iter().take(5).enumerate() {
+        value |= i32::from(byte & 0x7F) << (7 * i);
+
+        if byte & 0x80 == 0 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+
+    if buf.len() >= 5 {
+        bail!("VarInt is too large");
+    }
+
+    Ok(None)
+}
+
+pub(crate) fn write_var_int(buf: &mut BytesMut, value: i32) {
+    let mut value = u32::from_ne_bytes(value.to_ne_bytes());
+
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            buf.put_u8(byte);
+            return;
+        }
+
+        buf.put_u8(byte | 0x80);
+    }
+}
+
+pub(crate) struct Frame<'a> {
+    pub(crate) id: i32,
+    pub(crate) body: &'a [u8],
+    /// The length of the entire frame including the length prefix
+    pub(crate) len: usize,
+}
+
+/// Parses the uncompressed packet at the start of `buf`. Returns `None` if `buf` does not contain
+/// the entire packet yet.
+pub(crate) fn next_frame(buf: &[u8]) -> anyhow::Result<Option<Frame<'_>>> {
+    let Some((packet_len, prefix_len)) = read_var_int(buf)? else {
+        return Ok(None);
+    };
+
+    let packet_len = usize::try_from(packet_len).context("packet length is negative")?;
+
+    if packet_len > MAX_INSPECTED_PACKET_LEN {
+        bail!("packet is too large to be inspected ({packet_len} bytes)");
+    }
+
+    let Some(packet) = buf.get(prefix_len..prefix_len + packet_len) else {
+        return Ok(None);
+    };
+
+    let (id, id_len) = read_var_int(packet)?.context("packet is missing its id")?;
+
+    Ok(Some(Frame {
+        id,
+        body: &packet[id_len..],
+        len: prefix_len + packet_len,
+    }))
+}
+
+/// Reads the `next_state` from the body of a handshake packet
+fn next_state(body: &[u8]) -> anyhow::Result<i32> {
+    let (_protocol_version, mut position) =
+        read_var_int(body)?.context("handshake is missing the protocol version")?;
+
+    let (address_len, prefix_len) =
+        read_var_int(&body[position..])?.context("handshake is missing the server address")?;
+    let address_len = usize::try_from(address_len).context("address length is negative")?;
+
+    // Skip the address and the port
+    position += prefix_len + address_len + size_of::<u16>();
+
+    let remaining = body
+        .get(position..)
+        .context("handshake is missing the next state")?;
+    let (next_state, _) =
+        read_var_int(remaining)?.context("handshake is missing the next state")?;
+
+    Ok(next_state)
+}
+
+/// Whether the body of a handshake packet requests the status
+fn requests_status(body: &[u8]) -> anyhow::Result<bool> {
+    Ok(next_state(body)? == NEXT_STATE_STATUS)
+}
+
+/// The server address in the handshake packet at the start of `buf`, which is the address the
+/// client connected to
+pub(crate) fn server_address(buf: &[u8]) -> Option<String> {
+    let frame = next_frame(buf).ok()??;
+
+    if frame.id != HANDSHAKE_ID {
+        return None;
+    }
+
+    let (_protocol_version, position) = read_var_int(frame.body).ok()??;
+    let (address_len, prefix_len) = read_var_int(frame.body.get(position..)?).ok()??;
+    let address_len = usize::try_from(address_len).ok()?;
+
+    let start = position + prefix_len;
+    let address = frame.body.get(start..start + address_len)?;
+
+    String::from_utf8(address.to_vec()).ok()
+}
+
+/// Whether `buf` starts with a handshake packet which requests to log in
+pub(crate) fn requests_login(buf: &[u8]) -> bool {
+    matches!(
+        next_frame(buf),
+        Ok(Some(frame)) if frame.id == HANDSHAKE_ID
+            && next_state(frame.body).is_ok_and(|next_state| next_state == NEXT_STATE_LOGIN)
+    )
+}
+
+/// Encodes a status response packet containing the given json.
+#[must_use]
+pub fn encode_status_response(json: &str) -> Bytes {
+    let json_len = i32::try_from(json.len()).unwrap_or(i32::MAX);
+
+    let mut body = BytesMut::with_capacity(json.len() + 6);
+    write_var_int(&mut body, STATUS_RESPONSE_ID);
+    write_var_int(&mut body, json_len);
+    body.put_slice(json.as_bytes());
+
+    let body_len = i32::try_from(body.len()).unwrap_or(i32::MAX);
+
+    let mut packet = BytesMut::with_capacity(body.len() + 5);
+    write_var_int(&mut packet, body_len);
+    packet.put_slice(&body);
+
+    packet.freeze()
+}
+
+pub(crate) async fn read_more(
+    reader: &mut (impl AsyncRead + Unpin),
+    read_buffer: &mut Vec<u8>,
+) -> anyhow::Result<usize> {
+    read_buffer.reserve(MAX_INSPECTED_PACKET_LEN);
+    let bytes_read = reader.read_buf(read_buffer).await?;
+    Ok(bytes_read)
+}
+
+/// Reads the handshake of a new connection into `read_buffer` and answers the status request if the
+/// connection requests the status and a status has been received from the server.
+///
+/// If [`Handshake::Forward`] is returned, `read_buffer` contains the unmodified bytes which were
+/// read, which must be forwarded to the server.
+pub async fn try_answer_status(
+    reader: &mut (impl AsyncRead + Unpin),
+    read_buffer: &mut Vec<u8>,
+    status: &StatusReceiver,
+    mut send: impl FnMut(Bytes) -> anyhow::Result<()>,
+) -> anyhow::Result<Handshake> {
+    let handshake_len = loop {
+        match next_frame(read_buffer) {
+            Ok(Some(frame)) => {
+                // Packets which cannot be understood are forwarded to the server, which is
+                // responsible for rejecting them
+                if frame.id != HANDSHAKE_ID || !requests_status(frame.body).unwrap_or(false) {
+                    return Ok(Handshake::Forward);
+                }
+
+                break frame.len;
+            }
+            Ok(None) => {}
+            Err(_) => return Ok(Handshake::Forward),
+        }
+
+        if read_more(reader, read_buffer).await? == 0 {
+            bail!("connection closed before sending a handshake");
+        }
+    };
+
+    let Some(response) = status.borrow().clone() else {
+        return Ok(Handshake::Forward);
+    };
+
+    read_buffer.drain(..handshake_len);
+
+    loop {
+        while let Some(frame) = next_frame(read_buffer)? {
+            match frame.id {
+                STATUS_REQUEST_ID if frame.body.is_empty() => send(response.clone())?,
+                // The pong response is identical to the ping request
+                PING_REQUEST_ID if frame.body.len() == size_of::<i64>() => {
+                    send(Bytes::copy_from_slice(&read_buffer[..frame.len]))?;
+                }
+                id => bail!("unexpected packet with id {id} in the status state"),
+            }
+
+            let frame_len = frame.len;
+            read_buffer.drain(..frame_len);
+        }
+
+        // Wait for the client to close the connection so that the responses are sent before the
+        // connection is closed
+        if read_more(reader, read_buffer).await? == 0 {
+            return Ok(Handshake::Answered);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(id: u8, body: &[u8]) -> Vec<u8> {
+        let mut packet = vec![u8::try_from(body.len() + 1).unwrap(), id];
+        packet.extend_from_slice(body);
+        packet
+    }
+
+    fn handshake(next_state: u8) -> Vec<u8> {
+        let mut body = vec![0xFB, 0x05]; // protocol version 763
+        body.push(9);
+        body.extend_from_slice(b"localhost");
+        body.extend_from_slice(&25565_u16.to_be_bytes());
+        body.push(next_state);
+        frame(0x00, &body)
+    }
+
+    #[test]
+    fn test_var_int_round_trip() {
+        for value in [
+            0,
+            1,
+            127,
+            128,
+            255,
+            25565,
+            2_097_151,
+            i32::MAX,
+            -1,
+            i32::MIN,
+        ] {
+            let mut buf = BytesMut::new();
+            write_var_int(&mut buf, value);
+            assert_eq!(read_var_int(&buf).unwrap(), Some((value, buf.len())));
+        }
+    }
+
+    #[test]
+    fn test_requests_status() {
+        let status = handshake(1);
+        let frame = next_frame(&status).unwrap().unwrap();
+        assert_eq!(frame.len, status.len());
+        assert!(requests_status(frame.body).unwrap());
+
+        let login = handshake(2);
+        let frame = next_frame(&login).unwrap().unwrap();
+        assert!(!requests_status(frame.body).unwrap());
+        assert!(requests_login(&login));
+        assert!(!requests_login(&status));
+        assert_eq!(server_address(&login).as_deref(), Some("localhost"));
+    }
+
+    #[test]
+    fn test_incomplete_frame() {
+        let status = handshake(1);
+        assert!(next_frame(&status[..status.len() - 1]).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_answer_status() {
+        let mut input = handshake(1);
+        input.extend(frame(0x00, &[]));
+        let ping = frame(0x01, &42_i64.to_be_bytes());
+        input.extend(&ping);
+
+        let response = encode_status_response("{}");
+        let (_tx, rx) = tokio::sync::watch::channel(Some(response.clone()));
+
+        let mut sent = Vec::new();
+        let mut read_buffer = Vec::new();
+        let result = try_answer_status(&mut input.as_slice(), &mut read_buffer, &rx, |bytes| {
+            sent.push(bytes);
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(result, Handshake::Answered);
+        assert_eq!(sent, [response, Bytes::from(ping)]);
+    }
+
+    #[tokio::test]
+    async fn test_forward_without_status() {
+        let input = handshake(1);
+        let (_tx, rx) = tokio::sync::watch::channel(None);
+
+        let mut read_buffer = Vec::new();
+        let result = try_answer_status(&mut input.as_slice(), &mut read_buffer, &rx, |_| {
+            panic!("nothing should be sent")
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(result, Handshake::Forward);
+        assert_eq!(read_buffer, input);
+    }
+}
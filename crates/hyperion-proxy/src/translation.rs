@@ -0,0 +1,907 @@
+//! Translates packets between the server and clients on other protocol versions.
+//!
+//! The server only speaks [`SERVER_PROTOCOL_VERSION`]. Clients on another version can still join
+//! if a mapping table for their version is loaded. The proxy rewrites the protocol version in their
+//! handshake, remaps the ids of their packets in both directions and remaps the block state and
+//! item ids in the packets listed below. Clients on the server version are not translated.
+//!
+//! Mapping tables are generated from the data reports of each Minecraft version and loaded from a
+//! directory of json files, one per client version:
+//!
+//! ```json
+//! {
+//!     "protocol": 762,
+//!     "name": "1.19.4",
+//!     "serverbound": { "play": { "6": 5 } },
+//!     "clientbound": { "play": { "10": 11 } },
+//!     "blocks": { "2": 3 },
+//!     "items": { "4": 5 }
+//! }
+//! ```
+//!
+//! Serverbound packet ids map client ids to server ids, and every other table maps server ids to
+//! client ids. Ids which are the same in both versions are left out, and packets mapped to `-1`
+//! have no counterpart and are dropped.
+//!
+//! Only the block states in block updates and in the palettes of chunk sections and the items in
+//! container and creative mode slots are remapped. Other packets are forwarded with only their id
+//! remapped, so tables are only useful for versions whose login sequence and packet layouts match
+//! the server version.
+//!
+//! This limits translation to clients which are older than the server. Every newer version starts
+//! with 1.20.2, whose configuration state changes the login sequence, so tables for
+//! [`CONFIGURATION_PROTOCOL_VERSION`] and newer versions are rejected. Newer clients can only join
+//! once the server itself is updated.
+
+use std::{
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicI32, AtomicU8, Ordering},
+    },
+};
+
+use anyhow::{Context, bail, ensure};
+use bytes::{BufMut, Bytes, BytesMut};
+use libdeflater::{CompressionLvl, Compressor, Decompressor};
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::status::{read_var_int, write_var_int};
+
+/// The protocol version of the server, which is 1.20.1
+pub const SERVER_PROTOCOL_VERSION: i32 = 763;
+
+/// The first protocol version with the configuration state, which is 1.20.2. Clients on this
+/// version and newer cannot be translated.
+pub const CONFIGURATION_PROTOCOL_VERSION: i32 = 764;
+
+/// The largest packet which is translated, matching the limit of the vanilla client
+const MAX_PACKET_LEN: usize = 2_097_152;
+
+/// The most bits per entry of paletted containers which have a palette. Containers with more bits
+/// per entry contain the ids themselves.
+const MAX_INDIRECT_BLOCK_BITS: u8 = 8;
+const MAX_INDIRECT_BIOME_BITS: u8 = 3;
+
+/// How deeply the compounds and lists of NBT may be nested, matching the vanilla client
+const MAX_NBT_DEPTH: usize = 512;
+
+const HANDSHAKE_ID: i32 = 0x00;
+const NEXT_STATE_LOGIN: i32 = 2;
+
+const LOGIN_SUCCESS_ID: i32 = 0x02;
+const SET_COMPRESSION_ID: i32 = 0x03;
+
+// Packets whose bodies are rewritten, by their id in the server version
+const BLOCK_UPDATE_S2C_ID: i32 = 0x0A;
+const SET_CONTAINER_SLOT_S2C_ID: i32 = 0x14;
+const CHUNK_DATA_S2C_ID: i32 = 0x24;
+const SET_CREATIVE_MODE_SLOT_C2S_ID: i32 = 0x2B;
+
+#[derive(Debug, Default, Deserialize)]
+struct PacketIds {
+    #[serde(default)]
+    login: FxHashMap<i32, i32>,
+    #[serde(default)]
+    play: FxHashMap<i32, i32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MappingsFile {
+    protocol: i32,
+    name: String,
+    #[serde(default)]
+    serverbound: PacketIds,
+    #[serde(default)]
+    clientbound: PacketIds,
+    #[serde(default)]
+    blocks: FxHashMap<i32, i32>,
+    #[serde(default)]
+    items: FxHashMap<i32, i32>,
+}
+
+/// How one client protocol version differs from the server version
+#[derive(Debug)]
+pub struct Mappings {
+    protocol: i32,
+    name: String,
+    serverbound: PacketIds,
+    clientbound: PacketIds,
+    blocks: FxHashMap<i32, i32>,
+    items: FxHashMap<i32, i32>,
+    /// `items` from client ids to server ids
+    items_to_server: FxHashMap<i32, i32>,
+}
+
+impl Mappings {
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let file: MappingsFile = serde_json::from_str(json)?;
+
+        ensure!(
+            file.protocol != SERVER_PROTOCOL_VERSION,
+            "mappings cannot be loaded for the server protocol version"
+        );
+        ensure!(
+            file.protocol < CONFIGURATION_PROTOCOL_VERSION,
+            "protocol {} has a configuration state, which cannot be translated; only versions \
+             older than 1.20.2 are supported",
+            file.protocol
+        );
+
+        let items_to_server = file
+            .items
+            .iter()
+            .map(|(&server, &client)| (client, server))
+            .collect();
+
+        Ok(Self {
+            protocol: file.protocol,
+            name: file.name,
+            serverbound: file.serverbound,
+            clientbound: file.clientbound,
+            blocks: file.blocks,
+            items: file.items,
+            items_to_server,
+        })
+    }
+
+    #[must_use]
+    pub const fn protocol(&self) -> i32 {
+        self.protocol
+    }
+
+    #[must_use]
+    #[expect(clippy::missing_const_for_fn, reason = "false positive")]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The mapping tables of every client version which can join in addition to the server version
+#[derive(Debug, Default)]
+pub struct Translations {
+    mappings: FxHashMap<i32, Arc<Mappings>>,
+}
+
+impl Translations {
+    /// Loads every `.json` mapping table in `dir`
+    pub fn load_dir(dir: &Path) -> anyhow::Result<Self> {
+        let mut translations = Self::default();
+
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("failed to read mappings directory {}", dir.display()))?;
+
+        for entry in entries {
+            let path = entry?.path();
+
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+
+            let json = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read mappings {}", path.display()))?;
+            let mappings = Mappings::from_json(&json)
+                .with_context(|| format!("failed to parse mappings {}", path.display()))?;
+
+            info!(
+                "Loaded mappings for {} (protocol {})",
+                mappings.name, mappings.protocol
+            );
+            translations.insert(mappings);
+        }
+
+        Ok(translations)
+    }
+
+    pub fn insert(&mut self, mappings: Mappings) {
+        self.mappings.insert(mappings.protocol, Arc::new(mappings));
+    }
+
+    /// The mappings for clients on `protocol`, or `None` if they cannot be translated
+    #[must_use]
+    pub fn get(&self, protocol: i32) -> Option<Arc<Mappings>> {
+        self.mappings.get(&protocol).cloned()
+    }
+}
+
+/// The protocol version in the handshake at the start of `buf`, if `buf` starts with a complete
+/// handshake
+#[must_use]
+pub fn handshake_protocol(buf: &[u8]) -> Option<i32> {
+    let (_, body) = split_frame(buf).ok()??;
+    let (id, id_len) = read_var_int(body).ok()??;
+
+    if id != HANDSHAKE_ID {
+        return None;
+    }
+
+    let (protocol, _) = read_var_int(&body[id_len..]).ok()??;
+    Some(protocol)
+}
+
+/// Splits the packet at the start of `buf` into its length and its contents. Returns `None` if
+/// `buf` does not contain the entire packet yet.
+fn split_frame(buf: &[u8]) -> anyhow::Result<Option<(usize, &[u8])>> {
+    let Some((packet_len, prefix_len)) = read_var_int(buf)? else {
+        return Ok(None);
+    };
+
+    let packet_len = usize::try_from(packet_len).context("packet length is negative")?;
+    ensure!(
+        packet_len <= MAX_PACKET_LEN,
+        "packet is too large ({packet_len} bytes)"
+    );
+
+    Ok(buf
+        .get(prefix_len..prefix_len + packet_len)
+        .map(|packet| (prefix_len + packet_len, packet)))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Direction {
+    Serverbound,
+    Clientbound,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+enum State {
+    Handshake = 0,
+    Login = 1,
+    Play = 2,
+}
+
+/// Connection state which both directions of a connection depend on
+#[derive(Debug)]
+struct Shared {
+    state: AtomicU8,
+    /// The compression threshold, or -1 if packets are not compressed
+    compression: AtomicI32,
+}
+
+impl Shared {
+    fn state(&self) -> State {
+        match self.state.load(Ordering::Acquire) {
+            0 => State::Handshake,
+            1 => State::Login,
+            _ => State::Play,
+        }
+    }
+
+    fn set_state(&self, state: State) {
+        self.state.store(state as u8, Ordering::Release);
+    }
+}
+
+/// Translates the packets of one direction of a connection
+pub struct Translator {
+    mappings: Arc<Mappings>,
+    shared: Arc<Shared>,
+    direction: Direction,
+    /// The start of a packet which has not been received entirely
+    pending: Vec<u8>,
+    compressor: Compressor,
+    decompressor: Decompressor,
+}
+
+impl Translator {
+    /// The serverbound and clientbound translators of a new connection
+    #[must_use]
+    pub fn pair(mappings: Arc<Mappings>) -> (Self, Self) {
+        let shared = Arc::new(Shared {
+            state: AtomicU8::new(State::Handshake as u8),
+            compression: AtomicI32::new(-1),
+        });
+
+        let translator = |direction| Self {
+            mappings: mappings.clone(),
+            shared: shared.clone(),
+            direction,
+            pending: Vec::new(),
+            compressor: Compressor::new(CompressionLvl::default()),
+            decompressor: Decompressor::new(),
+        };
+
+        (
+            translator(Direction::Serverbound),
+            translator(Direction::Clientbound),
+        )
+    }
+
+    /// Translates every complete packet in `input`. The start of an incomplete packet at the end
+    /// of `input` is kept until the rest of it is received.
+    pub fn translate(&mut self, input: &[u8]) -> anyhow::Result<Bytes> {
+        let mut buf = std::mem::take(&mut self.pending);
+        buf.extend_from_slice(input);
+
+        let mut out = BytesMut::with_capacity(buf.len());
+        let mut position = 0;
+
+        while let Some((len, packet)) = split_frame(&buf[position..])? {
+            self.translate_packet(&buf[position..position + len], packet, &mut out)?;
+            position += len;
+        }
+
+        buf.drain(..position);
+        self.pending = buf;
+
+        Ok(out.freeze())
+    }
+
+    fn translate_packet(
+        &mut self,
+        frame: &[u8],
+        packet: &[u8],
+        out: &mut BytesMut,
+    ) -> anyhow::Result<()> {
+        let threshold = self.shared.compression.load(Ordering::Acquire);
+
+        let decompressed;
+        let data = if threshold >= 0 {
+            let (data_len, data_len_size) =
+                read_var_int(packet)?.context("packet is missing its data length")?;
+            let compressed = &packet[data_len_size..];
+
+            if data_len == 0 {
+                compressed
+            } else {
+                let data_len = usize::try_from(data_len).context("data length is negative")?;
+                ensure!(
+                    data_len <= MAX_PACKET_LEN,
+                    "packet is too large ({data_len} bytes)"
+                );
+
+                let mut buf = vec![0; data_len];
+                let written = self
+                    .decompressor
+                    .zlib_decompress(compressed, &mut buf)
+                    .context("failed to decompress packet")?;
+                ensure!(
+                    written == data_len,
+                    "decompressed packet has the wrong length"
+                );
+
+                decompressed = buf;
+                &decompressed
+            }
+        } else {
+            packet
+        };
+
+        let (id, id_len) = read_var_int(data)?.context("packet is missing its id")?;
+        let body = &data[id_len..];
+
+        let state = self.shared.state();
+
+        let (new_id, new_body) = match self.direction {
+            Direction::Serverbound => {
+                let server_id = self.packet_id(state, id);
+
+                let new_body = match (state, server_id) {
+                    (State::Handshake, HANDSHAKE_ID) => Some(self.rewrite_handshake(body)?),
+                    (State::Play, SET_CREATIVE_MODE_SLOT_C2S_ID) => {
+                        rewrite_slot(body, 2, &self.mappings.items_to_server)?
+                    }
+                    _ => None,
+                };
+
+                (server_id, new_body)
+            }
+            Direction::Clientbound => {
+                let new_body = match (state, id) {
+                    (State::Login, SET_COMPRESSION_ID) => {
+                        let (threshold, _) =
+                            read_var_int(body)?.context("set compression is missing threshold")?;
+                        self.shared.compression.store(threshold, Ordering::Release);
+                        None
+                    }
+                    (State::Login, LOGIN_SUCCESS_ID) => {
+                        self.shared.set_state(State::Play);
+                        None
+                    }
+                    (State::Play, BLOCK_UPDATE_S2C_ID) => {
+                        rewrite_var_int_at(body, size_of::<i64>(), &self.mappings.blocks)?
+                    }
+                    (State::Play, CHUNK_DATA_S2C_ID) => rewrite_chunk(body, &self.mappings.blocks)?,
+                    (State::Play, SET_CONTAINER_SLOT_S2C_ID) => {
+                        // The window id, the state id and the slot come before the item
+                        let (_, state_id_len) = read_var_int(body.get(1..).unwrap_or_default())?
+                            .context("set container slot is missing the state id")?;
+                        rewrite_slot(body, 1 + state_id_len + 2, &self.mappings.items)?
+                    }
+                    _ => None,
+                };
+
+                (self.packet_id(state, id), new_body)
+            }
+        };
+
+        if new_id == -1 {
+            return Ok(());
+        }
+
+        // Packets which are not changed are copied without compressing them again
+        if new_id == id && new_body.is_none() {
+            out.put_slice(frame);
+            return Ok(());
+        }
+
+        let mut data = BytesMut::with_capacity(body.len() + 5);
+        write_var_int(&mut data, new_id);
+        data.put_slice(new_body.as_deref().unwrap_or(body));
+
+        self.write_packet(&data, threshold, out)
+    }
+
+    fn packet_id(&self, state: State, id: i32) -> i32 {
+        let ids = match self.direction {
+            Direction::Serverbound => &self.mappings.serverbound,
+            Direction::Clientbound => &self.mappings.clientbound,
+        };
+
+        let ids = match state {
+            State::Handshake => return id,
+            State::Login => &ids.login,
+            State::Play => &ids.play,
+        };
+
+        ids.get(&id).copied().unwrap_or(id)
+    }
+
+    /// Replaces the protocol version of the client with the server version
+    fn rewrite_handshake(&self, body: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let (_, protocol_len) =
+            read_var_int(body)?.context("handshake is missing the protocol version")?;
+        let rest = &body[protocol_len..];
+
+        let (address_len, prefix_len) =
+            read_var_int(rest)?.context("handshake is missing the server address")?;
+        let address_len = usize::try_from(address_len).context("address length is negative")?;
+
+        let next_state_position = prefix_len + address_len + size_of::<u16>();
+        let (next_state, _) = read_var_int(rest.get(next_state_position..).unwrap_or_default())?
+            .context("handshake is missing the next state")?;
+
+        if next_state != NEXT_STATE_LOGIN {
+            bail!("only logins are translated");
+        }
+
+        self.shared.set_state(State::Login);
+
+        let mut new_body = BytesMut::with_capacity(body.len());
+        write_var_int(&mut new_body, SERVER_PROTOCOL_VERSION);
+        new_body.put_slice(rest);
+
+        Ok(new_body.to_vec())
+    }
+
+    /// Writes a packet containing `data`, compressing it if it is larger than the threshold
+    fn write_packet(
+        &mut self,
+        data: &[u8],
+        threshold: i32,
+        out: &mut BytesMut,
+    ) -> anyhow::Result<()> {
+        let data_len = i32::try_from(data.len()).context("packet is too large")?;
+
+        let mut packet = BytesMut::with_capacity(data.len() + 5);
+
+        if threshold < 0 {
+            packet.put_slice(data);
+        } else if data_len > threshold {
+            let mut compressed = vec![0; self.compressor.zlib_compress_bound(data.len())];
+            let written = self
+                .compressor
+                .zlib_compress(data, &mut compressed)
+                .context("failed to compress packet")?;
+
+            write_var_int(&mut packet, data_len);
+            packet.put_slice(&compressed[..written]);
+        } else {
+            write_var_int(&mut packet, 0);
+            packet.put_slice(data);
+        }
+
+        let packet_len = i32::try_from(packet.len()).context("packet is too large")?;
+        write_var_int(out, packet_len);
+        out.put_slice(&packet);
+
+        Ok(())
+    }
+}
+
+/// Replaces the `VarInt` at `position` in `body` using `ids`. Returns `None` if the id does not
+/// change.
+fn rewrite_var_int_at(
+    body: &[u8],
+    position: usize,
+    ids: &FxHashMap<i32, i32>,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let (id, id_len) = read_var_int(body.get(position..).unwrap_or_default())?
+        .context("packet is missing an id")?;
+
+    let Some(&new_id) = ids.get(&id) else {
+        return Ok(None);
+    };
+
+    let mut new_body = BytesMut::with_capacity(body.len() + 5);
+    new_body.put_slice(&body[..position]);
+    write_var_int(&mut new_body, new_id);
+    new_body.put_slice(&body[position + id_len..]);
+
+    Ok(Some(new_body.to_vec()))
+}
+
+/// Replaces the item id of the slot at `position` in `body` using `items`
+fn rewrite_slot(
+    body: &[u8],
+    position: usize,
+    items: &FxHashMap<i32, i32>,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let present = body.get(position).context("packet is missing a slot")?;
+
+    if *present == 0 {
+        return Ok(None);
+    }
+
+    rewrite_var_int_at(body, position + 1, items)
+}
+
+/// Replaces the block states in the chunk sections of a chunk data packet using `blocks`
+fn rewrite_chunk(body: &[u8], blocks: &FxHashMap<i32, i32>) -> anyhow::Result<Option<Vec<u8>>> {
+    if blocks.is_empty() {
+        return Ok(None);
+    }
+
+    // The chunk position and the heightmaps come before the sections
+    let mut rest = body
+        .get(2 * size_of::<i32>()..)
+        .context("chunk data is missing the heightmaps")?;
+    skip_nbt(&mut rest)?;
+
+    let position = body.len() - rest.len();
+    let sections_len = take_var_int(&mut rest)?;
+    let sections_len = usize::try_from(sections_len).context("sections length is negative")?;
+
+    let mut sections = take(&mut rest, sections_len)?;
+    let mut new_sections = BytesMut::with_capacity(sections_len + 64);
+
+    while !sections.is_empty() {
+        // The number of blocks which are not air
+        new_sections.put_slice(take(&mut sections, size_of::<i16>())?);
+        rewrite_paletted_container(
+            &mut sections,
+            MAX_INDIRECT_BLOCK_BITS,
+            blocks,
+            &mut new_sections,
+        )?;
+        rewrite_paletted_container(
+            &mut sections,
+            MAX_INDIRECT_BIOME_BITS,
+            &FxHashMap::default(),
+            &mut new_sections,
+        )?;
+    }
+
+    let new_sections_len = i32::try_from(new_sections.len()).context("chunk is too large")?;
+
+    let mut new_body = BytesMut::with_capacity(body.len() + 64);
+    new_body.put_slice(&body[..position]);
+    write_var_int(&mut new_body, new_sections_len);
+    new_body.put_slice(&new_sections);
+    // The block entities and the light data are not changed
+    new_body.put_slice(rest);
+
+    Ok(Some(new_body.to_vec()))
+}
+
+/// Copies the paletted container at the start of `buf` to `out`, replacing its values using `ids`
+fn rewrite_paletted_container(
+    buf: &mut &[u8],
+    max_indirect_bits: u8,
+    ids: &FxHashMap<i32, i32>,
+    out: &mut BytesMut,
+) -> anyhow::Result<()> {
+    let remap = |id: i32| ids.get(&id).copied().unwrap_or(id);
+
+    let bits = take(buf, 1)?[0];
+    out.put_u8(bits);
+
+    if bits == 0 {
+        // Every entry has the same value, which is stored instead of a palette
+        write_var_int(out, remap(take_var_int(buf)?));
+    } else if bits <= max_indirect_bits {
+        let palette_len = take_var_int(buf)?;
+        write_var_int(out, palette_len);
+
+        for _ in 0..palette_len {
+            write_var_int(out, remap(take_var_int(buf)?));
+        }
+    }
+
+    let longs = take_var_int(buf)?;
+    write_var_int(out, longs);
+
+    let longs = usize::try_from(longs).context("data array length is negative")?;
+    let data = take(buf, longs.saturating_mul(size_of::<u64>()))?;
+
+    if bits <= max_indirect_bits {
+        // The data array only contains indices into the palette
+        out.put_slice(data);
+        return Ok(());
+    }
+
+    // The data array contains the ids themselves, packed into longs without spanning two of them
+    ensure!(bits < 32, "paletted container has {bits} bits per entry");
+
+    let mask = (1_u64 << bits) - 1;
+    let step = usize::from(bits);
+
+    for long in data.chunks_exact(size_of::<u64>()) {
+        let long = u64::from_be_bytes(long.try_into()?);
+        let mut new_long = 0;
+
+        for shift in (0..=64 - step).step_by(step) {
+            let id = i32::try_from((long >> shift) & mask)?;
+            let new_id = u64::try_from(remap(id)).context("id is negative")?;
+            ensure!(new_id <= mask, "id {new_id} does not fit in {bits} bits");
+
+            new_long |= new_id << shift;
+        }
+
+        out.put_u64(new_long);
+    }
+
+    Ok(())
+}
+
+/// Skips the network NBT at the start of `buf`, which has a named root compound
+fn skip_nbt(buf: &mut &[u8]) -> anyhow::Result<()> {
+    let tag = take(buf, 1)?[0];
+
+    // An empty compound is sent as a single end tag
+    if tag == 0 {
+        return Ok(());
+    }
+
+    skip_nbt_string(buf)?;
+    skip_nbt_payload(buf, tag, 0)
+}
+
+fn skip_nbt_string(buf: &mut &[u8]) -> anyhow::Result<()> {
+    let len = take(buf, size_of::<u16>())?;
+    let len = u16::from_be_bytes(len.try_into()?);
+    take(buf, usize::from(len))?;
+    Ok(())
+}
+
+fn skip_nbt_payload(buf: &mut &[u8], tag: u8, depth: usize) -> anyhow::Result<()> {
+    ensure!(depth < MAX_NBT_DEPTH, "nbt is nested too deeply");
+
+    match tag {
+        // Byte, short, int, long, float and double
+        1 => take(buf, 1).map(drop),
+        2 => take(buf, 2).map(drop),
+        3 | 5 => take(buf, 4).map(drop),
+        4 | 6 => take(buf, 8).map(drop),
+        7 => skip_nbt_array(buf, 1),
+        11 => skip_nbt_array(buf, 4),
+        12 => skip_nbt_array(buf, 8),
+        8 => skip_nbt_string(buf),
+        9 => {
+            let element = take(buf, 1)?[0];
+            let len = take(buf, size_of::<i32>())?;
+            let len = i32::from_be_bytes(len.try_into()?);
+
+            for _ in 0..len {
+                skip_nbt_payload(buf, element, depth + 1)?;
+            }
+
+            Ok(())
+        }
+        10 => loop {
+            let tag = take(buf, 1)?[0];
+
+            if tag == 0 {
+                return Ok(());
+            }
+
+            skip_nbt_string(buf)?;
+            skip_nbt_payload(buf, tag, depth + 1)?;
+        },
+        _ => bail!("invalid nbt tag {tag}"),
+    }
+}
+
+fn skip_nbt_array(buf: &mut &[u8], element_len: usize) -> anyhow::Result<()> {
+    let len = take(buf, size_of::<i32>())?;
+    let len = i32::from_be_bytes(len.try_into()?);
+    let len = usize::try_from(len).context("nbt array length is negative")?;
+    take(buf, len.saturating_mul(element_len))?;
+    Ok(())
+}
+
+/// Removes the first `len` bytes from `buf` and returns them
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> anyhow::Result<&'a [u8]> {
+    ensure!(buf.len() >= len, "packet is truncated");
+
+    let (taken, rest) = buf.split_at(len);
+    *buf = rest;
+
+    Ok(taken)
+}
+
+/// Removes the `VarInt` at the start of `buf` and returns it
+fn take_var_int(buf: &mut &[u8]) -> anyhow::Result<i32> {
+    let (value, len) = read_var_int(buf)?.context("packet is truncated")?;
+    *buf = &buf[len..];
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAPPINGS: &str = r#"{
+        "protocol": 762,
+        "name": "test",
+        "serverbound": { "play": { "44": 43 } },
+        "clientbound": { "login": {}, "play": { "10": 11, "16": -1 } },
+        "blocks": { "1": 2 },
+        "items": { "5": 6 }
+    }"#;
+
+    fn packet(id: u8, body: &[u8]) -> Vec<u8> {
+        let mut packet = BytesMut::new();
+        write_var_int(&mut packet, i32::try_from(body.len() + 1).unwrap());
+        packet.put_u8(id);
+        packet.put_slice(body);
+        packet.to_vec()
+    }
+
+    fn handshake(protocol: &[u8]) -> Vec<u8> {
+        let mut body = protocol.to_vec();
+        body.push(9);
+        body.extend_from_slice(b"localhost");
+        body.extend_from_slice(&25565_u16.to_be_bytes());
+        body.push(2);
+        packet(0x00, &body)
+    }
+
+    fn pair() -> (Translator, Translator) {
+        Translator::pair(Arc::new(Mappings::from_json(MAPPINGS).unwrap()))
+    }
+
+    #[test]
+    fn test_configuration_state_rejected() {
+        let json = MAPPINGS.replace("762", "764");
+        assert!(Mappings::from_json(&json).is_err());
+    }
+
+    #[test]
+    fn test_handshake() {
+        let (mut serverbound, _) = pair();
+
+        let input = handshake(&[0xFA, 0x05]);
+        assert_eq!(handshake_protocol(&input), Some(762));
+
+        let output = serverbound.translate(&input).unwrap();
+        assert_eq!(handshake_protocol(&output), Some(SERVER_PROTOCOL_VERSION));
+        assert_eq!(output.len(), input.len());
+    }
+
+    #[test]
+    fn test_play_packets() {
+        let (mut serverbound, mut clientbound) = pair();
+
+        serverbound.translate(&handshake(&[0xFA, 0x05])).unwrap();
+        // Login success
+        clientbound.translate(&packet(0x02, &[])).unwrap();
+
+        // The block state is remapped along with the packet id
+        let mut block_update = 7_i64.to_be_bytes().to_vec();
+        block_update.push(1);
+        let output = clientbound.translate(&packet(0x0A, &block_update)).unwrap();
+        assert_eq!(output[1], 11);
+        assert_eq!(output[10], 2);
+
+        // Packets without a counterpart are dropped
+        let output = clientbound.translate(&packet(0x10, &[1, 2, 3])).unwrap();
+        assert!(output.is_empty());
+
+        // The creative mode slot is remapped from the client item id
+        let slot = [0, 36, 1, 6, 1, 0];
+        let output = serverbound.translate(&packet(44, &slot)).unwrap();
+        assert_eq!(output.as_ref(), packet(43, &[0, 36, 1, 5, 1, 0]));
+    }
+
+    /// A chunk with a section which has `block` in its palette and a section which contains
+    /// `block` itself
+    fn chunk(block: u8) -> Vec<u8> {
+        let mut sections = BytesMut::new();
+
+        sections.put_i16(16);
+        sections.put_u8(4);
+        sections.put_slice(&[2, 0, block]);
+        write_var_int(&mut sections, 256);
+        sections.put_bytes(0x11, 256 * 8);
+        // Biomes with a single value
+        sections.put_slice(&[0, 1, 0]);
+
+        sections.put_i16(16);
+        sections.put_u8(15);
+        write_var_int(&mut sections, 1024);
+        for _ in 0..1024 {
+            sections.put_u64(u64::from(block) | (u64::from(block) << 15) | (3 << 30));
+        }
+        sections.put_slice(&[0, 1, 0]);
+
+        let mut body = BytesMut::new();
+        body.put_i32(3);
+        body.put_i32(-4);
+        // Heightmaps with a single long array
+        body.put_slice(&[10, 0, 0, 12, 0, 1, b'M', 0, 0, 0, 1]);
+        body.put_u64(u64::MAX);
+        body.put_u8(0);
+        write_var_int(&mut body, i32::try_from(sections.len()).unwrap());
+        body.put_slice(&sections);
+        // The block entities and the light data
+        body.put_slice(&[0, 0xAB, 0xCD]);
+
+        body.to_vec()
+    }
+
+    #[test]
+    fn test_chunk_palettes() {
+        let (mut serverbound, mut clientbound) = pair();
+
+        serverbound.translate(&handshake(&[0xFA, 0x05])).unwrap();
+        // Login success
+        clientbound.translate(&packet(0x02, &[])).unwrap();
+
+        let output = clientbound.translate(&packet(0x24, &chunk(1))).unwrap();
+        assert_eq!(output.as_ref(), packet(0x24, &chunk(2)));
+
+        // Chunks which end early are rejected instead of being forwarded
+        let truncated = chunk(1);
+        let truncated = &truncated[..truncated.len() - 1000];
+        assert!(clientbound.translate(&packet(0x24, truncated)).is_err());
+    }
+
+    #[test]
+    fn test_split_and_compressed_packets() {
+        let (mut serverbound, mut clientbound) = pair();
+
+        serverbound.translate(&handshake(&[0xFA, 0x05])).unwrap();
+        // Set compression followed by a login success which is not compressed
+        clientbound.translate(&packet(0x03, &[0])).unwrap();
+        clientbound.translate(&[2, 0, 0x02]).unwrap();
+
+        let mut data = vec![0x0A];
+        data.extend_from_slice(&7_i64.to_be_bytes());
+        data.push(1);
+
+        let mut compressor = Compressor::new(CompressionLvl::default());
+        let mut compressed = vec![0; compressor.zlib_compress_bound(data.len())];
+        let written = compressor.zlib_compress(&data, &mut compressed).unwrap();
+
+        let mut input = vec![u8::try_from(written + 1).unwrap(), data.len() as u8];
+        input.extend_from_slice(&compressed[..written]);
+
+        // Nothing is sent until the entire packet is received
+        let (first, second) = input.split_at(4);
+        assert!(clientbound.translate(first).unwrap().is_empty());
+        let output = clientbound.translate(second).unwrap();
+
+        let (_, packet) = split_frame(&output).unwrap().unwrap();
+        let mut decompressed = vec![0; usize::from(packet[0])];
+        Decompressor::new()
+            .zlib_decompress(&packet[1..], &mut decompressed)
+            .unwrap();
+
+        assert_eq!(decompressed[0], 11);
+        assert_eq!(decompressed[9], 2);
+    }
+}
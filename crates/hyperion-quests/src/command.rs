@@ -0,0 +1,63 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::net::{Compose, ConnectionId, DataBundle, agnostic};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+use crate::{QuestProgress, Quests};
+
+/// Lists the quests and your progress
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "quests")]
+#[command_permission(group = "Normal")]
+pub struct QuestsCommand;
+
+impl MinecraftCommand for QuestsCommand {
+    type State = SystemState<(
+        Query<'static, 'static, (&'static ConnectionId, &'static QuestProgress)>,
+        Res<'static, Quests>,
+        Res<'static, Compose>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (query, quests, compose) = state.get(world);
+
+        let (&connection_id, progress) = match query.get(caller) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("quests command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let mut lines = vec!["§6Quests:".to_owned()];
+        lines.extend(quests.iter().map(|(id, quest)| {
+            let state = progress.get(id);
+            let status = if state.completed {
+                "§a✔".to_owned()
+            } else {
+                format!("§e{}/{}", state.progress, quest.objective.goal())
+            };
+
+            format!(
+                "§7- §f{}§7: {} {status}",
+                quest.name,
+                quest.objective.describe()
+            )
+        }));
+
+        if lines.len() == 1 {
+            lines.push("§7There are no quests".to_owned());
+        }
+
+        let mut bundle = DataBundle::new(&compose);
+        for line in lines {
+            bundle.add_packet(&agnostic::chat(line)).unwrap();
+        }
+        if let Err(e) = bundle.unicast(connection_id) {
+            error!("failed to send quest list: {e}");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,427 @@
+//! Quests with objectives which players complete by playing.
+//!
+//! Quests are defined in the [`Quests`] resource. Every player works on every quest until they
+//! complete it, and their [`QuestProgress`] is saved in the [`LocalDb`] so it is kept across
+//! restarts. Progress is shown above the hotbar, and completing a quest gives its reward items and
+//! triggers [`QuestCompleted`] on the player.
+//!
+//! ```ignore
+//! quests.define("first_blood", Quest::new("First Blood", Objective::KillPlayers { count: 1 })
+//!     .with_reward(ItemStack::new(ItemKind::GoldenApple, 1, None)));
+//!
+//! app.add_observer(|trigger: Trigger<'_, QuestCompleted>| {
+//!     info!("{} completed {}", trigger.target(), trigger.event().quest);
+//! });
+//! ```
+
+mod command;
+
+use std::collections::BTreeMap;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use hyperion::{
+    ItemStack,
+    glam::Vec3,
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{
+        Position, Uuid,
+        event::{CancelEvents, Cancellable, DestroyBlock, PlayerDeath},
+    },
+    storage::{Bucket, LocalDb},
+    timings::timed,
+};
+use hyperion_clap::MinecraftCommand;
+use hyperion_inventory::PlayerInventory;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::command::QuestsCommand;
+
+/// What a player has to do to complete a quest
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Objective {
+    BreakBlocks {
+        count: u32,
+    },
+    KillPlayers {
+        count: u32,
+    },
+    /// Come within `radius` blocks of `position`
+    ReachLocation {
+        position: Vec3,
+        radius: f32,
+    },
+}
+
+impl Objective {
+    /// The progress needed to complete the objective
+    #[must_use]
+    pub const fn goal(&self) -> u32 {
+        match *self {
+            Self::BreakBlocks { count } | Self::KillPlayers { count } => count,
+            Self::ReachLocation { .. } => 1,
+        }
+    }
+
+    #[must_use]
+    pub fn describe(&self) -> String {
+        match *self {
+            Self::BreakBlocks { count } => format!("Break {count} blocks"),
+            Self::KillPlayers { count } => format!("Kill {count} players"),
+            Self::ReachLocation { position, .. } => format!(
+                "Reach ({:.0}, {:.0}, {:.0})",
+                position.x, position.y, position.z
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Quest {
+    /// The name shown to players
+    pub name: String,
+    pub objective: Objective,
+    /// Items given to the player when they complete the quest
+    pub rewards: Vec<ItemStack>,
+}
+
+impl Quest {
+    #[must_use]
+    pub fn new(name: impl Into<String>, objective: Objective) -> Self {
+        Self {
+            name: name.into(),
+            objective,
+            rewards: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_reward(mut self, item: ItemStack) -> Self {
+        self.rewards.push(item);
+        self
+    }
+}
+
+/// Every quest, keyed by id
+#[derive(Resource, Default, Debug)]
+pub struct Quests {
+    quests: BTreeMap<String, Quest>,
+}
+
+impl Quests {
+    /// Defines a quest, replacing any quest with the same id. Progress is kept by id, so changing
+    /// the objective of a quest keeps the progress players made on it.
+    pub fn define(&mut self, id: impl Into<String>, quest: Quest) {
+        self.quests.insert(id.into(), quest);
+    }
+
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&Quest> {
+        self.quests.get(id)
+    }
+
+    /// The quests sorted by id
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Quest)> {
+        self.quests.iter().map(|(id, quest)| (id.as_str(), quest))
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuestState {
+    pub progress: u32,
+    pub completed: bool,
+}
+
+/// The progress of a player on each quest they have worked on, keyed by quest id
+#[derive(
+    Component,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize
+)]
+pub struct QuestProgress(BTreeMap<String, QuestState>);
+
+impl QuestProgress {
+    #[must_use]
+    pub fn get(&self, quest: &str) -> QuestState {
+        self.0.get(quest).copied().unwrap_or_default()
+    }
+
+    /// Adds `amount` to every unfinished quest with an objective accepted by `matches`, returning
+    /// the quests which progressed with their new state
+    pub fn advance(
+        &mut self,
+        quests: &Quests,
+        amount: u32,
+        mut matches: impl FnMut(&Objective) -> bool,
+    ) -> Vec<(String, QuestState)> {
+        let mut progressed = Vec::new();
+
+        for (id, quest) in quests.iter() {
+            if !matches(&quest.objective) {
+                continue;
+            }
+
+            let state = self.0.entry(id.to_owned()).or_default();
+            if state.completed {
+                continue;
+            }
+
+            let goal = quest.objective.goal();
+            state.progress = state.progress.saturating_add(amount).min(goal);
+            state.completed = state.progress >= goal;
+
+            progressed.push((id.to_owned(), *state));
+        }
+
+        progressed
+    }
+}
+
+/// Where the quest progress of players is saved, keyed by their uuid
+#[derive(Resource, Clone)]
+pub struct QuestStorage(pub Bucket<QuestProgress>);
+
+/// Triggered on a player when they complete a quest
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct QuestCompleted {
+    pub quest: String,
+}
+
+fn save(storage: &QuestStorage, uuid: &Uuid, progress: &QuestProgress) {
+    if let Err(e) = storage.0.insert(&uuid.0.to_string(), progress) {
+        error!("failed to save quest progress: {e}");
+    }
+}
+
+/// Advances quests of players and reports their progress
+#[derive(SystemParam)]
+struct QuestTracker<'w, 's> {
+    players: Query<
+        'w,
+        's,
+        (
+            &'static ConnectionId,
+            &'static Uuid,
+            &'static mut QuestProgress,
+        ),
+    >,
+    quests: Res<'w, Quests>,
+    storage: Res<'w, QuestStorage>,
+    compose: Res<'w, Compose>,
+    commands: Commands<'w, 's>,
+}
+
+impl QuestTracker<'_, '_> {
+    fn advance(&mut self, player: Entity, amount: u32, matches: impl FnMut(&Objective) -> bool) {
+        let Ok((&connection_id, uuid, mut progress)) = self.players.get_mut(player) else {
+            return;
+        };
+
+        let progressed = progress.advance(&self.quests, amount, matches);
+        if progressed.is_empty() {
+            return;
+        }
+
+        save(&self.storage, uuid, &progress);
+
+        for (id, state) in progressed {
+            let Some(quest) = self.quests.get(&id) else {
+                continue;
+            };
+
+            let msg = if state.completed {
+                format!("§aQuest complete: {}", quest.name)
+            } else {
+                format!(
+                    "§6{}: §e{}/{}",
+                    quest.name,
+                    state.progress,
+                    quest.objective.goal()
+                )
+            };
+
+            if let Err(e) = self
+                .compose
+                .unicast(&agnostic::action_bar(msg), connection_id)
+            {
+                error!("failed to send quest progress: {e}");
+            }
+
+            if state.completed {
+                self.commands
+                    .entity(player)
+                    .trigger(QuestCompleted { quest: id });
+            }
+        }
+    }
+}
+
+fn load_progress(
+    trigger: Trigger<'_, OnAdd, Uuid>,
+    query: Query<'_, '_, &Uuid, With<ConnectionId>>,
+    storage: Res<'_, QuestStorage>,
+    mut commands: Commands<'_, '_>,
+) {
+    let Ok(uuid) = query.get(trigger.target()) else {
+        return;
+    };
+
+    let progress = storage
+        .0
+        .get(&uuid.0.to_string())
+        .unwrap_or_else(|e| {
+            error!("failed to load quest progress: {e}");
+            None
+        })
+        .unwrap_or_default();
+
+    commands.entity(trigger.target()).insert(progress);
+}
+
+fn track_broken_blocks(
+    mut events: EventReader<'_, '_, Cancellable<DestroyBlock>>,
+    mut tracker: QuestTracker<'_, '_>,
+) {
+    for event in events.read() {
+        if event.is_cancelled() {
+            continue;
+        }
+
+        tracker.advance(event.from, 1, |objective| {
+            matches!(objective, Objective::BreakBlocks { .. })
+        });
+    }
+}
+
+fn track_kills(mut events: EventReader<'_, '_, PlayerDeath>, mut tracker: QuestTracker<'_, '_>) {
+    for event in events.read() {
+        let Some(killer) = event.killer else {
+            continue;
+        };
+
+        tracker.advance(killer, 1, |objective| {
+            matches!(objective, Objective::KillPlayers { .. })
+        });
+    }
+}
+
+fn track_locations(
+    query: Query<'_, '_, (Entity, &Position), (Changed<Position>, With<QuestProgress>)>,
+    mut tracker: QuestTracker<'_, '_>,
+) {
+    for (player, position) in &query {
+        tracker.advance(player, 1, |objective| match *objective {
+            Objective::ReachLocation {
+                position: target,
+                radius,
+            } => position.distance(target) <= radius,
+            _ => false,
+        });
+    }
+}
+
+fn give_rewards(
+    trigger: Trigger<'_, QuestCompleted>,
+    mut query: Query<'_, '_, (&ConnectionId, &mut PlayerInventory)>,
+    quests: Res<'_, Quests>,
+    compose: Res<'_, Compose>,
+) {
+    let Some(quest) = quests.get(&trigger.event().quest) else {
+        return;
+    };
+
+    let (&connection_id, mut inventory) = match query.get_mut(trigger.target()) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("failed to give quest rewards: query failed: {e}");
+            return;
+        }
+    };
+
+    let mut full = false;
+    for item in &quest.rewards {
+        full |= inventory.try_add_item(item.clone()).remaining.is_some();
+    }
+
+    let mut msg = format!("§aYou completed the quest {}", quest.name);
+    if full {
+        msg.push_str("§c, but some rewards did not fit in your inventory");
+    }
+
+    if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+        error!("failed to send quest reward message: {e}");
+    }
+}
+
+/// Adds `/quests` and tracks the progress of players on the [`Quests`]
+pub struct QuestsPlugin;
+
+impl Plugin for QuestsPlugin {
+    fn build(&self, app: &mut App) {
+        let bucket = app
+            .world()
+            .resource::<LocalDb>()
+            .bucket("hyperion-quests")
+            .expect("failed to open quest progress");
+
+        app.insert_resource(QuestStorage(bucket));
+        app.init_resource::<Quests>();
+        app.add_observer(load_progress);
+        app.add_observer(give_rewards);
+        app.add_systems(
+            FixedUpdate,
+            (
+                timed(track_broken_blocks).after(CancelEvents),
+                timed(track_kills),
+                timed(track_locations),
+            ),
+        );
+
+        QuestsCommand::register(app.world_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance() {
+        let mut quests = Quests::default();
+        quests.define(
+            "miner",
+            Quest::new("Miner", Objective::BreakBlocks { count: 3 }),
+        );
+        quests.define(
+            "hunter",
+            Quest::new("Hunter", Objective::KillPlayers { count: 1 }),
+        );
+
+        let breaks = |objective: &Objective| matches!(objective, Objective::BreakBlocks { .. });
+
+        let mut progress = QuestProgress::default();
+        assert_eq!(progress.advance(&quests, 2, breaks), vec![(
+            "miner".to_owned(),
+            QuestState {
+                progress: 2,
+                completed: false
+            }
+        )]);
+        assert_eq!(progress.advance(&quests, 2, breaks), vec![(
+            "miner".to_owned(),
+            QuestState {
+                progress: 3,
+                completed: true
+            }
+        )]);
+
+        // Completed quests no longer progress
+        assert!(progress.advance(&quests, 1, breaks).is_empty());
+        assert_eq!(progress.get("hunter"), QuestState::default());
+    }
+}
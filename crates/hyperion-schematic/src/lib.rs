@@ -0,0 +1,391 @@
+//! Loading Sponge (`.schem`) and Litematica (`.litematic`) schematics and pasting them into
+//! [`Blocks`].
+//!
+//! [`Schematic::paste`] pastes a schematic immediately, loading any chunks it needs. Large
+//! schematics, such as arenas which are reset between games, should be pasted with
+//! [`PasteQueue::push`] instead, which spreads the paste over several ticks.
+
+mod parse;
+mod transform;
+
+use std::{collections::VecDeque, io::Read, path::Path, sync::Arc};
+
+use bevy::prelude::*;
+use hyperion::{
+    BlockState,
+    glam::{I16Vec2, IVec2, IVec3},
+    runtime::AsyncRuntime,
+    simulation::blocks::{Blocks, TrySetBlockDeltaError},
+    timings::timed,
+};
+pub use parse::{SchematicError, parse_block_state};
+use tracing::warn;
+pub use transform::{Mirror, Rotation, Transform};
+use valence_nbt::Compound;
+
+/// A cuboid of blocks
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schematic {
+    size: IVec3,
+    offset: IVec3,
+    /// Ordered by Y, then Z, then X
+    blocks: Vec<BlockState>,
+}
+
+impl Schematic {
+    /// Creates a schematic of `size` filled with `state`
+    #[must_use]
+    pub fn filled(size: IVec3, state: BlockState) -> Self {
+        let volume = size.max(IVec3::ZERO).element_product();
+        Self {
+            size: size.max(IVec3::ZERO),
+            offset: IVec3::ZERO,
+            blocks: vec![state; usize::try_from(volume).unwrap_or(0)],
+        }
+    }
+
+    /// Reads a schematic from a file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SchematicError> {
+        let bytes = std::fs::read(path)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Parses a schematic in either format from NBT, which may be gzip compressed
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SchematicError> {
+        let decompressed;
+        let mut bytes = if bytes.starts_with(&[0x1F, 0x8B]) {
+            let mut buf = Vec::new();
+            flate2::read::GzDecoder::new(bytes).read_to_end(&mut buf)?;
+            decompressed = buf;
+            decompressed.as_slice()
+        } else {
+            bytes
+        };
+
+        let (root, _): (Compound, String) = valence_nbt::from_binary(&mut bytes)?;
+        Self::from_nbt(&root)
+    }
+
+    /// Parses a schematic in either format from its root compound
+    pub fn from_nbt(root: &Compound) -> Result<Self, SchematicError> {
+        if root.contains_key("Regions") {
+            parse::parse_litematic(root)
+        } else if root.contains_key("Schematic") || root.contains_key("Version") {
+            parse::parse_sponge(root)
+        } else {
+            Err(SchematicError::UnknownFormat)
+        }
+    }
+
+    /// The size of the schematic in blocks
+    #[must_use]
+    pub const fn size(&self) -> IVec3 {
+        self.size
+    }
+
+    /// The position of the first block relative to the paste origin
+    #[must_use]
+    pub const fn offset(&self) -> IVec3 {
+        self.offset
+    }
+
+    /// Sets the position of the first block relative to the paste origin
+    pub const fn set_offset(&mut self, offset: IVec3) {
+        self.offset = offset;
+    }
+
+    fn index(&self, position: IVec3) -> usize {
+        let IVec3 { x, y, z } = position;
+        let index = x + z * self.size.x + y * self.size.x * self.size.z;
+        usize::try_from(index).unwrap()
+    }
+
+    fn position_of(size: IVec3, index: usize) -> IVec3 {
+        let index = i32::try_from(index).unwrap();
+        let x = index % size.x;
+        let z = index / size.x % size.z;
+        let y = index / (size.x * size.z);
+        IVec3::new(x, y, z)
+    }
+
+    /// Returns the block at `position`, where the first block is at the origin
+    #[must_use]
+    pub fn get(&self, position: IVec3) -> Option<BlockState> {
+        if position.cmplt(IVec3::ZERO).any() || position.cmpge(self.size).any() {
+            return None;
+        }
+
+        Some(self.blocks[self.index(position)])
+    }
+
+    /// Sets the block at `position`, where the first block is at the origin. Returns the old
+    /// block, or `None` if `position` is outside the schematic.
+    pub fn set(&mut self, position: IVec3, state: BlockState) -> Option<BlockState> {
+        let old = self.get(position)?;
+        let index = self.index(position);
+        self.blocks[index] = state;
+        Some(old)
+    }
+
+    /// Returns every block with its position, where the first block is at the origin
+    pub fn iter(&self) -> impl Iterator<Item = (IVec3, BlockState)> + '_ {
+        self.blocks
+            .iter()
+            .enumerate()
+            .map(|(index, &state)| (Self::position_of(self.size, index), state))
+    }
+
+    /// Returns the position and state the block at `index` is pasted with
+    fn placed(&self, index: usize, paste: &Paste) -> (IVec3, BlockState) {
+        let position = Self::position_of(self.size, index) + self.offset;
+        let position = paste.origin + paste.transform.apply(position);
+        (position, paste.transform.apply_state(self.blocks[index]))
+    }
+
+    /// Pastes every block immediately. Chunks which are not loaded are loaded, blocking until
+    /// they are.
+    ///
+    /// Returns the number of blocks which could not be placed because they are outside of the
+    /// world.
+    pub fn paste(&self, blocks: &mut Blocks, runtime: &AsyncRuntime, paste: &Paste) -> usize {
+        let mut skipped = 0;
+
+        for index in 0..self.blocks.len() {
+            let (position, state) = self.placed(index, paste);
+
+            if paste.ignore_air && state.is_air() {
+                continue;
+            }
+
+            match blocks.set_block(position, state) {
+                Ok(_) => {}
+                Err(TrySetBlockDeltaError::OutOfBounds) => skipped += 1,
+                Err(TrySetBlockDeltaError::ChunkNotLoaded) => {
+                    blocks.block_and_load(chunk_of(position), runtime);
+
+                    if blocks.set_block(position, state).is_err() {
+                        skipped += 1;
+                    }
+                }
+            }
+        }
+
+        skipped
+    }
+}
+
+fn chunk_of(position: IVec3) -> I16Vec2 {
+    (IVec2::new(position.x, position.z) >> 4).as_i16vec2()
+}
+
+/// Where and how a schematic is pasted
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Paste {
+    /// The position the schematic is transformed around
+    pub origin: IVec3,
+    pub transform: Transform,
+    /// Whether air in the schematic keeps the blocks which are already in the world
+    pub ignore_air: bool,
+}
+
+impl Paste {
+    #[must_use]
+    pub fn new(origin: IVec3) -> Self {
+        Self {
+            origin,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub const fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    #[must_use]
+    pub const fn ignoring_air(mut self) -> Self {
+        self.ignore_air = true;
+        self
+    }
+}
+
+/// Identifies a paste in the [`PasteQueue`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PasteId(u64);
+
+/// Sent when a paste from the [`PasteQueue`] has placed all of its blocks
+#[derive(Event, Copy, Clone, Debug)]
+pub struct PasteFinished {
+    pub id: PasteId,
+    /// The number of blocks which were outside of the world
+    pub skipped: usize,
+}
+
+struct PasteJob {
+    id: PasteId,
+    schematic: Arc<Schematic>,
+    paste: Paste,
+    /// The index of the next block to place
+    next: usize,
+    skipped: usize,
+}
+
+/// Pastes which are placed over several ticks, in the order they were pushed
+#[derive(Resource)]
+pub struct PasteQueue {
+    jobs: VecDeque<PasteJob>,
+    next_id: u64,
+    blocks_per_tick: usize,
+}
+
+impl PasteQueue {
+    /// Queues a paste. At most [`SchematicPlugin::blocks_per_tick`] blocks are placed each tick,
+    /// and a [`PasteFinished`] event is sent once all of them are placed.
+    pub fn push(&mut self, schematic: Arc<Schematic>, paste: Paste) -> PasteId {
+        let id = PasteId(self.next_id);
+        self.next_id += 1;
+
+        self.jobs.push_back(PasteJob {
+            id,
+            schematic,
+            paste,
+            next: 0,
+            skipped: 0,
+        });
+
+        id
+    }
+
+    /// Cancels a paste. Blocks which were already placed are kept. Returns whether the paste
+    /// was queued.
+    pub fn cancel(&mut self, id: PasteId) -> bool {
+        let len = self.jobs.len();
+        self.jobs.retain(|job| job.id != id);
+        self.jobs.len() != len
+    }
+
+    /// Returns the fraction of the blocks of a paste which have been placed, or `None` if the
+    /// paste is not queued
+    #[must_use]
+    pub fn progress(&self, id: PasteId) -> Option<f32> {
+        let job = self.jobs.iter().find(|job| job.id == id)?;
+
+        #[expect(
+            clippy::cast_precision_loss,
+            reason = "progress does not need to be exact"
+        )]
+        Some(job.next as f32 / job.schematic.blocks.len().max(1) as f32)
+    }
+
+    /// Returns whether any paste is queued
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+}
+
+fn process_paste_queue(
+    mut queue: ResMut<'_, PasteQueue>,
+    mut blocks: ResMut<'_, Blocks>,
+    mut finished: EventWriter<'_, PasteFinished>,
+) {
+    let mut budget = queue.blocks_per_tick;
+
+    while budget > 0 {
+        let Some(job) = queue.jobs.front_mut() else {
+            return;
+        };
+
+        while budget > 0 && job.next < job.schematic.blocks.len() {
+            let (position, state) = job.schematic.placed(job.next, &job.paste);
+
+            if job.paste.ignore_air && state.is_air() {
+                job.next += 1;
+                continue;
+            }
+
+            match blocks.set_block(position, state) {
+                Ok(_) => {}
+                Err(TrySetBlockDeltaError::OutOfBounds) => job.skipped += 1,
+                Err(TrySetBlockDeltaError::ChunkNotLoaded) => {
+                    // The paste continues from this block once the chunk is loaded
+                    let _ = blocks.get_cached_or_load(chunk_of(position));
+                    return;
+                }
+            }
+
+            job.next += 1;
+            budget -= 1;
+        }
+
+        if job.next < job.schematic.blocks.len() {
+            return;
+        }
+
+        let job = queue.jobs.pop_front().unwrap();
+        if job.skipped > 0 {
+            warn!(
+                "{} blocks of paste {:?} were outside of the world",
+                job.skipped, job.id
+            );
+        }
+
+        finished.write(PasteFinished {
+            id: job.id,
+            skipped: job.skipped,
+        });
+    }
+}
+
+/// Adds the [`PasteQueue`]
+pub struct SchematicPlugin {
+    /// The maximum number of blocks the [`PasteQueue`] places each tick
+    pub blocks_per_tick: usize,
+}
+
+impl Default for SchematicPlugin {
+    fn default() -> Self {
+        Self {
+            blocks_per_tick: 16_384,
+        }
+    }
+}
+
+impl Plugin for SchematicPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PasteQueue {
+            jobs: VecDeque::new(),
+            next_id: 0,
+            blocks_per_tick: self.blocks_per_tick,
+        });
+        app.add_event::<PasteFinished>();
+        app.add_systems(FixedUpdate, timed(process_paste_queue));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placed() {
+        let mut schematic = Schematic::filled(IVec3::new(2, 1, 3), BlockState::STONE);
+        schematic.set_offset(IVec3::new(1, 0, 0));
+
+        let paste = Paste::new(IVec3::new(10, 64, 10))
+            .with_transform(Transform::new(Rotation::Clockwise90, Mirror::None));
+
+        let index = schematic.index(IVec3::new(1, 0, 2));
+        assert_eq!(
+            Schematic::position_of(schematic.size(), index),
+            IVec3::new(1, 0, 2)
+        );
+
+        // (1, 0, 2) + offset = (2, 0, 2), which is rotated to (-2, 0, 2)
+        assert_eq!(
+            schematic.placed(index, &paste),
+            (IVec3::new(8, 64, 12), BlockState::STONE)
+        );
+    }
+}
@@ -0,0 +1,388 @@
+use hyperion::{
+    BlockKind, BlockState,
+    glam::IVec3,
+    valence_protocol::block::{PropName, PropValue},
+};
+use thiserror::Error;
+use valence_nbt::{Compound, List, Value};
+
+use crate::Schematic;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SchematicError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid nbt: {0}")]
+    Nbt(#[from] valence_nbt::Error),
+    #[error("unknown schematic format")]
+    UnknownFormat,
+    #[error("unsupported sponge schematic version {0}")]
+    UnsupportedVersion(i32),
+    #[error("missing or invalid field \"{0}\"")]
+    MissingField(&'static str),
+    #[error("invalid schematic size")]
+    BadSize,
+    #[error("invalid block state \"{0}\"")]
+    BadBlockState(String),
+    #[error("block data does not match the palette or size")]
+    BadBlockData,
+}
+
+/// Parses a block state in the format `minecraft:oak_stairs[facing=north,half=bottom]`
+pub fn parse_block_state(text: &str) -> Result<BlockState, SchematicError> {
+    let bad = || SchematicError::BadBlockState(text.to_owned());
+
+    let (name, properties) = match text.split_once('[') {
+        Some((name, properties)) => (name, properties.strip_suffix(']').ok_or_else(bad)?),
+        None => (text, ""),
+    };
+
+    let name = name.rsplit_once(':').map_or(name, |(_, path)| path);
+    let mut state = BlockKind::from_str(name).ok_or_else(bad)?.to_state();
+
+    for property in properties
+        .split(',')
+        .filter(|property| !property.is_empty())
+    {
+        let (key, value) = property.split_once('=').ok_or_else(bad)?;
+        let key = PropName::from_str(key).ok_or_else(bad)?;
+        let value = PropValue::from_str(value).ok_or_else(bad)?;
+        state = state.set(key, value);
+    }
+
+    Ok(state)
+}
+
+fn get_int(compound: &Compound, field: &'static str) -> Result<i32, SchematicError> {
+    match compound.get(field) {
+        Some(Value::Byte(value)) => Ok(i32::from(*value)),
+        Some(Value::Short(value)) => Ok(i32::from(*value)),
+        Some(Value::Int(value)) => Ok(*value),
+        _ => Err(SchematicError::MissingField(field)),
+    }
+}
+
+fn get_compound<'a>(
+    compound: &'a Compound,
+    field: &'static str,
+) -> Result<&'a Compound, SchematicError> {
+    match compound.get(field) {
+        Some(Value::Compound(value)) => Ok(value),
+        _ => Err(SchematicError::MissingField(field)),
+    }
+}
+
+fn get_vec3(compound: &Compound, field: &'static str) -> Result<IVec3, SchematicError> {
+    let vec = get_compound(compound, field)?;
+    Ok(IVec3::new(
+        get_int(vec, "x")?,
+        get_int(vec, "y")?,
+        get_int(vec, "z")?,
+    ))
+}
+
+fn volume(size: IVec3) -> Result<usize, SchematicError> {
+    if size.min_element() <= 0 {
+        return Err(SchematicError::BadSize);
+    }
+
+    let volume = i64::from(size.x) * i64::from(size.y) * i64::from(size.z);
+    usize::try_from(volume).map_err(|_| SchematicError::BadSize)
+}
+
+/// Reads the unsigned varints of the block data of a Sponge schematic
+fn read_varints(bytes: &[i8], len: usize) -> Result<Vec<usize>, SchematicError> {
+    let mut values = Vec::with_capacity(len);
+    let mut value = 0_usize;
+    let mut shift = 0_u32;
+
+    for &byte in bytes {
+        #[expect(clippy::cast_sign_loss, reason = "nbt byte arrays are signed")]
+        let byte = byte as u8;
+
+        value |= usize::from(byte & 0x7F) << shift;
+
+        if byte & 0x80 == 0 {
+            values.push(value);
+            value = 0;
+            shift = 0;
+        } else {
+            shift += 7;
+            if shift >= 32 {
+                return Err(SchematicError::BadBlockData);
+            }
+        }
+    }
+
+    if values.len() != len || shift != 0 {
+        return Err(SchematicError::BadBlockData);
+    }
+
+    Ok(values)
+}
+
+/// Parses a Sponge schematic of version 1, 2 or 3
+pub fn parse_sponge(root: &Compound) -> Result<Schematic, SchematicError> {
+    // Version 3 nests everything in a "Schematic" compound
+    let schematic = match root.get("Schematic") {
+        Some(Value::Compound(schematic)) => schematic,
+        _ => root,
+    };
+
+    let version = get_int(schematic, "Version")?;
+    let (palette, data) = match version {
+        1 | 2 => (
+            get_compound(schematic, "Palette")?,
+            schematic.get("BlockData"),
+        ),
+        3 => {
+            let blocks = get_compound(schematic, "Blocks")?;
+            (get_compound(blocks, "Palette")?, blocks.get("Data"))
+        }
+        version => return Err(SchematicError::UnsupportedVersion(version)),
+    };
+
+    let Some(Value::ByteArray(data)) = data else {
+        return Err(SchematicError::MissingField("BlockData"));
+    };
+
+    let size = IVec3::new(
+        get_int(schematic, "Width")?,
+        get_int(schematic, "Height")?,
+        get_int(schematic, "Length")?,
+    );
+
+    // Sizes are stored as unsigned shorts
+    let size = size.map(|value| if value < 0 { value + 0x1_0000 } else { value });
+
+    let mut states = Vec::new();
+    for (name, id) in palette {
+        let Value::Int(id) = id else {
+            return Err(SchematicError::MissingField("Palette"));
+        };
+        let id = usize::try_from(*id).map_err(|_| SchematicError::BadBlockData)?;
+
+        if states.len() <= id {
+            states.resize(id + 1, BlockState::AIR);
+        }
+        states[id] = parse_block_state(name)?;
+    }
+
+    let blocks = read_varints(data, volume(size)?)?
+        .into_iter()
+        .map(|id| states.get(id).copied().ok_or(SchematicError::BadBlockData))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // WorldEdit stores the position of the blocks relative to the origin of the clipboard in
+    // its metadata
+    let offset = match schematic.get("Metadata") {
+        Some(Value::Compound(metadata)) => IVec3::new(
+            get_int(metadata, "WEOffsetX").unwrap_or(0),
+            get_int(metadata, "WEOffsetY").unwrap_or(0),
+            get_int(metadata, "WEOffsetZ").unwrap_or(0),
+        ),
+        _ => IVec3::ZERO,
+    };
+
+    Ok(Schematic {
+        size,
+        offset,
+        blocks,
+    })
+}
+
+/// A region of a Litematica schematic, with its blocks in the same order as [`Schematic`]
+struct Region {
+    min: IVec3,
+    size: IVec3,
+    blocks: Vec<BlockState>,
+}
+
+fn parse_region(region: &Compound) -> Result<Region, SchematicError> {
+    let position = get_vec3(region, "Position")?;
+    let signed_size = get_vec3(region, "Size")?;
+
+    // A negative size extends the region from its position in the negative direction
+    let size = signed_size.abs();
+    let min = position + signed_size.map(|value| if value < 0 { value + 1 } else { 0 });
+
+    let Some(Value::List(List::Compound(palette))) = region.get("BlockStatePalette") else {
+        return Err(SchematicError::MissingField("BlockStatePalette"));
+    };
+
+    let states = palette
+        .iter()
+        .map(|entry| {
+            let Some(Value::String(name)) = entry.get("Name") else {
+                return Err(SchematicError::MissingField("Name"));
+            };
+
+            let name = name
+                .rsplit_once(':')
+                .map_or(name.as_str(), |(_, path)| path);
+            let mut state = BlockKind::from_str(name)
+                .ok_or_else(|| SchematicError::BadBlockState(name.to_owned()))?
+                .to_state();
+
+            if let Some(Value::Compound(properties)) = entry.get("Properties") {
+                for (key, value) in properties {
+                    let Value::String(value) = value else {
+                        return Err(SchematicError::MissingField("Properties"));
+                    };
+
+                    let bad = || SchematicError::BadBlockState(format!("{name}[{key}={value}]"));
+                    let key = PropName::from_str(key).ok_or_else(bad)?;
+                    let value = PropValue::from_str(value).ok_or_else(bad)?;
+                    state = state.set(key, value);
+                }
+            }
+
+            Ok(state)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if states.is_empty() {
+        return Err(SchematicError::MissingField("BlockStatePalette"));
+    }
+
+    let Some(Value::LongArray(data)) = region.get("BlockStates") else {
+        return Err(SchematicError::MissingField("BlockStates"));
+    };
+
+    let bits = (usize::BITS - (states.len() - 1).leading_zeros()).max(2) as usize;
+    let mask = (1_u64 << bits) - 1;
+    let len = volume(size)?;
+
+    if data.len() * 64 < len * bits {
+        return Err(SchematicError::BadBlockData);
+    }
+
+    // Entries are packed without padding, so an entry can span two longs
+    #[expect(clippy::cast_sign_loss, reason = "nbt long arrays are signed")]
+    let blocks = (0..len)
+        .map(|index| {
+            let bit = index * bits;
+            let (long, offset) = (bit / 64, bit % 64);
+
+            let mut value = (data[long] as u64) >> offset;
+            if offset + bits > 64 {
+                value |= (data[long + 1] as u64) << (64 - offset);
+            }
+
+            let id = usize::try_from(value & mask).map_err(|_| SchematicError::BadBlockData)?;
+            states.get(id).copied().ok_or(SchematicError::BadBlockData)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Region { min, size, blocks })
+}
+
+/// Parses a Litematica schematic, merging all of its regions
+pub fn parse_litematic(root: &Compound) -> Result<Schematic, SchematicError> {
+    let regions = get_compound(root, "Regions")?
+        .values()
+        .map(|region| match region {
+            Value::Compound(region) => parse_region(region),
+            _ => Err(SchematicError::MissingField("Regions")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let Some(min) = regions.iter().map(|region| region.min).reduce(IVec3::min) else {
+        return Err(SchematicError::MissingField("Regions"));
+    };
+    let max = regions
+        .iter()
+        .map(|region| region.min + region.size)
+        .fold(min, IVec3::max);
+
+    let mut schematic = Schematic {
+        size: max - min,
+        offset: min,
+        blocks: vec![BlockState::AIR; volume(max - min)?],
+    };
+
+    for region in &regions {
+        let start = region.min - min;
+
+        for (index, &state) in region.blocks.iter().enumerate() {
+            let position = start + Schematic::position_of(region.size, index);
+            let index = schematic.index(position);
+            schematic.blocks[index] = state;
+        }
+    }
+
+    Ok(schematic)
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::compound;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_block_state() {
+        let state = parse_block_state("minecraft:oak_stairs[facing=east,half=top]").unwrap();
+        assert_eq!(state.to_kind(), BlockKind::OakStairs);
+        assert_eq!(state.get(PropName::Facing), Some(PropValue::East));
+        assert_eq!(state.get(PropName::Half), Some(PropValue::Top));
+
+        assert_eq!(parse_block_state("stone").unwrap(), BlockState::STONE);
+        assert!(parse_block_state("minecraft:not_a_block").is_err());
+        assert!(parse_block_state("minecraft:oak_stairs[facing=east").is_err());
+    }
+
+    #[test]
+    fn test_parse_sponge() {
+        let root = compound! {
+            "Version" => 2,
+            "Width" => 2_i16,
+            "Height" => 1_i16,
+            "Length" => 2_i16,
+            "Palette" => compound! {
+                "minecraft:air" => 0,
+                "minecraft:stone" => 1,
+            },
+            "BlockData" => Value::ByteArray(vec![1, 0, 0, 1]),
+            "Metadata" => compound! {
+                "WEOffsetX" => -1,
+                "WEOffsetY" => 0,
+                "WEOffsetZ" => 0,
+            },
+        };
+
+        let schematic = parse_sponge(&root).unwrap();
+        assert_eq!(schematic.size(), IVec3::new(2, 1, 2));
+        assert_eq!(schematic.offset(), IVec3::new(-1, 0, 0));
+        assert_eq!(schematic.get(IVec3::new(0, 0, 0)), Some(BlockState::STONE));
+        assert_eq!(schematic.get(IVec3::new(1, 0, 0)), Some(BlockState::AIR));
+        assert_eq!(schematic.get(IVec3::new(1, 0, 1)), Some(BlockState::STONE));
+        assert_eq!(schematic.get(IVec3::new(2, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_parse_litematic() {
+        // Three entries of two bits: stone, air, stone
+        let root = compound! {
+            "Regions" => compound! {
+                "main" => compound! {
+                    "Position" => compound! { "x" => 0, "y" => 0, "z" => 0 },
+                    "Size" => compound! { "x" => -3, "y" => 1, "z" => 1 },
+                    "BlockStatePalette" => List::Compound(vec![
+                        compound! { "Name" => "minecraft:air" },
+                        compound! { "Name" => "minecraft:stone" },
+                    ]),
+                    "BlockStates" => Value::LongArray(vec![0b01_00_01]),
+                },
+            },
+        };
+
+        let schematic = parse_litematic(&root).unwrap();
+        assert_eq!(schematic.size(), IVec3::new(3, 1, 1));
+        assert_eq!(schematic.offset(), IVec3::new(-2, 0, 0));
+        assert_eq!(schematic.get(IVec3::new(0, 0, 0)), Some(BlockState::STONE));
+        assert_eq!(schematic.get(IVec3::new(1, 0, 0)), Some(BlockState::AIR));
+        assert_eq!(schematic.get(IVec3::new(2, 0, 0)), Some(BlockState::STONE));
+    }
+}
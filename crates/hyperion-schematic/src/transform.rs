@@ -0,0 +1,227 @@
+use hyperion::{
+    BlockState,
+    glam::IVec3,
+    valence_protocol::block::{PropName, PropValue},
+};
+
+/// A clockwise rotation around the Y axis when viewed from above
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+impl Rotation {
+    const fn quarter_turns(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Clockwise90 => 1,
+            Self::Clockwise180 => 2,
+            Self::Clockwise270 => 3,
+        }
+    }
+}
+
+/// A mirror which flips blocks along an axis
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Mirror {
+    #[default]
+    None,
+    /// Negates X, swapping east and west
+    X,
+    /// Negates Z, swapping north and south
+    Z,
+}
+
+/// How a schematic is placed relative to the paste origin. The mirror is applied before the
+/// rotation.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Transform {
+    pub rotation: Rotation,
+    pub mirror: Mirror,
+}
+
+const HORIZONTAL: [PropValue; 4] = [
+    PropValue::North,
+    PropValue::East,
+    PropValue::South,
+    PropValue::West,
+];
+
+const SIDES: [PropName; 4] = [
+    PropName::North,
+    PropName::East,
+    PropName::South,
+    PropName::West,
+];
+
+/// Returns the index of `value` in [`HORIZONTAL`]
+fn horizontal_index(value: PropValue) -> Option<usize> {
+    HORIZONTAL
+        .iter()
+        .position(|&horizontal| horizontal == value)
+}
+
+/// Returns the index in [`HORIZONTAL`] of the direction with index `index` after mirroring
+const fn mirror_index(mirror: Mirror, index: usize) -> usize {
+    match mirror {
+        Mirror::None => index,
+        Mirror::X => [0, 3, 2, 1][index],
+        Mirror::Z => [2, 1, 0, 3][index],
+    }
+}
+
+impl Transform {
+    #[must_use]
+    pub const fn new(rotation: Rotation, mirror: Mirror) -> Self {
+        Self { rotation, mirror }
+    }
+
+    /// Transforms a position relative to the paste origin
+    #[must_use]
+    pub const fn apply(self, position: IVec3) -> IVec3 {
+        let IVec3 { mut x, y, mut z } = position;
+
+        match self.mirror {
+            Mirror::None => {}
+            Mirror::X => x = -x,
+            Mirror::Z => z = -z,
+        }
+
+        match self.rotation {
+            Rotation::None => IVec3::new(x, y, z),
+            Rotation::Clockwise90 => IVec3::new(-z, y, x),
+            Rotation::Clockwise180 => IVec3::new(-x, y, -z),
+            Rotation::Clockwise270 => IVec3::new(z, y, -x),
+        }
+    }
+
+    /// Transforms the direction dependent properties of a block, such as `facing`, `axis`,
+    /// `rotation` and the connections of fences and walls
+    #[must_use]
+    pub fn apply_state(self, mut state: BlockState) -> BlockState {
+        if self == Self::default() {
+            return state;
+        }
+
+        let turns = usize::from(self.rotation.quarter_turns());
+        let direction = |index: usize| (mirror_index(self.mirror, index) + turns) % 4;
+
+        if let Some(index) = state.get(PropName::Facing).and_then(horizontal_index) {
+            state = state.set(PropName::Facing, HORIZONTAL[direction(index)]);
+        }
+
+        if turns % 2 == 1 {
+            match state.get(PropName::Axis) {
+                Some(PropValue::X) => state = state.set(PropName::Axis, PropValue::Z),
+                Some(PropValue::Z) => state = state.set(PropName::Axis, PropValue::X),
+                _ => {}
+            }
+        }
+
+        if let Some(rotation) = state.get(PropName::Rotation).and_then(PropValue::to_u16) {
+            let mirrored = match self.mirror {
+                Mirror::None => rotation,
+                Mirror::X => (16 - rotation) % 16,
+                Mirror::Z => (24 - rotation) % 16,
+            };
+            let rotated = (mirrored + 4 * u16::from(self.rotation.quarter_turns())) % 16;
+
+            if let Some(value) = PropValue::from_u16(rotated) {
+                state = state.set(PropName::Rotation, value);
+            }
+        }
+
+        let sides = SIDES.map(|side| state.get(side));
+        for (index, value) in sides.into_iter().enumerate() {
+            if let Some(value) = value {
+                state = state.set(SIDES[direction(index)], value);
+            }
+        }
+
+        if self.mirror != Mirror::None {
+            let mirrored = match state.get(PropName::Shape) {
+                Some(PropValue::InnerLeft) => Some(PropValue::InnerRight),
+                Some(PropValue::InnerRight) => Some(PropValue::InnerLeft),
+                Some(PropValue::OuterLeft) => Some(PropValue::OuterRight),
+                Some(PropValue::OuterRight) => Some(PropValue::OuterLeft),
+                _ => None,
+            };
+
+            if let Some(shape) = mirrored {
+                state = state.set(PropName::Shape, shape);
+            }
+
+            match state.get(PropName::Hinge) {
+                Some(PropValue::Left) => state = state.set(PropName::Hinge, PropValue::Right),
+                Some(PropValue::Right) => state = state.set(PropName::Hinge, PropValue::Left),
+                _ => {}
+            }
+        }
+
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion::BlockKind;
+
+    use super::*;
+
+    #[test]
+    fn test_apply_position() {
+        let position = IVec3::new(1, 2, -3);
+
+        assert_eq!(Transform::default().apply(position), position);
+        assert_eq!(
+            Transform::new(Rotation::Clockwise90, Mirror::None).apply(position),
+            IVec3::new(3, 2, 1)
+        );
+        assert_eq!(
+            Transform::new(Rotation::Clockwise180, Mirror::None).apply(position),
+            IVec3::new(-1, 2, 3)
+        );
+        assert_eq!(
+            Transform::new(Rotation::Clockwise270, Mirror::None).apply(position),
+            IVec3::new(-3, 2, -1)
+        );
+        assert_eq!(
+            Transform::new(Rotation::None, Mirror::X).apply(position),
+            IVec3::new(-1, 2, -3)
+        );
+        assert_eq!(
+            Transform::new(Rotation::Clockwise90, Mirror::Z).apply(position),
+            IVec3::new(-3, 2, 1)
+        );
+    }
+
+    #[test]
+    fn test_apply_state() {
+        let stairs = BlockState::from_kind(BlockKind::OakStairs)
+            .set(PropName::Facing, PropValue::North)
+            .set(PropName::Shape, PropValue::InnerLeft);
+
+        let rotated = Transform::new(Rotation::Clockwise90, Mirror::None).apply_state(stairs);
+        assert_eq!(rotated.get(PropName::Facing), Some(PropValue::East));
+        assert_eq!(rotated.get(PropName::Shape), Some(PropValue::InnerLeft));
+
+        let mirrored = Transform::new(Rotation::None, Mirror::Z).apply_state(stairs);
+        assert_eq!(mirrored.get(PropName::Facing), Some(PropValue::South));
+        assert_eq!(mirrored.get(PropName::Shape), Some(PropValue::InnerRight));
+
+        let log = BlockState::from_kind(BlockKind::OakLog).set(PropName::Axis, PropValue::X);
+        let rotated = Transform::new(Rotation::Clockwise270, Mirror::None).apply_state(log);
+        assert_eq!(rotated.get(PropName::Axis), Some(PropValue::Z));
+
+        let fence = BlockState::from_kind(BlockKind::OakFence)
+            .set(PropName::North, PropValue::True)
+            .set(PropName::East, PropValue::False);
+        let rotated = Transform::new(Rotation::Clockwise90, Mirror::None).apply_state(fence);
+        assert_eq!(rotated.get(PropName::North), Some(PropValue::False));
+        assert_eq!(rotated.get(PropName::East), Some(PropValue::True));
+    }
+}
@@ -0,0 +1,74 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::net::{Compose, ConnectionId, DataBundle, agnostic};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+use crate::{Shops, open_shop};
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "shop")]
+#[command_permission(group = "Normal")]
+pub struct ShopCommand {
+    /// The shop to open. Lists the shops if not given.
+    id: Option<String>,
+}
+
+impl MinecraftCommand for ShopCommand {
+    type State = SystemState<(
+        Query<'static, 'static, &'static ConnectionId>,
+        Res<'static, Shops>,
+        Res<'static, Compose>,
+        Commands<'static, 'static>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (query, shops, compose, mut commands) = state.get(world);
+
+        let &connection_id = match query.get(caller) {
+            Ok(connection_id) => connection_id,
+            Err(e) => {
+                error!("shop command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let mut result = Ok(());
+        let lines = match self.id {
+            Some(id) if shops.get(&id).is_some() => {
+                commands.queue(move |world: &mut World| {
+                    open_shop(world, caller, &id);
+                });
+                return Ok(());
+            }
+            Some(id) => {
+                result = Err(CommandFailed);
+                vec![format!("§cNo shop is named {id}")]
+            }
+            None => {
+                let mut lines = vec!["§6Shops:".to_owned()];
+                lines.extend(
+                    shops
+                        .iter()
+                        .map(|(id, shop)| format!("§7- §e{id}§7: {}", shop.title)),
+                );
+
+                if lines.len() == 1 {
+                    lines.push("§7There are no shops".to_owned());
+                }
+
+                lines
+            }
+        };
+
+        let mut bundle = DataBundle::new(&compose);
+        for line in lines {
+            bundle.add_packet(&agnostic::chat(line)).unwrap();
+        }
+        if let Err(e) = bundle.unicast(connection_id) {
+            error!("failed to send shop list: {e}");
+        }
+
+        result
+    }
+}
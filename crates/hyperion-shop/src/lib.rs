@@ -0,0 +1,717 @@
+//! Inventory menus where players buy and sell items for coins, opened with `/shop <id>`.
+//!
+//! Shops are defined in code through the [`Shops`] resource or loaded from TOML and JSON files
+//! as [`ShopDefinition`]s. Purchases go through the [`Bank`] of `hyperion-economy`, so they show
+//! up as [`Transaction`](hyperion_economy::Transaction)s like any other payment.
+//!
+//! ```ignore
+//! shops.define(
+//!     "blocks",
+//!     Shop::new("Blocks").with_entry(ShopEntry::new(ItemKind::WhiteWool, 16).buy(4).sell(2)),
+//! );
+//! ```
+
+mod command;
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use bevy::{ecs::system::SystemState, prelude::*};
+use hyperion::{
+    ItemKind, ItemStack,
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{Uuid, entity_kind::EntityKind},
+    valence_protocol::packets::play::{click_slot_c2s::ClickMode, open_screen_s2c::WindowType},
+};
+use hyperion_clap::MinecraftCommand;
+use hyperion_economy::{Bank, EconomyError, format_coins};
+use hyperion_gui::{Gui, GuiClick};
+use hyperion_inventory::{Inventory, OpenInventory, PlayerInventory};
+use hyperion_item::builder::ItemBuilder;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::command::ShopCommand;
+
+/// The number of items on each page of a shop
+const ITEMS_PER_PAGE: usize = 45;
+
+const PREVIOUS_PAGE_SLOT: usize = 45;
+const BALANCE_SLOT: usize = 49;
+const NEXT_PAGE_SLOT: usize = 53;
+
+/// An item which can be bought or sold in a shop
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShopEntry {
+    pub item: ItemKind,
+    pub count: i8,
+    /// A custom name given to the item
+    pub name: Option<String>,
+    /// The price to buy the item, or `None` if it cannot be bought
+    pub buy: Option<u64>,
+    /// The price paid for selling the item, or `None` if it cannot be sold
+    pub sell: Option<u64>,
+}
+
+impl ShopEntry {
+    #[must_use]
+    pub const fn new(item: ItemKind, count: i8) -> Self {
+        Self {
+            item,
+            count,
+            name: None,
+            buy: None,
+            sell: None,
+        }
+    }
+
+    #[must_use]
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    #[must_use]
+    pub const fn buy(mut self, price: u64) -> Self {
+        self.buy = Some(price);
+        self
+    }
+
+    #[must_use]
+    pub const fn sell(mut self, price: u64) -> Self {
+        self.sell = Some(price);
+        self
+    }
+
+    fn builder(&self) -> ItemBuilder {
+        let builder = ItemBuilder::new(self.item).count(self.count);
+
+        match &self.name {
+            Some(name) => builder.name(name.clone()),
+            None => builder,
+        }
+    }
+
+    /// The items given to players who buy this entry
+    #[must_use]
+    pub fn stack(&self) -> ItemStack {
+        self.builder().build()
+    }
+
+    /// The item shown in the shop, with the prices below its name
+    fn icon(&self) -> ItemStack {
+        let mut lore = Vec::new();
+
+        if let Some(price) = self.buy {
+            lore.push(format!("§7Buy: §e{}", format_coins(price)));
+        }
+
+        if let Some(price) = self.sell {
+            lore.push(format!("§7Sell: §e{}", format_coins(price)));
+        }
+
+        lore.push("§8Left click to buy, right click to sell".to_owned());
+
+        self.builder().lore(lore).build()
+    }
+
+    /// A short description of the items, such as `16 white wool`
+    fn describe(&self) -> String {
+        let name = self
+            .name
+            .clone()
+            .unwrap_or_else(|| self.item.to_str().replace('_', " "));
+
+        format!("{} {name}", self.count)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Shop {
+    /// The title of the menu
+    pub title: String,
+    pub entries: Vec<ShopEntry>,
+}
+
+impl Shop {
+    #[must_use]
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            entries: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_entry(mut self, entry: ShopEntry) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    fn pages(&self) -> usize {
+        self.entries.len().div_ceil(ITEMS_PER_PAGE).max(1)
+    }
+}
+
+/// An item of a [`ShopDefinition`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShopItemDefinition {
+    /// The id of the item, such as `diamond_sword` or `minecraft:diamond_sword`
+    pub item: String,
+    #[serde(default = "default_count")]
+    pub count: i8,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub buy: Option<u64>,
+    #[serde(default)]
+    pub sell: Option<u64>,
+}
+
+const fn default_count() -> i8 {
+    1
+}
+
+/// A shop as written in a TOML or JSON file
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShopDefinition {
+    pub title: String,
+    pub items: Vec<ShopItemDefinition>,
+}
+
+impl ShopDefinition {
+    pub fn from_toml(text: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn from_json(text: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    /// Reads a definition from a `.toml` or `.json` file
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("json") => Self::from_json(&text),
+            _ => anyhow::bail!("shop files must end in .toml or .json"),
+        }
+    }
+}
+
+impl TryFrom<ShopDefinition> for Shop {
+    type Error = anyhow::Error;
+
+    fn try_from(definition: ShopDefinition) -> anyhow::Result<Self> {
+        let entries = definition
+            .items
+            .into_iter()
+            .map(|item| {
+                let id = item.item.strip_prefix("minecraft:").unwrap_or(&item.item);
+                let kind = ItemKind::from_str(id)
+                    .with_context(|| format!("unknown item {}", item.item))?;
+
+                anyhow::ensure!(
+                    (1..=kind.max_stack()).contains(&item.count),
+                    "{} cannot be sold in stacks of {}",
+                    item.item,
+                    item.count
+                );
+
+                Ok(ShopEntry {
+                    item: kind,
+                    count: item.count,
+                    name: item.name,
+                    buy: item.buy,
+                    sell: item.sell,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            title: definition.title,
+            entries,
+        })
+    }
+}
+
+/// Every shop, keyed by id
+#[derive(Resource, Default, Debug)]
+pub struct Shops {
+    shops: BTreeMap<String, Shop>,
+}
+
+impl Shops {
+    /// Defines a shop, replacing any shop with the same id
+    pub fn define(&mut self, id: impl Into<String>, shop: Shop) {
+        self.shops.insert(id.into(), shop);
+    }
+
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&Shop> {
+        self.shops.get(id)
+    }
+
+    /// The shops sorted by id
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Shop)> {
+        self.shops.iter().map(|(id, shop)| (id.as_str(), shop))
+    }
+
+    /// Defines a shop for every `.toml` and `.json` file in `dir`, with the name of the file as
+    /// its id. Files which cannot be read are skipped.
+    pub fn load_dir(&mut self, dir: &Path) -> anyhow::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            match ShopDefinition::load(&path).and_then(Shop::try_from) {
+                Ok(shop) => {
+                    info!("loaded shop {id} from {}", path.display());
+                    self.define(id, shop);
+                }
+                Err(e) => error!("failed to load shop {}: {e:#}", path.display()),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An open shop, which is on the entity with its [`Gui`]
+#[derive(Component, Clone, Debug)]
+struct ShopMenu {
+    viewer: Entity,
+    shop: String,
+    page: usize,
+}
+
+fn notify(world: &World, player: Entity, msg: String) {
+    let Some(&connection_id) = world.get::<ConnectionId>(player) else {
+        return;
+    };
+
+    if let Err(e) = world
+        .resource::<Compose>()
+        .unicast(&agnostic::chat(msg), connection_id)
+    {
+        error!("failed to send shop message: {e}");
+    }
+}
+
+/// Runs `f` with a [`Bank`], applying the events it sends
+fn with_bank<R>(world: &mut World, f: impl FnOnce(&mut Bank<'_, '_>) -> R) -> R {
+    let mut state = SystemState::<Bank<'static, 'static>>::new(world);
+    let mut bank = state.get_mut(world);
+    let result = f(&mut bank);
+    state.apply(world);
+    result
+}
+
+fn balance_item(world: &mut World, viewer: Entity) -> ItemStack {
+    let uuid = world.get::<Uuid>(viewer).map(|uuid| uuid.0);
+    let balance = uuid.map(|uuid| with_bank(world, |bank| bank.balance(uuid)));
+
+    let name = match balance {
+        Some(Ok(balance)) => format!("§6Balance: §e{}", format_coins(balance)),
+        _ => "§6Balance: -".to_owned(),
+    };
+
+    ItemBuilder::new(ItemKind::GoldIngot).name(name).build()
+}
+
+/// Shows `page` of the shop in the menu on `menu_entity`
+fn show(world: &mut World, menu_entity: Entity, page: usize) {
+    let Some(inventory_entity) = world.get::<Gui>(menu_entity).map(Gui::inventory) else {
+        return;
+    };
+
+    let Some(menu) = world.get_mut::<ShopMenu>(menu_entity).map(|mut menu| {
+        menu.page = page;
+        menu.clone()
+    }) else {
+        return;
+    };
+
+    let Some(shop) = world.resource::<Shops>().get(&menu.shop) else {
+        return;
+    };
+
+    let start = page * ITEMS_PER_PAGE;
+    let mut items = shop
+        .entries
+        .iter()
+        .skip(start)
+        .take(ITEMS_PER_PAGE)
+        .map(ShopEntry::icon)
+        .enumerate()
+        .collect::<Vec<_>>();
+
+    if page > 0 {
+        items.push((
+            PREVIOUS_PAGE_SLOT,
+            ItemBuilder::new(ItemKind::Arrow)
+                .name("§ePrevious page")
+                .build(),
+        ));
+    }
+
+    if page + 1 < shop.pages() {
+        items.push((
+            NEXT_PAGE_SLOT,
+            ItemBuilder::new(ItemKind::Arrow)
+                .name("§eNext page")
+                .build(),
+        ));
+    }
+
+    items.push((BALANCE_SLOT, balance_item(world, menu.viewer)));
+
+    let Some(mut inventory) = world.get_mut::<Inventory>(inventory_entity) else {
+        return;
+    };
+
+    inventory.clear();
+    for (slot, item) in items {
+        let _ = inventory.set(u16::try_from(slot).unwrap(), item);
+    }
+}
+
+fn close(world: &mut World, menu_entity: Entity) {
+    if let Some(inventory) = world.get::<Gui>(menu_entity).map(Gui::inventory) {
+        world.despawn(inventory);
+    }
+
+    world.despawn(menu_entity);
+}
+
+/// Opens the shop with this id for `viewer`, replacing any shop they already have open. Returns
+/// whether the shop exists.
+pub fn open_shop(world: &mut World, viewer: Entity, id: &str) -> bool {
+    let Some(title) = world
+        .resource::<Shops>()
+        .get(id)
+        .map(|shop| shop.title.clone())
+    else {
+        return false;
+    };
+
+    let mut menus = world.query::<(Entity, &ShopMenu)>();
+    let open = menus
+        .iter(world)
+        .filter(|(_, menu)| menu.viewer == viewer)
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+
+    for menu_entity in open {
+        close(world, menu_entity);
+    }
+
+    let inventory = Inventory::new(54, title, WindowType::Generic9x6, true);
+    let gui = Gui::new(inventory, world, viewer.to_bits());
+
+    let menu = ShopMenu {
+        viewer,
+        shop: id.to_owned(),
+        page: 0,
+    };
+    let menu_entity = world
+        .spawn((EntityKind::Gui, gui, menu))
+        .observe(on_click)
+        .id();
+
+    show(world, menu_entity, 0);
+
+    if let Some(gui) = world.get::<Gui>(menu_entity).cloned() {
+        gui.open(world, viewer);
+    }
+
+    true
+}
+
+fn on_click(trigger: Trigger<'_, GuiClick>, mut commands: Commands<'_, '_>) {
+    let menu_entity = trigger.target();
+    let click = *trigger.event();
+
+    commands.queue(move |world: &mut World| handle_click(world, menu_entity, click));
+}
+
+fn handle_click(world: &mut World, menu_entity: Entity, click: GuiClick) {
+    let Some(menu) = world.get::<ShopMenu>(menu_entity).cloned() else {
+        return;
+    };
+
+    if click.player != menu.viewer {
+        return;
+    }
+
+    let Some(shop) = world.resource::<Shops>().get(&menu.shop) else {
+        return;
+    };
+    let pages = shop.pages();
+    let entry = (click.slot < ITEMS_PER_PAGE)
+        .then(|| shop.entries.get(menu.page * ITEMS_PER_PAGE + click.slot))
+        .flatten()
+        .cloned();
+
+    match click.slot {
+        PREVIOUS_PAGE_SLOT if menu.page > 0 => show(world, menu_entity, menu.page - 1),
+        NEXT_PAGE_SLOT if menu.page + 1 < pages => show(world, menu_entity, menu.page + 1),
+        _ => {
+            let Some(entry) = entry else {
+                return;
+            };
+
+            let msg = match (click.mode, click.button) {
+                (ClickMode::Click, 0) => buy(world, menu.viewer, &entry),
+                (ClickMode::Click, 1) => sell(world, menu.viewer, &entry),
+                _ => return,
+            };
+
+            notify(world, menu.viewer, msg);
+
+            // Show the new balance
+            show(world, menu_entity, menu.page);
+        }
+    }
+}
+
+/// Buys `entry` for `player`, returning the message to show them
+fn buy(world: &mut World, player: Entity, entry: &ShopEntry) -> String {
+    let Some(price) = entry.buy else {
+        return "§cThis item cannot be bought".to_owned();
+    };
+
+    let Some(uuid) = world.get::<Uuid>(player).map(|uuid| uuid.0) else {
+        return "§cFailed to buy the item".to_owned();
+    };
+
+    let Some(inventory) = world.get::<PlayerInventory>(player) else {
+        return "§cFailed to buy the item".to_owned();
+    };
+
+    // Players are only charged for items which fit in their inventory
+    let fits = inventory
+        .clone()
+        .try_add_item(entry.stack())
+        .remaining
+        .is_none();
+
+    if !fits {
+        return "§cYour inventory is full".to_owned();
+    }
+
+    let description = entry.describe();
+    let reason = format!("bought {description}");
+
+    match with_bank(world, |bank| bank.withdraw(uuid, price, reason)) {
+        Ok(_) => {}
+        Err(e @ EconomyError::InsufficientFunds { .. }) => return format!("§cYou {e}"),
+        Err(e) => return format!("§cFailed to buy {description}: {e}"),
+    }
+
+    let remaining = world
+        .get_mut::<PlayerInventory>(player)
+        .and_then(|mut inventory| inventory.try_add_item(entry.stack()).remaining);
+
+    if remaining.is_some() {
+        error!("bought items did not fit in the inventory of {player} after checking they fit");
+    }
+
+    format!("§aBought {description} for {}", format_coins(price))
+}
+
+/// Sells `entry` for `player`, returning the message to show them
+fn sell(world: &mut World, player: Entity, entry: &ShopEntry) -> String {
+    let Some(price) = entry.sell else {
+        return "§cThis item cannot be sold".to_owned();
+    };
+
+    let Some(uuid) = world.get::<Uuid>(player).map(|uuid| uuid.0) else {
+        return "§cFailed to sell the item".to_owned();
+    };
+
+    let description = entry.describe();
+    let stack = entry.stack();
+
+    let taken = world
+        .get_mut::<PlayerInventory>(player)
+        .is_some_and(|mut inventory| take_items(&mut inventory, &stack));
+
+    if !taken {
+        return format!("§cYou do not have {description} to sell");
+    }
+
+    let reason = format!("sold {description}");
+
+    match with_bank(world, |bank| bank.deposit(uuid, price, reason)) {
+        Ok(_) => format!("§aSold {description} for {}", format_coins(price)),
+        Err(e) => {
+            // Give the items back since the player was not paid for them
+            if let Some(mut inventory) = world.get_mut::<PlayerInventory>(player) {
+                inventory.try_add_item(stack);
+            }
+
+            format!("§cFailed to sell {description}: {e}")
+        }
+    }
+}
+
+/// Removes `stack.count` items matching `stack` from the main inventory and hotbar. Returns
+/// `false` without removing anything if there are not enough.
+fn take_items(inventory: &mut PlayerInventory, stack: &ItemStack) -> bool {
+    let matches = |slot: &ItemStack| slot.item == stack.item && slot.nbt == stack.nbt;
+
+    let available: i32 = inventory
+        .slots_inventory()
+        .iter()
+        .filter(|slot| matches(&slot.stack))
+        .map(|slot| i32::from(slot.stack.count))
+        .sum();
+
+    if available < i32::from(stack.count) {
+        return false;
+    }
+
+    let mut needed = stack.count;
+
+    for slot in inventory.slots_inventory_mut() {
+        if needed == 0 {
+            break;
+        }
+
+        if !matches(&slot.stack) {
+            continue;
+        }
+
+        let taken = needed.min(slot.stack.count);
+        needed -= taken;
+        slot.stack.count -= taken;
+        slot.changed = true;
+
+        if slot.stack.count == 0 {
+            slot.stack = ItemStack::EMPTY;
+        }
+    }
+
+    true
+}
+
+/// Closes the shop once its viewer closes the inventory or leaves
+fn close_menus(
+    trigger: Trigger<'_, OnRemove, OpenInventory>,
+    menus: Query<'_, '_, (Entity, &ShopMenu)>,
+    mut commands: Commands<'_, '_>,
+) {
+    for (menu_entity, menu) in &menus {
+        if menu.viewer == trigger.target() {
+            commands.queue(move |world: &mut World| close(world, menu_entity));
+        }
+    }
+}
+
+/// Adds `/shop` and loads the shops in `dir`
+pub struct ShopPlugin {
+    /// The directory with shop files, which does not have to exist
+    pub dir: PathBuf,
+}
+
+impl Default for ShopPlugin {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("shops"),
+        }
+    }
+}
+
+impl Plugin for ShopPlugin {
+    fn build(&self, app: &mut App) {
+        let mut shops = Shops::default();
+
+        if self.dir.exists() {
+            if let Err(e) = shops.load_dir(&self.dir) {
+                error!("failed to load shops from {}: {e:#}", self.dir.display());
+            }
+        }
+
+        app.insert_resource(shops);
+        app.add_observer(close_menus);
+
+        ShopCommand::register(app.world_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_definition() {
+        let definition = ShopDefinition::from_toml(
+            r#"
+            title = "Blocks"
+
+            [[items]]
+            item = "minecraft:white_wool"
+            count = 16
+            buy = 4
+            sell = 2
+
+            [[items]]
+            item = "diamond_sword"
+            name = "Sharp Sword"
+            buy = 100
+            "#,
+        )
+        .unwrap();
+
+        let shop = Shop::try_from(definition).unwrap();
+        assert_eq!(
+            shop,
+            Shop::new("Blocks")
+                .with_entry(ShopEntry::new(ItemKind::WhiteWool, 16).buy(4).sell(2))
+                .with_entry(
+                    ShopEntry::new(ItemKind::DiamondSword, 1)
+                        .name("Sharp Sword")
+                        .buy(100)
+                )
+        );
+
+        let unknown = ShopDefinition::from_json(
+            r#"{ "title": "Bad", "items": [{ "item": "not_an_item" }] }"#,
+        )
+        .unwrap();
+        assert!(Shop::try_from(unknown).is_err());
+    }
+
+    #[test]
+    fn test_take_items() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(36, ItemStack::new(ItemKind::WhiteWool, 10, None))
+            .unwrap();
+        inventory
+            .set(9, ItemStack::new(ItemKind::WhiteWool, 10, None))
+            .unwrap();
+
+        let stack = ItemStack::new(ItemKind::WhiteWool, 16, None);
+        assert!(take_items(&mut inventory, &stack));
+
+        let left: i8 = [9, 36]
+            .into_iter()
+            .map(|slot| inventory.get(slot).unwrap().stack.count)
+            .sum();
+        assert_eq!(left, 4);
+
+        // Nothing is taken when there are not enough items
+        assert!(!take_items(&mut inventory, &stack));
+        assert_eq!(inventory.get(9).unwrap().stack.count, 0);
+        assert_eq!(inventory.get(36).unwrap().stack.count, 4);
+    }
+}
@@ -4,19 +4,85 @@
 
 use std::simd::{f64x4, num::SimdFloat};
 
-#[derive(Debug, Clone)]
+pub use quantile::P2Quantile;
+pub use rolling::{RollingMode, RollingStats};
+use serde::{Deserialize, Serialize};
+
+mod quantile;
+mod rolling;
+
+/// Serializes the infinite min and max of empty series as `null`, because formats such as json
+/// cannot represent infinity.
+mod infinite_as_none {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(values: &[f64], serializer: S) -> Result<S::Ok, S::Error> {
+        values
+            .iter()
+            .map(|value| value.is_finite().then_some(*value))
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+        infinity: f64,
+    ) -> Result<Vec<f64>, D::Error> {
+        let values = Vec::<Option<f64>>::deserialize(deserializer)?;
+        Ok(values
+            .into_iter()
+            .map(|value| value.unwrap_or(infinity))
+            .collect())
+    }
+
+    pub fn deserialize_min<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<f64>, D::Error> {
+        deserialize(deserializer, f64::INFINITY)
+    }
+
+    pub fn deserialize_max<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<f64>, D::Error> {
+        deserialize(deserializer, f64::NEG_INFINITY)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParallelStats {
     counts: Vec<u64>,
     means: Vec<f64>,
     m2s: Vec<f64>,
+    #[serde(
+        serialize_with = "infinite_as_none::serialize",
+        deserialize_with = "infinite_as_none::deserialize_min"
+    )]
     mins: Vec<f64>,
+    #[serde(
+        serialize_with = "infinite_as_none::serialize",
+        deserialize_with = "infinite_as_none::deserialize_max"
+    )]
     maxs: Vec<f64>,
     width: usize, // Number of parallel statistics being tracked
+    quantiles: Vec<f64>,
+    /// One estimator per tracked quantile for each series, grouped by series
+    estimators: Vec<P2Quantile>,
 }
 
 impl ParallelStats {
     #[must_use]
     pub fn new(width: usize) -> Self {
+        Self::with_quantiles(width, &[])
+    }
+
+    /// Creates statistics which also estimate the given quantiles (such as `0.95` for p95) of
+    /// every series. Each quantile adds a small cost to every update that is not vectorized.
+    #[must_use]
+    pub fn with_quantiles(width: usize, quantiles: &[f64]) -> Self {
+        let estimators = (0..width)
+            .flat_map(|_| quantiles.iter().map(|&quantile| P2Quantile::new(quantile)))
+            .collect();
+
         Self {
             counts: vec![0; width],
             means: vec![0.0; width],
@@ -24,6 +90,8 @@ impl ParallelStats {
             mins: vec![f64::INFINITY; width],
             maxs: vec![f64::NEG_INFINITY; width],
             width,
+            quantiles: quantiles.to_vec(),
+            estimators,
         }
     }
 
@@ -46,6 +114,15 @@ impl ParallelStats {
                 self.update_single(i, values[i]);
             }
         }
+
+        if !self.quantiles.is_empty() {
+            let series = self.estimators.chunks_exact_mut(self.quantiles.len());
+            for (estimators, &value) in series.zip(values) {
+                for estimator in estimators {
+                    estimator.add(value);
+                }
+            }
+        }
     }
 
     fn simd_update(&mut self, chunk_start: usize, chunk_end: usize, values: &[f64]) {
@@ -148,6 +225,64 @@ impl ParallelStats {
             None
         }
     }
+
+    /// Combines the statistics of `other` into `self` as if every update of `other` had also been
+    /// applied to `self`. This allows statistics to be collected on multiple threads and then
+    /// aggregated.
+    ///
+    /// Quantile estimates cannot be combined, so the estimates of `other` are only used for series
+    /// which are empty in `self`.
+    ///
+    /// # Panics
+    /// If `other` has a different width or tracks different quantiles.
+    pub fn merge(&mut self, other: &Self) {
+        assert_eq!(self.width, other.width, "Widths must match");
+        assert_eq!(self.quantiles, other.quantiles, "Quantiles must match");
+
+        let quantiles = self.quantiles.len();
+
+        for idx in 0..self.width {
+            let count_b = other.counts[idx];
+            if count_b == 0 {
+                continue;
+            }
+
+            let count_a = self.counts[idx];
+            if count_a == 0 {
+                self.counts[idx] = count_b;
+                self.means[idx] = other.means[idx];
+                self.m2s[idx] = other.m2s[idx];
+                self.mins[idx] = other.mins[idx];
+                self.maxs[idx] = other.maxs[idx];
+                self.estimators[idx * quantiles..(idx + 1) * quantiles]
+                    .clone_from_slice(&other.estimators[idx * quantiles..(idx + 1) * quantiles]);
+                continue;
+            }
+
+            // Parallel combination of Welford's algorithm (Chan et al.)
+            let count = count_a + count_b;
+            let (n_a, n_b, n) = (count_a as f64, count_b as f64, count as f64);
+            let delta = other.means[idx] - self.means[idx];
+
+            self.means[idx] += delta * n_b / n;
+            self.m2s[idx] += (delta * delta).mul_add(n_a * n_b / n, other.m2s[idx]);
+            self.counts[idx] = count;
+            self.mins[idx] = self.mins[idx].min(other.mins[idx]);
+            self.maxs[idx] = self.maxs[idx].max(other.maxs[idx]);
+        }
+    }
+
+    /// The estimated `quantile` of a series. Returns `None` if the series is empty or if
+    /// `quantile` was not passed to [`ParallelStats::with_quantiles`].
+    #[must_use]
+    pub fn quantile(&self, idx: usize, quantile: f64) -> Option<f64> {
+        let position = self
+            .quantiles
+            .iter()
+            .position(|&tracked| (tracked - quantile).abs() < f64::EPSILON)?;
+
+        self.estimators[idx * self.quantiles.len() + position].estimate()
+    }
 }
 
 #[cfg(test)]
@@ -211,4 +346,82 @@ mod tests {
             assert_relative_eq!(stats.mean(i).unwrap(), (i + 1) as f64);
         }
     }
+
+    #[test]
+    fn test_quantiles() {
+        let mut stats = ParallelStats::with_quantiles(5, &[0.5, 0.95]);
+
+        for i in 0..1000 {
+            let value = f64::from(i);
+            stats.update(&[value, value * 2.0, value, value, -value]);
+        }
+
+        assert_relative_eq!(stats.quantile(0, 0.5).unwrap(), 500.0, epsilon = 10.0);
+        assert_relative_eq!(stats.quantile(1, 0.95).unwrap(), 1900.0, epsilon = 20.0);
+        assert_relative_eq!(stats.quantile(4, 0.5).unwrap(), -500.0, epsilon = 10.0);
+        assert_eq!(stats.quantile(0, 0.99), None);
+
+        let stats = ParallelStats::new(1);
+        assert_eq!(stats.quantile(0, 0.5), None);
+    }
+
+    #[test]
+    fn test_merge() {
+        let values = [[1.0, 10.0], [2.0, 20.0], [3.0, 30.0], [4.0, 40.0], [
+            5.0, 50.0,
+        ]];
+
+        let mut all = ParallelStats::new(2);
+        let mut a = ParallelStats::new(2);
+        let mut b = ParallelStats::new(2);
+
+        for (i, update) in values.iter().enumerate() {
+            all.update(update);
+            if i < 2 {
+                a.update(update);
+            } else {
+                b.update(update);
+            }
+        }
+
+        a.merge(&b);
+
+        for idx in 0..2 {
+            assert_eq!(a.count(idx), all.count(idx));
+            assert_relative_eq!(a.mean(idx).unwrap(), all.mean(idx).unwrap());
+            assert_relative_eq!(a.variance(idx).unwrap(), all.variance(idx).unwrap());
+            assert_relative_eq!(a.min(idx).unwrap(), all.min(idx).unwrap());
+            assert_relative_eq!(a.max(idx).unwrap(), all.max(idx).unwrap());
+        }
+
+        // Merging into empty statistics copies the other statistics
+        let mut empty = ParallelStats::new(2);
+        empty.merge(&all);
+        assert_relative_eq!(empty.variance(1).unwrap(), all.variance(1).unwrap());
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let mut stats = ParallelStats::with_quantiles(2, &[0.5]);
+        for value in 0..10 {
+            stats.update(&[f64::from(value), 0.0]);
+        }
+
+        let json = serde_json::to_string(&stats).unwrap();
+        let restored: ParallelStats = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.count(0), 10);
+        assert_relative_eq!(restored.mean(0).unwrap(), stats.mean(0).unwrap());
+        assert_relative_eq!(restored.max(0).unwrap(), 9.0);
+        assert_eq!(restored.quantile(0, 0.5), stats.quantile(0, 0.5));
+
+        let json = serde_json::to_string(&ParallelStats::new(2)).unwrap();
+        let mut restored: ParallelStats = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.min(0), None);
+
+        // Empty series keep an infinite minimum and maximum so that the next update replaces them
+        restored.update(&[5.0, 6.0]);
+        assert_relative_eq!(restored.min(0).unwrap(), 5.0);
+        assert_relative_eq!(restored.max(1).unwrap(), 6.0);
+    }
 }
@@ -0,0 +1,224 @@
+//! Streaming quantile estimation using the P² algorithm.
+//!
+//! See Jain and Chlamtac, "The P² algorithm for dynamic calculation of quantiles and histograms
+//! without storing observations" (1985).
+
+use serde::{Deserialize, Serialize};
+
+/// Estimates a single quantile of a stream of values in constant memory.
+///
+/// The estimate is exact until five values have been added. After that, five markers are kept
+/// whose heights are adjusted with piecewise-parabolic interpolation as values are added.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct P2Quantile {
+    quantile: f64,
+    count: u64,
+    /// Marker heights
+    heights: [f64; 5],
+    /// Actual marker positions, which are whole numbers
+    positions: [f64; 5],
+    /// Desired marker positions
+    desired: [f64; 5],
+    /// How much the desired positions increase with every value
+    increments: [f64; 5],
+}
+
+impl P2Quantile {
+    /// Creates an estimator for `quantile`, which must be between 0 and 1.
+    #[must_use]
+    pub fn new(quantile: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&quantile),
+            "quantile must be between 0 and 1"
+        );
+
+        let p = quantile;
+
+        Self {
+            quantile,
+            count: 0,
+            heights: [0.0; 5],
+            positions: [1.0, 2.0, 3.0, 4.0, 5.0],
+            desired: [
+                1.0,
+                2.0f64.mul_add(p, 1.0),
+                4.0f64.mul_add(p, 1.0),
+                2.0f64.mul_add(p, 3.0),
+                5.0,
+            ],
+            increments: [0.0, p / 2.0, p, (1.0 + p) / 2.0, 1.0],
+        }
+    }
+
+    /// The quantile which is being estimated
+    #[must_use]
+    pub const fn quantile(&self) -> f64 {
+        self.quantile
+    }
+
+    /// The number of values which have been added
+    #[must_use]
+    pub const fn count(&self) -> u64 {
+        self.count
+    }
+
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "the count is only cast while it is less than 5"
+    )]
+    pub fn add(&mut self, value: f64) {
+        if self.count < 5 {
+            self.heights[self.count as usize] = value;
+            self.count += 1;
+
+            if self.count == 5 {
+                self.heights.sort_unstable_by(f64::total_cmp);
+            }
+
+            return;
+        }
+
+        self.count += 1;
+
+        // Find the cell containing the value, extending the outer markers if needed
+        let cell = if value < self.heights[0] {
+            self.heights[0] = value;
+            0
+        } else if value >= self.heights[4] {
+            self.heights[4] = value;
+            3
+        } else {
+            (1..4)
+                .find(|&i| value < self.heights[i])
+                .map_or(3, |i| i - 1)
+        };
+
+        for position in &mut self.positions[cell + 1..] {
+            *position += 1.0;
+        }
+
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments) {
+            *desired += increment;
+        }
+
+        // Move the middle markers towards their desired positions
+        for i in 1..4 {
+            let offset = self.desired[i] - self.positions[i];
+
+            let can_move_right = offset >= 1.0 && self.positions[i + 1] - self.positions[i] > 1.0;
+            let can_move_left = offset <= -1.0 && self.positions[i - 1] - self.positions[i] < -1.0;
+
+            if !can_move_right && !can_move_left {
+                continue;
+            }
+
+            let direction = offset.signum();
+            let parabolic = self.parabolic(i, direction);
+
+            self.heights[i] = if self.heights[i - 1] < parabolic && parabolic < self.heights[i + 1]
+            {
+                parabolic
+            } else {
+                self.linear(i, direction)
+            };
+
+            self.positions[i] += direction;
+        }
+    }
+
+    fn parabolic(&self, i: usize, direction: f64) -> f64 {
+        let q = &self.heights;
+        let n = &self.positions;
+
+        let left = (n[i] - n[i - 1] + direction) * (q[i + 1] - q[i]) / (n[i + 1] - n[i]);
+        let right = (n[i + 1] - n[i] - direction) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]);
+
+        (direction / (n[i + 1] - n[i - 1])).mul_add(left + right, q[i])
+    }
+
+    fn linear(&self, i: usize, direction: f64) -> f64 {
+        let q = &self.heights;
+        let n = &self.positions;
+
+        let neighbor = if direction > 0.0 { i + 1 } else { i - 1 };
+
+        q[i] + direction * (q[neighbor] - q[i]) / (n[neighbor] - n[i])
+    }
+
+    /// The estimated quantile, or `None` if no values have been added
+    #[must_use]
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "the count is less than 5 and the rank is between 0 and the count"
+    )]
+    pub fn estimate(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            1..5 => {
+                // Too few values have been added for the markers, so the exact quantile is used
+                let len = self.count as usize;
+                let mut values = self.heights;
+                let values = &mut values[..len];
+                values.sort_unstable_by(f64::total_cmp);
+
+                let rank = (self.quantile * (len - 1) as f64).round() as usize;
+                Some(values[rank])
+            }
+            _ => Some(self.heights[2]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    #[test]
+    fn test_few_values() {
+        let mut median = P2Quantile::new(0.5);
+        assert_eq!(median.estimate(), None);
+
+        for value in [3.0, 1.0, 2.0] {
+            median.add(value);
+        }
+
+        assert_relative_eq!(median.estimate().unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_uniform() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let mut estimators = [0.5, 0.95, 0.99].map(P2Quantile::new);
+
+        for _ in 0..100_000 {
+            let value = rng.random::<f64>() * 100.0;
+            for estimator in &mut estimators {
+                estimator.add(value);
+            }
+        }
+
+        for estimator in &estimators {
+            assert_relative_eq!(
+                estimator.estimate().unwrap(),
+                estimator.quantile() * 100.0,
+                epsilon = 1.0
+            );
+        }
+    }
+
+    #[test]
+    fn test_sorted_input() {
+        let mut p95 = P2Quantile::new(0.95);
+
+        for value in 0..1000 {
+            p95.add(f64::from(value));
+        }
+
+        assert_relative_eq!(p95.estimate().unwrap(), 950.0, epsilon = 10.0);
+    }
+}
@@ -0,0 +1,297 @@
+//! Statistics over recent values rather than every value since the statistics were created.
+
+use serde::{Deserialize, Serialize};
+
+/// How [`RollingStats`] forgets old values
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RollingMode {
+    /// Only the most recent `len` updates are included, with each update weighted equally. With
+    /// one update per tick, a window of `20 * 60` covers the last 60 seconds.
+    Window { len: usize },
+    /// Every update is included, with the weight of older updates decaying exponentially. `alpha`
+    /// is the weight of the newest update and must be between 0 and 1.
+    Ewma { alpha: f64 },
+}
+
+impl RollingMode {
+    /// An exponentially-weighted mode in which the weight of an update halves after `half_life`
+    /// more updates.
+    #[must_use]
+    pub fn ewma_with_half_life(half_life: f64) -> Self {
+        Self::Ewma {
+            alpha: 1.0 - 0.5_f64.powf(half_life.recip()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum State {
+    Window {
+        len: usize,
+        /// Ring buffer of the most recent updates, stored as rows with one column per series
+        values: Vec<f64>,
+        /// The number of rows in `values` which have been filled
+        filled: usize,
+        /// The row which the next update is written to
+        next: usize,
+        sums: Vec<f64>,
+        sums_sq: Vec<f64>,
+    },
+    Ewma {
+        alpha: f64,
+        means: Vec<f64>,
+        variances: Vec<f64>,
+        counts: Vec<u64>,
+    },
+}
+
+/// Tracks multiple parallel series like [`crate::ParallelStats`], but only over recent values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollingStats {
+    width: usize,
+    state: State,
+}
+
+impl RollingStats {
+    #[must_use]
+    pub fn new(width: usize, mode: RollingMode) -> Self {
+        let state = match mode {
+            RollingMode::Window { len } => {
+                assert!(len > 0, "window length must be greater than 0");
+                State::Window {
+                    len,
+                    values: vec![0.0; len * width],
+                    filled: 0,
+                    next: 0,
+                    sums: vec![0.0; width],
+                    sums_sq: vec![0.0; width],
+                }
+            }
+            RollingMode::Ewma { alpha } => {
+                assert!(
+                    alpha > 0.0 && alpha <= 1.0,
+                    "alpha must be greater than 0 and at most 1"
+                );
+                State::Ewma {
+                    alpha,
+                    means: vec![0.0; width],
+                    variances: vec![0.0; width],
+                    counts: vec![0; width],
+                }
+            }
+        };
+
+        Self { width, state }
+    }
+
+    /// Each slice must be the same length as width
+    pub fn update(&mut self, values: &[f64]) {
+        assert_eq!(values.len(), self.width, "Input length must match width");
+
+        let width = self.width;
+        if width == 0 {
+            return;
+        }
+
+        match &mut self.state {
+            State::Window {
+                len,
+                values: window,
+                filled,
+                next,
+                sums,
+                sums_sq,
+            } => {
+                let row = &mut window[*next * width..(*next + 1) * width];
+
+                for (i, (old, &new)) in row.iter_mut().zip(values).enumerate() {
+                    if *filled == *len {
+                        sums[i] -= *old;
+                        sums_sq[i] -= *old * *old;
+                    }
+                    sums[i] += new;
+                    sums_sq[i] += new * new;
+                    *old = new;
+                }
+
+                *filled = (*filled + 1).min(*len);
+                *next = (*next + 1) % *len;
+
+                // Recompute the sums whenever the ring buffer wraps around so that floating point
+                // errors from removing old values do not accumulate
+                if *next == 0 {
+                    sums.fill(0.0);
+                    sums_sq.fill(0.0);
+                    for row in window.chunks_exact(width) {
+                        for (i, &value) in row.iter().enumerate() {
+                            sums[i] += value;
+                            sums_sq[i] += value * value;
+                        }
+                    }
+                }
+            }
+            State::Ewma {
+                alpha,
+                means,
+                variances,
+                counts,
+            } => {
+                for (i, &value) in values.iter().enumerate() {
+                    counts[i] += 1;
+
+                    if counts[i] == 1 {
+                        means[i] = value;
+                        continue;
+                    }
+
+                    let delta = value - means[i];
+                    let increment = *alpha * delta;
+                    means[i] += increment;
+                    variances[i] = (1.0 - *alpha) * delta.mul_add(increment, variances[i]);
+                }
+            }
+        }
+    }
+
+    /// The number of updates included in the statistics. In [`RollingMode::Ewma`], this is the
+    /// number of updates since the statistics were created.
+    #[must_use]
+    pub fn count(&self, idx: usize) -> u64 {
+        match &self.state {
+            State::Window { filled, .. } => *filled as u64,
+            State::Ewma { counts, .. } => counts[idx],
+        }
+    }
+
+    #[must_use]
+    pub fn mean(&self, idx: usize) -> Option<f64> {
+        if self.count(idx) == 0 {
+            return None;
+        }
+
+        match &self.state {
+            State::Window { filled, sums, .. } => Some(sums[idx] / *filled as f64),
+            State::Ewma { means, .. } => Some(means[idx]),
+        }
+    }
+
+    #[must_use]
+    pub fn variance(&self, idx: usize) -> Option<f64> {
+        if self.count(idx) <= 1 {
+            return None;
+        }
+
+        match &self.state {
+            State::Window {
+                filled,
+                sums,
+                sums_sq,
+                ..
+            } => {
+                let n = *filled as f64;
+                let mean = sums[idx] / n;
+                let variance = mean.mul_add(-sums[idx], sums_sq[idx]) / (n - 1.0);
+
+                // Rounding errors may make the variance slightly negative when all values are equal
+                Some(variance.max(0.0))
+            }
+            State::Ewma { variances, .. } => Some(variances[idx]),
+        }
+    }
+
+    #[must_use]
+    pub fn std_dev(&self, idx: usize) -> Option<f64> {
+        self.variance(idx).map(f64::sqrt)
+    }
+
+    /// The minimum value in the window. This is always `None` in [`RollingMode::Ewma`].
+    #[must_use]
+    pub fn min(&self, idx: usize) -> Option<f64> {
+        self.window_values(idx)?.reduce(f64::min)
+    }
+
+    /// The maximum value in the window. This is always `None` in [`RollingMode::Ewma`].
+    #[must_use]
+    pub fn max(&self, idx: usize) -> Option<f64> {
+        self.window_values(idx)?.reduce(f64::max)
+    }
+
+    fn window_values(&self, idx: usize) -> Option<impl Iterator<Item = f64> + '_> {
+        match &self.state {
+            State::Window { values, filled, .. } => Some(
+                values
+                    .chunks_exact(self.width)
+                    .take(*filled)
+                    .map(move |row| row[idx]),
+            ),
+            State::Ewma { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_window() {
+        let mut stats = RollingStats::new(2, RollingMode::Window { len: 3 });
+        assert_eq!(stats.mean(0), None);
+
+        for value in [1.0, 2.0, 3.0, 4.0, 5.0] {
+            stats.update(&[value, -value]);
+        }
+
+        // Only 3.0, 4.0 and 5.0 remain in the window
+        assert_eq!(stats.count(0), 3);
+        assert_relative_eq!(stats.mean(0).unwrap(), 4.0);
+        assert_relative_eq!(stats.variance(0).unwrap(), 1.0);
+        assert_relative_eq!(stats.min(0).unwrap(), 3.0);
+        assert_relative_eq!(stats.max(0).unwrap(), 5.0);
+
+        assert_relative_eq!(stats.mean(1).unwrap(), -4.0);
+        assert_relative_eq!(stats.min(1).unwrap(), -5.0);
+    }
+
+    #[test]
+    fn test_window_partially_filled() {
+        let mut stats = RollingStats::new(1, RollingMode::Window { len: 10 });
+        stats.update(&[2.0]);
+        stats.update(&[4.0]);
+
+        assert_eq!(stats.count(0), 2);
+        assert_relative_eq!(stats.mean(0).unwrap(), 3.0);
+        assert_relative_eq!(stats.variance(0).unwrap(), 2.0);
+        assert_relative_eq!(stats.max(0).unwrap(), 4.0);
+    }
+
+    #[test]
+    fn test_ewma() {
+        let mut stats = RollingStats::new(1, RollingMode::Ewma { alpha: 0.5 });
+
+        stats.update(&[10.0]);
+        assert_relative_eq!(stats.mean(0).unwrap(), 10.0);
+        assert_eq!(stats.variance(0), None);
+
+        stats.update(&[20.0]);
+        assert_relative_eq!(stats.mean(0).unwrap(), 15.0);
+        assert_relative_eq!(stats.variance(0).unwrap(), 25.0);
+
+        // The mean converges to a constant input
+        for _ in 0..100 {
+            stats.update(&[0.0]);
+        }
+        assert_relative_eq!(stats.mean(0).unwrap(), 0.0, epsilon = 1e-9);
+        assert_eq!(stats.min(0), None);
+    }
+
+    #[test]
+    fn test_half_life() {
+        let RollingMode::Ewma { alpha } = RollingMode::ewma_with_half_life(1.0) else {
+            unreachable!();
+        };
+        assert_relative_eq!(alpha, 0.5);
+    }
+}
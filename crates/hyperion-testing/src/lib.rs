@@ -0,0 +1,326 @@
+//! End-to-end tests for plugins without the proxy or `rust-mc-bot`.
+//!
+//! A [`FakeClient`] speaks the real protocol to an [`App`] with
+//! [`HyperionCore`](hyperion::HyperionCore) in the same process. Packets sent by the client are
+//! decoded by the same systems as packets from a proxy, and the messages which the server sends
+//! to proxies are routed to clients by a [`FakeProxy`] in the same way as the proxy routes them.
+//!
+//! ```no_run
+//! use bevy::prelude::*;
+//! use hyperion::{HyperionCore, protocol::packets::play::GameJoinS2c};
+//! use hyperion_testing::{FakeClient, tick};
+//!
+//! let mut app = App::new();
+//! app.add_plugins(HyperionCore);
+//!
+//! let world = app.world_mut();
+//! let mut client = FakeClient::join(world, "Steve").unwrap();
+//! assert!(client.has_received::<GameJoinS2c<'static>>());
+//!
+//! client.move_to([0.0, 64.0, 1.0].into(), true).unwrap();
+//! tick(world);
+//! ```
+
+use anyhow::{Context, bail};
+use bevy::{app::FixedMain, prelude::*};
+use bytes::Bytes;
+use glam::{DVec3, Vec3};
+use hyperion::{
+    command_channel::CommandChannel,
+    net::{
+        ConnectionId, PROTOCOL_VERSION,
+        bandwidth::ConnectionBandwidth,
+        proxy::{despawn_connection, spawn_connection},
+    },
+};
+use hyperion_utils::EntityExt;
+use valence_protocol::{
+    BlockPos, Bounded, CompressionThreshold, DecodeBytes, Direction, Encode, Hand, Packet,
+    PacketDecoder, PacketEncoder, VarInt,
+    decode::PacketFrame,
+    packets::{
+        handshaking::{HandshakeC2s, handshake_c2s::HandshakeNextState},
+        login::{LoginCompressionS2c, LoginHelloC2s, LoginSuccessS2c},
+        play::{
+            GameJoinS2c, HandSwingC2s, LookAndOnGroundC2s, PlayerInteractBlockC2s,
+            PlayerInteractEntityC2s, PlayerPositionLookS2c, PositionAndOnGroundC2s,
+            TeleportConfirmC2s, player_interact_entity_c2s::EntityInteraction,
+        },
+    },
+};
+
+pub mod proxy;
+
+pub use proxy::FakeProxy;
+
+/// The most ticks which [`FakeClient::login`] and [`FakeClient::join`] wait for the server
+const MAX_WAIT_TICKS: usize = 100;
+
+/// Runs one tick of the server, including commands from async tasks, and then routes the messages
+/// which the server sent to the [`FakeProxy`]
+pub fn tick(world: &mut World) {
+    let command_channel = world.resource::<CommandChannel>().clone();
+    command_channel.apply(world);
+    FixedMain::run_fixed_main(world);
+
+    if world.contains_resource::<FakeProxy>() {
+        FakeProxy::route(world);
+    }
+}
+
+/// Whether the client has finished logging in. Packet ids are reused between states, so
+/// packets are only handled once the client knows which state they are in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    Login,
+    Play,
+}
+
+/// A client which is connected to the server through a [`FakeProxy`].
+///
+/// Like the vanilla client, the fake client enables compression when the server asks for it and
+/// confirms teleports. All other packets are kept until they are cleared with
+/// [`FakeClient::clear`].
+pub struct FakeClient {
+    connection_id: ConnectionId,
+    entity: Entity,
+    sender: packet_channel::Sender,
+    encoder: PacketEncoder,
+    decoder: PacketDecoder,
+    state: State,
+    received: Vec<PacketFrame>,
+}
+
+impl FakeClient {
+    /// Connects a client in the handshake state
+    pub fn connect(world: &mut World) -> Self {
+        if !world.contains_resource::<FakeProxy>() {
+            let proxy = FakeProxy::connect(world);
+            world.insert_resource(proxy);
+        }
+
+        let connection_id = world.resource_mut::<FakeProxy>().add_player();
+        let (sender, receiver) = packet_channel::channel(4096);
+        let entity = spawn_connection(
+            world,
+            connection_id,
+            receiver,
+            ConnectionBandwidth::default(),
+        );
+
+        Self {
+            connection_id,
+            entity,
+            sender,
+            encoder: PacketEncoder::new(),
+            decoder: PacketDecoder::new(),
+            state: State::Login,
+            received: Vec::new(),
+        }
+    }
+
+    /// Connects a client, logs in and waits until the player has joined the world
+    pub fn join(world: &mut World, username: &str) -> anyhow::Result<Self> {
+        let mut client = Self::connect(world);
+        client.login(world, username)?;
+        client.wait_for::<GameJoinS2c<'static>>(world)?;
+        Ok(client)
+    }
+
+    /// Logs in as an offline player and waits until the server has accepted the login
+    pub fn login(&mut self, world: &mut World, username: &str) -> anyhow::Result<()> {
+        self.send(&HandshakeC2s {
+            protocol_version: VarInt(PROTOCOL_VERSION),
+            server_address: Bounded("localhost"),
+            server_port: 25565,
+            next_state: HandshakeNextState::Login,
+        })?;
+        self.send(&LoginHelloC2s {
+            username: Bounded(username),
+            profile_id: None,
+        })?;
+
+        for _ in 0..MAX_WAIT_TICKS {
+            tick(world);
+            self.poll(world)?;
+
+            if self.state == State::Play {
+                return Ok(());
+            }
+        }
+
+        bail!("the server did not accept the login of {username}")
+    }
+
+    /// Runs ticks until a packet of type `P` is received
+    pub fn wait_for<P: Packet>(&mut self, world: &mut World) -> anyhow::Result<()> {
+        for _ in 0..MAX_WAIT_TICKS {
+            self.poll(world)?;
+
+            if self.has_received::<P>() {
+                return Ok(());
+            }
+
+            tick(world);
+        }
+
+        bail!("the server did not send {}", P::NAME)
+    }
+
+    #[must_use]
+    pub const fn connection_id(&self) -> ConnectionId {
+        self.connection_id
+    }
+
+    /// The entity of the player on the server
+    #[must_use]
+    pub const fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Sends a packet, which is decoded by the server on the next tick
+    pub fn send<P: Packet + Encode>(&mut self, packet: &P) -> anyhow::Result<()> {
+        self.encoder.append_packet(packet)?;
+        let bytes = self.encoder.take();
+
+        self.sender
+            .send(&bytes)
+            .map_err(|e| anyhow::anyhow!("failed to send {}: {e:?}", P::NAME))
+    }
+
+    /// Moves the player to `position`
+    pub fn move_to(&mut self, position: DVec3, on_ground: bool) -> anyhow::Result<()> {
+        self.send(&PositionAndOnGroundC2s {
+            position,
+            on_ground,
+        })
+    }
+
+    /// Turns the player to face `yaw` and `pitch`
+    pub fn look(&mut self, yaw: f32, pitch: f32, on_ground: bool) -> anyhow::Result<()> {
+        self.send(&LookAndOnGroundC2s {
+            yaw,
+            pitch,
+            on_ground,
+        })
+    }
+
+    /// Swings the main hand
+    pub fn swing(&mut self) -> anyhow::Result<()> {
+        self.send(&HandSwingC2s { hand: Hand::Main })
+    }
+
+    /// Left clicks an entity
+    pub fn attack(&mut self, target: Entity) -> anyhow::Result<()> {
+        self.send(&PlayerInteractEntityC2s {
+            entity_id: VarInt(target.minecraft_id()),
+            interact: EntityInteraction::Attack,
+            sneaking: false,
+        })
+    }
+
+    /// Right clicks a face of a block with the item in the main hand
+    pub fn use_item_on(&mut self, position: BlockPos, face: Direction) -> anyhow::Result<()> {
+        self.send(&PlayerInteractBlockC2s {
+            hand: Hand::Main,
+            position,
+            face,
+            cursor_pos: Vec3::splat(0.5),
+            head_inside_block: false,
+            sequence: VarInt(0),
+        })
+    }
+
+    /// Reads the packets which the server has sent since the last call
+    pub fn poll(&mut self, world: &mut World) -> anyhow::Result<()> {
+        FakeProxy::route(world);
+
+        let stream = self.connection_id.inner();
+        let bytes = world.resource_mut::<FakeProxy>().take_inbox(stream);
+        self.decoder.queue_bytes(bytes);
+
+        while let Some(frame) = self.decoder.try_next_packet()? {
+            self.handle(&frame)?;
+            self.received.push(frame);
+        }
+
+        Ok(())
+    }
+
+    fn handle(&mut self, frame: &PacketFrame) -> anyhow::Result<()> {
+        match self.state {
+            State::Login => {
+                if frame.id == LoginCompressionS2c::ID {
+                    let packet = decode::<LoginCompressionS2c>(frame)?;
+                    let threshold = CompressionThreshold(packet.threshold.0);
+                    self.decoder.set_compression(threshold);
+                    self.encoder.set_compression(threshold);
+                } else if frame.id == LoginSuccessS2c::ID {
+                    self.state = State::Play;
+                }
+            }
+            State::Play => {
+                if frame.id == PlayerPositionLookS2c::ID {
+                    let packet = decode::<PlayerPositionLookS2c>(frame)?;
+                    self.send(&TeleportConfirmC2s {
+                        teleport_id: packet.teleport_id,
+                    })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the server has disconnected this client
+    #[must_use]
+    pub fn is_disconnected(&self, world: &World) -> bool {
+        world
+            .get_resource::<FakeProxy>()
+            .is_none_or(|proxy| proxy.is_shutdown(self.connection_id.inner()))
+    }
+
+    /// Every packet received since the last call to [`FakeClient::clear`], in the order they were
+    /// received
+    #[must_use]
+    pub fn received(&self) -> &[PacketFrame] {
+        &self.received
+    }
+
+    /// Whether a packet of type `P` has been received
+    #[must_use]
+    pub fn has_received<P: Packet>(&self) -> bool {
+        self.received.iter().any(|frame| frame.id == P::ID)
+    }
+
+    /// Decodes every received packet of type `P`
+    pub fn received_packets<P: Packet + DecodeBytes>(&self) -> anyhow::Result<Vec<P>> {
+        self.received
+            .iter()
+            .filter(|frame| frame.id == P::ID)
+            .map(decode)
+            .collect()
+    }
+
+    /// Forgets the packets which have been received
+    pub fn clear(&mut self) {
+        self.received.clear();
+    }
+
+    /// Closes the connection in the same way as the proxy does when a player leaves
+    pub fn disconnect(self, world: &mut World) {
+        let stream = self.connection_id.inner();
+        world.resource_mut::<FakeProxy>().remove_player(stream);
+        despawn_connection(world, stream);
+    }
+}
+
+fn decode<P: Packet + DecodeBytes>(frame: &PacketFrame) -> anyhow::Result<P> {
+    let mut body = Bytes::copy_from_slice(&frame.body);
+    let packet =
+        P::decode_bytes(&mut body).with_context(|| format!("failed to decode {}", P::NAME))?;
+    if !body.is_empty() {
+        bail!("missed {} bytes while decoding {}", body.len(), P::NAME);
+    }
+    Ok(packet)
+}
@@ -0,0 +1,375 @@
+//! A proxy which routes the messages the server sends to proxies to fake clients in the same
+//! process.
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bytes::{Bytes, BytesMut};
+use glam::I16Vec2;
+use hyperion::{
+    net::{ConnectionId, ProxyId, proxy::connect_local_proxy},
+    simulation::RequestSubscribeChannelPackets,
+};
+use hyperion_proto::{ArchivedServerToProxyMessage, ChunkPosition};
+use hyperion_utils::EntityExt;
+use rkyv::util::AlignedVec;
+use rustc_hash::FxHashMap;
+use tokio::sync::mpsc::{UnboundedReceiver, error::TryRecvError};
+
+#[derive(Default)]
+struct FakePlayer {
+    /// Packets which have not been read by the client yet
+    inbox: BytesMut,
+    receive_broadcasts: bool,
+    chunk_position: Option<I16Vec2>,
+    /// The chunks which receive local broadcasts for this player
+    chunks: HashSet<I16Vec2>,
+    shutdown: bool,
+}
+
+struct FakeChannel {
+    pending: HashSet<u64>,
+    subscribed: HashSet<u64>,
+    unsubscribe_packets: Bytes,
+}
+
+/// The proxy which fake clients are connected through. This is added by
+/// [`FakeClient::connect`](crate::FakeClient::connect) when the first client connects.
+///
+/// Messages are only routed after a [`tick`](crate::tick) and when a client reads its packets, so
+/// routing happens at the same point in a test every time it runs.
+#[derive(Resource)]
+pub struct FakeProxy {
+    proxy_id: ProxyId,
+    rx: UnboundedReceiver<Bytes>,
+    next_stream: u64,
+    players: FxHashMap<u64, FakePlayer>,
+    channels: FxHashMap<u32, FakeChannel>,
+    /// The players which each channel is hidden from
+    hidden: FxHashMap<u32, HashSet<u64>>,
+}
+
+impl FakeProxy {
+    /// Connects a fake proxy to the server
+    pub fn connect(world: &mut World) -> Self {
+        let (proxy_id, rx) = connect_local_proxy(world);
+        Self {
+            proxy_id,
+            rx,
+            next_stream: 0,
+            players: FxHashMap::default(),
+            channels: FxHashMap::default(),
+            hidden: FxHashMap::default(),
+        }
+    }
+
+    #[must_use]
+    pub const fn proxy_id(&self) -> ProxyId {
+        self.proxy_id
+    }
+
+    pub(crate) fn add_player(&mut self) -> ConnectionId {
+        let stream = self.next_stream;
+        self.next_stream += 1;
+        self.players.insert(stream, FakePlayer::default());
+        ConnectionId::new(stream, self.proxy_id)
+    }
+
+    pub(crate) fn remove_player(&mut self, stream: u64) {
+        self.players.remove(&stream);
+        for channel in self.channels.values_mut() {
+            channel.pending.remove(&stream);
+            channel.subscribed.remove(&stream);
+        }
+    }
+
+    /// Takes the packets routed to a player
+    pub(crate) fn take_inbox(&mut self, stream: u64) -> BytesMut {
+        self.players
+            .get_mut(&stream)
+            .map(|player| player.inbox.split())
+            .unwrap_or_default()
+    }
+
+    /// Whether the server has disconnected a player
+    pub(crate) fn is_shutdown(&self, stream: u64) -> bool {
+        self.players
+            .get(&stream)
+            .is_none_or(|player| player.shutdown)
+    }
+
+    /// Routes every message which the server has sent since the last call
+    pub fn route(world: &mut World) {
+        world.resource_scope(|world, mut proxy: Mut<'_, Self>| {
+            let mut requested = Vec::new();
+
+            loop {
+                let buffer = match proxy.rx.try_recv() {
+                    Ok(buffer) => buffer,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        panic!("the server removed the fake proxy")
+                    }
+                };
+
+                // The message is prefixed by its length, and rkyv requires it to be aligned
+                let mut message = AlignedVec::<16>::new();
+                message.extend_from_slice(&buffer[size_of::<u64>()..]);
+
+                // SAFETY: The message was serialized by the server in this process
+                let message =
+                    unsafe { rkyv::access_unchecked::<ArchivedServerToProxyMessage<'_>>(&message) };
+
+                proxy.handle(message, &mut requested);
+            }
+
+            if !requested.is_empty() {
+                let events = requested
+                    .into_iter()
+                    .filter_map(|channel_id| Entity::from_id(channel_id, world).ok())
+                    .map(RequestSubscribeChannelPackets)
+                    .collect::<Vec<_>>();
+                world
+                    .resource_mut::<Events<RequestSubscribeChannelPackets>>()
+                    .send_batch(events);
+            }
+        });
+    }
+
+    fn unicast(&mut self, stream: u64, data: &[u8]) {
+        if let Some(player) = self.players.get_mut(&stream) {
+            player.inbox.extend_from_slice(data);
+        }
+    }
+
+    fn in_range(&self, center: I16Vec2, radius: i16) -> Vec<u64> {
+        self.players
+            .iter()
+            .filter(|(_, player)| {
+                player.chunk_position.is_some_and(|position| {
+                    (position.as_ivec2() - center.as_ivec2())
+                        .abs()
+                        .max_element()
+                        <= i32::from(radius)
+                })
+            })
+            .map(|(&stream, _)| stream)
+            .collect()
+    }
+
+    fn handle(&mut self, message: &ArchivedServerToProxyMessage<'_>, requested: &mut Vec<u32>) {
+        match message {
+            ArchivedServerToProxyMessage::UpdatePlayerPositions(message) => {
+                for player in self.players.values_mut() {
+                    player.chunk_position = None;
+                }
+
+                for (stream, position) in message.stream.iter().zip(message.positions.iter()) {
+                    let position = chunk_position(position);
+                    if let Some(player) = self.players.get_mut(&stream.to_native()) {
+                        player.chunk_position = Some(position);
+                    }
+                }
+            }
+            ArchivedServerToProxyMessage::AddChannel(message) => {
+                self.channels
+                    .insert(message.channel_id.to_native(), FakeChannel {
+                        pending: HashSet::new(),
+                        subscribed: HashSet::new(),
+                        unsubscribe_packets: Bytes::copy_from_slice(&message.unsubscribe_packets),
+                    });
+            }
+            ArchivedServerToProxyMessage::UpdateChannelPositions(message) => {
+                for update in message.updates.iter() {
+                    let channel_id = update.channel_id.to_native();
+                    let position = chunk_position(&update.position);
+                    let hidden = self.hidden.get(&channel_id);
+                    let in_range = self
+                        .in_range(position, update.radius.to_native())
+                        .into_iter()
+                        .filter(|stream| self.players[stream].receive_broadcasts)
+                        .filter(|stream| !hidden.is_some_and(|hidden| hidden.contains(stream)))
+                        .collect::<HashSet<_>>();
+
+                    let Some(channel) = self.channels.get_mut(&channel_id) else {
+                        continue;
+                    };
+                    let unsubscribed = channel
+                        .subscribed
+                        .difference(&in_range)
+                        .copied()
+                        .collect::<Vec<_>>();
+                    let unsubscribe_packets = channel.unsubscribe_packets.clone();
+
+                    let was_pending = !channel.pending.is_empty();
+                    channel
+                        .subscribed
+                        .retain(|stream| in_range.contains(stream));
+                    channel.pending.extend(
+                        in_range
+                            .iter()
+                            .filter(|stream| !channel.subscribed.contains(stream)),
+                    );
+                    if !was_pending && !channel.pending.is_empty() {
+                        requested.push(channel_id);
+                    }
+
+                    for stream in unsubscribed {
+                        self.unicast(stream, &unsubscribe_packets);
+                    }
+                }
+            }
+            ArchivedServerToProxyMessage::RemoveChannel(message) => {
+                self.hidden.remove(&message.channel_id.to_native());
+                let Some(channel) = self.channels.remove(&message.channel_id.to_native()) else {
+                    return;
+                };
+
+                for stream in channel.subscribed {
+                    self.unicast(stream, &channel.unsubscribe_packets);
+                }
+            }
+            ArchivedServerToProxyMessage::SubscribeChannelPackets(message) => {
+                let exclude = message.exclude.to_native();
+                let Some(channel) = self.channels.get_mut(&message.channel_id.to_native()) else {
+                    return;
+                };
+
+                let pending = std::mem::take(&mut channel.pending);
+                channel.subscribed.extend(&pending);
+
+                for stream in pending {
+                    if stream != exclude {
+                        self.unicast(stream, &message.data);
+                    }
+                }
+            }
+            ArchivedServerToProxyMessage::BroadcastGlobal(message) => {
+                let exclude = message.exclude.to_native();
+                let streams = self
+                    .players
+                    .iter()
+                    .filter(|&(&stream, player)| player.receive_broadcasts && stream != exclude)
+                    .map(|(&stream, _)| stream)
+                    .collect::<Vec<_>>();
+
+                for stream in streams {
+                    self.unicast(stream, &message.data);
+                }
+            }
+            ArchivedServerToProxyMessage::BroadcastLocal(message) => {
+                let exclude = message.exclude.to_native();
+                let center = chunk_position(&message.center);
+                let streams = self
+                    .players
+                    .iter()
+                    .filter(|&(&stream, player)| {
+                        player.chunks.contains(&center) && stream != exclude
+                    })
+                    .map(|(&stream, _)| stream)
+                    .collect::<Vec<_>>();
+
+                for stream in streams {
+                    self.unicast(stream, &message.data);
+                }
+            }
+            ArchivedServerToProxyMessage::UpdateChunkSubscriptions(message) => {
+                let Some(player) = self.players.get_mut(&message.stream.to_native()) else {
+                    return;
+                };
+
+                if message.clear {
+                    player.chunks.clear();
+                }
+
+                for chunk in message.unsubscribe.iter() {
+                    player.chunks.remove(&chunk_position(chunk));
+                }
+
+                player
+                    .chunks
+                    .extend(message.subscribe.iter().map(chunk_position));
+            }
+            ArchivedServerToProxyMessage::SetChannelSubscription(message) => {
+                let stream = message.stream.to_native();
+                let Some(channel) = self.channels.get_mut(&message.channel_id.to_native()) else {
+                    return;
+                };
+
+                let hidden = self
+                    .hidden
+                    .get(&message.channel_id.to_native())
+                    .is_some_and(|hidden| hidden.contains(&stream));
+
+                if message.subscribed {
+                    if !hidden {
+                        channel.subscribed.insert(stream);
+                    }
+                } else if channel.subscribed.remove(&stream) {
+                    let unsubscribe_packets = channel.unsubscribe_packets.clone();
+                    self.unicast(stream, &unsubscribe_packets);
+                }
+            }
+            ArchivedServerToProxyMessage::SetChannelHidden(message) => {
+                let channel_id = message.channel_id.to_native();
+                let stream = message.stream.to_native();
+
+                if !message.hidden {
+                    if let Some(hidden) = self.hidden.get_mut(&channel_id) {
+                        hidden.remove(&stream);
+                    }
+                    return;
+                }
+
+                self.hidden.entry(channel_id).or_default().insert(stream);
+
+                let Some(channel) = self.channels.get_mut(&channel_id) else {
+                    return;
+                };
+
+                channel.pending.remove(&stream);
+                if channel.subscribed.remove(&stream) {
+                    let unsubscribe_packets = channel.unsubscribe_packets.clone();
+                    self.unicast(stream, &unsubscribe_packets);
+                }
+            }
+            ArchivedServerToProxyMessage::BroadcastChannel(message) => {
+                let exclude = message.exclude.to_native();
+                let Some(channel) = self.channels.get(&message.channel_id.to_native()) else {
+                    return;
+                };
+
+                let streams = channel.subscribed.iter().copied().collect::<Vec<_>>();
+                for stream in streams {
+                    if stream != exclude {
+                        self.unicast(stream, &message.data);
+                    }
+                }
+            }
+            ArchivedServerToProxyMessage::Unicast(message) => {
+                self.unicast(message.stream.to_native(), &message.data);
+            }
+            ArchivedServerToProxyMessage::SetReceiveBroadcasts(message) => {
+                if let Some(player) = self.players.get_mut(&message.stream.to_native()) {
+                    player.receive_broadcasts = true;
+                }
+            }
+            ArchivedServerToProxyMessage::Shutdown(message) => {
+                if let Some(player) = self.players.get_mut(&message.stream.to_native()) {
+                    player.shutdown = true;
+                }
+            }
+            // Fake clients are always connected to this server, so messages about the network are
+            // ignored
+            ArchivedServerToProxyMessage::UpdateStatus(_)
+            | ArchivedServerToProxyMessage::UpdateWaitingRoom(_)
+            | ArchivedServerToProxyMessage::Transfer(_)
+            | ArchivedServerToProxyMessage::RegisterServer(_)
+            | ArchivedServerToProxyMessage::PlayerCount(_) => {}
+        }
+    }
+}
+
+fn chunk_position(position: &<ChunkPosition as rkyv::Archive>::Archived) -> I16Vec2 {
+    I16Vec2::new(position.x.to_native(), position.z.to_native())
+}
@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+use glam::DVec3;
+use hyperion::{
+    HyperionCore,
+    egress::player_join::Hidden,
+    net::{
+        Compose,
+        agnostic::chat,
+        packet_channel::{PacketChannel, PacketChannelExt},
+    },
+    protocol::packets::play::{ClearTitleS2c, GameJoinS2c, GameMessageS2c, PlayerSpawnS2c},
+    simulation::{Position, Uuid, packet_state},
+};
+use hyperion_testing::{FakeClient, tick};
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn join_move_and_leave() {
+    let mut app = App::new();
+    app.add_plugins(HyperionCore);
+
+    let world = app.world_mut();
+    let mut client = FakeClient::join(world, "Steve").unwrap();
+
+    assert!(client.has_received::<GameJoinS2c<'static>>());
+    assert!(world.get::<packet_state::Play>(client.entity()).is_some());
+
+    // Let the server receive the teleport confirmation before moving
+    tick(world);
+    let start = **world.get::<Position>(client.entity()).unwrap();
+
+    let destination = start.as_dvec3() + DVec3::new(0.5, 0.0, 0.0);
+    client.move_to(destination, true).unwrap();
+    tick(world);
+
+    assert_eq!(
+        **world.get::<Position>(client.entity()).unwrap(),
+        destination.as_vec3()
+    );
+
+    let player = client.entity();
+    client.disconnect(world);
+    assert!(world.get_entity(player).is_err());
+}
+
+#[test]
+#[serial]
+fn players_see_each_other() {
+    let mut app = App::new();
+    app.add_plugins(HyperionCore);
+
+    let world = app.world_mut();
+    let first = FakeClient::join(world, "Alice").unwrap();
+    let mut second = FakeClient::join(world, "Bob").unwrap();
+
+    // Players are spawned for each other once the proxy subscribes them to each other's channels
+    for _ in 0..5 {
+        tick(world);
+    }
+    second.poll(world).unwrap();
+
+    let uuid = world.get::<Uuid>(first.entity()).unwrap().0;
+    let spawns = second.received_packets::<PlayerSpawnS2c>().unwrap();
+    assert!(spawns.iter().any(|spawn| spawn.player_uuid == uuid));
+}
+
+#[test]
+#[serial]
+fn hidden_players_are_not_spawned() {
+    let mut app = App::new();
+    app.add_plugins(HyperionCore);
+
+    let world = app.world_mut();
+    let first = FakeClient::join(world, "Alice").unwrap();
+    world.entity_mut(first.entity()).insert(Hidden);
+    let mut second = FakeClient::join(world, "Bob").unwrap();
+
+    for _ in 0..5 {
+        tick(world);
+    }
+    second.poll(world).unwrap();
+
+    let uuid = world.get::<Uuid>(first.entity()).unwrap().0;
+    let spawns = second.received_packets::<PlayerSpawnS2c>().unwrap();
+    assert!(!spawns.iter().any(|spawn| spawn.player_uuid == uuid));
+
+    // The player is spawned once they are shown again
+    world.entity_mut(first.entity()).remove::<Hidden>();
+    for _ in 0..5 {
+        tick(world);
+    }
+    second.poll(world).unwrap();
+
+    let spawns = second.received_packets::<PlayerSpawnS2c>().unwrap();
+    assert!(spawns.iter().any(|spawn| spawn.player_uuid == uuid));
+}
+
+#[test]
+#[serial]
+fn packet_channel_subscriptions() {
+    let mut app = App::new();
+    app.add_plugins(HyperionCore);
+
+    let world = app.world_mut();
+    let mut member = FakeClient::join(world, "Alice").unwrap();
+    let mut outsider = FakeClient::join(world, "Bob").unwrap();
+
+    let channel = PacketChannel::new()
+        .with_unsubscribe_packet(world.resource::<Compose>(), ClearTitleS2c { reset: true })
+        .unwrap();
+    let channel = world.spawn(channel).id();
+    world.commands().entity(channel).subscribe(member.entity());
+    world.flush();
+
+    tick(world);
+    member.poll(world).unwrap();
+    outsider.poll(world).unwrap();
+    member.clear();
+    outsider.clear();
+
+    world
+        .resource::<Compose>()
+        .broadcast_channel(chat("only for the team"), channel.into())
+        .send()
+        .unwrap();
+    tick(world);
+    member.poll(world).unwrap();
+    outsider.poll(world).unwrap();
+
+    assert!(member.has_received::<GameMessageS2c<'static>>());
+    assert!(!outsider.has_received::<GameMessageS2c<'static>>());
+
+    // Despawning the channel unsubscribes the remaining players
+    world.despawn(channel);
+    tick(world);
+    member.poll(world).unwrap();
+
+    assert!(member.has_received::<ClearTitleS2c>());
+}
@@ -1,67 +1,431 @@
-use std::path::PathBuf;
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
 
-use anyhow::Context;
+use anyhow::{Context, bail};
 use bevy::prelude::*;
 use futures_util::stream::StreamExt;
+use reqwest::{
+    StatusCode,
+    header::{ETAG, IF_NONE_MATCH, IF_RANGE, RANGE},
+};
 use sha2::Digest;
 use tar::Archive;
-use tokio_util::io::{StreamReader, SyncIoBridge};
-use tracing::info;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
 
 use crate::AppId;
 
-pub fn cached_save<U: reqwest::IntoUrl + 'static>(
-    world: &World,
-    url: U,
-) -> impl Future<Output = anyhow::Result<PathBuf>> + 'static {
-    let project_dirs = world.resource::<AppId>();
+/// Options for [`cached_save`]
+#[derive(Clone, Debug, Default)]
+pub struct CacheOptions {
+    /// The expected SHA-256 of the archive in hex. The archive is not unpacked if it does not
+    /// match.
+    pub sha256: Option<String>,
+    /// Download and unpack the archive again even if the cached save is up to date
+    pub force_refresh: bool,
+}
+
+/// Where a save is loaded from
+#[derive(Debug, PartialEq, Eq)]
+enum Source {
+    /// A `.tar.gz` archive served over http or https
+    Remote(String),
+    /// A `.tar.gz` archive or an unpacked directory, given as a path or a `file://` url
+    Local(PathBuf),
+}
+
+impl Source {
+    fn parse(source: &str) -> Self {
+        if let Some(path) = source.strip_prefix("file://") {
+            return Self::Local(PathBuf::from(path));
+        }
 
-    let cache = project_dirs.cache_dir();
+        if source.starts_with("http://") || source.starts_with("https://") {
+            return Self::Remote(source.to_owned());
+        }
+
+        Self::Local(PathBuf::from(source))
+    }
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(sha2::Sha256::digest(bytes))
+}
 
+fn file_sha256(path: &Path) -> anyhow::Result<String> {
     let mut hasher = sha2::Sha256::new();
-    let url_str = url.as_str().to_string();
-    Digest::update(&mut hasher, url_str.as_bytes());
-    // Get the final hash result
-    let url_sha = hasher.finalize();
-    let url_sha = hex::encode(url_sha);
+    let mut file = File::open(path)?;
+    std::io::copy(&mut file, &mut hasher)?;
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// The file next to a cached save with the SHA-256 of the archive it was unpacked from
+fn cached_sha256_path(directory: &Path) -> PathBuf {
+    directory.with_extension("sha256")
+}
+
+/// The file next to a cached save with the `ETag` of the archive it was downloaded from
+fn cached_etag_path(directory: &Path) -> PathBuf {
+    directory.with_extension("etag")
+}
+
+/// Unpacks the `.tar.gz` archive at `archive` into `directory`, replacing anything already
+/// there. The archive is unpacked next to `directory` first, so `directory` only exists once the
+/// archive has been fully unpacked.
+fn unpack(archive: &Path, directory: &Path) -> anyhow::Result<()> {
+    let unpacking = directory.with_extension("unpacking");
+    if unpacking.exists() {
+        std::fs::remove_dir_all(&unpacking)?;
+    }
+
+    let reader = BufReader::new(File::open(archive)?);
+    let reader = flate2::read::GzDecoder::new(reader);
+
+    Archive::new(reader)
+        .unpack(&unpacking)
+        .context("failed to unpack archive")?;
+
+    if directory.exists() {
+        std::fs::remove_dir_all(directory)?;
+    }
 
-    let directory = cache.join(url_sha);
+    std::fs::rename(&unpacking, directory)?;
+
+    Ok(())
+}
+
+/// Unpacks `archive` into `directory` unless `directory` was already unpacked from the same
+/// archive, which is checked with the SHA-256 saved next to it. Returns an error without
+/// unpacking if the SHA-256 of `archive` is not `expected`.
+fn refresh(archive: &Path, directory: &Path, expected: Option<&str>) -> anyhow::Result<()> {
+    let actual = file_sha256(archive)?;
+
+    if let Some(expected) = expected
+        && !actual.eq_ignore_ascii_case(expected)
+    {
+        bail!("checksum mismatch for {archive:?}: expected {expected}, got {actual}");
+    }
+
+    let sha256_path = cached_sha256_path(directory);
+    let cached = std::fs::read_to_string(&sha256_path).ok();
+
+    if directory.exists() && cached.as_deref() == Some(actual.as_str()) {
+        return Ok(());
+    }
+
+    // The old SHA-256 is removed first so that a half unpacked save is never taken as up to date
+    if cached.is_some() {
+        std::fs::remove_file(&sha256_path)?;
+    }
+
+    unpack(archive, directory)?;
+    std::fs::write(&sha256_path, actual)?;
+
+    Ok(())
+}
+
+/// The result of [`download`]
+enum Download {
+    /// The file has not changed since it was downloaded with the `ETag` which was given
+    NotModified,
+    /// The file was saved, with its `ETag` if the server sent one
+    Saved { etag: Option<String> },
+}
+
+/// Downloads `url` to `archive` unless it still has the `ETag` `cached_etag`. An earlier partial
+/// download is resumed if the server supports range requests and the file has not changed since,
+/// which is checked with its `ETag`.
+async fn download(
+    url: &str,
+    archive: &Path,
+    cached_etag: Option<&str>,
+) -> anyhow::Result<Download> {
+    let partial = archive.with_extension("part");
+    let etag_path = archive.with_extension("etag");
+
+    let downloaded = tokio::fs::metadata(&partial)
+        .await
+        .map_or(0, |metadata| metadata.len());
+    let etag = tokio::fs::read_to_string(&etag_path).await.ok();
+
+    let client = reqwest::Client::new();
+    let get = || match cached_etag {
+        Some(cached_etag) => client.get(url).header(IF_NONE_MATCH, cached_etag),
+        None => client.get(url),
+    };
+
+    let mut request = get();
+    if let Some(etag) = etag.filter(|_| downloaded > 0) {
+        request = request
+            .header(RANGE, format!("bytes={downloaded}-"))
+            .header(IF_RANGE, etag);
+    }
+
+    let mut response = request
+        .send()
+        .await
+        .with_context(|| format!("failed to get {url}"))?;
+
+    if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial download does not fit the file, so it is downloaded again from the start
+        tokio::fs::remove_file(&partial).await?;
+        response = get()
+            .send()
+            .await
+            .with_context(|| format!("failed to get {url}"))?;
+    }
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Download::NotModified);
+    }
+
+    let response = response
+        .error_for_status()
+        .with_context(|| format!("failed to get {url}"))?;
+
+    let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+    if resumed {
+        info!("resuming download of {url} from {downloaded} bytes");
+    }
+
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(ToOwned::to_owned);
+
+    match &etag {
+        Some(etag) => tokio::fs::write(&etag_path, etag).await?,
+        None => {
+            // Without an ETag, a partial download can not be resumed safely
+            tokio::fs::remove_file(&etag_path).await.ok();
+        }
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&partial)
+        .await?;
+
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.with_context(|| format!("failed to download {url}"))?;
+        file.write_all(&chunk).await?;
+    }
+
+    file.flush().await?;
+    drop(file);
+
+    tokio::fs::rename(&partial, archive).await?;
+    tokio::fs::remove_file(&etag_path).await.ok();
+
+    Ok(Download::Saved { etag })
+}
+
+/// Returns the directory of a save, downloading and unpacking it into the cache if needed.
+///
+/// `source` is either an http(s) url or a local path (optionally as a `file://` url) of a
+/// `.tar.gz` archive, or a local directory which is used as is.
+///
+/// A cached save is only used while it is up to date. If [`CacheOptions::sha256`] is set, it is
+/// compared to the SHA-256 of the archive the save was unpacked from. Otherwise a download is
+/// revalidated with its `ETag`, and a local archive is hashed again. If a cached download can
+/// not be revalidated, for example because the server is unreachable, it is used as is.
+pub fn cached_save(
+    world: &World,
+    source: impl Into<String>,
+    options: CacheOptions,
+) -> impl Future<Output = anyhow::Result<PathBuf>> + 'static {
+    let cache = world.resource::<AppId>().cache_dir();
+    let source = source.into();
 
     async move {
-        if directory.exists() {
-            info!("using cached NewYork load");
-        } else {
-            // download
-            let response = reqwest::get(url)
+        let archive = match Source::parse(&source) {
+            Source::Local(path) if path.is_dir() => return Ok(path),
+            Source::Local(path) => {
+                if !path.exists() {
+                    bail!("save {path:?} does not exist");
+                }
+                Some(path)
+            }
+            Source::Remote(_) => None,
+        };
+
+        let directory = cache.join(sha256_hex(source.as_bytes()));
+
+        if options.force_refresh {
+            tokio::fs::remove_file(cached_sha256_path(&directory))
+                .await
+                .ok();
+        }
+
+        let cached_sha256 = if directory.exists() {
+            tokio::fs::read_to_string(cached_sha256_path(&directory))
                 .await
-                .with_context(|| format!("failed to get {url_str}"))?;
+                .ok()
+        } else {
+            None
+        };
 
-            let byte_stream = response.bytes_stream();
-            // Convert the byte stream into an AsyncRead
+        if let Some(expected) = &options.sha256
+            && cached_sha256
+                .as_ref()
+                .is_some_and(|cached| cached.eq_ignore_ascii_case(expected))
+        {
+            info!("using cached save for {source}");
+            return Ok(directory);
+        }
 
-            let reader = StreamReader::new(byte_stream.map(|result| {
-                result.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-            }));
+        // If a SHA-256 is given, it decides whether the cached save is up to date instead
+        let revalidate = cached_sha256.is_some() && options.sha256.is_none();
 
-            let directory = directory.clone();
-            let handle = tokio::task::spawn_blocking(move || {
-                let reader = SyncIoBridge::new(reader);
-                let reader = std::io::BufReader::new(reader);
-                let reader = flate2::read::GzDecoder::new(reader);
+        tokio::fs::create_dir_all(&cache).await?;
+
+        let (archive, downloaded, etag) = match archive {
+            Some(archive) => (archive, false, None),
+            None => {
+                let archive = directory.with_extension("tar.gz");
+
+                let cached_etag = if revalidate {
+                    tokio::fs::read_to_string(cached_etag_path(&directory))
+                        .await
+                        .ok()
+                } else {
+                    None
+                };
+
+                match download(&source, &archive, cached_etag.as_deref()).await {
+                    Ok(Download::NotModified) => {
+                        info!("using cached save for {source}");
+                        return Ok(directory);
+                    }
+                    Ok(Download::Saved { etag }) => (archive, true, etag),
+                    Err(e) if revalidate => {
+                        warn!(
+                            "failed to revalidate cached save for {source}, using it as is: {e:#}"
+                        );
+                        return Ok(directory);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        };
 
-                // Create the archive in the blocking context
-                let mut archive = Archive::new(reader);
+        let result = {
+            let archive = archive.clone();
+            let directory = directory.clone();
+            tokio::task::spawn_blocking(move || {
+                refresh(&archive, &directory, options.sha256.as_deref())
+            })
+            .await?
+        };
 
-                archive
-                    .unpack(&directory)
-                    .context("failed to unpack archive")?;
+        if downloaded {
+            // The archive is removed even if it is invalid so it is downloaded again next time
+            if let Err(e) = tokio::fs::remove_file(&archive).await {
+                warn!("failed to remove downloaded archive {archive:?}: {e}");
+            }
+        }
 
-                anyhow::Ok(())
-            });
+        result?;
 
-            handle.await??;
+        if downloaded {
+            // The ETag is only saved once the save is unpacked, so it always matches the save
+            let etag_path = cached_etag_path(&directory);
+            match etag {
+                Some(etag) => tokio::fs::write(&etag_path, etag).await?,
+                None => {
+                    tokio::fs::remove_file(&etag_path).await.ok();
+                }
+            }
         }
 
         Ok(directory)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_source_parse() {
+        assert_eq!(
+            Source::parse("https://example.com/map.tar.gz"),
+            Source::Remote("https://example.com/map.tar.gz".to_owned())
+        );
+        assert_eq!(
+            Source::parse("file:///maps/map.tar.gz"),
+            Source::Local(PathBuf::from("/maps/map.tar.gz"))
+        );
+        assert_eq!(
+            Source::parse("maps/map"),
+            Source::Local(PathBuf::from("maps/map"))
+        );
+    }
+
+    #[test]
+    fn test_refresh() {
+        let dir = std::env::temp_dir().join(format!("hyperion-cached-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let archive = dir.join("save.tar.gz");
+        {
+            let file = File::create(&archive).unwrap();
+            let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            let mut builder = tar::Builder::new(encoder);
+
+            let contents = b"hello";
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, "level.dat", &contents[..])
+                .unwrap();
+
+            builder
+                .into_inner()
+                .unwrap()
+                .finish()
+                .unwrap()
+                .flush()
+                .unwrap();
+        }
+
+        let sha256 = sha256_hex(&std::fs::read(&archive).unwrap());
+        let directory = dir.join("save");
+
+        assert!(refresh(&archive, &directory, Some("00")).is_err());
+        assert!(!directory.exists());
+
+        refresh(&archive, &directory, Some(&sha256.to_uppercase())).unwrap();
+        assert_eq!(
+            std::fs::read(directory.join("level.dat")).unwrap(),
+            b"hello"
+        );
+        assert_eq!(
+            std::fs::read_to_string(cached_sha256_path(&directory)).unwrap(),
+            sha256
+        );
+
+        // The save is not unpacked again from the same archive
+        std::fs::write(directory.join("level.dat"), b"changed").unwrap();
+        refresh(&archive, &directory, None).unwrap();
+        assert_eq!(
+            std::fs::read(directory.join("level.dat")).unwrap(),
+            b"changed"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,3 +1,5 @@
+#![feature(let_chains)]
+
 mod cached_save;
 pub mod iterator;
 pub mod prev;
@@ -7,7 +9,7 @@ use bevy::{
     ecs::system::{SystemParam, SystemState},
     prelude::*,
 };
-pub use cached_save::cached_save;
+pub use cached_save::{CacheOptions, cached_save};
 pub use prev::{Prev, track_prev};
 
 pub trait EntityExt: Sized {
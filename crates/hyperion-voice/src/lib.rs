@@ -0,0 +1,294 @@
+//! Handshake of the Simple Voice Chat mod.
+//!
+//! Clients with the mod send their protocol version on the `voicechat:request_secret` plugin
+//! channel once they join. Clients with a compatible version are sent a random secret and the
+//! [`VoiceSettings`] on the `voicechat:secret` channel, after which they connect to the voice
+//! server over UDP.
+//!
+//! The voice server is not part of Hyperion because neither the server nor the proxy handle UDP.
+//! It reads [`VoicePeers`] to authenticate players by their secret and to relay their voice to
+//! the players who can hear them.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use bevy::prelude::*;
+use hyperion::{
+    glam::Vec3,
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{Position, Uuid, packet::play, packet_state},
+    timings::timed,
+    uuid,
+    valence_ident::ident,
+};
+use tracing::error;
+use valence_bytes::CowBytes;
+use valence_protocol::{Encode, RawBytes, packets::play::CustomPayloadS2c};
+
+/// The version of the Simple Voice Chat network protocol which is supported
+pub const COMPATIBILITY_VERSION: i32 = 18;
+
+/// The Opus profile clients encode voice with
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Codec {
+    #[default]
+    Voip,
+    Audio,
+    RestrictedLowDelay,
+}
+
+/// What clients are told about the voice server
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct VoiceSettings {
+    /// The host of the voice server. Clients use the address of the Minecraft server if this is
+    /// empty.
+    pub host: String,
+    pub port: u16,
+    /// How far away players can be heard, in blocks
+    pub distance: f64,
+    pub codec: Codec,
+    /// The largest UDP packet sent, in bytes
+    pub mtu: i32,
+    /// How often the voice server sends keep alive packets, in milliseconds
+    pub keep_alive: i32,
+    /// Whether players may create voice groups
+    pub groups: bool,
+    pub allow_recording: bool,
+}
+
+impl Default for VoiceSettings {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: 24454,
+            distance: 48.0,
+            codec: Codec::default(),
+            mtu: 1024,
+            keep_alive: 1000,
+            groups: false,
+            allow_recording: false,
+        }
+    }
+}
+
+impl VoiceSettings {
+    /// The payload of the `voicechat:secret` plugin message
+    fn secret_payload(&self, secret: uuid::Uuid, player: uuid::Uuid) -> anyhow::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        secret.encode(&mut buf)?;
+        i32::from(self.port).encode(&mut buf)?;
+        player.encode(&mut buf)?;
+        (self.codec as u8).encode(&mut buf)?;
+        self.mtu.encode(&mut buf)?;
+        self.distance.encode(&mut buf)?;
+        self.keep_alive.encode(&mut buf)?;
+        self.groups.encode(&mut buf)?;
+        self.host.as_str().encode(&mut buf)?;
+        self.allow_recording.encode(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// A player who was sent a secret
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VoicePeer {
+    pub secret: uuid::Uuid,
+    pub position: Vec3,
+}
+
+/// The players who were sent a secret by their uuid. This is shared with the voice server, so it
+/// can be cloned and read from other threads.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct VoicePeers(Arc<RwLock<HashMap<uuid::Uuid, VoicePeer>>>);
+
+impl VoicePeers {
+    #[must_use]
+    pub fn get(&self, player: uuid::Uuid) -> Option<VoicePeer> {
+        self.0.read().unwrap().get(&player).copied()
+    }
+
+    /// The players within `distance` blocks of `speaker`, who can hear them
+    #[must_use]
+    pub fn listeners(&self, speaker: uuid::Uuid, distance: f64) -> Vec<uuid::Uuid> {
+        let peers = self.0.read().unwrap();
+
+        let Some(speaker_peer) = peers.get(&speaker) else {
+            return Vec::new();
+        };
+
+        #[expect(
+            clippy::cast_possible_truncation,
+            reason = "the distance is at most a few hundred blocks"
+        )]
+        let distance_squared = (distance * distance) as f32;
+
+        peers
+            .iter()
+            .filter(|&(&player, peer)| {
+                player != speaker
+                    && peer.position.distance_squared(speaker_peer.position) <= distance_squared
+            })
+            .map(|(&player, _)| player)
+            .collect()
+    }
+
+    fn insert(&self, player: uuid::Uuid, peer: VoicePeer) {
+        self.0.write().unwrap().insert(player, peer);
+    }
+
+    fn remove(&self, player: uuid::Uuid) {
+        self.0.write().unwrap().remove(&player);
+    }
+}
+
+/// Marks players who were sent a secret
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VoiceClient;
+
+/// Clients only send plugin messages on channels which the server registered
+fn register_channels(
+    trigger: Trigger<'_, OnAdd, packet_state::Play>,
+    query: Query<'_, '_, &ConnectionId>,
+    compose: Res<'_, Compose>,
+) {
+    let Ok(&connection_id) = query.get(trigger.target()) else {
+        return;
+    };
+
+    let pkt = CustomPayloadS2c {
+        channel: ident!("minecraft:register"),
+        data: RawBytes::from(CowBytes::Borrowed(b"voicechat:request_secret")).into(),
+    };
+
+    if let Err(e) = compose.unicast(&pkt, connection_id) {
+        error!("failed to register voice chat channels: {e}");
+    }
+}
+
+fn handle_secret_requests(
+    mut packets: EventReader<'_, '_, play::CustomPayload>,
+    query: Query<'_, '_, (&Uuid, &Position)>,
+    settings: Res<'_, VoiceSettings>,
+    peers: Res<'_, VoicePeers>,
+    compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
+) {
+    for packet in packets.read() {
+        if packet.channel.as_str() != "voicechat:request_secret" {
+            continue;
+        }
+
+        let Ok((player, position)) = query.get(packet.sender()) else {
+            continue;
+        };
+
+        let data: &[u8] = &packet.data.0.0;
+        let version = data
+            .first_chunk::<4>()
+            .map(|&version| i32::from_be_bytes(version));
+
+        if version != Some(COMPATIBILITY_VERSION) {
+            let msg = agnostic::chat(
+                "§cYour version of Simple Voice Chat is not compatible with this server",
+            );
+            if let Err(e) = compose.unicast(&msg, packet.connection_id()) {
+                error!("failed to send voice secret: {e}");
+            }
+            continue;
+        }
+
+        let secret = uuid::Uuid::new_v4();
+
+        let payload = match settings.secret_payload(secret, player.0) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("failed to encode voice chat secret: {e}");
+                continue;
+            }
+        };
+
+        let pkt = CustomPayloadS2c {
+            channel: ident!("voicechat:secret"),
+            data: RawBytes::from(CowBytes::Borrowed(&payload)).into(),
+        };
+
+        if let Err(e) = compose.unicast(&pkt, packet.connection_id()) {
+            error!("failed to send voice chat secret: {e}");
+            continue;
+        }
+
+        peers.insert(player.0, VoicePeer {
+            secret,
+            position: **position,
+        });
+        commands.entity(packet.sender()).insert(VoiceClient);
+    }
+}
+
+fn sync_positions(
+    query: Query<'_, '_, (&Uuid, &Position), (With<VoiceClient>, Changed<Position>)>,
+    peers: Res<'_, VoicePeers>,
+) {
+    let mut peers = peers.0.write().unwrap();
+
+    for (uuid, position) in &query {
+        if let Some(peer) = peers.get_mut(&uuid.0) {
+            peer.position = **position;
+        }
+    }
+}
+
+fn remove_peer(
+    trigger: Trigger<'_, OnRemove, VoiceClient>,
+    query: Query<'_, '_, &Uuid>,
+    peers: Res<'_, VoicePeers>,
+) {
+    if let Ok(uuid) = query.get(trigger.target()) {
+        peers.remove(uuid.0);
+    }
+}
+
+/// Lets players with Simple Voice Chat connect to the voice server described by `settings`
+#[derive(Default)]
+pub struct VoicePlugin {
+    pub settings: VoiceSettings,
+}
+
+impl Plugin for VoicePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone());
+        app.init_resource::<VoicePeers>();
+        app.add_observer(register_channels);
+        app.add_observer(remove_peer);
+        app.add_systems(
+            FixedUpdate,
+            (timed(handle_secret_requests), timed(sync_positions)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listeners() {
+        let peers = VoicePeers::default();
+        let speaker = uuid::Uuid::from_u128(1);
+        let near = uuid::Uuid::from_u128(2);
+        let far = uuid::Uuid::from_u128(3);
+
+        for (player, x) in [(speaker, 0.0), (near, 10.0), (far, 100.0)] {
+            peers.insert(player, VoicePeer {
+                secret: uuid::Uuid::from_u128(0),
+                position: Vec3::new(x, 64.0, 0.0),
+            });
+        }
+
+        assert_eq!(peers.listeners(speaker, 48.0), vec![near]);
+        assert!(peers.listeners(uuid::Uuid::from_u128(4), 48.0).is_empty());
+    }
+}
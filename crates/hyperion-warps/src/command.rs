@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{Pitch, Position, Uuid, WorldSpawn, Yaw},
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+use crate::{Location, WarpExt, WarpStorage};
+
+/// The most homes a player may set
+const MAX_HOMES: usize = 5;
+
+/// The home used when no name is given
+const DEFAULT_HOME: &str = "home";
+
+type PlayerQuery = Query<
+    'static,
+    'static,
+    (
+        &'static ConnectionId,
+        &'static Uuid,
+        &'static Position,
+        &'static Yaw,
+        &'static Pitch,
+    ),
+>;
+
+type State = SystemState<(
+    PlayerQuery,
+    Res<'static, WarpStorage>,
+    Res<'static, Compose>,
+    Commands<'static, 'static>,
+)>;
+
+/// Runs `f` with the location of `caller` and sends them the message it returns. The command
+/// fails if `f` returns an error message.
+fn run(
+    name: &str,
+    world: &World,
+    state: &mut State,
+    caller: Entity,
+    f: impl FnOnce(&WarpStorage, &mut Commands<'_, '_>, &Uuid, Location) -> Result<String, String>,
+) -> CommandResult {
+    let (query, storage, compose, mut commands) = state.get(world);
+
+    let (&connection_id, uuid, position, yaw, pitch) = match query.get(caller) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("{name} command failed: query failed: {e}");
+            return Err(CommandFailed);
+        }
+    };
+
+    let location = Location::new(**position, **yaw, **pitch);
+    let (msg, result) = match f(&storage, &mut commands, uuid, location) {
+        Ok(msg) => (msg, Ok(())),
+        Err(msg) => (msg, Err(CommandFailed)),
+    };
+
+    if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+        error!("failed to send warp message: {e}");
+    }
+
+    result
+}
+
+fn names<T>(entries: impl IntoIterator<Item = (String, T)>) -> String {
+    entries
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "warp")]
+#[command_permission(group = "Normal")]
+pub struct WarpCommand {
+    /// The warp to teleport to. Lists the warps if not given.
+    name: Option<String>,
+}
+
+impl MinecraftCommand for WarpCommand {
+    type State = State;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        run("warp", world, state, caller, |storage, commands, _, _| {
+            let Some(name) = self.name else {
+                return match storage.warps.entries() {
+                    Ok(warps) if warps.is_empty() => Ok("§7There are no warps".to_owned()),
+                    Ok(warps) => Ok(format!("§6Warps: §e{}", names(warps))),
+                    Err(e) => Err(format!("§cFailed to read warps: {e}")),
+                };
+            };
+
+            match storage.warps.get(&name) {
+                Ok(Some(location)) => {
+                    commands.entity(caller).teleport_after_warmup(location);
+                    Ok(format!("§aWarping to {name}"))
+                }
+                Ok(None) => Err(format!("§cNo warp is named {name}")),
+                Err(e) => Err(format!("§cFailed to read warp {name}: {e}")),
+            }
+        })
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "setwarp")]
+#[command_permission(group = "Admin")]
+pub struct SetWarpCommand {
+    name: String,
+}
+
+impl MinecraftCommand for SetWarpCommand {
+    type State = State;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        run(
+            "setwarp",
+            world,
+            state,
+            caller,
+            |storage, _, _, location| {
+                let name = self.name;
+                match storage.warps.insert(&name, &location) {
+                    Ok(()) => Ok(format!("§aSet the warp {name} to your location")),
+                    Err(e) => Err(format!("§cFailed to save warp {name}: {e}")),
+                }
+            },
+        )
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "delwarp")]
+#[command_permission(group = "Admin")]
+pub struct DelWarpCommand {
+    name: String,
+}
+
+impl MinecraftCommand for DelWarpCommand {
+    type State = State;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        run("delwarp", world, state, caller, |storage, _, _, _| {
+            let name = self.name;
+            match storage.warps.remove(&name) {
+                Ok(true) => Ok(format!("§aDeleted the warp {name}")),
+                Ok(false) => Err(format!("§cNo warp is named {name}")),
+                Err(e) => Err(format!("§cFailed to delete warp {name}: {e}")),
+            }
+        })
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "home")]
+#[command_permission(group = "Normal")]
+pub struct HomeCommand {
+    /// The home to teleport to. Defaults to `home`.
+    name: Option<String>,
+}
+
+impl MinecraftCommand for HomeCommand {
+    type State = State;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        run(
+            "home",
+            world,
+            state,
+            caller,
+            |storage, commands, uuid, _| {
+                let homes = match storage.homes.get(&uuid.0.to_string()) {
+                    Ok(homes) => homes.unwrap_or_default(),
+                    Err(e) => return Err(format!("§cFailed to read your homes: {e}")),
+                };
+
+                let name = self.name.unwrap_or_else(|| DEFAULT_HOME.to_owned());
+
+                match homes.get(&name).copied() {
+                    Some(location) => {
+                        commands.entity(caller).teleport_after_warmup(location);
+                        Ok(format!("§aTeleporting to {name}"))
+                    }
+                    None if homes.is_empty() => {
+                        Err("§cYou have no homes, set one with /sethome".to_owned())
+                    }
+                    None => Err(format!(
+                        "§cNo home is named {name}. Your homes: §e{}",
+                        names(homes)
+                    )),
+                }
+            },
+        )
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "sethome")]
+#[command_permission(group = "Normal")]
+pub struct SetHomeCommand {
+    /// The name of the home. Defaults to `home`.
+    name: Option<String>,
+}
+
+impl MinecraftCommand for SetHomeCommand {
+    type State = State;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        run(
+            "sethome",
+            world,
+            state,
+            caller,
+            |storage, _, uuid, location| {
+                let name = self.name.unwrap_or_else(|| DEFAULT_HOME.to_owned());
+                let mut full = false;
+
+                let result = storage.homes.update(
+                    &uuid.0.to_string(),
+                    |homes: Option<BTreeMap<String, Location>>| {
+                        let mut homes = homes.unwrap_or_default();
+
+                        if homes.len() >= MAX_HOMES && !homes.contains_key(&name) {
+                            full = true;
+                        } else {
+                            homes.insert(name.clone(), location);
+                        }
+
+                        Some(homes)
+                    },
+                );
+
+                match result {
+                    Ok(_) if full => Err(format!("§cYou cannot set more than {MAX_HOMES} homes")),
+                    Ok(_) => Ok(format!("§aSet your home {name} to your location")),
+                    Err(e) => Err(format!("§cFailed to save home {name}: {e}")),
+                }
+            },
+        )
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "spawn")]
+#[command_permission(group = "Normal")]
+pub struct SpawnCommand;
+
+impl MinecraftCommand for SpawnCommand {
+    type State = State;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let spawn = *world.resource::<WorldSpawn>();
+
+        run("spawn", world, state, caller, |_, commands, _, _| {
+            commands.entity(caller).teleport_after_warmup(Location::new(
+                spawn.position,
+                spawn.yaw,
+                0.0,
+            ));
+            Ok("§aTeleporting to spawn".to_owned())
+        })
+    }
+}
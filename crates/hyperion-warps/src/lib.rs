@@ -0,0 +1,265 @@
+//! Teleport commands for warps, homes and the world spawn.
+//!
+//! Warps are shared by every player and set by admins, while each player sets their own homes.
+//! Both are saved in the [`LocalDb`]. Teleports start after a warm-up which is cancelled if the
+//! player moves or takes damage, so players cannot use them to escape a fight.
+//!
+//! Other plugins can teleport players with the same warm-up:
+//!
+//! ```ignore
+//! commands.entity(player).teleport_after_warmup(Location::new(Vec3::new(0.0, 64.0, 0.0), 0.0, 0.0));
+//! ```
+
+mod command;
+
+use std::{collections::BTreeMap, time::Duration};
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use hyperion::{
+    glam::Vec3,
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{PendingTeleportation, Pitch, Position, Yaw, metadata::living_entity::Health},
+    storage::{Bucket, LocalDb},
+    tick_rate::TickRate,
+    timings::timed,
+};
+use hyperion_clap::MinecraftCommand;
+use hyperion_scheduled::Scheduled;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::command::{
+    DelWarpCommand, HomeCommand, SetHomeCommand, SetWarpCommand, SpawnCommand, WarpCommand,
+};
+
+/// How far a player may move during a warm-up before it is cancelled, in blocks
+const MOVE_TOLERANCE: f32 = 0.1;
+
+/// A place which players can teleport to
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Location {
+    #[must_use]
+    pub const fn new(position: Vec3, yaw: f32, pitch: f32) -> Self {
+        Self {
+            position,
+            yaw,
+            pitch,
+        }
+    }
+}
+
+/// Where warps and homes are saved
+#[derive(Resource, Clone)]
+pub struct WarpStorage {
+    /// Warps keyed by name
+    pub warps: Bucket<Location>,
+    /// Homes keyed by the uuid of their player and then by name
+    pub homes: Bucket<BTreeMap<String, Location>>,
+}
+
+/// How long players have to stand still before they are teleported. It is converted to ticks at
+/// the current [`TickRate`] when each warm-up starts.
+#[derive(Resource, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Warmup(pub Duration);
+
+/// A teleport which happens once the warm-up ends at `tick`
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct PendingWarp {
+    pub destination: Location,
+    pub tick: i64,
+    /// Where the player was when the warm-up started
+    start: Vec3,
+    /// The health of the player when the warm-up started
+    health: f32,
+}
+
+/// The players with a [`PendingWarp`], keyed by the tick their warm-up ends
+#[derive(Resource, Default)]
+struct WarmupQueue(Scheduled<i64, Entity>);
+
+/// Teleports the target player to `destination` after the warm-up
+#[derive(Event, Copy, Clone, Debug, PartialEq)]
+pub struct TeleportAfterWarmup {
+    pub destination: Location,
+}
+
+pub trait WarpExt {
+    /// Teleports the player after the warm-up by triggering [`TeleportAfterWarmup`]
+    fn teleport_after_warmup(&mut self, destination: Location) -> &mut Self;
+}
+
+impl WarpExt for EntityCommands<'_> {
+    fn teleport_after_warmup(&mut self, destination: Location) -> &mut Self {
+        self.trigger(TeleportAfterWarmup { destination })
+    }
+}
+
+fn notify(compose: &Compose, connection_id: ConnectionId, msg: impl Into<String>) {
+    if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+        error!("failed to send warp message: {e}");
+    }
+}
+
+fn start_warmup(
+    trigger: Trigger<'_, TeleportAfterWarmup>,
+    query: Query<'_, '_, (&ConnectionId, &Position, Option<&Health>)>,
+    warmup: Res<'_, Warmup>,
+    tick_rate: Res<'_, TickRate>,
+    compose: Res<'_, Compose>,
+    mut queue: ResMut<'_, WarmupQueue>,
+    mut commands: Commands<'_, '_>,
+) {
+    let player = trigger.target();
+    let (&connection_id, position, health) = match query.get(player) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("failed to start teleport: query failed: {e}");
+            return;
+        }
+    };
+
+    let tick = compose.global().tick + tick_rate.ticks_in(warmup.0);
+
+    commands.entity(player).insert(PendingWarp {
+        destination: trigger.event().destination,
+        tick,
+        start: **position,
+        health: health.map_or(0.0, |health| **health),
+    });
+    queue.0.schedule(tick, player);
+
+    let seconds = warmup.0.as_secs_f32();
+    notify(
+        &compose,
+        connection_id,
+        format!("§eTeleporting in {seconds:.0} seconds, do not move"),
+    );
+}
+
+fn cancel_warmups(
+    query: Query<
+        '_,
+        '_,
+        (
+            Entity,
+            &ConnectionId,
+            &Position,
+            Option<&Health>,
+            &PendingWarp,
+        ),
+    >,
+    compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
+) {
+    for (player, &connection_id, position, health, pending) in &query {
+        let moved = position.distance(pending.start) > MOVE_TOLERANCE;
+        let damaged = health.is_some_and(|health| **health < pending.health);
+
+        if !moved && !damaged {
+            continue;
+        }
+
+        commands.entity(player).remove::<PendingWarp>();
+
+        let reason = if moved {
+            "you moved"
+        } else {
+            "you took damage"
+        };
+        notify(
+            &compose,
+            connection_id,
+            format!("§cTeleport cancelled because {reason}"),
+        );
+    }
+}
+
+fn finish_warmups(
+    mut queue: ResMut<'_, WarmupQueue>,
+    mut query: Query<'_, '_, (&mut Position, &mut Yaw, &mut Pitch, &PendingWarp)>,
+    compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
+) {
+    let tick = compose.global().tick;
+
+    for player in queue.0.pop_until(&tick) {
+        let Ok((mut position, mut yaw, mut pitch, pending)) = query.get_mut(player) else {
+            // The warm-up was cancelled or the player left
+            continue;
+        };
+
+        // A newer warm-up replaced this one
+        if pending.tick > tick {
+            continue;
+        }
+
+        let destination = pending.destination;
+        **position = destination.position;
+        **yaw = destination.yaw;
+        **pitch = destination.pitch;
+
+        commands
+            .entity(player)
+            .remove::<PendingWarp>()
+            .insert(PendingTeleportation::new(destination.position));
+    }
+}
+
+/// Adds `/warp`, `/setwarp`, `/delwarp`, `/home`, `/sethome` and `/spawn`. Teleports happen after
+/// `warmup`.
+pub struct WarpsPlugin {
+    pub warmup: Duration,
+}
+
+impl Default for WarpsPlugin {
+    fn default() -> Self {
+        Self {
+            warmup: Duration::from_secs(3),
+        }
+    }
+}
+
+impl Plugin for WarpsPlugin {
+    fn build(&self, app: &mut App) {
+        let db = app.world().resource::<LocalDb>();
+        let storage = WarpStorage {
+            warps: db.bucket("hyperion-warps").expect("failed to open warps"),
+            homes: db.bucket("hyperion-homes").expect("failed to open homes"),
+        };
+
+        app.insert_resource(storage);
+        app.insert_resource(Warmup(self.warmup));
+        app.init_resource::<WarmupQueue>();
+        app.add_observer(start_warmup);
+        app.add_systems(
+            FixedUpdate,
+            (timed(cancel_warmups), timed(finish_warmups)).chain(),
+        );
+
+        WarpCommand::register(app.world_mut());
+        SetWarpCommand::register(app.world_mut());
+        DelWarpCommand::register(app.world_mut());
+        HomeCommand::register(app.world_mut());
+        SetHomeCommand::register(app.world_mut());
+        SpawnCommand::register(app.world_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warmup_ticks() {
+        let warmup = Warmup(Duration::from_secs(3));
+        assert_eq!(TickRate::default().ticks_in(warmup.0), 60);
+        assert_eq!(TickRate::new(40.0, 10).ticks_in(warmup.0), 120);
+        assert_eq!(TickRate::new(5.0, 10).ticks_in(warmup.0), 15);
+    }
+}
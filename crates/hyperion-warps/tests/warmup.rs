@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use hyperion::{
+    HyperionCore,
+    glam::{DVec3, Vec3},
+    simulation::Position,
+    tick_rate::TickRate,
+};
+use hyperion_testing::{FakeClient, tick};
+use hyperion_warps::{Location, PendingWarp, TeleportAfterWarmup, WarpsPlugin};
+use serial_test::serial;
+
+const WARMUP: Duration = Duration::from_secs(1);
+
+/// Joins a player and starts a warm-up to 10 blocks away. Returns the client, where the player
+/// started and the number of ticks in the warm-up.
+fn start_warmup(world: &mut World) -> (FakeClient, Vec3, i64) {
+    let client = FakeClient::join(world, "Steve").unwrap();
+
+    // Let the server receive the teleport confirmation before the warm-up starts
+    tick(world);
+    let start = **world.get::<Position>(client.entity()).unwrap();
+
+    let destination = Location::new(start + Vec3::new(10.0, 0.0, 0.0), 0.0, 0.0);
+    world.trigger_targets(TeleportAfterWarmup { destination }, client.entity());
+    world.flush();
+
+    let ticks = world.resource::<TickRate>().ticks_in(WARMUP);
+    (client, start, ticks)
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins((HyperionCore, WarpsPlugin { warmup: WARMUP }));
+    app
+}
+
+#[test]
+#[serial]
+fn teleports_when_warmup_ends() {
+    let mut app = app();
+    let world = app.world_mut();
+    let (client, start, ticks) = start_warmup(world);
+
+    for _ in 0..ticks / 2 {
+        tick(world);
+    }
+
+    assert!(world.get::<PendingWarp>(client.entity()).is_some());
+    assert_eq!(**world.get::<Position>(client.entity()).unwrap(), start);
+
+    for _ in 0..=ticks {
+        tick(world);
+    }
+
+    assert!(world.get::<PendingWarp>(client.entity()).is_none());
+    assert_eq!(
+        **world.get::<Position>(client.entity()).unwrap(),
+        start + Vec3::new(10.0, 0.0, 0.0)
+    );
+}
+
+#[test]
+#[serial]
+fn moving_cancels_warmup() {
+    let mut app = app();
+    let world = app.world_mut();
+    let (mut client, start, ticks) = start_warmup(world);
+
+    tick(world);
+    assert!(world.get::<PendingWarp>(client.entity()).is_some());
+
+    let moved = start.as_dvec3() + DVec3::new(0.5, 0.0, 0.0);
+    client.move_to(moved, true).unwrap();
+    tick(world);
+
+    assert!(world.get::<PendingWarp>(client.entity()).is_none());
+
+    for _ in 0..=ticks {
+        tick(world);
+    }
+
+    assert_eq!(
+        **world.get::<Position>(client.entity()).unwrap(),
+        moved.as_vec3()
+    );
+}
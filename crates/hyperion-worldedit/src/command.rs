@@ -0,0 +1,339 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{BlockState, ItemStack, glam::IVec3, simulation::blocks::Blocks};
+use hyperion_clap::{CommandPermission, CommandResult, MinecraftCommand};
+use hyperion_inventory::PlayerInventory;
+use hyperion_schematic::{Mirror, Rotation, Schematic, Transform, parse_block_state};
+use tracing::error;
+
+use crate::{Edit, EditSession, MAX_EDIT_VOLUME, WAND, run_edit};
+
+type EditState = SystemState<Commands<'static, 'static>>;
+
+/// Runs an edit for `caller` once the world can be modified. Edits run after the command returns,
+/// so a command which queues an edit counts as run even if the edit itself is rejected.
+fn queue_edit(
+    world: &World,
+    state: &mut EditState,
+    caller: Entity,
+    f: impl FnOnce(&mut EditSession, &mut Blocks, IVec3) -> String + Send + 'static,
+) {
+    let mut commands = state.get(world);
+    commands.queue(move |world: &mut World| run_edit(world, caller, f));
+}
+
+fn parse_block(text: &str) -> Result<BlockState, String> {
+    parse_block_state(text).map_err(|e| e.to_string())
+}
+
+/// Returns the selection of the session, or a message explaining why it can not be edited
+fn selection(session: &EditSession) -> Result<(IVec3, IVec3), String> {
+    let Some((min, max)) = session.selection() else {
+        return Err("§cSelect both corners with the wand or //pos1 and //pos2".to_owned());
+    };
+
+    let size = (max - min + IVec3::ONE).as_i64vec3();
+    let volume = size.x * size.y * size.z;
+
+    if !usize::try_from(volume).is_ok_and(|volume| volume <= MAX_EDIT_VOLUME) {
+        return Err(format!(
+            "§cThe selection has {volume} blocks, but at most {MAX_EDIT_VOLUME} can be edited"
+        ));
+    }
+
+    Ok((min, max))
+}
+
+/// Returns every position in the cuboid from `min` to `max` (inclusive)
+fn cuboid(min: IVec3, max: IVec3) -> impl Iterator<Item = IVec3> {
+    (min.y..=max.y).flat_map(move |y| {
+        (min.z..=max.z).flat_map(move |z| (min.x..=max.x).map(move |x| IVec3::new(x, y, z)))
+    })
+}
+
+/// Applies `changes` as one edit and describes the result
+fn edit(
+    session: &mut EditSession,
+    blocks: &mut Blocks,
+    changes: impl IntoIterator<Item = (IVec3, BlockState)>,
+) -> String {
+    let mut edit = Edit::default();
+    let skipped = edit.apply(blocks, changes);
+    let changed = edit.changes.len();
+    session.record(edit);
+
+    if skipped > 0 {
+        format!("§d{changed} blocks changed§7, {skipped} blocks were not loaded")
+    } else {
+        format!("§d{changed} blocks changed")
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "/wand")]
+#[command_permission(group = "Admin")]
+pub struct WandCommand;
+
+impl MinecraftCommand for WandCommand {
+    type State = EditState;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let mut commands = state.get(world);
+        commands
+            .entity(caller)
+            .insert_if_new(EditSession::default())
+            .queue(|mut caller: EntityWorldMut<'_>| {
+                let Some(mut inventory) = caller.get_mut::<PlayerInventory>() else {
+                    error!("wand command failed: player is missing PlayerInventory component");
+                    return;
+                };
+
+                inventory.try_add_item(ItemStack {
+                    item: WAND,
+                    count: 1,
+                    nbt: None,
+                });
+            });
+
+        Ok(())
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "/pos1")]
+#[command_permission(group = "Admin")]
+pub struct Pos1Command;
+
+impl MinecraftCommand for Pos1Command {
+    type State = EditState;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        queue_edit(world, state, caller, |session, _, block| {
+            session.first = Some(block);
+            format!(
+                "§dFirst position set to ({}, {}, {})",
+                block.x, block.y, block.z
+            )
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "/pos2")]
+#[command_permission(group = "Admin")]
+pub struct Pos2Command;
+
+impl MinecraftCommand for Pos2Command {
+    type State = EditState;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        queue_edit(world, state, caller, |session, _, block| {
+            session.second = Some(block);
+            format!(
+                "§dSecond position set to ({}, {}, {})",
+                block.x, block.y, block.z
+            )
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "/set")]
+#[command_permission(group = "Admin")]
+pub struct SetCommand {
+    /// The block to set, such as `stone` or `oak_stairs[facing=north]`
+    #[arg(value_parser = parse_block)]
+    block: BlockState,
+}
+
+impl MinecraftCommand for SetCommand {
+    type State = EditState;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let block = self.block;
+
+        queue_edit(world, state, caller, move |session, blocks, _| {
+            let (min, max) = match selection(session) {
+                Ok(selection) => selection,
+                Err(msg) => return msg,
+            };
+
+            edit(
+                session,
+                blocks,
+                cuboid(min, max).map(|position| (position, block)),
+            )
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "/replace")]
+#[command_permission(group = "Admin")]
+pub struct ReplaceCommand {
+    /// Blocks of this kind are replaced, regardless of their properties
+    #[arg(value_parser = parse_block)]
+    from: BlockState,
+
+    #[arg(value_parser = parse_block)]
+    to: BlockState,
+}
+
+impl MinecraftCommand for ReplaceCommand {
+    type State = EditState;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let Self { from, to } = self;
+
+        queue_edit(world, state, caller, move |session, blocks, _| {
+            let (min, max) = match selection(session) {
+                Ok(selection) => selection,
+                Err(msg) => return msg,
+            };
+
+            let changes = cuboid(min, max)
+                .filter(|&position| {
+                    blocks
+                        .get_block(position)
+                        .is_some_and(|block| block.to_kind() == from.to_kind())
+                })
+                .map(|position| (position, to))
+                .collect::<Vec<_>>();
+
+            edit(session, blocks, changes)
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "/copy")]
+#[command_permission(group = "Admin")]
+pub struct CopyCommand;
+
+impl MinecraftCommand for CopyCommand {
+    type State = EditState;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        queue_edit(world, state, caller, |session, blocks, block| {
+            let (min, max) = match selection(session) {
+                Ok(selection) => selection,
+                Err(msg) => return msg,
+            };
+
+            let mut clipboard = Schematic::filled(max - min + IVec3::ONE, BlockState::AIR);
+            clipboard.set_offset(min - block);
+
+            for position in cuboid(min, max) {
+                if let Some(state) = blocks.get_block(position) {
+                    clipboard.set(position - min, state);
+                }
+            }
+
+            let size = clipboard.size();
+            session.set_clipboard(clipboard);
+
+            format!("§d{} blocks copied", size.x * size.y * size.z)
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "/paste")]
+#[command_permission(group = "Admin")]
+pub struct PasteCommand {
+    /// The clockwise rotation in degrees: 0, 90, 180 or 270
+    #[arg(long, default_value_t = 0)]
+    rotate: u16,
+
+    /// Keep the existing blocks where the clipboard has air
+    #[arg(long, short = 'a')]
+    ignore_air: bool,
+}
+
+impl MinecraftCommand for PasteCommand {
+    type State = EditState;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let Self { rotate, ignore_air } = self;
+
+        queue_edit(world, state, caller, move |session, blocks, block| {
+            let rotation = match rotate {
+                0 => Rotation::None,
+                90 => Rotation::Clockwise90,
+                180 => Rotation::Clockwise180,
+                270 => Rotation::Clockwise270,
+                _ => return "§cThe rotation must be 0, 90, 180 or 270".to_owned(),
+            };
+            let transform = Transform::new(rotation, Mirror::None);
+
+            let Some(clipboard) = session.clipboard().cloned() else {
+                return "§cYour clipboard is empty, copy a selection with //copy".to_owned();
+            };
+
+            let offset = clipboard.offset();
+            let changes = clipboard
+                .iter()
+                .filter(|(_, state)| !ignore_air || !state.is_air())
+                .map(|(position, state)| {
+                    (
+                        block + transform.apply(position + offset),
+                        transform.apply_state(state),
+                    )
+                });
+
+            edit(session, blocks, changes)
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "/undo")]
+#[command_permission(group = "Admin")]
+pub struct UndoCommand;
+
+impl MinecraftCommand for UndoCommand {
+    type State = EditState;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        queue_edit(world, state, caller, |session, blocks, _| {
+            match session.undo(blocks) {
+                Some(restored) => format!("§d{restored} blocks restored"),
+                None => "§cThere is nothing to undo".to_owned(),
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "/redo")]
+#[command_permission(group = "Admin")]
+pub struct RedoCommand;
+
+impl MinecraftCommand for RedoCommand {
+    type State = EditState;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        queue_edit(world, state, caller, |session, blocks, _| {
+            match session.redo(blocks) {
+                Some(changed) => format!("§d{changed} blocks changed"),
+                None => "§cThere is nothing to redo".to_owned(),
+            }
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,311 @@
+//! Selection and editing commands in the style of WorldEdit.
+//!
+//! Every player who uses the commands or the wand has an [`EditSession`], which stores their
+//! selection, clipboard and undo history.
+
+mod command;
+
+use std::{collections::VecDeque, sync::Arc};
+
+use bevy::prelude::*;
+use hyperion::{
+    BlockState, ItemKind,
+    glam::IVec3,
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{
+        Position,
+        blocks::Blocks,
+        event::{CancelEvents, Cancellable, DestroyBlock, StartDestroyBlock},
+        packet::play,
+    },
+    timings::timed,
+};
+use hyperion_clap::MinecraftCommand;
+use hyperion_inventory::PlayerInventory;
+use hyperion_schematic::Schematic;
+use tracing::error;
+
+use crate::command::{
+    CopyCommand, PasteCommand, Pos1Command, Pos2Command, RedoCommand, ReplaceCommand, SetCommand,
+    UndoCommand, WandCommand,
+};
+
+/// The item which selects corners when clicking blocks
+pub const WAND: ItemKind = ItemKind::WoodenAxe;
+
+/// The maximum number of blocks a single edit may change
+pub const MAX_EDIT_VOLUME: usize = 1 << 20;
+
+/// The maximum number of changed blocks each player keeps for undo and redo
+pub const MAX_HISTORY_BLOCKS: usize = 1 << 21;
+
+/// The blocks changed by an edit with the states they had before it
+#[derive(Default)]
+struct Edit {
+    changes: Vec<(IVec3, BlockState)>,
+}
+
+impl Edit {
+    /// Sets every block, recording the blocks which changed. Returns the number of blocks which
+    /// were not set because they are outside of the world or in a chunk which is not loaded.
+    fn apply(
+        &mut self,
+        blocks: &mut Blocks,
+        changes: impl IntoIterator<Item = (IVec3, BlockState)>,
+    ) -> usize {
+        let mut skipped = 0;
+
+        for (position, state) in changes {
+            match blocks.set_block(position, state) {
+                Ok(old) if old != state => self.changes.push((position, old)),
+                Ok(_) => {}
+                Err(_) => skipped += 1,
+            }
+        }
+
+        skipped
+    }
+
+    /// Restores the blocks changed by this edit, returning the edit which reverts the restore
+    fn revert(self, blocks: &mut Blocks) -> Self {
+        let mut inverse = Self::default();
+        inverse.apply(blocks, self.changes.into_iter().rev());
+        inverse
+    }
+}
+
+/// The selection, clipboard and history of a player
+#[derive(Component, Default)]
+pub struct EditSession {
+    pub first: Option<IVec3>,
+    pub second: Option<IVec3>,
+    clipboard: Option<Arc<Schematic>>,
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    /// The number of changed blocks in `undo` and `redo`
+    history_blocks: usize,
+}
+
+impl EditSession {
+    /// Returns the minimum and maximum corners of the selection if both corners are selected
+    #[must_use]
+    pub fn selection(&self) -> Option<(IVec3, IVec3)> {
+        let (first, second) = (self.first?, self.second?);
+        Some((first.min(second), first.max(second)))
+    }
+
+    /// The copied blocks, which are pasted relative to the player
+    #[must_use]
+    pub fn clipboard(&self) -> Option<&Arc<Schematic>> {
+        self.clipboard.as_ref()
+    }
+
+    pub fn set_clipboard(&mut self, clipboard: Schematic) {
+        self.clipboard = Some(Arc::new(clipboard));
+    }
+
+    /// Adds an edit to the undo history, dropping the oldest edits if the history is too large
+    fn record(&mut self, edit: Edit) {
+        if edit.changes.is_empty() {
+            return;
+        }
+
+        self.history_blocks -= self
+            .redo
+            .drain(..)
+            .map(|edit| edit.changes.len())
+            .sum::<usize>();
+        self.push_undo(edit);
+    }
+
+    fn push_undo(&mut self, edit: Edit) {
+        self.history_blocks += edit.changes.len();
+        self.undo.push_back(edit);
+
+        while self.history_blocks > MAX_HISTORY_BLOCKS {
+            let Some(oldest) = self.undo.pop_front() else {
+                break;
+            };
+            self.history_blocks -= oldest.changes.len();
+        }
+    }
+
+    /// Reverts the last edit. Returns the number of blocks which were restored.
+    fn undo(&mut self, blocks: &mut Blocks) -> Option<usize> {
+        let edit = self.undo.pop_back()?;
+        self.history_blocks -= edit.changes.len();
+
+        let inverse = edit.revert(blocks);
+        let restored = inverse.changes.len();
+
+        self.history_blocks += inverse.changes.len();
+        self.redo.push(inverse);
+
+        Some(restored)
+    }
+
+    /// Applies the last undone edit again. Returns the number of blocks which were changed.
+    fn redo(&mut self, blocks: &mut Blocks) -> Option<usize> {
+        let edit = self.redo.pop()?;
+        self.history_blocks -= edit.changes.len();
+
+        let inverse = edit.revert(blocks);
+        let changed = inverse.changes.len();
+        self.push_undo(inverse);
+
+        Some(changed)
+    }
+}
+
+/// Runs `f` with the session of `caller`, creating the session if needed, and sends the
+/// returned message to them
+fn run_edit(
+    world: &mut World,
+    caller: Entity,
+    f: impl FnOnce(&mut EditSession, &mut Blocks, IVec3) -> String,
+) {
+    let Some(position) = world.get::<Position>(caller) else {
+        error!("edit failed: player is missing Position component");
+        return;
+    };
+    let block = position.floor().as_ivec3();
+
+    let msg = world.resource_scope(|world, mut blocks: Mut<'_, Blocks>| {
+        let Ok(mut entity) = world.get_entity_mut(caller) else {
+            return None;
+        };
+
+        if !entity.contains::<EditSession>() {
+            entity.insert(EditSession::default());
+        }
+
+        let mut session = entity.get_mut::<EditSession>()?;
+        Some(f(&mut session, &mut blocks, block))
+    });
+
+    let (Some(msg), Some(&connection_id)) = (msg, world.get::<ConnectionId>(caller)) else {
+        return;
+    };
+
+    if let Err(e) = world
+        .resource::<Compose>()
+        .unicast(&agnostic::chat(msg), connection_id)
+    {
+        error!("failed to send worldedit message: {e}");
+    }
+}
+
+fn holds_wand(inventory: &PlayerInventory) -> bool {
+    inventory.get_cursor().stack.item == WAND
+}
+
+/// Selects corners when players with a session click blocks with the wand, and cancels breaking
+/// blocks with the wand
+fn wand_select(
+    mut start_events: EventReader<'_, '_, StartDestroyBlock>,
+    mut interact_packets: EventReader<'_, '_, play::PlayerInteractBlock>,
+    mut destroy_events: EventMutator<'_, '_, Cancellable<DestroyBlock>>,
+    mut query: Query<'_, '_, (&PlayerInventory, &mut EditSession, &ConnectionId)>,
+    compose: Res<'_, Compose>,
+) {
+    let mut select = |player: Entity, position: IVec3, first: bool| {
+        let Ok((inventory, mut session, &connection_id)) = query.get_mut(player) else {
+            return;
+        };
+
+        if !holds_wand(inventory) {
+            return;
+        }
+
+        let corner = if first {
+            session.first = Some(position);
+            "First"
+        } else {
+            session.second = Some(position);
+            "Second"
+        };
+
+        let msg = format!(
+            "§d{corner} position set to ({}, {}, {})",
+            position.x, position.y, position.z
+        );
+        if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+            error!("failed to send worldedit message: {e}");
+        }
+    };
+
+    for event in start_events.read() {
+        select(event.from, event.position, true);
+    }
+
+    for packet in interact_packets.read() {
+        let position = IVec3::new(packet.position.x, packet.position.y, packet.position.z);
+        select(packet.sender(), position, false);
+    }
+
+    for event in destroy_events.read() {
+        let holding = query
+            .get(event.from)
+            .is_ok_and(|(inventory, ..)| holds_wand(inventory));
+
+        if holding {
+            event.cancel();
+        }
+    }
+}
+
+pub struct WorldEditPlugin;
+
+impl Plugin for WorldEditPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, timed(wand_select).in_set(CancelEvents));
+
+        let world = app.world_mut();
+        WandCommand::register(world);
+        Pos1Command::register(world);
+        Pos2Command::register(world);
+        SetCommand::register(world);
+        ReplaceCommand::register(world);
+        CopyCommand::register(world);
+        PasteCommand::register(world);
+        UndoCommand::register(world);
+        RedoCommand::register(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut session = EditSession::default();
+
+        for i in 0..3 {
+            session.record(Edit {
+                changes: vec![(IVec3::new(i, 0, 0), BlockState::STONE); MAX_HISTORY_BLOCKS / 2],
+            });
+        }
+
+        assert_eq!(session.undo.len(), 2);
+        assert_eq!(session.history_blocks, MAX_HISTORY_BLOCKS);
+        assert_eq!(session.undo[0].changes[0].0, IVec3::new(1, 0, 0));
+
+        session.record(Edit::default());
+        assert_eq!(session.undo.len(), 2);
+    }
+
+    #[test]
+    fn test_selection() {
+        let session = EditSession {
+            first: Some(IVec3::new(5, -2, 3)),
+            second: Some(IVec3::new(-1, 4, 3)),
+            ..EditSession::default()
+        };
+
+        assert_eq!(
+            session.selection(),
+            Some((IVec3::new(-1, -2, 3), IVec3::new(5, 4, 3)))
+        );
+    }
+}
@@ -1,6 +1,11 @@
 //! Configuration for the server.
 
-use std::{fmt::Debug, fs::File, io::Read, path::Path};
+use std::{
+    fmt::Debug,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -14,7 +19,52 @@ pub struct Config {
     pub view_distance: i16,
     pub simulation_distance: i32,
     pub server_desc: String,
+    /// Ticks per second, which can be changed at runtime through
+    /// [`TickRate`](crate::tick_rate::TickRate)
+    #[serde(default = "default_tick_rate")]
+    pub tick_rate: f64,
+    /// The maximum number of ticks which are run back to back to catch up after a long tick.
+    /// Ticks beyond this are skipped instead of making the server fall further behind.
+    #[serde(default = "default_max_catch_up_ticks")]
+    pub max_catch_up_ticks: u32,
+    /// Whether offline players get the same UUIDs as on vanilla servers. This is off by default
+    /// because existing offline player data is stored under the UUIDs of earlier versions.
+    #[serde(default)]
+    pub vanilla_offline_uuids: bool,
+    /// What happens when a player joins with the username of a player who is already online
+    #[serde(default)]
+    pub duplicate_login: DuplicateLoginPolicy,
+    /// The Postgres database used for storage instead of the local database, which requires the
+    /// `postgres` feature
+    #[serde(default)]
+    pub database_url: Option<String>,
+    /// Where players spawn, which can be changed at runtime through
+    /// [`WorldSpawn`](crate::simulation::WorldSpawn)
     pub spawn: Spawn,
+    /// When idle players are marked as AFK and kicked
+    #[serde(default)]
+    pub afk: AfkConfig,
+    /// How this server is described to proxies which route players between several servers
+    #[serde(default)]
+    pub shard: ShardConfig,
+    /// How small entity movements may be before they are sent to other players
+    #[serde(default)]
+    pub movement_sync: MovementSyncConfig,
+    /// A directory to record every packet received from each player to, which is meant for
+    /// debugging. See [`capture`](crate::ingress::capture) for how recordings are replayed.
+    #[serde(default)]
+    pub capture_dir: Option<PathBuf>,
+    /// The Discord channel which chat is bridged to, which requires the `hyperion-bridge` plugin
+    #[serde(default)]
+    pub bridge: BridgeConfig,
+}
+
+const fn default_tick_rate() -> f64 {
+    20.0
+}
+
+const fn default_max_catch_up_ticks() -> u32 {
+    10
 }
 
 #[derive(Serialize, Deserialize, Debug, Component)]
@@ -24,9 +74,77 @@ pub struct Spawn {
     pub x: i32,
     pub y: i32,
     pub z: i32,
+    #[serde(default)]
+    pub yaw: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct AfkConfig {
+    /// Seconds without input before a player is marked as AFK
+    pub idle_secs: u64,
+    /// Seconds without input before a player is kicked. Idle players are never kicked if this is
+    /// not set.
+    pub kick_secs: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ShardConfig {
+    /// A name which identifies this server in the network, such as `lobby-1`
+    pub name: String,
+    /// The region which the server is in, such as `eu`
+    pub region: Option<String>,
+    /// The arena which the server hosts
+    pub arena: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+/// Where chat is bridged to on Discord. Messages are posted through the webhook if it is set and
+/// through the bot otherwise. Messages are only relayed back from Discord if the bot is set.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct BridgeConfig {
+    /// The URL of a Discord webhook which chat, joins, leaves and deaths are posted to
+    pub webhook_url: Option<String>,
+    /// The token of a Discord bot, which needs the message content intent to relay messages back
+    pub bot_token: Option<String>,
+    /// The Discord channel which is bridged to by the bot
+    pub channel_id: Option<String>,
+    /// How often the channel is checked for new messages, in seconds
+    pub poll_secs: u64,
+}
+
+/// Entity movement smaller than these thresholds is not sent until it adds up to more than the
+/// threshold or until the next forced sync. The defaults match vanilla.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct MovementSyncConfig {
+    /// The distance in blocks which an entity must move from the position last sent
+    pub min_position_delta: f32,
+    /// The angle in degrees which the yaw or pitch of an entity must change by from the rotation
+    /// last sent. Clients only see rotation in steps of 360 / 256 degrees.
+    pub min_rotation_delta: f32,
+    /// Every this many ticks, any movement below the thresholds is sent so that clients do not
+    /// stay slightly out of sync. Zero sends all movement.
+    pub force_sync_ticks: u32,
+}
+
+/// What happens when a player joins with the username of a player who is already online. Plugins
+/// can choose a different policy for each login through
+/// [`DuplicateLogin`](crate::simulation::event::DuplicateLogin).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateLoginPolicy {
+    /// Kick the player who is already online
+    #[default]
+    KickOld,
+    /// Disconnect the player who is joining
+    RejectNew,
+    /// Let the player join with a number appended to their username, such as `Steve_2`. This is
+    /// meant for offline mode, where anyone can join with any username.
+    Suffix,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Radius {
     Chebyshev,
     Euclidean,
@@ -40,7 +158,47 @@ impl Default for Config {
             view_distance: 32,
             simulation_distance: 10,
             server_desc: "Hyperion Test Server".to_owned(),
+            tick_rate: default_tick_rate(),
+            max_catch_up_ticks: default_max_catch_up_ticks(),
+            vanilla_offline_uuids: false,
+            duplicate_login: DuplicateLoginPolicy::default(),
+            database_url: None,
             spawn: Spawn::default(),
+            afk: AfkConfig::default(),
+            shard: ShardConfig::default(),
+            movement_sync: MovementSyncConfig::default(),
+            capture_dir: None,
+            bridge: BridgeConfig::default(),
+        }
+    }
+}
+
+impl Default for MovementSyncConfig {
+    fn default() -> Self {
+        Self {
+            min_position_delta: 0.0028,
+            min_rotation_delta: 360.0 / 256.0,
+            force_sync_ticks: 60,
+        }
+    }
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            bot_token: None,
+            channel_id: None,
+            poll_secs: 2,
+        }
+    }
+}
+
+impl Default for AfkConfig {
+    fn default() -> Self {
+        Self {
+            idle_secs: 5 * 60,
+            kick_secs: None,
         }
     }
 }
@@ -53,6 +211,7 @@ impl Default for Spawn {
             x: 0,
             y: 64,
             z: 0,
+            yaw: 0.0,
         }
     }
 }
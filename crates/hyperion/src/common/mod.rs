@@ -11,6 +11,10 @@ use valence_protocol::CompressionThreshold;
 pub mod command_channel;
 pub mod config;
 pub mod runtime;
+pub mod schedule_graph;
+pub mod tick_health;
+pub mod tick_rate;
+pub mod timings;
 pub mod util;
 
 /// Shared data that is shared between the ECS framework and the IO thread.
@@ -24,7 +28,7 @@ pub struct Shared {
 
 #[derive(Component)]
 pub struct Global {
-    /// The current tick of the game. This is incremented every 50 ms.
+    /// The current tick of the game. This is incremented every tick unless the game is frozen.
     pub tick: i64,
 
     /// The maximum amount of time a player is resistant to being hurt. This is weird as this is 20 in vanilla
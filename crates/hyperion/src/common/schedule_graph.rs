@@ -0,0 +1,300 @@
+//! Exports the systems of a schedule and the ordering constraints between them, which helps to
+//! find out why a system runs later than expected.
+//!
+//! ```ignore
+//! let schedule = app.get_schedule(FixedUpdate).unwrap();
+//! std::fs::write("fixed_update.dot", export_graph(schedule, GraphFormat::Dot))?;
+//! ```
+//!
+//! The dot output can be rendered with Graphviz, such as with `dot -Tsvg fixed_update.dot`.
+
+use std::{collections::VecDeque, fmt::Write};
+
+use bevy::{
+    ecs::schedule::{NodeId, ScheduleGraph, graph::Direction},
+    prelude::*,
+};
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+
+use crate::timings::short_name;
+
+/// The format returned by [`export_graph`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// A Graphviz digraph
+    Dot,
+    /// A [`ScheduleGraphExport`] serialized as json
+    Json,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    System,
+    SystemSet,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// `from` runs before `to`
+    Before,
+    /// `to` is part of the set `from`
+    InSet,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportedNode {
+    pub id: String,
+    pub name: String,
+    pub kind: NodeKind,
+    /// The length of the longest chain of systems and sets which must run before this node, or
+    /// `None` if the node is part of a dependency cycle
+    pub depth: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportedEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+}
+
+/// The systems and sets of a schedule along with the ordering constraints between them.
+///
+/// Each system is implicitly part of a set for its system type, which is used by constraints such
+/// as `.after(system)`. These sets are merged into their system.
+#[derive(Clone, Debug, Serialize)]
+pub struct ScheduleGraphExport {
+    pub schedule: String,
+    pub nodes: Vec<ExportedNode>,
+    pub edges: Vec<ExportedEdge>,
+}
+
+fn node_id(node: NodeId) -> String {
+    match node {
+        NodeId::System(idx) => format!("system:{idx}"),
+        NodeId::Set(idx) => format!("set:{idx}"),
+    }
+}
+
+/// Maps each system type set which contains exactly one system to that system.
+fn system_type_sets(graph: &ScheduleGraph) -> FxHashMap<NodeId, NodeId> {
+    graph
+        .system_sets()
+        .filter(|(_, set, _)| set.system_type().is_some())
+        .filter_map(|(id, ..)| {
+            let mut members = graph
+                .hierarchy()
+                .graph()
+                .neighbors_directed(id, Direction::Outgoing);
+            let system = members.next()?;
+            members.next().is_none().then_some((id, system))
+        })
+        .collect()
+}
+
+/// Computes the depth of every node with Kahn's algorithm. Being part of a set does not increase
+/// the depth, but a node is never shallower than the sets it is part of.
+fn depths(nodes: &[NodeId], edges: &[(NodeId, NodeId, EdgeKind)]) -> FxHashMap<NodeId, usize> {
+    let mut in_degree = nodes
+        .iter()
+        .map(|&node| (node, 0_usize))
+        .collect::<FxHashMap<_, _>>();
+    let mut outgoing = FxHashMap::<NodeId, Vec<(NodeId, usize)>>::default();
+
+    for &(from, to, kind) in edges {
+        let weight = usize::from(kind == EdgeKind::Before);
+        outgoing.entry(from).or_default().push((to, weight));
+        *in_degree.entry(to).or_default() += 1;
+    }
+
+    let mut depths = FxHashMap::default();
+    let mut queue = in_degree
+        .iter()
+        .filter(|(_, degree)| **degree == 0)
+        .map(|(&node, _)| node)
+        .collect::<VecDeque<_>>();
+
+    for &node in &queue {
+        depths.insert(node, 0);
+    }
+
+    while let Some(node) = queue.pop_front() {
+        let depth = depths[&node];
+
+        for &(next, weight) in outgoing.get(&node).into_iter().flatten() {
+            let next_depth = depths.entry(next).or_insert(0);
+            *next_depth = (*next_depth).max(depth + weight);
+
+            let degree = in_degree.get_mut(&next).unwrap();
+            *degree -= 1;
+            if *degree == 0 {
+                queue.push_back(next);
+            }
+        }
+    }
+
+    // Nodes in a cycle are never added to the queue, but they may have been given a partial depth
+    depths.retain(|node, _| in_degree[node] == 0);
+    depths
+}
+
+impl ScheduleGraphExport {
+    /// Collects the graph of `schedule`. This works both before and after the schedule has been
+    /// initialized.
+    #[must_use]
+    pub fn new(schedule: &Schedule) -> Self {
+        let graph = schedule.graph();
+        let merged = system_type_sets(graph);
+        let resolve = |node: NodeId| merged.get(&node).copied().unwrap_or(node);
+
+        let mut names = FxHashMap::default();
+        for (id, system, _) in graph.systems() {
+            names.insert(id, (system.name().to_string(), NodeKind::System));
+        }
+        for (id, set, _) in graph.system_sets() {
+            if merged.contains_key(&id) {
+                continue;
+            }
+            let name = if set.is_anonymous() {
+                "anonymous set".to_string()
+            } else {
+                format!("{set:?}")
+            };
+            names.insert(id, (name, NodeKind::SystemSet));
+        }
+
+        let mut edges = Vec::new();
+        for (from, to) in graph.dependency().graph().all_edges() {
+            let (from, to) = (resolve(from), resolve(to));
+            if from != to {
+                edges.push((from, to, EdgeKind::Before));
+            }
+        }
+        for (set, member) in graph.hierarchy().graph().all_edges() {
+            if !merged.contains_key(&set) {
+                edges.push((set, resolve(member), EdgeKind::InSet));
+            }
+        }
+        edges.sort_unstable();
+        edges.dedup();
+
+        let mut nodes = names.into_iter().collect::<Vec<_>>();
+        nodes.sort_unstable_by_key(|(id, _)| *id);
+
+        let ids = nodes.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        let depths = depths(&ids, &edges);
+
+        let nodes = nodes
+            .into_iter()
+            .map(|(id, (name, kind))| ExportedNode {
+                id: node_id(id),
+                name,
+                kind,
+                depth: depths.get(&id).copied(),
+            })
+            .collect();
+
+        let edges = edges
+            .into_iter()
+            .map(|(from, to, kind)| ExportedEdge {
+                from: node_id(from),
+                to: node_id(to),
+                kind,
+            })
+            .collect();
+
+        Self {
+            schedule: format!("{:?}", schedule.label()),
+            nodes,
+            edges,
+        }
+    }
+
+    /// Renders the graph as a Graphviz digraph in which systems are boxes and system sets are
+    /// dashed ellipses.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        fn escape(text: &str) -> String {
+            text.replace('\\', "\\\\").replace('"', "\\\"")
+        }
+
+        let mut out = String::new();
+        let _ = writeln!(out, "digraph \"{}\" {{", escape(&self.schedule));
+        let _ = writeln!(out, "    rankdir=LR;");
+
+        for node in &self.nodes {
+            let depth = node
+                .depth
+                .map_or_else(|| "in cycle".to_string(), |depth| format!("depth {depth}"));
+            let (label, shape) = match node.kind {
+                NodeKind::System => (short_name(&node.name), "shape=box"),
+                NodeKind::SystemSet => (node.name.clone(), "shape=ellipse, style=dashed"),
+            };
+            let _ = writeln!(
+                out,
+                "    \"{}\" [label=\"{}\\n{depth}\", {shape}];",
+                node.id,
+                escape(&label)
+            );
+        }
+
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Before => "",
+                EdgeKind::InSet => " [style=dashed]",
+            };
+            let _ = writeln!(out, "    \"{}\" -> \"{}\"{style};", edge.from, edge.to);
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Exports the systems and sets of `schedule` and the ordering constraints between them.
+#[must_use]
+pub fn export_graph(schedule: &Schedule, format: GraphFormat) -> String {
+    let export = ScheduleGraphExport::new(schedule);
+    match format {
+        GraphFormat::Dot => export.to_dot(),
+        GraphFormat::Json => {
+            serde_json::to_string_pretty(&export).expect("schedule graph is always serializable")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first() {}
+    fn second() {}
+    fn third() {}
+
+    #[test]
+    fn test_depths() {
+        let mut schedule = Schedule::new(Update);
+        schedule.add_systems((first, second.after(first), third.after(second)));
+
+        let export = ScheduleGraphExport::new(&schedule);
+
+        let depth = |name: &str| {
+            export
+                .nodes
+                .iter()
+                .find(|node| node.kind == NodeKind::System && node.name.ends_with(name))
+                .unwrap()
+                .depth
+        };
+
+        assert_eq!(depth("first"), Some(0));
+        assert_eq!(depth("second"), Some(1));
+        assert_eq!(depth("third"), Some(2));
+
+        let dot = export.to_dot();
+        assert!(dot.contains("label=\"third\\ndepth 2\""));
+    }
+}
@@ -0,0 +1,308 @@
+//! Tracks how long each tick takes to detect when the server is unable to keep up with the tick
+//! rate.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use tracing::warn;
+
+use crate::{net::Compose, tick_rate::TickRate};
+
+/// How long ticks are kept in [`TickHealth`], which is the longest window which can be averaged
+const RECENT_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+/// The default of [`TickHealth::overload_duration`]
+const DEFAULT_OVERLOAD_DURATION: Duration = Duration::from_secs(5);
+
+/// Sent when the server is overloaded.
+///
+/// This is sent when ticks have taken longer than [`TickHealth::budget`] for
+/// [`TickHealth::overload_threshold`] consecutive ticks. While the server stays overloaded, this is
+/// sent again every [`TickHealth::overload_threshold`] ticks.
+///
+/// Plugins may listen for this event to shed load, such as by lowering the view distance or
+/// pausing expensive systems.
+#[derive(Event, Copy, Clone, Debug, PartialEq)]
+pub struct ServerOverloaded {
+    /// The time the most recent tick took in milliseconds
+    pub mspt: f64,
+    /// The number of consecutive ticks which took longer than [`TickHealth::budget`]
+    pub consecutive_ticks: u32,
+}
+
+/// The average tick performance over a window of time.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TickAverage {
+    /// Milliseconds per tick
+    pub mspt: f64,
+    /// Ticks per second, which is at most the tick rate
+    pub tps: f64,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct TickSample {
+    start: Instant,
+    mspt: f64,
+}
+
+/// The durations of recent ticks.
+///
+/// A tick is measured from the start of [`FixedFirst`] to the end of [`FixedLast`].
+#[derive(Resource, Debug)]
+pub struct TickHealth {
+    /// How long ticks must exceed [`TickHealth::budget`] back to back before
+    /// [`ServerOverloaded`] is sent
+    pub overload_duration: Duration,
+    /// The time between the start of each tick, which is set by [`TickRate`]
+    budget: Duration,
+    /// The start of the tick which is currently running
+    current_start: Option<Instant>,
+    /// The ticks which started within [`RECENT_WINDOW`] of the most recent tick, from oldest to
+    /// newest
+    samples: VecDeque<TickSample>,
+    consecutive_overloaded: u32,
+}
+
+impl Default for TickHealth {
+    fn default() -> Self {
+        Self::from(&TickRate::default())
+    }
+}
+
+impl From<&TickRate> for TickHealth {
+    fn from(tick_rate: &TickRate) -> Self {
+        Self {
+            overload_duration: DEFAULT_OVERLOAD_DURATION,
+            budget: tick_rate.timestep(),
+            current_start: None,
+            samples: VecDeque::new(),
+            consecutive_overloaded: 0,
+        }
+    }
+}
+
+impl TickHealth {
+    /// The amount of time a tick may take without slowing down the tick rate
+    #[must_use]
+    pub const fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Updates [`TickHealth::budget`] to the timestep of `tick_rate`
+    pub fn set_tick_rate(&mut self, tick_rate: &TickRate) {
+        self.budget = tick_rate.timestep();
+    }
+
+    /// The number of consecutive ticks which must exceed [`TickHealth::budget`] before
+    /// [`ServerOverloaded`] is sent, which is the number of ticks in
+    /// [`TickHealth::overload_duration`]
+    #[must_use]
+    pub fn overload_threshold(&self) -> u32 {
+        let ticks = (self.overload_duration.as_secs_f64() / self.budget.as_secs_f64()).round();
+
+        #[expect(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            reason = "the cast saturates and the tick count is never negative"
+        )]
+        let ticks = ticks as u32;
+        ticks.max(1)
+    }
+
+    /// The time the most recent tick took in milliseconds, or `None` if no tick has finished yet
+    #[must_use]
+    pub fn last_mspt(&self) -> Option<f64> {
+        self.samples.back().map(|sample| sample.mspt)
+    }
+
+    /// The number of consecutive ticks up to and including the most recent tick which took longer
+    /// than [`TickHealth::budget`]
+    #[must_use]
+    pub const fn consecutive_overloaded(&self) -> u32 {
+        self.consecutive_overloaded
+    }
+
+    /// Whether the most recent [`TickHealth::overload_threshold`] ticks all took longer than
+    /// [`TickHealth::budget`]
+    #[must_use]
+    pub fn is_overloaded(&self) -> bool {
+        self.consecutive_overloaded >= self.overload_threshold()
+    }
+
+    /// The average over the ticks which started within `window` of the most recent tick. Windows
+    /// longer than 15 minutes are limited to 15 minutes. Returns `None` if no tick has finished
+    /// yet.
+    #[must_use]
+    pub fn average(&self, window: Duration) -> Option<TickAverage> {
+        let newest = self.samples.back()?;
+
+        let in_window = self
+            .samples
+            .iter()
+            .rev()
+            .take_while(|sample| newest.start.duration_since(sample.start) < window)
+            .collect::<Vec<_>>();
+
+        let oldest = in_window.last()?;
+        let ticks = in_window.len() as f64;
+
+        let mspt = in_window.iter().map(|sample| sample.mspt).sum::<f64>() / ticks;
+
+        // The time from the start of the oldest tick to the end of the newest tick
+        let elapsed =
+            newest.start.duration_since(oldest.start).as_secs_f64() + newest.mspt / 1000.0;
+
+        // A tick which takes less than the budget still occupies an entire tick interval
+        let budget = self.budget.as_secs_f64();
+        let elapsed = elapsed.max(ticks * budget);
+
+        let tps = (ticks / elapsed).min(budget.recip());
+
+        Some(TickAverage { mspt, tps })
+    }
+
+    /// Records a tick which started at `start` and took `duration`. Returns the event which should
+    /// be sent if the server is overloaded.
+    pub fn record(&mut self, start: Instant, duration: Duration) -> Option<ServerOverloaded> {
+        let mspt = duration.as_secs_f64() * 1000.0;
+
+        while self
+            .samples
+            .front()
+            .is_some_and(|oldest| start.duration_since(oldest.start) >= RECENT_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(TickSample { start, mspt });
+
+        if duration <= self.budget {
+            self.consecutive_overloaded = 0;
+            return None;
+        }
+
+        self.consecutive_overloaded += 1;
+
+        (self.consecutive_overloaded % self.overload_threshold() == 0).then_some(ServerOverloaded {
+            mspt,
+            consecutive_ticks: self.consecutive_overloaded,
+        })
+    }
+}
+
+fn start_tick(mut health: ResMut<'_, TickHealth>) {
+    health.current_start = Some(Instant::now());
+}
+
+fn end_tick(
+    mut health: ResMut<'_, TickHealth>,
+    mut compose: ResMut<'_, Compose>,
+    mut overloaded: EventWriter<'_, ServerOverloaded>,
+) {
+    let Some(start) = health.current_start.take() else {
+        return;
+    };
+
+    let duration = start.elapsed();
+
+    #[expect(clippy::cast_possible_truncation)]
+    {
+        compose.global_mut().ms_last_tick = (duration.as_secs_f64() * 1000.0) as f32;
+    }
+
+    if let Some(event) = health.record(start, duration) {
+        warn!(
+            "server is overloaded: {} consecutive ticks exceeded {} ms (last tick took {:.2} ms)",
+            event.consecutive_ticks,
+            health.budget().as_millis(),
+            event.mspt
+        );
+        overloaded.write(event);
+    }
+}
+
+/// Measures the duration of every tick into [`TickHealth`] and sends [`ServerOverloaded`].
+pub struct TickHealthPlugin;
+
+impl Plugin for TickHealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TickHealth>();
+        app.add_event::<ServerOverloaded>();
+        app.add_systems(FixedFirst, start_tick);
+        app.add_systems(FixedLast, end_tick);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record_ticks(health: &mut TickHealth, start: Instant, durations: &[u64]) -> Vec<u32> {
+        durations
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &ms)| {
+                let tick_start = start + health.budget() * u32::try_from(i).unwrap();
+                health.record(tick_start, Duration::from_millis(ms))
+            })
+            .map(|event| event.consecutive_ticks)
+            .collect()
+    }
+
+    #[test]
+    fn test_overloaded() {
+        let mut health = TickHealth {
+            overload_duration: Duration::from_millis(150),
+            ..TickHealth::default()
+        };
+        assert_eq!(health.overload_threshold(), 3);
+
+        let events = record_ticks(&mut health, Instant::now(), &[
+            60, 60, 10, 60, 60, 60, 60, 60, 60, 60,
+        ]);
+
+        assert_eq!(events, [3, 6]);
+        assert_eq!(health.consecutive_overloaded(), 7);
+        assert!(health.is_overloaded());
+    }
+
+    #[test]
+    fn test_overload_threshold_follows_tick_rate() {
+        let mut health = TickHealth::default();
+        assert_eq!(health.overload_threshold(), 100);
+
+        health.set_tick_rate(&TickRate::new(40.0, 10));
+        assert_eq!(health.budget(), Duration::from_millis(25));
+        assert_eq!(health.overload_threshold(), 200);
+    }
+
+    #[test]
+    fn test_average() {
+        let mut health = TickHealth::default();
+        assert_eq!(health.average(Duration::from_secs(60)), None);
+
+        let start = Instant::now();
+        record_ticks(&mut health, start, &[10; 20]);
+
+        let average = health.average(Duration::from_secs(60)).unwrap();
+        assert!((average.mspt - 10.0).abs() < 1e-9);
+        assert!((average.tps - 20.0).abs() < 1e-9);
+
+        // Ticks which take twice the budget halve the tick rate
+        let mut health = TickHealth::default();
+        let budget = health.budget();
+        for i in 0..20_u32 {
+            health.record(start + budget * 2 * i, budget * 2);
+        }
+
+        let average = health.average(Duration::from_secs(60)).unwrap();
+        assert!((average.mspt - 100.0).abs() < 1e-9);
+        assert!((average.tps - 10.0).abs() < 1e-9);
+
+        // Only the most recent second is included
+        let average = health.average(Duration::from_secs(1)).unwrap();
+        assert!((average.tps - 10.0).abs() < 1e-9);
+    }
+}
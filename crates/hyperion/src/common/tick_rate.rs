@@ -0,0 +1,300 @@
+//! Controls how often ticks run, similar to the vanilla `/tick` command.
+//!
+//! Networking runs in the same fixed schedule as the game, so freezing the tick rate cannot stop
+//! [`FixedMain`] entirely. Instead, systems which advance the game state are in [`AdvanceTick`],
+//! which only runs while [`tick_running`] is true.
+
+use std::time::{Duration, Instant};
+
+use bevy::{
+    app::PluginsState, prelude::*, tasks::tick_global_task_pools_on_main_thread, time::TimeSystem,
+};
+use tracing::warn;
+
+use crate::{config::Config, tick_health::TickHealth};
+
+/// The lowest tick rate which may be set
+pub const MIN_TICK_RATE: f64 = 1.0;
+
+/// The highest tick rate which may be set
+pub const MAX_TICK_RATE: f64 = 10_000.0;
+
+/// How often the app is updated per tick, so that ticks start close to when they are due
+const UPDATES_PER_TICK: u32 = 5;
+
+/// Systems which advance the game state, such as physics, timers and anything which runs every N
+/// ticks. The set is configured in [`FixedUpdate`] and [`FixedPostUpdate`] and does not run while
+/// the game is frozen with [`TickRate::set_frozen`].
+///
+/// ```ignore
+/// app.add_systems(FixedUpdate, grow_crops.in_set(AdvanceTick));
+/// ```
+#[derive(SystemSet, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AdvanceTick;
+
+/// The tick rate and whether the game is frozen.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct TickRate {
+    /// Ticks per second
+    rate: f64,
+    /// The maximum number of ticks which are run back to back to catch up after a long tick. Any
+    /// ticks beyond this are skipped.
+    max_catch_up_ticks: u32,
+    frozen: bool,
+    /// The number of ticks left to run while frozen
+    steps: u32,
+}
+
+impl TickRate {
+    /// Creates a tick rate with `rate` ticks per second, clamped to [`MIN_TICK_RATE`] and
+    /// [`MAX_TICK_RATE`]
+    #[must_use]
+    pub const fn new(rate: f64, max_catch_up_ticks: u32) -> Self {
+        Self {
+            rate: rate.clamp(MIN_TICK_RATE, MAX_TICK_RATE),
+            max_catch_up_ticks,
+            frozen: false,
+            steps: 0,
+        }
+    }
+
+    /// Ticks per second
+    #[must_use]
+    pub const fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Sets the number of ticks per second, clamped to [`MIN_TICK_RATE`] and [`MAX_TICK_RATE`]
+    pub const fn set_rate(&mut self, rate: f64) {
+        self.rate = rate.clamp(MIN_TICK_RATE, MAX_TICK_RATE);
+    }
+
+    /// The time between the start of each tick
+    #[must_use]
+    pub fn timestep(&self) -> Duration {
+        Duration::from_secs_f64(self.rate.recip())
+    }
+
+    /// The number of ticks which run in `duration` at this rate, rounded to the nearest tick
+    #[must_use]
+    pub fn ticks_in(&self, duration: Duration) -> i64 {
+        #[expect(
+            clippy::cast_possible_truncation,
+            reason = "the cast saturates, and no duration needs more ticks than an i64 can hold"
+        )]
+        let ticks = (duration.as_secs_f64() * self.rate).round() as i64;
+        ticks
+    }
+
+    #[must_use]
+    pub const fn max_catch_up_ticks(&self) -> u32 {
+        self.max_catch_up_ticks
+    }
+
+    pub const fn set_max_catch_up_ticks(&mut self, ticks: u32) {
+        self.max_catch_up_ticks = ticks;
+    }
+
+    #[must_use]
+    pub const fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Freezes or unfreezes the game. Unfreezing cancels any remaining steps.
+    pub const fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+        self.steps = 0;
+    }
+
+    /// The number of ticks left to run while frozen
+    #[must_use]
+    pub const fn remaining_steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// Runs `ticks` more ticks while frozen. Returns `false` without doing anything if the game is
+    /// not frozen.
+    pub const fn step(&mut self, ticks: u32) -> bool {
+        if !self.frozen {
+            return false;
+        }
+        self.steps = self.steps.saturating_add(ticks);
+        true
+    }
+
+    /// Whether the game state advances during the current tick
+    #[must_use]
+    pub const fn is_running(&self) -> bool {
+        !self.frozen || self.steps > 0
+    }
+}
+
+impl Default for TickRate {
+    fn default() -> Self {
+        Self::new(20.0, 10)
+    }
+}
+
+impl From<&Config> for TickRate {
+    fn from(config: &Config) -> Self {
+        Self::new(config.tick_rate, config.max_catch_up_ticks)
+    }
+}
+
+/// Run condition of [`AdvanceTick`], which is false while the game is frozen with
+/// [`TickRate::set_frozen`]
+#[must_use]
+pub fn tick_running(tick_rate: Res<'_, TickRate>) -> bool {
+    tick_rate.is_running()
+}
+
+/// Applies changes to [`TickRate`] to the fixed timestep.
+fn apply_tick_rate(
+    tick_rate: Res<'_, TickRate>,
+    mut fixed: ResMut<'_, Time<Fixed>>,
+    mut virt: ResMut<'_, Time<Virtual>>,
+    mut health: ResMut<'_, TickHealth>,
+) {
+    if !tick_rate.is_changed() {
+        return;
+    }
+
+    let timestep = tick_rate.timestep();
+    fixed.set_timestep(timestep);
+    virt.set_max_delta(timestep * tick_rate.max_catch_up_ticks.max(1));
+    health.set_tick_rate(&tick_rate);
+}
+
+/// Warns when a frame took long enough that ticks had to be skipped instead of caught up.
+fn warn_skipped_ticks(
+    real: Res<'_, Time<Real>>,
+    virt: Res<'_, Time<Virtual>>,
+    fixed: Res<'_, Time<Fixed>>,
+) {
+    let skipped = real.delta().saturating_sub(virt.max_delta());
+    if skipped.is_zero() {
+        return;
+    }
+
+    let timestep = fixed.timestep();
+    warn!(
+        "can't keep up! skipping {} ticks ({} ms behind)",
+        skipped.as_nanos() / timestep.as_nanos().max(1),
+        skipped.as_millis()
+    );
+}
+
+fn finish_step(mut tick_rate: ResMut<'_, TickRate>) {
+    if tick_rate.frozen && tick_rate.steps > 0 {
+        tick_rate.steps -= 1;
+    }
+}
+
+/// Updates the app in a loop. Unlike [`ScheduleRunnerPlugin`](bevy::app::ScheduleRunnerPlugin),
+/// the time between updates follows changes to [`TickRate`].
+fn run_loop(mut app: App) -> AppExit {
+    if app.plugins_state() != PluginsState::Cleaned {
+        while app.plugins_state() == PluginsState::Adding {
+            tick_global_task_pools_on_main_thread();
+        }
+        app.finish();
+        app.cleanup();
+    }
+
+    loop {
+        let start = Instant::now();
+        app.update();
+
+        if let Some(exit) = app.should_exit() {
+            return exit;
+        }
+
+        let wait = app.world().resource::<TickRate>().timestep() / UPDATES_PER_TICK;
+        std::thread::sleep(wait.saturating_sub(start.elapsed()));
+    }
+}
+
+/// Applies [`TickRate`] to the fixed timestep and to how often the app is updated.
+pub struct TickRatePlugin;
+
+impl Plugin for TickRatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TickRate>();
+        app.set_runner(run_loop);
+        app.configure_sets(FixedUpdate, AdvanceTick.run_if(tick_running));
+        app.configure_sets(FixedPostUpdate, AdvanceTick.run_if(tick_running));
+        app.add_systems(
+            First,
+            (apply_tick_rate, warn_skipped_ticks)
+                .chain()
+                .after(TimeSystem),
+        );
+        app.add_systems(FixedLast, finish_step);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_while_frozen() {
+        let mut tick_rate = TickRate::default();
+        assert!(tick_rate.is_running());
+        assert!(!tick_rate.step(1));
+
+        tick_rate.set_frozen(true);
+        assert!(!tick_rate.is_running());
+
+        assert!(tick_rate.step(2));
+        assert!(tick_rate.is_running());
+        assert_eq!(tick_rate.remaining_steps(), 2);
+
+        tick_rate.set_frozen(false);
+        assert_eq!(tick_rate.remaining_steps(), 0);
+    }
+
+    #[test]
+    fn test_advance_tick_is_skipped_while_frozen() {
+        #[derive(Resource, Default)]
+        struct Ticks(u32);
+
+        let mut app = App::new();
+        app.add_plugins(TickRatePlugin);
+        app.init_resource::<Ticks>();
+        app.add_systems(
+            FixedUpdate,
+            (|mut ticks: ResMut<'_, Ticks>| ticks.0 += 1).in_set(AdvanceTick),
+        );
+
+        app.world_mut().run_schedule(FixedUpdate);
+        app.world_mut().resource_mut::<TickRate>().set_frozen(true);
+        app.world_mut().run_schedule(FixedUpdate);
+        assert_eq!(app.world().resource::<Ticks>().0, 1);
+
+        app.world_mut().resource_mut::<TickRate>().step(1);
+        app.world_mut().run_schedule(FixedUpdate);
+        assert_eq!(app.world().resource::<Ticks>().0, 2);
+    }
+
+    #[test]
+    fn test_rate_is_clamped() {
+        let mut tick_rate = TickRate::new(0.0, 10);
+        assert!((tick_rate.rate() - MIN_TICK_RATE).abs() < f64::EPSILON);
+
+        tick_rate.set_rate(40.0);
+        assert_eq!(tick_rate.timestep(), Duration::from_millis(25));
+    }
+
+    #[test]
+    fn test_ticks_in() {
+        let mut tick_rate = TickRate::default();
+        assert_eq!(tick_rate.ticks_in(Duration::ZERO), 0);
+        assert_eq!(tick_rate.ticks_in(Duration::from_secs(3)), 60);
+
+        tick_rate.set_rate(40.0);
+        assert_eq!(tick_rate.ticks_in(Duration::from_secs(3)), 120);
+        assert_eq!(tick_rate.ticks_in(Duration::from_millis(10)), 0);
+        assert_eq!(tick_rate.ticks_in(Duration::from_millis(15)), 1);
+    }
+}
@@ -0,0 +1,294 @@
+//! Per-system tick timings, similar to Paper timings.
+//!
+//! Systems are timed by wrapping them with [`timed`]. The time spent in each timed system during a
+//! tick is collected into [`SystemTimings`] at the end of every fixed update.
+
+use std::{
+    borrow::Cow,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+use bevy::{
+    ecs::{
+        archetype::ArchetypeComponentId,
+        component::{ComponentId, Tick},
+        query::Access,
+        schedule::InternedSystemSet,
+        system::{SystemIn, SystemInput, SystemParamValidationError},
+        world::{DeferredWorld, unsafe_world_cell::UnsafeWorldCell},
+    },
+    prelude::*,
+};
+use hyperion_stats::ParallelStats;
+use serde::Serialize;
+
+/// The quantiles which are estimated for each system
+const QUANTILES: [f64; 2] = [0.95, 0.99];
+
+/// A system which is being timed. This is shared between the [`Timed`] system and
+/// [`SystemTimings`].
+#[derive(Debug)]
+struct TimedSystem {
+    name: String,
+    /// Time spent in the system since the timings were last collected
+    nanos: AtomicU64,
+}
+
+/// A system which measures how long it runs. It registers itself in [`SystemTimings`] when it is
+/// initialized.
+#[derive(Debug)]
+pub struct Timed<S> {
+    system: S,
+    timed_system: Arc<TimedSystem>,
+}
+
+impl<S: System> System for Timed<S> {
+    type In = S::In;
+    type Out = S::Out;
+
+    fn name(&self) -> Cow<'static, str> {
+        self.system.name()
+    }
+
+    fn component_access(&self) -> &Access<ComponentId> {
+        self.system.component_access()
+    }
+
+    fn archetype_component_access(&self) -> &Access<ArchetypeComponentId> {
+        self.system.archetype_component_access()
+    }
+
+    fn is_send(&self) -> bool {
+        self.system.is_send()
+    }
+
+    fn is_exclusive(&self) -> bool {
+        self.system.is_exclusive()
+    }
+
+    fn has_deferred(&self) -> bool {
+        self.system.has_deferred()
+    }
+
+    unsafe fn run_unsafe(
+        &mut self,
+        input: SystemIn<'_, Self>,
+        world: UnsafeWorldCell<'_>,
+    ) -> S::Out {
+        let start = Instant::now();
+        // SAFETY: `system.run_unsafe` has the same invariants as `self.run_unsafe`.
+        let out = unsafe { self.system.run_unsafe(input, world) };
+        let elapsed = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.timed_system
+            .nanos
+            .fetch_add(elapsed, Ordering::Relaxed);
+        out
+    }
+
+    fn apply_deferred(&mut self, world: &mut World) {
+        self.system.apply_deferred(world);
+    }
+
+    fn queue_deferred(&mut self, world: DeferredWorld<'_>) {
+        self.system.queue_deferred(world);
+    }
+
+    unsafe fn validate_param_unsafe(
+        &mut self,
+        world: UnsafeWorldCell<'_>,
+    ) -> Result<(), SystemParamValidationError> {
+        // SAFETY: `system.validate_param_unsafe` has the same invariants as
+        // `self.validate_param_unsafe`.
+        unsafe { self.system.validate_param_unsafe(world) }
+    }
+
+    fn initialize(&mut self, world: &mut World) {
+        world
+            .get_resource_or_init::<SystemTimings>()
+            .register(&self.timed_system);
+        self.system.initialize(world);
+    }
+
+    fn update_archetype_component_access(&mut self, world: UnsafeWorldCell<'_>) {
+        self.system.update_archetype_component_access(world);
+    }
+
+    fn check_change_tick(&mut self, change_tick: Tick) {
+        self.system.check_change_tick(change_tick);
+    }
+
+    fn default_system_sets(&self) -> Vec<InternedSystemSet> {
+        self.system.default_system_sets()
+    }
+
+    fn get_last_run(&self) -> Tick {
+        self.system.get_last_run()
+    }
+
+    fn set_last_run(&mut self, last_run: Tick) {
+        self.system.set_last_run(last_run);
+    }
+}
+
+/// Removes the module path from a system name, keeping only the function name and any generic
+/// arguments
+pub(crate) fn short_name(name: &str) -> String {
+    let (path, generics) = name.find('<').map_or((name, ""), |i| name.split_at(i));
+    let function = path.rsplit("::").next().unwrap_or(path);
+    format!("{function}{generics}")
+}
+
+/// Wraps a system so that the time it takes is recorded in [`SystemTimings`].
+///
+/// ```ignore
+/// app.add_systems(FixedUpdate, (timed(update_health), timed(send_health)).chain());
+/// ```
+pub fn timed<F, I, O, M>(system: F) -> Timed<F::System>
+where
+    F: IntoSystem<I, O, M>,
+    I: SystemInput,
+{
+    let system = IntoSystem::into_system(system);
+
+    let timed_system = Arc::new(TimedSystem {
+        name: short_name(&system.name()),
+        nanos: AtomicU64::new(0),
+    });
+
+    Timed {
+        system,
+        timed_system,
+    }
+}
+
+/// Timing statistics of a single system. All durations are in milliseconds per tick.
+#[derive(Clone, Debug, Serialize)]
+pub struct SystemTiming {
+    pub name: String,
+    pub mean: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+    /// The number of ticks included in the timing
+    pub ticks: u64,
+}
+
+/// The time spent in each [`timed`] system per tick.
+#[derive(Resource)]
+pub struct SystemTimings {
+    systems: Vec<Arc<TimedSystem>>,
+    /// Statistics over all ticks since the timings were last reset
+    stats: ParallelStats,
+    /// Reused buffer of the times of the current tick
+    current: Vec<f64>,
+}
+
+impl Default for SystemTimings {
+    fn default() -> Self {
+        Self {
+            systems: Vec::new(),
+            stats: ParallelStats::new(0),
+            current: Vec::new(),
+        }
+    }
+}
+
+impl SystemTimings {
+    /// Clears all collected timings
+    pub fn reset(&mut self) {
+        self.stats = ParallelStats::with_quantiles(self.systems.len(), &QUANTILES);
+    }
+
+    fn register(&mut self, system: &Arc<TimedSystem>) {
+        if self
+            .systems
+            .iter()
+            .any(|registered| Arc::ptr_eq(registered, system))
+        {
+            return;
+        }
+
+        // Statistics cannot be extended with more systems, so they are restarted when systems
+        // are added. This normally only happens when each schedule first runs.
+        self.systems.push(system.clone());
+        self.reset();
+    }
+
+    fn collect(&mut self) {
+        if self.systems.is_empty() {
+            return;
+        }
+
+        self.current.clear();
+        self.current.extend(self.systems.iter().map(|system| {
+            let nanos = system.nanos.swap(0, Ordering::Relaxed);
+            nanos as f64 / 1_000_000.0
+        }));
+
+        self.stats.update(&self.current);
+    }
+
+    /// Returns the timings of every system, sorted from the slowest to the fastest mean time.
+    #[must_use]
+    pub fn report(&self) -> Vec<SystemTiming> {
+        let mut report = self
+            .systems
+            .iter()
+            .enumerate()
+            .map(|(idx, system)| SystemTiming {
+                name: system.name.clone(),
+                mean: self.stats.mean(idx).unwrap_or_default(),
+                p95: self.stats.quantile(idx, 0.95).unwrap_or_default(),
+                p99: self.stats.quantile(idx, 0.99).unwrap_or_default(),
+                max: self.stats.max(idx).unwrap_or_default(),
+                ticks: self.stats.count(idx),
+            })
+            .collect::<Vec<_>>();
+
+        report.sort_unstable_by(|a, b| b.mean.total_cmp(&a.mean));
+        report
+    }
+
+    /// Writes [`SystemTimings::report`] to `path` as json.
+    pub fn dump(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(&self.report())?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+fn collect_timings(mut timings: ResMut<'_, SystemTimings>) {
+    timings.collect();
+}
+
+/// Collects the timings of systems wrapped with [`timed`].
+pub struct TimingsPlugin;
+
+impl Plugin for TimingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SystemTimings>();
+        app.add_systems(FixedLast, collect_timings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_name() {
+        assert_eq!(
+            short_name("hyperion::simulation::handlers::hand_swing"),
+            "hand_swing"
+        );
+        assert_eq!(
+            short_name("hyperion::egress::track_prev<hyperion::simulation::Xp>"),
+            "track_prev<hyperion::simulation::Xp>"
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! See [`MineSkinClient`].
+
+use anyhow::{Context, bail};
+use bevy::prelude::*;
+use reqwest::header::{CONTENT_TYPE, USER_AGENT};
+use serde_json::Value;
+
+const GENERATE_URL: &str = "https://api.mineskin.org/generate/url";
+
+/// A client for the [MineSkin](https://mineskin.org) API.
+///
+/// Skins are only shown by the client if their textures are signed by Mojang. MineSkin uploads
+/// skins from arbitrary texture URLs to Mojang and returns the signed textures.
+#[derive(Resource, Clone)]
+pub struct MineSkinClient {
+    req: reqwest::Client,
+    api_key: Option<String>,
+}
+
+impl MineSkinClient {
+    /// Creates a client which uses the given API key. Requests without an API key are more
+    /// heavily rate limited.
+    #[must_use]
+    pub fn new(api_key: Option<String>) -> Self {
+        Self {
+            req: reqwest::Client::new(),
+            api_key,
+        }
+    }
+
+    /// Signs the skin texture at `url`, returning the MineSkin response.
+    pub async fn generate_from_url(&self, url: &str) -> anyhow::Result<Value> {
+        let body = serde_json::json!({ "url": url }).to_string();
+
+        let mut request = self
+            .req
+            .post(GENERATE_URL)
+            .header(CONTENT_TYPE, "application/json")
+            .header(USER_AGENT, "hyperion")
+            .body(body);
+
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        let json_object = serde_json::from_str::<Value>(&body)
+            .with_context(|| format!("failed to parse json from response: {body:?}"))?;
+
+        if let Some(error) = json_object.get("error") {
+            bail!(
+                "MineSkin API Error: {}",
+                error.as_str().unwrap_or("Unknown error")
+            );
+        }
+
+        if !status.is_success() {
+            bail!("MineSkin API returned {status}");
+        }
+
+        Ok(json_object)
+    }
+}
@@ -1 +1,2 @@
+pub mod mineskin;
 pub mod mojang;
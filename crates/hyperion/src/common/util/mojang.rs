@@ -1,18 +1,45 @@
 //! See [`MojangClient`].
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::{Context, bail};
+use anyhow::{Context, anyhow};
 use bevy::prelude::*;
+use lru::LruCache;
+use parking_lot::Mutex;
+use reqwest::{StatusCode, header::RETRY_AFTER};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::{
-    sync::Semaphore,
+    sync::{OnceCell, Semaphore},
     time::{MissedTickBehavior, interval},
 };
 use tracing::warn;
 use uuid::Uuid;
 
-use crate::AsyncRuntime;
+use crate::{
+    AsyncRuntime,
+    storage::{LocalDb, ProfileCacheHandler},
+};
+
+/// How long the UUID of a username is cached for
+const USERNAME_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long profiles, which include skins, are cached for
+const PROFILE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// The number of responses kept in memory
+const MEMORY_CACHE_SIZE: NonZeroUsize = NonZeroUsize::new(4096).unwrap();
+
+/// The backoff after the first rate limited response without a `Retry-After` header. This is
+/// doubled for every following rate limited response.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
 
 /// The API provider to use for Minecraft profile lookups
 #[derive(Clone, Copy)]
@@ -56,54 +83,205 @@ impl ApiProvider {
     }
 }
 
+/// Backs off from a provider after it responds with 429 Too Many Requests
+#[derive(Default, Debug)]
+struct Backoff {
+    until: Option<Instant>,
+    delay: Duration,
+}
+
+impl Backoff {
+    fn is_active(&self, now: Instant) -> bool {
+        self.until.is_some_and(|until| now < until)
+    }
+
+    fn rate_limited(&mut self, now: Instant, retry_after: Option<Duration>) {
+        self.delay =
+            retry_after.unwrap_or_else(|| (self.delay * 2).clamp(INITIAL_BACKOFF, MAX_BACKOFF));
+        self.until = Some(now + self.delay);
+    }
+
+    fn succeeded(&mut self) {
+        *self = Self::default();
+    }
+}
+
+struct Provider {
+    api: ApiProvider,
+    rate_limit: Arc<Semaphore>,
+    backoff: Mutex<Backoff>,
+}
+
+/// Whether a failed request should be tried again with the next provider
+enum RequestError {
+    Retryable(anyhow::Error),
+    Fatal(anyhow::Error),
+}
+
+/// A response from the API and when it was received
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedResponse {
+    /// Seconds since the Unix epoch
+    fetched_at: u64,
+    value: Value,
+}
+
+impl CachedResponse {
+    fn is_fresh(&self, ttl: Duration, now: u64) -> bool {
+        now.saturating_sub(self.fetched_at) < ttl.as_secs()
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Caches responses in memory and optionally in a [`LocalDb`]
+struct ResponseCache {
+    memory: Mutex<LruCache<String, CachedResponse>>,
+    persistent: Option<ProfileCacheHandler>,
+}
+
+impl ResponseCache {
+    fn new(persistent: Option<ProfileCacheHandler>) -> Self {
+        Self {
+            memory: Mutex::new(LruCache::new(MEMORY_CACHE_SIZE)),
+            persistent,
+        }
+    }
+
+    fn get(&self, key: &str, ttl: Duration) -> Option<Value> {
+        let now = unix_now();
+
+        if let Some(cached) = self.memory.lock().get(key)
+            && cached.is_fresh(ttl, now)
+        {
+            return Some(cached.value.clone());
+        }
+
+        let bytes = match self.persistent.as_ref()?.find(key) {
+            Ok(bytes) => bytes?,
+            Err(e) => {
+                warn!("failed to read cached profile api response: {e}");
+                return None;
+            }
+        };
+
+        let cached = serde_json::from_slice::<CachedResponse>(&bytes)
+            .ok()
+            .filter(|cached| cached.is_fresh(ttl, now));
+
+        let Some(cached) = cached else {
+            // Stale or unreadable responses would otherwise stay in the database forever
+            if let Some(persistent) = &self.persistent
+                && let Err(e) = persistent.remove(key)
+            {
+                warn!("failed to remove cached profile api response: {e}");
+            }
+            return None;
+        };
+
+        self.memory.lock().put(key.to_string(), cached.clone());
+        Some(cached.value)
+    }
+
+    fn insert(&self, key: &str, value: &Value) {
+        let cached = CachedResponse {
+            fetched_at: unix_now(),
+            value: value.clone(),
+        };
+
+        if let Some(persistent) = &self.persistent {
+            let result = serde_json::to_vec(&cached)
+                .map_err(anyhow::Error::from)
+                .and_then(|bytes| persistent.insert(key, &bytes));
+
+            if let Err(e) = result {
+                warn!("failed to cache profile api response: {e}");
+            }
+        }
+
+        self.memory.lock().put(key.to_string(), cached);
+    }
+}
+
+type InFlight = Arc<OnceCell<Result<Value, Arc<anyhow::Error>>>>;
+
+fn username_key(username: &str) -> String {
+    // Usernames are case insensitive
+    format!("username:{}", username.to_ascii_lowercase())
+}
+
+fn profile_key(uuid: &Uuid) -> String {
+    format!("profile:{}", uuid.simple())
+}
+
 /// A client to interface with the Minecraft profile API.
 ///
 /// Can use either the official Mojang API or [matdoes/mowojang](https://matdoes.dev/minecraft-uuids) as a data source.
-/// This does not include caching, this should be done separately probably using [`crate::storage::LocalDb`].
+/// Lookups are tried with each provider in order until one succeeds, and providers which respond
+/// with 429 Too Many Requests are skipped until they can be used again.
+///
+/// Responses are cached in memory, and also in a [`LocalDb`] when created with
+/// [`MojangClient::with_profile_cache`]. Concurrent lookups of the same player only send one
+/// request.
 #[derive(Resource, Clone)]
 pub struct MojangClient {
     req: reqwest::Client,
-    rate_limit: Arc<Semaphore>,
-    provider: ApiProvider,
+    providers: Arc<[Provider]>,
+    cache: Arc<ResponseCache>,
+    in_flight: Arc<Mutex<HashMap<String, InFlight>>>,
 }
 
 impl MojangClient {
     #[must_use]
     pub fn new(runtime: &AsyncRuntime, provider: ApiProvider) -> Self {
-        let rate_limit = Arc::new(Semaphore::new(provider.max_requests()));
-        let interval_duration = provider.interval();
-
-        runtime.spawn({
-            let rate_limit = Arc::downgrade(&rate_limit);
-            let max_requests = provider.max_requests();
-            async move {
-                let mut interval = interval(interval_duration);
-                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
-
-                loop {
-                    interval.tick().await;
-
-                    let Some(rate_limit) = rate_limit.upgrade() else {
-                        return;
-                    };
+        Self::with_providers(runtime, &[provider])
+    }
 
-                    let available = rate_limit.available_permits();
-                    rate_limit.add_permits(max_requests - available);
+    /// Creates a client which falls back to the next provider when a lookup fails
+    #[must_use]
+    pub fn with_providers(runtime: &AsyncRuntime, providers: &[ApiProvider]) -> Self {
+        let providers = providers
+            .iter()
+            .map(|&api| {
+                let rate_limit = Arc::new(Semaphore::new(api.max_requests()));
+                spawn_rate_limit_refill(runtime, &rate_limit, api);
+
+                Provider {
+                    api,
+                    rate_limit,
+                    backoff: Mutex::default(),
                 }
-            }
-        });
+            })
+            .collect();
 
         Self {
             req: reqwest::Client::new(),
-            rate_limit,
-            provider,
+            providers,
+            cache: Arc::new(ResponseCache::new(None)),
+            in_flight: Arc::default(),
         }
     }
 
+    /// Also caches responses in `db` so they are kept across restarts. Prefer
+    /// [`ProfileCacheHandler::open`], which keeps the cache in its own database, over sharing one.
+    pub fn with_persistent_cache(self, db: &LocalDb) -> anyhow::Result<Self> {
+        Ok(self.with_profile_cache(ProfileCacheHandler::new(db)?))
+    }
+
+    /// Also caches responses in `cache` so they are kept across restarts
+    #[must_use]
+    pub fn with_profile_cache(mut self, cache: ProfileCacheHandler) -> Self {
+        self.cache = Arc::new(ResponseCache::new(Some(cache)));
+        self
+    }
+
     /// Gets a player's UUID from their username.
     pub async fn get_uuid(&self, username: &str) -> anyhow::Result<Uuid> {
-        let url = self.provider.username_url(username);
-        let json_object = self.response_raw(&url).await?;
+        let json_object = self.data_from_username(username).await?;
 
         let id = json_object
             .get("id")
@@ -116,8 +294,7 @@ impl MojangClient {
 
     /// Gets a player's username from their UUID.
     pub async fn get_username(&self, uuid: Uuid) -> anyhow::Result<String> {
-        let url = self.provider.uuid_url(&uuid);
-        let json_object = self.response_raw(&url).await?;
+        let json_object = self.data_from_uuid(&uuid).await?;
 
         json_object
             .get("name")
@@ -129,59 +306,288 @@ impl MojangClient {
 
     /// Gets player data from their UUID.
     pub async fn data_from_uuid(&self, uuid: &Uuid) -> anyhow::Result<Value> {
-        let url = self.provider.uuid_url(uuid);
-        self.response_raw(&url).await
+        let json_object = self
+            .lookup(profile_key(uuid), PROFILE_TTL, |api| api.uuid_url(uuid))
+            .await?;
+
+        // Profiles also contain the username, so the username lookup can be cached as well
+        if let (Some(id), Some(name)) = (json_object.get("id"), json_object.get("name"))
+            && let Some(name) = name.as_str()
+        {
+            let username = serde_json::json!({ "id": id, "name": name });
+            self.cache.insert(&username_key(name), &username);
+        }
+
+        Ok(json_object)
     }
 
     /// Gets player data from their username.
     pub async fn data_from_username(&self, username: &str) -> anyhow::Result<Value> {
-        let url = self.provider.username_url(username);
-        self.response_raw(&url).await
+        self.lookup(username_key(username), USERNAME_TTL, |api| {
+            api.username_url(username)
+        })
+        .await
+    }
+
+    /// Returns the cached response for `key`, or otherwise requests it. Concurrent lookups of the
+    /// same key share one request.
+    async fn lookup(
+        &self,
+        key: String,
+        ttl: Duration,
+        url: impl Fn(&ApiProvider) -> String,
+    ) -> anyhow::Result<Value> {
+        if let Some(value) = self.cache.get(&key, ttl) {
+            return Ok(value);
+        }
+
+        let cell = self
+            .in_flight
+            .lock()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        let key_ref = key.as_str();
+        let result = cell
+            .get_or_init(|| async move {
+                let result = self.fetch(url).await;
+                if let Ok(value) = &result {
+                    self.cache.insert(key_ref, value);
+                }
+                result.map_err(Arc::new)
+            })
+            .await
+            .clone();
+
+        {
+            let mut in_flight = self.in_flight.lock();
+            if in_flight
+                .get(&key)
+                .is_some_and(|current| Arc::ptr_eq(current, &cell))
+            {
+                in_flight.remove(&key);
+            }
+        }
+
+        result.map_err(|e| anyhow!("{e:#}"))
     }
 
-    async fn response_raw(&self, url: &str) -> anyhow::Result<Value> {
-        self.rate_limit
+    /// Requests from each provider in order until one succeeds
+    async fn fetch(&self, url: impl Fn(&ApiProvider) -> String) -> anyhow::Result<Value> {
+        let mut last_error = None;
+
+        for provider in self.providers.iter() {
+            if provider.backoff.lock().is_active(Instant::now()) {
+                continue;
+            }
+
+            match self.response_raw(provider, &url(&provider.api)).await {
+                Ok(value) => return Ok(value),
+                Err(RequestError::Fatal(e)) => return Err(e),
+                Err(RequestError::Retryable(e)) => {
+                    warn!("profile api request failed, trying the next provider: {e}");
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow!("all profile api providers are rate limited")))
+    }
+
+    async fn response_raw(&self, provider: &Provider, url: &str) -> Result<Value, RequestError> {
+        provider
+            .rate_limit
             .acquire()
             .await
             .expect("semaphore is never closed")
             .forget();
 
-        if self.rate_limit.available_permits() == 0 {
+        if provider.rate_limit.available_permits() == 0 {
             warn!(
                 "rate limiting will be applied: {} requests have been sent in the past {:?} \
                  interval",
-                self.provider.max_requests(),
-                self.provider.interval()
+                provider.api.max_requests(),
+                provider.api.interval()
             );
         }
 
-        let response = self.req.get(url).send().await?;
+        let response = self
+            .req
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| RequestError::Retryable(e.into()))?;
+
+        let status = response.status();
+
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+                .map(Duration::from_secs);
+
+            provider
+                .backoff
+                .lock()
+                .rate_limited(Instant::now(), retry_after);
+
+            return Err(RequestError::Retryable(anyhow!(
+                "rate limited by {}",
+                provider.api.username_base_url
+            )));
+        }
 
-        if response.status().is_success() {
-            let body = response.text().await?;
-            let json_object = serde_json::from_str::<Value>(&body)
-                .with_context(|| format!("failed to parse json from response: {body:?}"))?;
+        if status.is_server_error() {
+            return Err(RequestError::Retryable(anyhow!(
+                "server error {status} from {}",
+                provider.api.username_base_url
+            )));
+        }
 
-            if let Some(error) = json_object.get("error") {
-                bail!("API Error: {}", error.as_str().unwrap_or("Unknown error"));
-            }
-            Ok(json_object)
-        } else {
-            bail!("Failed to retrieve data from API");
+        provider.backoff.lock().succeeded();
+
+        if !status.is_success() {
+            return Err(RequestError::Fatal(anyhow!(
+                "Failed to retrieve data from API: {status}"
+            )));
+        }
+
+        let body = response
+            .text()
+            .await
+            .map_err(|e| RequestError::Retryable(e.into()))?;
+
+        let json_object = serde_json::from_str::<Value>(&body)
+            .with_context(|| format!("failed to parse json from response: {body:?}"))
+            .map_err(RequestError::Fatal)?;
+
+        if let Some(error) = json_object.get("error") {
+            return Err(RequestError::Fatal(anyhow!(
+                "API Error: {}",
+                error.as_str().unwrap_or("Unknown error")
+            )));
         }
+
+        Ok(json_object)
     }
 }
 
+/// Refills the rate limit of a provider every interval
+fn spawn_rate_limit_refill(runtime: &AsyncRuntime, rate_limit: &Arc<Semaphore>, api: ApiProvider) {
+    runtime.spawn({
+        let rate_limit = Arc::downgrade(rate_limit);
+        let max_requests = api.max_requests();
+        let interval_duration = api.interval();
+        async move {
+            let mut interval = interval(interval_duration);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                interval.tick().await;
+
+                let Some(rate_limit) = rate_limit.upgrade() else {
+                    return;
+                };
+
+                let available = rate_limit.available_permits();
+                rate_limit.add_permits(max_requests - available);
+            }
+        }
+    });
+}
+
 #[cfg(test)]
 #[expect(clippy::unwrap_used, clippy::print_stdout, reason = "these are tests")]
 mod tests {
-    use std::str::FromStr;
+    use std::{
+        str::FromStr,
+        time::{Duration, Instant},
+    };
 
     use crate::{
         runtime::AsyncRuntime,
-        util::mojang::{ApiProvider, MojangClient},
+        storage::{ProfileCacheHandler, TempLocalDb},
+        util::mojang::{
+            ApiProvider, Backoff, CachedResponse, INITIAL_BACKOFF, MAX_BACKOFF, MojangClient,
+            ResponseCache,
+        },
     };
 
+    #[test]
+    fn test_backoff() {
+        let now = Instant::now();
+        let mut backoff = Backoff::default();
+        assert!(!backoff.is_active(now));
+
+        backoff.rate_limited(now, None);
+        assert_eq!(backoff.delay, INITIAL_BACKOFF);
+        assert!(backoff.is_active(now));
+        assert!(!backoff.is_active(now + INITIAL_BACKOFF));
+
+        backoff.rate_limited(now, None);
+        assert_eq!(backoff.delay, INITIAL_BACKOFF * 2);
+
+        for _ in 0..32 {
+            backoff.rate_limited(now, None);
+        }
+        assert_eq!(backoff.delay, MAX_BACKOFF);
+
+        // Retry-After takes precedence over the exponential delay
+        backoff.rate_limited(now, Some(Duration::from_secs(3)));
+        assert_eq!(backoff.delay, Duration::from_secs(3));
+
+        backoff.succeeded();
+        assert!(!backoff.is_active(now));
+    }
+
+    #[test]
+    fn test_cached_response_ttl() {
+        let cached = CachedResponse {
+            fetched_at: 1000,
+            value: serde_json::Value::Null,
+        };
+
+        let ttl = Duration::from_secs(60);
+        assert!(cached.is_fresh(ttl, 1000));
+        assert!(cached.is_fresh(ttl, 1059));
+        assert!(!cached.is_fresh(ttl, 1060));
+
+        // A clock that went backwards does not expire the entry
+        assert!(cached.is_fresh(ttl, 0));
+    }
+
+    #[test]
+    fn test_stale_response_is_removed() {
+        let db = TempLocalDb::new().unwrap();
+        let persistent = ProfileCacheHandler::new(&db).unwrap();
+
+        let stale = CachedResponse {
+            fetched_at: 0,
+            value: serde_json::Value::Null,
+        };
+        let bytes = serde_json::to_vec(&stale).unwrap();
+        persistent.insert("profile:stale", &bytes).unwrap();
+
+        let cache = ResponseCache::new(Some(persistent.clone()));
+        assert!(
+            cache
+                .get("profile:stale", Duration::from_secs(60))
+                .is_none()
+        );
+        assert!(persistent.find("profile:stale").unwrap().is_none());
+
+        cache.insert("profile:fresh", &serde_json::Value::Bool(true));
+        assert!(
+            cache
+                .get("profile:fresh", Duration::from_secs(60))
+                .is_some()
+        );
+        assert!(persistent.find("profile:fresh").unwrap().is_some());
+    }
+
     #[test]
     fn test_get_uuid() {
         let tasks = AsyncRuntime::new();
@@ -8,16 +8,46 @@ use valence_protocol::{ByteAngle, RawBytes, VarInt, packets::play};
 use crate::{
     egress::metadata::show_all,
     net::{
-        Channel, ChannelId, Compose, ConnectionId,
+        Channel, ChannelId, Compose, ConnectionId, TrackingRange,
         intermediate::{IntermediateServerToProxyMessage, UpdateChannelPositions},
+        packet_channel,
     },
     simulation::{
-        Pitch, Position, RequestSubscribeChannelPackets, Uuid, Velocity, Yaw,
+        Owner, Pitch, Position, RequestSubscribeChannelPackets, SentMovement, Uuid, Velocity, Yaw,
         entity_kind::EntityKind,
         metadata::{MetadataChanges, get_and_clear_metadata},
     },
+    timings::timed,
 };
 
+/// Gives every entity which is not a player a channel, which the players near it are subscribed
+/// to. Players are given their channel once they join the world.
+fn add_entity_channel(
+    trigger: Trigger<'_, OnAdd, EntityKind>,
+    query: Query<'_, '_, (&EntityKind, Option<&Position>, Option<&Yaw>, Option<&Pitch>)>,
+    mut commands: Commands<'_, '_>,
+) {
+    let Ok((&kind, position, yaw, pitch)) = query.get(trigger.target()) else {
+        return;
+    };
+
+    // Guis are never shown to players, so nobody needs to subscribe to them
+    if matches!(kind, EntityKind::Player | EntityKind::Gui) {
+        return;
+    }
+
+    let mut entity = commands.entity(trigger.target());
+    entity.try_insert(Channel);
+
+    if let Some(position) = position {
+        entity.try_insert(SentMovement::new(
+            **position,
+            yaw.map_or(0.0, |yaw| **yaw),
+            pitch.map_or(0.0, |pitch| **pitch),
+        ));
+    }
+}
+
 fn add_channel(trigger: Trigger<'_, OnAdd, Channel>, compose: Res<'_, Compose>) {
     let packet = play::EntitiesDestroyS2c {
         entity_ids: vec![VarInt(trigger.target().minecraft_id())].into(),
@@ -38,13 +68,14 @@ fn remove_channel(trigger: Trigger<'_, OnDespawn, Channel>, compose: Res<'_, Com
 
 fn update_channel_positions(
     compose: Res<'_, Compose>,
-    query: Query<'_, '_, (Entity, &Position), With<Channel>>,
+    query: Query<'_, '_, (Entity, &Position, Option<&TrackingRange>), With<Channel>>,
 ) {
     let updates = query
         .iter()
-        .map(|(entity, position)| UpdateChannelPosition {
+        .map(|(entity, position, range)| UpdateChannelPosition {
             channel_id: entity.id(),
             position: position.to_chunk().into(),
+            radius: range.copied().unwrap_or_default().0,
         })
         .collect::<Vec<_>>();
 
@@ -55,6 +86,22 @@ fn update_channel_positions(
         ));
 }
 
+/// The data field of the spawn packet, which is the id of the owner for projectiles
+fn spawn_data(kind: EntityKind, owner: Option<&Owner>) -> i32 {
+    let Some(owner) = owner else {
+        return 0;
+    };
+
+    match kind {
+        EntityKind::FishingBobber => owner.entity.minecraft_id(),
+        // Arrows use the id of the shooter plus one, where 0 means there is no shooter
+        EntityKind::Arrow | EntityKind::SpectralArrow | EntityKind::Trident => {
+            owner.entity.minecraft_id() + 1
+        }
+        _ => 0,
+    }
+}
+
 fn send_subscribe_channel_packets(
     mut events: EventReader<'_, '_, RequestSubscribeChannelPackets>,
     compose: Res<'_, Compose>,
@@ -69,13 +116,14 @@ fn send_subscribe_channel_packets(
             &Yaw,
             &Velocity,
             &EntityKind,
+            Option<&Owner>,
             Option<&ConnectionId>,
         ),
     >,
     world: &World,
 ) {
     for event in events.read() {
-        let (entity, uuid, position, pitch, yaw, velocity, &entity_kind, connection_id) =
+        let (entity, uuid, position, pitch, yaw, velocity, &entity_kind, owner, connection_id) =
             match query.get(event.0) {
                 Ok(data) => data,
                 Err(e) => {
@@ -117,7 +165,7 @@ fn send_subscribe_channel_packets(
                     pitch: ByteAngle::from_degrees(**pitch),
                     yaw: ByteAngle::from_degrees(**yaw),
                     head_yaw: ByteAngle::from_degrees(0.0), // todo:
-                    data: VarInt::default(),                // todo:
+                    data: VarInt(spawn_data(entity_kind, owner)),
                     velocity,
                 };
                 packet_buf = compose
@@ -161,11 +209,16 @@ pub struct ChannelPlugin;
 
 impl Plugin for ChannelPlugin {
     fn build(&self, app: &mut App) {
+        app.add_observer(add_entity_channel);
         app.add_observer(add_channel);
         app.add_observer(remove_channel);
+        packet_channel::build(app);
         app.add_systems(
             FixedUpdate,
-            (update_channel_positions, send_subscribe_channel_packets),
+            (
+                timed(update_channel_positions),
+                timed(send_subscribe_channel_packets),
+            ),
         );
     }
 }
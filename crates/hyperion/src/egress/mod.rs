@@ -55,6 +55,13 @@ fn broadcast_chunk_deltas(
                 return;
             }
         }
+
+        for packet in chunk.block_entity_drain_packets() {
+            if let Err(e) = compose.broadcast(&packet).send() {
+                error!("failed to send block entity update packet: {e}");
+                return;
+            }
+        }
     });
     blocks.clear_should_update();
 
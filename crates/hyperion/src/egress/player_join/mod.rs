@@ -5,36 +5,82 @@ use bevy::prelude::*;
 use glam::DVec3;
 use hyperion_crafting::{Action, CraftingRegistry, RecipeBookState};
 use hyperion_utils::EntityExt;
-use tracing::{error, info};
-use valence_bytes::{CowBytes, CowUtf8Bytes, Utf8Bytes};
+use tracing::{error, info, warn};
+use valence_bytes::{CowBytes, Utf8Bytes};
 use valence_protocol::{
-    GameMode, Ident, PacketEncoder, RawBytes, VarInt,
+    CompressionThreshold, GameMode, Ident, PacketEncoder, RawBytes, VarInt,
     game_mode::OptGameMode,
     ident,
     packets::play::{
-        self, GameJoinS2c,
+        self, GameJoinS2c, ServerMetadataS2c,
+        player_position_look_s2c::PlayerPositionLookFlags,
         team_s2c::{CollisionRule, Mode, NameTagVisibility, TeamColor, TeamFlags},
     },
 };
-use valence_registry::{BiomeRegistry, RegistryCodec};
 use valence_text::IntoText;
 
-use crate::simulation::{MovementTracking, packet_state};
+use crate::simulation::{MovementTracking, encode_position, packet_state};
 
 mod list;
+mod reveal;
 pub use list::*;
+pub use reveal::*;
 
 use crate::{
     config::Config,
+    ingress::ServerPingResponse,
     net::{Channel, Compose, ConnectionId, DataBundle},
     simulation::{
-        PendingTeleportation, Position, Uuid, Yaw, skin::PlayerSkin, util::registry_codec_raw,
+        PendingTeleportation, Position, Uuid, WorldSpawn,
+        registry::{DIMENSION_TYPE, Registries},
+        skin::PlayerSkin,
     },
+    timings::timed,
 };
 
 #[derive(Event)]
 struct ProcessPlayerJoin(Entity);
 
+/// Join data which is the same for every player, such as tags, recipes and the server brand.
+///
+/// The data is rebuilt before the next player joins when the [`CraftingRegistry`], the
+/// [`ServerPingResponse`], the [`Registries`] or the compression threshold change. Plugins which
+/// change anything else that is sent on join should call [`JoinCache::invalidate`].
+#[derive(Resource, Default)]
+pub struct JoinCache {
+    version: u64,
+    cached: Option<CachedJoinData>,
+}
+
+struct CachedJoinData {
+    version: u64,
+    registries_generation: u64,
+    compression_threshold: i32,
+    dimension_names: BTreeSet<Ident>,
+    data: bytes::Bytes,
+}
+
+impl JoinCache {
+    /// Rebuilds the join data before the next player joins
+    pub const fn invalidate(&mut self) {
+        self.version += 1;
+    }
+
+    /// Increases every time the cache is invalidated
+    #[must_use]
+    pub const fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn is_stale(&self, registries: &Registries, compression_threshold: i32) -> bool {
+        self.cached.as_ref().is_none_or(|cached| {
+            cached.version != self.version
+                || cached.registries_generation != registries.generation()
+                || cached.compression_threshold != compression_threshold
+        })
+    }
+}
+
 fn add_process_player_join(
     trigger: Trigger<'_, OnAdd, PlayerSkin>,
     mut events: EventWriter<'_, ProcessPlayerJoin>,
@@ -46,14 +92,62 @@ fn process_player_join(
     mut events: EventReader<'_, '_, ProcessPlayerJoin>,
     compose: Res<'_, Compose>,
     crafting_registry: Res<'_, CraftingRegistry>,
+    ping_response: Res<'_, ServerPingResponse>,
+    registries: Res<'_, Registries>,
     config: Res<'_, Config>,
-    target_query: Query<'_, '_, (&Uuid, &Name, &ConnectionId, &Position, &Yaw, &PlayerSkin)>,
-    others_query: Query<'_, '_, (Entity, &Uuid, &Name)>,
+    world_spawn: Res<'_, WorldSpawn>,
+    target_query: Query<'_, '_, (&Name, &ConnectionId, &Position)>,
+    players: Query<'_, '_, EntityRef<'_>, (With<Uuid>, With<Name>)>,
+    reveal_rules: Res<'_, RevealRules>,
     commands: ParallelCommands<'_, '_>,
+    mut cache: ResMut<'_, JoinCache>,
 ) {
-    static CACHED_DATA: once_cell::sync::OnceCell<bytes::Bytes> = once_cell::sync::OnceCell::new();
+    if crafting_registry.is_changed() || ping_response.is_changed() {
+        cache.invalidate();
+    }
+
+    if events.is_empty() {
+        return;
+    }
+
+    let compression_level = compose.global().shared.compression_threshold;
+
+    if cache.is_stale(&registries, compression_level.0) {
+        if cache
+            .cached
+            .as_ref()
+            .is_some_and(|cached| cached.registries_generation != registries.generation())
+        {
+            warn!(
+                "registries changed after players joined; they will see the changes once they \
+                 rejoin"
+            );
+        }
+
+        let mut encoder = PacketEncoder::new();
+        encoder.set_compression(compression_level);
 
-    let crafting_registry = &crafting_registry;
+        info!("caching world data for new players with compression level {compression_level:?}");
+
+        #[expect(
+            clippy::unwrap_used,
+            reason = "this is only called on startup and when the cache is invalidated; it should \
+                      be fine. we mostly care about crashing during server execution"
+        )]
+        generate_cached_packet_bytes(&mut encoder, &crafting_registry, &ping_response).unwrap();
+
+        cache.cached = Some(CachedJoinData {
+            version: cache.version,
+            registries_generation: registries.generation(),
+            compression_threshold: compression_level.0,
+            dimension_names: registries.names(DIMENSION_TYPE),
+            data: encoder.take().freeze(),
+        });
+    }
+
+    let Some(cached) = &cache.cached else {
+        return;
+    };
 
     events.par_read().for_each(|event| {
         let mut bundle = DataBundle::new(&compose);
@@ -61,7 +155,7 @@ fn process_player_join(
         let entity_id = event.0;
         let id = entity_id.minecraft_id();
 
-        let (uuid, name, &connection_id, position, yaw, skin) = match target_query.get(entity_id) {
+        let (name, &connection_id, position) = match target_query.get(entity_id) {
             Ok(components) => components,
             Err(e) => {
                 error!("player_join_world failed: {e}");
@@ -69,23 +163,14 @@ fn process_player_join(
             }
         };
 
-        let registry_codec = registry_codec_raw();
-        let codec = RegistryCodec::default();
-
-        let dimension_names: BTreeSet<Ident> = codec
-            .registry(BiomeRegistry::KEY)
-            .iter()
-            .map(|value| value.name.clone())
-            .collect();
-
         let dimension_name = ident!("overworld");
         // let dimension_name: Ident<Cow<str>> = chunk_layer.dimension_type_name().into();
 
         let pkt = GameJoinS2c {
             entity_id: id,
             is_hardcore: false,
-            dimension_names: Cow::Owned(dimension_names),
-            registry_codec: Cow::Borrowed(registry_codec),
+            dimension_names: Cow::Borrowed(&cached.dimension_names),
+            registry_codec: Cow::Borrowed(registries.codec()),
             max_players: config.max_players.into(),
             view_distance: VarInt(i32::from(config.view_distance)),
             simulation_distance: config.simulation_distance.into(),
@@ -114,149 +199,58 @@ fn process_player_join(
         bundle.add_packet(&pkt).unwrap();
 
         let pkt = play::PlayerSpawnPositionS2c {
-            position: position.as_dvec3().into(),
-            angle: **yaw,
+            position: world_spawn.position.as_dvec3().into(),
+            angle: world_spawn.yaw,
         };
 
         bundle.add_packet(&pkt).unwrap();
 
-        let cached_data = CACHED_DATA
-            .get_or_init(|| {
-                let compression_level = compose.global().shared.compression_threshold;
-                let mut encoder = PacketEncoder::new();
-                encoder.set_compression(compression_level);
-
-                info!(
-                    "caching world data for new players with compression level \
-                     {compression_level:?}"
-                );
-
-                #[expect(
-                    clippy::unwrap_used,
-                    reason = "this is only called once on startup; it should be fine. we mostly \
-                              care about crashing during server execution"
-                )]
-                generate_cached_packet_bytes(&mut encoder, crafting_registry).unwrap();
-
-                let bytes = encoder.take();
-                bytes.freeze()
-            })
-            .clone();
-
-        bundle.add_raw(&cached_data);
+        bundle.add_raw(&cached.data);
 
         let text = play::GameMessageS2c {
             chat: format!("{name} joined the world").into_cow_text(),
             overlay: false,
         };
 
-        compose.broadcast(&text).send().unwrap();
+        if let Err(e) = compose.broadcast(&text).send() {
+            error!("failed to send join message: {e}");
+        }
 
-        // Subtracts one to exclude current player
-        let others_len = others_query.iter().len() - 1;
-        let mut entries = Vec::with_capacity(others_len);
-        let mut all_player_names = Vec::with_capacity(others_len);
+        let Ok(joiner) = players.get(entity_id) else {
+            error!("player_join_world failed: {name} has no uuid or name");
+            return;
+        };
 
-        let scope = tracing::info_span!("collect_others").entered();
-        for (current_entity, uuid, name) in others_query {
-            if entity_id == current_entity {
-                continue;
-            }
+        {
+            let _scope = tracing::info_span!("revealing_players").entered();
 
-            // Update player list entries
-            let entry = PlayerListEntry {
-                player_uuid: uuid.0,
-                username: CowUtf8Bytes::Borrowed(name),
-                properties: Cow::Owned(Vec::new()),
-                chat_data: None,
-                listed: true,
-                ping: 20,
-                game_mode: GameMode::Creative,
-                display_name: Some(name.to_string().into_cow_text()),
-            };
-
-            entries.push(entry);
-            all_player_names.push(name.to_string());
-        }
-        scope.exit();
+            if let Err(e) = reveal_players_to(joiner, &players, &reveal_rules, &mut bundle) {
+                error!("failed to reveal players to {name}: {e}");
+            }
 
-        let all_player_names = all_player_names
-            .iter()
-            .map(String::as_str)
-            .map(Into::into)
-            .collect();
+            if let Err(e) = reveal_to_players(joiner, &players, &reveal_rules, &compose) {
+                error!("failed to reveal {name} to players: {e}");
+            }
 
-        let actions = PlayerListActions::default()
-            .with_add_player(true)
-            .with_update_listed(true)
-            .with_update_display_name(true);
+            hide_entities(joiner, &players, &reveal_rules, &compose);
+        }
 
-        {
-            let _scope = tracing::info_span!("unicasting_player_list").entered();
+        // The player sees themselves, which also gives them their own skin
+        if let Some(entry) = list_entry(joiner) {
             bundle
                 .add_packet(&PlayerListS2c {
-                    actions,
-                    entries: Cow::Owned(entries),
+                    actions: PlayerListActions::default()
+                        .with_add_player(true)
+                        .with_update_listed(true)
+                        .with_update_display_name(true),
+                    entries: Cow::Owned(vec![entry]),
                 })
                 .unwrap();
         }
 
-        let PlayerSkin {
-            textures,
-            signature,
-        } = skin.clone();
-
-        // todo: in future, do not clone
-        let property = valence_protocol::profile::Property {
-            name: Utf8Bytes::from_static("textures"),
-            value: textures.into(),
-            signature: Some(signature.into()),
-        };
-
-        let property = &[property];
-
-        let singleton_entry = &[PlayerListEntry {
-            player_uuid: **uuid,
-            username: CowUtf8Bytes::Borrowed(name),
-            properties: Cow::Borrowed(property),
-            chat_data: None,
-            listed: true,
-            ping: 20,
-            game_mode: GameMode::Survival,
-            display_name: Some(name.to_string().into_cow_text()),
-        }];
-
-        let pkt = PlayerListS2c {
-            actions,
-            entries: Cow::Borrowed(singleton_entry),
-        };
-
-        compose.broadcast(&pkt).send().unwrap();
-        bundle.add_packet(&pkt).unwrap();
-
-        let player_name = vec![CowUtf8Bytes::Borrowed(name.as_str())];
-
-        compose
-            .broadcast(&play::TeamS2c {
-                team_name: Utf8Bytes::from_static("no_tag").into(),
-                mode: Mode::AddEntities {
-                    entities: player_name,
-                },
-            })
-            .exclude(connection_id)
-            .send()
-            .unwrap();
-
-        bundle
-            .add_packet(&play::TeamS2c {
-                team_name: Utf8Bytes::from_static("no_tag").into(),
-                mode: Mode::AddEntities {
-                    entities: all_player_names,
-                },
-            })
-            .unwrap();
-
-        bundle.unicast(connection_id).unwrap();
+        if let Err(e) = bundle.unicast(connection_id) {
+            error!("failed to send join packets: {e}");
+        }
 
         compose.io_buf().set_receive_broadcasts(connection_id);
 
@@ -272,6 +266,10 @@ fn process_player_join(
                     server_velocity: DVec3::ZERO,
                     sprinting: false,
                     was_on_ground: false,
+                    sent_position: encode_position(position),
+                    sent_yaw: 0.0,
+                    sent_pitch: 0.0,
+                    ticks_since_sync: 0,
                 },
                 PendingTeleportation::new(position),
                 packet_state::Play(()),
@@ -298,15 +296,25 @@ fn send_sync_tags(encoder: &mut PacketEncoder) -> anyhow::Result<()> {
 
 #[expect(
     clippy::unwrap_used,
-    reason = "this is only called once on startup; it should be fine. we mostly care about \
-              crashing during server execution"
+    reason = "this is only called on startup and when the join cache is invalidated; it should be \
+              fine. we mostly care about crashing during server execution"
 )]
 fn generate_cached_packet_bytes(
     encoder: &mut PacketEncoder,
     crafting_registry: &CraftingRegistry,
+    ping_response: &ServerPingResponse,
 ) -> anyhow::Result<()> {
     send_sync_tags(encoder)?;
 
+    // Chat is not signed, so tell clients not to warn about it
+    encoder
+        .append_packet(&ServerMetadataS2c {
+            motd: ping_response.description.as_str().into_cow_text(),
+            icon: None,
+            enforce_secure_chat: false,
+        })
+        .map_err(|e| anyhow::anyhow!(e))?;
+
     let mut buf: heapless::Vec<u8, 32> = heapless::Vec::new();
     let brand = b"hyperion";
     let brand_len = u8::try_from(brand.len()).context("brand length too long to fit in u8")?;
@@ -364,13 +372,65 @@ fn generate_cached_packet_bytes(
     Ok(())
 }
 
+/// Encodes the empty world which the proxy logs queued players into. See
+/// [`hyperion_proto::UpdateWaitingRoom`].
+pub(crate) fn waiting_room_packets(
+    registries: &Registries,
+    compression_threshold: CompressionThreshold,
+) -> anyhow::Result<bytes::Bytes> {
+    let mut encoder = PacketEncoder::new();
+    encoder.set_compression(compression_threshold);
+
+    let dimension_names = registries.names(DIMENSION_TYPE);
+
+    // The player is in spectator mode in an unloaded chunk, so they cannot move or interact with
+    // anything
+    encoder
+        .append_packet(&GameJoinS2c {
+            entity_id: 0,
+            is_hardcore: false,
+            dimension_names: Cow::Borrowed(&dimension_names),
+            registry_codec: Cow::Borrowed(registries.codec()),
+            max_players: 0.into(),
+            view_distance: VarInt(2),
+            simulation_distance: VarInt(2),
+            reduced_debug_info: true,
+            enable_respawn_screen: false,
+            dimension_name: ident!("overworld"),
+            hashed_seed: 0,
+            game_mode: GameMode::Spectator,
+            is_flat: true,
+            last_death_location: None,
+            portal_cooldown: 0.into(),
+            previous_game_mode: OptGameMode(None),
+            dimension_type_name: ident!("minecraft:overworld"),
+            is_debug: false,
+        })
+        .map_err(|e| anyhow::anyhow!(e))?;
+
+    // Closes the loading screen
+    encoder
+        .append_packet(&play::PlayerPositionLookS2c {
+            position: DVec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.0,
+            flags: PlayerPositionLookFlags::default(),
+            teleport_id: VarInt(0),
+        })
+        .map_err(|e| anyhow::anyhow!(e))?;
+
+    Ok(encoder.take().freeze())
+}
+
 #[derive(Component)]
 pub struct PlayerJoinPlugin;
 
 impl Plugin for PlayerJoinPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<JoinCache>();
         app.add_event::<ProcessPlayerJoin>();
+        reveal::build(app);
         app.add_observer(add_process_player_join);
-        app.add_systems(FixedUpdate, process_player_join);
+        app.add_systems(FixedUpdate, timed(process_player_join));
     }
 }
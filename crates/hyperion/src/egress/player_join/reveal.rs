@@ -0,0 +1,353 @@
+//! Which players are shown to each other in the player list and in the world.
+//!
+//! Every player with a [`Uuid`] and [`Name`] is revealed to other players unless a
+//! [`RevealRule`] hides them. [`Hidden`] players are always hidden, and plugins may add rules for
+//! things such as worlds or spectators. A hidden player is also left out of the player list, and
+//! the proxy never spawns their entity for the viewer. After changing anything a rule depends on,
+//! call [`RevealExt::refresh_reveal`] so the player list and entities are updated.
+//!
+//! ```ignore
+//! fn same_arena(viewer: EntityRef<'_>, target: EntityRef<'_>) -> bool {
+//!     viewer.get::<Arena>() == target.get::<Arena>()
+//! }
+//!
+//! app.world_mut().resource_mut::<RevealRules>().add(same_arena);
+//!
+//! commands.entity(player).insert(Arena(2)).refresh_reveal();
+//! ```
+
+use std::borrow::Cow;
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use tracing::error;
+use valence_bytes::{CowUtf8Bytes, Utf8Bytes};
+use valence_protocol::{
+    GameMode,
+    packets::play::{self, team_s2c::Mode},
+    profile::Property,
+};
+
+use super::{PlayerListActions, PlayerListEntry, PlayerListS2c};
+use crate::{
+    net::{ChannelId, Compose, ConnectionId, DataBundle},
+    simulation::{
+        Uuid,
+        afk::Afk,
+        packet_state,
+        player_list::{DisplayName, Nickname, list_name, profile_name},
+        skin::PlayerSkin,
+    },
+};
+
+/// The most players which are sent in one player list packet
+pub const REVEAL_PAGE_SIZE: usize = 256;
+
+/// Whether `viewer` may see `target`. Players can always see themselves.
+pub type RevealRule = fn(viewer: EntityRef<'_>, target: EntityRef<'_>) -> bool;
+
+/// The rules which decide which players are revealed to each other. A player is only revealed
+/// if every rule allows it.
+#[derive(Resource)]
+pub struct RevealRules {
+    rules: Vec<RevealRule>,
+}
+
+impl Default for RevealRules {
+    fn default() -> Self {
+        Self {
+            rules: vec![not_hidden],
+        }
+    }
+}
+
+impl RevealRules {
+    pub fn add(&mut self, rule: RevealRule) {
+        self.rules.push(rule);
+    }
+
+    #[must_use]
+    pub fn can_see(&self, viewer: EntityRef<'_>, target: EntityRef<'_>) -> bool {
+        viewer.id() == target.id() || self.rules.iter().all(|rule| rule(viewer, target))
+    }
+}
+
+/// Hides a player from every other player, such as when they are vanished
+#[derive(Component, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Hidden;
+
+fn not_hidden(_viewer: EntityRef<'_>, target: EntityRef<'_>) -> bool {
+    !target.contains::<Hidden>()
+}
+
+/// Updates which players the target player can see, and which players can see the target player,
+/// using the current [`RevealRules`]
+#[derive(Event, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RefreshReveal;
+
+pub trait RevealExt {
+    /// Updates the player list of this player and the player lists of other players by triggering
+    /// [`RefreshReveal`]
+    fn refresh_reveal(&mut self) -> &mut Self;
+}
+
+impl RevealExt for EntityCommands<'_> {
+    fn refresh_reveal(&mut self) -> &mut Self {
+        self.trigger(RefreshReveal)
+    }
+}
+
+const ACTIONS: PlayerListActions = PlayerListActions::new()
+    .with_add_player(true)
+    .with_update_listed(true)
+    .with_update_display_name(true);
+
+/// The player list entry of a player, including their skin if they have one
+pub(crate) fn list_entry(player: EntityRef<'_>) -> Option<PlayerListEntry<'_>> {
+    let uuid = player.get::<Uuid>()?;
+    let name = player.get::<Name>()?;
+    let nickname = player.get::<Nickname>();
+
+    let properties = player
+        .get::<PlayerSkin>()
+        .map(|skin| Property {
+            name: Utf8Bytes::from_static("textures"),
+            value: skin.textures.clone().into(),
+            signature: Some(skin.signature.clone().into()),
+        })
+        .into_iter()
+        .collect();
+
+    Some(PlayerListEntry {
+        player_uuid: uuid.0,
+        username: CowUtf8Bytes::Borrowed(profile_name(name, nickname)),
+        properties: Cow::Owned(properties),
+        chat_data: None,
+        listed: true,
+        ping: 20,
+        game_mode: GameMode::Survival,
+        display_name: Some(Cow::Owned(list_name(
+            name,
+            nickname,
+            player.get::<DisplayName>(),
+            player.contains::<Afk>(),
+        ))),
+    })
+}
+
+/// Adds every player which `viewer` can see to the player list of `viewer`, in pages of
+/// [`REVEAL_PAGE_SIZE`] players
+pub(crate) fn reveal_players_to<'a>(
+    viewer: EntityRef<'_>,
+    players: impl IntoIterator<Item = EntityRef<'a>>,
+    rules: &RevealRules,
+    bundle: &mut DataBundle<'_>,
+) -> anyhow::Result<()> {
+    let entries: Vec<_> = players
+        .into_iter()
+        .filter(|&player| player.id() != viewer.id() && rules.can_see(viewer, player))
+        .filter_map(list_entry)
+        .collect();
+
+    for page in entries.chunks(REVEAL_PAGE_SIZE) {
+        bundle.add_packet(&PlayerListS2c {
+            actions: ACTIONS,
+            entries: Cow::Borrowed(page),
+        })?;
+
+        bundle.add_packet(&play::TeamS2c {
+            team_name: Utf8Bytes::from_static("no_tag").into(),
+            mode: Mode::AddEntities {
+                entities: page.iter().map(|entry| entry.username.clone()).collect(),
+            },
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Adds `target` to the player list of every player in the play state who can see it. The
+/// target itself is skipped.
+pub(crate) fn reveal_to_players<'a>(
+    target: EntityRef<'_>,
+    players: impl IntoIterator<Item = EntityRef<'a>> + Clone,
+    rules: &RevealRules,
+    compose: &Compose,
+) -> anyhow::Result<()> {
+    let Some(entry) = list_entry(target) else {
+        return Ok(());
+    };
+
+    let target_connection = target.get::<ConnectionId>().copied();
+
+    let team = play::TeamS2c {
+        team_name: Utf8Bytes::from_static("no_tag").into(),
+        mode: Mode::AddEntities {
+            entities: vec![entry.username.clone()],
+        },
+    };
+
+    let entries = [entry];
+    let list = PlayerListS2c {
+        actions: ACTIONS,
+        entries: Cow::Borrowed(&entries),
+    };
+
+    let viewers = players
+        .into_iter()
+        .filter(|player| player.id() != target.id() && player.contains::<packet_state::Play>());
+
+    // Most players can be seen by everyone, so broadcast unless a rule hides the target
+    if viewers.clone().all(|viewer| rules.can_see(viewer, target)) {
+        compose.broadcast(&list).exclude(target_connection).send()?;
+        compose.broadcast(&team).exclude(target_connection).send()?;
+        return Ok(());
+    }
+
+    for viewer in viewers {
+        let Some(&connection_id) = viewer.get::<ConnectionId>() else {
+            continue;
+        };
+
+        if rules.can_see(viewer, target) {
+            compose.unicast(&list, connection_id)?;
+            compose.unicast(&team, connection_id)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes `target` from the player list of `viewer`
+fn conceal(target: EntityRef<'_>, viewer: ConnectionId, compose: &Compose) -> anyhow::Result<()> {
+    let Some(uuid) = target.get::<Uuid>() else {
+        return Ok(());
+    };
+
+    compose.unicast(
+        &PlayerListS2c {
+            actions: PlayerListActions::new().with_update_listed(true),
+            entries: Cow::Owned(vec![PlayerListEntry {
+                player_uuid: uuid.0,
+                listed: false,
+                ..Default::default()
+            }]),
+        },
+        viewer,
+    )?;
+
+    Ok(())
+}
+
+/// Hides the entity of `target` from `viewer` if `viewer` cannot see it. If `show` is set, the
+/// entity is otherwise shown to `viewer` again.
+fn update_entity_visibility(
+    viewer: EntityRef<'_>,
+    target: EntityRef<'_>,
+    rules: &RevealRules,
+    compose: &Compose,
+    show: bool,
+) {
+    if viewer.id() == target.id() {
+        return;
+    }
+
+    let Some(&connection_id) = viewer.get::<ConnectionId>() else {
+        return;
+    };
+
+    let hidden = !rules.can_see(viewer, target);
+    if hidden || show {
+        compose
+            .io_buf()
+            .set_channel_hidden(ChannelId::from(target.id()), connection_id, hidden);
+    }
+}
+
+/// Hides the entity of `player` from every player who cannot see it, and hides the entities of
+/// every player who `player` cannot see from `player`. Entities are visible by default, so this
+/// is only needed when `player` joins.
+pub(crate) fn hide_entities<'a>(
+    player: EntityRef<'_>,
+    players: impl IntoIterator<Item = EntityRef<'a>>,
+    rules: &RevealRules,
+    compose: &Compose,
+) {
+    for other in players {
+        update_entity_visibility(player, other, rules, compose, false);
+        update_entity_visibility(other, player, rules, compose, false);
+    }
+}
+
+fn refresh_reveal(
+    trigger: Trigger<'_, RefreshReveal>,
+    players: Query<'_, '_, EntityRef<'_>, (With<Uuid>, With<Name>)>,
+    rules: Res<'_, RevealRules>,
+    compose: Res<'_, Compose>,
+) {
+    let Ok(player) = players.get(trigger.target()) else {
+        return;
+    };
+
+    if let Err(e) = refresh(player, &players, &rules, &compose) {
+        error!("failed to refresh revealed players: {e}");
+    }
+}
+
+fn refresh<'a>(
+    player: EntityRef<'_>,
+    players: impl IntoIterator<Item = EntityRef<'a>> + Clone,
+    rules: &RevealRules,
+    compose: &Compose,
+) -> anyhow::Result<()> {
+    // Players are revealed when they join
+    if !player.contains::<packet_state::Play>() {
+        return Ok(());
+    }
+
+    if let Some(&connection_id) = player.get::<ConnectionId>() {
+        let mut bundle = DataBundle::new(compose);
+        reveal_players_to(player, players.clone(), rules, &mut bundle)?;
+        bundle.unicast(connection_id)?;
+
+        for other in players.clone() {
+            if !rules.can_see(player, other) {
+                conceal(other, connection_id, compose)?;
+            }
+        }
+    }
+
+    reveal_to_players(player, players.clone(), rules, compose)?;
+
+    for other in players {
+        if !other.contains::<packet_state::Play>() {
+            continue;
+        }
+
+        if let Some(&connection_id) = other.get::<ConnectionId>()
+            && !rules.can_see(other, player)
+        {
+            conceal(player, connection_id, compose)?;
+        }
+
+        update_entity_visibility(player, other, rules, compose, true);
+        update_entity_visibility(other, player, rules, compose, true);
+    }
+
+    Ok(())
+}
+
+fn hide(trigger: Trigger<'_, OnInsert, Hidden>, mut commands: Commands<'_, '_>) {
+    commands.trigger_targets(RefreshReveal, trigger.target());
+}
+
+fn unhide(trigger: Trigger<'_, OnRemove, Hidden>, mut commands: Commands<'_, '_>) {
+    // This is queued so that the rules see the player after Hidden was removed
+    commands.trigger_targets(RefreshReveal, trigger.target());
+}
+
+pub(crate) fn build(app: &mut App) {
+    app.init_resource::<RevealRules>();
+    app.add_observer(refresh_reveal);
+    app.add_observer(hide);
+    app.add_observer(unhide);
+}
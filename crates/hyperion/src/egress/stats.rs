@@ -3,13 +3,21 @@ use bevy::prelude::*;
 use crate::{
     net::Compose,
     simulation::{blocks::Blocks, packet_state},
+    tick_rate::AdvanceTick,
+    timings::timed,
 };
 
 pub struct StatsPlugin;
 
 impl Plugin for StatsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(FixedUpdate, (global_update, load_pending));
+        app.add_systems(
+            FixedUpdate,
+            (
+                timed(global_update).in_set(AdvanceTick),
+                timed(load_pending),
+            ),
+        );
         app.add_observer(player_join_world);
         app.add_observer(player_leave_world);
     }
@@ -12,12 +12,13 @@ use valence_protocol::{
 
 use crate::{
     config::Config,
-    net::{Compose, ConnectionId, DataBundle},
+    net::{Compose, ConnectionId, DataBundle, Priority},
     simulation::{
-        ChunkPosition, Position,
+        ChunkPosition, ClientSettings, Position,
         blocks::{Blocks, GetChunk},
         packet_state,
     },
+    timings::timed,
 };
 
 #[derive(Component, Deref, DerefMut, Default)]
@@ -31,8 +32,12 @@ impl Plugin for SyncChunksPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             FixedUpdate,
-            (generate_chunk_changes, send_full_loaded_chunks),
+            (
+                timed(generate_chunk_changes),
+                timed(send_full_loaded_chunks),
+            ),
         );
+        app.add_observer(clear_chunk_subscriptions);
     }
 }
 
@@ -47,16 +52,18 @@ fn generate_chunk_changes(
             &mut ChunkPosition,
             &mut ChunkSendQueue,
             &Position,
+            &ClientSettings,
         ),
         With<packet_state::Play>,
     >,
 ) {
     let compose = compose.into_inner();
-    let radius = config.view_distance;
-    let liberal_radius = radius + 2;
-    query
-        .par_iter_mut()
-        .for_each(|(&stream_id, mut last_sent, mut chunk_changes, pose)| {
+    query.par_iter_mut().for_each(
+        |(&stream_id, mut last_sent, mut chunk_changes, pose, settings)| {
+            // Chunks past the render distance of the client would not be shown
+            let radius = config.view_distance.min(i16::from(settings.view_distance));
+            let liberal_radius = radius + 2;
+
             let last_sent_chunk = last_sent.position;
 
             let current_chunk = pose.to_chunk();
@@ -101,18 +108,29 @@ fn generate_chunk_changes(
                 .clone()
                 .cartesian_product(last_sent_range_z.clone())
                 .filter(|(x, y)| !current_range_x.contains(x) || !current_range_z.contains(y))
-                .map(|(x, y)| I16Vec2::new(x, y));
+                .map(|(x, y)| I16Vec2::new(x, y))
+                .collect_vec();
 
             let mut bundle = DataBundle::new(compose);
 
-            for chunk in removed_chunks {
+            for chunk in &removed_chunks {
                 let pos = ChunkPos::new(i32::from(chunk.x), i32::from(chunk.y));
                 let unload_chunk = play::UnloadChunkS2c { pos };
 
                 bundle.add_packet(&unload_chunk).unwrap();
             }
 
-            bundle.unicast(stream_id).unwrap();
+            if let Err(e) = bundle.unicast(stream_id) {
+                error!("failed to send chunk changes: {e}");
+            }
+
+            let removed_chunks = removed_chunks
+                .into_iter()
+                .map(hyperion_proto::ChunkPosition::from)
+                .collect_vec();
+            compose
+                .io_buf()
+                .update_chunk_subscriptions(stream_id, false, &[], &removed_chunks);
 
             let added_chunks = current_range_x
                 .cartesian_product(current_range_z)
@@ -154,7 +172,8 @@ fn generate_chunk_changes(
                 });
                 chunk_changes.dedup();
             }
-        });
+        },
+    );
 }
 
 fn send_full_loaded_chunks(
@@ -168,8 +187,10 @@ fn send_full_loaded_chunks(
         let last = None;
 
         let mut iter_count = 0;
+        let mut sent_chunks = Vec::new();
 
-        let mut bundle = DataBundle::new(&compose);
+        // Chunks which the proxy drops are sent again after it reports `BulkShed`
+        let mut bundle = DataBundle::new(&compose).priority(Priority::Bulk);
 
         #[expect(
             clippy::cast_possible_wrap,
@@ -203,7 +224,14 @@ fn send_full_loaded_chunks(
                 GetChunk::Loaded(chunk) => {
                     bundle.add_raw(&chunk.base_packet_bytes);
 
+                    for packet in chunk.block_entity_packets() {
+                        if let Err(e) = bundle.add_packet(&packet) {
+                            error!("failed to add block entity update packet: {e}");
+                        }
+                    }
+
                     iter_count += 1;
+                    sent_chunks.push(hyperion_proto::ChunkPosition::from(elem));
                     #[expect(clippy::cast_sign_loss, reason = "we are checking if < 0")]
                     queue.changes.swap_remove(idx as usize);
                 }
@@ -213,6 +241,28 @@ fn send_full_loaded_chunks(
             idx -= 1;
         }
 
-        bundle.unicast(stream_id).unwrap();
+        if let Err(e) = bundle.unicast(stream_id) {
+            error!("failed to send chunks: {e}");
+        }
+
+        // Local broadcasts in these chunks are sent to the player from now on
+        compose
+            .io_buf()
+            .update_chunk_subscriptions(stream_id, false, &sent_chunks, &[]);
     });
 }
+
+/// Unsubscribes players who leave from every chunk
+fn clear_chunk_subscriptions(
+    trigger: Trigger<'_, OnRemove, packet_state::Play>,
+    query: Query<'_, '_, &ConnectionId>,
+    compose: Res<'_, Compose>,
+) {
+    let Ok(&stream_id) = query.get(trigger.target()) else {
+        return;
+    };
+
+    compose
+        .io_buf()
+        .update_chunk_subscriptions(stream_id, true, &[], &[]);
+}
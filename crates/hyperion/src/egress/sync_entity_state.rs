@@ -1,27 +1,34 @@
 use bevy::{ecs::batching::BatchingStrategy, prelude::*};
-use glam::{IVec3, Vec3};
+use glam::{I64Vec3, IVec3, Vec3};
+use hyperion_inventory::PlayerInventory;
 use hyperion_utils::{EntityExt, Prev, track_prev};
 use itertools::Either;
 use tracing::error;
 use valence_bytes::CowBytes;
 use valence_protocol::{
-    ByteAngle, RawBytes, VarInt,
+    ByteAngle, Hand, RawBytes, VarInt,
     packets::play::{self},
 };
 
 use crate::{
     Blocks,
+    config::Config,
     net::{Compose, ConnectionId, DataBundle},
     simulation::{
         EntitySize, Flight, MovementTracking, Owner, PendingTeleportation, Pitch, Position,
-        Velocity, Xp, Yaw,
-        animation::ActiveAnimation,
-        event,
+        SentMovement, Velocity, Xp, Yaw,
+        animation::{self, ActiveAnimation},
+        encode_position, event,
         event::HitGroundEvent,
         handlers::is_grounded,
-        metadata::{MetadataChanges, get_and_clear_metadata},
+        metadata::{
+            EncodeMetadata, MetadataChanges, get_and_clear_metadata, player::DisplayedSkinParts,
+        },
+        physics::Physics,
+        util::enchantment_level,
     },
     spatial::{SpatialIndex, get_first_collision},
+    tick_rate::AdvanceTick,
 };
 
 pub struct EntityStateSyncPlugin;
@@ -40,7 +47,9 @@ fn entity_xp_sync(
                 total_xp: VarInt::default(),
             };
 
-            compose.unicast(&packet, connection_id).unwrap();
+            if let Err(e) = compose.unicast(&packet, connection_id) {
+                error!("failed to send experience: {e}");
+            }
         }
     }
 }
@@ -57,45 +66,121 @@ fn entity_metadata_sync(
                 entity_id: VarInt(entity_id.minecraft_id()),
                 tracked_values: RawBytes(CowBytes::Borrowed(&view)),
             };
-            compose
-                .broadcast_channel(&pkt, entity_id.into())
-                .send()
-                .unwrap();
+            if let Err(e) = compose.broadcast_channel(&pkt, entity_id.into()).send() {
+                error!("failed to send entity metadata: {e}");
+            }
         }
     }
 }
 
+/// Metadata changes are only broadcast to the players who can see the entity, so players are sent
+/// their own skin layers separately. The client needs these to render its own skin in third person
+/// and in the inventory.
+fn own_skin_parts_sync(
+    compose: Res<'_, Compose>,
+    query: Query<'_, '_, (Entity, &ConnectionId, &DisplayedSkinParts), Changed<DisplayedSkinParts>>,
+) {
+    for (entity, &connection_id, skin_parts) in &query {
+        let mut metadata = MetadataChanges::default();
+        metadata.encode(skin_parts.clone());
+
+        let Some(view) = get_and_clear_metadata(&mut metadata) else {
+            continue;
+        };
+
+        let pkt = play::EntityTrackerUpdateS2c {
+            entity_id: VarInt(entity.minecraft_id()),
+            tracked_values: RawBytes(CowBytes::Borrowed(&view)),
+        };
+
+        if let Err(e) = compose.unicast(&pkt, connection_id) {
+            error!("failed to send skin parts: {e}");
+        }
+    }
+}
+
+fn swing_arm_animation(
+    mut events: EventReader<'_, '_, event::SwingArm>,
+    mut query: Query<'_, '_, &mut ActiveAnimation>,
+) {
+    for event in events.read() {
+        let Ok(mut animation) = query.get_mut(event.client) else {
+            continue;
+        };
+
+        animation.push(match event.hand {
+            Hand::Main => animation::Kind::SwingMainArm,
+            Hand::Off => animation::Kind::SwingOffHand,
+        });
+    }
+}
+
+/// Animations are sent to the players who can see the entity. Players already play their own
+/// animations, so they are not sent back to them.
 fn active_animation_sync(
     compose: Res<'_, Compose>,
-    mut query: Query<'_, '_, (Entity, &ConnectionId, &mut ActiveAnimation)>,
+    mut query: Query<'_, '_, (Entity, Option<&ConnectionId>, &mut ActiveAnimation)>,
 ) {
-    for (entity, &connection_id, mut animation) in &mut query {
+    for (entity, connection_id, mut animation) in &mut query {
         let entity_id = VarInt(entity.minecraft_id());
 
         for pkt in animation.packets(entity_id) {
-            compose
+            if let Err(e) = compose
                 .broadcast_channel(&pkt, entity.into())
-                .exclude(Some(connection_id))
+                .exclude(connection_id.copied())
                 .send()
-                .unwrap();
+            {
+                error!("failed to send animation: {e}");
+            }
         }
 
         animation.clear();
     }
 }
 
+/// Sends [`event::Hurt`] to the players who can see the entity and to the entity itself, which is
+/// not subscribed to its own channel
+fn hurt_animation_sync(
+    compose: Res<'_, Compose>,
+    mut events: EventReader<'_, '_, event::Hurt>,
+    query: Query<'_, '_, &ConnectionId>,
+) {
+    // The source ids are optional and encoded as the entity id plus one
+    let source_id =
+        |source: Option<Entity>| VarInt(source.map_or(0, |source| source.minecraft_id() + 1));
+
+    for event in events.read() {
+        let pkt = play::EntityDamageS2c {
+            entity_id: VarInt(event.target.minecraft_id()),
+            source_type_id: VarInt(event.damage_type),
+            source_cause_id: source_id(event.source_cause),
+            source_direct_id: source_id(event.source_direct),
+            source_pos: None,
+        };
+
+        if let Err(e) = compose.broadcast_channel(&pkt, event.target.into()).send() {
+            error!("failed to send hurt animation: {e}");
+        }
+
+        if let Ok(&connection_id) = query.get(event.target) {
+            if let Err(e) = compose.unicast(&pkt, connection_id) {
+                error!("failed to send hurt animation: {e}");
+            }
+        }
+    }
+}
+
 /// What ever you do DO NOT!!! I REPEAT DO NOT SET VELOCITY ANYWHERE
 /// IF YOU WANT TO APPLY VELOCITY SEND 1 VELOCITY PAKCET WHEN NEEDED LOOK in events/tag/src/module/attack.rs
 fn sync_player_entity(
     compose: Res<'_, Compose>,
     blocks: Res<'_, Blocks>,
+    config: Res<'_, Config>,
     mut query: Query<
         '_,
         '_,
         (
             Entity,
-            &Prev<Yaw>,
-            &Prev<Pitch>,
             &Position,
             &mut Velocity,
             &Yaw,
@@ -103,6 +188,7 @@ fn sync_player_entity(
             Option<&mut PendingTeleportation>,
             &mut MovementTracking,
             &Flight,
+            Option<&PlayerInventory>,
         ),
     >,
     mut event_writer: EventWriter<'_, HitGroundEvent>,
@@ -118,8 +204,6 @@ fn sync_player_entity(
         .for_each(
             |(
                 entity,
-                prev_yaw,
-                prev_pitch,
                 position,
                 mut velocity,
                 yaw,
@@ -127,6 +211,7 @@ fn sync_player_entity(
                 pending_teleport,
                 mut tracking,
                 flight,
+                inventory,
             )| {
                 let entity_id = VarInt(entity.minecraft_id());
 
@@ -134,20 +219,46 @@ fn sync_player_entity(
                     if pending_teleport.ttl == 0 {
                         // This needs to trigger OnInsert, so pending_teleport cannot be modified directly
                         commands.command_scope(|mut commands| {
-                            commands
-                                .entity(entity)
-                                .insert(PendingTeleportation::new(pending_teleport.destination));
+                            commands.entity(entity).insert(pending_teleport.resend());
                         });
                     } else {
                         pending_teleport.ttl -= 1;
                     }
                 } else {
                     let position_delta = **position - tracking.last_tick_position;
-                    let needs_teleport = position_delta.abs().max_element() >= 8.0;
-                    let changed_position = **position != tracking.last_tick_position;
 
-                    let look_changed = (**yaw - ***prev_yaw).abs() >= 0.01
-                        || (**pitch - ***prev_pitch).abs() >= 0.01;
+                    // Relative moves are limited to an i16 in units of 1/4096 of a block, which is
+                    // just under 8 blocks
+                    let sent_position = encode_position(**position);
+                    let sent_delta = sent_position - tracking.sent_position;
+                    let needs_teleport = sent_delta.abs().max_element() > i64::from(i16::MAX);
+
+                    // Movement below the thresholds adds up until it is sent
+                    let thresholds = &config.movement_sync;
+                    tracking.ticks_since_sync += 1;
+                    let force_sync = tracking.ticks_since_sync >= thresholds.force_sync_ticks;
+                    if force_sync {
+                        tracking.ticks_since_sync = 0;
+                    }
+
+                    #[expect(
+                        clippy::cast_precision_loss,
+                        reason = "precision is not needed to compare with the threshold"
+                    )]
+                    let sent_distance = sent_delta.as_vec3().length() / 4096.0;
+                    let changed_position = sent_delta != I64Vec3::ZERO
+                        && (force_sync || sent_distance >= thresholds.min_position_delta);
+                    #[expect(
+                        clippy::cast_possible_truncation,
+                        reason = "the delta is only sent if it fits in an i16"
+                    )]
+                    let sent_delta = sent_delta.to_array().map(|x| x as i16);
+
+                    let rotation_delta = (**yaw - tracking.sent_yaw)
+                        .abs()
+                        .max((**pitch - tracking.sent_pitch).abs());
+                    let look_changed = rotation_delta > 0.0
+                        && (force_sync || rotation_delta >= thresholds.min_rotation_delta);
 
                     let mut bundle = DataBundle::new(&compose);
 
@@ -172,15 +283,15 @@ fn sync_player_entity(
 
                     let grounded = is_grounded(position, &blocks);
                     tracking.was_on_ground = grounded;
-                    if grounded
-                        && !tracking.last_tick_flying
-                        && tracking.fall_start_y - position.y > 3.
-                    {
-                        let event = HitGroundEvent {
-                            client: entity,
-                            fall_distance: tracking.fall_start_y - position.y,
-                        };
-                        events.push(event);
+                    let fall_distance = tracking.fall_distance(position);
+                    if grounded && !tracking.last_tick_flying && fall_distance > 3. {
+                        let feather_falling = inventory.map_or(0, |inventory| {
+                            enchantment_level(
+                                &inventory.get_boots().stack,
+                                "minecraft:feather_falling",
+                            )
+                        });
+                        events.push(HitGroundEvent::new(entity, fall_distance, feather_falling));
                         tracking.fall_start_y = position.y;
                     }
 
@@ -191,8 +302,7 @@ fn sync_player_entity(
                     if changed_position && !needs_teleport && look_changed {
                         let packet = play::RotateAndMoveRelativeS2c {
                             entity_id,
-                            #[allow(clippy::cast_possible_truncation)]
-                            delta: (position_delta * 4096.0).to_array().map(|x| x as i16),
+                            delta: sent_delta,
                             yaw: ByteAngle::from_degrees(**yaw),
                             pitch: ByteAngle::from_degrees(**pitch),
                             on_ground: grounded,
@@ -203,8 +313,7 @@ fn sync_player_entity(
                         if changed_position && !needs_teleport {
                             let packet = play::MoveRelativeS2c {
                                 entity_id,
-                                #[allow(clippy::cast_possible_truncation)]
-                                delta: (position_delta * 4096.0).to_array().map(|x| x as i16),
+                                delta: sent_delta,
                                 on_ground: grounded,
                             };
 
@@ -221,6 +330,9 @@ fn sync_player_entity(
 
                             bundle.add_packet(&packet).unwrap();
                         }
+                    }
+
+                    if look_changed {
                         let packet = play::EntitySetHeadYawS2c {
                             entity_id,
                             head_yaw: ByteAngle::from_degrees(**yaw),
@@ -251,7 +363,18 @@ fn sync_player_entity(
                         velocity.0 = Vec3::ZERO;
                     }
 
-                    bundle.broadcast_channel(entity.into()).unwrap();
+                    if let Err(e) = bundle.broadcast_channel(entity.into()) {
+                        error!("failed to send player movement: {e}");
+                    }
+
+                    if changed_position || needs_teleport {
+                        tracking.sent_position = sent_position;
+                    }
+
+                    if look_changed || needs_teleport {
+                        tracking.sent_yaw = **yaw;
+                        tracking.sent_pitch = **pitch;
+                    }
                 }
 
                 tracking.received_movement_packets = 0;
@@ -294,6 +417,134 @@ fn sync_player_entity(
     event_writer.write_batch(events);
 }
 
+/// Sends the movement of entities which are not players, such as mobs moved by [`Physics`]. The
+/// packets are encoded once and broadcast to the channel of the entity, so the work does not grow
+/// with the number of players watching it.
+fn sync_entity_movement(
+    compose: Res<'_, Compose>,
+    config: Res<'_, Config>,
+    mut query: Query<
+        '_,
+        '_,
+        (
+            Entity,
+            &Position,
+            &Yaw,
+            &Pitch,
+            &mut SentMovement,
+            Option<&Physics>,
+        ),
+    >,
+) {
+    let thresholds = &config.movement_sync;
+
+    query
+        .par_iter_mut()
+        .for_each(|(entity, position, yaw, pitch, mut sent, physics)| {
+            let entity_id = VarInt(entity.minecraft_id());
+            let on_ground = physics.is_some_and(|physics| physics.on_ground);
+
+            sent.ticks_since_sync += 1;
+            let force_sync = sent.ticks_since_sync >= thresholds.force_sync_ticks;
+            if force_sync {
+                sent.ticks_since_sync = 0;
+            }
+
+            let sent_position = encode_position(**position);
+            let sent_delta = sent_position - sent.position;
+            let needs_teleport = sent_delta.abs().max_element() > i64::from(i16::MAX);
+
+            #[expect(
+                clippy::cast_precision_loss,
+                reason = "precision is not needed to compare with the threshold"
+            )]
+            let sent_distance = sent_delta.as_vec3().length() / 4096.0;
+            let changed_position = sent_delta != I64Vec3::ZERO
+                && (force_sync || sent_distance >= thresholds.min_position_delta);
+
+            let rotation_delta = (**yaw - sent.yaw).abs().max((**pitch - sent.pitch).abs());
+            let look_changed = rotation_delta > 0.0
+                && (force_sync || rotation_delta >= thresholds.min_rotation_delta);
+
+            if !changed_position && !look_changed {
+                return;
+            }
+
+            let mut bundle = DataBundle::new(&compose);
+            let yaw_angle = ByteAngle::from_degrees(**yaw);
+            let pitch_angle = ByteAngle::from_degrees(**pitch);
+
+            if changed_position && needs_teleport {
+                let packet = play::EntityPositionS2c {
+                    entity_id,
+                    position: position.as_dvec3(),
+                    yaw: yaw_angle,
+                    pitch: pitch_angle,
+                    on_ground,
+                };
+
+                bundle.add_packet(&packet).unwrap();
+            } else if changed_position {
+                #[expect(
+                    clippy::cast_possible_truncation,
+                    reason = "the delta is only sent if it fits in an i16"
+                )]
+                let delta = sent_delta.to_array().map(|x| x as i16);
+
+                if look_changed {
+                    let packet = play::RotateAndMoveRelativeS2c {
+                        entity_id,
+                        delta,
+                        yaw: yaw_angle,
+                        pitch: pitch_angle,
+                        on_ground,
+                    };
+
+                    bundle.add_packet(&packet).unwrap();
+                } else {
+                    let packet = play::MoveRelativeS2c {
+                        entity_id,
+                        delta,
+                        on_ground,
+                    };
+
+                    bundle.add_packet(&packet).unwrap();
+                }
+            } else {
+                let packet = play::RotateS2c {
+                    entity_id,
+                    yaw: yaw_angle,
+                    pitch: pitch_angle,
+                    on_ground,
+                };
+
+                bundle.add_packet(&packet).unwrap();
+            }
+
+            if look_changed {
+                let packet = play::EntitySetHeadYawS2c {
+                    entity_id,
+                    head_yaw: yaw_angle,
+                };
+
+                bundle.add_packet(&packet).unwrap();
+            }
+
+            if let Err(e) = bundle.broadcast_channel(entity.into()) {
+                error!("failed to send entity movement: {e}");
+            }
+
+            if changed_position {
+                sent.position = sent_position;
+            }
+
+            if look_changed || needs_teleport {
+                sent.yaw = **yaw;
+                sent.pitch = **pitch;
+            }
+        });
+}
+
 fn update_projectile_positions(
     arrow_query: Query<'_, '_, (Entity, &Owner)>,
     mut query_set: ParamSet<
@@ -379,16 +630,18 @@ impl Plugin for EntityStateSyncPlugin {
             FixedPostUpdate,
             (
                 entity_xp_sync,
-                entity_metadata_sync,
-                active_animation_sync,
+                entity_metadata_sync.after(EncodeMetadata),
+                own_skin_parts_sync,
+                swing_arm_animation,
+                active_animation_sync.after(swing_arm_animation),
+                hurt_animation_sync,
                 sync_player_entity,
-                update_projectile_positions,
+                sync_entity_movement,
+                update_projectile_positions.in_set(AdvanceTick),
             ),
         );
 
         track_prev::<Xp>(app);
         track_prev::<Position>(app);
-        track_prev::<Yaw>(app);
-        track_prev::<Pitch>(app);
     }
 }
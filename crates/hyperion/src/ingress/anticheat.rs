@@ -0,0 +1,533 @@
+//! Detects movement and interactions which are not possible for a vanilla client.
+//!
+//! Checks do not punish players. Instead, they send [`SuspicionEvent`]s so that game modes can
+//! decide on their own policy, such as kicking players whose recent scores add up to more than a
+//! threshold. Game modes may also send [`SuspicionEvent`]s from their own checks with
+//! [`Check::Other`].
+//!
+//! The exception is reach. Breaking, placing and using blocks which are out of reach or behind a
+//! wall is cancelled, and so are melee attacks, because the client would not have been able to do
+//! them.
+
+use std::iter;
+
+use bevy::{ecs::entity::Entities, prelude::*};
+use geometry::aabb::Aabb;
+use tracing::error;
+use valence_generated::block::{BlockKind, BlockState};
+use valence_protocol::{
+    GameMode,
+    packets::play::{
+        player_action_c2s::PlayerAction, player_interact_entity_c2s::EntityInteraction,
+    },
+};
+
+use crate::{
+    ingress,
+    simulation::{
+        EntitySize, Flight, MovementTracking, PendingTeleportation, Pitch, Position, Yaw, aabb,
+        blocks::Blocks,
+        event::{CancelEvents, Cancellable, DestroyBlock, PlaceBlock, ToggleDoor},
+        get_direction_from_rotation,
+        handlers::is_grounded,
+        packet::play,
+        packet_state,
+        world_event::PlayerGameMode,
+    },
+    timings::timed,
+};
+
+/// The height of a player's eyes above their feet while standing
+pub const EYE_HEIGHT: f32 = 1.62;
+
+/// The check which detected a [`SuspicionEvent`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// Moving horizontally faster than [`AnticheatConfig::max_speed`]
+    Speed,
+    /// Staying in the air without falling for longer than [`AnticheatConfig::max_air_ticks`]
+    Fly,
+    /// Interacting with a block or entity which is out of reach
+    Reach,
+    /// Interacting with a block or entity which the player is not looking at
+    Angle,
+    /// Interacting with a block or entity through a wall
+    LineOfSight,
+    /// Breaking a block faster than is possible with the held tool. The score is the fraction of
+    /// the block which was not broken yet.
+    FastBreak,
+    /// A check which is not part of hyperion
+    Other(&'static str),
+}
+
+/// Sent when a player does something which a vanilla client would not do. This may also be
+/// caused by lag, so a single event should not be treated as proof of cheating.
+#[derive(Event, Copy, Clone, Debug, PartialEq)]
+pub struct SuspicionEvent {
+    pub player: Entity,
+    pub check: Check,
+    /// How far the player exceeded the limit of the check relative to the limit. For example, a
+    /// score of 0.5 for [`Check::Reach`] means the player reached 50% further than allowed.
+    pub score: f32,
+}
+
+/// The limits used by the checks. Lower limits detect more cheats, but they also cause more false
+/// positives for players with high latency.
+#[derive(Resource, Copy, Clone, Debug, PartialEq)]
+pub struct AnticheatConfig {
+    /// Maximum horizontal blocks moved per tick while not flying. Sprint jumping moves about 0.6
+    /// blocks per tick.
+    pub max_speed: f32,
+    /// Maximum horizontal blocks moved per tick while flying
+    pub max_flying_speed: f32,
+    /// Maximum number of consecutive ticks in which a player who may not fly can move without
+    /// touching the ground or falling. A jump rises for about 6 ticks.
+    pub max_air_ticks: u16,
+    /// Maximum distance from the eyes of a player to a block they interact with. Interactions
+    /// with blocks which are further away are cancelled.
+    pub block_reach: f32,
+    /// [`AnticheatConfig::block_reach`] for players in creative, who reach further
+    pub creative_block_reach: f32,
+    /// Maximum distance from the eyes of a player to the bounding box of an entity they attack.
+    /// Attacks on entities which are further away are cancelled.
+    pub entity_reach: f32,
+    /// Maximum angle in degrees between where a player is looking and the center of what they
+    /// interact with. Larger targets are given more leeway.
+    pub max_angle: f32,
+}
+
+impl Default for AnticheatConfig {
+    fn default() -> Self {
+        Self {
+            max_speed: 1.0,
+            max_flying_speed: 2.0,
+            max_air_ticks: 20,
+            block_reach: 4.5,
+            creative_block_reach: 5.0,
+            entity_reach: 3.0,
+            max_angle: 60.0,
+        }
+    }
+}
+
+impl AnticheatConfig {
+    /// The block reach of a player, who is in survival if they have no [`PlayerGameMode`]
+    #[must_use]
+    pub const fn block_reach_of(&self, game_mode: Option<&PlayerGameMode>) -> f32 {
+        match game_mode {
+            Some(PlayerGameMode(GameMode::Creative)) => self.creative_block_reach,
+            _ => self.block_reach,
+        }
+    }
+}
+
+/// Per-player state used by the movement checks
+#[derive(Component, Copy, Clone, Debug, Default)]
+pub struct AirTicks(u16);
+
+/// The score of a value which must be at most `limit`, or `None` if the value is within the limit
+fn excess(value: f32, limit: f32) -> Option<f32> {
+    (value > limit).then(|| value / limit - 1.0)
+}
+
+/// Returns the reach score and the angle score of looking from `eye` in `direction` at `target`
+fn reach_and_angle(
+    eye: Vec3,
+    direction: Vec3,
+    target: Aabb,
+    max_reach: f32,
+    max_angle: f32,
+) -> (Option<f32>, Option<f32>) {
+    let reach = reach(eye, target);
+
+    let to_center = target.mid() - eye;
+    let distance = to_center.length();
+
+    // The player may be inside of the target, in which case every angle is valid
+    if target.contains_point(eye) || distance <= f32::EPSILON {
+        return (excess(reach, max_reach), None);
+    }
+
+    // Any part of the target may be looked at, so the angle it takes up is added to the limit
+    let radius = target.lens().length() / 2.0;
+    let target_angle = (radius / distance).min(1.0).asin().to_degrees();
+
+    let angle = direction.angle_between(to_center).to_degrees();
+
+    (
+        excess(reach, max_reach),
+        excess(angle, max_angle + target_angle),
+    )
+}
+
+/// The distance from `eye` to the closest point of `target`
+#[must_use]
+pub fn reach(eye: Vec3, target: Aabb) -> f32 {
+    #[expect(clippy::cast_possible_truncation)]
+    let reach = target.dist2(eye).sqrt() as f32;
+    reach
+}
+
+/// Whether any part of `target` can be seen from `eye` without a block in the way. The block at
+/// `target_block` does not count as being in the way, so that a block can be seen even though its
+/// own collision shape is hit first.
+#[must_use]
+pub fn line_of_sight(
+    eye: Vec3,
+    target: Aabb,
+    target_block: Option<IVec3>,
+    blocks: &Blocks,
+) -> bool {
+    // The corners are moved inwards so that rays to them do not graze neighbouring blocks
+    let inner = target.shrink(0.05);
+    let corners = (0..8).map(|i| {
+        Vec3::new(
+            if i & 1 == 0 { inner.min.x } else { inner.max.x },
+            if i & 2 == 0 { inner.min.y } else { inner.max.y },
+            if i & 4 == 0 { inner.min.z } else { inner.max.z },
+        )
+    });
+
+    iter::once(target.mid()).chain(corners).any(|point| {
+        match blocks.first_collision_between(eye, point) {
+            Some(collision) => Some(collision.location) == target_block,
+            None => true,
+        }
+    })
+}
+
+pub(crate) fn is_climbable_or_liquid(block: BlockState) -> bool {
+    block.is_liquid()
+        || matches!(
+            block.to_kind(),
+            BlockKind::Ladder | BlockKind::Vine | BlockKind::Scaffolding | BlockKind::Cobweb
+        )
+}
+
+fn initialize_player(
+    trigger: Trigger<'_, OnAdd, packet_state::Play>,
+    mut commands: Commands<'_, '_>,
+) {
+    commands
+        .entity(trigger.target())
+        .insert(AirTicks::default());
+}
+
+fn check_movement(
+    config: Res<'_, AnticheatConfig>,
+    blocks: Res<'_, Blocks>,
+    mut query: Query<
+        '_,
+        '_,
+        (
+            Entity,
+            Ref<'_, Position>,
+            &MovementTracking,
+            &Flight,
+            &mut AirTicks,
+        ),
+        Without<PendingTeleportation>,
+    >,
+    mut writer: EventWriter<'_, SuspicionEvent>,
+) {
+    for (player, position, tracking, flight, mut air_ticks) in &mut query {
+        // Players who have not sent a movement packet this tick are not checked, which avoids
+        // counting ticks in the air while a client is lagging
+        if !position.is_changed() {
+            continue;
+        }
+
+        let delta = **position - tracking.last_tick_position;
+
+        let max_speed = if flight.is_flying {
+            config.max_flying_speed
+        } else {
+            config.max_speed
+        };
+
+        // Knockback may move players faster than they can move on their own
+        #[expect(clippy::cast_possible_truncation)]
+        let knockback = tracking.server_velocity.xz().length() as f32;
+
+        if let Some(score) = excess(delta.xz().length(), max_speed + knockback) {
+            writer.write(SuspicionEvent {
+                player,
+                check: Check::Speed,
+                score,
+            });
+        }
+
+        let feet = position.floor().as_ivec3();
+        let supported = flight.allow
+            || delta.y < 0.0
+            || is_grounded(&position, &blocks)
+            || blocks.get_block(feet).is_some_and(is_climbable_or_liquid);
+
+        if supported {
+            air_ticks.0 = 0;
+            continue;
+        }
+
+        air_ticks.0 = air_ticks.0.saturating_add(1);
+
+        if let Some(score) = excess(
+            f32::from(air_ticks.0),
+            f32::from(config.max_air_ticks.max(1)),
+        ) {
+            writer.write(SuspicionEvent {
+                player,
+                check: Check::Fly,
+                score,
+            });
+        }
+    }
+}
+
+fn write_reach_and_angle(
+    writer: &mut EventWriter<'_, SuspicionEvent>,
+    player: Entity,
+    (reach, angle): (Option<f32>, Option<f32>),
+) {
+    if let Some(score) = reach {
+        writer.write(SuspicionEvent {
+            player,
+            check: Check::Reach,
+            score,
+        });
+    }
+
+    if let Some(score) = angle {
+        writer.write(SuspicionEvent {
+            player,
+            check: Check::Angle,
+            score,
+        });
+    }
+}
+
+fn check_block_interactions(
+    mut interact_packets: EventReader<'_, '_, play::PlayerInteractBlock>,
+    mut action_packets: EventReader<'_, '_, play::PlayerAction>,
+    config: Res<'_, AnticheatConfig>,
+    query: Query<'_, '_, (&Position, &Yaw, &Pitch, Option<&PlayerGameMode>)>,
+    mut writer: EventWriter<'_, SuspicionEvent>,
+) {
+    let interactions = interact_packets
+        .read()
+        .map(|packet| (packet.sender(), packet.position))
+        .chain(
+            action_packets
+                .read()
+                .filter(|packet| {
+                    matches!(
+                        packet.action,
+                        PlayerAction::StartDestroyBlock | PlayerAction::StopDestroyBlock
+                    )
+                })
+                .map(|packet| (packet.sender(), packet.position)),
+        );
+
+    for (player, block) in interactions {
+        let (position, yaw, pitch, game_mode) = match query.get(player) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("failed to check block interaction: query failed: {e}");
+                continue;
+            }
+        };
+
+        let eye = **position + Vec3::new(0.0, EYE_HEIGHT, 0.0);
+        let direction = get_direction_from_rotation(**yaw, **pitch);
+
+        let min = Vec3::new(block.x as f32, block.y as f32, block.z as f32);
+        let target = Aabb::new(min, min + Vec3::ONE);
+
+        let scores = reach_and_angle(
+            eye,
+            direction,
+            target,
+            config.block_reach_of(game_mode),
+            config.max_angle,
+        );
+        write_reach_and_angle(&mut writer, player, scores);
+    }
+}
+
+fn check_attacks(
+    mut packets: EventReader<'_, '_, play::PlayerInteractEntity>,
+    config: Res<'_, AnticheatConfig>,
+    entities: &Entities,
+    origin_query: Query<'_, '_, (&Position, &Yaw, &Pitch)>,
+    target_query: Query<'_, '_, (&Position, &EntitySize)>,
+    mut writer: EventWriter<'_, SuspicionEvent>,
+) {
+    for packet in packets.read() {
+        if packet.interact != EntityInteraction::Attack {
+            continue;
+        }
+
+        let player = packet.sender();
+
+        let Some(target) = entities.resolve_from_id(bytemuck::cast(packet.entity_id.0)) else {
+            continue;
+        };
+
+        let Ok((position, yaw, pitch)) = origin_query.get(player) else {
+            continue;
+        };
+
+        let Ok((target_position, &target_size)) = target_query.get(target) else {
+            continue;
+        };
+
+        let eye = **position + Vec3::new(0.0, EYE_HEIGHT, 0.0);
+        let direction = get_direction_from_rotation(**yaw, **pitch);
+
+        let scores = reach_and_angle(
+            eye,
+            direction,
+            aabb(**target_position, target_size),
+            config.entity_reach,
+            config.max_angle,
+        );
+        write_reach_and_angle(&mut writer, player, scores);
+    }
+}
+
+/// Cancels interactions with blocks which are out of reach or behind a wall. Interactions which are
+/// out of reach were already reported by [`check_block_interactions`].
+fn reject_unreachable_blocks(
+    mut place_events: EventMutator<'_, '_, Cancellable<PlaceBlock>>,
+    mut destroy_events: EventMutator<'_, '_, Cancellable<DestroyBlock>>,
+    mut door_events: EventMutator<'_, '_, Cancellable<ToggleDoor>>,
+    config: Res<'_, AnticheatConfig>,
+    blocks: Res<'_, Blocks>,
+    query: Query<'_, '_, (&Position, Option<&PlayerGameMode>)>,
+    mut writer: EventWriter<'_, SuspicionEvent>,
+) {
+    let mut is_reachable = |player: Entity, block: IVec3, solid: bool| {
+        let Ok((position, game_mode)) = query.get(player) else {
+            return true;
+        };
+
+        let eye = **position + Vec3::new(0.0, EYE_HEIGHT, 0.0);
+        let min = block.as_vec3();
+        let target = Aabb::new(min, min + Vec3::ONE);
+
+        if reach(eye, target) > config.block_reach_of(game_mode) {
+            return false;
+        }
+
+        // Placed blocks are not in the world yet, so nothing at their position is the target
+        let target_block = solid.then_some(block);
+
+        if !line_of_sight(eye, target, target_block, &blocks) {
+            writer.write(SuspicionEvent {
+                player,
+                check: Check::LineOfSight,
+                score: 1.0,
+            });
+            return false;
+        }
+
+        true
+    };
+
+    for event in place_events.read() {
+        if !is_reachable(event.from, event.position, false) {
+            event.cancel();
+        }
+    }
+
+    for event in destroy_events.read() {
+        if !is_reachable(event.from, event.position, true) {
+            event.cancel();
+        }
+    }
+
+    for event in door_events.read() {
+        if !is_reachable(event.from, event.position, true) {
+            event.cancel();
+        }
+    }
+}
+
+/// Runs the built-in checks and registers [`SuspicionEvent`]
+pub struct AnticheatPlugin;
+
+impl Plugin for AnticheatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AnticheatConfig>();
+        app.add_event::<SuspicionEvent>();
+        app.add_observer(initialize_player);
+        app.add_systems(
+            FixedUpdate,
+            (
+                timed(check_block_interactions),
+                timed(check_attacks),
+                timed(reject_unreachable_blocks).in_set(CancelEvents),
+                // Movement packets are handled before CancelEvents
+                timed(check_movement).after(CancelEvents),
+            )
+                .after(ingress::decode::play),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(x: f32, y: f32, z: f32) -> Aabb {
+        let min = Vec3::new(x, y, z);
+        Aabb::new(min, min + Vec3::ONE)
+    }
+
+    #[test]
+    fn test_reach() {
+        let eye = Vec3::new(0.5, EYE_HEIGHT, 0.5);
+
+        // The closest face of the block is 4 blocks away
+        let (reach, angle) = reach_and_angle(eye, Vec3::X, block(4.5, 1.0, 0.0), 6.0, 60.0);
+        assert_eq!(reach, None);
+        assert_eq!(angle, None);
+
+        // The closest face of the block is 8 blocks away
+        let (reach, _) = reach_and_angle(eye, Vec3::X, block(8.5, 1.0, 0.0), 6.0, 60.0);
+        assert!((reach.unwrap() - 1.0 / 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_block_reach_of() {
+        let config = AnticheatConfig::default();
+
+        assert!((config.block_reach_of(None) - 4.5).abs() < f32::EPSILON);
+        assert!(
+            (config.block_reach_of(Some(&PlayerGameMode(GameMode::Creative))) - 5.0).abs()
+                < f32::EPSILON
+        );
+        assert!(
+            (config.block_reach_of(Some(&PlayerGameMode(GameMode::Adventure))) - 4.5).abs()
+                < f32::EPSILON
+        );
+    }
+
+    #[test]
+    fn test_angle() {
+        let eye = Vec3::new(0.5, EYE_HEIGHT, 0.5);
+        let target = block(4.5, 1.0, 0.0);
+
+        // Looking directly away from the block
+        let (_, angle) = reach_and_angle(eye, -Vec3::X, target, 6.0, 60.0);
+        assert!(angle.unwrap() > 0.0);
+
+        // The center of the block is just over 60 degrees away from looking straight down, but
+        // the block is close enough that its edge is within the limit
+        let (_, angle) = reach_and_angle(eye, Vec3::NEG_Y, block(2.0, 0.0, 0.0), 6.0, 60.0);
+        assert_eq!(angle, None);
+
+        // Standing inside of the target allows every angle
+        let (reach, angle) = reach_and_angle(eye, -Vec3::X, block(0.0, 1.0, 0.0), 6.0, 60.0);
+        assert_eq!((reach, angle), (None, None));
+    }
+}
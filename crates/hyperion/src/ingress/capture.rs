@@ -0,0 +1,284 @@
+//! Recordings of the packets received from players, which are meant for debugging.
+//!
+//! When [`Config::capture_dir`](crate::config::Config::capture_dir) is set, every packet decoded
+//! from a player is written to `<capture_dir>/<proxy>-<stream>-<unix ms>.hcap` along with the time
+//! since the player connected. A recording can be read with [`Recording::open`] and fed back
+//! through the decode systems with [`Recording::replay`], which makes it possible to reproduce
+//! packet handling bugs in tests.
+//!
+//! A recording starts with [`MAGIC`] and [`VERSION`], followed by one record per packet with these
+//! little-endian fields:
+//! - `u64` microseconds since the player connected
+//! - `u8` packet state, see [`CapturedState`]
+//! - `i32` compression threshold of the connection when the packet was received
+//! - `i32` packet id
+//! - `u32` length of the body
+//! - the body of the packet after the packet id
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{Context, bail, ensure};
+use bevy::prelude::*;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::{Compression, read::ZlibEncoder};
+use tracing::warn;
+use valence_protocol::{CompressionThreshold, Encode, VarInt};
+
+use crate::net::ConnectionId;
+
+/// The bytes at the start of every recording
+pub const MAGIC: [u8; 4] = *b"HCAP";
+
+/// The version of the recording format
+pub const VERSION: u8 = 1;
+
+/// Where recordings are written to. Packets are only recorded while this resource exists.
+#[derive(Resource, Clone, Debug)]
+pub struct PacketCapture {
+    dir: PathBuf,
+}
+
+impl PacketCapture {
+    #[must_use]
+    pub const fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn start(&self, connection_id: ConnectionId) -> io::Result<PacketRecorder> {
+        std::fs::create_dir_all(&self.dir)?;
+
+        let started = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = self.dir.join(format!(
+            "{}-{}-{started}.hcap",
+            connection_id.proxy_id().inner(),
+            connection_id.inner()
+        ));
+
+        PacketRecorder::create(&path)
+    }
+}
+
+/// The state of the connection when a packet was received
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CapturedState {
+    Handshake = 0,
+    Status = 1,
+    Login = 2,
+    Play = 3,
+}
+
+impl TryFrom<u8> for CapturedState {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> anyhow::Result<Self> {
+        Ok(match value {
+            0 => Self::Handshake,
+            1 => Self::Status,
+            2 => Self::Login,
+            3 => Self::Play,
+            _ => bail!("invalid packet state {value}"),
+        })
+    }
+}
+
+/// Writes the packets received from one player to a recording
+#[derive(Component, Debug)]
+pub struct PacketRecorder {
+    writer: BufWriter<File>,
+    started: Instant,
+}
+
+impl PacketRecorder {
+    /// Creates a recording at `path`, replacing the file if it exists
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&MAGIC)?;
+        writer.write_u8(VERSION)?;
+
+        Ok(Self {
+            writer,
+            started: Instant::now(),
+        })
+    }
+
+    /// Adds a packet to the recording. `body` is the packet after the packet id.
+    pub fn record(
+        &mut self,
+        state: CapturedState,
+        threshold: CompressionThreshold,
+        id: i32,
+        body: &[u8],
+    ) -> io::Result<()> {
+        let elapsed = u64::try_from(self.started.elapsed().as_micros()).unwrap_or(u64::MAX);
+        let len = u32::try_from(body.len()).map_err(io::Error::other)?;
+
+        self.writer.write_u64::<LittleEndian>(elapsed)?;
+        self.writer.write_u8(state as u8)?;
+        self.writer.write_i32::<LittleEndian>(threshold.0)?;
+        self.writer.write_i32::<LittleEndian>(id)?;
+        self.writer.write_u32::<LittleEndian>(len)?;
+        self.writer.write_all(body)
+    }
+
+    /// Writes the buffered packets to the file
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// A packet read from a recording
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedPacket {
+    /// The time between the player connecting and the packet being received
+    pub elapsed: Duration,
+    pub state: CapturedState,
+    /// The compression threshold of the connection when the packet was received
+    pub threshold: CompressionThreshold,
+    pub id: i32,
+    /// The packet after the packet id
+    pub body: Vec<u8>,
+}
+
+impl CapturedPacket {
+    /// Encodes the packet as a length-prefixed frame, compressed the same way as it was when it
+    /// was received
+    pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(self.body.len() + 5);
+        VarInt(self.id).encode(&mut data)?;
+        data.extend_from_slice(&self.body);
+
+        let mut packet = Vec::with_capacity(data.len() + 5);
+        match usize::try_from(self.threshold.0) {
+            Ok(threshold) if data.len() > threshold => {
+                VarInt(i32::try_from(data.len())?).encode(&mut packet)?;
+                ZlibEncoder::new(data.as_slice(), Compression::default())
+                    .read_to_end(&mut packet)?;
+            }
+            Ok(_) => {
+                VarInt(0).encode(&mut packet)?;
+                packet.extend_from_slice(&data);
+            }
+            Err(_) => packet = data,
+        }
+
+        let mut frame = Vec::with_capacity(packet.len() + 5);
+        VarInt(i32::try_from(packet.len())?).encode(&mut frame)?;
+        frame.extend_from_slice(&packet);
+        Ok(frame)
+    }
+}
+
+/// The packets in a recording, in the order they were received
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub packets: Vec<CapturedPacket>,
+}
+
+impl Recording {
+    /// Reads the recording at `path`
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("failed to open recording {path:?}"))?;
+        Self::read(BufReader::new(file))
+    }
+
+    /// Reads a recording
+    pub fn read(mut reader: impl Read) -> anyhow::Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        ensure!(magic == MAGIC, "not a packet recording");
+
+        let version = reader.read_u8()?;
+        ensure!(
+            version == VERSION,
+            "unsupported recording version {version}, expected {VERSION}"
+        );
+
+        let mut packets = Vec::new();
+        loop {
+            // The recording may end at any record because it is written until the player
+            // disconnects
+            let elapsed = match reader.read_u64::<LittleEndian>() {
+                Ok(elapsed) => Duration::from_micros(elapsed),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            };
+            let state = CapturedState::try_from(reader.read_u8()?)?;
+            let threshold = CompressionThreshold(reader.read_i32::<LittleEndian>()?);
+            let id = reader.read_i32::<LittleEndian>()?;
+            let len = reader.read_u32::<LittleEndian>()?;
+
+            let mut body = vec![0; len as usize];
+            reader.read_exact(&mut body)?;
+
+            packets.push(CapturedPacket {
+                elapsed,
+                state,
+                threshold,
+                id,
+                body,
+            });
+        }
+
+        Ok(Self { packets })
+    }
+
+    /// Sends every packet to a connection. The packets are decoded on the next tick in the same
+    /// way as packets from a proxy, so the connection should have the decoder settings and
+    /// packet state which it had when the recording started.
+    pub fn replay(&self, sender: &mut packet_channel::Sender) -> anyhow::Result<()> {
+        for packet in &self.packets {
+            sender
+                .send(&packet.encode()?)
+                .map_err(|e| anyhow::anyhow!("failed to replay packet {}: {e:?}", packet.id))?;
+        }
+        Ok(())
+    }
+}
+
+fn start_recording(
+    trigger: Trigger<'_, OnAdd, ConnectionId>,
+    capture: Option<Res<'_, PacketCapture>>,
+    query: Query<'_, '_, &ConnectionId>,
+    mut commands: Commands<'_, '_>,
+) {
+    let Some(capture) = capture else {
+        return;
+    };
+
+    let player = trigger.target();
+    let Ok(&connection_id) = query.get(player) else {
+        return;
+    };
+
+    match capture.start(connection_id) {
+        Ok(recorder) => {
+            commands.entity(player).insert(recorder);
+        }
+        Err(e) => {
+            warn!("failed to start recording packets from {connection_id:?}: {e}");
+        }
+    }
+}
+
+pub struct CapturePlugin;
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(start_recording);
+    }
+}
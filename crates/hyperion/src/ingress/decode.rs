@@ -7,13 +7,23 @@
 )]
 
 use bevy::{ecs::batching::BatchingStrategy, prelude::*};
+use itertools::Either;
 use paste::paste;
-use tracing::error;
+use tracing::warn;
 use valence_protocol::Packet as _;
 
 use crate::{
-    net::{Compose, ConnectionId, PacketDecoder, decoder::BorrowedPacketFrame},
-    simulation::{packet::Packet, packet_state},
+    ingress::{
+        capture::{CapturedState, PacketRecorder},
+        validate,
+    },
+    net::{ConnectionId, MAX_PACKET_SIZE, PacketDecoder, decoder::BorrowedPacketFrame},
+    simulation::{
+        kick::{KickExt, Kicked},
+        packet::Packet,
+        packet_state,
+    },
+    timings::timed,
 };
 
 mod __private {
@@ -87,24 +97,55 @@ mod may_transition {
     pub const play: bool = false;
 }
 
-fn try_next_frame(
-    compose: &Compose,
-    connection_id: ConnectionId,
+/// The largest packet body, after decompression, which is accepted in each state. Packets in the
+/// handshake and status states are tiny, and the login state only needs room for plugin responses.
+mod max_packet_len {
+    pub const handshake: usize = 2048;
+    pub const status: usize = 16;
+    pub const login: usize = 1024 * 1024;
+    pub const play: usize = super::MAX_PACKET_SIZE;
+}
+
+/// The state which packets in each state are recorded as
+mod capture_state {
+    use super::CapturedState;
+
+    pub const handshake: CapturedState = CapturedState::Handshake;
+    pub const status: CapturedState = CapturedState::Status;
+    pub const login: CapturedState = CapturedState::Login;
+    pub const play: CapturedState = CapturedState::Play;
+}
+
+fn record_frame(
+    recorder: &mut PacketRecorder,
+    state: CapturedState,
     decoder: &PacketDecoder,
-    decompressor: &mut libdeflater::Decompressor,
-    receiver: &mut packet_channel::Receiver,
-) -> Option<BorrowedPacketFrame> {
-    let raw_packet = receiver.try_recv()?;
-    match decoder.try_next_packet(decompressor, raw_packet) {
-        Ok(packet) => Some(packet),
-        Err(e) => {
-            error!("failed to decode packet: {e}");
-            compose.io_buf().shutdown(connection_id);
-            None
-        }
+    frame: &BorrowedPacketFrame,
+) {
+    let body: &[u8] = match &frame.body {
+        Either::Left(bytes) => bytes,
+        Either::Right(packet) => packet,
+    };
+
+    if let Err(e) = recorder.record(state, decoder.compression(), frame.id, body) {
+        warn!("failed to record packet: {e}");
     }
 }
 
+fn check_len(frame: &BorrowedPacketFrame, max_len: usize) -> anyhow::Result<()> {
+    let len = match &frame.body {
+        Either::Left(bytes) => bytes.len(),
+        Either::Right(packet) => packet.len(),
+    };
+
+    anyhow::ensure!(
+        len <= max_len,
+        "packet is {len} bytes long, but at most {max_len} bytes are allowed in this state"
+    );
+
+    Ok(())
+}
+
 hyperion_packet_macros::for_each_state! {
     #{
         pub fn #state(
@@ -116,46 +157,54 @@ hyperion_packet_macros::for_each_state! {
                 &ConnectionId,
                 &PacketDecoder,
                 &mut packet_channel::Receiver,
+                Option<&mut PacketRecorder>,
             ),
-            paste! { With<packet_state::[< #state:camel >]> }
+            (paste! { With<packet_state::[< #state:camel >]> }, Without<Kicked>)
             >,
-            compose: Res<'_, Compose>,
             packet_id_generator: Res<'_, __private::PacketIdGenerator>,
             decompressor: Res<'_, __private::Decompressor>,
             mut writers: writers::#state<'_>,
+            mut commands: Commands<'_, '_>,
         ) {
-            let compose = &compose;
             let packet_id_generator = &packet_id_generator;
             let buffers = buffers::#state::default();
 
+            // Connections which sent a packet that cannot be accepted, and why
+            let rejected = boxcar::Vec::<(Entity, anyhow::Error)>::new();
+
             // Fill buffers
             let scope = tracing::info_span!("fill_buffers").entered();
             query.par_iter_mut().batching_strategy(BatchingStrategy {
                 batch_size_limits: 1..128,
                 batches_per_thread: 1,
-            }).for_each(|(sender, &connection_id, decoder, receiver)| {
+            }).for_each(|(sender, &connection_id, decoder, receiver, recorder)| {
                 let receiver = receiver.into_inner();
+                let mut recorder = recorder.map(Mut::into_inner);
                 let mut decompressor = decompressor.0.get_or_default().borrow_mut();
 
-                loop {
-                    let Some(frame) = try_next_frame(
-                        compose,
-                        connection_id,
-                        decoder,
-                        &mut decompressor,
-                        receiver,
-                    ) else {
-                        break;
+                while let Some(raw_packet) = receiver.try_recv() {
+                    let frame = match decoder.try_next_packet(&mut decompressor, raw_packet) {
+                        Ok(frame) => frame,
+                        Err(e) => {
+                            rejected.push((sender, e.context("failed to decode packet")));
+                            break;
+                        }
                     };
 
+                    if let Some(recorder) = &mut recorder {
+                        record_frame(recorder, capture_state::#state, decoder, &frame);
+                    }
+
                     let frame_id = frame.id;
 
+                    // Only the packets of the current state are accepted
                     #for_each_packet! {
-                        let result: anyhow::Result<()> = match frame_id {
-                            #{
-                                #valence_packet::ID => {
-                                    match frame.decode::<#static_valence_packet>() {
-                                        Ok(data) => {
+                        let result: anyhow::Result<()> = check_len(&frame, max_packet_len::#state)
+                            .and_then(|()| match frame_id {
+                                #{
+                                    #valence_packet::ID => {
+                                        frame.decode::<#static_valence_packet>().and_then(|data| {
+                                            validate::packet(&data)?;
                                             buffers.#packet_name.push(Packet::new(
                                                 sender,
                                                 connection_id,
@@ -163,23 +212,23 @@ hyperion_packet_macros::for_each_state! {
                                                 data
                                             ));
                                             Ok(())
-                                        },
-                                        Err(e) => Err(e)
-                                    }
-                                },
-                            }
-                            _ => {
-                                Err(anyhow::Error::msg("invalid packet id"))
-                            }
-                        };
+                                        })
+                                    },
+                                }
+                                _ => Err(anyhow::anyhow!("packet is not allowed in this state")),
+                            });
                     }
 
                     if let Err(e) = result {
-                        // The call to error! is placed outside of the match statement to help reduce
-                        // compile times by reducing code duplication from the expansion of the error!
-                        // macro
-                        error!("error while decoding packet (id: {frame_id}): {e}");
-                        compose.io_buf().shutdown(connection_id);
+                        // The error is recorded outside of the match statement to help reduce
+                        // compile times by reducing code duplication in the expansion
+                        rejected.push((
+                            sender,
+                            e.context(format!(
+                                "packet {frame_id:#04x} in the {:?} state",
+                                capture_state::#state
+                            )),
+                        ));
                         break;
                     }
 
@@ -190,6 +239,12 @@ hyperion_packet_macros::for_each_state! {
                         break;
                     }
                 }
+
+                if let Some(recorder) = recorder
+                    && let Err(e) = recorder.flush()
+                {
+                    warn!("failed to write recorded packets: {e}");
+                }
             });
             scope.exit();
 
@@ -201,6 +256,12 @@ hyperion_packet_macros::for_each_state! {
                 }
             }
             scope.exit();
+
+            // Packets after an invalid packet cannot be trusted, so the connection is closed
+            for (sender, e) in rejected {
+                warn!("kicking {sender:?}: {e:#}");
+                commands.entity(sender).kick(format!("Invalid packet: {e:#}"));
+            }
         }
     }
 }
@@ -215,7 +276,7 @@ impl Plugin for DecodePlugin {
             app.add_systems(
                 FixedUpdate, (
                     #{
-                        #state,
+                        timed(#state),
                     }
                 )
             );
@@ -3,36 +3,44 @@ use std::borrow::Cow;
 use bevy::prelude::*;
 use colored::Colorize;
 use hyperion_utils::EntityExt;
+use rustc_hash::FxHashMap;
 use serde_json::json;
 use sha2::Digest;
 use tracing::{error, info, warn};
 use valence_protocol::{
-    VarInt,
+    Bounded, VarInt,
     packets::{
         handshaking::handshake_c2s::HandshakeNextState,
-        login::{LoginCompressionS2c, LoginSuccessS2c},
+        login::{LoginCompressionS2c, LoginDisconnectS2c, LoginSuccessS2c},
         play::{EntitiesDestroyS2c, PlayerRemoveS2c},
         status::{QueryPongS2c, QueryResponseS2c},
     },
 };
+use valence_text::{IntoText, Text};
 
 use crate::{
     InitializePlayerPosition,
     command_channel::CommandChannel,
+    config::{Config, DuplicateLoginPolicy},
     egress::sync_chunks::ChunkSendQueue,
-    net::{Compose, MINECRAFT_VERSION, PROTOCOL_VERSION, PacketDecoder},
+    net::{Compose, ConnectionId, MINECRAFT_VERSION, PROTOCOL_VERSION, PacketDecoder},
     runtime::AsyncRuntime,
     simulation::{
         AiTargetable,
         ChunkPosition,
+        IgnMap,
         ImmuneStatus,
         Pitch,
+        Position,
         Uuid,
         Velocity,
+        WorldSpawn,
         Xp,
         Yaw,
         animation::ActiveAnimation,
         entity_kind::EntityKind,
+        event::{DuplicateLogin, PlayerLimitReached},
+        kick::{Kick, Kicked},
         packet,
         // animation::ActiveAnimation,
         // blocks::Blocks,
@@ -42,13 +50,32 @@ use crate::{
         skin::PlayerSkin,
     },
     storage::SkinHandler,
+    tick_rate::AdvanceTick,
+    timings::timed,
     util::mojang::MojangClient,
 };
 
+pub mod anticheat;
+pub mod capture;
 pub mod decode;
+mod validate;
+
+/// The oldest protocol version whose clients can translate
+/// `multiplayer.disconnect.incompatible`, which is 1.17
+const INCOMPATIBLE_KEY_PROTOCOL_VERSION: i32 = 755;
+
+/// The longest username which clients accept
+const MAX_USERNAME_LEN: usize = 16;
+
+/// The players who have logged in but are not in the play state yet, such as while their skin is
+/// fetched, by username. Players are only added to the [`IgnMap`] once they are in the play state,
+/// so their usernames are reserved here until then.
+#[derive(Resource, Default)]
+struct JoiningPlayers(FxHashMap<String, Entity>);
 
 pub fn process_handshake(
     mut packets: EventReader<'_, '_, packet::handshake::Handshake>,
+    compose: Res<'_, Compose>,
     mut commands: Commands<'_, '_>,
 ) {
     for packet in packets.read() {
@@ -59,6 +86,16 @@ pub fn process_handshake(
             HandshakeNextState::Status => {
                 entity.insert(packet_state::Status(()));
             }
+            HandshakeNextState::Login if packet.protocol_version.0 != PROTOCOL_VERSION => {
+                // The client would fail to decode the login packets of another version, so it is
+                // disconnected before entering the login state
+                let reason = reject_incompatible_client(
+                    &compose,
+                    packet.connection_id(),
+                    packet.protocol_version.0,
+                );
+                entity.insert(Kicked { reason });
+            }
             HandshakeNextState::Login => {
                 entity.insert(packet_state::Login(()));
             }
@@ -66,65 +103,186 @@ pub fn process_handshake(
     }
 }
 
+/// Disconnects a client on another protocol version with a message in their language asking them
+/// to use the server version. Returns the message.
+fn reject_incompatible_client(
+    compose: &Compose,
+    connection_id: ConnectionId,
+    protocol: i32,
+) -> Text {
+    info!("rejected client on protocol {protocol}; the server requires {PROTOCOL_VERSION}");
+
+    // Older clients do not have the translation used by newer ones
+    let key = if protocol < INCOMPATIBLE_KEY_PROTOCOL_VERSION {
+        "multiplayer.disconnect.outdated_client"
+    } else {
+        "multiplayer.disconnect.incompatible"
+    };
+
+    let reason = Text::translate(key, [MINECRAFT_VERSION.into_text()]);
+    let pkt = LoginDisconnectS2c {
+        reason: Cow::Borrowed(&reason),
+    };
+
+    if let Err(e) = compose.unicast_no_compression(&pkt, connection_id) {
+        error!("failed to send disconnect packet: {e}");
+    }
+
+    compose.io_buf().shutdown(connection_id);
+    reason
+}
+
+/// What the server list shows about this server. Plugins can change it at any time, and the
+/// change is sent to the proxies immediately.
+///
+/// ```ignore
+/// fn show_round(mut ping_response: ResMut<'_, ServerPingResponse>, round: Res<'_, Round>) {
+///     if round.is_changed() {
+///         ping_response.set_sample([format!("In game: Bedwars round {}", round.0)]);
+///     }
+/// }
+/// ```
 #[derive(Resource)]
 pub struct ServerPingResponse {
     pub description: String,
     pub max_players: u32,
+    /// Lines shown when hovering over the player count instead of the names of a random sample of
+    /// online players
+    pub sample: Option<Vec<String>>,
 }
 
-impl Default for ServerPingResponse {
-    fn default() -> Self {
+impl ServerPingResponse {
+    /// Replaces the description, which is also known as the MOTD
+    pub fn set_description(&mut self, description: impl Into<String>) {
+        self.description = description.into();
+    }
+
+    /// Shows `lines` when hovering over the player count
+    pub fn set_sample(&mut self, lines: impl IntoIterator<Item = impl Into<String>>) {
+        self.sample = Some(lines.into_iter().map(Into::into).collect());
+    }
+
+    /// Shows a random sample of online players when hovering over the player count again
+    pub fn clear_sample(&mut self) {
+        self.sample = None;
+    }
+}
+
+impl FromWorld for ServerPingResponse {
+    fn from_world(world: &mut World) -> Self {
+        // Show the same limit as the one enforced when players log in
+        let max_players = world.get_resource::<Config>().map_or(12_000, |config| {
+            u32::try_from(config.max_players).unwrap_or(0)
+        });
+
         Self {
             description: String::from(
                 "Getting 10k Players to PvP at Once on a Minecraft Server to Break the Guinness \
                  World Record",
             ),
-            max_players: 12_000,
+            max_players,
+            sample: None,
         }
     }
 }
 
+/// How often the status is sent to the proxies, which answer status requests using the most recent
+/// status they have received
+const STATUS_UPDATE_INTERVAL_TICKS: i64 = 20;
+
+/// The most online players which are listed in the status, which matches vanilla
+const MAX_SAMPLE_LEN: usize = 12;
+
+/// Creates the json response to a status request
+fn status_json<'a>(
+    ping_response_data: &ServerPingResponse,
+    compose: &Compose,
+    players: impl Iterator<Item = (&'a Name, &'a Uuid)>,
+) -> String {
+    // let img_bytes = include_bytes!("data/hyperion.png");
+
+    // let favicon = general_purpose::STANDARD.encode(img_bytes);
+    // let favicon = format!("data:image/png;base64,{favicon}");
+
+    let online = compose
+        .global()
+        .player_count
+        .load(std::sync::atomic::Ordering::Relaxed);
+
+    // Vanilla clients show the name of each entry and ignore the id
+    let sample: Vec<_> = match &ping_response_data.sample {
+        Some(lines) => lines
+            .iter()
+            .map(|line| json!({ "name": line, "id": uuid::Uuid::nil().to_string() }))
+            .collect(),
+        None => fastrand::choose_multiple(players, MAX_SAMPLE_LEN)
+            .into_iter()
+            .map(|(name, uuid)| json!({ "name": name.as_str(), "id": uuid.to_string() }))
+            .collect(),
+    };
+
+    // https://wiki.vg/Server_List_Ping#Response
+    let json = json!({
+        "version": {
+            // Only shown in the server list of clients on another version
+            "name": format!("Requires {MINECRAFT_VERSION}"),
+            "protocol": PROTOCOL_VERSION,
+        },
+        "players": {
+            "online": online,
+            "max": ping_response_data.max_players,
+            "sample": sample,
+        },
+        "description": ping_response_data.description,
+        // "favicon": favicon,
+        // Chat is sent as system messages, so clients should not warn about unsigned chat
+        "enforcesSecureChat": false,
+        "preventsChatReports": true,
+    });
+
+    serde_json::to_string_pretty(&json).expect("json serialization should succeed")
+}
+
+fn send_status_to_proxies(
+    ping_response_data: Res<'_, ServerPingResponse>,
+    compose: Res<'_, Compose>,
+    players: Query<'_, '_, (&Name, &Uuid), With<packet_state::Play>>,
+) {
+    if !ping_response_data.is_changed() && compose.global().tick % STATUS_UPDATE_INTERVAL_TICKS != 0
+    {
+        return;
+    }
+
+    let json = status_json(&ping_response_data, &compose, players.iter());
+    compose.io_buf().update_status(&json);
+
+    let online = compose
+        .global()
+        .player_count
+        .load(std::sync::atomic::Ordering::Relaxed);
+    compose.io_buf().update_player_count(
+        u32::try_from(online).unwrap_or(u32::MAX),
+        ping_response_data.max_players,
+    );
+}
+
 fn process_status_request(
     mut packets: EventReader<'_, '_, packet::status::QueryRequest>,
     ping_response_data: Res<'_, ServerPingResponse>,
     compose: Res<'_, Compose>,
+    players: Query<'_, '_, (&Name, &Uuid), With<packet_state::Play>>,
 ) {
     for packet in packets.read() {
-        // let img_bytes = include_bytes!("data/hyperion.png");
-
-        // let favicon = general_purpose::STANDARD.encode(img_bytes);
-        // let favicon = format!("data:image/png;base64,{favicon}");
-
-        let online = compose
-            .global()
-            .player_count
-            .load(std::sync::atomic::Ordering::Relaxed);
-
-        // https://wiki.vg/Server_List_Ping#Response
-        let json = json!({
-            "version": {
-                "name": MINECRAFT_VERSION,
-                "protocol": PROTOCOL_VERSION,
-            },
-            "players": {
-                "online": online,
-                "max": ping_response_data.max_players,
-                "sample": [],
-            },
-            "description": ping_response_data.description,
-            // "favicon": favicon,
-        });
-
-        let json = serde_json::to_string_pretty(&json).expect("json serialization should succeed");
+        let json = status_json(&ping_response_data, &compose, players.iter());
 
         let send = QueryResponseS2c {
             json: json.as_str().into(),
         };
 
         info!("sent query response: {packet:?}");
-        compose
-            .unicast_no_compression(&send, packet.connection_id())
-            .unwrap();
+        if let Err(e) = compose.unicast_no_compression(&send, packet.connection_id()) {
+            error!("failed to send status response: {e}");
+        }
     }
 }
 
@@ -136,9 +294,9 @@ fn process_status_ping(
         let payload = packet.payload;
         let send = QueryPongS2c { payload };
         info!("sent ping response: {send:?}");
-        compose
-            .unicast_no_compression(&send, packet.connection_id())
-            .unwrap();
+        if let Err(e) = compose.unicast_no_compression(&send, packet.connection_id()) {
+            error!("failed to send status pong: {e}");
+        }
     }
 }
 pub fn process_login_hello(
@@ -148,6 +306,7 @@ pub fn process_login_hello(
     skins_collection: Res<'_, SkinHandler>,
     mojang: Res<'_, MojangClient>,
     command_channel: Res<'_, CommandChannel>,
+    config: Res<'_, Config>,
     mut commands: Commands<'_, '_>,
     mut query: Query<'_, '_, &mut PacketDecoder>,
 ) {
@@ -165,23 +324,15 @@ pub fn process_login_hello(
         let pkt = LoginCompressionS2c {
             threshold: VarInt(global.shared.compression_threshold.0),
         };
-        compose
-            .unicast_no_compression(&pkt, packet.connection_id())
-            .unwrap();
+        if let Err(e) = compose.unicast_no_compression(&pkt, packet.connection_id()) {
+            error!("failed to send login compression: {e}");
+        }
         decoder.set_compression(global.shared.compression_threshold);
 
-        let uuid = profile_id.unwrap_or_else(|| offline_uuid(username));
+        let uuid = profile_id.unwrap_or_else(|| config_offline_uuid(&config, username));
         let uuid_s = format!("{uuid:?}").dimmed();
         info!("Starting login: {sender:?} {username} {uuid_s}");
 
-        let pkt = LoginSuccessS2c {
-            uuid,
-            username: username.clone(),
-            properties: Cow::default(),
-        };
-
-        compose.unicast(&pkt, packet.connection_id()).unwrap();
-
         let skin = if profile_id.is_some() {
             let mojang = mojang.as_ref().clone();
             let skins_collection = skins_collection.as_ref().clone();
@@ -217,38 +368,235 @@ pub fn process_login_hello(
         };
 
         let username = username.to_string();
+        let connection_id = packet.connection_id();
         commands.queue(move |world: &mut World| {
-            let mut entity = world.entity_mut(sender);
-
-            // TODO: The more specific components (such as ChunkSendQueue) should be added in a
-            // separate system
-            entity.remove::<packet_state::Login>().insert((
-                Name::new(username.to_string()),
-                ActiveAnimation::NONE,
-                AiTargetable,
-                ImmuneStatus::default(),
-                Uuid::from(uuid),
-                ChunkPosition::null(),
-                ChunkSendQueue::default(),
-                Yaw::default(),
-                Pitch::default(),
-                Velocity::default(),
-                Xp::default(),
-                EntityKind::Player,
-            ));
-
-            world.trigger(InitializePlayerPosition(sender));
-
-            if let Some(skin) = skin {
-                let mut entity = world.entity_mut(sender);
-                entity.insert(skin);
+            let Some((username, uuid)) = resolve_duplicate_login(world, sender, username, uuid)
+            else {
+                return;
+            };
+
+            // A player who replaces their existing session does not take up another slot
+            let replaces_existing = online_player(world, &username).is_some();
+            if !replaces_existing && !admit_player(world, sender, &username, uuid) {
+                return;
             }
+
+            let pkt = LoginSuccessS2c {
+                uuid,
+                username: Bounded(username.as_str()),
+                properties: Cow::default(),
+            };
+
+            if let Err(e) = world.resource::<Compose>().unicast(&pkt, connection_id) {
+                error!("failed to send login success: {e}");
+            }
+
+            world.entity_mut(sender).remove::<packet_state::Login>();
+            complete_login(world, sender, username, uuid, skin);
         });
     }
 }
 
-/// Get a [`uuid::Uuid`] based on the given user's name.
+/// The player who is online or joining with `username`. The [`IgnMap`] also contains nicknames,
+/// which do not stop other players from joining with the same username.
+fn online_player(world: &World, username: &str) -> Option<Entity> {
+    world
+        .resource::<IgnMap>()
+        .get(username)
+        .copied()
+        .filter(|&player| {
+            world
+                .get::<Name>(player)
+                .is_some_and(|name| name.as_str() == username)
+        })
+        .or_else(|| world.resource::<JoiningPlayers>().0.get(username).copied())
+}
+
+/// Decides what happens if a player with the same username is already online by triggering
+/// [`DuplicateLogin`]. Returns the username and UUID which the player joins with, or `None` if the
+/// player was kicked instead.
+fn resolve_duplicate_login(
+    world: &mut World,
+    player: Entity,
+    username: String,
+    uuid: uuid::Uuid,
+) -> Option<(String, uuid::Uuid)> {
+    let Some(existing) = online_player(world, &username) else {
+        return Some((username, uuid));
+    };
+
+    let mut event = DuplicateLogin {
+        player,
+        existing,
+        username,
+        policy: world.resource::<Config>().duplicate_login,
+    };
+    world.trigger_ref(&mut event);
+
+    match event.policy {
+        // The existing player is kicked once this player joins, or now if they have not joined yet
+        DuplicateLoginPolicy::KickOld => {
+            if !world.entity(existing).contains::<packet_state::Play>() {
+                world.trigger_targets(
+                    Kick::new(
+                        "A different player with the same username as your account has joined on \
+                         a different device",
+                    ),
+                    existing,
+                );
+            }
+
+            Some((event.username, uuid))
+        }
+        DuplicateLoginPolicy::RejectNew => {
+            world.trigger_targets(
+                Kick::new("A player with the same username is already connected to the server"),
+                player,
+            );
+            None
+        }
+        DuplicateLoginPolicy::Suffix => {
+            let ign_map = world.resource::<IgnMap>();
+            let joining = world.resource::<JoiningPlayers>();
+            let username = unused_username(&event.username, |name| {
+                ign_map.contains_key(name) || joining.0.contains_key(name)
+            });
+
+            // Only the name changes, so the player keeps their data
+            info!(
+                "Renamed {:?} to {username} because the name is taken",
+                event.username
+            );
+            Some((username, uuid))
+        }
+    }
+}
+
+/// Whether there is room on the server for the player to join. If the server is full,
+/// [`PlayerLimitReached`] is triggered so that plugins can let the player join anyway, and the
+/// player is kicked otherwise.
+fn admit_player(world: &mut World, player: Entity, username: &str, uuid: uuid::Uuid) -> bool {
+    let max_players = usize::try_from(world.resource::<Config>().max_players).unwrap_or(0);
+
+    let online = world
+        .resource::<Compose>()
+        .global()
+        .player_count
+        .load(std::sync::atomic::Ordering::Relaxed);
+
+    // Players who have logged in but are still waiting for their skin are not counted as online
+    // yet, but they will be soon
+    let joining = world
+        .query_filtered::<(), (
+            With<ConnectionId>,
+            With<Name>,
+            Without<packet_state::Play>,
+            Without<Kicked>,
+        )>()
+        .iter(world)
+        .count();
+
+    if online + joining < max_players {
+        return true;
+    }
+
+    let mut event = PlayerLimitReached {
+        player,
+        uuid,
+        username: username.to_owned(),
+        allow: false,
+    };
+    world.trigger_ref(&mut event);
+
+    if event.allow {
+        info!("{username} joined the full server");
+    } else {
+        world.trigger_targets(
+            Kick::new(Text::translate(
+                "multiplayer.disconnect.server_full",
+                Vec::new(),
+            )),
+            player,
+        );
+    }
+
+    event.allow
+}
+
+/// The first of `<username>_2`, `<username>_3` and so on which is not taken, shortening the
+/// username as needed to stay within the 16 character limit
+fn unused_username(username: &str, is_taken: impl Fn(&str) -> bool) -> String {
+    (2_u32..)
+        .map(|n| {
+            let suffix = format!("_{n}");
+            let len = MAX_USERNAME_LEN.saturating_sub(suffix.len());
+            let base: String = username.chars().take(len).collect();
+            base + &suffix
+        })
+        .find(|candidate| !is_taken(candidate))
+        .expect("there is always an unused username")
+}
+
+/// Adds the components of a player who has finished logging in. The player joins once their
+/// [`PlayerSkin`] is added, which is inserted immediately if `skin` is provided.
+pub(crate) fn complete_login(
+    world: &mut World,
+    player: Entity,
+    username: String,
+    uuid: uuid::Uuid,
+    skin: Option<PlayerSkin>,
+) {
+    let spawn = *world.resource::<WorldSpawn>();
+
+    world
+        .resource_mut::<JoiningPlayers>()
+        .0
+        .insert(username.clone(), player);
+
+    // TODO: The more specific components (such as ChunkSendQueue) should be added in a
+    // separate system
+    world.entity_mut(player).insert((
+        Name::new(username),
+        ActiveAnimation::NONE,
+        AiTargetable,
+        ImmuneStatus::default(),
+        Uuid::from(uuid),
+        ChunkPosition::null(),
+        ChunkSendQueue::default(),
+        Position::from(spawn.position),
+        Yaw::new(spawn.yaw),
+        Pitch::default(),
+        Velocity::default(),
+        Xp::default(),
+        EntityKind::Player,
+    ));
+
+    world.trigger(InitializePlayerPosition(player));
+
+    if let Some(skin) = skin {
+        world.entity_mut(player).insert(skin);
+    }
+}
+
+/// The [`uuid::Uuid`] of an offline player, which depends on [`Config::vanilla_offline_uuids`]
+fn config_offline_uuid(config: &Config, username: &str) -> uuid::Uuid {
+    if config.vanilla_offline_uuids {
+        offline_uuid(username)
+    } else {
+        legacy_offline_uuid(username)
+    }
+}
+
+/// Get the [`uuid::Uuid`] of an offline player in the same way as vanilla, which is a name-based
+/// version 3 UUID of `OfflinePlayer:<name>` without a namespace.
 fn offline_uuid(username: &str) -> uuid::Uuid {
+    let digest = md5::Md5::digest(format!("OfflinePlayer:{username}"));
+    uuid::Builder::from_md5_bytes(digest.into()).into_uuid()
+}
+
+/// Get a [`uuid::Uuid`] based on the given user's name. This does not match vanilla and is only
+/// used when [`Config::vanilla_offline_uuids`] is disabled.
+fn legacy_offline_uuid(username: &str) -> uuid::Uuid {
     let digest = sha2::Sha256::digest(username);
     let digest: [u8; 32] = digest.into();
     let (&digest, ..) = digest.split_array_ref::<16>();
@@ -258,6 +606,34 @@ fn offline_uuid(username: &str) -> uuid::Uuid {
     uuid::Uuid::from_u128(digest)
 }
 
+/// Releases the username reserved in [`JoiningPlayers`]
+fn release_username(joining: &mut JoiningPlayers, name: &Name, player: Entity) {
+    if joining.0.get(name.as_str()) == Some(&player) {
+        joining.0.remove(name.as_str());
+    }
+}
+
+fn release_joined_username(
+    trigger: Trigger<'_, OnAdd, packet_state::Play>,
+    query: Query<'_, '_, &Name>,
+    mut joining: ResMut<'_, JoiningPlayers>,
+) {
+    if let Ok(name) = query.get(trigger.target()) {
+        release_username(&mut joining, name, trigger.target());
+    }
+}
+
+/// Players who disconnect while joining release their username when their [`Name`] is removed
+fn release_disconnected_username(
+    trigger: Trigger<'_, OnRemove, Name>,
+    query: Query<'_, '_, &Name>,
+    mut joining: ResMut<'_, JoiningPlayers>,
+) {
+    if let Ok(name) = query.get(trigger.target()) {
+        release_username(&mut joining, name, trigger.target());
+    }
+}
+
 fn remove_player_from_visibility(
     trigger: Trigger<'_, OnRemove, packet_state::Play>,
     query: Query<'_, '_, &Uuid>,
@@ -297,16 +673,54 @@ pub struct IngressPlugin;
 
 impl Plugin for IngressPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(decode::DecodePlugin);
+        app.add_plugins((
+            anticheat::AnticheatPlugin,
+            capture::CapturePlugin,
+            decode::DecodePlugin,
+        ));
         app.add_systems(
             FixedUpdate,
             (
-                process_handshake.after(decode::handshake),
-                (process_status_request, process_status_ping).after(decode::status),
-                process_login_hello.after(decode::login),
+                timed(process_handshake).after(decode::handshake),
+                (timed(process_status_request), timed(process_status_ping)).after(decode::status),
+                timed(process_login_hello).after(decode::login),
+                timed(send_status_to_proxies).in_set(AdvanceTick),
             ),
         );
         app.add_observer(remove_player_from_visibility);
+        app.add_observer(release_joined_username);
+        app.add_observer(release_disconnected_username);
         app.init_resource::<ServerPingResponse>();
+        app.init_resource::<JoiningPlayers>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::{offline_uuid, unused_username};
+    use crate::simulation::IgnMap;
+
+    #[test]
+    fn test_offline_uuid() {
+        let uuid = offline_uuid("Notch");
+        assert_eq!(uuid.to_string(), "b50ad385-829d-3141-a216-7e7d7539ba7f");
+        assert_eq!(uuid.get_version_num(), 3);
+    }
+
+    #[test]
+    fn test_unused_username() {
+        let mut ign_map = IgnMap::default();
+        ign_map.insert("Steve".to_owned(), Entity::PLACEHOLDER);
+        ign_map.insert("Steve_2".to_owned(), Entity::PLACEHOLDER);
+
+        let is_taken = |name: &str| ign_map.contains_key(name);
+
+        assert_eq!(unused_username("Steve", is_taken), "Steve_3");
+        assert_eq!(
+            unused_username("SixteenCharsLong", is_taken),
+            "SixteenCharsLo_2"
+        );
     }
 }
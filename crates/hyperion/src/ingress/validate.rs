@@ -0,0 +1,98 @@
+//! Checks on decoded packets which the decoder does not already enforce.
+
+use std::any::Any;
+
+use anyhow::ensure;
+use valence_nbt::{Compound, List, Value};
+use valence_protocol::{
+    ItemStack,
+    packets::play::{ClickSlotC2s, CreativeInventoryActionC2s},
+};
+
+/// The deepest nesting of compounds and lists allowed in the NBT of items sent by clients. Vanilla
+/// items are far shallower than this.
+const MAX_ITEM_NBT_DEPTH: usize = 32;
+
+/// Rejects packets which decoded successfully but contain data which the server should not accept
+pub fn packet(packet: &dyn Any) -> anyhow::Result<()> {
+    if let Some(packet) = packet.downcast_ref::<ClickSlotC2s<'static>>() {
+        for change in packet.slot_changes.iter() {
+            item(&change.stack)?;
+        }
+
+        item(&packet.carried_item)?;
+    } else if let Some(packet) = packet.downcast_ref::<CreativeInventoryActionC2s>() {
+        item(&packet.clicked_item)?;
+    }
+
+    Ok(())
+}
+
+fn item(stack: &ItemStack) -> anyhow::Result<()> {
+    if let Some(nbt) = &stack.nbt {
+        let depth = compound_depth(nbt);
+
+        ensure!(
+            depth <= MAX_ITEM_NBT_DEPTH,
+            "item nbt is nested {depth} levels deep, but at most {MAX_ITEM_NBT_DEPTH} are allowed"
+        );
+    }
+
+    Ok(())
+}
+
+fn compound_depth(compound: &Compound) -> usize {
+    1 + compound
+        .iter()
+        .map(|(_, value)| value_depth(value))
+        .max()
+        .unwrap_or(0)
+}
+
+fn value_depth(value: &Value) -> usize {
+    match value {
+        Value::Compound(compound) => compound_depth(compound),
+        Value::List(list) => list_depth(list),
+        _ => 0,
+    }
+}
+
+fn list_depth(list: &List) -> usize {
+    let depth = match list {
+        List::Compound(compounds) => compounds.iter().map(compound_depth).max(),
+        List::List(lists) => lists.iter().map(list_depth).max(),
+        _ => None,
+    };
+
+    1 + depth.unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::ItemKind;
+
+    use super::*;
+
+    fn nested(depth: usize) -> Compound {
+        let mut compound = Compound::new();
+
+        for _ in 1..depth {
+            let mut parent = Compound::new();
+            parent.insert("tag", Value::Compound(compound));
+            compound = parent;
+        }
+
+        compound
+    }
+
+    #[test]
+    fn test_item_nbt_depth() {
+        assert_eq!(compound_depth(&nested(3)), 3);
+
+        let shallow = ItemStack::new(ItemKind::Stone, 1, Some(nested(MAX_ITEM_NBT_DEPTH)));
+        assert!(item(&shallow).is_ok());
+
+        let deep = ItemStack::new(ItemKind::Stone, 1, Some(nested(MAX_ITEM_NBT_DEPTH + 1)));
+        assert!(item(&deep).is_err());
+    }
+}
@@ -27,7 +27,12 @@
 pub const CHUNK_HEIGHT_SPAN: u32 = 384; // 512; // usually 384
 
 use std::{
-    alloc::Allocator, fmt::Debug, io::Write, net::SocketAddr, path::Path, sync::Arc, time::Duration,
+    alloc::Allocator,
+    fmt::Debug,
+    io::Write,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use bevy::prelude::*;
@@ -37,7 +42,7 @@ pub use glam;
 use libc::{RLIMIT_NOFILE, getrlimit, setrlimit};
 use libdeflater::CompressionLvl;
 use rustls_pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
-use storage::{LocalDb, SkinHandler};
+use storage::{EntityHandler, LocalDb, ProfileCacheHandler, SkinHandler, Storage};
 use tracing::{info, warn};
 pub use uuid;
 pub use valence_protocol as protocol;
@@ -59,11 +64,24 @@ pub use valence_ident;
 use crate::{
     command_channel::{CommandChannel, CommandChannelPlugin},
     ingress::IngressPlugin,
-    net::{Compose, ConnectionId, IoBuf, MAX_PACKET_SIZE, PacketDecoder, proxy::init_proxy_comms},
+    net::{
+        Compose, ConnectionId, IoBuf, MAX_PACKET_SIZE, PacketDecoder,
+        bandwidth::BandwidthPlugin,
+        proxy::{
+            ProxyTlsConfig, Shard, init_crypto_reload, init_proxy_comms, reload_crypto,
+            track_connection_added, track_connection_removed,
+        },
+    },
     runtime::AsyncRuntime,
     simulation::{IgnMap, SimPlugin, StreamLookup, blocks::Blocks},
     spatial::SpatialPlugin,
-    util::mojang::{ApiProvider, MojangClient},
+    tick_health::TickHealthPlugin,
+    tick_rate::{TickRate, TickRatePlugin},
+    timings::TimingsPlugin,
+    util::{
+        mineskin::MineSkinClient,
+        mojang::{ApiProvider, MojangClient},
+    },
 };
 
 pub mod egress;
@@ -151,6 +169,28 @@ impl Crypto {
     }
 }
 
+/// The file paths which a [`Crypto`] is loaded from. If this resource is present when
+/// [`HyperionCore`] is added, the certificates are reloaded whenever these files change, which
+/// allows certificates to be rotated without restarting the server.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct CryptoPaths {
+    /// The file path to the root certificate authority's certificate
+    pub root_ca_cert: PathBuf,
+
+    /// The file path to the game server's certificate
+    pub cert: PathBuf,
+
+    /// The file path to the game server's private key
+    pub key: PathBuf,
+}
+
+impl CryptoPaths {
+    /// Loads a [`Crypto`] from these paths.
+    pub fn load(&self) -> Result<Crypto, rustls_pki_types::pem::Error> {
+        Crypto::new(&self.root_ca_cert, &self.cert, &self.key)
+    }
+}
+
 impl Clone for Crypto {
     fn clone(&self) -> Self {
         Self {
@@ -232,27 +272,64 @@ impl Plugin for HyperionCore {
 
         info!("starting hyperion");
         let config = config::Config::load("run/config.toml").expect("failed to load config");
-        app.insert_resource(config);
 
         let runtime = AsyncRuntime::new();
 
         let db = LocalDb::new().expect("failed to load database");
-        let skins = SkinHandler::new(&db).expect("failed to load skin handler");
-
+        let storage = Storage::connect(config.database_url.as_deref(), &runtime, &db)
+            .expect("failed to connect to storage");
+        let skins = SkinHandler::new(storage.clone());
+        let entities = EntityHandler::new(&db).expect("failed to load entity handler");
+
+        let mojang = MojangClient::with_providers(&runtime, &[
+            ApiProvider::MAT_DOES_DEV,
+            ApiProvider::MOJANG,
+        ])
+        .with_profile_cache(ProfileCacheHandler::open().expect("failed to load profile api cache"));
+
+        let tick_rate = TickRate::from(&config);
+
+        app.insert_resource(simulation::WorldSpawn::from(&config.spawn));
+        app.insert_resource(simulation::afk::AfkSettings::from(&config.afk));
+        app.insert_resource(Shard::from(&config.shard));
+        if let Some(dir) = &config.capture_dir {
+            app.insert_resource(ingress::capture::PacketCapture::new(dir.clone()));
+        }
+        app.insert_resource(config);
         app.insert_resource(db);
+        app.insert_resource(storage);
         app.insert_resource(skins);
-        app.insert_resource(MojangClient::new(&runtime, ApiProvider::MAT_DOES_DEV));
+        app.insert_resource(entities);
+        app.insert_resource(mojang);
+        app.insert_resource(MineSkinClient::new(std::env::var("MINESKIN_API_KEY").ok()));
         app.insert_resource(Blocks::empty(&runtime));
         app.add_event::<InitializePlayerPosition>();
+        app.add_observer(reload_crypto);
+        app.add_observer(track_connection_added);
+        app.add_observer(track_connection_removed);
 
         let global = Global::new(shared.clone());
 
         app.add_plugins(CommandChannelPlugin);
 
-        if let Some(address) = app.world().get_resource::<Endpoint>() {
+        if let Some(address) = app.world().get_resource::<Endpoint>().cloned() {
             let crypto = app.world().resource::<Crypto>();
-            let command_channel = app.world().resource::<CommandChannel>();
-            init_proxy_comms(&runtime, command_channel.clone(), address.0, crypto.clone());
+            let command_channel = app.world().resource::<CommandChannel>().clone();
+            let tls_config =
+                ProxyTlsConfig::new(crypto).expect("failed to create proxy tls config");
+
+            init_proxy_comms(
+                &runtime,
+                command_channel.clone(),
+                address.0,
+                tls_config.clone(),
+            );
+
+            if let Some(paths) = app.world().get_resource::<CryptoPaths>() {
+                init_crypto_reload(&runtime, command_channel, paths.clone());
+            }
+
+            app.insert_resource(tls_config);
         } else {
             warn!("Endpoint was not set while loading HyperionCore");
         }
@@ -264,21 +341,25 @@ impl Plugin for HyperionCore {
         ));
         app.insert_resource(runtime);
         app.insert_resource(CraftingRegistry::default());
+        app.insert_resource(simulation::registry::Registries::default());
         app.insert_resource(StreamLookup::default());
 
         app.add_plugins((
             bevy::time::TimePlugin,
-            bevy::app::ScheduleRunnerPlugin::run_loop(Duration::from_millis(10)),
             IngressPlugin,
             EgressPlugin,
+            BandwidthPlugin,
+            TickHealthPlugin,
+            TickRatePlugin,
+            TimingsPlugin,
             SimPlugin,
             SpatialPlugin,
             HyperionUtilsPlugin,
         ));
 
         app.insert_resource(IgnMap::default());
-        // Minecraft is 20 TPS
-        app.insert_resource(Time::<Fixed>::from_hz(20.0));
+        app.insert_resource(Time::<Fixed>::from_duration(tick_rate.timestep()));
+        app.insert_resource(tick_rate);
     }
 }
 
@@ -1,7 +1,10 @@
 //! Agnostic networking primitives. Translates to correct protocol version.
 
 mod chat;
-pub use chat::{Chat, chat};
+pub use chat::{Chat, action_bar, chat, player_chat};
+
+mod particle;
+pub use particle::{ParticleBuilder, Particles, particle};
 
 mod sound;
 pub use sound::{Sound, SoundBuilder, sound};
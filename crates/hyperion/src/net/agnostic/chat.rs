@@ -1,7 +1,7 @@
 use std::io::Write;
 
 use valence_protocol::packets::play;
-use valence_text::IntoText;
+use valence_text::{Color, IntoText, Text};
 
 use crate::PacketBundle;
 
@@ -19,6 +19,37 @@ pub fn chat(chat: impl Into<String>) -> Chat {
     }
 }
 
+/// A message shown above the hotbar instead of in chat
+pub fn action_bar(msg: impl Into<String>) -> Chat {
+    let msg = msg.into();
+    Chat {
+        raw: play::GameMessageS2c {
+            chat: msg.into_cow_text(),
+            overlay: true,
+        },
+    }
+}
+
+/// A chat message from `sender` in the form `<sender> message`.
+///
+/// Chat signatures are not verified or forwarded, so player chat is sent to other players as a
+/// system message. Clients are told on join that the server does not enforce secure chat, so
+/// they neither warn about these messages nor offer to report them.
+pub fn player_chat(sender: Text, message: &str) -> Chat {
+    let chat = Text::default()
+        + "<".color(Color::DARK_GRAY)
+        + sender
+        + "> ".color(Color::DARK_GRAY)
+        + message.to_owned();
+
+    Chat {
+        raw: play::GameMessageS2c {
+            chat: chat.into(),
+            overlay: false,
+        },
+    }
+}
+
 #[macro_export]
 macro_rules! chat {
     ($($arg:tt)*) => {
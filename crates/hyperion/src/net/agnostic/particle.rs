@@ -0,0 +1,75 @@
+use std::{borrow::Cow, io::Write};
+
+use glam::Vec3;
+use valence_protocol::{Particle, packets::play};
+
+use crate::PacketBundle;
+
+#[must_use]
+pub struct Particles {
+    raw: play::ParticleS2c<'static>,
+}
+
+#[must_use]
+pub struct ParticleBuilder {
+    particle: Particle,
+    position: Vec3,
+    offset: Vec3,
+    speed: f32,
+    count: i32,
+    long_distance: bool,
+}
+
+impl ParticleBuilder {
+    /// Spreads the particles randomly up to `offset` away from the position on each axis
+    pub const fn offset(mut self, offset: Vec3) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub const fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub const fn count(mut self, count: i32) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Shows the particles up to 256 blocks away instead of 32
+    pub const fn long_distance(mut self, long_distance: bool) -> Self {
+        self.long_distance = long_distance;
+        self
+    }
+
+    pub fn build(self) -> Particles {
+        Particles {
+            raw: play::ParticleS2c {
+                particle: Cow::Owned(self.particle),
+                long_distance: self.long_distance,
+                position: self.position.as_dvec3(),
+                offset: self.offset,
+                max_speed: self.speed,
+                count: self.count,
+            },
+        }
+    }
+}
+
+impl PacketBundle for &Particles {
+    fn encode_including_ids(self, mut w: impl Write) -> anyhow::Result<()> {
+        self.raw.encode_including_ids(&mut w)
+    }
+}
+
+pub const fn particle(particle: Particle, position: Vec3) -> ParticleBuilder {
+    ParticleBuilder {
+        particle,
+        position,
+        offset: Vec3::ZERO,
+        speed: 0.0,
+        count: 1,
+        long_distance: false,
+    }
+}
@@ -0,0 +1,269 @@
+//! Byte counters used to find out where network bandwidth is being spent.
+
+use std::{
+    cell::RefCell,
+    ops::AddAssign,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use bevy::prelude::*;
+use rustc_hash::FxHashMap;
+use thread_local::ThreadLocal;
+
+use crate::{
+    net::{Compose, ConnectionId},
+    simulation::StreamLookup,
+};
+
+/// Counts the bytes sent and received over a connection, and the size of the game packets sent
+/// over it before and after compression.
+#[derive(Default, Debug)]
+pub struct ByteCounters {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    uncompressed: AtomicU64,
+    compressed: AtomicU64,
+}
+
+impl ByteCounters {
+    pub(crate) fn add_in(&self, bytes: usize) {
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_out(&self, bytes: usize) {
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_encoded(&self, encoded: EncodedBytes) {
+        self.uncompressed
+            .fetch_add(encoded.uncompressed, Ordering::Relaxed);
+        self.compressed
+            .fetch_add(encoded.compressed, Ordering::Relaxed);
+    }
+
+    /// The total number of bytes received
+    #[must_use]
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in.load(Ordering::Relaxed)
+    }
+
+    /// The total number of bytes sent
+    #[must_use]
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+
+    /// The total size of the game packets sent before and after compression
+    #[must_use]
+    pub fn encoded(&self) -> EncodedBytes {
+        EncodedBytes {
+            uncompressed: self.uncompressed.load(Ordering::Relaxed),
+            compressed: self.compressed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The bandwidth used by a single player connection.
+///
+/// Incoming bytes are the raw bytes sent by the player. Outgoing bytes and the encoded sizes only
+/// include packets which were unicast to the player because broadcasts are fanned out to players
+/// by the proxy.
+#[derive(Component, Clone, Default, Debug, Deref)]
+pub struct ConnectionBandwidth(Arc<ByteCounters>);
+
+/// The number of bytes which were passed to the packet encoder and the number of bytes it produced.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct EncodedBytes {
+    /// The size of the packets before compression, excluding the packet length prefix
+    pub uncompressed: u64,
+    /// The size of the packets after compression, including the packet length prefix
+    pub compressed: u64,
+}
+
+impl AddAssign for EncodedBytes {
+    fn add_assign(&mut self, rhs: Self) {
+        self.uncompressed += rhs.uncompressed;
+        self.compressed += rhs.compressed;
+    }
+}
+
+/// Bandwidth counters for the server. This is part of [`crate::net::IoBuf`].
+#[derive(Default)]
+pub struct Bandwidth {
+    uncompressed: AtomicU64,
+    compressed: AtomicU64,
+    /// Packets unicast to each connection during the current tick. These are kept per thread to
+    /// avoid contention and are moved into [`ConnectionBandwidth`] by
+    /// [`collect_unicast_bandwidth`].
+    unicast: ThreadLocal<RefCell<FxHashMap<ConnectionId, EncodedBytes>>>,
+}
+
+impl Bandwidth {
+    pub(crate) fn record_encoded(&self, encoded: EncodedBytes) {
+        self.uncompressed
+            .fetch_add(encoded.uncompressed, Ordering::Relaxed);
+        self.compressed
+            .fetch_add(encoded.compressed, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_unicast(&self, stream: ConnectionId, encoded: EncodedBytes) {
+        *self
+            .unicast
+            .get_or_default()
+            .borrow_mut()
+            .entry(stream)
+            .or_default() += encoded;
+    }
+
+    /// The total number of bytes encoded by the server
+    #[must_use]
+    pub fn encoded(&self) -> EncodedBytes {
+        EncodedBytes {
+            uncompressed: self.uncompressed.load(Ordering::Relaxed),
+            compressed: self.compressed.load(Ordering::Relaxed),
+        }
+    }
+
+    fn drain_unicast(&mut self) -> impl Iterator<Item = (ConnectionId, EncodedBytes)> + '_ {
+        self.unicast
+            .iter_mut()
+            .flat_map(|unicast| unicast.get_mut().drain())
+    }
+}
+
+fn read_var_int(buf: &[u8]) -> Option<(usize, usize)> {
+    let mut value = 0_usize;
+    for (i, &byte) in buf.iter().take(5).enumerate() {
+        value |= usize::from(byte & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Reads the size of an encoded packet before compression from its header. Returns `None` if the
+/// header is malformed.
+fn uncompressed_len(packet: &[u8], compressed: bool) -> Option<usize> {
+    let (packet_len, packet_len_size) = read_var_int(packet)?;
+
+    if !compressed {
+        return Some(packet_len);
+    }
+
+    // A data length of 0 means that the packet is below the compression threshold and was not
+    // compressed
+    let (data_len, data_len_size) = read_var_int(packet.get(packet_len_size..)?)?;
+
+    if data_len == 0 {
+        packet_len.checked_sub(data_len_size)
+    } else {
+        Some(data_len)
+    }
+}
+
+/// Reads the sizes of the encoded packets in `packets` before and after compression from their
+/// headers. Anything after a malformed header is counted as not compressed.
+pub(crate) fn encoded_len(mut packets: &[u8], compressed: bool) -> EncodedBytes {
+    let mut encoded = EncodedBytes {
+        uncompressed: 0,
+        compressed: packets.len() as u64,
+    };
+
+    while !packets.is_empty() {
+        let frame_len = read_var_int(packets)
+            .map(|(packet_len, packet_len_size)| packet_len_size + packet_len)
+            .filter(|&frame_len| frame_len <= packets.len());
+
+        let (Some(frame_len), Some(uncompressed)) =
+            (frame_len, uncompressed_len(packets, compressed))
+        else {
+            encoded.uncompressed += packets.len() as u64;
+            break;
+        };
+
+        encoded.uncompressed += uncompressed as u64;
+        packets = &packets[frame_len..];
+    }
+
+    encoded
+}
+
+fn collect_unicast_bandwidth(
+    mut compose: ResMut<'_, Compose>,
+    lookup: Res<'_, StreamLookup>,
+    query: Query<'_, '_, (&ConnectionId, &ConnectionBandwidth)>,
+) {
+    for (stream, encoded) in compose.io_buf_mut().bandwidth_mut().drain_unicast() {
+        let Some(&entity) = lookup.get(&stream.inner()) else {
+            continue;
+        };
+
+        let Ok((&connection_id, bandwidth)) = query.get(entity) else {
+            continue;
+        };
+
+        // Stream ids are only unique within a proxy
+        if connection_id != stream {
+            continue;
+        }
+
+        bandwidth
+            .bytes_out
+            .fetch_add(encoded.compressed, Ordering::Relaxed);
+        bandwidth.add_encoded(encoded);
+    }
+}
+
+/// Collects the bandwidth used by each connection
+pub struct BandwidthPlugin;
+
+impl Plugin for BandwidthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Last, collect_unicast_bandwidth);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uncompressed_len() {
+        // Not using the compressed format
+        assert_eq!(uncompressed_len(&[3, 0x01, 0xAA, 0xBB], false), Some(3));
+
+        // Below the compression threshold
+        assert_eq!(uncompressed_len(&[4, 0, 0x01, 0xAA, 0xBB], true), Some(3));
+
+        // Compressed with a data length of 300
+        assert_eq!(
+            uncompressed_len(&[5, 0xAC, 0x02, 0x78, 0x9C, 0x00], true),
+            Some(300)
+        );
+    }
+
+    #[test]
+    fn test_encoded_len() {
+        // Two packets below the compression threshold and one compressed packet
+        let packets = [
+            4, 0, 0x01, 0xAA, 0xBB, 3, 0, 0x02, 0xCC, 5, 0xAC, 0x02, 0x78, 0x9C, 0x00,
+        ];
+        assert_eq!(encoded_len(&packets, true), EncodedBytes {
+            uncompressed: 3 + 2 + 300,
+            compressed: 15,
+        });
+
+        // The truncated packet at the end is counted as not compressed
+        assert_eq!(
+            encoded_len(&[3, 0x01, 0xAA, 0xBB, 9, 0x01], false),
+            EncodedBytes {
+                uncompressed: 5,
+                compressed: 6,
+            }
+        );
+    }
+}
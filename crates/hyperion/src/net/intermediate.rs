@@ -1,4 +1,6 @@
-use hyperion_proto::{ChunkPosition, ServerToProxyMessage, UpdateChannelPosition};
+use hyperion_proto::{
+    ChunkPosition, PlayerProfile, Priority, ServerToProxyMessage, UpdateChannelPosition,
+};
 
 use crate::net::{ConnectionId, ProxyId};
 
@@ -33,6 +35,20 @@ pub struct SubscribeChannelPackets<'a> {
     pub data: &'a [u8],
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SetChannelSubscription {
+    pub channel_id: u32,
+    pub stream: ConnectionId,
+    pub subscribed: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SetChannelHidden {
+    pub channel_id: u32,
+    pub stream: ConnectionId,
+    pub hidden: bool,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct SetReceiveBroadcasts {
     pub stream: ConnectionId,
@@ -41,6 +57,7 @@ pub struct SetReceiveBroadcasts {
 #[derive(Clone, PartialEq, Eq)]
 pub struct BroadcastGlobal<'a> {
     pub exclude: Option<ConnectionId>,
+    pub priority: Priority,
 
     pub data: &'a [u8],
 }
@@ -49,6 +66,7 @@ pub struct BroadcastGlobal<'a> {
 pub struct BroadcastLocal<'a> {
     pub center: ChunkPosition,
     pub exclude: Option<ConnectionId>,
+    pub priority: Priority,
 
     pub data: &'a [u8],
 }
@@ -57,6 +75,7 @@ pub struct BroadcastLocal<'a> {
 pub struct BroadcastChannel<'a> {
     pub channel_id: u32,
     pub exclude: Option<ConnectionId>,
+    pub priority: Priority,
 
     pub data: &'a [u8],
 }
@@ -64,6 +83,7 @@ pub struct BroadcastChannel<'a> {
 #[derive(Clone, PartialEq, Eq)]
 pub struct Unicast<'a> {
     pub stream: ConnectionId,
+    pub priority: Priority,
 
     pub data: &'a [u8],
 }
@@ -73,6 +93,40 @@ pub struct Shutdown {
     pub stream: ConnectionId,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UpdateStatus<'a> {
+    pub json: &'a str,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transfer<'a> {
+    pub stream: ConnectionId,
+    pub server: &'a str,
+    pub profile: PlayerProfile<'a>,
+    pub on_success: &'a [u8],
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RegisterServer<'a> {
+    pub name: &'a str,
+    pub region: &'a str,
+    pub arena: &'a str,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PlayerCount {
+    pub online: u32,
+    pub max: u32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct UpdateChunkSubscriptions<'a> {
+    pub stream: ConnectionId,
+    pub clear: bool,
+    pub subscribe: &'a [ChunkPosition],
+    pub unsubscribe: &'a [ChunkPosition],
+}
+
 #[derive(Clone, PartialEq)]
 pub enum IntermediateServerToProxyMessage<'a> {
     UpdatePlayerPositions(UpdatePlayerPositions),
@@ -86,6 +140,13 @@ pub enum IntermediateServerToProxyMessage<'a> {
     Unicast(Unicast<'a>),
     SetReceiveBroadcasts(SetReceiveBroadcasts),
     Shutdown(Shutdown),
+    UpdateStatus(UpdateStatus<'a>),
+    Transfer(Transfer<'a>),
+    RegisterServer(RegisterServer<'a>),
+    PlayerCount(PlayerCount),
+    UpdateChunkSubscriptions(UpdateChunkSubscriptions<'a>),
+    SetChannelSubscription(SetChannelSubscription),
+    SetChannelHidden(SetChannelHidden),
 }
 
 impl IntermediateServerToProxyMessage<'_> {
@@ -101,8 +162,17 @@ impl IntermediateServerToProxyMessage<'_> {
             | Self::BroadcastChannel(_)
             | Self::Unicast(_)
             | Self::SetReceiveBroadcasts(_)
-            | Self::Shutdown(_) => true,
-            Self::AddChannel(_) | Self::UpdateChannelPositions(_) | Self::RemoveChannel(_) => false,
+            | Self::Shutdown(_)
+            | Self::Transfer(_)
+            | Self::UpdateChunkSubscriptions(_)
+            | Self::SetChannelSubscription(_)
+            | Self::SetChannelHidden(_) => true,
+            Self::AddChannel(_)
+            | Self::UpdateChannelPositions(_)
+            | Self::RemoveChannel(_)
+            | Self::UpdateStatus(_)
+            | Self::RegisterServer(_)
+            | Self::PlayerCount(_) => false,
         }
     }
 
@@ -162,6 +232,7 @@ impl IntermediateServerToProxyMessage<'_> {
                         .exclude
                         .and_then(filter_map_connection_id)
                         .unwrap_or_default(),
+                    priority: message.priority,
                     data: message.data,
                 },
             )),
@@ -172,6 +243,7 @@ impl IntermediateServerToProxyMessage<'_> {
                         .exclude
                         .and_then(filter_map_connection_id)
                         .unwrap_or_default(),
+                    priority: message.priority,
                     data: message.data,
                 },
             )),
@@ -182,12 +254,14 @@ impl IntermediateServerToProxyMessage<'_> {
                         .exclude
                         .and_then(filter_map_connection_id)
                         .unwrap_or_default(),
+                    priority: message.priority,
                     data: message.data,
                 },
             )),
             Self::Unicast(message) => {
                 Some(ServerToProxyMessage::Unicast(hyperion_proto::Unicast {
                     stream: filter_map_connection_id(message.stream)?,
+                    priority: message.priority,
                     data: message.data,
                 }))
             }
@@ -196,9 +270,59 @@ impl IntermediateServerToProxyMessage<'_> {
                     stream: filter_map_connection_id(message.stream)?,
                 }),
             ),
-            Self::Shutdown(message) => Some(ServerToProxyMessage::SetReceiveBroadcasts(
-                hyperion_proto::SetReceiveBroadcasts {
+            Self::Shutdown(message) => {
+                Some(ServerToProxyMessage::Shutdown(hyperion_proto::Shutdown {
+                    stream: filter_map_connection_id(message.stream)?,
+                }))
+            }
+            Self::UpdateStatus(message) => Some(ServerToProxyMessage::UpdateStatus(
+                hyperion_proto::UpdateStatus { json: message.json },
+            )),
+            Self::Transfer(message) => {
+                Some(ServerToProxyMessage::Transfer(hyperion_proto::Transfer {
+                    stream: filter_map_connection_id(message.stream)?,
+                    server: message.server,
+                    profile: message.profile,
+                    on_success: message.on_success,
+                }))
+            }
+            Self::RegisterServer(message) => Some(ServerToProxyMessage::RegisterServer(
+                hyperion_proto::RegisterServer {
+                    name: message.name,
+                    region: message.region,
+                    arena: message.arena,
+                },
+            )),
+            Self::PlayerCount(message) => Some(ServerToProxyMessage::PlayerCount(
+                hyperion_proto::PlayerCount {
+                    online: message.online,
+                    max: message.max,
+                },
+            )),
+            Self::UpdateChunkSubscriptions(message) => {
+                Some(ServerToProxyMessage::UpdateChunkSubscriptions(
+                    hyperion_proto::UpdateChunkSubscriptions {
+                        stream: filter_map_connection_id(message.stream)?,
+                        clear: message.clear,
+                        subscribe: message.subscribe,
+                        unsubscribe: message.unsubscribe,
+                    },
+                ))
+            }
+            Self::SetChannelSubscription(message) => {
+                Some(ServerToProxyMessage::SetChannelSubscription(
+                    hyperion_proto::SetChannelSubscription {
+                        channel_id: message.channel_id,
+                        stream: filter_map_connection_id(message.stream)?,
+                        subscribed: message.subscribed,
+                    },
+                ))
+            }
+            Self::SetChannelHidden(message) => Some(ServerToProxyMessage::SetChannelHidden(
+                hyperion_proto::SetChannelHidden {
+                    channel_id: message.channel_id,
                     stream: filter_map_connection_id(message.stream)?,
+                    hidden: message.hidden,
                 },
             )),
         }
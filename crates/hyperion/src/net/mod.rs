@@ -3,6 +3,7 @@
 use std::{
     cell::{Cell, RefCell},
     fmt::Debug,
+    sync::atomic::{AtomicU64, Ordering},
 };
 
 use bevy::prelude::*;
@@ -10,16 +11,18 @@ use byteorder::WriteBytesExt;
 use bytes::{Bytes, BytesMut};
 pub use decoder::PacketDecoder;
 use glam::I16Vec2;
-use hyperion_proto::{ChunkPosition, ServerToProxyMessage};
+pub use hyperion_proto::Priority;
+use hyperion_proto::{ChunkPosition, PlayerProfile, ServerToProxyMessage};
 use hyperion_utils::EntityExt;
 use libdeflater::CompressionLvl;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use thread_local::ThreadLocal;
 use tracing::error;
 
 use crate::{
     Global, PacketBundle, Scratch,
     net::{
+        bandwidth::{Bandwidth, ByteCounters, EncodedBytes, encoded_len},
         encoder::{PacketEncoder, append_packet_without_compression},
         intermediate::IntermediateServerToProxyMessage,
     },
@@ -27,9 +30,11 @@ use crate::{
 };
 
 pub mod agnostic;
+pub mod bandwidth;
 pub mod decoder;
 pub mod encoder;
 pub mod intermediate;
+pub mod packet_channel;
 pub mod packets;
 pub mod proxy;
 
@@ -126,10 +131,52 @@ impl ConnectionId {
     }
 }
 
-/// A component marking an entity as a packet channel.
+/// Why a packet could not be sent
+#[derive(Debug, thiserror::Error)]
+pub enum SendError {
+    /// The packet could not be encoded, such as because it is larger than
+    /// [`MAX_PACKET_SIZE`]
+    #[error("failed to encode packet: {0:#}")]
+    Encode(anyhow::Error),
+    /// The player has disconnected
+    #[error("{0:?} has disconnected")]
+    ConnectionGone(ConnectionId),
+    /// The proxy which the player was connected through has disconnected
+    #[error("{0:?} has disconnected")]
+    ProxyGone(ProxyId),
+}
+
+impl SendError {
+    /// Whether the packet was not sent because the player or their proxy is gone, which is
+    /// expected when sending to players who may have just left
+    #[must_use]
+    pub const fn is_disconnected(&self) -> bool {
+        matches!(self, Self::ConnectionGone(_) | Self::ProxyGone(_))
+    }
+}
+
+/// A component marking an entity as a packet channel. Players near the entity are subscribed to the
+/// channel by the proxy. See [`PacketChannel`](packet_channel::PacketChannel) for channels whose
+/// subscribers are chosen by the server.
 #[derive(Component, Copy, Clone, Debug)]
 pub struct Channel;
 
+/// How many chunks away from an entity with a [`Channel`] players are subscribed to its channel.
+/// Entities which are watched from far away, such as bosses, can use a larger range than the
+/// default of [`TrackingRange::DEFAULT`].
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TrackingRange(pub i16);
+
+impl TrackingRange {
+    pub const DEFAULT: Self = Self(16);
+}
+
+impl Default for TrackingRange {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
 /// A unique identifier for a channel. The server is responsible for managing channel IDs.
 #[derive(Component, Copy, Clone, Debug)]
 pub struct ChannelId {
@@ -175,6 +222,9 @@ pub struct Compose {
 pub struct DataBundle<'a> {
     compose: &'a Compose,
     data: BytesMut,
+    /// The size of `data` before and after compression
+    encoded: EncodedBytes,
+    priority: Priority,
 }
 
 impl<'a> DataBundle<'a> {
@@ -182,50 +232,78 @@ impl<'a> DataBundle<'a> {
         Self {
             compose,
             data: BytesMut::new(),
+            encoded: EncodedBytes::default(),
+            priority: Priority::Normal,
         }
     }
 
-    pub fn add_packet(&mut self, pkt: impl PacketBundle) -> anyhow::Result<()> {
-        let data = self.compose.io_buf.encode_packet(pkt, self.compose)?;
+    /// Sets the [`Priority`] of the bundle, which is [`Priority::Normal`] by default
+    pub const fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn add_packet(&mut self, pkt: impl PacketBundle) -> Result<(), SendError> {
+        let (data, encoded) = self
+            .compose
+            .io_buf
+            .encode_packet_counted(pkt, self.compose)
+            .map_err(SendError::Encode)?;
         // todo: test to see if this ever actually unsplits
         self.data.unsplit(data);
+        self.encoded += encoded;
         Ok(())
     }
 
+    /// Adds packets which were already encoded with the encoder of the [`Compose`]
     pub fn add_raw(&mut self, raw: &[u8]) {
         self.data.extend_from_slice(raw);
+        self.encoded += encoded_len(raw, self.compose.is_compressed());
     }
 
-    pub fn unicast(&self, stream: ConnectionId) -> anyhow::Result<()> {
+    /// Sends the bundle to a single player. Bundles for players who have disconnected are
+    /// dropped in the same way as by [`Compose::unicast`].
+    pub fn unicast(&self, stream: ConnectionId) -> Result<(), SendError> {
         if self.data.is_empty() {
             return Ok(());
         }
 
-        self.compose.io_buf.unicast_raw(&self.data, stream);
+        let io_buf = &self.compose.io_buf;
+        io_buf.ignore_disconnected(io_buf.check_connection(stream))?;
+
+        io_buf.unicast_raw(&self.data, self.encoded, stream, self.priority);
         Ok(())
     }
 
     // todo: use builder pattern for excluding
-    pub fn broadcast_local(&self, center: I16Vec2) -> anyhow::Result<()> {
+    pub fn broadcast_local(&self, center: I16Vec2) -> Result<(), SendError> {
         if self.data.is_empty() {
             return Ok(());
         }
 
-        self.compose
-            .io_buf
-            .broadcast_local_raw(&self.data, center, None);
+        self.compose.io_buf.broadcast_local_raw(
+            &self.data,
+            self.encoded,
+            center,
+            None,
+            self.priority,
+        );
         Ok(())
     }
 
     // todo: use builder pattern for excluding
-    pub fn broadcast_channel(&self, channel: ChannelId) -> anyhow::Result<()> {
+    pub fn broadcast_channel(&self, channel: ChannelId) -> Result<(), SendError> {
         if self.data.is_empty() {
             return Ok(());
         }
 
-        self.compose
-            .io_buf
-            .broadcast_channel_raw(&self.data, channel, None);
+        self.compose.io_buf.broadcast_channel_raw(
+            &self.data,
+            self.encoded,
+            channel,
+            None,
+            self.priority,
+        );
 
         Ok(())
     }
@@ -265,6 +343,7 @@ impl Compose {
             packet,
             compose: self,
             exclude: None,
+            priority: Priority::Normal,
         }
     }
 
@@ -279,9 +358,9 @@ impl Compose {
         &mut self.io_buf
     }
 
-    /// Broadcast a packet within a certain region.
+    /// Broadcast a packet to the players who have loaded the chunk at `center`.
     ///
-    /// See <https://github.com/andrewgazelka/hyperion-proto/blob/main/src/server_to_proxy.proto#L17-L22>
+    /// See [`hyperion_proto::UpdateChunkSubscriptions`]
     pub const fn broadcast_local<P>(&self, packet: P, center: I16Vec2) -> BroadcastLocal<'_, P>
     where
         P: PacketBundle,
@@ -290,6 +369,7 @@ impl Compose {
             packet,
             compose: self,
             exclude: None,
+            priority: Priority::Normal,
             center: ChunkPosition {
                 x: center.x,
                 z: center.y,
@@ -310,22 +390,59 @@ impl Compose {
             packet,
             compose: self,
             exclude: None,
+            priority: Priority::Normal,
             channel,
         }
     }
 
     /// Send a packet to a single player.
-    pub fn unicast<P>(&self, packet: P, stream_id: ConnectionId) -> anyhow::Result<()>
+    ///
+    /// Packets for players who have disconnected are dropped without an error and counted in
+    /// [`IoBuf::dropped_sends`], so this only fails if the packet cannot be encoded. Use
+    /// [`Compose::try_unicast`] to find out whether the player is still connected.
+    pub fn unicast<P>(&self, packet: P, stream_id: ConnectionId) -> Result<(), SendError>
     where
         P: PacketBundle,
     {
-        Unicast {
+        self.unicast_with_priority(packet, stream_id, Priority::Normal)
+    }
+
+    /// Send a packet to a single player with a [`Priority`] other than [`Priority::Normal`].
+    pub fn unicast_with_priority<P>(
+        &self,
+        packet: P,
+        stream_id: ConnectionId,
+        priority: Priority,
+    ) -> Result<(), SendError>
+    where
+        P: PacketBundle,
+    {
+        let result = Unicast {
             packet,
             stream_id,
             compose: self,
             // todo: Should we have this true by default, or is there a better way?
             // Or a better word for no_compress, or should we just use negative field names?
             compress: true,
+            priority,
+        }
+        .send();
+
+        self.io_buf.ignore_disconnected(result)
+    }
+
+    /// Send a packet to a single player, failing with [`SendError::ConnectionGone`] or
+    /// [`SendError::ProxyGone`] if the player has disconnected.
+    pub fn try_unicast<P>(&self, packet: P, stream_id: ConnectionId) -> Result<(), SendError>
+    where
+        P: PacketBundle,
+    {
+        Unicast {
+            packet,
+            stream_id,
+            compose: self,
+            compress: true,
+            priority: Priority::Normal,
         }
         .send()
     }
@@ -335,17 +452,20 @@ impl Compose {
         &self,
         packet: &P,
         stream_id: ConnectionId,
-    ) -> anyhow::Result<()>
+    ) -> Result<(), SendError>
     where
         P: valence_protocol::Packet + valence_protocol::Encode,
     {
-        Unicast {
+        let result = Unicast {
             packet,
             stream_id,
             compose: self,
             compress: false,
+            priority: Priority::Normal,
         }
-        .send()
+        .send();
+
+        self.io_buf.ignore_disconnected(result)
     }
 
     #[must_use]
@@ -355,6 +475,11 @@ impl Compose {
         PacketEncoder::new(threshold)
     }
 
+    /// Whether packets from [`Compose::encoder`] use the format of compressed connections
+    fn is_compressed(&self) -> bool {
+        self.global.shared.compression_threshold.0 >= 0
+    }
+
     /// Obtain a thread-local scratch buffer.
     #[must_use]
     pub fn scratch(&self) -> &RefCell<Scratch> {
@@ -377,6 +502,10 @@ pub struct IoBuf {
     temp_buffer: ThreadLocal<RefCell<BytesMut>>,
     idx: ThreadLocal<Cell<u16>>,
     egress_comms: FxHashMap<ProxyId, EgressComm>,
+    /// The players who are connected to one of the proxies
+    connections: FxHashSet<ConnectionId>,
+    dropped_sends: AtomicU64,
+    bandwidth: Bandwidth,
 }
 
 impl IoBuf {
@@ -398,6 +527,53 @@ impl IoBuf {
     pub(crate) fn remove_proxy(&mut self, proxy_id: ProxyId) -> Option<EgressComm> {
         self.egress_comms.remove(&proxy_id)
     }
+
+    /// Returns the bandwidth used by each proxy connection. The encoded sizes count the game
+    /// packets which were sent to each proxy, including broadcasts.
+    pub fn proxy_bandwidth(&self) -> impl Iterator<Item = (ProxyId, &ByteCounters)> + '_ {
+        self.egress_comms
+            .iter()
+            .map(|(&proxy_id, egress_comm)| (proxy_id, &*egress_comm.bandwidth))
+    }
+
+    #[must_use]
+    #[expect(missing_docs)]
+    pub const fn bandwidth(&self) -> &Bandwidth {
+        &self.bandwidth
+    }
+
+    pub(crate) const fn bandwidth_mut(&mut self) -> &mut Bandwidth {
+        &mut self.bandwidth
+    }
+
+    /// How many packets were not sent because the player had already disconnected
+    #[must_use]
+    pub fn dropped_sends(&self) -> u64 {
+        self.dropped_sends.load(Ordering::Relaxed)
+    }
+
+    fn check_connection(&self, stream: ConnectionId) -> Result<(), SendError> {
+        if !self.egress_comms.contains_key(&stream.proxy_id()) {
+            return Err(SendError::ProxyGone(stream.proxy_id()));
+        }
+
+        if !self.connections.contains(&stream) {
+            return Err(SendError::ConnectionGone(stream));
+        }
+
+        Ok(())
+    }
+
+    /// Treats sends to players who have disconnected as successful, counting them instead
+    fn ignore_disconnected(&self, result: Result<(), SendError>) -> Result<(), SendError> {
+        match result {
+            Err(e) if e.is_disconnected() => {
+                self.dropped_sends.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            result => result,
+        }
+    }
 }
 
 /// A broadcast builder
@@ -406,6 +582,7 @@ pub struct Broadcast<'a, P> {
     packet: P,
     compose: &'a Compose,
     exclude: Option<ConnectionId>,
+    priority: Priority,
 }
 
 /// A unicast builder
@@ -415,34 +592,39 @@ struct Unicast<'a, P> {
     stream_id: ConnectionId,
     compose: &'a Compose,
     compress: bool,
+    priority: Priority,
 }
 
 impl<P> Unicast<'_, P>
 where
     P: PacketBundle,
 {
-    fn send(self) -> anyhow::Result<()> {
+    fn send(self) -> Result<(), SendError> {
         self.compose.io_buf.unicast_private(
             self.packet,
             self.stream_id,
             self.compose,
             self.compress,
+            self.priority,
         )
     }
 }
 
 impl<P> Broadcast<'_, P> {
     /// Send the packet to all players.
-    pub fn send(self) -> anyhow::Result<()>
+    pub fn send(self) -> Result<(), SendError>
     where
         P: PacketBundle,
     {
-        let bytes = self
+        let (bytes, encoded) = self
             .compose
             .io_buf
-            .encode_packet(self.packet, self.compose)?;
+            .encode_packet_counted(self.packet, self.compose)
+            .map_err(SendError::Encode)?;
 
-        self.compose.io_buf.broadcast_raw(&bytes, self.exclude);
+        self.compose
+            .io_buf
+            .broadcast_raw(&bytes, encoded, self.exclude, self.priority);
 
         Ok(())
     }
@@ -454,8 +636,14 @@ impl<P> Broadcast<'_, P> {
             packet: self.packet,
             compose: self.compose,
             exclude,
+            priority: self.priority,
         }
     }
+
+    /// Sets the [`Priority`] of the packet, which is [`Priority::Normal`] by default.
+    pub fn priority(self, priority: Priority) -> Self {
+        Self { priority, ..self }
+    }
 }
 
 #[must_use]
@@ -465,22 +653,28 @@ pub struct BroadcastLocal<'a, P> {
     compose: &'a Compose,
     center: ChunkPosition,
     exclude: Option<ConnectionId>,
+    priority: Priority,
 }
 
 impl<P> BroadcastLocal<'_, P> {
     /// Send the packet
-    pub fn send(self) -> anyhow::Result<()>
+    pub fn send(self) -> Result<(), SendError>
     where
         P: PacketBundle,
     {
-        let bytes = self
+        let (bytes, encoded) = self
             .compose
             .io_buf
-            .encode_packet(self.packet, self.compose)?;
+            .encode_packet_counted(self.packet, self.compose)
+            .map_err(SendError::Encode)?;
 
-        self.compose
-            .io_buf
-            .broadcast_local_raw(&bytes, self.center, self.exclude);
+        self.compose.io_buf.broadcast_local_raw(
+            &bytes,
+            encoded,
+            self.center,
+            self.exclude,
+            self.priority,
+        );
 
         Ok(())
     }
@@ -493,8 +687,14 @@ impl<P> BroadcastLocal<'_, P> {
             compose: self.compose,
             center: self.center,
             exclude,
+            priority: self.priority,
         }
     }
+
+    /// Sets the [`Priority`] of the packet, which is [`Priority::Normal`] by default.
+    pub fn priority(self, priority: Priority) -> Self {
+        Self { priority, ..self }
+    }
 }
 
 #[must_use]
@@ -503,23 +703,29 @@ pub struct BroadcastChannel<'a, P> {
     packet: P,
     compose: &'a Compose,
     exclude: Option<ConnectionId>,
+    priority: Priority,
     channel: ChannelId,
 }
 
 impl<P> BroadcastChannel<'_, P> {
     /// Send the packet
-    pub fn send(self) -> anyhow::Result<()>
+    pub fn send(self) -> Result<(), SendError>
     where
         P: PacketBundle,
     {
-        let bytes = self
+        let (bytes, encoded) = self
             .compose
             .io_buf
-            .encode_packet(self.packet, self.compose)?;
+            .encode_packet_counted(self.packet, self.compose)
+            .map_err(SendError::Encode)?;
 
-        self.compose
-            .io_buf
-            .broadcast_channel_raw(&bytes, self.channel, self.exclude);
+        self.compose.io_buf.broadcast_channel_raw(
+            &bytes,
+            encoded,
+            self.channel,
+            self.exclude,
+            self.priority,
+        );
 
         Ok(())
     }
@@ -529,10 +735,29 @@ impl<P> BroadcastChannel<'_, P> {
         let exclude = exclude.into();
         Self { exclude, ..self }
     }
+
+    /// Sets the [`Priority`] of the packet, which is [`Priority::Normal`] by default.
+    pub fn priority(self, priority: Priority) -> Self {
+        Self { priority, ..self }
+    }
 }
 
 impl IoBuf {
     pub fn encode_packet<P>(&self, packet: P, compose: &Compose) -> anyhow::Result<BytesMut>
+    where
+        P: PacketBundle,
+    {
+        self.encode_packet_counted(packet, compose)
+            .map(|(bytes, _)| bytes)
+    }
+
+    /// Encodes `packet` like [`IoBuf::encode_packet`] and also returns its size before and after
+    /// compression
+    fn encode_packet_counted<P>(
+        &self,
+        packet: P,
+        compose: &Compose,
+    ) -> anyhow::Result<(BytesMut, EncodedBytes)>
     where
         P: PacketBundle,
     {
@@ -545,15 +770,28 @@ impl IoBuf {
         let scratch = compose.scratch();
         let mut scratch = scratch.borrow_mut();
 
-        let result =
-            compose
-                .encoder()
-                .append_packet(packet, temp_buffer, &mut *scratch, &mut compressor)?;
+        let encoder = compose.encoder();
+        let result = encoder.append_packet(packet, temp_buffer, &mut *scratch, &mut compressor)?;
+
+        let compressed = encoder.compression_threshold().0 >= 0;
+        let encoded = encoded_len(&result, compressed);
+        self.bandwidth.record_encoded(encoded);
 
-        Ok(result)
+        Ok((result, encoded))
     }
 
     pub fn encode_packet_no_compression<P>(&self, packet: P) -> anyhow::Result<BytesMut>
+    where
+        P: PacketBundle,
+    {
+        self.encode_packet_no_compression_counted(packet)
+            .map(|(bytes, _)| bytes)
+    }
+
+    fn encode_packet_no_compression_counted<P>(
+        &self,
+        packet: P,
+    ) -> anyhow::Result<(BytesMut, EncodedBytes)>
     where
         P: PacketBundle,
     {
@@ -562,7 +800,10 @@ impl IoBuf {
 
         let result = append_packet_without_compression(packet, temp_buffer)?;
 
-        Ok(result)
+        let encoded = encoded_len(&result, false);
+        self.bandwidth.record_encoded(encoded);
+
+        Ok((result, encoded))
     }
 
     fn unicast_private<P>(
@@ -571,17 +812,21 @@ impl IoBuf {
         id: ConnectionId,
         compose: &Compose,
         compress: bool,
-    ) -> anyhow::Result<()>
+        priority: Priority,
+    ) -> Result<(), SendError>
     where
         P: PacketBundle,
     {
-        let bytes = if compress {
-            self.encode_packet(packet, compose)?
+        self.check_connection(id)?;
+
+        let (bytes, encoded) = if compress {
+            self.encode_packet_counted(packet, compose)
         } else {
-            self.encode_packet_no_compression(packet)?
-        };
+            self.encode_packet_no_compression_counted(packet)
+        }
+        .map_err(SendError::Encode)?;
 
-        self.unicast_raw(&bytes, id);
+        self.unicast_raw(&bytes, encoded, id, priority);
         Ok(())
     }
 
@@ -606,10 +851,9 @@ impl IoBuf {
                     continue;
                 };
 
-                egress_comm
-                    .tx
-                    .send(Self::encode_proxy_message(&message))
-                    .unwrap();
+                let buffer = Self::encode_proxy_message(&message);
+                egress_comm.bandwidth.add_out(buffer.len());
+                egress_comm.tx.send(buffer).unwrap();
             }
         } else {
             // Encode the message once and then send it to each proxy. This uses a placeholder
@@ -620,23 +864,36 @@ impl IoBuf {
 
             let buffer = Self::encode_proxy_message(&message);
             for egress_comm in self.egress_comms.values() {
+                egress_comm.bandwidth.add_out(buffer.len());
                 egress_comm.tx.send(buffer.clone()).unwrap();
             }
         }
     }
 
+    /// Counts packets sent to every proxy in the bandwidth of each proxy
+    fn record_broadcast(&self, encoded: EncodedBytes) {
+        for egress_comm in self.egress_comms.values() {
+            egress_comm.bandwidth.add_encoded(encoded);
+        }
+    }
+
     fn broadcast_local_raw(
         &self,
         data: &[u8],
+        encoded: EncodedBytes,
         center: impl Into<ChunkPosition>,
         exclude: Option<ConnectionId>,
+        priority: Priority,
     ) {
         let center = center.into();
 
+        self.record_broadcast(encoded);
+
         self.add_proxy_message(&IntermediateServerToProxyMessage::BroadcastLocal(
             intermediate::BroadcastLocal {
                 center,
                 exclude,
+                priority,
                 data,
             },
         ));
@@ -645,27 +902,87 @@ impl IoBuf {
     fn broadcast_channel_raw(
         &self,
         data: &[u8],
+        encoded: EncodedBytes,
         channel: ChannelId,
         exclude: Option<ConnectionId>,
+        priority: Priority,
     ) {
+        self.record_broadcast(encoded);
         self.add_proxy_message(&IntermediateServerToProxyMessage::BroadcastChannel(
             intermediate::BroadcastChannel {
                 channel_id: channel.inner(),
                 data,
                 exclude,
+                priority,
             },
         ));
     }
 
-    pub(crate) fn broadcast_raw(&self, data: &[u8], exclude: Option<ConnectionId>) {
+    pub(crate) fn broadcast_raw(
+        &self,
+        data: &[u8],
+        encoded: EncodedBytes,
+        exclude: Option<ConnectionId>,
+        priority: Priority,
+    ) {
+        self.record_broadcast(encoded);
         self.add_proxy_message(&IntermediateServerToProxyMessage::BroadcastGlobal(
-            intermediate::BroadcastGlobal { exclude, data },
+            intermediate::BroadcastGlobal {
+                exclude,
+                priority,
+                data,
+            },
         ));
     }
 
-    pub(crate) fn unicast_raw(&self, data: &[u8], stream: ConnectionId) {
+    pub(crate) fn unicast_raw(
+        &self,
+        data: &[u8],
+        encoded: EncodedBytes,
+        stream: ConnectionId,
+        priority: Priority,
+    ) {
+        self.bandwidth.record_unicast(stream, encoded);
+        if let Some(egress_comm) = self.egress_comms.get(&stream.proxy_id()) {
+            egress_comm.bandwidth.add_encoded(encoded);
+        }
         self.add_proxy_message(&IntermediateServerToProxyMessage::Unicast(
-            intermediate::Unicast { stream, data },
+            intermediate::Unicast {
+                stream,
+                priority,
+                data,
+            },
+        ));
+    }
+
+    pub(crate) fn add_connection(&mut self, stream: ConnectionId) {
+        self.connections.insert(stream);
+    }
+
+    pub(crate) fn remove_connection(&mut self, stream: ConnectionId) {
+        self.connections.remove(&stream);
+    }
+
+    /// Changes which chunks the proxy sends [`Compose::broadcast_local`] packets centered on to
+    /// `stream`. See [`hyperion_proto::UpdateChunkSubscriptions`].
+    pub(crate) fn update_chunk_subscriptions(
+        &self,
+        stream: ConnectionId,
+        clear: bool,
+        subscribe: &[ChunkPosition],
+        unsubscribe: &[ChunkPosition],
+    ) {
+        if !clear && subscribe.is_empty() && unsubscribe.is_empty() {
+            return;
+        }
+
+        self.add_proxy_message(&IntermediateServerToProxyMessage::UpdateChunkSubscriptions(
+            intermediate::UpdateChunkSubscriptions {
+                stream,
+                clear,
+                subscribe,
+                unsubscribe,
+            },
         ));
     }
 
@@ -699,6 +1016,36 @@ impl IoBuf {
         ));
     }
 
+    pub(crate) fn set_channel_subscription(
+        &self,
+        channel: ChannelId,
+        stream: ConnectionId,
+        subscribed: bool,
+    ) {
+        self.add_proxy_message(&IntermediateServerToProxyMessage::SetChannelSubscription(
+            intermediate::SetChannelSubscription {
+                channel_id: channel.inner(),
+                stream,
+                subscribed,
+            },
+        ));
+    }
+
+    pub(crate) fn set_channel_hidden(
+        &self,
+        channel: ChannelId,
+        stream: ConnectionId,
+        hidden: bool,
+    ) {
+        self.add_proxy_message(&IntermediateServerToProxyMessage::SetChannelHidden(
+            intermediate::SetChannelHidden {
+                channel_id: channel.inner(),
+                stream,
+                hidden,
+            },
+        ));
+    }
+
     pub(crate) fn remove_channel(&self, channel: ChannelId) {
         self.add_proxy_message(&IntermediateServerToProxyMessage::RemoveChannel(
             intermediate::RemoveChannel {
@@ -707,9 +1054,82 @@ impl IoBuf {
         ));
     }
 
+    pub(crate) fn update_status(&self, json: &str) {
+        self.add_proxy_message(&IntermediateServerToProxyMessage::UpdateStatus(
+            intermediate::UpdateStatus { json },
+        ));
+    }
+
+    pub(crate) fn register_server(&self, name: &str, region: &str, arena: &str) {
+        self.add_proxy_message(&IntermediateServerToProxyMessage::RegisterServer(
+            intermediate::RegisterServer {
+                name,
+                region,
+                arena,
+            },
+        ));
+    }
+
+    pub(crate) fn update_player_count(&self, online: u32, max: u32) {
+        self.add_proxy_message(&IntermediateServerToProxyMessage::PlayerCount(
+            intermediate::PlayerCount { online, max },
+        ));
+    }
+
     pub fn shutdown(&self, stream: ConnectionId) {
         self.add_proxy_message(&IntermediateServerToProxyMessage::Shutdown(
             intermediate::Shutdown { stream },
         ));
     }
+
+    /// Asks the proxy to move a player to another server. See [`hyperion_proto::Transfer`].
+    pub fn transfer(
+        &self,
+        stream: ConnectionId,
+        server: &str,
+        profile: PlayerProfile<'_>,
+        on_success: &[u8],
+    ) {
+        self.add_proxy_message(&IntermediateServerToProxyMessage::Transfer(
+            intermediate::Transfer {
+                stream,
+                server,
+                profile,
+                on_success,
+            },
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_check_connection() {
+        let mut io_buf = IoBuf::default();
+        let proxy_id = ProxyId::new(1);
+        let connection_id = ConnectionId::new(2, proxy_id);
+
+        assert!(matches!(
+            io_buf.check_connection(connection_id),
+            Err(SendError::ProxyGone(id)) if id == proxy_id
+        ));
+
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        io_buf.add_proxy(proxy_id, EgressComm::new(tx, Arc::default()));
+        assert!(matches!(
+            io_buf.check_connection(connection_id),
+            Err(SendError::ConnectionGone(id)) if id == connection_id
+        ));
+
+        io_buf.add_connection(connection_id);
+        assert!(io_buf.check_connection(connection_id).is_ok());
+
+        let result = io_buf.ignore_disconnected(Err(SendError::ConnectionGone(connection_id)));
+        assert!(result.is_ok());
+        assert_eq!(io_buf.dropped_sends(), 1);
+    }
 }
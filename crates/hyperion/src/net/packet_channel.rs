@@ -0,0 +1,227 @@
+//! Channels whose subscribers are chosen by the server, such as one channel for each team.
+//!
+//! Players are subscribed to an entity with a [`Channel`](super::Channel) by the proxy when they
+//! are near it. A [`PacketChannel`] has no position, so players only receive its broadcasts once
+//! they are subscribed with [`PacketChannelExt::subscribe`]. Players are unsubscribed when they
+//! disconnect, and every subscriber is unsubscribed when the channel is despawned.
+//!
+//! ```ignore
+//! use hyperion::net::packet_channel::{PacketChannel, PacketChannelExt};
+//!
+//! // Players who leave the team are sent this packet, which removes the team on their client
+//! let red = PacketChannel::new().with_unsubscribe_packet(
+//!     &compose,
+//!     play::TeamS2c {
+//!         team_name: "red",
+//!         mode: play::team_s2c::Mode::RemoveTeam,
+//!     },
+//! )?;
+//! let red = commands.spawn(red).id();
+//!
+//! commands.entity(red).subscribe(player);
+//!
+//! // Only the players on the red team receive this message
+//! compose
+//!     .broadcast_channel(&play::GameMessageS2c { .. }, red.into())
+//!     .send()?;
+//!
+//! commands.entity(red).unsubscribe(player);
+//!
+//! // The remaining players on the team are unsubscribed
+//! commands.entity(red).despawn();
+//! ```
+
+use bevy::prelude::*;
+use bytes::BytesMut;
+use rustc_hash::FxHashSet;
+use tracing::warn;
+
+use crate::{
+    PacketBundle,
+    net::{ChannelId, Compose, ConnectionId, SendError},
+};
+
+/// A channel which players are subscribed to with [`PacketChannelExt::subscribe`]. The channel is
+/// added to the proxies when this component is added and removed from them when it is removed or
+/// the entity is despawned.
+///
+/// The entity must not also have a [`Channel`](super::Channel), because both use the id of the
+/// entity as the channel id.
+#[derive(Component, Clone, Debug, Default)]
+pub struct PacketChannel {
+    unsubscribe_packets: BytesMut,
+    subscribers: FxHashSet<Entity>,
+}
+
+impl PacketChannel {
+    /// A channel which sends nothing to players when they are unsubscribed
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a packet which is sent to players when they are unsubscribed, or when the channel is
+    /// removed while they are subscribed
+    pub fn with_unsubscribe_packet(
+        mut self,
+        compose: &Compose,
+        packet: impl PacketBundle,
+    ) -> Result<Self, SendError> {
+        let data = compose
+            .io_buf()
+            .encode_packet(packet, compose)
+            .map_err(SendError::Encode)?;
+        self.unsubscribe_packets.unsplit(data);
+        Ok(self)
+    }
+
+    /// The players subscribed to this channel
+    pub fn subscribers(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.subscribers.iter().copied()
+    }
+
+    #[must_use]
+    pub(crate) fn unsubscribe_packets(&self) -> &[u8] {
+        &self.unsubscribe_packets
+    }
+
+    #[must_use]
+    pub fn is_subscribed(&self, player: Entity) -> bool {
+        self.subscribers.contains(&player)
+    }
+}
+
+/// Subscribes `player` to the target [`PacketChannel`]. Players who are already subscribed are
+/// ignored.
+#[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Subscribe {
+    pub player: Entity,
+}
+
+/// Unsubscribes `player` from the target [`PacketChannel`] and sends them its unsubscribe packets.
+/// Players who are not subscribed are ignored.
+#[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Unsubscribe {
+    pub player: Entity,
+}
+
+pub trait PacketChannelExt {
+    /// Subscribes `player` to this channel by triggering [`Subscribe`]
+    fn subscribe(&mut self, player: Entity) -> &mut Self;
+
+    /// Unsubscribes `player` from this channel by triggering [`Unsubscribe`]
+    fn unsubscribe(&mut self, player: Entity) -> &mut Self;
+}
+
+impl PacketChannelExt for EntityCommands<'_> {
+    fn subscribe(&mut self, player: Entity) -> &mut Self {
+        self.trigger(Subscribe { player })
+    }
+
+    fn unsubscribe(&mut self, player: Entity) -> &mut Self {
+        self.trigger(Unsubscribe { player })
+    }
+}
+
+fn add_packet_channel(
+    trigger: Trigger<'_, OnAdd, PacketChannel>,
+    query: Query<'_, '_, &PacketChannel>,
+    compose: Res<'_, Compose>,
+) {
+    let Ok(channel) = query.get(trigger.target()) else {
+        return;
+    };
+
+    compose
+        .io_buf()
+        .add_channel(trigger.target().into(), &channel.unsubscribe_packets);
+}
+
+fn remove_packet_channel(trigger: Trigger<'_, OnRemove, PacketChannel>, compose: Res<'_, Compose>) {
+    compose.io_buf().remove_channel(trigger.target().into());
+}
+
+fn subscribe(
+    trigger: Trigger<'_, Subscribe>,
+    mut channels: Query<'_, '_, &mut PacketChannel>,
+    players: Query<'_, '_, &ConnectionId>,
+    compose: Res<'_, Compose>,
+) {
+    let channel_entity = trigger.target();
+    let player = trigger.event().player;
+
+    let Ok(mut channel) = channels.get_mut(channel_entity) else {
+        warn!("cannot subscribe {player} to {channel_entity}, which is not a packet channel");
+        return;
+    };
+
+    let Ok(&connection_id) = players.get(player) else {
+        warn!("cannot subscribe {player} to {channel_entity} because it is not a player");
+        return;
+    };
+
+    if channel.subscribers.insert(player) {
+        compose.io_buf().set_channel_subscription(
+            ChannelId::from(channel_entity),
+            connection_id,
+            true,
+        );
+    }
+}
+
+fn unsubscribe(
+    trigger: Trigger<'_, Unsubscribe>,
+    mut channels: Query<'_, '_, &mut PacketChannel>,
+    players: Query<'_, '_, &ConnectionId>,
+    compose: Res<'_, Compose>,
+) {
+    let channel_entity = trigger.target();
+    let player = trigger.event().player;
+
+    let Ok(mut channel) = channels.get_mut(channel_entity) else {
+        return;
+    };
+
+    if !channel.subscribers.remove(&player) {
+        return;
+    }
+
+    if let Ok(&connection_id) = players.get(player) {
+        compose.io_buf().set_channel_subscription(
+            ChannelId::from(channel_entity),
+            connection_id,
+            false,
+        );
+    }
+}
+
+/// Unsubscribes players from every channel when they disconnect
+fn unsubscribe_disconnected(
+    trigger: Trigger<'_, OnRemove, ConnectionId>,
+    mut channels: Query<'_, '_, (Entity, &mut PacketChannel)>,
+    players: Query<'_, '_, &ConnectionId>,
+    compose: Res<'_, Compose>,
+) {
+    let player = trigger.target();
+    let Ok(&connection_id) = players.get(player) else {
+        return;
+    };
+
+    for (channel_entity, mut channel) in &mut channels {
+        if channel.subscribers.remove(&player) {
+            compose.io_buf().set_channel_subscription(
+                ChannelId::from(channel_entity),
+                connection_id,
+                false,
+            );
+        }
+    }
+}
+
+pub(crate) fn build(app: &mut App) {
+    app.add_observer(add_packet_channel);
+    app.add_observer(remove_packet_channel);
+    app.add_observer(subscribe);
+    app.add_observer(unsubscribe);
+    app.add_observer(unsubscribe_disconnected);
+}
@@ -2,7 +2,7 @@ use std::{borrow::Cow, io::Write};
 
 use uuid::Uuid;
 use valence_protocol::{
-    Decode, DecodeBytesAuto, Encode, ItemStack, Packet, VarInt,
+    BlockPos, Decode, DecodeBytesAuto, Encode, ItemStack, Packet, VarInt,
     packets::play::{
         boss_bar_s2c::{BossBarColor, BossBarDivision, BossBarFlags},
         entity_equipment_update_s2c::EquipmentEntry,
@@ -79,3 +79,16 @@ pub enum BossBarAction<'a> {
     UpdateStyle(BossBarColor, BossBarDivision),
     UpdateFlags(BossBarFlags),
 }
+
+/// Sent in response to [`valence_protocol::packets::play::client_status_c2s::ClientStatusC2s::RequestStats`]
+#[derive(Clone, PartialEq, Eq, Debug, Encode, Packet)]
+pub struct StatisticsS2c {
+    pub statistics: Vec<Statistic>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Encode)]
+pub struct Statistic {
+    pub category_id: VarInt,
+    pub statistic_id: VarInt,
+    pub value: VarInt,
+}
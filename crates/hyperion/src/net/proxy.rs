@@ -7,9 +7,12 @@ use std::{
         Arc,
         atomic::{AtomicU64, Ordering},
     },
+    time::{Duration, SystemTime},
 };
 
+use arc_swap::ArcSwap;
 use bevy::prelude::*;
+use bytes::Bytes;
 use hyperion_proto::ArchivedProxyToServerMessage;
 use hyperion_utils::EntityExt;
 use rustc_hash::FxHashMap;
@@ -17,22 +20,206 @@ use rustls::{
     RootCertStore,
     server::{ServerConfig, WebPkiClientVerifier},
 };
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    sync::mpsc::UnboundedReceiver,
+};
 use tokio_rustls::TlsAcceptor;
 use tracing::{error, info, warn};
 use valence_protocol::{VarInt, packets::play};
 
 use crate::{
-    ConnectionId, Crypto, PacketDecoder,
+    ConnectionId, Crypto, CryptoPaths, PacketDecoder,
     command_channel::CommandChannel,
-    net::{Channel, ChannelId, Compose, IoBuf, ProxyId},
+    config::ShardConfig,
+    egress::player_join::{RevealRules, waiting_room_packets},
+    ingress,
+    net::{
+        Channel, ChannelId, Compose, IoBuf, ProxyId,
+        bandwidth::{ByteCounters, ConnectionBandwidth},
+        packet_channel::PacketChannel,
+    },
     runtime::AsyncRuntime,
-    simulation::{EgressComm, RequestSubscribeChannelPackets, StreamLookup, packet_state},
+    simulation::{
+        ChunkPosition, EgressComm, RequestSubscribeChannelPackets, StreamLookup, Uuid,
+        packet_state, registry::Registries, skin::PlayerSkin, transfer,
+    },
 };
 
 // TODO: Determine a better default
 const DEFAULT_FRAGMENT_SIZE: usize = 4096;
 
+/// How often the certificate files in [`CryptoPaths`] are checked for modifications
+const CERTIFICATE_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Event which reloads the certificates from [`CryptoPaths`]. Proxies which connect after the
+/// reload use the new certificates, while proxies which are already connected are unaffected.
+///
+/// This is triggered automatically when the certificate files change or when the server receives
+/// `SIGHUP`, but it may also be triggered manually.
+#[derive(Event, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ReloadCrypto;
+
+/// How this server is described to proxies, which use it to route new players when they are
+/// connected to several servers.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Shard {
+    pub name: String,
+    pub region: Option<String>,
+    pub arena: Option<String>,
+}
+
+impl From<&ShardConfig> for Shard {
+    fn from(config: &ShardConfig) -> Self {
+        Self {
+            name: config.name.clone(),
+            region: config.region.clone(),
+            arena: config.arena.clone(),
+        }
+    }
+}
+
+/// The TLS configuration used to accept new proxy connections.
+#[derive(Resource, Clone)]
+pub struct ProxyTlsConfig {
+    config: Arc<ArcSwap<ServerConfig>>,
+}
+
+impl ProxyTlsConfig {
+    /// Creates a TLS configuration from the given [`Crypto`].
+    pub fn new(crypto: &Crypto) -> anyhow::Result<Self> {
+        Ok(Self {
+            config: Arc::new(ArcSwap::from_pointee(server_config(crypto)?)),
+        })
+    }
+
+    /// Replaces the TLS configuration used for new proxy connections. The previous configuration
+    /// is kept if the new [`Crypto`] is invalid.
+    pub fn reload(&self, crypto: &Crypto) -> anyhow::Result<()> {
+        self.config.store(Arc::new(server_config(crypto)?));
+        Ok(())
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.config.load_full())
+    }
+}
+
+fn server_config(crypto: &Crypto) -> anyhow::Result<ServerConfig> {
+    let crypto = crypto.clone();
+
+    let root_cert_store = Arc::new(RootCertStore {
+        roots: vec![webpki::anchor_from_trusted_cert(&crypto.root_ca_cert)?.to_owned()],
+    });
+
+    let config = ServerConfig::builder()
+        .with_client_cert_verifier(WebPkiClientVerifier::builder(root_cert_store).build()?)
+        .with_single_cert(vec![crypto.cert, crypto.root_ca_cert], crypto.key)?;
+
+    Ok(config)
+}
+
+pub(crate) fn reload_crypto(
+    _: Trigger<'_, ReloadCrypto>,
+    paths: Option<Res<'_, CryptoPaths>>,
+    tls_config: Option<Res<'_, ProxyTlsConfig>>,
+    mut commands: Commands<'_, '_>,
+) {
+    let Some(paths) = paths else {
+        warn!("cannot reload certificates: CryptoPaths resource is missing");
+        return;
+    };
+
+    let Some(tls_config) = tls_config else {
+        warn!("cannot reload certificates: ProxyTlsConfig resource is missing");
+        return;
+    };
+
+    let crypto = match paths.load() {
+        Ok(crypto) => crypto,
+        Err(e) => {
+            error!("failed to reload certificates, keeping the previous certificates: {e}");
+            return;
+        }
+    };
+
+    if let Err(e) = tls_config.reload(&crypto) {
+        error!("failed to reload certificates, keeping the previous certificates: {e}");
+        return;
+    }
+
+    info!("reloaded certificates, new proxy connections will use the new certificates");
+    commands.insert_resource(crypto);
+}
+
+async fn last_modified(paths: &CryptoPaths) -> Option<[SystemTime; 3]> {
+    let mut times = [SystemTime::UNIX_EPOCH; 3];
+
+    for (time, path) in times
+        .iter_mut()
+        .zip([&paths.root_ca_cert, &paths.cert, &paths.key])
+    {
+        *time = tokio::fs::metadata(path).await.ok()?.modified().ok()?;
+    }
+
+    Some(times)
+}
+
+async fn watch_crypto_files(paths: CryptoPaths, command_channel: CommandChannel) {
+    let mut previous = last_modified(&paths).await;
+    let mut interval = tokio::time::interval(CERTIFICATE_POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let current = last_modified(&paths).await;
+
+        // Files which are missing are most likely in the middle of being replaced, so the reload is
+        // delayed until all of them exist
+        if current.is_none() || current == previous {
+            continue;
+        }
+
+        previous = current;
+
+        info!("certificate files changed, reloading certificates");
+        command_channel.push(|world: &mut World| world.trigger(ReloadCrypto));
+    }
+}
+
+#[cfg(unix)]
+async fn watch_sighup(command_channel: CommandChannel) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut sighup = match signal(SignalKind::hangup()) {
+        Ok(sighup) => sighup,
+        Err(e) => {
+            warn!(
+                "failed to register SIGHUP handler, certificates cannot be reloaded by SIGHUP: {e}"
+            );
+            return;
+        }
+    };
+
+    while sighup.recv().await.is_some() {
+        info!("SIGHUP received, reloading certificates");
+        command_channel.push(|world: &mut World| world.trigger(ReloadCrypto));
+    }
+}
+
+/// Watches the certificate files for changes and listens for `SIGHUP`, triggering
+/// [`ReloadCrypto`] whenever either happens.
+pub fn init_crypto_reload(
+    runtime: &AsyncRuntime,
+    command_channel: CommandChannel,
+    paths: CryptoPaths,
+) {
+    #[cfg(unix)]
+    runtime.spawn(watch_sighup(command_channel.clone()));
+
+    runtime.spawn(watch_crypto_files(paths, command_channel));
+}
+
 fn get_pid_from_port(port: u16) -> Result<Option<u32>, std::io::Error> {
     let output = if cfg!(target_os = "windows") {
         // todo: untested
@@ -52,13 +239,93 @@ fn get_pid_from_port(port: u16) -> Result<Option<u32>, std::io::Error> {
     Ok(pid)
 }
 
+/// Local proxy ids start here so that they never collide with proxies connected over TCP
+const FIRST_LOCAL_PROXY_ID: u64 = 1 << 63;
+
+/// Connects a proxy which runs in the same process as the server, such as the fake proxy used in
+/// tests. Messages for the proxy are sent to the returned receiver in the same format as they are
+/// sent over TCP. Players are added with [`spawn_connection`] instead of `PlayerConnect`.
+pub fn connect_local_proxy(world: &mut World) -> (ProxyId, UnboundedReceiver<Bytes>) {
+    static NEXT_LOCAL_PROXY_ID: AtomicU64 = AtomicU64::new(FIRST_LOCAL_PROXY_ID);
+
+    let proxy_id = ProxyId::new(NEXT_LOCAL_PROXY_ID.fetch_add(1, Ordering::Relaxed));
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let egress_comm = EgressComm::new(tx, Arc::default());
+
+    world
+        .resource_mut::<Compose>()
+        .io_buf_mut()
+        .add_proxy(proxy_id, egress_comm);
+
+    (proxy_id, rx)
+}
+
+/// Spawns the entity of a player who has connected to a proxy. The player starts in the handshake
+/// state and receives packets from `receiver`.
+pub fn spawn_connection(
+    world: &mut World,
+    connection_id: ConnectionId,
+    receiver: packet_channel::Receiver,
+    connection_bandwidth: ConnectionBandwidth,
+) -> Entity {
+    let player = world
+        .spawn((
+            connection_id,
+            packet_state::Handshake(()),
+            PacketDecoder::default(),
+            receiver,
+            connection_bandwidth,
+        ))
+        .id();
+    world
+        .get_resource_mut::<StreamLookup>()
+        .expect("StreamLookup resource should exist")
+        .insert(connection_id.inner(), player);
+    player
+}
+
+/// Despawns the entity of a player who has disconnected from a proxy
+pub fn despawn_connection(world: &mut World, stream: u64) {
+    let player = world
+        .get_resource_mut::<StreamLookup>()
+        .expect("StreamLookup resource should exist")
+        .remove(&stream)
+        .expect("player from PlayerDisconnect must exist in the stream lookup map");
+
+    world.despawn(player);
+}
+
+/// Allows packets to be sent to a player once their connection is added. See
+/// [`SendError::ConnectionGone`](crate::net::SendError::ConnectionGone).
+pub(crate) fn track_connection_added(
+    trigger: Trigger<'_, OnAdd, ConnectionId>,
+    query: Query<'_, '_, &ConnectionId>,
+    mut compose: ResMut<'_, Compose>,
+) {
+    if let Ok(&connection_id) = query.get(trigger.target()) {
+        compose.io_buf_mut().add_connection(connection_id);
+    }
+}
+
+pub(crate) fn track_connection_removed(
+    trigger: Trigger<'_, OnRemove, ConnectionId>,
+    query: Query<'_, '_, &ConnectionId>,
+    mut compose: ResMut<'_, Compose>,
+) {
+    if let Ok(&connection_id) = query.get(trigger.target()) {
+        compose.io_buf_mut().remove_connection(connection_id);
+    }
+}
+
 async fn handle_proxy_messages(
     read: impl AsyncRead + Unpin,
     command_channel: CommandChannel,
     proxy_id: ProxyId,
+    bandwidth: Arc<ByteCounters>,
 ) {
     let mut reader = ProxyReader::new(read);
     let mut player_packet_sender: FxHashMap<u64, packet_channel::Sender> = FxHashMap::default();
+    let mut player_bandwidth: FxHashMap<u64, ConnectionBandwidth> = FxHashMap::default();
 
     // Process packets
     loop {
@@ -82,6 +349,8 @@ async fn handle_proxy_messages(
             }
         };
 
+        bandwidth.add_in(size_of::<u64>() + buffer.len());
+
         let result = unsafe { rkyv::access_unchecked::<ArchivedProxyToServerMessage<'_>>(buffer) };
 
         match result {
@@ -96,19 +365,108 @@ async fn handle_proxy_messages(
                     );
                 }
 
+                let connection_bandwidth = ConnectionBandwidth::default();
+                player_bandwidth.insert(stream, connection_bandwidth.clone());
+
                 command_channel.push(move |world: &mut World| {
+                    spawn_connection(
+                        world,
+                        ConnectionId::new(stream, proxy_id),
+                        receiver,
+                        connection_bandwidth,
+                    );
+                });
+            }
+            ArchivedProxyToServerMessage::PlayerTransfer(message) => {
+                let Ok(stream) = rkyv::deserialize::<u64, !>(&message.stream);
+                let Ok(uuid) = rkyv::deserialize::<u128, !>(&message.profile.uuid);
+                let username = message.profile.username.to_string();
+                let skin = if message.profile.textures.is_empty() {
+                    PlayerSkin::EMPTY
+                } else {
+                    PlayerSkin::new(
+                        message.profile.textures.to_string(),
+                        message.profile.signature.to_string(),
+                    )
+                };
+
+                let (sender, receiver) = packet_channel::channel(DEFAULT_FRAGMENT_SIZE);
+                if player_packet_sender.insert(stream, sender).is_some() {
+                    error!(
+                        "PlayerTransfer: player with same stream id already exists in \
+                         player_packet_sender"
+                    );
+                }
+
+                let connection_bandwidth = ConnectionBandwidth::default();
+                player_bandwidth.insert(stream, connection_bandwidth.clone());
+
+                command_channel.push(move |world: &mut World| {
+                    // The player enabled compression while logging in to the previous server
+                    let mut decoder = PacketDecoder::default();
+                    decoder.set_compression(
+                        world
+                            .resource::<Compose>()
+                            .global()
+                            .shared
+                            .compression_threshold,
+                    );
+
                     let player = world
                         .spawn((
                             ConnectionId::new(stream, proxy_id),
-                            packet_state::Handshake(()),
-                            PacketDecoder::default(),
+                            decoder,
                             receiver,
+                            connection_bandwidth,
                         ))
                         .id();
                     world
                         .get_resource_mut::<StreamLookup>()
                         .expect("StreamLookup resource should exist")
                         .insert(stream, player);
+
+                    ingress::complete_login(
+                        world,
+                        player,
+                        username,
+                        uuid::Uuid::from_u128(uuid),
+                        Some(skin),
+                    );
+                });
+            }
+            ArchivedProxyToServerMessage::TransferFailed(message) => {
+                let Ok(stream) = rkyv::deserialize::<u64, !>(&message.stream);
+
+                command_channel.push(move |world: &mut World| {
+                    let Some(&player) = world
+                        .get_resource::<StreamLookup>()
+                        .expect("StreamLookup resource should exist")
+                        .get(&stream)
+                    else {
+                        warn!("TransferFailed: no player with stream id exists");
+                        return;
+                    };
+
+                    transfer::transfer_failed(world, player);
+                });
+            }
+            ArchivedProxyToServerMessage::BulkShed(message) => {
+                let Ok(stream) = rkyv::deserialize::<u64, !>(&message.stream);
+
+                command_channel.push(move |world: &mut World| {
+                    let Some(&player) = world
+                        .get_resource::<StreamLookup>()
+                        .expect("StreamLookup resource should exist")
+                        .get(&stream)
+                    else {
+                        return;
+                    };
+
+                    // Chunks are sent in bulk, so all chunks in view are sent again as if the
+                    // player had just joined
+                    if let Some(mut chunk_position) = world.get_mut::<ChunkPosition>(player) {
+                        *chunk_position = ChunkPosition::null();
+                    }
                 });
             }
             ArchivedProxyToServerMessage::PlayerDisconnect(message) => {
@@ -120,14 +478,10 @@ async fn handle_proxy_messages(
                     );
                 }
 
-                command_channel.push(move |world: &mut World| {
-                    let player = world
-                        .get_resource_mut::<StreamLookup>()
-                        .expect("StreamLookup resource should exist")
-                        .remove(&stream)
-                        .expect("player from PlayerDisconnect must exist in the stream lookup map");
+                player_bandwidth.remove(&stream);
 
-                    world.despawn(player);
+                command_channel.push(move |world: &mut World| {
+                    despawn_connection(world, stream);
                 });
             }
             ArchivedProxyToServerMessage::PlayerPackets(message) => {
@@ -140,6 +494,10 @@ async fn handle_proxy_messages(
                     continue;
                 };
 
+                if let Some(connection_bandwidth) = player_bandwidth.get(&stream) {
+                    connection_bandwidth.add_in(message.data.len());
+                }
+
                 if let Err(e) = sender.send(&message.data) {
                     use packet_channel::SendError;
                     let needs_shutdown = match e {
@@ -214,7 +572,7 @@ async fn handle_proxy_messages(
     });
 }
 
-async fn inner(socket: SocketAddr, crypto: Crypto, command_channel: CommandChannel) {
+async fn inner(socket: SocketAddr, tls_config: ProxyTlsConfig, command_channel: CommandChannel) {
     let listener = match tokio::net::TcpListener::bind(socket).await {
         Ok(listener) => listener,
         Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
@@ -242,25 +600,6 @@ async fn inner(socket: SocketAddr, crypto: Crypto, command_channel: CommandChann
         Err(e) => panic!("Failed to bind to address {socket}: {e}"),
     };
 
-    let root_cert_store = Arc::new(RootCertStore {
-        roots: vec![
-            webpki::anchor_from_trusted_cert(&crypto.root_ca_cert)
-                .unwrap()
-                .to_owned(),
-        ],
-    });
-
-    let config = ServerConfig::builder()
-        .with_client_cert_verifier(
-            WebPkiClientVerifier::builder(root_cert_store)
-                .build()
-                .unwrap(),
-        )
-        .with_single_cert(vec![crypto.cert, crypto.root_ca_cert], crypto.key)
-        .unwrap();
-
-    let acceptor = TlsAcceptor::from(Arc::new(config));
-
     tokio::spawn(
         async move {
             let next_proxy_id = Arc::new(AtomicU64::new(0));
@@ -280,7 +619,9 @@ async fn inner(socket: SocketAddr, crypto: Crypto, command_channel: CommandChann
 
                 let command_channel = command_channel.clone();
                 let next_proxy_id = next_proxy_id.clone();
-                let stream = acceptor.accept(socket);
+                // The acceptor is obtained for each connection so that reloaded certificates are
+                // used by proxies which connect after the reload
+                let stream = tls_config.acceptor().accept(socket);
 
                 tokio::spawn(async move {
                     let stream = match stream.await {
@@ -299,12 +640,19 @@ async fn inner(socket: SocketAddr, crypto: Crypto, command_channel: CommandChann
                     let (read, mut write) = tokio::io::split(stream);
 
                     let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
-                    let egress_comm = EgressComm::from(tx.clone());
+                    let bandwidth = Arc::new(ByteCounters::default());
+                    let egress_comm = EgressComm::new(tx.clone(), bandwidth.clone());
                     let proxy_id = ProxyId::new(next_proxy_id.fetch_add(1, Ordering::Relaxed));
 
                     command_channel.push(move |world: &mut World| {
+                        let shard = world.get_resource::<Shard>().cloned().unwrap_or_default();
                         let mut compose = world.resource_mut::<Compose>();
                         compose.io_buf_mut().add_proxy(proxy_id, egress_comm);
+                        compose.io_buf().register_server(
+                            &shard.name,
+                            shard.region.as_deref().unwrap_or_default(),
+                            shard.arena.as_deref().unwrap_or_default(),
+                        );
                     });
 
                     let command_channel_clone = command_channel.clone();
@@ -357,12 +705,106 @@ async fn inner(socket: SocketAddr, crypto: Crypto, command_channel: CommandChann
                             ))
                             .unwrap();
                         }
+
+                        let mut query = world.query::<(Entity, &PacketChannel)>();
+                        for (channel, packet_channel) in query.iter(world) {
+                            let channel_id = ChannelId::from(channel).inner();
+
+                            tx.send(IoBuf::encode_proxy_message(
+                                &hyperion_proto::ServerToProxyMessage::AddChannel(
+                                    hyperion_proto::AddChannel {
+                                        channel_id,
+                                        unsubscribe_packets: packet_channel.unsubscribe_packets(),
+                                    },
+                                ),
+                            ))
+                            .unwrap();
+
+                            // The proxy only knows the subscribers which it was told about
+                            for subscriber in packet_channel.subscribers() {
+                                let Some(&stream) = world.get::<ConnectionId>(subscriber) else {
+                                    continue;
+                                };
+
+                                if stream.proxy_id() != proxy_id {
+                                    continue;
+                                }
+
+                                tx.send(IoBuf::encode_proxy_message(
+                                    &hyperion_proto::ServerToProxyMessage::SetChannelSubscription(
+                                        hyperion_proto::SetChannelSubscription {
+                                            channel_id,
+                                            stream: stream.inner(),
+                                            subscribed: true,
+                                        },
+                                    ),
+                                ))
+                                .unwrap();
+                            }
+                        }
+
+                        // Entities are visible by default, so the proxy is told again which
+                        // entities are hidden from the players connected through it
+                        let mut query =
+                            world.query_filtered::<EntityRef<'_>, (With<Uuid>, With<Name>)>();
+                        if let Some(rules) = world.get_resource::<RevealRules>() {
+                            let players = query.iter(world).collect::<Vec<_>>();
+
+                            for &viewer in &players {
+                                let Some(&stream) = viewer.get::<ConnectionId>() else {
+                                    continue;
+                                };
+
+                                if stream.proxy_id() != proxy_id {
+                                    continue;
+                                }
+
+                                for &target in &players {
+                                    if rules.can_see(viewer, target) {
+                                        continue;
+                                    }
+
+                                    tx.send(IoBuf::encode_proxy_message(
+                                        &hyperion_proto::ServerToProxyMessage::SetChannelHidden(
+                                            hyperion_proto::SetChannelHidden {
+                                                channel_id: ChannelId::from(target.id()).inner(),
+                                                stream: stream.inner(),
+                                                hidden: true,
+                                            },
+                                        ),
+                                    ))
+                                    .unwrap();
+                                }
+                            }
+                        }
+
+                        // Players who wait in the queue of the proxy are logged into this world
+                        if let Some(registries) = world.get_resource::<Registries>() {
+                            let compose = world.resource::<Compose>();
+                            let compression_threshold =
+                                compose.global().shared.compression_threshold;
+
+                            match waiting_room_packets(registries, compression_threshold) {
+                                Ok(data) => tx
+                                    .send(IoBuf::encode_proxy_message(
+                                        &hyperion_proto::ServerToProxyMessage::UpdateWaitingRoom(
+                                            hyperion_proto::UpdateWaitingRoom {
+                                                compression_threshold: compression_threshold.0,
+                                                data: &data,
+                                            },
+                                        ),
+                                    ))
+                                    .unwrap(),
+                                Err(e) => error!("failed to encode the waiting room: {e}"),
+                            }
+                        }
                     });
 
                     tokio::spawn(handle_proxy_messages(
                         read,
                         command_channel.clone(),
                         proxy_id,
+                        bandwidth,
                     ));
                 });
             }
@@ -375,9 +817,9 @@ pub fn init_proxy_comms(
     runtime: &AsyncRuntime,
     command_channel: CommandChannel,
     socket: SocketAddr,
-    crypto: Crypto,
+    tls_config: ProxyTlsConfig,
 ) {
-    runtime.spawn(inner(socket, crypto, command_channel));
+    runtime.spawn(inner(socket, tls_config, command_channel));
 }
 
 #[derive(Debug)]
@@ -0,0 +1,220 @@
+//! Detects players who are away from keyboard.
+//!
+//! Players without any input for [`AfkSettings::idle`] are marked with [`Afk`], which is removed
+//! again once they send input. AFK players are marked in the player list, and plugins may observe
+//! [`Afk`] being added or removed to update scoreboards. If [`AfkSettings::kick`] is set, players
+//! are kicked once they have been idle for that long, which frees up slots held by idle clients.
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use glam::DVec3;
+
+use crate::{
+    config::AfkConfig,
+    ingress,
+    simulation::{
+        kick::{KickExt, Kicked},
+        packet::play,
+        packet_state,
+    },
+    timings::timed,
+};
+
+/// How long players may be idle before being marked as AFK and kicked.
+#[derive(Resource, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AfkSettings {
+    pub idle: Duration,
+    /// Players are never kicked for being idle if this is `None`
+    pub kick: Option<Duration>,
+}
+
+impl Default for AfkSettings {
+    fn default() -> Self {
+        Self::from(&AfkConfig::default())
+    }
+}
+
+impl From<&AfkConfig> for AfkSettings {
+    fn from(config: &AfkConfig) -> Self {
+        Self {
+            idle: Duration::from_secs(config.idle_secs),
+            kick: config.kick_secs.map(Duration::from_secs),
+        }
+    }
+}
+
+/// Marks players who have been idle for at least [`AfkSettings::idle`].
+#[derive(Component, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Afk;
+
+/// The last meaningful input from a player. Movement packets only count when the player actually
+/// moved or looked around because clients send their position periodically even when idle.
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct LastInput {
+    pub at: Instant,
+    position: Option<DVec3>,
+    look: Option<(f32, f32)>,
+}
+
+impl LastInput {
+    #[must_use]
+    pub fn new(at: Instant) -> Self {
+        Self {
+            at,
+            position: None,
+            look: None,
+        }
+    }
+
+    /// How long the player has been idle
+    #[must_use]
+    pub fn idle(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.at)
+    }
+
+    fn moved(&mut self, position: DVec3, now: Instant) {
+        if self
+            .position
+            .replace(position)
+            .is_some_and(|old| old != position)
+        {
+            self.at = now;
+        }
+    }
+
+    fn looked(&mut self, yaw: f32, pitch: f32, now: Instant) {
+        if self
+            .look
+            .replace((yaw, pitch))
+            .is_some_and(|old| old != (yaw, pitch))
+        {
+            self.at = now;
+        }
+    }
+}
+
+fn initialize_last_input(
+    trigger: Trigger<'_, OnAdd, packet_state::Play>,
+    mut commands: Commands<'_, '_>,
+) {
+    commands
+        .entity(trigger.target())
+        .insert(LastInput::new(Instant::now()));
+}
+
+#[expect(
+    clippy::too_many_arguments,
+    reason = "each input packet needs a reader"
+)]
+fn track_input(
+    mut full: EventReader<'_, '_, play::Full>,
+    mut position: EventReader<'_, '_, play::PositionAndOnGround>,
+    mut look: EventReader<'_, '_, play::LookAndOnGround>,
+    mut hand_swing: EventReader<'_, '_, play::HandSwing>,
+    mut player_action: EventReader<'_, '_, play::PlayerAction>,
+    mut interact_block: EventReader<'_, '_, play::PlayerInteractBlock>,
+    mut interact_entity: EventReader<'_, '_, play::PlayerInteractEntity>,
+    mut interact_item: EventReader<'_, '_, play::PlayerInteractItem>,
+    mut chat: EventReader<'_, '_, play::ChatMessage>,
+    mut command: EventReader<'_, '_, play::CommandExecution>,
+    mut click_slot: EventReader<'_, '_, play::ClickSlot>,
+    mut query: Query<'_, '_, &mut LastInput>,
+) {
+    let now = Instant::now();
+
+    for packet in full.read() {
+        if let Ok(mut last_input) = query.get_mut(packet.sender()) {
+            last_input.moved(packet.position, now);
+            last_input.looked(packet.yaw, packet.pitch, now);
+        }
+    }
+
+    for packet in position.read() {
+        if let Ok(mut last_input) = query.get_mut(packet.sender()) {
+            last_input.moved(packet.position, now);
+        }
+    }
+
+    for packet in look.read() {
+        if let Ok(mut last_input) = query.get_mut(packet.sender()) {
+            last_input.looked(packet.yaw, packet.pitch, now);
+        }
+    }
+
+    let senders = hand_swing
+        .read()
+        .map(|packet| packet.sender())
+        .chain(player_action.read().map(|packet| packet.sender()))
+        .chain(interact_block.read().map(|packet| packet.sender()))
+        .chain(interact_entity.read().map(|packet| packet.sender()))
+        .chain(interact_item.read().map(|packet| packet.sender()))
+        .chain(chat.read().map(|packet| packet.sender()))
+        .chain(command.read().map(|packet| packet.sender()))
+        .chain(click_slot.read().map(|packet| packet.sender()));
+
+    for sender in senders {
+        if let Ok(mut last_input) = query.get_mut(sender) {
+            last_input.at = now;
+        }
+    }
+}
+
+fn update_afk(
+    settings: Res<'_, AfkSettings>,
+    query: Query<'_, '_, (Entity, &LastInput, Has<Afk>), Without<Kicked>>,
+    mut commands: Commands<'_, '_>,
+) {
+    let now = Instant::now();
+
+    for (player, last_input, afk) in &query {
+        let idle = last_input.idle(now);
+
+        if settings.kick.is_some_and(|kick| idle >= kick) {
+            commands
+                .entity(player)
+                .kick("You have been kicked for being idle for too long");
+        } else if idle >= settings.idle {
+            if !afk {
+                commands.entity(player).insert(Afk);
+            }
+        } else if afk {
+            commands.entity(player).remove::<Afk>();
+        }
+    }
+}
+
+pub struct AfkPlugin;
+
+impl Plugin for AfkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AfkSettings>();
+        app.add_observer(initialize_last_input);
+        app.add_systems(
+            FixedUpdate,
+            (timed(track_input), timed(update_afk))
+                .chain()
+                .after(ingress::decode::play),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_periodic_position_is_not_input() {
+        let start = Instant::now();
+        let mut last_input = LastInput::new(start);
+
+        let later = start + Duration::from_secs(10);
+        last_input.moved(DVec3::new(1.0, 64.0, 1.0), later);
+        last_input.moved(DVec3::new(1.0, 64.0, 1.0), later);
+        last_input.looked(90.0, 0.0, later);
+        assert_eq!(last_input.at, start);
+
+        last_input.moved(DVec3::new(2.0, 64.0, 1.0), later);
+        assert_eq!(last_input.at, later);
+    }
+}
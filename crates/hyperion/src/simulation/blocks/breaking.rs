@@ -0,0 +1,550 @@
+//! Validates how long players take to break blocks in survival and shows their progress to nearby
+//! players.
+//!
+//! The time needed to break a block follows the vanilla formula, which depends on the hardness of
+//! the block, the held tool and its efficiency level, [`Haste`], and whether the player is on the
+//! ground. Only blocks with known hardness are validated, and breaking any other block is always
+//! allowed.
+
+use bevy::prelude::*;
+use glam::IVec3;
+use hyperion_inventory::PlayerInventory;
+use hyperion_utils::EntityExt;
+use tracing::error;
+use valence_generated::block::BlockState;
+use valence_protocol::{BlockPos, ItemKind, VarInt, packets::play::BlockBreakingProgressS2c};
+
+use crate::{
+    ingress::anticheat::{Check, SuspicionEvent},
+    net::{Compose, ConnectionId},
+    simulation::{
+        MovementTracking, Position,
+        blocks::Blocks,
+        event::{AbortDestroyBlock, CancelEvents, Cancellable, DestroyBlock, StartDestroyBlock},
+        util::enchantment_level,
+    },
+    timings::timed,
+};
+
+/// The fraction of a block which must have been broken when a player finishes breaking it. This
+/// matches vanilla, which accepts some progress being lost to latency.
+pub const MIN_PROGRESS: f32 = 0.7;
+
+/// The destroy stage which removes the breaking animation of a block
+const CLEAR_STAGE: u8 = 10;
+
+/// The level of the haste effect of a player. Game modes insert this component while the effect
+/// is active.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Haste {
+    pub level: u8,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ToolKind {
+    Pickaxe,
+    Axe,
+    Shovel,
+    Hoe,
+    Sword,
+    Shears,
+}
+
+/// A tool held by a player
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Tool {
+    pub kind: ToolKind,
+    /// Wood and gold are 0, stone is 1, iron is 2, diamond is 3 and netherite is 4
+    pub tier: u8,
+    /// The mining speed of the tool on blocks it is effective against
+    pub speed: u8,
+}
+
+impl Tool {
+    #[must_use]
+    pub const fn from_item(item: ItemKind) -> Option<Self> {
+        use ItemKind as I;
+        use ToolKind as T;
+
+        let (kind, tier, speed) = match item {
+            I::WoodenPickaxe => (T::Pickaxe, 0, 2),
+            I::StonePickaxe => (T::Pickaxe, 1, 4),
+            I::IronPickaxe => (T::Pickaxe, 2, 6),
+            I::DiamondPickaxe => (T::Pickaxe, 3, 8),
+            I::NetheritePickaxe => (T::Pickaxe, 4, 9),
+            I::GoldenPickaxe => (T::Pickaxe, 0, 12),
+            I::WoodenAxe => (T::Axe, 0, 2),
+            I::StoneAxe => (T::Axe, 1, 4),
+            I::IronAxe => (T::Axe, 2, 6),
+            I::DiamondAxe => (T::Axe, 3, 8),
+            I::NetheriteAxe => (T::Axe, 4, 9),
+            I::GoldenAxe => (T::Axe, 0, 12),
+            I::WoodenShovel => (T::Shovel, 0, 2),
+            I::StoneShovel => (T::Shovel, 1, 4),
+            I::IronShovel => (T::Shovel, 2, 6),
+            I::DiamondShovel => (T::Shovel, 3, 8),
+            I::NetheriteShovel => (T::Shovel, 4, 9),
+            I::GoldenShovel => (T::Shovel, 0, 12),
+            I::WoodenHoe => (T::Hoe, 0, 2),
+            I::StoneHoe => (T::Hoe, 1, 4),
+            I::IronHoe => (T::Hoe, 2, 6),
+            I::DiamondHoe => (T::Hoe, 3, 8),
+            I::NetheriteHoe => (T::Hoe, 4, 9),
+            I::GoldenHoe => (T::Hoe, 0, 12),
+            I::WoodenSword => (T::Sword, 0, 1),
+            I::StoneSword => (T::Sword, 1, 1),
+            I::IronSword => (T::Sword, 2, 1),
+            I::DiamondSword => (T::Sword, 3, 1),
+            I::NetheriteSword => (T::Sword, 4, 1),
+            I::GoldenSword => (T::Sword, 0, 1),
+            I::Shears => (T::Shears, 0, 1),
+            _ => return None,
+        };
+
+        Some(Self { kind, tier, speed })
+    }
+}
+
+/// How a block is broken
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BlockHardness {
+    /// The hardness of the block, which is negative for unbreakable blocks
+    pub hardness: f32,
+    /// The tool which breaks the block faster
+    pub tool: Option<ToolKind>,
+    /// The minimum tier of [`BlockHardness::tool`] needed for the block to drop anything. Blocks
+    /// without a required tier drop items regardless of the tool.
+    pub required_tier: Option<u8>,
+}
+
+impl BlockHardness {
+    const fn new(hardness: f32, tool: Option<ToolKind>, required_tier: Option<u8>) -> Self {
+        Self {
+            hardness,
+            tool,
+            required_tier,
+        }
+    }
+
+    /// Returns the hardness of common blocks, or `None` if the hardness of the block is unknown
+    #[must_use]
+    pub fn of(block: BlockState) -> Option<Self> {
+        use ToolKind::{Axe, Hoe, Pickaxe, Shovel, Sword};
+
+        let name = block.to_kind().to_str();
+
+        let hardness = match name {
+            "air" | "cave_air" | "void_air" | "fire" => Self::new(0.0, None, None),
+            "bedrock" | "barrier" | "end_portal_frame" => Self::new(-1.0, None, None),
+            "obsidian" | "crying_obsidian" => Self::new(50.0, Some(Pickaxe), Some(3)),
+            "stone" | "stone_bricks" | "smooth_stone" => Self::new(1.5, Some(Pickaxe), Some(0)),
+            "cobblestone" | "bricks" | "mossy_cobblestone" => {
+                Self::new(2.0, Some(Pickaxe), Some(0))
+            }
+            "deepslate" | "end_stone" => Self::new(3.0, Some(Pickaxe), Some(0)),
+            "sandstone" | "red_sandstone" => Self::new(0.8, Some(Pickaxe), Some(0)),
+            "netherrack" => Self::new(0.4, Some(Pickaxe), Some(0)),
+            "coal_ore" => Self::new(3.0, Some(Pickaxe), Some(0)),
+            "iron_ore" | "lapis_ore" | "copper_ore" => Self::new(3.0, Some(Pickaxe), Some(1)),
+            "gold_ore" | "diamond_ore" | "emerald_ore" | "redstone_ore" => {
+                Self::new(3.0, Some(Pickaxe), Some(2))
+            }
+            "dirt" | "sand" | "red_sand" | "soul_sand" => Self::new(0.5, Some(Shovel), None),
+            "grass_block" | "gravel" | "clay" | "farmland" => Self::new(0.6, Some(Shovel), None),
+            "ladder" => Self::new(0.4, Some(Axe), None),
+            "cobweb" => Self::new(4.0, Some(Sword), Some(0)),
+            "terracotta" => Self::new(1.25, Some(Pickaxe), Some(0)),
+            "glass" | "glass_pane" => Self::new(0.3, None, None),
+            _ if name.ends_with("_glazed_terracotta") => Self::new(1.4, Some(Pickaxe), Some(0)),
+            _ if name.ends_with("_terracotta") => Self::new(1.25, Some(Pickaxe), Some(0)),
+            _ if name.ends_with("_wool") => Self::new(0.8, None, None),
+            _ if name.ends_with("_planks") => Self::new(2.0, Some(Axe), None),
+            _ if name.ends_with("_log") || name.ends_with("_wood") => {
+                Self::new(2.0, Some(Axe), None)
+            }
+            _ if name.ends_with("_leaves") => Self::new(0.2, Some(Hoe), None),
+            _ if name.ends_with("_stained_glass") || name.ends_with("_stained_glass_pane") => {
+                Self::new(0.3, None, None)
+            }
+            _ if name.ends_with("_bed") => Self::new(0.2, None, None),
+            _ => return None,
+        };
+
+        Some(hardness)
+    }
+
+    fn is_wool(block: BlockState) -> bool {
+        block.to_kind().to_str().ends_with("_wool")
+    }
+
+    fn is_cobweb_or_leaves(block: BlockState) -> bool {
+        let name = block.to_kind().to_str();
+        name == "cobweb" || name.ends_with("_leaves")
+    }
+}
+
+/// Everything which affects how fast a player breaks a block
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Miner {
+    pub tool: Option<Tool>,
+    pub efficiency: u8,
+    pub haste: u8,
+    pub on_ground: bool,
+}
+
+impl Miner {
+    /// The fraction of `block` which is broken every tick. This is infinite for blocks which break
+    /// instantly and blocks with unknown hardness, and zero for unbreakable blocks.
+    #[must_use]
+    pub fn progress_per_tick(&self, block: BlockState) -> f32 {
+        let Some(hardness) = BlockHardness::of(block) else {
+            return f32::INFINITY;
+        };
+
+        if hardness.hardness < 0.0 {
+            return 0.0;
+        }
+
+        if hardness.hardness == 0.0 {
+            return f32::INFINITY;
+        }
+
+        let shears = self.tool.filter(|tool| tool.kind == ToolKind::Shears);
+        let effective = self
+            .tool
+            .filter(|tool| Some(tool.kind) == hardness.tool && tool.kind != ToolKind::Sword);
+
+        let mut speed = match (shears, effective) {
+            (Some(_), _) if BlockHardness::is_cobweb_or_leaves(block) => 15.0,
+            (Some(_), _) if BlockHardness::is_wool(block) => 5.0,
+            (None, _)
+                if hardness.tool == Some(ToolKind::Sword)
+                    && self.tool.is_some_and(|tool| tool.kind == ToolKind::Sword) =>
+            {
+                15.0
+            }
+            (_, Some(tool)) => f32::from(tool.speed),
+            _ => 1.0,
+        };
+
+        if speed > 1.0 && self.efficiency > 0 {
+            speed += f32::from(self.efficiency).powi(2) + 1.0;
+        }
+
+        speed *= 0.2f32.mul_add(f32::from(self.haste), 1.0);
+
+        if !self.on_ground {
+            speed /= 5.0;
+        }
+
+        let can_harvest = hardness.required_tier.is_none_or(|tier| {
+            let sword_or_shears_on_cobweb = hardness.tool == Some(ToolKind::Sword)
+                && self
+                    .tool
+                    .is_some_and(|tool| matches!(tool.kind, ToolKind::Sword | ToolKind::Shears));
+
+            sword_or_shears_on_cobweb
+                || self
+                    .tool
+                    .is_some_and(|tool| Some(tool.kind) == hardness.tool && tool.tier >= tier)
+        });
+
+        let divisor = if can_harvest { 30.0 } else { 100.0 };
+        speed / hardness.hardness / divisor
+    }
+
+    /// The number of ticks needed to break `block`, or `None` if the block cannot be broken
+    #[must_use]
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "the progress per tick is positive and the result is rounded up"
+    )]
+    pub fn ticks_to_break(&self, block: BlockState) -> Option<u32> {
+        let progress = self.progress_per_tick(block);
+
+        if progress <= 0.0 {
+            return None;
+        }
+
+        if progress >= 1.0 {
+            return Some(0);
+        }
+
+        Some(progress.recip().ceil() as u32)
+    }
+}
+
+/// The block a player is currently breaking
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct BreakingBlock {
+    pub position: IVec3,
+    pub start_tick: i64,
+    /// The value of [`Miner::progress_per_tick`] when the player started breaking the block
+    pub progress_per_tick: f32,
+    /// The destroy stage which was last sent to nearby players
+    stage: Option<u8>,
+}
+
+impl BreakingBlock {
+    /// The fraction of the block which has been broken by the end of `tick`
+    #[must_use]
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "blocks are never broken over enough ticks for this to lose precision"
+    )]
+    pub fn progress(&self, tick: i64) -> f32 {
+        let ticks = (tick - self.start_tick + 1).max(0) as f32;
+        (self.progress_per_tick * ticks).min(1.0)
+    }
+
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "the progress is between 0 and 1"
+    )]
+    fn stage(&self, tick: i64) -> u8 {
+        ((self.progress(tick) * 10.0) as u8).min(9)
+    }
+}
+
+fn broadcast_stage(
+    compose: &Compose,
+    player: Entity,
+    connection_id: ConnectionId,
+    position: &Position,
+    block: IVec3,
+    stage: u8,
+) {
+    let pkt = BlockBreakingProgressS2c {
+        entity_id: VarInt(player.minecraft_id()),
+        position: BlockPos::new(block.x, block.y, block.z),
+        destroy_stage: stage,
+    };
+
+    // The player already shows their own progress
+    if let Err(e) = compose
+        .broadcast_local(&pkt, position.to_chunk())
+        .exclude(connection_id)
+        .send()
+    {
+        error!("failed to send block breaking progress: {e}");
+    }
+}
+
+fn start_breaking(
+    mut events: EventReader<'_, '_, StartDestroyBlock>,
+    blocks: Res<'_, Blocks>,
+    compose: Res<'_, Compose>,
+    query: Query<'_, '_, (&PlayerInventory, &MovementTracking, Option<&Haste>)>,
+    mut commands: Commands<'_, '_>,
+) {
+    let tick = compose.global().tick;
+
+    for event in events.read() {
+        let (inventory, tracking, haste) = match query.get(event.from) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("failed to start breaking block: query failed: {e}");
+                continue;
+            }
+        };
+
+        let Some(block) = blocks.get_block(event.position) else {
+            continue;
+        };
+
+        let held = &inventory.get_cursor().stack;
+
+        let miner = Miner {
+            tool: Tool::from_item(held.item),
+            efficiency: enchantment_level(held, "minecraft:efficiency"),
+            haste: haste.map_or(0, |haste| haste.level),
+            on_ground: tracking.was_on_ground,
+        };
+
+        commands.entity(event.from).insert(BreakingBlock {
+            position: event.position,
+            start_tick: tick,
+            progress_per_tick: miner.progress_per_tick(block),
+            stage: None,
+        });
+    }
+}
+
+fn abort_breaking(
+    mut events: EventReader<'_, '_, AbortDestroyBlock>,
+    compose: Res<'_, Compose>,
+    query: Query<'_, '_, (&BreakingBlock, &Position, &ConnectionId)>,
+    mut commands: Commands<'_, '_>,
+) {
+    for event in events.read() {
+        let Ok((breaking, position, &connection_id)) = query.get(event.from) else {
+            continue;
+        };
+
+        if breaking.stage.is_some() {
+            broadcast_stage(
+                &compose,
+                event.from,
+                connection_id,
+                position,
+                breaking.position,
+                CLEAR_STAGE,
+            );
+        }
+
+        commands.entity(event.from).remove::<BreakingBlock>();
+    }
+}
+
+/// Cancels [`DestroyBlock`] events for blocks which were broken too quickly
+fn validate_breaking(
+    mut events: EventMutator<'_, '_, Cancellable<DestroyBlock>>,
+    compose: Res<'_, Compose>,
+    query: Query<'_, '_, (Option<&BreakingBlock>, &Position, &ConnectionId)>,
+    mut suspicion_writer: EventWriter<'_, SuspicionEvent>,
+    mut commands: Commands<'_, '_>,
+) {
+    let tick = compose.global().tick;
+
+    for event in events.read() {
+        let (breaking, position, &connection_id) = match query.get(event.from) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("failed to validate block breaking: query failed: {e}");
+                continue;
+            }
+        };
+
+        let progress = breaking
+            .filter(|breaking| breaking.position == event.position)
+            .map_or(0.0, |breaking| breaking.progress(tick));
+
+        if progress < MIN_PROGRESS {
+            event.cancel();
+            suspicion_writer.write(SuspicionEvent {
+                player: event.from,
+                check: Check::FastBreak,
+                score: (MIN_PROGRESS - progress) / MIN_PROGRESS,
+            });
+        }
+
+        if let Some(breaking) = breaking {
+            if breaking.stage.is_some() {
+                broadcast_stage(
+                    &compose,
+                    event.from,
+                    connection_id,
+                    position,
+                    breaking.position,
+                    CLEAR_STAGE,
+                );
+            }
+
+            commands.entity(event.from).remove::<BreakingBlock>();
+        }
+    }
+}
+
+fn send_breaking_progress(
+    compose: Res<'_, Compose>,
+    mut query: Query<'_, '_, (Entity, &mut BreakingBlock, &Position, &ConnectionId)>,
+) {
+    let tick = compose.global().tick;
+
+    for (player, mut breaking, position, &connection_id) in &mut query {
+        if !breaking.progress_per_tick.is_finite() {
+            continue;
+        }
+
+        let stage = breaking.stage(tick);
+
+        if breaking.stage == Some(stage) {
+            continue;
+        }
+
+        breaking.stage = Some(stage);
+        broadcast_stage(
+            &compose,
+            player,
+            connection_id,
+            position,
+            breaking.position,
+            stage,
+        );
+    }
+}
+
+pub struct BlockBreakingPlugin;
+
+impl Plugin for BlockBreakingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                timed(start_breaking),
+                timed(abort_breaking),
+                timed(validate_breaking),
+                timed(send_breaking_progress),
+            )
+                .chain()
+                .in_set(CancelEvents),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_generated::block::BlockKind;
+
+    use super::*;
+
+    fn ticks(tool: Option<ItemKind>, efficiency: u8, haste: u8, block: BlockKind) -> Option<u32> {
+        let miner = Miner {
+            tool: tool.and_then(Tool::from_item),
+            efficiency,
+            haste,
+            on_ground: true,
+        };
+        miner.ticks_to_break(BlockState::from_kind(block))
+    }
+
+    #[test]
+    fn test_ticks_to_break() {
+        // Vanilla break times in ticks
+        assert_eq!(ticks(None, 0, 0, BlockKind::WhiteWool), Some(24));
+        assert_eq!(
+            ticks(Some(ItemKind::Shears), 0, 0, BlockKind::WhiteWool),
+            Some(5)
+        );
+        assert_eq!(ticks(None, 0, 0, BlockKind::EndStone), Some(300));
+        assert_eq!(
+            ticks(Some(ItemKind::WoodenPickaxe), 0, 0, BlockKind::EndStone),
+            Some(45)
+        );
+        assert_eq!(
+            ticks(Some(ItemKind::DiamondPickaxe), 0, 0, BlockKind::Obsidian),
+            Some(188)
+        );
+        assert_eq!(
+            ticks(Some(ItemKind::DiamondPickaxe), 5, 2, BlockKind::Obsidian),
+            Some(32)
+        );
+        assert_eq!(ticks(None, 0, 0, BlockKind::Bedrock), None);
+        assert_eq!(ticks(None, 0, 0, BlockKind::Air), Some(0));
+    }
+
+    #[test]
+    fn test_not_on_ground() {
+        let block = BlockState::from_kind(BlockKind::OakPlanks);
+        let mut miner = Miner {
+            on_ground: true,
+            ..Miner::default()
+        };
+        let on_ground = miner.progress_per_tick(block);
+
+        miner.on_ground = false;
+        assert!((miner.progress_per_tick(block) * 5.0 - on_ground).abs() < 1e-6);
+    }
+}
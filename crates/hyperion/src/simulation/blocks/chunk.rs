@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{collections::BTreeSet, fmt::Debug};
 
 use bytes::Bytes;
 use glam::{IVec2, IVec3};
@@ -67,6 +67,13 @@ pub struct Column {
     pub data: ColumnData,
 
     pub position: IVec2,
+
+    /// Indices of block entities which changed after the chunk was loaded. These are not included
+    /// in [`Column::base_packet_bytes`], so they are sent separately.
+    pub changed_block_entities: BTreeSet<u32>,
+
+    /// Indices of block entities which changed since the last tick
+    pub block_entities_changed_since_last_tick: Vec<u32>,
 }
 
 fn y_index(y: i16) -> u16 {
@@ -79,6 +86,8 @@ impl Column {
             base_packet_bytes,
             data,
             position,
+            changed_block_entities: BTreeSet::new(),
+            block_entities_changed_since_last_tick: Vec::new(),
         }
     }
 
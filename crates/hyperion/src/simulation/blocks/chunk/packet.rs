@@ -1,10 +1,13 @@
-use std::io::Write;
+use std::{borrow::Cow, io::Write};
 
 use glam::IVec2;
 use valence_protocol::{
-    ChunkSectionPos, Encode, Packet, VarInt,
-    packets::play::{ChunkDeltaUpdateS2c, chunk_delta_update_s2c::ChunkDeltaUpdateEntry},
+    BlockPos, ChunkSectionPos, Encode, Packet, VarInt,
+    packets::play::{
+        BlockEntityUpdateS2c, ChunkDeltaUpdateS2c, chunk_delta_update_s2c::ChunkDeltaUpdateEntry,
+    },
 };
+use valence_server::layer::chunk::Chunk;
 
 use crate::{
     PacketBundle,
@@ -134,4 +137,46 @@ impl Column {
                 }
             })
     }
+
+    /// Packets for every block entity which changed after the chunk was loaded. These are sent
+    /// after [`Column::base_packet_bytes`].
+    pub fn block_entity_packets(&self) -> impl Iterator<Item = BlockEntityUpdateS2c<'_>> + '_ {
+        self.changed_block_entities
+            .iter()
+            .filter_map(|&idx| self.block_entity_packet(idx))
+    }
+
+    /// Packets for the block entities which changed since the last tick
+    pub fn block_entity_drain_packets(&mut self) -> Vec<BlockEntityUpdateS2c<'_>> {
+        let mut changed = std::mem::take(&mut self.block_entities_changed_since_last_tick);
+        changed.sort_unstable();
+        changed.dedup();
+
+        changed
+            .into_iter()
+            .filter_map(|idx| self.block_entity_packet(idx))
+            .collect()
+    }
+
+    fn block_entity_packet(&self, idx: u32) -> Option<BlockEntityUpdateS2c<'_>> {
+        let x = idx % 16;
+        let z = (idx / 16) % 16;
+        let y = idx / 16 / 16;
+
+        // The block entity may have been removed
+        let data = self.data.block_entities.get(&idx)?;
+        let kind = self.data.block_state(x, y, z).block_entity_kind()?;
+
+        let position = BlockPos::new(
+            self.position.x * 16 + i32::try_from(x).ok()?,
+            i32::try_from(y).ok()? + i32::from(START_Y),
+            self.position.y * 16 + i32::try_from(z).ok()?,
+        );
+
+        Some(BlockEntityUpdateS2c {
+            position,
+            kind,
+            data: Cow::Borrowed(data),
+        })
+    }
 }
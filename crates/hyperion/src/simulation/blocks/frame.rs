@@ -48,6 +48,7 @@ impl Blocks {
                 };
 
                 self.should_update.insert(idx as u32);
+                self.modified.insert(I16Vec2::new(section_x, section_z));
 
                 let chunk = &mut loaded_chunk.data;
 
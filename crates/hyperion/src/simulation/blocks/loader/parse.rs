@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, sync::Arc};
 
 use thiserror::Error;
 use tracing::warn;
@@ -148,9 +148,8 @@ impl Chunk for ColumnData {
     fn fill_block_state_section(&mut self, sect_y: u32, block: BlockState) {
         check_section_oob(self, sect_y);
 
-        self.sections[sect_y as usize]
-            .block_states
-            .fill(block.to_raw());
+        self.sections[sect_y as usize].block_states =
+            Arc::new(hyperion_palette::PalettedContainer::Single(block.to_raw()));
     }
 
     fn block_entity(&self, x: u32, y: u32, z: u32) -> Option<&Compound> {
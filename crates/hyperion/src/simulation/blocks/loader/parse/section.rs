@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use glam::IVec3;
 use more_asserts::debug_assert_lt;
 use roaring::RoaringBitmap;
@@ -6,7 +8,9 @@ use valence_server::layer::chunk::BiomeContainer;
 
 #[derive(Clone, Debug)]
 pub struct Section {
-    pub block_states: hyperion_palette::PalettedContainer,
+    /// The block states are shared with [`Snapshot`](crate::simulation::blocks::snapshot::Snapshot)s
+    /// of the section, and are copied the first time a block changes while they are shared.
+    pub block_states: Arc<hyperion_palette::PalettedContainer>,
     pub biomes: BiomeContainer,
 
     // todo: maybe make stack array of 2048
@@ -20,7 +24,7 @@ pub struct Section {
 impl Default for Section {
     fn default() -> Self {
         Self {
-            block_states: hyperion_palette::PalettedContainer::Single(0),
+            block_states: Arc::new(hyperion_palette::PalettedContainer::Single(0)),
             biomes: BiomeContainer::default(),
             block_light: None,
             sky_light: None,
@@ -49,7 +53,8 @@ impl Section {
 
 impl Section {
     pub fn set(&mut self, idx: u16, new: BlockState) -> BlockState {
-        let prev = unsafe { self.block_states.set_unchecked(idx as usize, new.to_raw()) };
+        let block_states = Arc::make_mut(&mut self.block_states);
+        let prev = unsafe { block_states.set_unchecked(idx as usize, new.to_raw()) };
         unsafe { BlockState::from_raw(prev).unwrap_unchecked() }
     }
 
@@ -71,9 +76,13 @@ impl Section {
         debug_assert_lt!(idx, 4096);
 
         let new = new.to_raw();
-        let before = unsafe { self.block_states.set_unchecked(idx as usize, new) };
+        let before = unsafe { self.block_states.get_unchecked(idx as usize) };
 
+        // Setting a block to the same state would needlessly copy shared block states
         if before != new {
+            let block_states = Arc::make_mut(&mut self.block_states);
+            unsafe { block_states.set_unchecked(idx as usize, new) };
+
             self.changed_since_last_tick.insert(u32::from(idx));
             self.changed.insert(u32::from(idx));
         }
@@ -92,7 +101,7 @@ mod tests {
 
     fn create_test_section() -> Section {
         Section {
-            block_states: hyperion_palette::PalettedContainer::Single(0), // air (probably)
+            block_states: Arc::new(hyperion_palette::PalettedContainer::Single(0)), /* air (probably) */
             biomes: BiomeContainer::new(),
             block_light: None,
             sky_light: None,
@@ -194,4 +203,17 @@ mod tests {
             BlockState::GRASS_BLOCK.to_raw()
         );
     }
+
+    #[test]
+    fn test_shared_block_states_are_copied_on_write() {
+        let mut section = create_test_section();
+        let shared = section.block_states.clone();
+
+        section.set_delta(0, BlockState::AIR);
+        assert!(Arc::ptr_eq(&section.block_states, &shared));
+
+        section.set_delta(0, BlockState::STONE);
+        assert!(!Arc::ptr_eq(&section.block_states, &shared));
+        assert_eq!(shared.get(0), BlockState::AIR.to_raw());
+    }
 }
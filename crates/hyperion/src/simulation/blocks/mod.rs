@@ -1,6 +1,12 @@
 //! Constructs for working with blocks.
 
-use std::{future::Future, ops::Try, path::Path, pin::Pin, sync::Arc};
+use std::{
+    future::Future,
+    ops::Try,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+};
 
 use anyhow::Context;
 use bevy::prelude::*;
@@ -13,10 +19,11 @@ use indexmap::IndexMap;
 use loader::{ChunkLoaderHandle, launch_loader};
 use rayon::iter::ParallelIterator;
 use roaring::RoaringBitmap;
-use rustc_hash::FxBuildHasher;
+use rustc_hash::{FxBuildHasher, FxHashSet};
 use shared::WorldShared;
 use tracing::error;
 use valence_generated::block::BlockState;
+use valence_nbt::Compound;
 use valence_server::layer::chunk::Chunk;
 
 use crate::{
@@ -33,9 +40,16 @@ pub mod chunk;
 mod loader;
 mod manager;
 
+pub mod breaking;
 pub mod frame;
 mod region;
 mod shared;
+pub mod snapshot;
+
+/// The index of a block entity in [`loader::parse::ColumnData::block_entities`]
+const fn block_entity_index(x: u32, y: u32, z: u32) -> u32 {
+    x + z * 16 + y * 16 * 16
+}
 
 pub enum GetChunk<'a> {
     Loaded(&'a Column),
@@ -69,6 +83,10 @@ pub struct Blocks {
     /// Map to a Chunk by Entity ID
     chunk_cache: IndexMap<I16Vec2, Column, FxBuildHasher>,
     should_update: RoaringBitmap,
+    /// Chunks which were changed since they were loaded
+    modified: FxHashSet<I16Vec2>,
+    /// The directory the world was loaded from
+    save_directory: Option<PathBuf>,
 
     loader_handle: ChunkLoaderHandle,
 
@@ -83,6 +101,8 @@ impl From<ChunkLoaderHandle> for Blocks {
         Self {
             chunk_cache: IndexMap::default(),
             should_update: RoaringBitmap::default(),
+            modified: FxHashSet::default(),
+            save_directory: None,
             loader_handle,
             tx_loaded_chunks,
             rx_loaded_chunks,
@@ -101,7 +121,8 @@ impl Blocks {
 
         let loader_handle = launch_loader(shared, runtime);
 
-        let result = Self::from(loader_handle);
+        let mut result = Self::from(loader_handle);
+        result.save_directory = Some(path.to_path_buf());
 
         Ok(result)
     }
@@ -118,6 +139,29 @@ impl Blocks {
         let bounds_min = IVec3::new(i32::MIN / 2, -64, i32::MIN / 2);
         let bounds_max = IVec3::new(i32::MAX / 2, 320, i32::MAX / 2);
 
+        self.first_collision_in(ray, bounds_min, bounds_max)
+    }
+
+    /// The first block which is hit on the way from `origin` to `end`. The distance of the
+    /// collision is the fraction of the way to `end`.
+    #[must_use]
+    pub fn first_collision_between(&self, origin: Vec3, end: Vec3) -> Option<RayCollision> {
+        let ray = Ray::from_points(origin, end);
+
+        // The bounds are padded because the traversal starts from the truncated origin
+        let bounds_min = origin.min(end).floor().as_ivec3() - IVec3::ONE;
+        let bounds_max = origin.max(end).floor().as_ivec3() + IVec3::ONE;
+
+        self.first_collision_in(ray, bounds_min, bounds_max)
+            .filter(|collision| collision.distance <= 1.0)
+    }
+
+    fn first_collision_in(
+        &self,
+        ray: Ray,
+        bounds_min: IVec3,
+        bounds_max: IVec3,
+    ) -> Option<RayCollision> {
         // Use voxel traversal to efficiently walk through blocks
         for cell in ray.voxel_traversal(bounds_min, bounds_max) {
             if let Some(block) = self.get_block(cell) {
@@ -190,6 +234,18 @@ impl Blocks {
         self.should_update.clear();
     }
 
+    /// The directory the world was loaded from, which is `None` for an empty world. Changes to
+    /// blocks are never written to this directory.
+    #[must_use]
+    pub fn save_directory(&self) -> Option<&Path> {
+        self.save_directory.as_deref()
+    }
+
+    /// Returns the chunks which have been changed since they were loaded
+    pub fn modified_chunks(&self) -> impl Iterator<Item = I16Vec2> + '_ {
+        self.modified.iter().copied()
+    }
+
     pub const fn cache_mut(&mut self) -> &mut IndexMap<I16Vec2, Column, FxBuildHasher> {
         &mut self.chunk_cache
     }
@@ -414,12 +470,85 @@ impl Blocks {
         let old_state = chunk.data.set_delta(x, y, z, state);
 
         if old_state != state {
+            // The block entity of the old block, such as the text of a sign, does not belong to
+            // the new block
+            if old_state.block_entity_kind() != state.block_entity_kind() {
+                chunk.data.set_block_entity(x, y, z, None);
+                chunk
+                    .changed_block_entities
+                    .remove(&block_entity_index(x, y, z));
+            }
+
             self.should_update.insert(u32::try_from(chunk_idx).unwrap());
+            self.modified.insert(chunk_pos);
         }
 
         Ok(old_state)
     }
 
+    /// Get the block entity data of a block, such as the text of a sign
+    #[must_use]
+    pub fn get_block_entity(&self, position: IVec3) -> Option<&Compound> {
+        const START_Y: i32 = -64;
+
+        let chunk_pos: IVec2 = IVec2::new(position.x, position.z) >> 4;
+        let chunk_start_block: IVec2 = chunk_pos << 4;
+
+        let chunk = self.get_loaded_chunk(chunk_pos.as_i16vec2())?;
+
+        let x = u32::try_from(position.x - chunk_start_block[0]).ok()?;
+        let y = u32::try_from(position.y - START_Y).ok()?;
+        let z = u32::try_from(position.z - chunk_start_block[1]).ok()?;
+
+        if y >= chunk.data.height() {
+            return None;
+        }
+
+        chunk.data.block_entity(x, y, z)
+    }
+
+    /// Sets or removes the block entity data of a block. The data is sent to every player at the
+    /// end of the tick. Returns the old block entity data.
+    pub fn set_block_entity(
+        &mut self,
+        position: IVec3,
+        data: Option<Compound>,
+    ) -> Result<Option<Compound>, TrySetBlockDeltaError> {
+        const START_Y: i32 = -64;
+
+        if position.y < START_Y {
+            return Err(TrySetBlockDeltaError::OutOfBounds);
+        }
+
+        let chunk_pos: IVec2 = IVec2::new(position.x, position.z) >> 4;
+        let chunk_start_block: IVec2 = chunk_pos << 4;
+
+        let chunk_pos = chunk_pos.as_i16vec2();
+
+        let Some((chunk_idx, _, chunk)) = self.chunk_cache.get_full_mut(&chunk_pos) else {
+            return Err(TrySetBlockDeltaError::ChunkNotLoaded);
+        };
+
+        let x = u32::try_from(position.x - chunk_start_block[0]).unwrap();
+        let y = u32::try_from(position.y - START_Y).unwrap();
+        let z = u32::try_from(position.z - chunk_start_block[1]).unwrap();
+
+        if y >= chunk.data.height() {
+            return Err(TrySetBlockDeltaError::OutOfBounds);
+        }
+
+        let old = chunk.data.set_block_entity(x, y, z, data);
+
+        let idx = block_entity_index(x, y, z);
+        chunk.changed_block_entities.insert(idx);
+        chunk.block_entities_changed_since_last_tick.push(idx);
+
+        self.should_update.insert(u32::try_from(chunk_idx).unwrap());
+        self.modified.insert(chunk_pos);
+
+        Ok(old)
+    }
+
     // todo: allow modifying the chunk. we will need to implement resending
     // So,
     // for instance, if a player modifies a chunk, we're going to need to rebroadcast it to all the players in that region.
@@ -0,0 +1,213 @@
+//! Snapshots of a region of blocks which can be restored later, such as to reset an arena between
+//! rounds.
+//!
+//! Taking a snapshot does not copy any blocks. The snapshot shares the block states of each chunk
+//! section with [`Blocks`], and a section is only copied once one of its blocks changes, so only
+//! the sections which changed during a round take up extra memory.
+//!
+//! ```ignore
+//! let arena = blocks.snapshot(IVec3::new(-64, 0, -64), IVec3::new(64, 128, 64));
+//!
+//! // ... the round is played ...
+//!
+//! let changed = blocks.rollback(&arena);
+//! ```
+
+use std::sync::Arc;
+
+use glam::{I16Vec2, IVec2, IVec3, UVec3};
+use hyperion_palette::PalettedContainer;
+use rustc_hash::{FxHashMap, FxHashSet};
+use valence_generated::block::BlockState;
+use valence_nbt::Compound;
+
+use crate::simulation::blocks::{Blocks, chunk::Column};
+
+const START_Y: i32 = -64;
+
+/// The blocks and block entities in a cuboid region at the time [`Blocks::snapshot`] was called.
+/// Only chunks which were loaded at that time are included.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    min: IVec3,
+    max: IVec3,
+    /// The block states of every section which overlaps the region, by chunk position and
+    /// section index
+    sections: FxHashMap<(I16Vec2, usize), Arc<PalettedContainer>>,
+    block_entities: FxHashMap<IVec3, Compound>,
+    chunks: FxHashSet<I16Vec2>,
+}
+
+impl Snapshot {
+    /// The lowest corner of the region
+    #[must_use]
+    pub const fn min(&self) -> IVec3 {
+        self.min
+    }
+
+    /// The highest corner of the region, which is included in the region
+    #[must_use]
+    pub const fn max(&self) -> IVec3 {
+        self.max
+    }
+
+    /// Whether `position` is in the region
+    #[must_use]
+    pub fn contains(&self, position: IVec3) -> bool {
+        position.cmpge(self.min).all() && position.cmple(self.max).all()
+    }
+}
+
+/// The indices of the sections of `column` which overlap the heights from `min_y` to `max_y`
+fn section_range(column: &Column, min_y: i32, max_y: i32) -> impl Iterator<Item = usize> {
+    let len = i32::try_from(column.data.sections.len()).unwrap();
+    let first = ((min_y - START_Y) >> 4).max(0);
+    let last = ((max_y - START_Y) >> 4).min(len - 1);
+
+    (first..=last).map(|index| usize::try_from(index).unwrap())
+}
+
+/// The block entities of `column` which are in the region from `min` to `max`
+fn block_entities_in(
+    column: &Column,
+    min: IVec3,
+    max: IVec3,
+) -> impl Iterator<Item = (IVec3, &Compound)> + '_ {
+    let origin = IVec3::new(column.position.x << 4, START_Y, column.position.y << 4);
+
+    column
+        .data
+        .block_entities
+        .iter()
+        .map(move |(&index, data)| {
+            let offset = UVec3::new(index & 0xF, index >> 8, (index >> 4) & 0xF);
+            (origin + offset.as_ivec3(), data)
+        })
+        .filter(move |(position, _)| position.cmpge(min).all() && position.cmple(max).all())
+}
+
+impl Blocks {
+    /// The loaded chunks which overlap the region from `min` to `max`
+    fn columns_in(&self, min: IVec3, max: IVec3) -> impl Iterator<Item = (I16Vec2, &Column)> + '_ {
+        let min = (IVec2::new(min.x, min.z) >> 4).as_i16vec2();
+        let max = (IVec2::new(max.x, max.z) >> 4).as_i16vec2();
+
+        (min.x..=max.x)
+            .flat_map(move |x| (min.y..=max.y).map(move |z| I16Vec2::new(x, z)))
+            .filter_map(move |position| Some((position, self.get_loaded_chunk(position)?)))
+    }
+
+    /// Takes a snapshot of the blocks from `min` to `max` (inclusive), which can be restored with
+    /// [`Blocks::rollback`]
+    #[must_use]
+    pub fn snapshot(&self, min: IVec3, max: IVec3) -> Snapshot {
+        let (min, max) = (min.min(max), min.max(max));
+
+        let mut snapshot = Snapshot {
+            min,
+            max,
+            sections: FxHashMap::default(),
+            block_entities: FxHashMap::default(),
+            chunks: FxHashSet::default(),
+        };
+
+        for (position, column) in self.columns_in(min, max) {
+            snapshot.chunks.insert(position);
+
+            for index in section_range(column, min.y, max.y) {
+                let block_states = column.data.sections[index].block_states.clone();
+                snapshot.sections.insert((position, index), block_states);
+            }
+
+            snapshot.block_entities.extend(
+                block_entities_in(column, min, max)
+                    .map(|(position, data)| (position, data.clone())),
+            );
+        }
+
+        snapshot
+    }
+
+    /// Restores the blocks and block entities in the region of `snapshot`. The changes are sent
+    /// to players in the same way as other block changes. Chunks which were not loaded when the
+    /// snapshot was taken or which are not loaded now are left unchanged.
+    ///
+    /// Returns the number of blocks which were changed.
+    pub fn rollback(&mut self, snapshot: &Snapshot) -> usize {
+        let mut changes = Vec::new();
+
+        for (&(chunk_position, index), block_states) in &snapshot.sections {
+            let Some(section) = self
+                .get_loaded_chunk(chunk_position)
+                .and_then(|column| column.data.sections.get(index))
+            else {
+                continue;
+            };
+
+            // Sections which did not change since the snapshot still share their block states
+            if Arc::ptr_eq(&section.block_states, block_states) {
+                continue;
+            }
+
+            let origin = IVec3::new(
+                i32::from(chunk_position.x) << 4,
+                START_Y + i32::try_from(index).unwrap() * 16,
+                i32::from(chunk_position.y) << 4,
+            );
+            let min = (snapshot.min - origin).max(IVec3::ZERO).as_uvec3();
+            let max = (snapshot.max - origin).min(IVec3::splat(15)).as_uvec3();
+
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    for x in min.x..=max.x {
+                        let block_index = (x + z * 16 + y * 256) as usize;
+                        let state = block_states.get(block_index);
+
+                        if section.block_states.get(block_index) != state {
+                            let offset = UVec3::new(x, y, z).as_ivec3();
+                            changes.push((origin + offset, state));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut changed = 0;
+
+        for (position, state) in changes {
+            let Some(state) = BlockState::from_raw(state) else {
+                continue;
+            };
+
+            if self.set_block(position, state).is_ok() {
+                changed += 1;
+            }
+        }
+
+        // Block entities are restored after the blocks, because changing a block removes the block
+        // entity of the old block
+        let current = self
+            .columns_in(snapshot.min, snapshot.max)
+            .filter(|(position, _)| snapshot.chunks.contains(position))
+            .flat_map(|(_, column)| block_entities_in(column, snapshot.min, snapshot.max))
+            .map(|(position, data)| (position, data.clone()))
+            .collect::<FxHashMap<_, _>>();
+
+        let removed = current
+            .keys()
+            .filter(|&position| !snapshot.block_entities.contains_key(position))
+            .map(|&position| (position, None));
+
+        let restored = snapshot
+            .block_entities
+            .iter()
+            .filter(|&(position, data)| current.get(position) != Some(data))
+            .map(|(&position, data)| (position, Some(data.clone())));
+
+        for (position, data) in removed.chain(restored) {
+            let _ = self.set_block_entity(position, data);
+        }
+
+        changed
+    }
+}
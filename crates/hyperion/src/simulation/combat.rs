@@ -0,0 +1,313 @@
+//! Turns melee attacks from players into [`AttackEntity`] events, including vanilla critical hits
+//! and sweeping attacks.
+//!
+//! The damage of these events only accounts for the held weapon. Game modes are expected to apply
+//! armor, immunity and knockback when handling [`AttackEntity`]. Attacks on entities which are out
+//! of reach or behind a wall are dropped.
+
+use std::borrow::Cow;
+
+use bevy::prelude::*;
+use geometry::aabb::Aabb;
+use glam::{DVec3, Vec3};
+use hyperion_inventory::PlayerInventory;
+use tracing::error;
+use valence_generated::{block::BlockState, item::ItemKind};
+use valence_protocol::{
+    Particle, ident,
+    packets::play::{ParticleS2c, player_interact_entity_c2s::EntityInteraction},
+};
+
+use crate::{
+    ingress::{
+        self,
+        anticheat::{AnticheatConfig, Check, EYE_HEIGHT, SuspicionEvent, line_of_sight, reach},
+    },
+    net::{Compose, Priority},
+    simulation::{
+        EntitySize, Flight, MovementTracking, Position, Yaw, aabb,
+        blocks::Blocks,
+        event::{AttackEntity, CancelEvents, Cancellable},
+        packet::play,
+        util::enchantment_level,
+    },
+    spatial::SpatialIndex,
+    timings::timed,
+};
+
+/// The damage multiplier of critical hits
+pub const CRITICAL_HIT_MULTIPLIER: f32 = 1.5;
+
+/// The maximum distance between the attacker and the entities hit by a sweeping attack
+const SWEEP_RANGE: f32 = 3.0;
+
+/// The damage dealt by a melee attack with `item`, which is 1 for items which are not weapons
+#[must_use]
+pub const fn weapon_damage(item: ItemKind) -> f32 {
+    match item {
+        ItemKind::WoodenSword | ItemKind::GoldenSword => 4.0,
+        ItemKind::StoneSword => 5.0,
+        ItemKind::IronSword => 6.0,
+        ItemKind::DiamondSword => 7.0,
+        ItemKind::NetheriteSword => 8.0,
+        ItemKind::WoodenPickaxe => 2.0,
+        _ => 1.0,
+    }
+}
+
+const fn is_sword(item: ItemKind) -> bool {
+    matches!(
+        item,
+        ItemKind::WoodenSword
+            | ItemKind::GoldenSword
+            | ItemKind::StoneSword
+            | ItemKind::IronSword
+            | ItemKind::DiamondSword
+            | ItemKind::NetheriteSword
+    )
+}
+
+/// Returns true if an attack by a player is a critical hit, which requires the player to be
+/// falling without sprinting, flying, climbing or swimming.
+#[must_use]
+pub fn is_critical_hit(
+    tracking: &MovementTracking,
+    flight: &Flight,
+    position: &Position,
+    block: Option<BlockState>,
+) -> bool {
+    tracking.fall_distance(position) > 0.0
+        && !tracking.was_on_ground
+        && !tracking.sprinting
+        && !flight.is_flying
+        && !block.is_some_and(ingress::anticheat::is_climbable_or_liquid)
+}
+
+/// The damage dealt to the other entities hit by a sweeping attack with the given level of
+/// sweeping edge
+#[must_use]
+pub fn sweep_damage(damage: f32, sweeping_edge: u8) -> f32 {
+    let level = f32::from(sweeping_edge);
+    (level / (level + 1.0)).mul_add(damage, 1.0)
+}
+
+/// The area in which other entities are hit by a sweeping attack against `target`
+#[must_use]
+pub fn sweep_area(target: Aabb) -> Aabb {
+    let inflate = Vec3::new(1.0, 0.25, 1.0);
+    Aabb::new(target.min - inflate, target.max + inflate)
+}
+
+fn crit_particles(position: Vec3) -> ParticleS2c<'static> {
+    ParticleS2c {
+        particle: Cow::Owned(Particle::Crit),
+        long_distance: true,
+        position: position.as_dvec3() + DVec3::new(0.0, 1.0, 0.0),
+        max_speed: 0.5,
+        count: 100,
+        offset: Vec3::new(0.5, 0.5, 0.5),
+    }
+}
+
+fn handle_melee_attacks(
+    mut packets: EventReader<'_, '_, play::PlayerInteractEntity>,
+    entities: &Entities,
+    compose: Res<'_, Compose>,
+    blocks: Res<'_, Blocks>,
+    index: Res<'_, SpatialIndex>,
+    origin_query: Query<
+        '_,
+        '_,
+        (
+            &Position,
+            &Yaw,
+            &PlayerInventory,
+            &MovementTracking,
+            &Flight,
+        ),
+    >,
+    target_query: Query<'_, '_, (&Position, &EntitySize)>,
+    config: Res<'_, AnticheatConfig>,
+    mut writer: EventWriter<'_, Cancellable<AttackEntity>>,
+    mut suspicion_writer: EventWriter<'_, SuspicionEvent>,
+) {
+    for packet in packets.read() {
+        if packet.interact != EntityInteraction::Attack {
+            continue;
+        }
+
+        let origin = packet.sender();
+
+        let Some(target) = entities.resolve_from_id(bytemuck::cast(packet.entity_id.0)) else {
+            error!("handle melee attack failed: target id is invalid");
+            continue;
+        };
+
+        let (origin_pos, origin_yaw, inventory, tracking, flight) = match origin_query.get(origin) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("handle melee attack failed: query failed: {e}");
+                continue;
+            }
+        };
+
+        let (&target_pos, &target_size) = match target_query.get(target) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("handle melee attack failed: query failed: {e}");
+                continue;
+            }
+        };
+
+        let eye = **origin_pos + Vec3::new(0.0, EYE_HEIGHT, 0.0);
+        let target_aabb = aabb(*target_pos, target_size);
+
+        // Attacks which are out of reach are reported by the anticheat
+        if reach(eye, target_aabb) > config.entity_reach {
+            continue;
+        }
+
+        if !line_of_sight(eye, target_aabb, None, &blocks) {
+            suspicion_writer.write(SuspicionEvent {
+                player: origin,
+                check: Check::LineOfSight,
+                score: 1.0,
+            });
+            continue;
+        }
+
+        let weapon = &inventory.get_cursor().stack;
+        let damage = weapon_damage(weapon.item);
+
+        let block = blocks.get_block(origin_pos.floor().as_ivec3());
+        let critical = is_critical_hit(tracking, flight, origin_pos, block);
+
+        let sweeping =
+            !critical && !tracking.sprinting && tracking.was_on_ground && is_sword(weapon.item);
+
+        writer.write(Cancellable::new(AttackEntity {
+            origin,
+            target,
+            direction: (*target_pos - **origin_pos).normalize(),
+            damage: if critical {
+                damage * CRITICAL_HIT_MULTIPLIER
+            } else {
+                damage
+            },
+            sound: if critical {
+                ident!("minecraft:entity.player.attack.crit")
+            } else if sweeping {
+                ident!("minecraft:entity.player.attack.sweep")
+            } else {
+                ident!("minecraft:entity.player.attack.knockback")
+            },
+            particles: critical.then(|| crit_particles(*target_pos)),
+            extra_knockback: 0.0,
+            fire_ticks: 0,
+        }));
+
+        if !sweeping {
+            continue;
+        }
+
+        let damage = sweep_damage(damage, enchantment_level(weapon, "minecraft:sweeping"));
+        let area = sweep_area(target_aabb);
+
+        for other in index.get_collisions(area, target_query.as_readonly()) {
+            if other == origin || other == target {
+                continue;
+            }
+
+            let Ok((&other_pos, _)) = target_query.get(other) else {
+                continue;
+            };
+
+            if origin_pos.distance(*other_pos) >= SWEEP_RANGE {
+                continue;
+            }
+
+            writer.write(Cancellable::new(AttackEntity {
+                origin,
+                target: other,
+                direction: (*other_pos - **origin_pos).normalize(),
+                damage,
+                sound: ident!("minecraft:entity.player.hurt"),
+                particles: None,
+                extra_knockback: 0.0,
+                fire_ticks: 0,
+            }));
+        }
+
+        // The sweep particle appears in front of the attacker
+        let (sin, cos) = origin_yaw.to_radians().sin_cos();
+        let particle = ParticleS2c {
+            particle: Cow::Owned(Particle::SweepAttack),
+            long_distance: false,
+            position: (**origin_pos + Vec3::new(-sin, 0.9, cos)).as_dvec3(),
+            max_speed: 0.0,
+            count: 1,
+            offset: Vec3::ZERO,
+        };
+
+        if let Err(e) = compose
+            .broadcast_local(&particle, origin_pos.to_chunk())
+            .priority(Priority::Bulk)
+            .send()
+        {
+            error!("failed to send attack particles: {e}");
+        }
+    }
+}
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            timed(handle_melee_attacks)
+                .after(ingress::decode::play)
+                .before(CancelEvents),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep_damage() {
+        assert!((sweep_damage(7.0, 0) - 1.0).abs() < f32::EPSILON);
+        assert!((sweep_damage(7.0, 1) - 4.5).abs() < f32::EPSILON);
+        assert!((sweep_damage(8.0, 3) - 7.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_critical_hit() {
+        let position = Position::from(Vec3::new(0.0, 10.0, 0.0));
+        let flight = Flight::default();
+        let mut tracking = MovementTracking {
+            fall_start_y: 11.0,
+            ..MovementTracking::default()
+        };
+
+        assert!(is_critical_hit(&tracking, &flight, &position, None));
+
+        tracking.sprinting = true;
+        assert!(!is_critical_hit(&tracking, &flight, &position, None));
+
+        tracking.sprinting = false;
+        tracking.was_on_ground = true;
+        assert!(!is_critical_hit(&tracking, &flight, &position, None));
+
+        tracking.was_on_ground = false;
+        let ladder = BlockState::LADDER;
+        assert!(!is_critical_hit(
+            &tracking,
+            &flight,
+            &position,
+            Some(ladder)
+        ));
+    }
+}
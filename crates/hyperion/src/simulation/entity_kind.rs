@@ -1,6 +1,17 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(
+    Component,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize
+)]
 #[repr(C)]
 pub enum EntityKind {
     Allay = 0,
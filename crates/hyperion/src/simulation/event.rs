@@ -10,7 +10,63 @@ use valence_protocol::{
 };
 
 use super::blocks::RayCollision;
-use crate::simulation::skin::PlayerSkin;
+use crate::{config::DuplicateLoginPolicy, simulation::skin::PlayerSkin};
+
+/// An event which can be cancelled by systems that run before the systems which handle it.
+///
+/// Systems which cancel events should read them with an [`EventMutator`] and be added to
+/// [`CancelEvents`], which runs after the events are written and before they are handled. Handlers
+/// should skip cancelled events, although they may still need to resync the client, such as by
+/// resending a block which the client predicted would be placed.
+///
+/// ```ignore
+/// fn protect_spawn(mut events: EventMutator<'_, '_, Cancellable<event::PlaceBlock>>) {
+///     for event in events.read() {
+///         if event.position.y > 100 {
+///             event.cancel();
+///         }
+///     }
+/// }
+///
+/// app.add_systems(FixedUpdate, protect_spawn.in_set(CancelEvents));
+/// ```
+#[derive(Event, Copy, Clone, Debug, PartialEq, Eq, Deref)]
+pub struct Cancellable<T> {
+    #[deref]
+    event: T,
+    cancelled: bool,
+}
+
+impl<T> Cancellable<T> {
+    #[must_use]
+    pub const fn new(event: T) -> Self {
+        Self {
+            event,
+            cancelled: false,
+        }
+    }
+
+    /// Prevents handlers which run after this system from handling the event
+    pub const fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    #[must_use]
+    pub const fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+impl<T> From<T> for Cancellable<T> {
+    fn from(event: T) -> Self {
+        Self::new(event)
+    }
+}
+
+/// Systems which cancel [`Cancellable`] events. Systems which write cancellable events run before
+/// this set and systems which handle them run after it.
+#[derive(SystemSet, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CancelEvents;
 
 // TODO: Check that all of these events are needed
 
@@ -34,8 +90,8 @@ pub struct SetSkin {
 }
 
 /// Represents an attack action by a player in the game. This attack may not be succesful such as
-/// when a player attempts to attack a teammate.
-#[derive(Event, Clone, Debug)]
+/// when a player attempts to attack a teammate. This is sent as a [`Cancellable`] event.
+#[derive(Clone, Debug)]
 pub struct AttackEntity {
     /// The player that is performing the attack. This can be indirect, such as the player who
     /// fired an arrow.
@@ -51,6 +107,11 @@ pub struct AttackEntity {
     /// Particles to broadcast to all clients except the origin. The origin may already have
     /// generated these particles locally
     pub particles: Option<ParticleS2c<'static>>,
+    /// Horizontal knockback in blocks per tick on top of the usual knockback, such as from the
+    /// Punch enchantment
+    pub extra_knockback: f32,
+    /// The number of ticks to set the target on fire for, such as from the Flame enchantment
+    pub fire_ticks: u16,
 }
 
 #[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
@@ -60,14 +121,24 @@ pub struct StartDestroyBlock {
     pub sequence: i32,
 }
 
+/// Sent when a player stops breaking a block before it is broken
 #[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AbortDestroyBlock {
+    pub position: IVec3,
+    pub from: Entity,
+    pub sequence: i32,
+}
+
+/// Sent as a [`Cancellable`] event
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct DestroyBlock {
     pub position: IVec3,
     pub from: Entity,
     pub sequence: i32,
 }
 
-#[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
+/// Sent as a [`Cancellable`] event
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct PlaceBlock {
     pub position: IVec3,
     pub block: BlockState,
@@ -75,18 +146,37 @@ pub struct PlaceBlock {
     pub sequence: i32,
 }
 
-#[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
+/// Sent as a [`Cancellable`] event
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct ToggleDoor {
     pub position: IVec3,
     pub from: Entity,
     pub sequence: i32,
 }
 
+/// Swings an arm of an entity. Players who can see the entity are sent the swing animation, so
+/// plugins can send this to animate non-player entities too.
 #[derive(Event, Copy, Clone, Debug)]
 pub struct SwingArm {
+    pub client: Entity,
     pub hand: Hand,
 }
 
+/// Plays the hurt animation and sound of an entity to the players who can see it and to the
+/// entity itself if it is a player. This does not change the health of the entity.
+///
+/// <https://wiki.vg/index.php?title=Protocol&oldid=18375#Damage_Event>
+#[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Hurt {
+    pub target: Entity,
+    /// The id of the damage type in the `minecraft:damage_type` registry
+    pub damage_type: i32,
+    /// The entity which caused the damage, such as the player who shot an arrow
+    pub source_cause: Option<Entity>,
+    /// The entity which dealt the damage, such as the arrow
+    pub source_direct: Option<Entity>,
+}
+
 #[derive(Event, Copy, Clone, Debug)]
 pub struct ReleaseUseItem {
     pub from: Entity,
@@ -113,9 +203,15 @@ pub enum Posture {
     Digging = 14,
 }
 
+/// Sent when the posture of a player changes, such as when they start sneaking or swimming. The
+/// [`Pose`](crate::simulation::metadata::entity::Pose) and
+/// [`EntityFlags`](crate::simulation::metadata::entity::EntityFlags) of the player are updated
+/// from this event, so other players see the new posture.
+///
 /// <https://wiki.vg/index.php?title=Protocol&oldid=18375#Set_Entity_Metadata>
 #[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
 pub struct PostureUpdate {
+    pub client: Entity,
     /// The new posture of the entity.
     pub state: Posture,
 }
@@ -160,11 +256,51 @@ pub struct UpdateSelectedSlotEvent {
     pub slot: u8,
 }
 
+/// Sent when a player lands after falling more than 3 blocks.
+///
+/// Game modes which handle fall damage differently may change [`HitGroundEvent::damage`] with an
+/// [`EventMutator`] before the damage is applied.
 #[derive(Event, Clone, Debug)]
 pub struct HitGroundEvent {
     pub client: Entity,
     /// This is at least 3
     pub fall_distance: f32,
+    /// The fall damage taken by the player in vanilla
+    pub damage: f32,
+}
+
+impl HitGroundEvent {
+    /// Computes the vanilla fall damage from the fall distance and the level of feather falling on
+    /// the boots of the player. Each level of feather falling reduces the damage by 12%.
+    #[must_use]
+    pub fn new(client: Entity, fall_distance: f32, feather_falling: u8) -> Self {
+        let base = (fall_distance - 3.0).ceil().max(0.0);
+        let reduction = (f32::from(feather_falling) * 3.0).min(20.0) / 25.0;
+
+        Self {
+            client,
+            fall_distance,
+            damage: base * (1.0 - reduction),
+        }
+    }
+}
+
+/// Sent when the health of a player drops to zero
+#[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PlayerDeath {
+    pub victim: Entity,
+    /// The player who dealt the final hit, or `None` if the player died without being attacked,
+    /// such as from fall damage
+    pub killer: Option<Entity>,
+}
+
+/// Sent by the game once it accepts a chat message and shows it to other players. Messages
+/// which are rejected, such as by a cooldown, are not sent, so plugins which forward chat should
+/// read this instead of the chat packets.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct ChatSent {
+    pub sender: Entity,
+    pub message: String,
 }
 
 #[derive(Event, Clone, Debug)]
@@ -173,3 +309,52 @@ pub struct InteractEvent {
     pub hand: Hand,
     pub sequence: i32,
 }
+
+/// Sent when a player right clicks a sign. The block which the player is holding is not placed.
+#[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SignClickEvent {
+    pub client: Entity,
+    pub position: IVec3,
+    pub hand: Hand,
+}
+
+/// Sent after a player finished editing a sign and the new text was stored
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct SignEditEvent {
+    pub client: Entity,
+    pub position: IVec3,
+    pub is_front_text: bool,
+    pub lines: [String; 4],
+}
+
+/// Triggered while a player logs in with the username of a player who is already online.
+/// Observers may change [`DuplicateLogin::policy`], which starts as the configured
+/// [`DuplicateLoginPolicy`], to decide what happens to this login.
+///
+/// ```ignore
+/// app.add_observer(|mut trigger: Trigger<'_, DuplicateLogin>| {
+///     trigger.event_mut().policy = DuplicateLoginPolicy::RejectNew;
+/// });
+/// ```
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateLogin {
+    /// The player who is logging in
+    pub player: Entity,
+    /// The player who is already online with the same username
+    pub existing: Entity,
+    pub username: String,
+    pub policy: DuplicateLoginPolicy,
+}
+
+/// Triggered while a player logs in to a server which already has
+/// [`Config::max_players`](crate::config::Config::max_players) players. The player is disconnected
+/// unless an observer sets [`PlayerLimitReached::allow`], such as to let staff join anyway.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct PlayerLimitReached {
+    /// The player who is logging in
+    pub player: Entity,
+    pub uuid: uuid::Uuid,
+    pub username: String,
+    /// Whether the player may join even though the server is full
+    pub allow: bool,
+}
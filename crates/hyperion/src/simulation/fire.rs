@@ -0,0 +1,49 @@
+//! Entities which are on fire.
+//!
+//! [`Burning`] only shows the entity as being on fire. Game modes which want burning entities to
+//! take damage must apply it themselves.
+
+use bevy::prelude::*;
+
+use crate::{simulation::metadata::entity::EntityFlags, tick_rate::AdvanceTick, timings::timed};
+
+/// Sets an entity on fire for a number of ticks. The component is removed once the fire goes out.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Burning {
+    pub ticks: u16,
+}
+
+fn update_burning(
+    mut query: Query<'_, '_, (Entity, &mut Burning, Option<&mut EntityFlags>)>,
+    mut commands: Commands<'_, '_>,
+) {
+    for (entity, mut burning, flags) in &mut query {
+        if let Some(mut flags) = flags
+            && (*flags & EntityFlags::ON_FIRE) != EntityFlags::ON_FIRE
+        {
+            *flags |= EntityFlags::ON_FIRE;
+        }
+
+        if burning.ticks == 0 {
+            commands.entity(entity).remove::<Burning>();
+            continue;
+        }
+
+        burning.ticks -= 1;
+    }
+}
+
+fn extinguish(trigger: Trigger<'_, OnRemove, Burning>, mut query: Query<'_, '_, &mut EntityFlags>) {
+    if let Ok(mut flags) = query.get_mut(trigger.target()) {
+        *flags &= !EntityFlags::ON_FIRE;
+    }
+}
+
+pub struct FirePlugin;
+
+impl Plugin for FirePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(extinguish);
+        app.add_systems(FixedUpdate, timed(update_burning).in_set(AdvanceTick));
+    }
+}
@@ -8,10 +8,11 @@ use valence_generated::{
     item::ItemKind,
 };
 use valence_protocol::{
-    Hand, VarInt,
+    VarInt,
     packets::play::{
         GameMessageS2c, OpenWrittenBookS2c, UpdatePlayerAbilitiesC2s,
-        client_command_c2s::ClientCommand, player_action_c2s::PlayerAction,
+        client_command_c2s::ClientCommand, client_settings_c2s::MainArm,
+        player_action_c2s::PlayerAction,
     },
 };
 use valence_text::IntoText;
@@ -20,15 +21,19 @@ use crate::{
     ingress,
     net::{Compose, ConnectionId},
     simulation::{
-        Aabb, ConfirmBlockSequences, EntitySize, Flight, MovementTracking, PendingTeleportation,
-        Pitch, Position, Yaw, aabb,
-        animation::{self, ActiveAnimation},
-        block_bounds,
+        Aabb, ClientSettings, ConfirmBlockSequences, EntitySize, Flight, MovementTracking,
+        PendingTeleportation, Pitch, Position, Yaw, aabb, block_bounds,
         blocks::Blocks,
         event,
-        metadata::{entity::Pose, living_entity::HandStates},
+        metadata::{
+            entity::{EntityFlags, Pose},
+            living_entity::HandStates,
+            player::{DisplayedSkinParts, MainHand},
+        },
         packet::{OrderedPacketRef, play},
+        sign,
     },
+    timings::timed,
 };
 
 #[expect(
@@ -47,7 +52,17 @@ fn position_and_look_updates(
         '_,
         '_,
         (
-            Query<'_, '_, (&EntitySize, &mut MovementTracking, &mut Position, &Yaw)>,
+            Query<
+                '_,
+                '_,
+                (
+                    &EntitySize,
+                    &mut MovementTracking,
+                    &mut Position,
+                    &Yaw,
+                    &mut Pose,
+                ),
+            >,
             Query<'_, '_, (&mut Yaw, &mut Pitch)>,
             Query<'_, '_, &mut Position>,
         ),
@@ -187,14 +202,24 @@ fn position_and_look_updates(
 fn change_position_or_correct_client(
     client: Entity,
     connection_id: ConnectionId,
-    mut query: Query<'_, '_, (&EntitySize, &mut MovementTracking, &mut Position, &Yaw)>,
+    mut query: Query<
+        '_,
+        '_,
+        (
+            &EntitySize,
+            &mut MovementTracking,
+            &mut Position,
+            &Yaw,
+            &mut Pose,
+        ),
+    >,
     blocks: &Blocks,
     compose: &Compose,
     commands: &mut Commands<'_, '_>,
     proposed: Vec3,
     on_ground: bool,
 ) {
-    let (&size, mut tracking, mut pose, yaw) = match query.get_mut(client) {
+    let (&size, mut tracking, mut pose, yaw, mut entity_pose) = match query.get_mut(client) {
         Ok(data) => data,
         Err(e) => {
             error!("change_position_or_correct_client failed: query failed: {e}");
@@ -236,6 +261,11 @@ fn change_position_or_correct_client(
     }
 
     **pose = proposed;
+
+    // Gliding stops once the player lands
+    if on_ground && *entity_pose == Pose::FallFlying {
+        *entity_pose = Pose::Standing;
+    }
 }
 
 /// Returns true if the position was changed, false if it was not.
@@ -308,25 +338,13 @@ fn has_block_collision(position: &Vec3, size: EntitySize, blocks: &Blocks) -> bo
 
 fn hand_swing(
     mut packets: EventReader<'_, '_, play::HandSwing>,
-    mut query: Query<'_, '_, &mut ActiveAnimation>,
+    mut swing_writer: EventWriter<'_, event::SwingArm>,
 ) {
     for packet in packets.read() {
-        let mut animation = match query.get_mut(packet.sender()) {
-            Ok(animation) => animation,
-            Err(e) => {
-                error!("failed to handle hand swing: query failed: {e}");
-                continue;
-            }
-        };
-
-        match packet.hand {
-            Hand::Main => {
-                animation.push(animation::Kind::SwingMainArm);
-            }
-            Hand::Off => {
-                animation.push(animation::Kind::SwingOffHand);
-            }
-        }
+        swing_writer.write(event::SwingArm {
+            client: packet.sender(),
+            hand: packet.hand,
+        });
     }
 }
 
@@ -334,7 +352,8 @@ fn hand_swing(
 fn player_action(
     mut packets: EventReader<'_, '_, play::PlayerAction>,
     mut start_destroy_writer: EventWriter<'_, event::StartDestroyBlock>,
-    mut stop_destroy_writer: EventWriter<'_, event::DestroyBlock>,
+    mut abort_destroy_writer: EventWriter<'_, event::AbortDestroyBlock>,
+    mut stop_destroy_writer: EventWriter<'_, event::Cancellable<event::DestroyBlock>>,
     mut release_writer: EventWriter<'_, event::ReleaseUseItem>,
     mut commands: Commands<'_, '_>,
 ) {
@@ -351,6 +370,14 @@ fn player_action(
                 };
                 start_destroy_writer.write(event);
             }
+            PlayerAction::AbortDestroyBlock => {
+                let event = event::AbortDestroyBlock {
+                    position,
+                    from: packet.sender(),
+                    sequence,
+                };
+                abort_destroy_writer.write(event);
+            }
             PlayerAction::StopDestroyBlock => {
                 let event = event::DestroyBlock {
                     position,
@@ -358,7 +385,7 @@ fn player_action(
                     sequence,
                 };
 
-                stop_destroy_writer.write(event);
+                stop_destroy_writer.write(event::Cancellable::new(event));
             }
             PlayerAction::ReleaseUseItem => {
                 let event = event::ReleaseUseItem {
@@ -379,10 +406,13 @@ fn player_action(
 // for sneaking/crouching/etc
 fn client_command(
     mut packets: EventReader<'_, '_, play::ClientCommand>,
-    mut query: Query<'_, '_, (&mut Pose, &mut EntitySize, &mut MovementTracking)>,
+    mut query: Query<'_, '_, (&mut MovementTracking, &mut EntityFlags)>,
+    mut posture_writer: EventWriter<'_, event::PostureUpdate>,
 ) {
     for packet in packets.read() {
-        let (mut pose, mut size, mut tracking) = match query.get_mut(packet.sender()) {
+        let client = packet.sender();
+
+        let (mut tracking, mut flags) = match query.get_mut(client) {
             Ok(data) => data,
             Err(e) => {
                 error!("failed to handle client command: query failed: {e}");
@@ -390,26 +420,129 @@ fn client_command(
             }
         };
 
-        match packet.action {
-            ClientCommand::StartSneaking => {
-                *pose = Pose::Sneaking;
-                size.height = 1.5;
-            }
-            ClientCommand::StopSneaking | ClientCommand::LeaveBed => {
-                *pose = Pose::Standing;
-                size.height = 1.8;
-            }
-            ClientCommand::StartSprinting => {
-                tracking.sprinting = true;
-            }
-            ClientCommand::StopSprinting => {
-                tracking.sprinting = false;
+        let state = match packet.action {
+            ClientCommand::StartSneaking => event::Posture::Sneaking,
+            ClientCommand::StopSneaking | ClientCommand::LeaveBed => event::Posture::Standing,
+            ClientCommand::StartFlyingWithElytra => event::Posture::FallFlying,
+            ClientCommand::StartSprinting | ClientCommand::StopSprinting => {
+                let sprinting = matches!(packet.action, ClientCommand::StartSprinting);
+                tracking.sprinting = sprinting;
+
+                // Clients show sprint particles below other entities with this flag
+                let mut new_flags = *flags;
+                new_flags.set(EntityFlags::SPRINTING, sprinting);
+                flags.set_if_neq(new_flags);
+                continue;
             }
             ClientCommand::StartJumpWithHorse
             | ClientCommand::StopJumpWithHorse
-            | ClientCommand::OpenHorseInventory
-            | ClientCommand::StartFlyingWithElytra => {}
+            | ClientCommand::OpenHorseInventory => continue,
+        };
+
+        posture_writer.write(event::PostureUpdate { client, state });
+    }
+}
+
+/// Whether the block containing `position` is water
+fn is_water(blocks: &Blocks, position: Vec3) -> bool {
+    blocks
+        .get_block(position.floor().as_ivec3())
+        .is_some_and(|block| matches!(block.to_kind(), BlockKind::Water | BlockKind::BubbleColumn))
+}
+
+/// Updates the posture of players which depends on their movement rather than on a client command,
+/// like the vanilla server does. Players start swimming when they sprint with their head under
+/// water and keep swimming while they sprint in water, and they stop flying with an elytra once
+/// they land.
+fn movement_posture(
+    query: Query<'_, '_, (Entity, &Pose, &Position, &EntitySize, &MovementTracking)>,
+    blocks: Res<'_, Blocks>,
+    mut posture_writer: EventWriter<'_, event::PostureUpdate>,
+) {
+    for (client, &pose, position, size, tracking) in &query {
+        let state = match pose {
+            Pose::Swimming if !tracking.sprinting || !is_water(&blocks, **position) => {
+                event::Posture::Standing
+            }
+            Pose::Standing
+                if tracking.sprinting
+                    && is_water(&blocks, **position + Vec3::Y * (size.height * 0.85)) =>
+            {
+                event::Posture::Swimming
+            }
+            Pose::FallFlying if tracking.was_on_ground => event::Posture::Standing,
+            _ => continue,
+        };
+
+        posture_writer.write(event::PostureUpdate { client, state });
+    }
+}
+
+/// Applies [`event::PostureUpdate`]s to the [`Pose`] and [`EntityFlags`] of players, which are
+/// sent to other players so that they see the correct animation
+fn sync_posture(
+    mut events: EventReader<'_, '_, event::PostureUpdate>,
+    mut query: Query<'_, '_, (&mut Pose, &mut EntityFlags)>,
+) {
+    for event in events.read() {
+        let Ok((mut pose, mut flags)) = query.get_mut(event.client) else {
+            continue;
+        };
+
+        pose.set_if_neq(Pose::from(event.state));
+
+        let mut new_flags = *flags;
+        new_flags.set(
+            EntityFlags::CROUCHING,
+            event.state == event::Posture::Sneaking,
+        );
+        new_flags.set(
+            EntityFlags::SWIMMING,
+            event.state == event::Posture::Swimming,
+        );
+        new_flags.set(
+            EntityFlags::FLYING_WITH_ELYTRA,
+            event.state == event::Posture::FallFlying,
+        );
+        flags.set_if_neq(new_flags);
+    }
+}
+
+/// Updates the hitbox of players whose [`Pose`] changed. If the larger hitbox of the new pose
+/// would be inside a block, such as when standing up under a slab, the player is kept in a
+/// smaller pose which fits, like the vanilla client does.
+fn update_player_size(
+    mut query: Query<
+        '_,
+        '_,
+        (&mut Pose, &mut EntitySize, &Position),
+        (Changed<Pose>, With<ConnectionId>),
+    >,
+    blocks: Res<'_, Blocks>,
+) {
+    for (mut pose, mut size, position) in &mut query {
+        let requested = *pose;
+        let current = *size;
+
+        let fits = |pose: Pose| {
+            let size = EntitySize::player(pose);
+            size.height <= current.height || !has_block_collision(position, size, &blocks)
+        };
+
+        let Some(new_pose) = [requested, Pose::Sneaking, Pose::Swimming]
+            .into_iter()
+            .find(|&pose| fits(pose))
+        else {
+            // The player is already stuck in blocks, so there is nothing to correct
+            *size = EntitySize::player(requested);
+            continue;
+        };
+
+        if new_pose != requested {
+            *pose = new_pose;
         }
+
+        *size = EntitySize::player(new_pose);
     }
 }
 
@@ -452,12 +585,12 @@ fn player_interact_item(
                 sequence: packet.sequence.0,
             };
             if cursor.item == ItemKind::WrittenBook {
-                compose
-                    .unicast(
-                        &OpenWrittenBookS2c { hand: packet.hand },
-                        packet.connection_id(),
-                    )
-                    .unwrap();
+                if let Err(e) = compose.unicast(
+                    &OpenWrittenBookS2c { hand: packet.hand },
+                    packet.connection_id(),
+                ) {
+                    error!("failed to send written book: {e}");
+                }
             }
             item_interact_writer.write(event);
         }
@@ -479,8 +612,9 @@ fn player_interact_block(
         ),
     >,
     blocks: Res<'_, Blocks>,
-    mut toggle_door_writer: EventWriter<'_, event::ToggleDoor>,
-    mut place_block_writer: EventWriter<'_, event::PlaceBlock>,
+    mut toggle_door_writer: EventWriter<'_, event::Cancellable<event::ToggleDoor>>,
+    mut place_block_writer: EventWriter<'_, event::Cancellable<event::PlaceBlock>>,
+    mut sign_click_writer: EventWriter<'_, event::SignClickEvent>,
 ) {
     for packet in packets.read() {
         // PlayerInteractBlock contains:
@@ -513,16 +647,22 @@ fn player_interact_block(
             continue;
         };
 
-        if interacted_block.get(PropName::Open).is_some() {
+        if sign::is_sign(interacted_block) {
+            sign_click_writer.write(event::SignClickEvent {
+                client: packet.sender(),
+                position: interacted_block_pos_vec,
+                hand: packet.hand,
+            });
+        } else if interacted_block.get(PropName::Open).is_some() {
             // Toggle the open state of a door
             // todo: place block instead of toggling door if the player is crouching and holding a
             // block
 
-            toggle_door_writer.write(event::ToggleDoor {
+            toggle_door_writer.write(event::Cancellable::new(event::ToggleDoor {
                 position: interacted_block_pos_vec,
                 from: packet.sender(),
                 sequence: packet.sequence.0,
-            });
+            }));
         } else {
             // Attempt to place a block
 
@@ -560,12 +700,12 @@ fn player_interact_block(
                 continue;
             }
 
-            place_block_writer.write(event::PlaceBlock {
+            place_block_writer.write(event::Cancellable::new(event::PlaceBlock {
                 position,
                 from: packet.sender(),
                 sequence: packet.sequence.0,
                 block: block_state,
-            });
+            }));
         }
     }
 }
@@ -616,6 +756,34 @@ fn player_abilities(
     }
 }
 
+fn client_settings(
+    mut packets: EventReader<'_, '_, play::ClientSettings>,
+    mut query: Query<'_, '_, (&mut ClientSettings, &mut DisplayedSkinParts, &mut MainHand)>,
+) {
+    for packet in packets.read() {
+        let (mut settings, mut skin_parts, mut main_hand) = match query.get_mut(packet.sender()) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("client settings failed: query failed: {e}");
+                continue;
+            }
+        };
+
+        *settings = ClientSettings {
+            locale: packet.locale.to_owned(),
+            view_distance: packet.view_distance,
+            displayed_skin_parts: u8::from(packet.displayed_skin_parts),
+            main_arm: packet.main_arm,
+        };
+
+        skin_parts.set_if_neq(DisplayedSkinParts::new(settings.displayed_skin_parts));
+        main_hand.set_if_neq(MainHand::new(match settings.main_arm {
+            MainArm::Left => 0,
+            MainArm::Right => 1,
+        }));
+    }
+}
+
 pub struct HandlersPlugin;
 
 impl Plugin for HandlersPlugin {
@@ -623,16 +791,25 @@ impl Plugin for HandlersPlugin {
         app.add_systems(
             FixedUpdate,
             (
-                position_and_look_updates,
-                hand_swing,
-                player_action,
-                client_command,
-                player_interact_item,
-                player_interact_block,
-                creative_inventory_action,
-                player_abilities,
+                timed(position_and_look_updates),
+                timed(hand_swing),
+                timed(player_action),
+                timed(client_command),
+                timed(movement_posture).after(position_and_look_updates),
+                timed(sync_posture)
+                    .after(client_command)
+                    .after(movement_posture),
+                timed(player_interact_item),
+                timed(player_interact_block),
+                timed(creative_inventory_action),
+                timed(player_abilities),
+                timed(client_settings),
+                timed(update_player_size)
+                    .after(position_and_look_updates)
+                    .after(sync_posture),
             )
-                .after(ingress::decode::play),
+                .after(ingress::decode::play)
+                .before(event::CancelEvents),
         );
     }
 }
@@ -22,6 +22,7 @@ use crate::{
     ingress,
     net::{Compose, ConnectionId, DataBundle},
     simulation::{packet, packet_state},
+    timings::timed,
 };
 
 pub struct InventoryPlugin;
@@ -35,12 +36,12 @@ impl Plugin for InventoryPlugin {
             FixedUpdate,
             (
                 (
-                    handle_close_window,
-                    handle_update_selected_slot,
-                    handle_click_slot,
+                    timed(handle_close_window),
+                    timed(handle_update_selected_slot),
+                    timed(handle_click_slot),
                 )
                     .after(ingress::decode::play),
-                update_player_inventory
+                timed(update_player_inventory)
                     .after(handle_close_window)
                     .after(handle_update_selected_slot)
                     .after(handle_click_slot),
@@ -99,7 +100,9 @@ fn on_inventory_open(
         window_title: inventory.title().to_string().into_cow_text(),
     });
 
-    compose.unicast(packet, stream_id).unwrap();
+    if let Err(e) = compose.unicast(packet, stream_id) {
+        error!("failed to send open inventory: {e}");
+    }
 
     let packet = &(play::InventoryS2c {
         window_id: inv_state.window_id(),
@@ -114,7 +117,9 @@ fn on_inventory_open(
         carried_item: Cow::Borrowed(&cursor_item.0),
     });
 
-    compose.unicast(packet, stream_id).unwrap();
+    if let Err(e) = compose.unicast(packet, stream_id) {
+        error!("failed to send inventory contents: {e}");
+    }
 }
 
 fn on_inventory_close(
@@ -136,7 +141,9 @@ fn on_inventory_close(
         window_id: inv_state.window_id(),
     });
 
-    compose.unicast(packet, stream_id).unwrap();
+    if let Err(e) = compose.unicast(packet, stream_id) {
+        error!("failed to send close inventory: {e}");
+    }
 }
 
 fn update_player_inventory(
@@ -219,11 +226,13 @@ fn update_player_inventory(
                 equipment: equipment_changes,
             });
 
-            compose
+            if let Err(e) = compose
                 .broadcast_channel(packet, entity.into())
                 .exclude(stream_id)
                 .send()
-                .unwrap();
+            {
+                error!("failed to send equipment: {e}");
+            }
         }
 
         if let Some(mut open_inv) = open_inv {
@@ -276,7 +285,9 @@ fn update_player_inventory_inner<'a>(
     }
 
     if changed_slots {
-        bundle.unicast(stream_id).unwrap();
+        if let Err(e) = bundle.unicast(stream_id) {
+            error!("failed to send inventory contents: {e}");
+        }
 
         let packet = &(play::ScreenHandlerSlotUpdateS2c {
             window_id: -1,
@@ -285,7 +296,9 @@ fn update_player_inventory_inner<'a>(
             slot_data: Cow::Borrowed(&cursor_item.0),
         });
 
-        compose.unicast(packet, stream_id).unwrap();
+        if let Err(e) = compose.unicast(packet, stream_id) {
+            error!("failed to send inventory slot: {e}");
+        }
     }
 }
 
@@ -1242,7 +1255,9 @@ fn resync_inventory(
         carried_item: Cow::Borrowed(&cursor_item.0),
     });
 
-    compose.unicast(packet, stream_id).unwrap();
+    if let Err(e) = compose.unicast(packet, stream_id) {
+        error!("failed to send inventory contents: {e}");
+    }
 
     let packet = &(play::ScreenHandlerSlotUpdateS2c {
         window_id: -1,
@@ -1251,5 +1266,7 @@ fn resync_inventory(
         slot_data: Cow::Borrowed(&cursor_item.0),
     });
 
-    compose.unicast(packet, stream_id).unwrap();
+    if let Err(e) = compose.unicast(packet, stream_id) {
+        error!("failed to send cursor item: {e}");
+    }
 }
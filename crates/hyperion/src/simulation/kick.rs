@@ -0,0 +1,124 @@
+//! Disconnecting players with a reason.
+//!
+//! ```ignore
+//! commands.entity(player).kick("§cYou have been banned");
+//! ```
+//!
+//! The player entity is despawned once the proxy reports that the connection has closed, which
+//! happens after the disconnect packet is sent.
+
+use std::borrow::Cow;
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use tracing::error;
+use valence_protocol::packets::{login, play};
+use valence_text::{IntoText, Text};
+
+use crate::{
+    net::{Compose, ConnectionId, PacketDecoder, Priority},
+    simulation::packet_state,
+};
+
+/// Disconnects the target player, showing `reason` on their disconnect screen. Players in the
+/// handshake or status state are disconnected without a reason.
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct Kick {
+    pub reason: Text,
+}
+
+impl Kick {
+    #[must_use]
+    pub fn new(reason: impl IntoText<'static>) -> Self {
+        Self {
+            reason: reason.into_text(),
+        }
+    }
+}
+
+/// Marks players who have been kicked and are waiting for the proxy to close their connection.
+/// Players are only kicked once, so later [`Kick`] events for these players are ignored, and no
+/// more packets are decoded from them.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct Kicked {
+    /// Why the player was kicked
+    pub reason: Text,
+}
+
+pub trait KickExt {
+    /// Disconnects this player with a reason by triggering [`Kick`]
+    fn kick(&mut self, reason: impl IntoText<'static>) -> &mut Self;
+}
+
+impl KickExt for EntityCommands<'_> {
+    fn kick(&mut self, reason: impl IntoText<'static>) -> &mut Self {
+        self.trigger(Kick::new(reason))
+    }
+}
+
+fn kick_player(
+    trigger: Trigger<'_, Kick>,
+    query: Query<
+        '_,
+        '_,
+        (
+            &ConnectionId,
+            &PacketDecoder,
+            Has<packet_state::Login>,
+            Has<packet_state::Play>,
+            Has<Kicked>,
+        ),
+    >,
+    compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
+) {
+    let player = trigger.target();
+
+    let (&connection_id, decoder, login, play, kicked) = match query.get(player) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("failed to kick player: query failed: {e}");
+            return;
+        }
+    };
+
+    if kicked {
+        return;
+    }
+
+    let reason = Cow::Borrowed(&trigger.reason);
+
+    let result = if login {
+        let pkt = login::LoginDisconnectS2c { reason };
+        // Compression is enabled part way through the login state
+        if decoder.compression().0 >= 0 {
+            compose.unicast_with_priority(&pkt, connection_id, Priority::Critical)
+        } else {
+            compose.unicast_no_compression(&pkt, connection_id)
+        }
+    } else if play {
+        compose.unicast_with_priority(
+            &play::DisconnectS2c { reason },
+            connection_id,
+            Priority::Critical,
+        )
+    } else {
+        Ok(())
+    };
+
+    if let Err(e) = result {
+        error!("failed to send disconnect packet: {e}");
+    }
+
+    compose.io_buf().shutdown(connection_id);
+    commands.entity(player).insert(Kicked {
+        reason: trigger.reason.clone(),
+    });
+}
+
+pub struct KickPlugin;
+
+impl Plugin for KickPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(kick_player);
+    }
+}
@@ -0,0 +1,69 @@
+// Index	Type	Meaning	Default
+// 8	Byte (0)	Bit mask	0
+// Bit mask	Meaning
+// 0x01	Is critical
+// 0x02	Is noclip (used by loyalty tridents when returning)
+// 9	Byte (0)	Piercing level	0
+//
+// Arrow
+// 10	VarInt (1)	Color (-1 for no particles)	-1
+
+use bevy::prelude::*;
+
+use super::Metadata;
+use crate::define_and_register_components;
+
+define_and_register_components! {
+    8, ArrowFlags -> u8,
+    9, PiercingLevel -> u8,
+}
+
+impl ArrowFlags {
+    pub const CRITICAL: u8 = 0x01;
+    pub const NO_CLIP: u8 = 0x02;
+
+    #[must_use]
+    pub const fn is_critical(&self) -> bool {
+        self.value & Self::CRITICAL != 0
+    }
+
+    /// Whether the arrow shows critical particles while flying
+    pub const fn set_critical(&mut self, critical: bool) {
+        if critical {
+            self.value |= Self::CRITICAL;
+        } else {
+            self.value &= !Self::CRITICAL;
+        }
+    }
+}
+
+impl Default for ArrowFlags {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Default for PiercingLevel {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// Metadata of arrows, which are not shared with spectral arrows and tridents
+pub mod tipped {
+    use bevy::prelude::*;
+    use valence_protocol::VarInt;
+
+    use crate::{define_and_register_components, simulation::metadata::Metadata};
+
+    define_and_register_components! {
+        // The color of the potion particles, or -1 for no particles
+        10, ArrowColor -> VarInt,
+    }
+
+    impl Default for ArrowColor {
+        fn default() -> Self {
+            Self::new(VarInt(-1))
+        }
+    }
+}
@@ -17,7 +17,10 @@ use bevy::prelude::*;
 use valence_protocol::{Encode, VarInt};
 use valence_text::Text;
 
-use crate::{define_and_register_components, simulation::Metadata};
+use crate::{
+    define_and_register_components,
+    simulation::{Metadata, event::Posture},
+};
 
 mod flags;
 pub use flags::EntityFlags;
@@ -90,6 +93,28 @@ pub enum Pose {
     Digging,
 }
 
+impl From<Posture> for Pose {
+    fn from(posture: Posture) -> Self {
+        match posture {
+            Posture::Standing => Self::Standing,
+            Posture::FallFlying => Self::FallFlying,
+            Posture::Sleeping => Self::Sleeping,
+            Posture::Swimming => Self::Swimming,
+            Posture::SpinAttack => Self::SpinAttack,
+            Posture::Sneaking => Self::Sneaking,
+            Posture::LongJumping => Self::LongJumping,
+            Posture::Dying => Self::Dying,
+            Posture::Croaking => Self::Croaking,
+            Posture::UsingTongue => Self::UsingTongue,
+            Posture::Sitting => Self::Sitting,
+            Posture::Roaring => Self::Roaring,
+            Posture::Sniffing => Self::Sniffing,
+            Posture::Emerging => Self::Emerging,
+            Posture::Digging => Self::Digging,
+        }
+    }
+}
+
 impl Metadata for Pose {
     type Type = Self;
 
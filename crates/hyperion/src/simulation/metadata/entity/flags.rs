@@ -28,6 +28,21 @@ impl EntityFlags {
     const fn new() -> Self {
         Self { value: 0 }
     }
+
+    /// Whether every flag in `flags` is set
+    #[must_use]
+    pub const fn contains(self, flags: Self) -> bool {
+        self.value & flags.value == flags.value
+    }
+
+    /// Sets every flag in `flags` if `enabled` is true, and clears them otherwise
+    pub const fn set(&mut self, flags: Self, enabled: bool) {
+        if enabled {
+            self.value |= flags.value;
+        } else {
+            self.value &= !flags.value;
+        }
+    }
 }
 
 impl std::ops::BitOrAssign for EntityFlags {
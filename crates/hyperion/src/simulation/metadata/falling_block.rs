@@ -0,0 +1,19 @@
+// Index	Type	Meaning	Default
+// 8	Position (10)	Spawn position	(0, 0, 0)
+
+use bevy::prelude::*;
+use valence_protocol::BlockPos;
+
+use super::Metadata;
+use crate::define_and_register_components;
+
+define_and_register_components! {
+    // The position the block fell from, which the client uses to choose its texture variant
+    8, SpawnPosition -> BlockPos,
+}
+
+impl Default for SpawnPosition {
+    fn default() -> Self {
+        Self::new(BlockPos::new(0, 0, 0))
+    }
+}
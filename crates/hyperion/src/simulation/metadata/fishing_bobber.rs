@@ -0,0 +1,16 @@
+use bevy::prelude::*;
+use valence_protocol::VarInt;
+
+use super::Metadata;
+use crate::define_and_register_components;
+
+define_and_register_components! {
+    // The hooked entity id plus one, or 0 if no entity is hooked
+    8, HookedEntity -> VarInt,
+}
+
+impl Default for HookedEntity {
+    fn default() -> Self {
+        Self::new(VarInt(0))
+    }
+}
@@ -0,0 +1,48 @@
+// Mob
+// Index	Type	Meaning	Default
+// 15	Byte (0)	Bit mask	0
+// Bit mask	Meaning
+// 0x01	No AI
+// 0x02	Is left handed
+// 0x04	Is aggressive
+//
+// Ageable mob
+// 16	Boolean (8)	Is baby	false
+
+use bevy::prelude::*;
+
+use super::Metadata;
+use crate::define_and_register_components;
+
+define_and_register_components! {
+    15, MobFlags -> u8,
+}
+
+impl MobFlags {
+    pub const AGGRESSIVE: u8 = 0x04;
+    pub const LEFT_HANDED: u8 = 0x02;
+    pub const NO_AI: u8 = 0x01;
+}
+
+impl Default for MobFlags {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// Metadata of mobs which can be babies, such as animals
+pub mod ageable {
+    use bevy::prelude::*;
+
+    use crate::{define_and_register_components, simulation::metadata::Metadata};
+
+    define_and_register_components! {
+        16, IsBaby -> bool,
+    }
+
+    impl Default for IsBaby {
+        fn default() -> Self {
+            Self::new(false)
+        }
+    }
+}
@@ -1,38 +1,76 @@
 use std::fmt::Debug;
 
 use bevy::prelude::*;
-use hyperion_utils::{Prev, track_prev};
+use hyperion_utils::track_prev;
 use tracing::error;
 use valence_protocol::{Encode, VarInt};
 
 use crate::simulation::metadata::entity::{EntityFlags, Pose};
 
+pub mod arrow;
 pub mod block_display;
+pub mod creeper;
 pub mod display;
 pub mod entity;
+pub mod falling_block;
+pub mod fishing_bobber;
 pub mod item;
 pub mod living_entity;
+pub mod mob;
 pub mod player;
+pub mod sheep;
+pub mod slime;
+pub mod wolf;
+
+/// Encodes metadata components into [`MetadataChanges`] in `FixedPostUpdate`. Systems which send
+/// the changes run after this set.
+#[derive(SystemSet, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EncodeMetadata;
+
+/// The value of a metadata component which was last encoded, or which the entity was spawned with
+#[derive(Component)]
+struct Encoded<T>(T);
+
+fn initialize_encoded<T: Component + Clone>(
+    trigger: Trigger<'_, OnAdd, T>,
+    query: Query<'_, '_, &T>,
+    mut commands: Commands<'_, '_>,
+) {
+    let Ok(value) = query.get(trigger.target()) else {
+        return;
+    };
+
+    commands
+        .entity(trigger.target())
+        .insert(Encoded(value.clone()));
+}
+
+/// Encodes the components which changed since this system last ran. Comparing against the value
+/// which was last encoded means that no change is missed, no matter when in the tick it happened.
+fn encode_changes<T>(
+    mut query: Query<'_, '_, (&mut Encoded<T>, &T, &mut MetadataChanges), Changed<T>>,
+) where
+    T: Component + Clone + PartialEq + Metadata,
+{
+    for (mut encoded, current, mut metadata_changes) in &mut query {
+        if encoded.0 != *current {
+            metadata_changes.encode(current.clone());
+            encoded.0 = current.clone();
+        }
+    }
+}
 
 /// Set up a system to track metadata changes
 fn component_and_track<T>(app: &mut App)
 where
     T: Component + Clone + PartialEq + Metadata + Default + Debug,
 {
+    // Prev is not used for metadata anymore, but game modes use it to compare against the
+    // previous tick
     track_prev::<T>(app);
 
-    // TODO: This will silently ignore changes to the metadata between this system's execution and
-    // the time that Prev is updated. There should be a warning for this.
-    app.add_systems(
-        FixedPostUpdate,
-        |mut query: Query<'_, '_, (&Prev<T>, &T, &mut MetadataChanges)>| {
-            for (prev, current, mut metadata_changes) in &mut query {
-                if **prev != *current {
-                    metadata_changes.encode(current.clone());
-                }
-            }
-        },
-    );
+    app.add_observer(initialize_encoded::<T>);
+    app.add_systems(FixedPostUpdate, encode_changes::<T>.in_set(EncodeMetadata));
 }
 
 fn initialize_entity(
@@ -73,6 +111,51 @@ fn initialize_entity(
         EntityKind::Item => {
             entity.insert(item::default_components());
         }
+        EntityKind::FishingBobber => {
+            entity.insert(fishing_bobber::default_components());
+        }
+        EntityKind::Arrow => {
+            entity.insert((
+                arrow::default_components(),
+                arrow::tipped::default_components(),
+            ));
+        }
+        EntityKind::SpectralArrow | EntityKind::Trident => {
+            entity.insert(arrow::default_components());
+        }
+        EntityKind::FallingBlock => {
+            entity.insert(falling_block::default_components());
+        }
+        EntityKind::Slime | EntityKind::MagmaCube => {
+            entity.insert((
+                living_entity::default_components(),
+                mob::default_components(),
+                slime::default_components(),
+            ));
+        }
+        EntityKind::Creeper => {
+            entity.insert((
+                living_entity::default_components(),
+                mob::default_components(),
+                creeper::default_components(),
+            ));
+        }
+        EntityKind::Sheep => {
+            entity.insert((
+                living_entity::default_components(),
+                mob::default_components(),
+                mob::ageable::default_components(),
+                sheep::default_components(),
+            ));
+        }
+        EntityKind::Wolf => {
+            entity.insert((
+                living_entity::default_components(),
+                mob::default_components(),
+                mob::ageable::default_components(),
+                wolf::default_components(),
+            ));
+        }
         _ => {}
     }
 }
@@ -89,8 +172,18 @@ impl Plugin for MetadataPlugin {
         display::register(app);
         block_display::register(app);
         item::register(app);
+        fishing_bobber::register(app);
         living_entity::register(app);
         player::register(app);
+        arrow::register(app);
+        arrow::tipped::register(app);
+        falling_block::register(app);
+        mob::register(app);
+        mob::ageable::register(app);
+        slime::register(app);
+        creeper::register(app);
+        sheep::register(app);
+        wolf::register(app);
     }
 }
 
@@ -124,6 +217,7 @@ macro_rules! define_metadata_component {
             derive_more::Deref,
             derive_more::DerefMut,
             derive_more::Constructor,
+            derive_more::From,
             Debug
         )]
         #[allow(clippy::derive_partial_eq_without_eq)]
@@ -242,6 +336,29 @@ impl MetadataChanges {
             EntityKind::Item => {
                 item::encode_non_default_components(entity, self);
             }
+            EntityKind::FishingBobber => {
+                fishing_bobber::encode_non_default_components(entity, self);
+            }
+            EntityKind::Arrow | EntityKind::SpectralArrow | EntityKind::Trident => {
+                arrow::encode_non_default_components(entity, self);
+                arrow::tipped::encode_non_default_components(entity, self);
+            }
+            EntityKind::FallingBlock => {
+                falling_block::encode_non_default_components(entity, self);
+            }
+            EntityKind::Slime
+            | EntityKind::MagmaCube
+            | EntityKind::Creeper
+            | EntityKind::Sheep
+            | EntityKind::Wolf => {
+                living_entity::encode_non_default_components(entity, self);
+                mob::encode_non_default_components(entity, self);
+                mob::ageable::encode_non_default_components(entity, self);
+                slime::encode_non_default_components(entity, self);
+                creeper::encode_non_default_components(entity, self);
+                sheep::encode_non_default_components(entity, self);
+                wolf::encode_non_default_components(entity, self);
+            }
             _ => {}
         }
     }
@@ -0,0 +1,48 @@
+// Index	Type	Meaning	Default
+// 17	Byte (0)	Bit mask	0
+// Bit mask	Meaning
+// 0x0F	Color ID
+// 0x10	Is sheared
+
+use bevy::prelude::*;
+
+use super::Metadata;
+use crate::define_and_register_components;
+
+define_and_register_components! {
+    17, SheepFlags -> u8,
+}
+
+impl SheepFlags {
+    const COLOR: u8 = 0x0F;
+    const SHEARED: u8 = 0x10;
+
+    /// The dye color of the wool, from 0 (white) to 15 (black)
+    #[must_use]
+    pub const fn color(&self) -> u8 {
+        self.value & Self::COLOR
+    }
+
+    pub const fn set_color(&mut self, color: u8) {
+        self.value = (self.value & !Self::COLOR) | (color & Self::COLOR);
+    }
+
+    #[must_use]
+    pub const fn is_sheared(&self) -> bool {
+        self.value & Self::SHEARED != 0
+    }
+
+    pub const fn set_sheared(&mut self, sheared: bool) {
+        if sheared {
+            self.value |= Self::SHEARED;
+        } else {
+            self.value &= !Self::SHEARED;
+        }
+    }
+}
+
+impl Default for SheepFlags {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
@@ -0,0 +1,19 @@
+// Index	Type	Meaning	Default
+// 16	VarInt (1)	Size	1
+
+use bevy::prelude::*;
+use valence_protocol::VarInt;
+
+use super::Metadata;
+use crate::define_and_register_components;
+
+define_and_register_components! {
+    // The size of slimes and magma cubes, which also sets the size of their hitbox
+    16, SlimeSize -> VarInt,
+}
+
+impl Default for SlimeSize {
+    fn default() -> Self {
+        Self::new(VarInt(1))
+    }
+}
@@ -32,7 +32,7 @@
 //! | 29 | Quaternion | (Float, Float, Float, Float) | x, y, z, w |
 
 use valence_generated::block::BlockState;
-use valence_protocol::{ItemStack, VarInt};
+use valence_protocol::{BlockPos, ItemStack, VarInt};
 use valence_text::Text;
 
 use crate::simulation::metadata::entity::Pose;
@@ -60,6 +60,8 @@ impl_metadata_type! {
     6 => Option<Text>,
     7 => ItemStack,
     8 => bool,
+    10 => BlockPos,
+    13 => Option<uuid::Uuid>,
     14 => BlockState,
     20 => Pose,
     26 => glam::Vec3,
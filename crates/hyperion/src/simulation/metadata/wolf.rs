@@ -0,0 +1,62 @@
+// Tameable animal
+// Index	Type	Meaning	Default
+// 17	Byte (0)	Bit mask	0
+// Bit mask	Meaning
+// 0x01	Is sitting
+// 0x04	Is tamed
+// 18	Optional UUID (13)	Owner	Absent
+//
+// Wolf
+// 19	Boolean (8)	Is begging	false
+// 20	VarInt (1)	Collar color (dye color)	14 (red)
+// 21	VarInt (1)	Anger time	0
+
+use bevy::prelude::*;
+use uuid::Uuid;
+use valence_protocol::VarInt;
+
+use super::Metadata;
+use crate::define_and_register_components;
+
+define_and_register_components! {
+    17, TameFlags -> u8,
+    18, TameOwner -> Option<Uuid>,
+    19, Begging -> bool,
+    20, CollarColor -> VarInt,
+    21, AngerTime -> VarInt,
+}
+
+impl TameFlags {
+    pub const SITTING: u8 = 0x01;
+    pub const TAMED: u8 = 0x04;
+}
+
+impl Default for TameFlags {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Default for TameOwner {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl Default for Begging {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl Default for CollarColor {
+    fn default() -> Self {
+        Self::new(VarInt(14))
+    }
+}
+
+impl Default for AngerTime {
+    fn default() -> Self {
+        Self::new(VarInt(0))
+    }
+}
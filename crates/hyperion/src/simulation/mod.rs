@@ -1,13 +1,14 @@
 use std::{
     collections::{HashMap, hash_map::Entry},
     hash::Hash,
+    sync::Arc,
 };
 
 use bevy::prelude::*;
 use bytemuck::{Pod, Zeroable};
 use derive_more::{Add, Constructor, Deref, DerefMut, Display, From, Sub};
 use geometry::aabb::Aabb;
-use glam::{DVec3, I16Vec2, IVec3, Vec3};
+use glam::{DVec3, I16Vec2, I64Vec3, IVec2, IVec3, Vec3};
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 use tracing::{error, info};
@@ -15,37 +16,62 @@ use valence_protocol::{
     VarInt,
     packets::play::{
         self,
+        client_settings_c2s::MainArm,
         player_abilities_s2c::{PlayerAbilitiesFlags, PlayerAbilitiesS2c},
         player_position_look_s2c::PlayerPositionLookFlags,
     },
 };
-use valence_text::IntoText;
 
 use crate::{
     Global,
-    net::{Compose, ConnectionId},
+    config::{Radius, Spawn},
+    net::{Compose, ConnectionId, Priority, bandwidth::ByteCounters},
     simulation::{
+        afk::AfkPlugin,
+        blocks::breaking::BlockBreakingPlugin,
+        combat::CombatPlugin,
         command::CommandPlugin,
         entity_kind::EntityKind,
+        fire::FirePlugin,
         handlers::HandlersPlugin,
         inventory::InventoryPlugin,
-        metadata::{Metadata, MetadataPlugin},
+        kick::{KickExt, KickPlugin},
+        metadata::{Metadata, MetadataPlugin, entity::Pose},
         packet::PacketPlugin,
+        persistent::PersistentPlugin,
+        sign::SignPlugin,
+        statistics::StatisticsPlugin,
+        team::TeamPlugin,
+        transfer::TransferPlugin,
     },
 };
 
+pub mod afk;
 pub mod animation;
 pub mod blocks;
+pub mod combat;
 pub mod command;
 pub mod entity_kind;
 pub mod event;
+pub mod fire;
 pub mod handlers;
 pub mod inventory;
+pub mod kick;
 pub mod metadata;
 pub mod packet;
 pub mod packet_state;
+pub mod persistent;
+pub mod physics;
+pub mod player_list;
+pub mod registry;
+pub mod sign;
 pub mod skin;
+pub mod statistics;
+pub mod team;
+pub mod totem;
+pub mod transfer;
 pub mod util;
+pub mod world_event;
 
 #[derive(Resource, Default, Debug, Deref, DerefMut)]
 pub struct StreamLookup {
@@ -60,9 +86,22 @@ pub struct PlayerUuidLookup {
 }
 
 /// Communicates with the proxy server.
-#[derive(Clone, Deref, DerefMut, From)]
+#[derive(Clone, Deref, DerefMut)]
 pub struct EgressComm {
+    #[deref]
+    #[deref_mut]
     pub(crate) tx: tokio::sync::mpsc::UnboundedSender<bytes::Bytes>,
+    /// The bytes sent to and received from the proxy
+    pub(crate) bandwidth: Arc<ByteCounters>,
+}
+
+impl EgressComm {
+    pub(crate) const fn new(
+        tx: tokio::sync::mpsc::UnboundedSender<bytes::Bytes>,
+        bandwidth: Arc<ByteCounters>,
+    ) -> Self {
+        Self { tx, bandwidth }
+    }
 }
 
 #[derive(Resource, Deref, DerefMut, From, Debug, Default)]
@@ -273,6 +312,10 @@ impl Uuid {
 #[derive(Component, Debug)]
 pub struct Npc;
 
+/// Text shown before the name of a player in chat, such as a rank or a cosmetic prefix
+#[derive(Component, Clone, Debug, PartialEq, Eq, Deref, DerefMut)]
+pub struct ChatPrefix(pub String);
+
 /// The running multiplier of the entity. This defaults to 1.0.
 #[derive(Component, Debug, Copy, Clone)]
 pub struct RunningSpeed(pub f32);
@@ -400,6 +443,25 @@ impl Default for EntitySize {
     }
 }
 
+impl EntitySize {
+    /// The hitbox of a player in a pose, which is smaller while sneaking, swimming, gliding or
+    /// sleeping
+    #[must_use]
+    pub const fn player(pose: Pose) -> Self {
+        let (width, height) = match pose {
+            Pose::Sneaking => (PLAYER_WIDTH, 1.5),
+            Pose::FallFlying | Pose::Swimming | Pose::SpinAttack => (PLAYER_WIDTH, 0.6),
+            Pose::Sleeping | Pose::Dying => (0.2, 0.2),
+            _ => (PLAYER_WIDTH, PLAYER_HEIGHT),
+        };
+
+        Self {
+            half_width: width / 2.0,
+            height,
+        }
+    }
+}
+
 impl Position {
     #[must_use]
     pub fn sound_position(&self) -> IVec3 {
@@ -445,8 +507,50 @@ pub fn block_bounds(position: Vec3, size: EntitySize) -> (IVec3, IVec3) {
     (min, max)
 }
 
-/// The initial player spawn position. todo: this should not be a constant
-pub const PLAYER_SPAWN_POSITION: Vec3 = Vec3::new(-8_526_209_f32, 100f32, -6_028_464f32);
+/// Where players spawn, which is loaded from [`Config::spawn`](crate::config::Config::spawn) and
+/// can be changed at runtime
+#[derive(Resource, Debug, Copy, Clone, PartialEq)]
+pub struct WorldSpawn {
+    pub position: Vec3,
+    pub yaw: f32,
+    /// Players spawn at a random position within this many blocks of `position`
+    pub radius: i32,
+    pub kind: Radius,
+}
+
+impl WorldSpawn {
+    /// Returns a random horizontal offset within the spawn radius
+    #[must_use]
+    pub fn random_offset(&self) -> IVec2 {
+        let radius = self.radius.max(0);
+
+        loop {
+            let x = fastrand::i32(-radius..=radius);
+            let z = fastrand::i32(-radius..=radius);
+
+            match self.kind {
+                Radius::Chebyshev => return IVec2::new(x, z),
+                Radius::Euclidean => {
+                    let offset = IVec2::new(x, z).as_i64vec2();
+                    if offset.length_squared() <= i64::from(radius).pow(2) {
+                        return IVec2::new(x, z);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl From<&Spawn> for WorldSpawn {
+    fn from(spawn: &Spawn) -> Self {
+        Self {
+            position: IVec3::new(spawn.x, spawn.y, spawn.z).as_vec3() + Vec3::new(0.5, 0.0, 0.5),
+            yaw: spawn.yaw,
+            radius: spawn.radius,
+            kind: spawn.kind,
+        }
+    }
+}
 
 impl Position {
     /// Get the chunk position of the center of the player's bounding box.
@@ -498,12 +602,25 @@ pub struct PendingTeleportation {
 }
 
 impl PendingTeleportation {
+    /// The number of ticks the client has to confirm a teleport before it is sent again
+    pub const TTL: u8 = 20;
+
     #[must_use]
     pub fn new(destination: Vec3) -> Self {
         Self {
             teleport_id: fastrand::i32(..),
             destination,
-            ttl: 20,
+            ttl: Self::TTL,
+        }
+    }
+
+    /// The same teleport, which is sent again because the client did not confirm it in time. It
+    /// keeps its teleport id, so a late confirmation of the first packet still completes it.
+    #[must_use]
+    pub const fn resend(self) -> Self {
+        Self {
+            ttl: Self::TTL,
+            ..self
         }
     }
 }
@@ -535,6 +652,58 @@ pub struct MovementTracking {
     pub server_velocity: DVec3,
     pub sprinting: bool,
     pub was_on_ground: bool,
+
+    /// The position last sent to other players, encoded with [`encode_position`]. Relative move
+    /// packets are computed from this rather than from the previous position so that rounding
+    /// errors do not accumulate on the client.
+    pub sent_position: I64Vec3,
+    /// The yaw last sent to other players
+    pub sent_yaw: f32,
+    /// The pitch last sent to other players
+    pub sent_pitch: f32,
+    /// Ticks since movement below the thresholds in
+    /// [`MovementSyncConfig`](crate::config::MovementSyncConfig) was last sent
+    pub ticks_since_sync: u32,
+}
+
+/// The movement last sent to players for an entity which is not a player. Like
+/// [`MovementTracking::sent_position`], relative moves are computed from this so that rounding
+/// errors do not accumulate on the client.
+#[derive(Component, Default, Debug, Copy, Clone, PartialEq)]
+pub struct SentMovement {
+    /// The position last sent, encoded with [`encode_position`]
+    pub position: I64Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Ticks since movement below the thresholds in
+    /// [`MovementSyncConfig`](crate::config::MovementSyncConfig) was last sent
+    pub ticks_since_sync: u32,
+}
+
+impl SentMovement {
+    #[must_use]
+    pub fn new(position: Vec3, yaw: f32, pitch: f32) -> Self {
+        Self {
+            position: encode_position(position),
+            yaw,
+            pitch,
+            ticks_since_sync: 0,
+        }
+    }
+}
+
+/// Encodes a position in the units of 1/4096 of a block used by relative move packets
+#[must_use]
+pub fn encode_position(position: Vec3) -> I64Vec3 {
+    (position.as_dvec3() * 4096.0).round().as_i64vec3()
+}
+
+impl MovementTracking {
+    /// The distance the entity has fallen since it was last on the ground or moving upwards
+    #[must_use]
+    pub fn fall_distance(&self, position: &Position) -> f32 {
+        (self.fall_start_y - position.y).max(0.0)
+    }
 }
 
 #[derive(Component, Default, Debug, Copy, Clone)]
@@ -543,15 +712,40 @@ pub struct Flight {
     pub is_flying: bool,
 }
 
+/// The settings a player sent in `ClientSettingsC2s`
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct ClientSettings {
+    /// The language of the client, such as `en_us`
+    pub locale: String,
+    /// The render distance of the client in chunks. Chunks are sent up to the smaller of this and
+    /// the view distance of the server.
+    pub view_distance: u8,
+    /// The bit mask of the skin layers which are shown, as in
+    /// [`metadata::player::DisplayedSkinParts`]
+    pub displayed_skin_parts: u8,
+    pub main_arm: MainArm,
+}
+
+impl Default for ClientSettings {
+    /// The settings used until the client sends its own, which do not limit the view distance
+    fn default() -> Self {
+        Self {
+            locale: String::from("en_us"),
+            view_distance: 32,
+            displayed_skin_parts: 0x7f,
+            main_arm: MainArm::Right,
+        }
+    }
+}
+
 fn initialize_player(
     trigger: Trigger<'_, OnAdd, packet_state::Play>,
     mut ign_map: ResMut<'_, IgnMap>,
-    compose: Res<'_, Compose>,
     name_query: Query<'_, '_, &Name>,
-    connection_id_query: Query<'_, '_, &ConnectionId>,
     mut commands: Commands<'_, '_>,
 ) {
     commands.entity(trigger.target()).insert((
+        ClientSettings::default(),
         ConfirmBlockSequences::default(),
         EntitySize::default(),
         Flight::default(),
@@ -564,28 +758,21 @@ fn initialize_player(
         return;
     };
 
-    if let Some(other) = ign_map.insert(name.to_string(), trigger.target()) {
-        // Another player with the same username is already connected to the server.
-        // Disconnect the previous player with the same username.
+    // The username of a player takes precedence over the nickname of another player
+    if let Some(other) = ign_map.insert(name.to_string(), trigger.target())
+        && name_query
+            .get(other)
+            .is_ok_and(|other_name| other_name == name)
+    {
+        // Another player with the same username is already connected to the server. The other
+        // `DuplicateLogin` policies are applied during login, so disconnect the previous player
+        // with the same username.
         // There are some Minecraft accounts with the same username, but this is an extremely
         // rare edge case which is not worth handling.
-
-        let Ok(&other_connection_id) = connection_id_query.get(other) else {
-            error!(
-                "failed to kick player with same username: other player is missing ConnectionId \
-                 component"
-            );
-            return;
-        };
-
-        let pkt = play::DisconnectS2c {
-            reason: "A different player with the same username as your account has joined on a \
-                     different device"
-                .into_cow_text(),
-        };
-
-        compose.unicast(&pkt, other_connection_id).unwrap();
-        compose.io_buf().shutdown(other_connection_id);
+        commands.entity(other).kick(
+            "A different player with the same username as your account has joined on a different \
+             device",
+        );
     }
 }
 
@@ -658,7 +845,9 @@ fn send_pending_teleportation(
         teleport_id: VarInt(pending_teleportation.teleport_id),
     };
 
-    compose.unicast(&pkt, connection).unwrap();
+    if let Err(e) = compose.unicast_with_priority(&pkt, connection, Priority::Critical) {
+        error!("failed to send teleport: {e}");
+    }
 }
 
 fn update_flight(
@@ -678,7 +867,9 @@ fn update_flight(
         fov_modifier: 0.0,
     };
 
-    compose.unicast(&pkt, connection_id).unwrap();
+    if let Err(e) = compose.unicast(&pkt, connection_id) {
+        error!("failed to send abilities: {e}");
+    }
 }
 
 pub struct SimPlugin;
@@ -691,24 +882,40 @@ impl Plugin for SimPlugin {
         app.add_observer(update_flight);
         app.add_observer(initialize_uuid);
 
+        world_event::build(app);
+        physics::build(app);
+        player_list::build(app);
+
         app.add_plugins((
+            AfkPlugin,
+            BlockBreakingPlugin,
+            CombatPlugin,
             CommandPlugin,
+            FirePlugin,
             HandlersPlugin,
             PacketPlugin,
             InventoryPlugin,
+            KickPlugin,
             MetadataPlugin,
+            PersistentPlugin,
+            SignPlugin,
+            StatisticsPlugin,
+            TeamPlugin,
+            TransferPlugin,
         ));
 
         app.add_event::<RequestSubscribeChannelPackets>();
         app.add_event::<event::ItemDropEvent>();
         app.add_event::<event::ItemInteract>();
         app.add_event::<event::SetSkin>();
-        app.add_event::<event::AttackEntity>();
+        app.add_event::<event::Cancellable<event::AttackEntity>>();
         app.add_event::<event::StartDestroyBlock>();
-        app.add_event::<event::DestroyBlock>();
-        app.add_event::<event::PlaceBlock>();
-        app.add_event::<event::ToggleDoor>();
+        app.add_event::<event::AbortDestroyBlock>();
+        app.add_event::<event::Cancellable<event::DestroyBlock>>();
+        app.add_event::<event::Cancellable<event::PlaceBlock>>();
+        app.add_event::<event::Cancellable<event::ToggleDoor>>();
         app.add_event::<event::SwingArm>();
+        app.add_event::<event::Hurt>();
         app.add_event::<event::ReleaseUseItem>();
         app.add_event::<event::PostureUpdate>();
         app.add_event::<event::BlockInteract>();
@@ -718,7 +925,11 @@ impl Plugin for SimPlugin {
         app.add_event::<event::DropItemStackEvent>();
         app.add_event::<event::UpdateSelectedSlotEvent>();
         app.add_event::<event::HitGroundEvent>();
+        app.add_event::<event::PlayerDeath>();
+        app.add_event::<event::ChatSent>();
         app.add_event::<event::InteractEvent>();
+        app.add_event::<event::SignClickEvent>();
+        app.add_event::<event::SignEditEvent>();
     }
 }
 
@@ -0,0 +1,342 @@
+//! Entities which are kept across restarts.
+//!
+//! Entities with the [`Persistent`] component are saved to the [`EntityHandler`] and spawned
+//! again when the server starts. The kind, position, velocity and rotation of an entity are
+//! always saved. Other components, including metadata, are only saved if they are registered in
+//! [`PersistentComponents`].
+
+use std::collections::HashMap;
+
+use bevy::{ecs::world::EntityRef, prelude::*};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use tracing::{error, info};
+use valence_protocol::{Decode, Encode};
+
+use crate::{
+    net::{Channel, Compose},
+    simulation::{
+        Pitch, Position, Uuid, Velocity, Yaw,
+        entity_kind::EntityKind,
+        metadata::{
+            Metadata,
+            entity::{CustomName, CustomNameVisible, NoGravity, Silent},
+            item::Item,
+        },
+    },
+    storage::EntityHandler,
+    tick_rate::AdvanceTick,
+};
+
+/// How often persistent entities are saved in ticks
+pub const SAVE_INTERVAL: i64 = 20 * 60;
+
+/// Marks an entity to be kept across restarts. Removing this component or despawning the entity
+/// removes it from the database.
+#[derive(Component, Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Persistent;
+
+/// The saved state of a persistent entity
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PersistedEntity {
+    pub kind: EntityKind,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Registered components, keyed by the name they were registered with
+    pub components: HashMap<String, Value>,
+}
+
+type SaveFn = fn(EntityRef<'_>) -> Option<anyhow::Result<Value>>;
+type LoadFn = fn(&mut EntityWorldMut<'_>, Value) -> anyhow::Result<()>;
+
+struct PersistentComponent {
+    name: &'static str,
+    save: SaveFn,
+    load: LoadFn,
+}
+
+/// The components which are saved for persistent entities.
+///
+/// The name a component is registered with is stored in the database, so it must not change
+/// after entities have been saved.
+#[derive(Resource, Default)]
+pub struct PersistentComponents {
+    components: Vec<PersistentComponent>,
+}
+
+impl PersistentComponents {
+    /// Registers a component which is saved with serde
+    pub fn register<T>(&mut self, name: &'static str)
+    where
+        T: Component + Serialize + DeserializeOwned,
+    {
+        self.register_with(name, save_component::<T>, load_component::<T>);
+    }
+
+    /// Registers a metadata component, which is saved in the same format it is sent to clients in
+    pub fn register_metadata<M>(&mut self, name: &'static str)
+    where
+        M: Metadata + Component + Clone + From<M::Type>,
+        M::Type: for<'a> Decode<'a>,
+    {
+        self.register_with(name, save_metadata::<M>, load_metadata::<M>);
+    }
+
+    /// Registers a component with custom save and load functions. `save` returns `None` if the
+    /// entity does not have the component.
+    pub fn register_with(&mut self, name: &'static str, save: SaveFn, load: LoadFn) {
+        if self
+            .components
+            .iter()
+            .any(|component| component.name == name)
+        {
+            error!("persistent component {name} is already registered");
+            return;
+        }
+
+        self.components
+            .push(PersistentComponent { name, save, load });
+    }
+
+    fn save(&self, entity: EntityRef<'_>) -> HashMap<String, Value> {
+        let mut components = HashMap::new();
+
+        for component in &self.components {
+            match (component.save)(entity) {
+                Some(Ok(value)) => {
+                    components.insert(component.name.to_owned(), value);
+                }
+                Some(Err(e)) => {
+                    error!(
+                        "failed to save persistent component {}: {e}",
+                        component.name
+                    );
+                }
+                None => {}
+            }
+        }
+
+        components
+    }
+
+    fn load(&self, entity: &mut EntityWorldMut<'_>, components: HashMap<String, Value>) {
+        for (name, value) in components {
+            let Some(component) = self
+                .components
+                .iter()
+                .find(|component| component.name == name)
+            else {
+                error!("failed to load persistent component {name}: component is not registered");
+                continue;
+            };
+
+            if let Err(e) = (component.load)(entity, value) {
+                error!("failed to load persistent component {name}: {e}");
+            }
+        }
+    }
+}
+
+fn save_component<T: Component + Serialize>(
+    entity: EntityRef<'_>,
+) -> Option<anyhow::Result<Value>> {
+    let component = entity.get::<T>()?;
+    Some(serde_json::to_value(component).map_err(Into::into))
+}
+
+fn load_component<T: Component + DeserializeOwned>(
+    entity: &mut EntityWorldMut<'_>,
+    value: Value,
+) -> anyhow::Result<()> {
+    entity.insert(serde_json::from_value::<T>(value)?);
+    Ok(())
+}
+
+fn save_metadata<M: Metadata + Component + Clone>(
+    entity: EntityRef<'_>,
+) -> Option<anyhow::Result<Value>> {
+    let component = entity.get::<M>()?;
+
+    let mut bytes = Vec::new();
+    let result = component
+        .clone()
+        .to_type()
+        .encode(&mut bytes)
+        .and_then(|()| serde_json::to_value(bytes).map_err(Into::into));
+
+    Some(result)
+}
+
+fn load_metadata<M>(entity: &mut EntityWorldMut<'_>, value: Value) -> anyhow::Result<()>
+where
+    M: Metadata + Component + From<M::Type>,
+    M::Type: for<'a> Decode<'a>,
+{
+    let bytes = serde_json::from_value::<Vec<u8>>(value)?;
+    let value = M::Type::decode(&mut bytes.as_slice())?;
+    entity.insert(M::from(value));
+    Ok(())
+}
+
+impl PersistedEntity {
+    fn from_entity(entity: EntityRef<'_>, components: &PersistentComponents) -> Option<Self> {
+        Some(Self {
+            kind: *entity.get::<EntityKind>()?,
+            position: **entity.get::<Position>()?,
+            velocity: entity
+                .get::<Velocity>()
+                .map_or(Vec3::ZERO, |velocity| velocity.0),
+            yaw: entity.get::<Yaw>().map_or(0.0, |yaw| **yaw),
+            pitch: entity.get::<Pitch>().map_or(0.0, |pitch| **pitch),
+            components: components.save(entity),
+        })
+    }
+}
+
+/// Spawns the entities saved by earlier runs of the server
+fn load_persistent_entities(world: &mut World) {
+    let entities = match world.resource::<EntityHandler>().all() {
+        Ok(entities) => entities,
+        Err(e) => {
+            error!("failed to load persistent entities: {e}");
+            return;
+        }
+    };
+
+    world.resource_scope(|world, components: Mut<'_, PersistentComponents>| {
+        for (uuid, bytes) in &entities {
+            let persisted = match serde_json::from_slice::<PersistedEntity>(bytes) {
+                Ok(persisted) => persisted,
+                Err(e) => {
+                    error!("failed to load persistent entity {uuid}: {e}");
+                    continue;
+                }
+            };
+
+            let id = world
+                .spawn((
+                    Uuid::from(*uuid),
+                    Position::from(persisted.position),
+                    Velocity(persisted.velocity),
+                    Yaw::new(persisted.yaw),
+                    Pitch::new(persisted.pitch),
+                    persisted.kind,
+                    Persistent,
+                    Channel,
+                ))
+                .id();
+
+            // The default metadata of the entity is inserted by an observer, so it must be
+            // applied before the saved components to avoid overwriting them
+            world.flush();
+
+            let mut entity = world.entity_mut(id);
+            components.load(&mut entity, persisted.components);
+        }
+    });
+
+    info!("loaded {} persistent entities", entities.len());
+}
+
+fn encode_entity(
+    entity: EntityRef<'_>,
+    components: &PersistentComponents,
+) -> Option<(uuid::Uuid, Vec<u8>)> {
+    let uuid = **entity.get::<Uuid>()?;
+    let persisted = PersistedEntity::from_entity(entity, components)?;
+
+    match serde_json::to_vec(&persisted) {
+        Ok(bytes) => Some((uuid, bytes)),
+        Err(e) => {
+            error!("failed to save persistent entity {uuid}: {e}");
+            None
+        }
+    }
+}
+
+/// Saves new persistent entities immediately and all persistent entities every
+/// [`SAVE_INTERVAL`] ticks
+fn save_persistent_entities(
+    compose: Res<'_, Compose>,
+    components: Res<'_, PersistentComponents>,
+    handler: Res<'_, EntityHandler>,
+    added: Query<'_, '_, EntityRef<'_>, Added<Persistent>>,
+    all: Query<'_, '_, EntityRef<'_>, With<Persistent>>,
+) {
+    let entities = if compose.global().tick % SAVE_INTERVAL == 0 {
+        all.iter()
+            .filter_map(|entity| encode_entity(entity, &components))
+            .collect::<Vec<_>>()
+    } else {
+        added
+            .iter()
+            .filter_map(|entity| encode_entity(entity, &components))
+            .collect::<Vec<_>>()
+    };
+
+    if entities.is_empty() {
+        return;
+    }
+
+    if let Err(e) = handler.insert_all(&entities) {
+        error!("failed to save persistent entities: {e}");
+    }
+}
+
+fn remove_persistent_entity(
+    trigger: Trigger<'_, OnRemove, Persistent>,
+    query: Query<'_, '_, &Uuid>,
+    handler: Res<'_, EntityHandler>,
+) {
+    let Ok(uuid) = query.get(trigger.target()) else {
+        return;
+    };
+
+    if let Err(e) = handler.remove(**uuid) {
+        error!("failed to remove persistent entity {}: {e}", **uuid);
+    }
+}
+
+pub struct PersistentPlugin;
+
+impl Plugin for PersistentPlugin {
+    fn build(&self, app: &mut App) {
+        let mut components = PersistentComponents::default();
+        components.register_metadata::<CustomName>("custom_name");
+        components.register_metadata::<CustomNameVisible>("custom_name_visible");
+        components.register_metadata::<Silent>("silent");
+        components.register_metadata::<NoGravity>("no_gravity");
+        components.register_metadata::<Item>("item");
+
+        app.insert_resource(components);
+        app.add_observer(remove_persistent_entity);
+        app.add_systems(Startup, load_persistent_entities);
+        app.add_systems(
+            FixedPostUpdate,
+            save_persistent_entities.in_set(AdvanceTick),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_persisted_entity_roundtrip() {
+        let persisted = PersistedEntity {
+            kind: EntityKind::ArmorStand,
+            position: Vec3::new(1.0, 64.0, -3.5),
+            velocity: Vec3::ZERO,
+            yaw: 90.0,
+            pitch: 0.0,
+            components: HashMap::from([("no_gravity".to_owned(), Value::from(vec![1_u8]))]),
+        };
+
+        let bytes = serde_json::to_vec(&persisted).unwrap();
+        let parsed = serde_json::from_slice::<PersistedEntity>(&bytes).unwrap();
+        assert_eq!(parsed, persisted);
+    }
+}
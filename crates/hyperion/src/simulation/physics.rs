@@ -0,0 +1,293 @@
+//! Movement of server-controlled entities through blocks.
+//!
+//! Entities with [`Physics`] are moved by their [`Velocity`] every tick. Their bounding box is
+//! moved one axis at a time and stopped by the collision shapes of blocks, so entities slide along
+//! walls, stop at ceilings and land on the ground instead of clipping through terrain. The
+//! velocity of every axis which hit a block is reset.
+//!
+//! ```ignore
+//! commands.spawn((
+//!     EntityKind::Zombie,
+//!     Position::new(0.0, 64.0, 0.0),
+//!     EntitySize::default(),
+//!     Velocity::default(),
+//!     Physics::mob(),
+//! ));
+//! ```
+
+use std::ops::ControlFlow;
+
+use bevy::prelude::*;
+use geometry::aabb::Aabb;
+use glam::Vec3;
+
+use crate::{
+    simulation::{EntitySize, Position, Velocity, aabb, blocks::Blocks},
+    tick_rate::AdvanceTick,
+    timings::timed,
+};
+
+/// How close a bounding box may get to a block before it is stopped
+const EPSILON: f32 = 1.0e-5;
+
+/// Moves an entity by its [`Velocity`] and stops it at blocks
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct Physics {
+    /// How much the downwards velocity grows every tick, in blocks per tick
+    pub gravity: f32,
+    /// How much of the velocity is kept every tick, from 0.0 to 1.0
+    pub drag: f32,
+    /// How high a block the entity walks up instead of being stopped by, such as 0.6 for mobs
+    pub step_height: f32,
+    /// Whether the entity was stopped by a block below it in the last tick. This is set by the
+    /// physics step.
+    pub on_ground: bool,
+}
+
+impl Physics {
+    /// The physics of dropped items
+    #[must_use]
+    pub const fn item() -> Self {
+        Self {
+            gravity: 0.04,
+            drag: 0.98,
+            step_height: 0.0,
+            on_ground: false,
+        }
+    }
+
+    /// The physics of walking mobs
+    #[must_use]
+    pub const fn mob() -> Self {
+        Self {
+            gravity: 0.08,
+            drag: 0.98,
+            step_height: 0.6,
+            on_ground: false,
+        }
+    }
+}
+
+/// The collision shapes of every block which the bounding box could hit while moving by `motion`
+#[must_use]
+pub fn block_shapes(bounds: Aabb, motion: Vec3, blocks: &Blocks) -> Vec<Aabb> {
+    let swept = Aabb::new(
+        bounds.min + motion.min(Vec3::ZERO),
+        bounds.max + motion.max(Vec3::ZERO),
+    );
+
+    let min = swept.min.floor().as_ivec3();
+    let max = swept.max.ceil().as_ivec3();
+
+    let mut shapes = Vec::new();
+
+    let _: ControlFlow<()> = blocks.get_blocks(min, max, |position, block| {
+        let origin = position.as_vec3();
+
+        shapes.extend(
+            block
+                .collision_shapes()
+                .map(|shape| Aabb::new(shape.min().as_vec3(), shape.max().as_vec3()))
+                .map(|shape| shape.move_by(origin)),
+        );
+
+        ControlFlow::Continue(())
+    });
+
+    shapes
+}
+
+/// How far `bounds` can move by `distance` along `axis` before it hits one of the `shapes`
+fn clip_axis(bounds: &Aabb, shapes: &[Aabb], axis: usize, mut distance: f32) -> f32 {
+    for shape in shapes {
+        // The shape is only in the way if it overlaps the bounding box on the other two axes
+        let overlaps = (0..3).filter(|&other| other != axis).all(|other| {
+            shape.max[other] > bounds.min[other] && shape.min[other] < bounds.max[other]
+        });
+
+        if !overlaps {
+            continue;
+        }
+
+        if distance > 0.0 && shape.min[axis] >= bounds.max[axis] - EPSILON {
+            distance = distance.min(shape.min[axis] - bounds.max[axis]);
+        } else if distance < 0.0 && shape.max[axis] <= bounds.min[axis] + EPSILON {
+            distance = distance.max(shape.max[axis] - bounds.min[axis]);
+        }
+    }
+
+    distance
+}
+
+/// Whether a block stopped the movement along an axis
+#[expect(
+    clippy::float_cmp,
+    reason = "the distance is returned unchanged unless a block was hit"
+)]
+fn hit(moved: f32, motion: f32) -> bool {
+    moved != motion
+}
+
+/// Moves `bounds` by `motion` along y, then x, then z, stopping at each of the `shapes`
+fn slide(bounds: Aabb, shapes: &[Aabb], motion: Vec3) -> Vec3 {
+    let mut bounds = bounds;
+    let mut moved = Vec3::ZERO;
+
+    for axis in [1, 0, 2] {
+        let distance = clip_axis(&bounds, shapes, axis, motion[axis]);
+
+        let mut offset = Vec3::ZERO;
+        offset[axis] = distance;
+
+        bounds = bounds.move_by(offset);
+        moved[axis] = distance;
+    }
+
+    moved
+}
+
+/// How far `bounds` moves when it tries to move by `motion` through the `shapes`. If a wall stops
+/// the entity while it is on the ground, it steps up onto blocks which are at most `step_height`
+/// high.
+#[must_use]
+pub fn move_and_collide(bounds: Aabb, shapes: &[Aabb], motion: Vec3, step_height: f32) -> Vec3 {
+    let moved = slide(bounds, shapes, motion);
+
+    let blocked_horizontally = hit(moved.x, motion.x) || hit(moved.z, motion.z);
+    let landed = motion.y < 0.0 && hit(moved.y, motion.y);
+
+    if step_height <= 0.0 || !blocked_horizontally || !landed {
+        return moved;
+    }
+
+    // Try again from `step_height` higher, then move back down onto the block
+    let up = clip_axis(&bounds, shapes, 1, step_height);
+    let raised = bounds.move_by(Vec3::new(0.0, up, 0.0));
+
+    let horizontal = slide(raised, shapes, Vec3::new(motion.x, 0.0, motion.z));
+    let stepped = raised.move_by(horizontal);
+    let down = clip_axis(&stepped, shapes, 1, -up + motion.y.min(0.0));
+
+    let stepped_moved = Vec3::new(horizontal.x, up + down, horizontal.z);
+
+    if stepped_moved.x.hypot(stepped_moved.z) > moved.x.hypot(moved.z) {
+        stepped_moved
+    } else {
+        moved
+    }
+}
+
+fn apply_physics(
+    mut query: Query<'_, '_, (&mut Physics, &mut Position, &mut Velocity, &EntitySize)>,
+    blocks: Res<'_, Blocks>,
+) {
+    for (mut physics, mut position, mut velocity, &size) in &mut query {
+        let motion = velocity.0;
+
+        if motion == Vec3::ZERO && physics.on_ground {
+            continue;
+        }
+
+        let bounds = aabb(**position, size);
+        let shapes = block_shapes(bounds, motion + Vec3::Y * physics.step_height, &blocks);
+        let moved = move_and_collide(bounds, &shapes, motion, physics.step_height);
+
+        **position += moved;
+
+        // Stop moving along every axis which hit a block
+        for axis in 0..3 {
+            if hit(moved[axis], motion[axis]) {
+                velocity.0[axis] = 0.0;
+            }
+        }
+
+        physics.on_ground = motion.y < 0.0 && hit(moved.y, motion.y);
+
+        velocity.0.y -= physics.gravity;
+        velocity.0 *= physics.drag;
+    }
+}
+
+pub(crate) fn build(app: &mut App) {
+    app.add_systems(FixedUpdate, timed(apply_physics).in_set(AdvanceTick));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(x: f32, y: f32, z: f32) -> Aabb {
+        Aabb::new(Vec3::new(x, y, z), Vec3::new(x + 1.0, y + 1.0, z + 1.0))
+    }
+
+    fn entity(position: Vec3) -> Aabb {
+        aabb(position, EntitySize::default())
+    }
+
+    #[test]
+    fn test_lands_on_ground() {
+        let shapes = [block(0.0, 63.0, 0.0)];
+        let moved = move_and_collide(
+            entity(Vec3::new(0.5, 64.5, 0.5)),
+            &shapes,
+            Vec3::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+
+        assert!((moved.y + 0.5).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn test_slides_along_wall() {
+        let shapes = [block(1.0, 64.0, 0.0), block(1.0, 65.0, 0.0)];
+        let moved = move_and_collide(
+            entity(Vec3::new(0.5, 64.0, 0.5)),
+            &shapes,
+            Vec3::new(0.5, 0.0, 0.25),
+            0.0,
+        );
+
+        assert!((moved.x - 0.2).abs() < 1.0e-4);
+        assert!((moved.z - 0.25).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn test_stops_at_ceiling() {
+        let shapes = [block(0.0, 66.0, 0.0)];
+        let moved = move_and_collide(
+            entity(Vec3::new(0.5, 64.0, 0.5)),
+            &shapes,
+            Vec3::new(0.0, 0.5, 0.0),
+            0.0,
+        );
+
+        assert!((moved.y - 0.2).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn test_steps_up() {
+        let shapes = [
+            block(0.0, 63.0, 0.0),
+            block(1.0, 63.0, 0.0),
+            Aabb::new(Vec3::new(1.0, 64.0, 0.0), Vec3::new(2.0, 64.5, 1.0)),
+        ];
+        let moved = move_and_collide(
+            entity(Vec3::new(0.5, 64.0, 0.5)),
+            &shapes,
+            Vec3::new(0.5, -0.08, 0.0),
+            0.6,
+        );
+
+        assert!((moved.x - 0.5).abs() < 1.0e-4);
+        assert!((moved.y - 0.5).abs() < 1.0e-4);
+
+        // Without a step height the slab stops the entity
+        let moved = move_and_collide(
+            entity(Vec3::new(0.5, 64.0, 0.5)),
+            &shapes,
+            Vec3::new(0.5, -0.08, 0.0),
+            0.0,
+        );
+        assert!((moved.x - 0.2).abs() < 1.0e-4);
+    }
+}
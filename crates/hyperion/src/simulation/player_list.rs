@@ -0,0 +1,301 @@
+//! Names shown in the player list.
+//!
+//! Players are listed with their username unless they have a [`Nickname`] or a [`DisplayName`],
+//! and players who are [`Afk`] are grayed out with an `[AFK]` prefix. Only the entries which
+//! changed are sent again. Prefixes, suffixes and the order of the player list come from
+//! scoreboard teams, which are set with [`ListRank`](crate::simulation::team::ListRank).
+//!
+//! A [`Nickname`] also replaces the name above the head of the player, while a [`DisplayName`] is
+//! only shown in the player list.
+//!
+//! ```ignore
+//! commands
+//!     .entity(player)
+//!     .insert(DisplayName("Notch".color(Color::GOLD)));
+//!
+//! commands.entity(player).insert(Nickname("Jeb_".to_owned()));
+//! ```
+
+use std::{borrow::Cow, collections::BTreeSet};
+
+use bevy::prelude::*;
+use hyperion_inventory::PlayerInventory;
+use hyperion_utils::EntityExt;
+use tracing::{error, warn};
+use valence_bytes::CowBytes;
+use valence_protocol::{
+    ByteAngle, RawBytes, VarInt,
+    packets::play::{self, entity_equipment_update_s2c::EquipmentEntry},
+};
+use valence_text::{Color, IntoText, Text};
+
+use crate::{
+    egress::player_join::{
+        PlayerListActions, PlayerListEntry, PlayerListS2c, RevealRules, reveal_to_players,
+    },
+    net::{Compose, ConnectionId},
+    simulation::{
+        IgnMap, Pitch, Position, Uuid, Yaw,
+        afk::Afk,
+        metadata::{MetadataChanges, get_and_clear_metadata},
+        packet_state,
+    },
+    timings::timed,
+};
+
+/// The name shown for a player in the player list instead of their username
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct DisplayName(pub Text);
+
+/// The name shown for a player instead of their username, in the player list and above their
+/// head. Players can be found by both their username and their nickname in the [`IgnMap`], unless
+/// the nickname is the name of another player.
+#[derive(Component, Clone, Debug, PartialEq, Eq, Deref)]
+pub struct Nickname(pub String);
+
+/// The name in the game profile of a player, which is shown above their head
+#[must_use]
+pub fn profile_name<'a>(name: &'a Name, nickname: Option<&'a Nickname>) -> &'a str {
+    nickname.map_or(name.as_str(), |nickname| nickname.as_str())
+}
+
+/// The name shown for a player in the player list
+#[must_use]
+pub fn list_name(
+    name: &Name,
+    nickname: Option<&Nickname>,
+    display_name: Option<&DisplayName>,
+    afk: bool,
+) -> Text {
+    let name = display_name.map_or_else(
+        || profile_name(name, nickname).to_owned().into_text(),
+        |display_name| display_name.0.clone(),
+    );
+
+    if afk {
+        "[AFK] ".color(Color::GRAY) + name
+    } else {
+        name
+    }
+}
+
+fn sync_display_names(
+    query: Query<
+        '_,
+        '_,
+        (
+            &Uuid,
+            &Name,
+            Option<&Nickname>,
+            Option<&DisplayName>,
+            Has<Afk>,
+        ),
+    >,
+    changed: Query<'_, '_, Entity, Or<(Changed<DisplayName>, Changed<Nickname>, Added<Afk>)>>,
+    mut removed_names: RemovedComponents<'_, '_, DisplayName>,
+    mut removed_nicknames: RemovedComponents<'_, '_, Nickname>,
+    mut removed_afk: RemovedComponents<'_, '_, Afk>,
+    compose: Res<'_, Compose>,
+) {
+    let players: BTreeSet<Entity> = changed
+        .iter()
+        .chain(removed_names.read())
+        .chain(removed_nicknames.read())
+        .chain(removed_afk.read())
+        .collect();
+
+    let entries: Vec<_> = players
+        .into_iter()
+        .filter_map(|player| query.get(player).ok())
+        .map(
+            |(uuid, name, nickname, display_name, afk)| PlayerListEntry {
+                player_uuid: uuid.0,
+                display_name: Some(Cow::Owned(list_name(name, nickname, display_name, afk))),
+                ..Default::default()
+            },
+        )
+        .collect();
+
+    if entries.is_empty() {
+        return;
+    }
+
+    let pkt = PlayerListS2c {
+        actions: PlayerListActions::default().with_update_display_name(true),
+        entries: Cow::Owned(entries),
+    };
+
+    if let Err(e) = compose.broadcast(&pkt).send() {
+        error!("failed to send display names: {e}");
+    }
+}
+
+fn add_nickname(
+    trigger: Trigger<'_, OnInsert, Nickname>,
+    query: Query<'_, '_, &Nickname>,
+    mut ign_map: ResMut<'_, IgnMap>,
+) {
+    let Ok(nickname) = query.get(trigger.target()) else {
+        return;
+    };
+
+    // The username of another player takes precedence
+    ign_map
+        .entry(nickname.0.clone())
+        .or_insert(trigger.target());
+}
+
+fn remove_nickname(
+    trigger: Trigger<'_, OnReplace, Nickname>,
+    query: Query<'_, '_, &Nickname>,
+    mut ign_map: ResMut<'_, IgnMap>,
+) {
+    let Ok(nickname) = query.get(trigger.target()) else {
+        return;
+    };
+
+    if ign_map.get(nickname.as_str()) == Some(&trigger.target()) {
+        ign_map.remove(nickname.as_str());
+    }
+}
+
+/// The equipment shown to other players, which is lost when the player is spawned again
+fn equipment(inventory: &PlayerInventory) -> Vec<EquipmentEntry> {
+    [
+        (0, inventory.get_cursor()),
+        (1, inventory.get_offhand()),
+        (2, inventory.get_boots()),
+        (3, inventory.get_leggings()),
+        (4, inventory.get_chestplate()),
+        (5, inventory.get_helmet()),
+    ]
+    .into_iter()
+    .map(|(slot, item)| EquipmentEntry {
+        slot,
+        item: item.stack.clone(),
+    })
+    .collect()
+}
+
+/// Replaces the player list entry of `player` for other players and spawns it again for the
+/// players who can see it
+fn respawn_player<'a>(
+    player: EntityRef<'_>,
+    players: impl IntoIterator<Item = EntityRef<'a>> + Clone,
+    rules: &RevealRules,
+    compose: &Compose,
+) -> anyhow::Result<()> {
+    let (Some(uuid), Some(position), Some(yaw), Some(pitch)) = (
+        player.get::<Uuid>(),
+        player.get::<Position>(),
+        player.get::<Yaw>(),
+        player.get::<Pitch>(),
+    ) else {
+        return Ok(());
+    };
+
+    let connection_id = player.get::<ConnectionId>().copied();
+
+    compose
+        .broadcast(&play::PlayerRemoveS2c {
+            uuids: Cow::Borrowed(&[uuid.0]),
+        })
+        .exclude(connection_id)
+        .send()?;
+
+    reveal_to_players(player, players, rules, compose)?;
+
+    let entity_id = VarInt(player.id().minecraft_id());
+    let channel = player.id().into();
+
+    compose
+        .broadcast_channel(
+            &play::EntitiesDestroyS2c {
+                entity_ids: Cow::Borrowed(&[entity_id]),
+            },
+            channel,
+        )
+        .exclude(connection_id)
+        .send()?;
+
+    compose
+        .broadcast_channel(
+            &play::PlayerSpawnS2c {
+                entity_id,
+                player_uuid: uuid.0,
+                position: position.as_dvec3(),
+                yaw: ByteAngle::from_degrees(**yaw),
+                pitch: ByteAngle::from_degrees(**pitch),
+            },
+            channel,
+        )
+        .exclude(connection_id)
+        .send()?;
+
+    let mut metadata = MetadataChanges::default();
+    metadata.encode_non_default_components(player);
+
+    if let Some(view) = get_and_clear_metadata(&mut metadata) {
+        compose
+            .broadcast_channel(
+                &play::EntityTrackerUpdateS2c {
+                    entity_id,
+                    tracked_values: RawBytes(CowBytes::Borrowed(&view)),
+                },
+                channel,
+            )
+            .exclude(connection_id)
+            .send()?;
+    }
+
+    if let Some(inventory) = player.get::<PlayerInventory>() {
+        compose
+            .broadcast_channel(
+                &play::EntityEquipmentUpdateS2c {
+                    entity_id,
+                    equipment: equipment(inventory),
+                },
+                channel,
+            )
+            .exclude(connection_id)
+            .send()?;
+    }
+
+    Ok(())
+}
+
+/// Clients only read the name above the head of a player from their game profile when the player
+/// is spawned, so players whose [`Nickname`] changed are spawned again for other players
+pub(crate) fn sync_nicknames(
+    changed: Query<'_, '_, Entity, Changed<Nickname>>,
+    mut removed: RemovedComponents<'_, '_, Nickname>,
+    players: Query<'_, '_, EntityRef<'_>, (With<Uuid>, With<Name>)>,
+    rules: Res<'_, RevealRules>,
+    compose: Res<'_, Compose>,
+) {
+    let changed: BTreeSet<Entity> = changed.iter().chain(removed.read()).collect();
+
+    for player in changed {
+        let Ok(player) = players.get(player) else {
+            continue;
+        };
+
+        // Players who have not joined yet are spawned with their nickname
+        if !player.contains::<packet_state::Play>() {
+            continue;
+        }
+
+        if let Err(e) = respawn_player(player, &players, &rules, &compose) {
+            warn!("failed to update the nickname of a player: {e}");
+        }
+    }
+}
+
+pub(crate) fn build(app: &mut App) {
+    app.add_observer(add_nickname);
+    app.add_observer(remove_nickname);
+    app.add_systems(
+        FixedPostUpdate,
+        (timed(sync_nicknames), timed(sync_display_names)),
+    );
+}
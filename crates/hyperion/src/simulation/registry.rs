@@ -0,0 +1,320 @@
+//! The registries which are sent to players when they join, such as dimension types, biomes and
+//! damage types.
+//!
+//! Plugins may add their own entries to [`Registries`] while the app is being built. Players
+//! which already joined do not see changes until they reconnect, so the registries should not be
+//! changed after the first player joins.
+//!
+//! ```ignore
+//! let mut registries = app.world_mut().resource_mut::<Registries>();
+//! registries.add_dimension_type("hyperion:arena", &DimensionSettings {
+//!     ambient_light: 1.0,
+//!     fixed_time: Some(6000),
+//!     ..DimensionSettings::default()
+//! })?;
+//! ```
+
+use std::collections::BTreeSet;
+
+use anyhow::{Context, bail};
+use bevy::prelude::*;
+use valence_nbt::{Compound, List, Value};
+use valence_protocol::Ident;
+
+use crate::simulation::util::registry_codec_raw;
+
+pub const DIMENSION_TYPE: &str = "minecraft:dimension_type";
+pub const BIOME: &str = "minecraft:worldgen/biome";
+pub const DAMAGE_TYPE: &str = "minecraft:damage_type";
+
+/// A dimension type. Settings which are not listed here are the same as in the overworld.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DimensionSettings {
+    /// The lowest y level of blocks, which must be a multiple of 16
+    pub min_y: i32,
+    /// The number of blocks above `min_y`, which must be a multiple of 16
+    pub height: i32,
+    /// How bright unlit blocks are, from 0.0 to 1.0
+    pub ambient_light: f32,
+    /// How the sky and fog are drawn, which is one of `minecraft:overworld`,
+    /// `minecraft:the_nether` or `minecraft:the_end`
+    pub effects: String,
+    pub has_skylight: bool,
+    pub has_ceiling: bool,
+    /// The time of day which is always shown, if any
+    pub fixed_time: Option<i64>,
+}
+
+impl Default for DimensionSettings {
+    fn default() -> Self {
+        Self {
+            min_y: -64,
+            height: 384,
+            ambient_light: 0.0,
+            effects: "minecraft:overworld".to_owned(),
+            has_skylight: true,
+            has_ceiling: false,
+            fixed_time: None,
+        }
+    }
+}
+
+/// A biome. Settings which are not listed here are the same as in plains.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BiomeSettings {
+    pub temperature: f32,
+    pub downfall: f32,
+    pub has_precipitation: bool,
+    pub sky_color: i32,
+    pub fog_color: i32,
+    pub water_color: i32,
+    pub water_fog_color: i32,
+    /// The color of grass, or `None` to use the color from the temperature and downfall
+    pub grass_color: Option<i32>,
+    /// The color of leaves, or `None` to use the color from the temperature and downfall
+    pub foliage_color: Option<i32>,
+}
+
+impl Default for BiomeSettings {
+    fn default() -> Self {
+        Self {
+            temperature: 0.8,
+            downfall: 0.4,
+            has_precipitation: true,
+            sky_color: 0x78_A7_FF,
+            fog_color: 0xC0_D8_FF,
+            water_color: 0x3F_76_E4,
+            water_fog_color: 0x05_05_33,
+            grass_color: None,
+            foliage_color: None,
+        }
+    }
+}
+
+/// The registry codec which is sent in the join packet. It starts out as the vanilla registries.
+#[derive(Resource, Debug)]
+pub struct Registries {
+    codec: Compound,
+    generation: u64,
+}
+
+impl Default for Registries {
+    fn default() -> Self {
+        Self {
+            codec: registry_codec_raw().clone(),
+            generation: 0,
+        }
+    }
+}
+
+impl Registries {
+    #[must_use]
+    pub const fn codec(&self) -> &Compound {
+        &self.codec
+    }
+
+    /// Increases every time the registries change, so cached join data can be rebuilt
+    #[must_use]
+    pub const fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The names of every entry in a registry, such as [`DIMENSION_TYPE`]
+    #[must_use]
+    pub fn names(&self, registry: &str) -> BTreeSet<Ident> {
+        self.entries(registry)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| match entry.get("name") {
+                Some(Value::String(name)) => Ident::new(name.clone()).ok().map(Ident::from),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Adds a dimension type, or replaces the dimension type with the same name
+    pub fn add_dimension_type(
+        &mut self,
+        name: &str,
+        settings: &DimensionSettings,
+    ) -> anyhow::Result<()> {
+        if settings.min_y % 16 != 0 || settings.height % 16 != 0 || settings.height <= 0 {
+            bail!("dimension type {name} must have a min_y and height which are multiples of 16");
+        }
+
+        let mut element = self.element(DIMENSION_TYPE, "minecraft:overworld")?;
+        element.insert("min_y", settings.min_y);
+        element.insert("height", settings.height);
+        element.insert("logical_height", settings.height);
+        element.insert("ambient_light", settings.ambient_light);
+        element.insert("effects", settings.effects.clone());
+        element.insert("has_skylight", settings.has_skylight);
+        element.insert("has_ceiling", settings.has_ceiling);
+        match settings.fixed_time {
+            Some(time) => element.insert("fixed_time", time),
+            None => element.remove("fixed_time"),
+        };
+
+        self.insert(DIMENSION_TYPE, name, element)
+    }
+
+    /// Adds a biome, or replaces the biome with the same name
+    pub fn add_biome(&mut self, name: &str, settings: &BiomeSettings) -> anyhow::Result<()> {
+        let mut element = self.element(BIOME, "minecraft:plains")?;
+        element.insert("temperature", settings.temperature);
+        element.insert("downfall", settings.downfall);
+        element.insert("has_precipitation", settings.has_precipitation);
+
+        let Some(Value::Compound(effects)) = element.get_mut("effects") else {
+            bail!("expected plains to have effects");
+        };
+        effects.insert("sky_color", settings.sky_color);
+        effects.insert("fog_color", settings.fog_color);
+        effects.insert("water_color", settings.water_color);
+        effects.insert("water_fog_color", settings.water_fog_color);
+        for (key, color) in [
+            ("grass_color", settings.grass_color),
+            ("foliage_color", settings.foliage_color),
+        ] {
+            match color {
+                Some(color) => effects.insert(key, color),
+                None => effects.remove(key),
+            };
+        }
+
+        self.insert(BIOME, name, element)
+    }
+
+    /// Adds a damage type, or replaces the damage type with the same name. The death message is
+    /// the translation key `death.attack.<message_id>`.
+    pub fn add_damage_type(
+        &mut self,
+        name: &str,
+        message_id: &str,
+        exhaustion: f32,
+    ) -> anyhow::Result<()> {
+        let mut element = self.element(DAMAGE_TYPE, "minecraft:generic")?;
+        element.insert("message_id", message_id.to_owned());
+        element.insert("exhaustion", exhaustion);
+
+        self.insert(DAMAGE_TYPE, name, element)
+    }
+
+    /// Adds an entry to any registry, or replaces the entry with the same name. Existing entries
+    /// keep their id and new entries are given the next free id.
+    pub fn insert(&mut self, registry: &str, name: &str, element: Compound) -> anyhow::Result<()> {
+        let name = Ident::new(name.to_owned())
+            .ok()
+            .with_context(|| format!("{name} is not a valid identifier"))?
+            .as_str()
+            .to_owned();
+
+        let entries = self
+            .entries_mut(registry)
+            .with_context(|| format!("there is no registry named {registry}"))?;
+
+        if let Some(entry) = entries
+            .iter_mut()
+            .find(|entry| matches!(entry.get("name"), Some(Value::String(found)) if *found == name))
+        {
+            entry.insert("element", element);
+        } else {
+            let id = entries
+                .iter()
+                .filter_map(|entry| match entry.get("id") {
+                    Some(&Value::Int(id)) => Some(id + 1),
+                    _ => None,
+                })
+                .max()
+                .unwrap_or(0);
+
+            let mut entry = Compound::new();
+            entry.insert("name", name);
+            entry.insert("id", id);
+            entry.insert("element", element);
+            entries.push(entry);
+        }
+
+        self.generation += 1;
+        Ok(())
+    }
+
+    /// A copy of the element of an entry, which new entries are based on
+    fn element(&self, registry: &str, name: &str) -> anyhow::Result<Compound> {
+        self.entries(registry)
+            .into_iter()
+            .flatten()
+            .find(|entry| matches!(entry.get("name"), Some(Value::String(found)) if found == name))
+            .and_then(|entry| match entry.get("element") {
+                Some(Value::Compound(element)) => Some(element.clone()),
+                _ => None,
+            })
+            .with_context(|| format!("expected {registry} to contain {name}"))
+    }
+
+    fn entries(&self, registry: &str) -> Option<&Vec<Compound>> {
+        let Some(Value::Compound(registry)) = self.codec.get(registry) else {
+            return None;
+        };
+
+        match registry.get("value") {
+            Some(Value::List(List::Compound(entries))) => Some(entries),
+            _ => None,
+        }
+    }
+
+    fn entries_mut(&mut self, registry: &str) -> Option<&mut Vec<Compound>> {
+        let Some(Value::Compound(registry)) = self.codec.get_mut(registry) else {
+            return None;
+        };
+
+        match registry.get_mut("value") {
+            Some(Value::List(List::Compound(entries))) => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_dimension_type() {
+        let mut registries = Registries::default();
+        let vanilla = registries.names(DIMENSION_TYPE).len();
+
+        registries
+            .add_dimension_type("hyperion:arena", &DimensionSettings {
+                ambient_light: 1.0,
+                ..DimensionSettings::default()
+            })
+            .unwrap();
+
+        assert_eq!(registries.generation(), 1);
+        assert_eq!(registries.names(DIMENSION_TYPE).len(), vanilla + 1);
+
+        let element = registries
+            .element(DIMENSION_TYPE, "hyperion:arena")
+            .unwrap();
+        assert_eq!(element.get("ambient_light"), Some(&Value::Float(1.0)));
+
+        // Replacing an entry keeps the number of entries
+        registries
+            .add_dimension_type("hyperion:arena", &DimensionSettings::default())
+            .unwrap();
+        assert_eq!(registries.names(DIMENSION_TYPE).len(), vanilla + 1);
+    }
+
+    #[test]
+    fn test_invalid_dimension_height() {
+        let mut registries = Registries::default();
+        let result = registries.add_dimension_type("hyperion:broken", &DimensionSettings {
+            height: 100,
+            ..DimensionSettings::default()
+        });
+
+        assert!(result.is_err());
+        assert_eq!(registries.generation(), 0);
+    }
+}
@@ -0,0 +1,255 @@
+//! Editable signs.
+//!
+//! The text of a sign is stored as its block entity, so it is sent to players with the chunk. The
+//! sign editor is opened when a player places a sign, and plugins may open it for any sign with
+//! [`SignExt::open_sign_editor`]. Finished edits are sent as [`SignEditEvent`], and right clicking
+//! a sign sends [`SignClickEvent`], which allows plugins to build interactive signs such as warps
+//! and shops.
+//!
+//! [`SignEditEvent`]: event::SignEditEvent
+//! [`SignClickEvent`]: event::SignClickEvent
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use glam::IVec3;
+use tracing::{error, warn};
+use valence_generated::block::{BlockEntityKind, BlockState};
+use valence_nbt::{Compound, List, Value, compound};
+use valence_protocol::{BlockPos, packets::play::SignEditorOpenS2c};
+use valence_text::Text;
+
+use crate::{
+    ingress,
+    net::{Compose, ConnectionId},
+    simulation::{
+        blocks::Blocks,
+        event::{self, Cancellable, PlaceBlock},
+        packet::play,
+    },
+    timings::timed,
+};
+
+/// Opens the sign editor for the target player
+#[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OpenSignEditor {
+    pub position: IVec3,
+    pub is_front_text: bool,
+}
+
+/// The sign which a player may edit. Edits to any other sign are rejected.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EditingSign {
+    pub position: IVec3,
+    pub is_front_text: bool,
+}
+
+pub trait SignExt {
+    /// Opens the sign editor for the sign at `position` by triggering [`OpenSignEditor`]
+    fn open_sign_editor(&mut self, position: IVec3, is_front_text: bool) -> &mut Self;
+}
+
+impl SignExt for EntityCommands<'_> {
+    fn open_sign_editor(&mut self, position: IVec3, is_front_text: bool) -> &mut Self {
+        self.trigger(OpenSignEditor {
+            position,
+            is_front_text,
+        })
+    }
+}
+
+/// Returns whether the block is a sign or a hanging sign
+#[must_use]
+pub fn is_sign(block: BlockState) -> bool {
+    matches!(
+        block.block_entity_kind(),
+        Some(BlockEntityKind::Sign | BlockEntityKind::HangingSign)
+    )
+}
+
+/// The block entity data of one side of a sign
+fn sign_side(lines: &[String; 4]) -> Compound {
+    let messages = lines
+        .iter()
+        .map(|line| serde_json::to_string(&Text::text(line.clone())).unwrap_or_default())
+        .collect();
+
+    compound! {
+        "messages" => List::String(messages),
+        "color" => "black".to_owned(),
+        "has_glowing_text" => false,
+    }
+}
+
+/// Returns the block entity data of a sign after one of its sides is set to `lines`. The other
+/// side of `current` is kept.
+#[must_use]
+pub fn sign_data(current: Option<&Compound>, is_front_text: bool, lines: &[String; 4]) -> Compound {
+    let empty = || sign_side(&Default::default());
+
+    let (side, other) = if is_front_text {
+        ("front_text", "back_text")
+    } else {
+        ("back_text", "front_text")
+    };
+
+    let other_data = current
+        .and_then(|current| current.get(other).cloned())
+        .unwrap_or_else(|| Value::Compound(empty()));
+
+    compound! {
+        side => sign_side(lines),
+        other => other_data,
+        "is_waxed" => false,
+    }
+}
+
+/// Waxed signs cannot be edited
+fn is_waxed(data: Option<&Compound>) -> bool {
+    matches!(
+        data.and_then(|data| data.get("is_waxed")),
+        Some(Value::Byte(1))
+    )
+}
+
+fn open_sign_editor(
+    trigger: Trigger<'_, OpenSignEditor>,
+    query: Query<'_, '_, &ConnectionId>,
+    compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
+) {
+    let OpenSignEditor {
+        position,
+        is_front_text,
+    } = *trigger.event();
+
+    let Ok(&connection_id) = query.get(trigger.target()) else {
+        error!("failed to open sign editor: player is missing ConnectionId");
+        return;
+    };
+
+    let pkt = SignEditorOpenS2c {
+        location: BlockPos::new(position.x, position.y, position.z),
+        is_front_text,
+    };
+
+    if let Err(e) = compose.unicast(&pkt, connection_id) {
+        error!("failed to open sign editor: {e}");
+        return;
+    }
+
+    commands.entity(trigger.target()).insert(EditingSign {
+        position,
+        is_front_text,
+    });
+}
+
+fn open_placed_signs(
+    mut events: EventReader<'_, '_, Cancellable<PlaceBlock>>,
+    mut commands: Commands<'_, '_>,
+) {
+    for event in events.read() {
+        if event.is_cancelled() || !is_sign(event.block) {
+            continue;
+        }
+
+        commands
+            .entity(event.from)
+            .open_sign_editor(event.position, true);
+    }
+}
+
+fn update_signs(
+    mut packets: EventReader<'_, '_, play::UpdateSign>,
+    query: Query<'_, '_, Option<&EditingSign>>,
+    mut blocks: ResMut<'_, Blocks>,
+    mut sign_edit_writer: EventWriter<'_, event::SignEditEvent>,
+    mut commands: Commands<'_, '_>,
+) {
+    for packet in packets.read() {
+        let position = IVec3::new(packet.position.x, packet.position.y, packet.position.z);
+
+        let editing = match query.get(packet.sender()) {
+            Ok(editing) => editing,
+            Err(e) => {
+                error!("failed to update sign: query failed: {e}");
+                continue;
+            }
+        };
+
+        let expected = EditingSign {
+            position,
+            is_front_text: packet.is_front_text,
+        };
+
+        if editing != Some(&expected) {
+            warn!("player tried to edit a sign which they did not open: {position}");
+            continue;
+        }
+
+        commands.entity(packet.sender()).remove::<EditingSign>();
+
+        if !blocks.get_block(position).is_some_and(is_sign) {
+            continue;
+        }
+
+        let current = blocks.get_block_entity(position);
+
+        if is_waxed(current) {
+            continue;
+        }
+
+        let lines = packet.lines.each_ref().map(|line| line.0.to_owned());
+        let data = sign_data(current, packet.is_front_text, &lines);
+
+        if let Err(e) = blocks.set_block_entity(position, Some(data)) {
+            error!("failed to update sign: {e:?}");
+            continue;
+        }
+
+        sign_edit_writer.write(event::SignEditEvent {
+            client: packet.sender(),
+            position,
+            is_front_text: packet.is_front_text,
+            lines,
+        });
+    }
+}
+
+pub struct SignPlugin;
+
+impl Plugin for SignPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(open_sign_editor);
+        app.add_systems(
+            FixedUpdate,
+            (
+                timed(open_placed_signs).after(event::CancelEvents),
+                timed(update_signs),
+            )
+                .after(ingress::decode::play),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_data_keeps_other_side() {
+        let front = ["a".to_owned(), "b".to_owned(), String::new(), String::new()];
+        let back = ["c".to_owned(), String::new(), String::new(), String::new()];
+
+        let data = sign_data(None, true, &front);
+        let data = sign_data(Some(&data), false, &back);
+
+        assert_eq!(
+            data.get("front_text"),
+            Some(&Value::Compound(sign_side(&front)))
+        );
+        assert_eq!(
+            data.get("back_text"),
+            Some(&Value::Compound(sign_side(&back)))
+        );
+        assert!(!is_waxed(Some(&data)));
+    }
+}
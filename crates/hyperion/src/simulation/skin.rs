@@ -1,11 +1,16 @@
 //! Constructs for obtaining a player's skin.
+use std::fmt;
+
 use anyhow::Context;
 use base64::{Engine as _, engine::general_purpose};
 use bevy::prelude::*;
 use rkyv::Archive;
 use tracing::info;
 
-use crate::{storage::SkinHandler, util::mojang::MojangClient};
+use crate::{
+    storage::SkinHandler,
+    util::{mineskin::MineSkinClient, mojang::MojangClient},
+};
 
 /// A signed player skin.
 #[derive(
@@ -52,7 +57,7 @@ impl PlayerSkin {
         mojang: &MojangClient,
         skins: &SkinHandler,
     ) -> anyhow::Result<Option<Self>> {
-        if let Some(skin) = skins.find(uuid)? {
+        if let Some(skin) = skins.find(uuid).await? {
             info!("Returning cached skin");
             return Ok(Some(skin));
         }
@@ -77,21 +82,117 @@ impl PlayerSkin {
                 .as_str()
                 .with_context(|| format!("no signature on {property_object:?}"))?;
 
-            // Validate base64 encoding
-            general_purpose::STANDARD
-                .decode(textures)
-                .context("invalid texture value")?;
-            general_purpose::STANDARD
-                .decode(signature)
-                .context("invalid signature value")?;
-
-            let res = Self {
-                textures: textures.to_string(),
-                signature: signature.to_string(),
-            };
-            skins.insert(uuid, &res)?;
+            let res = Self::from_base64(textures, signature)?;
+            skins.insert(uuid, &res).await?;
             return Ok(Some(res));
         }
         Ok(None)
     }
+
+    /// Gets the skin of the player with the given username.
+    ///
+    /// # Returns
+    /// A `PlayerSkin` based on the username, or `None` if the player has no skin.
+    pub async fn from_username(
+        username: &str,
+        mojang: &MojangClient,
+        skins: &SkinHandler,
+    ) -> anyhow::Result<Option<Self>> {
+        let uuid = mojang.get_uuid(username).await?;
+        Self::from_uuid(uuid, mojang, skins).await
+    }
+
+    /// Gets a signed skin from the skin texture at `url` using [MineSkin](https://mineskin.org).
+    pub async fn from_url(url: &str, mineskin: &MineSkinClient) -> anyhow::Result<Self> {
+        let json_object = mineskin.generate_from_url(url).await?;
+        let texture = &json_object["data"]["texture"];
+
+        let textures = texture["value"]
+            .as_str()
+            .with_context(|| format!("no texture value on {json_object:?}"))?;
+        let signature = texture["signature"]
+            .as_str()
+            .with_context(|| format!("no texture signature on {json_object:?}"))?;
+
+        Self::from_base64(textures, signature)
+    }
+
+    /// Gets the skin from `source`.
+    ///
+    /// # Returns
+    /// The `PlayerSkin`, or `None` if `source` is a player without a skin.
+    pub async fn from_source(
+        source: &SkinSource,
+        mojang: &MojangClient,
+        mineskin: &MineSkinClient,
+        skins: &SkinHandler,
+    ) -> anyhow::Result<Option<Self>> {
+        match source {
+            SkinSource::Username(username) => Self::from_username(username, mojang, skins).await,
+            SkinSource::Url(url) => Self::from_url(url, mineskin).await.map(Some),
+        }
+    }
+
+    fn from_base64(textures: &str, signature: &str) -> anyhow::Result<Self> {
+        // Validate base64 encoding
+        general_purpose::STANDARD
+            .decode(textures)
+            .context("invalid texture value")?;
+        general_purpose::STANDARD
+            .decode(signature)
+            .context("invalid signature value")?;
+
+        Ok(Self {
+            textures: textures.to_string(),
+            signature: signature.to_string(),
+        })
+    }
+}
+
+/// Where to get a [`PlayerSkin`] from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkinSource {
+    /// The skin of the player with this username
+    Username(String),
+    /// A skin texture at this URL
+    Url(String),
+}
+
+impl SkinSource {
+    /// Parses a username or a URL. URLs must start with `http://` or `https://`.
+    #[must_use]
+    pub fn parse(input: &str) -> Self {
+        if input.starts_with("http://") || input.starts_with("https://") {
+            Self::Url(input.to_string())
+        } else {
+            Self::Username(input.to_string())
+        }
+    }
+}
+
+impl fmt::Display for SkinSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Username(value) | Self::Url(value) => f.write_str(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skin_source() {
+        assert_eq!(
+            SkinSource::parse("Emerald_Explorer"),
+            SkinSource::Username(String::from("Emerald_Explorer"))
+        );
+        assert_eq!(
+            SkinSource::parse("https://textures.minecraft.net/texture/1510e3"),
+            SkinSource::Url(String::from(
+                "https://textures.minecraft.net/texture/1510e3"
+            ))
+        );
+    }
 }
@@ -0,0 +1,315 @@
+//! Vanilla statistics, which players can see in the statistics screen.
+//!
+//! [`Statistics`] tracks the blocks a player has mined, their deaths, play time and the distance
+//! they have walked. It is loaded from the [`Storage`] when a player joins and stored again when
+//! they leave.
+
+use std::collections::BTreeMap;
+
+use bevy::{ecs::world::OnDespawn, prelude::*};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use valence_protocol::{VarInt, packets::play::client_status_c2s::ClientStatusC2s};
+
+use crate::{
+    command_channel::CommandChannel,
+    ingress,
+    net::{
+        Compose, ConnectionId,
+        packets::{Statistic, StatisticsS2c},
+    },
+    runtime::AsyncRuntime,
+    simulation::{
+        MovementTracking, Position, Uuid,
+        blocks::Blocks,
+        event::{self, Cancellable, DestroyBlock},
+        metadata::living_entity::Health,
+        packet::play,
+    },
+    storage::Storage,
+    timings::timed,
+};
+
+/// The table which statistics are stored in, keyed by player uuid
+const TABLE: &str = "uuid-to-statistics";
+
+/// Statistic categories from the vanilla registry
+const MINED: i32 = 0;
+const CUSTOM: i32 = 8;
+
+/// Custom statistics from the vanilla registry
+const PLAY_TIME: i32 = 1;
+const WALK_ONE_CM: i32 = 6;
+const SPRINT_ONE_CM: i32 = 8;
+const DEATHS: i32 = 30;
+
+/// Movements longer than this many blocks in one tick are teleports, which do not count as
+/// walking
+const MAX_WALK_DISTANCE: f32 = 10.0;
+
+#[derive(
+    Component,
+    Serialize,
+    Deserialize,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq
+)]
+pub struct Statistics {
+    /// How many blocks of each kind were mined, keyed by the raw block kind
+    pub mined: BTreeMap<u16, u32>,
+    pub deaths: u32,
+    /// Play time in ticks
+    pub play_time: u32,
+    pub walk_one_cm: u32,
+    pub sprint_one_cm: u32,
+}
+
+impl Statistics {
+    /// The statistics in the format of [`StatisticsS2c`]
+    #[must_use]
+    pub fn to_packet(&self) -> StatisticsS2c {
+        let statistic = |category_id, statistic_id, value: u32| Statistic {
+            category_id: VarInt(category_id),
+            statistic_id: VarInt(statistic_id),
+            value: VarInt(i32::try_from(value).unwrap_or(i32::MAX)),
+        };
+
+        let mined = self
+            .mined
+            .iter()
+            .map(|(&kind, &count)| statistic(MINED, i32::from(kind), count));
+
+        let custom = [
+            (PLAY_TIME, self.play_time),
+            (WALK_ONE_CM, self.walk_one_cm),
+            (SPRINT_ONE_CM, self.sprint_one_cm),
+            (DEATHS, self.deaths),
+        ]
+        .into_iter()
+        .map(|(id, value)| statistic(CUSTOM, id, value));
+
+        StatisticsS2c {
+            statistics: mined.chain(custom).collect(),
+        }
+    }
+}
+
+/// What [`Statistics`] was last updated from
+#[derive(Component, Copy, Clone, Debug, Default)]
+struct LastTracked {
+    position: Option<Vec3>,
+    dead: bool,
+}
+
+fn load_statistics(
+    trigger: Trigger<'_, OnAdd, Uuid>,
+    query: Query<'_, '_, &Uuid, With<ConnectionId>>,
+    storage: Res<'_, Storage>,
+    runtime: Res<'_, AsyncRuntime>,
+    command_channel: Res<'_, CommandChannel>,
+) {
+    let Ok(uuid) = query.get(trigger.target()) else {
+        return;
+    };
+
+    let key = uuid.as_u128().to_ne_bytes();
+    let entity = trigger.target();
+    let storage = storage.clone();
+    let command_channel = command_channel.clone();
+
+    runtime.spawn(async move {
+        let statistics = match storage.get(TABLE, &key).await {
+            Ok(Some(value)) => serde_json::from_slice(&value).unwrap_or_else(|e| {
+                error!("failed to load statistics: invalid statistics: {e}");
+                Statistics::default()
+            }),
+            Ok(None) => Statistics::default(),
+            Err(e) => {
+                error!("failed to load statistics: {e}");
+                Statistics::default()
+            }
+        };
+
+        command_channel.push(move |world: &mut World| {
+            // The player may have left while the statistics were loading
+            if let Ok(mut entity) = world.get_entity_mut(entity) {
+                entity.insert((statistics, LastTracked::default()));
+            }
+        });
+    });
+}
+
+fn store_statistics(
+    trigger: Trigger<'_, OnDespawn, Statistics>,
+    query: Query<'_, '_, (&Uuid, &Statistics)>,
+    storage: Res<'_, Storage>,
+    runtime: Res<'_, AsyncRuntime>,
+) {
+    let (uuid, statistics) = match query.get(trigger.target()) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("failed to store statistics: query failed: {e}");
+            return;
+        }
+    };
+
+    let key = uuid.as_u128().to_ne_bytes();
+    let value = match serde_json::to_vec(statistics) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("failed to store statistics: {e}");
+            return;
+        }
+    };
+    let storage = storage.clone();
+
+    runtime.spawn(async move {
+        if let Err(e) = storage.put(TABLE, &key, &value).await {
+            error!("failed to store statistics: {e}");
+        }
+    });
+}
+
+fn track_statistics(
+    mut query: Query<
+        '_,
+        '_,
+        (
+            &mut Statistics,
+            &mut LastTracked,
+            &Position,
+            &MovementTracking,
+            Option<&Health>,
+        ),
+    >,
+) {
+    for (mut statistics, mut last, position, tracking, health) in &mut query {
+        statistics.play_time = statistics.play_time.saturating_add(1);
+
+        let dead = health.is_some_and(Health::is_dead);
+        if dead && !last.dead {
+            statistics.deaths = statistics.deaths.saturating_add(1);
+        }
+        last.dead = dead;
+
+        let position = **position;
+        if let Some(previous) = last.position.replace(position) {
+            let distance = (position - previous).with_y(0.0).length();
+
+            if !dead && tracking.was_on_ground && distance < MAX_WALK_DISTANCE {
+                #[expect(
+                    clippy::cast_possible_truncation,
+                    clippy::cast_sign_loss,
+                    reason = "the distance is positive and less than MAX_WALK_DISTANCE"
+                )]
+                let cm = (distance * 100.0).round() as u32;
+
+                let walked = if tracking.sprinting {
+                    &mut statistics.sprint_one_cm
+                } else {
+                    &mut statistics.walk_one_cm
+                };
+                *walked = walked.saturating_add(cm);
+            }
+        }
+    }
+}
+
+fn track_mined_blocks(
+    mut events: EventReader<'_, '_, Cancellable<DestroyBlock>>,
+    blocks: Res<'_, Blocks>,
+    mut query: Query<'_, '_, &mut Statistics>,
+) {
+    for event in events.read() {
+        if event.is_cancelled() {
+            continue;
+        }
+
+        let Ok(mut statistics) = query.get_mut(event.from) else {
+            continue;
+        };
+
+        let Some(block) = blocks.get_block(event.position) else {
+            continue;
+        };
+
+        if block.is_air() {
+            continue;
+        }
+
+        let count = statistics
+            .mined
+            .entry(block.to_kind().to_raw())
+            .or_default();
+        *count = count.saturating_add(1);
+    }
+}
+
+fn send_statistics(
+    mut packets: EventReader<'_, '_, play::ClientStatus>,
+    query: Query<'_, '_, (&Statistics, &ConnectionId)>,
+    compose: Res<'_, Compose>,
+) {
+    for packet in packets.read() {
+        if !matches!(**packet, ClientStatusC2s::RequestStats) {
+            continue;
+        }
+
+        // Statistics may still be loading
+        let Ok((statistics, &connection_id)) = query.get(packet.sender()) else {
+            continue;
+        };
+
+        if let Err(e) = compose.unicast(&statistics.to_packet(), connection_id) {
+            error!("failed to send statistics: {e}");
+        }
+    }
+}
+
+pub struct StatisticsPlugin;
+
+impl Plugin for StatisticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(load_statistics);
+        app.add_observer(store_statistics);
+        app.add_systems(
+            FixedUpdate,
+            (
+                timed(track_statistics),
+                timed(track_mined_blocks).after(event::CancelEvents),
+                timed(send_statistics),
+            )
+                .after(ingress::decode::play),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_packet() {
+        let mut statistics = Statistics {
+            deaths: 2,
+            play_time: 100,
+            ..Default::default()
+        };
+        statistics.mined.insert(1, 5);
+
+        let packet = statistics.to_packet();
+        assert!(packet.statistics.contains(&Statistic {
+            category_id: VarInt(MINED),
+            statistic_id: VarInt(1),
+            value: VarInt(5),
+        }));
+        assert!(packet.statistics.contains(&Statistic {
+            category_id: VarInt(CUSTOM),
+            statistic_id: VarInt(DEATHS),
+            value: VarInt(2),
+        }));
+    }
+}
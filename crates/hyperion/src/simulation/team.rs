@@ -0,0 +1,543 @@
+//! Name tags, glowing outlines and ranks.
+//!
+//! The color of a glowing outline, whether a player's name tag is shown and the prefix, suffix and
+//! position of a player in the player list all come from the scoreboard team of the entity, so
+//! every combination which is used gets its own team. Players without a [`NameTag`], [`Glowing`]
+//! or [`ListRank`] stay in the `no_tag` team, which hides their name tag.
+//!
+//! ```ignore
+//! commands.entity(zombie).insert((
+//!     NameTag::visible("§cBoss"),
+//!     Glowing(TeamColor::Red),
+//! ));
+//!
+//! commands
+//!     .entity(player)
+//!     .insert(ListRank::new("admin", 100).prefix("§c[Admin] "));
+//! ```
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
+
+use bevy::prelude::*;
+use tracing::error;
+use valence_bytes::CowUtf8Bytes;
+use valence_protocol::packets::play::{
+    self,
+    team_s2c::{CollisionRule, Mode, NameTagVisibility, TeamColor, TeamFlags},
+};
+use valence_text::{IntoText, Text};
+
+use crate::{
+    net::{Compose, ConnectionId},
+    simulation::{
+        Uuid,
+        entity_kind::EntityKind,
+        metadata::entity::{CustomName, CustomNameVisible, EntityFlags},
+        packet_state,
+        player_list::{self, Nickname, profile_name},
+    },
+    timings::timed,
+};
+
+/// The name shown above an entity. Players always show their username or
+/// [`Nickname`], so only `visible` is used for them.
+#[derive(Component, Clone, Debug, Default, PartialEq)]
+pub struct NameTag {
+    pub name: Option<Text>,
+    /// Whether the name is shown without looking at the entity
+    pub visible: bool,
+}
+
+impl NameTag {
+    /// A name which is always shown
+    #[must_use]
+    pub fn visible(name: impl IntoText<'static>) -> Self {
+        Self {
+            name: Some(name.into_text()),
+            visible: true,
+        }
+    }
+}
+
+/// Makes an entity glow with an outline of this color
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Glowing(pub TeamColor);
+
+/// Shows a prefix and suffix around the name of a player in the player list and above their head,
+/// and lists players with a higher `weight` first.
+///
+/// Players with the same rank `name` share a team, so they must have the same prefix and suffix.
+/// Inserting a rank with a different prefix or suffix updates them for every player in the rank.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct ListRank {
+    pub name: String,
+    pub weight: u16,
+    pub prefix: Text,
+    pub suffix: Text,
+}
+
+impl ListRank {
+    #[must_use]
+    pub fn new(name: impl Into<String>, weight: u16) -> Self {
+        Self {
+            name: name.into(),
+            weight,
+            prefix: Text::default(),
+            suffix: Text::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn prefix(mut self, prefix: impl IntoText<'static>) -> Self {
+        self.prefix = prefix.into_text();
+        self
+    }
+
+    #[must_use]
+    pub fn suffix(mut self, suffix: impl IntoText<'static>) -> Self {
+        self.suffix = suffix.into_text();
+        self
+    }
+}
+
+/// How the members of a team are shown
+#[derive(Clone, Debug, PartialEq)]
+struct TeamStyle {
+    color: TeamColor,
+    name_tag_visibility: NameTagVisibility,
+    prefix: Text,
+    suffix: Text,
+}
+
+impl TeamStyle {
+    fn new(color: TeamColor, name_tag_visibility: NameTagVisibility) -> Self {
+        Self {
+            color,
+            name_tag_visibility,
+            prefix: Text::default(),
+            suffix: Text::default(),
+        }
+    }
+}
+
+/// A team which entities are put in to style them
+#[derive(Debug)]
+struct StyleTeam {
+    style: TeamStyle,
+    /// The usernames of players and the uuids of other entities in the team
+    members: BTreeSet<String>,
+}
+
+impl StyleTeam {
+    fn create_packet(&self, name: &str) -> play::TeamS2c<'_> {
+        play::TeamS2c {
+            team_name: CowUtf8Bytes::Borrowed(name),
+            mode: Mode::CreateTeam {
+                team_display_name: Cow::default(),
+                friendly_flags: TeamFlags::default(),
+                name_tag_visibility: self.style.name_tag_visibility,
+                collision_rule: CollisionRule::Always,
+                team_color: self.style.color,
+                team_prefix: Cow::Borrowed(&self.style.prefix),
+                team_suffix: Cow::Borrowed(&self.style.suffix),
+                entities: self
+                    .members
+                    .iter()
+                    .map(|member| CowUtf8Bytes::Borrowed(member.as_str()))
+                    .collect(),
+            },
+        }
+    }
+
+    fn update_packet(&self, name: &str) -> play::TeamS2c<'_> {
+        play::TeamS2c {
+            team_name: CowUtf8Bytes::Borrowed(name),
+            mode: Mode::UpdateTeamInfo {
+                team_display_name: Cow::default(),
+                friendly_flags: TeamFlags::default(),
+                name_tag_visibility: self.style.name_tag_visibility,
+                collision_rule: CollisionRule::Always,
+                team_color: self.style.color,
+                team_prefix: Cow::Borrowed(&self.style.prefix),
+                team_suffix: Cow::Borrowed(&self.style.suffix),
+            },
+        }
+    }
+}
+
+/// The teams which have been created for styling entities, by team name
+#[derive(Resource, Debug, Default)]
+struct StyleTeams {
+    teams: BTreeMap<String, StyleTeam>,
+}
+
+/// The style team an entity is in and the name it is in the team as
+#[derive(Component, Debug)]
+struct TeamMember {
+    team: String,
+    member: String,
+}
+
+fn sync_name_tags(
+    mut query: Query<'_, '_, (&NameTag, &mut CustomName, &mut CustomNameVisible), Changed<NameTag>>,
+) {
+    for (tag, mut custom_name, mut custom_name_visible) in &mut query {
+        **custom_name = tag.name.clone();
+        **custom_name_visible = tag.visible;
+    }
+}
+
+fn add_glowing(trigger: Trigger<'_, OnAdd, Glowing>, mut query: Query<'_, '_, &mut EntityFlags>) {
+    if let Ok(mut flags) = query.get_mut(trigger.target()) {
+        *flags |= EntityFlags::GLOWING;
+    }
+}
+
+fn remove_glowing(
+    trigger: Trigger<'_, OnRemove, Glowing>,
+    mut query: Query<'_, '_, &mut EntityFlags>,
+) {
+    if let Ok(mut flags) = query.get_mut(trigger.target()) {
+        *flags &= !EntityFlags::GLOWING;
+    }
+}
+
+/// Resets the custom name of entities whose [`NameTag`] was removed
+fn remove_name_tag(
+    trigger: Trigger<'_, OnRemove, NameTag>,
+    mut query: Query<'_, '_, (&mut CustomName, &mut CustomNameVisible)>,
+) {
+    if let Ok((mut custom_name, mut custom_name_visible)) = query.get_mut(trigger.target()) {
+        **custom_name = None;
+        **custom_name_visible = false;
+    }
+}
+
+/// The name and style of the team an entity should be in, or `None` if it does not need a style
+/// team
+fn style_team(
+    kind: EntityKind,
+    glowing: Option<&Glowing>,
+    tag: Option<&NameTag>,
+    rank: Option<&ListRank>,
+) -> Option<(String, TeamStyle)> {
+    let glowing = glowing.map(|glowing| glowing.0);
+
+    // The name tags of other entities are shown through their metadata
+    if kind != EntityKind::Player {
+        return glowing.map(|color| {
+            (
+                format!("hyperion:{color:?}"),
+                TeamStyle::new(color, NameTagVisibility::Always),
+            )
+        });
+    }
+
+    let visible = tag.is_some_and(|tag| tag.visible);
+
+    // The player list is sorted by team name, so ranked teams start with their inverted weight
+    if let Some(rank) = rank {
+        let glow = glowing
+            .map(|color| format!(":{color:?}"))
+            .unwrap_or_default();
+        let no_tag = if visible { "" } else { ":no_tag" };
+        let name = format!("{:05}:{}{glow}{no_tag}", u16::MAX - rank.weight, rank.name);
+
+        let style = TeamStyle {
+            prefix: rank.prefix.clone(),
+            suffix: rank.suffix.clone(),
+            ..TeamStyle::new(
+                glowing.unwrap_or(TeamColor::White),
+                if visible {
+                    NameTagVisibility::Always
+                } else {
+                    NameTagVisibility::Never
+                },
+            )
+        };
+
+        return Some((name, style));
+    }
+
+    match (glowing, visible) {
+        (None, false) => None,
+        (None, true) => Some((
+            "hyperion:name_tag".to_owned(),
+            TeamStyle::new(TeamColor::White, NameTagVisibility::Always),
+        )),
+        (Some(color), true) => Some((
+            format!("hyperion:{color:?}"),
+            TeamStyle::new(color, NameTagVisibility::Always),
+        )),
+        (Some(color), false) => Some((
+            format!("hyperion:{color:?}:no_tag"),
+            TeamStyle::new(color, NameTagVisibility::Never),
+        )),
+    }
+}
+
+/// Creates the team `name` if it does not exist yet, or updates its style if it changed
+fn ensure_team(
+    name: &str,
+    style: TeamStyle,
+    teams: &mut StyleTeams,
+    compose: &Compose,
+) -> anyhow::Result<()> {
+    match teams.teams.get_mut(name) {
+        Some(team) if team.style == style => {}
+        Some(team) => {
+            team.style = style;
+            compose.broadcast(&team.update_packet(name)).send()?;
+        }
+        None => {
+            let team = StyleTeam {
+                style,
+                members: BTreeSet::new(),
+            };
+
+            compose.broadcast(&team.create_packet(name)).send()?;
+            teams.teams.insert(name.to_owned(), team);
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves `member` from its current team to `team`, creating the team if it does not exist yet.
+/// Players without a style team go back to `no_tag`.
+fn change_team(
+    member: String,
+    is_player: bool,
+    current: Option<&TeamMember>,
+    team: Option<(String, TeamStyle)>,
+    teams: &mut StyleTeams,
+    compose: &Compose,
+) -> anyhow::Result<Option<TeamMember>> {
+    if let Some(current) = current {
+        if let Some(old) = teams.teams.get_mut(&current.team) {
+            old.members.remove(&current.member);
+        }
+
+        compose
+            .broadcast(&play::TeamS2c {
+                team_name: CowUtf8Bytes::Borrowed(&current.team),
+                mode: Mode::RemoveEntities {
+                    entities: vec![CowUtf8Bytes::Borrowed(&current.member)],
+                },
+            })
+            .send()?;
+    }
+
+    let Some((team_name, style)) = team else {
+        if is_player {
+            compose
+                .broadcast(&play::TeamS2c {
+                    team_name: CowUtf8Bytes::Borrowed("no_tag"),
+                    mode: Mode::AddEntities {
+                        entities: vec![CowUtf8Bytes::Borrowed(&member)],
+                    },
+                })
+                .send()?;
+        }
+
+        return Ok(None);
+    };
+
+    ensure_team(&team_name, style, teams, compose)?;
+
+    if let Some(team) = teams.teams.get_mut(&team_name) {
+        team.members.insert(member.clone());
+    }
+
+    compose
+        .broadcast(&play::TeamS2c {
+            team_name: CowUtf8Bytes::Borrowed(&team_name),
+            mode: Mode::AddEntities {
+                entities: vec![CowUtf8Bytes::Borrowed(&member)],
+            },
+        })
+        .send()?;
+
+    Ok(Some(TeamMember {
+        team: team_name,
+        member,
+    }))
+}
+
+fn sync_teams(
+    query: Query<
+        '_,
+        '_,
+        (
+            &EntityKind,
+            &Uuid,
+            Option<&Name>,
+            Option<&Nickname>,
+            Option<&Glowing>,
+            Option<&NameTag>,
+            Option<&ListRank>,
+            Option<&TeamMember>,
+        ),
+    >,
+    changed: Query<
+        '_,
+        '_,
+        Entity,
+        Or<(
+            Changed<Glowing>,
+            Changed<NameTag>,
+            Changed<ListRank>,
+            Changed<Nickname>,
+        )>,
+    >,
+    mut removed_glowing: RemovedComponents<'_, '_, Glowing>,
+    mut removed_tags: RemovedComponents<'_, '_, NameTag>,
+    mut removed_ranks: RemovedComponents<'_, '_, ListRank>,
+    mut removed_nicknames: RemovedComponents<'_, '_, Nickname>,
+    mut teams: ResMut<'_, StyleTeams>,
+    compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
+) {
+    let entities: BTreeSet<Entity> = changed
+        .iter()
+        .chain(removed_glowing.read())
+        .chain(removed_tags.read())
+        .chain(removed_ranks.read())
+        .chain(removed_nicknames.read())
+        .collect();
+
+    for entity in entities {
+        let Ok((&kind, uuid, name, nickname, glowing, tag, rank, current)) = query.get(entity)
+        else {
+            // The entity was despawned
+            continue;
+        };
+
+        let team = style_team(kind, glowing, tag, rank);
+
+        // Players are team members by the name in their game profile
+        let is_player = kind == EntityKind::Player;
+        let member = match name {
+            Some(name) if is_player => profile_name(name, nickname).to_owned(),
+            _ => uuid.0.to_string(),
+        };
+
+        if let (Some(current), Some((team_name, style))) = (current, &team)
+            && current.team == *team_name
+            && current.member == member
+        {
+            // The prefix or suffix of the rank may have changed
+            if let Err(e) = ensure_team(team_name, style.clone(), &mut teams, &compose) {
+                error!("failed to update team {team_name}: {e}");
+            }
+            continue;
+        }
+
+        if current.is_none() && team.is_none() {
+            continue;
+        }
+
+        match change_team(member, is_player, current, team, &mut teams, &compose) {
+            Ok(Some(member)) => {
+                commands.entity(entity).insert(member);
+            }
+            Ok(None) => {
+                commands.entity(entity).remove::<TeamMember>();
+            }
+            Err(e) => error!("failed to change team of entity: {e}"),
+        }
+    }
+}
+
+/// Sends the style teams to players who join, after they were put in `no_tag`
+fn send_teams(
+    trigger: Trigger<'_, OnAdd, packet_state::Play>,
+    query: Query<'_, '_, (&ConnectionId, Option<&TeamMember>)>,
+    teams: Res<'_, StyleTeams>,
+    compose: Res<'_, Compose>,
+) {
+    let Ok((&connection_id, member)) = query.get(trigger.target()) else {
+        return;
+    };
+
+    for (name, team) in &teams.teams {
+        if let Err(e) = compose.unicast(&team.create_packet(name), connection_id) {
+            error!("failed to send team {name}: {e}");
+        }
+    }
+
+    // Other players were told that this player is in `no_tag` when they joined
+    if let Some(member) = member {
+        let packet = play::TeamS2c {
+            team_name: CowUtf8Bytes::Borrowed(&member.team),
+            mode: Mode::AddEntities {
+                entities: vec![CowUtf8Bytes::Borrowed(&member.member)],
+            },
+        };
+
+        if let Err(e) = compose.broadcast(&packet).send() {
+            error!("failed to send team of joining player: {e}");
+        }
+    }
+}
+
+/// Removes entities from their team when they are despawned
+fn remove_member(
+    trigger: Trigger<'_, OnRemove, TeamMember>,
+    query: Query<'_, '_, &TeamMember>,
+    mut teams: ResMut<'_, StyleTeams>,
+) {
+    let Ok(member) = query.get(trigger.target()) else {
+        return;
+    };
+
+    if let Some(team) = teams.teams.get_mut(&member.team) {
+        team.members.remove(&member.member);
+    }
+}
+
+pub struct TeamPlugin;
+
+impl Plugin for TeamPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StyleTeams>();
+        app.add_observer(add_glowing);
+        app.add_observer(remove_glowing);
+        app.add_observer(remove_name_tag);
+        app.add_observer(send_teams);
+        app.add_observer(remove_member);
+        app.add_systems(FixedUpdate, timed(sync_name_tags));
+        // Players whose nickname changed are added to `no_tag` again before moving to their team
+        app.add_systems(
+            FixedPostUpdate,
+            timed(sync_teams).after(player_list::sync_nicknames),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranks_sort_by_weight() {
+        let team_name = |rank: &ListRank| {
+            style_team(EntityKind::Player, None, None, Some(rank))
+                .map(|(name, _)| name)
+                .unwrap()
+        };
+
+        let admin = team_name(&ListRank::new("admin", 100));
+        let vip = team_name(&ListRank::new("vip", 10));
+        let member = team_name(&ListRank::new("member", 0));
+
+        assert!(admin < vip);
+        assert!(vip < member);
+        // Players without a rank are listed last
+        assert!(member.as_str() < "hyperion:name_tag");
+        assert!(member.as_str() < "no_tag");
+    }
+}
@@ -0,0 +1,139 @@
+//! Totems of undying, which save players from lethal damage.
+//!
+//! Game modes call [`try_use_totem`] after applying damage and before showing the death screen.
+
+use bevy::prelude::*;
+use hyperion_inventory::PlayerInventory;
+use hyperion_utils::EntityExt;
+use tracing::error;
+use valence_generated::item::ItemKind;
+use valence_protocol::{
+    ItemStack, VarInt,
+    packets::play::{
+        EntityStatusEffectS2c, EntityStatusS2c, RemoveEntityStatusEffectS2c,
+        entity_status_effect_s2c::Flags,
+    },
+};
+
+use crate::{
+    net::Compose,
+    simulation::{blocks::breaking::Haste, metadata::living_entity::Health},
+};
+
+/// The entity status which plays the totem animation and sound
+const TOTEM_STATUS: u8 = 35;
+
+/// The id of the haste effect, which is the only effect tracked by hyperion
+const HASTE_EFFECT: i32 = 3;
+
+/// The effects given by a totem in vanilla as `(effect id, amplifier, duration in ticks)`.
+/// These are regeneration II for 45 seconds, absorption II for 5 seconds and fire resistance for
+/// 40 seconds.
+const TOTEM_EFFECTS: [(i32, u8, i32); 3] = [(10, 1, 900), (22, 1, 100), (12, 0, 800)];
+
+/// Removes one totem of undying from the main hand or otherwise the off hand. Returns false if the
+/// player is not holding a totem.
+pub fn consume_totem(inventory: &mut PlayerInventory) -> bool {
+    for index in [inventory.get_cursor_index(), PlayerInventory::OFFHAND_SLOT] {
+        let Ok(slot) = inventory.get_mut_maybe_change(index) else {
+            continue;
+        };
+
+        if slot.stack.item != ItemKind::TotemOfUndying {
+            continue;
+        }
+
+        slot.stack.count -= 1;
+        if slot.stack.count <= 0 {
+            slot.stack = ItemStack::EMPTY;
+        }
+        slot.changed = true;
+
+        return true;
+    }
+
+    false
+}
+
+/// Saves a dead player if they hold a totem of undying in either hand.
+///
+/// The totem is consumed, the player is left with 1 health, their effects are replaced with the
+/// vanilla totem effects and the totem animation is shown to everyone. The totem effects are only
+/// shown to the player, so game modes which want them to have an effect must apply them.
+///
+/// Returns true if the player was saved.
+pub fn try_use_totem(
+    compose: &Compose,
+    commands: &mut Commands<'_, '_>,
+    player: Entity,
+    health: &mut Health,
+    inventory: &mut PlayerInventory,
+) -> bool {
+    if !health.is_dead() || !consume_totem(inventory) {
+        return false;
+    }
+
+    health.heal(1.0);
+
+    let entity_id = VarInt(player.minecraft_id());
+
+    commands.entity(player).remove::<Haste>();
+
+    let pkt = RemoveEntityStatusEffectS2c {
+        entity_id,
+        effect_id: VarInt(HASTE_EFFECT),
+    };
+    if let Err(e) = compose.broadcast(&pkt).send() {
+        error!("failed to send totem effect: {e}");
+    }
+
+    for (effect_id, amplifier, duration) in TOTEM_EFFECTS {
+        let pkt = EntityStatusEffectS2c {
+            entity_id,
+            effect_id: VarInt(effect_id),
+            amplifier,
+            duration: VarInt(duration),
+            flags: Flags::new().with_show_particles(true).with_show_icon(true),
+            factor_codec: None,
+        };
+        if let Err(e) = compose.broadcast(&pkt).send() {
+            error!("failed to send totem effect: {e}");
+        }
+    }
+
+    let pkt = EntityStatusS2c {
+        entity_id: entity_id.0,
+        entity_status: TOTEM_STATUS,
+    };
+    if let Err(e) = compose.broadcast(&pkt).send() {
+        error!("failed to send totem effect: {e}");
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consume_totem() {
+        let mut inventory = PlayerInventory::default();
+        assert!(!consume_totem(&mut inventory));
+
+        inventory.set_offhand(ItemStack::new(ItemKind::TotemOfUndying, 1, None));
+        inventory
+            .set_hotbar(0, ItemStack::new(ItemKind::TotemOfUndying, 2, None))
+            .unwrap();
+
+        // The main hand is used first
+        assert!(consume_totem(&mut inventory));
+        assert_eq!(inventory.get_cursor().stack.count, 1);
+        assert!(consume_totem(&mut inventory));
+        assert!(inventory.get_cursor().stack.is_empty());
+
+        assert!(consume_totem(&mut inventory));
+        assert!(inventory.get_offhand().stack.is_empty());
+        assert!(!consume_totem(&mut inventory));
+    }
+}
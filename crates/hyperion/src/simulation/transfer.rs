@@ -0,0 +1,143 @@
+//! Moving players to other servers in the same network.
+//!
+//! ```ignore
+//! commands.entity(player).transfer("10.0.0.2:35565");
+//! ```
+//!
+//! Minecraft 1.20.1 has no transfer packet, so transfers are done by the proxy. The proxy hands
+//! the connection to the other server along with the player's name, UUID and skin, so the player
+//! does not need to log in again. The player entity on this server is despawned once the proxy has
+//! moved the connection. If the transfer fails, the player stays on this server as if nothing had
+//! happened.
+
+use std::borrow::Cow;
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use hyperion_proto::PlayerProfile;
+use tracing::{error, warn};
+use valence_protocol::packets::play;
+
+use crate::{
+    net::{Compose, ConnectionId},
+    simulation::{Uuid, packet_state, skin::PlayerSkin},
+};
+
+/// Moves the target player to another server. The player must be in the play state.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct Transfer {
+    /// The address of the other server, such as `10.0.0.2:35565`. This must be the address which
+    /// the proxy uses to connect to the server.
+    pub server: String,
+}
+
+/// Sent to a player when their [`Transfer`] could not be completed, such as because the other
+/// server is offline. The player stays on this server.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct TransferFailed {
+    pub server: String,
+}
+
+/// Marks players who are being transferred to another server
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+pub struct Transferring {
+    pub server: String,
+}
+
+pub trait TransferExt {
+    /// Moves this player to another server by triggering [`Transfer`]
+    fn transfer(&mut self, server: impl Into<String>) -> &mut Self;
+}
+
+impl TransferExt for EntityCommands<'_> {
+    fn transfer(&mut self, server: impl Into<String>) -> &mut Self {
+        self.trigger(Transfer {
+            server: server.into(),
+        })
+    }
+}
+
+fn transfer_player(
+    trigger: Trigger<'_, Transfer>,
+    query: Query<
+        '_,
+        '_,
+        (&ConnectionId, &Name, &Uuid, &PlayerSkin, Has<Transferring>),
+        With<packet_state::Play>,
+    >,
+    others: Query<'_, '_, &Uuid, With<packet_state::Play>>,
+    compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
+) {
+    let player = trigger.target();
+
+    let (&connection_id, name, uuid, skin, transferring) = match query.get(player) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("failed to transfer player: query failed: {e}");
+            return;
+        }
+    };
+
+    if transferring {
+        warn!("{name} is already being transferred");
+        return;
+    }
+
+    // The other server resets the world with a new join packet, but the player list is kept by
+    // the client. The proxy only clears it once the other server has accepted the player, so the
+    // player list stays intact if the transfer fails.
+    let uuids = others.iter().map(|uuid| uuid.0).collect::<Vec<_>>();
+    let pkt = play::PlayerRemoveS2c {
+        uuids: Cow::Owned(uuids),
+    };
+    let clear_player_list = match compose.io_buf().encode_packet(&pkt, &compose) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("failed to transfer {name}: failed to encode player list: {e}");
+            return;
+        }
+    };
+
+    compose.io_buf().transfer(
+        connection_id,
+        &trigger.server,
+        PlayerProfile {
+            username: name.as_str(),
+            uuid: uuid.as_u128(),
+            textures: &skin.textures,
+            signature: &skin.signature,
+        },
+        &clear_player_list,
+    );
+
+    commands.entity(player).insert(Transferring {
+        server: trigger.server.clone(),
+    });
+}
+
+/// Called when the proxy reports that a transfer failed
+pub(crate) fn transfer_failed(world: &mut World, player: Entity) {
+    let Ok(mut entity) = world.get_entity_mut(player) else {
+        return;
+    };
+
+    let Some(transferring) = entity.take::<Transferring>() else {
+        warn!("proxy sent TransferFailed for a player who is not being transferred");
+        return;
+    };
+
+    world.trigger_targets(
+        TransferFailed {
+            server: transferring.server,
+        },
+        player,
+    );
+}
+
+pub struct TransferPlugin;
+
+impl Plugin for TransferPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(transfer_player);
+    }
+}
@@ -2,7 +2,8 @@ use std::sync::LazyLock;
 
 use anyhow::{Context, bail};
 use serde::Deserialize;
-use valence_nbt::{Compound, Value, value::ValueRef};
+use valence_nbt::{Compound, List, Value, value::ValueRef};
+use valence_protocol::ItemStack;
 use valence_registry::{
     BiomeRegistry,
     biome::{Biome, BiomeEffects},
@@ -24,6 +25,29 @@ pub fn registry_codec_raw() -> &'static Compound {
     &CACHED
 }
 
+/// The level of an enchantment on an item, or 0 if the item does not have the enchantment
+#[must_use]
+pub fn enchantment_level(stack: &ItemStack, id: &str) -> u8 {
+    let Some(Value::List(List::Compound(enchantments))) =
+        stack.nbt.as_ref().and_then(|nbt| nbt.get("Enchantments"))
+    else {
+        return 0;
+    };
+
+    enchantments
+        .iter()
+        .find(|enchantment| {
+            matches!(enchantment.get("id"), Some(Value::String(found)) if found == id)
+        })
+        .and_then(|enchantment| match enchantment.get("lvl") {
+            Some(Value::Short(level)) => u8::try_from(*level).ok(),
+            Some(Value::Int(level)) => u8::try_from(*level).ok(),
+            Some(Value::Byte(level)) => u8::try_from(*level).ok(),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
 pub fn generate_biome_registry() -> anyhow::Result<BiomeRegistry> {
     let registry_codec = registry_codec_raw();
 
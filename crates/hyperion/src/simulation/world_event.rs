@@ -0,0 +1,340 @@
+//! World events and game state changes.
+//!
+//! World events play vanilla sounds and particles at a block, such as anvils landing or blocks
+//! breaking. Game state changes affect a single player, such as starting rain or showing the
+//! elder guardian curse.
+//!
+//! ```ignore
+//! play_world_event(&compose, WorldEvent::BlockBreak(BlockState::STONE), position)?;
+//!
+//! commands
+//!     .entity(player)
+//!     .change_game_state(GameState::ElderGuardianCurse);
+//! ```
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use glam::{I16Vec2, IVec3};
+use tracing::error;
+use valence_generated::{block::BlockState, item::ItemKind};
+use valence_protocol::{
+    BlockPos, GameMode,
+    packets::play::{GameStateChangeS2c, WorldEventS2c, game_state_change_s2c::GameEventKind},
+};
+
+use crate::net::{Compose, ConnectionId, SendError};
+
+/// A sound or particle effect played at a block. Doors, trapdoors and fence gates have no world
+/// events in 1.20.1; their sounds are played with a sound packet instead.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WorldEvent {
+    DispenserDispenses,
+    DispenserFails,
+    DispenserShoots,
+    EnderEyeLaunched,
+    FireworkShot,
+    FireExtinguished,
+    /// Plays the music disc `item` as if it was inserted into a jukebox at the block
+    RecordPlayed(ItemKind),
+    /// Stops the music disc played at the block
+    RecordStopped,
+    GhastWarns,
+    GhastShoots,
+    EnderDragonShoots,
+    BlazeShoots,
+    ZombieAttacksWoodenDoor,
+    ZombieAttacksIronDoor,
+    ZombieBreaksWoodenDoor,
+    WitherBreaksBlock,
+    /// Heard by every player regardless of distance
+    WitherSpawned,
+    WitherShoots,
+    BatTakesOff,
+    ZombieInfects,
+    ZombieVillagerConverted,
+    /// Heard by every player regardless of distance
+    EnderDragonDeath,
+    AnvilDestroyed,
+    AnvilUsed,
+    AnvilLanded,
+    PortalTravel,
+    ChorusFlowerGrown,
+    ChorusFlowerDied,
+    BrewingStandBrewed,
+    /// Heard by every player regardless of distance
+    EndPortalCreated,
+    ComposterComposts,
+    LavaConvertsBlock,
+    RedstoneTorchBurnsOut,
+    EnderEyePlaced,
+    /// The particles and sound of bone meal. A count of 0 uses the default number of particles.
+    BoneMeal {
+        count: i32,
+    },
+    /// The particles and sound of breaking a block
+    BlockBreak(BlockState),
+    /// The particles of a splash potion with an RGB color
+    SplashPotion {
+        color: i32,
+    },
+    /// The particles of a lingering or instant splash potion with an RGB color
+    InstantSplashPotion {
+        color: i32,
+    },
+    EnderEyeBreaks,
+    MobSpawnParticles,
+    EnderDragonDestroysBlock,
+    WetSpongeVaporizes,
+    EndGatewaySpawn,
+    EnderDragonGrowl,
+    ElectricSpark,
+    WaxOn,
+    WaxOff,
+    Scrape,
+    /// Any other world event by its vanilla id and data
+    Other {
+        id: i32,
+        data: i32,
+    },
+}
+
+impl WorldEvent {
+    /// The vanilla id of the event
+    #[must_use]
+    pub const fn id(self) -> i32 {
+        match self {
+            Self::DispenserDispenses => 1000,
+            Self::DispenserFails => 1001,
+            Self::DispenserShoots => 1002,
+            Self::EnderEyeLaunched => 1003,
+            Self::FireworkShot => 1004,
+            Self::FireExtinguished => 1009,
+            Self::RecordPlayed(_) => 1010,
+            Self::RecordStopped => 1011,
+            Self::GhastWarns => 1015,
+            Self::GhastShoots => 1016,
+            Self::EnderDragonShoots => 1017,
+            Self::BlazeShoots => 1018,
+            Self::ZombieAttacksWoodenDoor => 1019,
+            Self::ZombieAttacksIronDoor => 1020,
+            Self::ZombieBreaksWoodenDoor => 1021,
+            Self::WitherBreaksBlock => 1022,
+            Self::WitherSpawned => 1023,
+            Self::WitherShoots => 1024,
+            Self::BatTakesOff => 1025,
+            Self::ZombieInfects => 1026,
+            Self::ZombieVillagerConverted => 1027,
+            Self::EnderDragonDeath => 1028,
+            Self::AnvilDestroyed => 1029,
+            Self::AnvilUsed => 1030,
+            Self::AnvilLanded => 1031,
+            Self::PortalTravel => 1032,
+            Self::ChorusFlowerGrown => 1033,
+            Self::ChorusFlowerDied => 1034,
+            Self::BrewingStandBrewed => 1035,
+            Self::EndPortalCreated => 1038,
+            Self::ComposterComposts => 1500,
+            Self::LavaConvertsBlock => 1501,
+            Self::RedstoneTorchBurnsOut => 1502,
+            Self::EnderEyePlaced => 1503,
+            Self::BoneMeal { .. } => 1505,
+            Self::BlockBreak(_) => 2001,
+            Self::SplashPotion { .. } => 2002,
+            Self::EnderEyeBreaks => 2003,
+            Self::MobSpawnParticles => 2004,
+            Self::InstantSplashPotion { .. } => 2007,
+            Self::EnderDragonDestroysBlock => 2008,
+            Self::WetSpongeVaporizes => 2009,
+            Self::EndGatewaySpawn => 3000,
+            Self::EnderDragonGrowl => 3001,
+            Self::ElectricSpark => 3002,
+            Self::WaxOn => 3003,
+            Self::WaxOff => 3004,
+            Self::Scrape => 3005,
+            Self::Other { id, .. } => id,
+        }
+    }
+
+    /// The data of the event, which is 0 for events without data
+    #[must_use]
+    pub fn data(self) -> i32 {
+        match self {
+            Self::BoneMeal { count } => count,
+            Self::RecordPlayed(item) => i32::from(item.to_raw()),
+            Self::BlockBreak(state) => i32::from(state.to_raw()),
+            Self::SplashPotion { color } | Self::InstantSplashPotion { color } => color,
+            Self::Other { data, .. } => data,
+            _ => 0,
+        }
+    }
+
+    /// Whether the event is heard by every player instead of only nearby players
+    #[must_use]
+    pub const fn is_global(self) -> bool {
+        matches!(
+            self,
+            Self::WitherSpawned | Self::EnderDragonDeath | Self::EndPortalCreated
+        )
+    }
+
+    #[must_use]
+    pub fn packet(self, position: IVec3) -> WorldEventS2c {
+        WorldEventS2c {
+            event: self.id(),
+            location: BlockPos::new(position.x, position.y, position.z),
+            data: self.data(),
+            disable_relative_volume: self.is_global(),
+        }
+    }
+}
+
+/// Plays a world event for the players near `position`, or for every player if the event is
+/// [global](WorldEvent::is_global)
+pub fn play_world_event(
+    compose: &Compose,
+    event: WorldEvent,
+    position: IVec3,
+) -> Result<(), SendError> {
+    let packet = event.packet(position);
+
+    if event.is_global() {
+        return compose.broadcast(&packet).send();
+    }
+
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "chunk positions in the world fit in an i16"
+    )]
+    let chunk = I16Vec2::new((position.x >> 4) as i16, (position.z >> 4) as i16);
+
+    compose.broadcast_local(&packet, chunk).send()
+}
+
+/// A change to the state of the game for one player
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameState {
+    /// Shows that the player has no respawn block or that it is obstructed
+    NoRespawnBlock,
+    BeginRaining,
+    EndRaining,
+    ChangeGameMode(GameMode),
+    /// Shows the end credits if `credits` is true, otherwise respawns the player
+    WinGame {
+        credits: bool,
+    },
+    /// Plays the sound of an arrow hitting a player
+    ArrowHitPlayer,
+    /// How strong the rain is, from 0.0 to 1.0
+    RainLevel(f32),
+    /// How strong the thunder is, from 0.0 to 1.0
+    ThunderLevel(f32),
+    PufferfishSting,
+    /// Shows the elder guardian and plays its curse sound
+    ElderGuardianCurse,
+    /// Whether the death screen is shown or the player respawns immediately
+    RespawnScreen(bool),
+}
+
+impl GameState {
+    #[must_use]
+    pub fn packet(self) -> GameStateChangeS2c {
+        let (kind, value) = match self {
+            Self::NoRespawnBlock => (GameEventKind::NoRespawnBlockAvailable, 0.0),
+            // The client starts the rain on event 1 and stops it on event 2, which valence names
+            // the other way around
+            Self::BeginRaining => (GameEventKind::EndRaining, 0.0),
+            Self::EndRaining => (GameEventKind::BeginRaining, 0.0),
+            Self::ChangeGameMode(game_mode) => {
+                (GameEventKind::ChangeGameMode, f32::from(game_mode as u8))
+            }
+            Self::WinGame { credits } => (GameEventKind::WinGame, f32::from(u8::from(credits))),
+            Self::ArrowHitPlayer => (GameEventKind::ArrowHitPlayer, 0.0),
+            Self::RainLevel(level) => (GameEventKind::RainLevelChange, level),
+            Self::ThunderLevel(level) => (GameEventKind::ThunderLevelChange, level),
+            Self::PufferfishSting => (GameEventKind::PlayPufferfishStingSound, 0.0),
+            Self::ElderGuardianCurse => (GameEventKind::PlayElderGuardianMobAppearance, 0.0),
+            // 0 shows the respawn screen and 1 respawns immediately
+            Self::RespawnScreen(enabled) => (
+                GameEventKind::EnableRespawnScreen,
+                f32::from(u8::from(!enabled)),
+            ),
+        };
+
+        GameStateChangeS2c { kind, value }
+    }
+}
+
+/// Changes the state of the game for the target player
+#[derive(Event, Copy, Clone, Debug, PartialEq)]
+pub struct ChangeGameState(pub GameState);
+
+/// The game mode of a player, which is kept up to date by [`GameState::ChangeGameMode`]. Players
+/// without it are in survival, which is the game mode they join in.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq, Deref)]
+pub struct PlayerGameMode(pub GameMode);
+
+pub trait GameStateExt {
+    /// Changes the state of the game for this player by triggering [`ChangeGameState`]
+    fn change_game_state(&mut self, state: GameState) -> &mut Self;
+}
+
+impl GameStateExt for EntityCommands<'_> {
+    fn change_game_state(&mut self, state: GameState) -> &mut Self {
+        self.trigger(ChangeGameState(state))
+    }
+}
+
+fn change_game_state(
+    trigger: Trigger<'_, ChangeGameState>,
+    query: Query<'_, '_, &ConnectionId>,
+    compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
+) {
+    let Ok(&connection_id) = query.get(trigger.target()) else {
+        return;
+    };
+
+    if let GameState::ChangeGameMode(game_mode) = trigger.event().0 {
+        commands
+            .entity(trigger.target())
+            .insert(PlayerGameMode(game_mode));
+    }
+
+    if let Err(e) = compose.unicast(&trigger.event().0.packet(), connection_id) {
+        error!("failed to change game state: {e}");
+    }
+}
+
+pub(crate) fn build(app: &mut App) {
+    app.add_observer(change_game_state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_break_data() {
+        let event = WorldEvent::BlockBreak(BlockState::STONE);
+        assert_eq!(event.id(), 2001);
+        assert_eq!(event.data(), i32::from(BlockState::STONE.to_raw()));
+        assert!(!event.packet(IVec3::ZERO).disable_relative_volume);
+    }
+
+    #[test]
+    fn test_respawn_screen() {
+        assert_eq!(
+            GameState::RespawnScreen(true).packet(),
+            GameStateChangeS2c {
+                kind: GameEventKind::EnableRespawnScreen,
+                value: 0.0,
+            }
+        );
+        assert_eq!(
+            GameState::RespawnScreen(false).packet(),
+            GameStateChangeS2c {
+                kind: GameEventKind::EnableRespawnScreen,
+                value: 1.0,
+            }
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use bevy::prelude::*;
+use bevy::{ecs::query::QueryFilter, prelude::*};
 use geometry::{aabb::Aabb, ray::Ray};
 use ordered_float::NotNan;
 use rayon::iter::Either;
@@ -74,6 +74,26 @@ impl SpatialIndex {
         self.query.range(target, get_aabb).copied()
     }
 
+    /// The entities which collide with `target` and for which `filter` returns true. The filter
+    /// is checked before the bounding box of an entity is looked up.
+    ///
+    /// ```ignore
+    /// let enemies = index.get_collisions_filtered(area, query, |entity| {
+    ///     team_query.get(entity).is_ok_and(|team| *team != attacker_team)
+    /// });
+    /// ```
+    pub fn get_collisions_filtered<'a>(
+        &'a self,
+        target: Aabb,
+        query: Query<'a, 'a, (&Position, &EntitySize)>,
+        filter: impl Fn(Entity) -> bool + 'a,
+    ) -> impl Iterator<Item = Entity> + 'a {
+        let get_aabb = get_aabb_func(query);
+        self.query
+            .range_filtered(target, get_aabb, move |&entity| filter(entity))
+            .copied()
+    }
+
     /// Get the closest player to the given position.
     #[must_use]
     pub fn closest_to(
@@ -85,6 +105,38 @@ impl SpatialIndex {
         Some(*self.query.get_closest(point, &get_aabb)?.0)
     }
 
+    /// Get the closest entity to the given position for which `filter` returns true, such as the
+    /// closest player of another team. Rejected entities are skipped while searching, so nearby
+    /// teammates do not make the search slower.
+    ///
+    /// ```ignore
+    /// let enemy_players = |entity| player_query.contains(entity) && !teammates.contains(entity);
+    /// let target = index.closest_to_filtered(position, query, enemy_players);
+    /// ```
+    #[must_use]
+    pub fn closest_to_filtered(
+        &self,
+        point: Vec3,
+        query: Query<'_, '_, (&Position, &EntitySize)>,
+        filter: impl Fn(Entity) -> bool,
+    ) -> Option<Entity> {
+        let get_aabb = get_aabb_func(query);
+        let filter = |&entity: &Entity| filter(entity);
+        Some(*self.query.get_closest_filtered(point, &get_aabb, filter)?.0)
+    }
+
+    /// Get the closest entity to the given position which matches the filter of `with`, such as
+    /// `Query<(), With<Player>>`
+    #[must_use]
+    pub fn closest_with<F: QueryFilter>(
+        &self,
+        point: Vec3,
+        query: Query<'_, '_, (&Position, &EntitySize)>,
+        with: &Query<'_, '_, (), F>,
+    ) -> Option<Entity> {
+        self.closest_to_filtered(point, query, |entity| with.contains(entity))
+    }
+
     #[must_use]
     pub fn first_ray_collision<'a>(
         &self,
@@ -95,6 +147,35 @@ impl SpatialIndex {
         let (entity, distance) = self.query.first_ray_collision(ray, get_aabb)?;
         Some((*entity, distance))
     }
+
+    /// The first entity hit by the ray before `max_distance`, which is in units of the length of
+    /// the ray direction
+    #[must_use]
+    pub fn first_ray_collision_within<'a>(
+        &self,
+        ray: Ray,
+        max_distance: f32,
+        query: Query<'a, 'a, (&Position, &EntitySize)>,
+    ) -> Option<(Entity, NotNan<f32>)> {
+        let get_aabb = get_aabb_func(query);
+        let (entity, distance) =
+            self.query
+                .first_ray_collision_within(ray, max_distance, get_aabb)?;
+        Some((*entity, distance))
+    }
+
+    /// Whether the ray hits any entity before `max_distance`. This is cheaper than
+    /// [`Self::first_ray_collision_within`] because it stops at the first hit.
+    #[must_use]
+    pub fn any_ray_collision(
+        &self,
+        ray: Ray,
+        max_distance: f32,
+        query: Query<'_, '_, (&Position, &EntitySize)>,
+    ) -> bool {
+        let get_aabb = get_aabb_func(query);
+        self.query.any_ray_collision(ray, max_distance, get_aabb)
+    }
 }
 
 fn recalculate_spatial_index(
@@ -0,0 +1,287 @@
+//! Storage backends for data which is shared between restarts or servers. See [`StorageBackend`].
+
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use bevy::prelude::*;
+use derive_more::Deref;
+use heed::{Database, types};
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+
+use crate::{runtime::AsyncRuntime, storage::LocalDb};
+
+/// The future returned by the methods of [`StorageBackend`]
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+/// A store of tables of binary keys and values.
+///
+/// Tables are created when they are first used. The embedded [`LocalDb`] is the default backend.
+/// With the `postgres` feature, `PostgresBackend` allows several servers to share state.
+pub trait StorageBackend: Send + Sync + 'static {
+    /// Returns the value for `key` in `table`.
+    fn get<'a>(&'a self, table: &'a str, key: &'a [u8]) -> BackendFuture<'a, Option<Vec<u8>>>;
+
+    /// Sets the value for `key` in `table`, replacing any previous value.
+    fn put<'a>(&'a self, table: &'a str, key: &'a [u8], value: &'a [u8]) -> BackendFuture<'a, ()>;
+
+    /// Removes the value for `key` in `table`. Returns whether there was a value.
+    fn delete<'a>(&'a self, table: &'a str, key: &'a [u8]) -> BackendFuture<'a, bool>;
+
+    /// Returns every entry in `table`, ordered by key.
+    fn entries<'a>(&'a self, table: &'a str) -> BackendFuture<'a, Vec<(Vec<u8>, Vec<u8>)>>;
+}
+
+/// The storage backend used by the server
+#[derive(Resource, Clone, Deref)]
+pub struct Storage(Arc<dyn StorageBackend>);
+
+impl Storage {
+    pub fn new(backend: impl StorageBackend) -> Self {
+        Self(Arc::new(backend))
+    }
+
+    /// Connects to the Postgres database at `url`, or uses `db` if there is no `url`
+    pub fn connect(
+        url: Option<&str>,
+        runtime: &AsyncRuntime,
+        db: &LocalDb,
+    ) -> anyhow::Result<Self> {
+        let Some(url) = url else {
+            return Ok(Self::new(EmbeddedBackend::new(db.clone())));
+        };
+
+        #[cfg(feature = "postgres")]
+        {
+            let backend = runtime.block_on(PostgresBackend::connect(url))?;
+            Ok(Self::new(backend))
+        }
+
+        #[cfg(not(feature = "postgres"))]
+        {
+            let _ = runtime;
+            anyhow::bail!(
+                "cannot connect to {url}: hyperion was built without the postgres feature"
+            )
+        }
+    }
+}
+
+/// The [`StorageBackend`] implementation of [`LocalDb`], where every table is a named database
+#[derive(Clone)]
+pub struct EmbeddedBackend {
+    db: LocalDb,
+    tables: Arc<Mutex<FxHashMap<String, Database<types::Bytes, types::Bytes>>>>,
+}
+
+impl EmbeddedBackend {
+    #[must_use]
+    pub fn new(db: LocalDb) -> Self {
+        Self {
+            db,
+            tables: Arc::default(),
+        }
+    }
+
+    fn table(&self, name: &str) -> anyhow::Result<Database<types::Bytes, types::Bytes>> {
+        let mut tables = self.tables.lock();
+
+        if let Some(table) = tables.get(name) {
+            return Ok(*table);
+        }
+
+        let mut wtxn = self.db.write_txn()?;
+        let table = self.db.create_database(&mut wtxn, Some(name))?;
+        wtxn.commit()?;
+
+        tables.insert(name.to_owned(), table);
+        Ok(table)
+    }
+
+    fn get_sync(&self, table: &str, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        let table = self.table(table)?;
+        let rtxn = self.db.read_txn()?;
+        Ok(table.get(&rtxn, key)?.map(<[u8]>::to_vec))
+    }
+
+    fn put_sync(&self, table: &str, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        let table = self.table(table)?;
+        let mut wtxn = self.db.write_txn()?;
+        table.put(&mut wtxn, key, value)?;
+        wtxn.commit()?;
+        Ok(())
+    }
+
+    fn delete_sync(&self, table: &str, key: &[u8]) -> anyhow::Result<bool> {
+        let table = self.table(table)?;
+        let mut wtxn = self.db.write_txn()?;
+        let deleted = table.delete(&mut wtxn, key)?;
+        wtxn.commit()?;
+        Ok(deleted)
+    }
+
+    fn entries_sync(&self, table: &str) -> anyhow::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let table = self.table(table)?;
+        let rtxn = self.db.read_txn()?;
+
+        table
+            .iter(&rtxn)?
+            .map(|entry| {
+                let (key, value) = entry?;
+                Ok((key.to_vec(), value.to_vec()))
+            })
+            .collect()
+    }
+}
+
+// LMDB operations are fast enough that they are run on the calling thread
+impl StorageBackend for EmbeddedBackend {
+    fn get<'a>(&'a self, table: &'a str, key: &'a [u8]) -> BackendFuture<'a, Option<Vec<u8>>> {
+        Box::pin(std::future::ready(self.get_sync(table, key)))
+    }
+
+    fn put<'a>(&'a self, table: &'a str, key: &'a [u8], value: &'a [u8]) -> BackendFuture<'a, ()> {
+        Box::pin(std::future::ready(self.put_sync(table, key, value)))
+    }
+
+    fn delete<'a>(&'a self, table: &'a str, key: &'a [u8]) -> BackendFuture<'a, bool> {
+        Box::pin(std::future::ready(self.delete_sync(table, key)))
+    }
+
+    fn entries<'a>(&'a self, table: &'a str) -> BackendFuture<'a, Vec<(Vec<u8>, Vec<u8>)>> {
+        Box::pin(std::future::ready(self.entries_sync(table)))
+    }
+}
+
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresBackend;
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    use sqlx::{PgPool, Row};
+
+    use super::{BackendFuture, StorageBackend};
+
+    /// A [`StorageBackend`] which stores every table in one Postgres table, so several servers
+    /// can share state
+    #[derive(Clone)]
+    pub struct PostgresBackend {
+        pool: PgPool,
+    }
+
+    impl PostgresBackend {
+        /// Connects to the database at `url` and creates the table used for storage if it does
+        /// not exist
+        pub async fn connect(url: &str) -> anyhow::Result<Self> {
+            let pool = PgPool::connect(url).await?;
+
+            sqlx::query(
+                "CREATE TABLE IF NOT EXISTS hyperion_storage (
+                    table_name TEXT NOT NULL,
+                    key BYTEA NOT NULL,
+                    value BYTEA NOT NULL,
+                    PRIMARY KEY (table_name, key)
+                )",
+            )
+            .execute(&pool)
+            .await?;
+
+            Ok(Self { pool })
+        }
+    }
+
+    impl StorageBackend for PostgresBackend {
+        fn get<'a>(&'a self, table: &'a str, key: &'a [u8]) -> BackendFuture<'a, Option<Vec<u8>>> {
+            Box::pin(async move {
+                let row = sqlx::query(
+                    "SELECT value FROM hyperion_storage WHERE table_name = $1 AND key = $2",
+                )
+                .bind(table)
+                .bind(key)
+                .fetch_optional(&self.pool)
+                .await?;
+
+                Ok(row.map(|row| row.get("value")))
+            })
+        }
+
+        fn put<'a>(
+            &'a self,
+            table: &'a str,
+            key: &'a [u8],
+            value: &'a [u8],
+        ) -> BackendFuture<'a, ()> {
+            Box::pin(async move {
+                sqlx::query(
+                    "INSERT INTO hyperion_storage (table_name, key, value) VALUES ($1, $2, $3)
+                     ON CONFLICT (table_name, key) DO UPDATE SET value = EXCLUDED.value",
+                )
+                .bind(table)
+                .bind(key)
+                .bind(value)
+                .execute(&self.pool)
+                .await?;
+
+                Ok(())
+            })
+        }
+
+        fn delete<'a>(&'a self, table: &'a str, key: &'a [u8]) -> BackendFuture<'a, bool> {
+            Box::pin(async move {
+                let result =
+                    sqlx::query("DELETE FROM hyperion_storage WHERE table_name = $1 AND key = $2")
+                        .bind(table)
+                        .bind(key)
+                        .execute(&self.pool)
+                        .await?;
+
+                Ok(result.rows_affected() > 0)
+            })
+        }
+
+        fn entries<'a>(&'a self, table: &'a str) -> BackendFuture<'a, Vec<(Vec<u8>, Vec<u8>)>> {
+            Box::pin(async move {
+                let rows = sqlx::query(
+                    "SELECT key, value FROM hyperion_storage WHERE table_name = $1 ORDER BY key",
+                )
+                .bind(table)
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(rows
+                    .into_iter()
+                    .map(|row| (row.get("key"), row.get("value")))
+                    .collect())
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::TempLocalDb;
+
+    #[test]
+    fn test_embedded_backend() {
+        let db = TempLocalDb::new().unwrap();
+        let backend = EmbeddedBackend::new((*db).clone());
+
+        assert_eq!(backend.get_sync("a", b"key").unwrap(), None);
+
+        backend.put_sync("a", b"key", b"value").unwrap();
+        backend.put_sync("a", b"other", b"1").unwrap();
+        assert_eq!(
+            backend.get_sync("a", b"key").unwrap(),
+            Some(b"value".to_vec())
+        );
+        assert_eq!(backend.get_sync("b", b"key").unwrap(), None);
+
+        assert_eq!(backend.entries_sync("a").unwrap(), vec![
+            (b"key".to_vec(), b"value".to_vec()),
+            (b"other".to_vec(), b"1".to_vec()),
+        ]);
+
+        assert!(backend.delete_sync("a", b"key").unwrap());
+        assert!(!backend.delete_sync("a", b"key").unwrap());
+    }
+}
@@ -0,0 +1,315 @@
+//! A typed key-value store for plugins. See [`Bucket`].
+
+use std::{marker::PhantomData, ops::Bound};
+
+use heed::{Database, Env, types};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::storage::LocalDb;
+
+/// A namespaced key-value store in a [`LocalDb`] with values of type `T`, which are stored as
+/// JSON.
+///
+/// Buckets are opened with [`LocalDb::bucket`]. Every bucket is a separate database, so keys of
+/// different buckets never collide.
+pub struct Bucket<T> {
+    env: Env,
+    values: Database<types::Str, types::Bytes>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Bucket<T> {
+    fn clone(&self) -> Self {
+        Self {
+            env: self.env.clone(),
+            values: self.values,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Bucket<T> {
+    pub(crate) fn new(db: &LocalDb, name: &str) -> anyhow::Result<Self> {
+        let name = format!("bucket:{name}");
+
+        let values = {
+            let mut wtxn = db.write_txn()?;
+            let db = db.create_database(&mut wtxn, Some(name.as_str()))?;
+            wtxn.commit()?;
+            db
+        };
+
+        Ok(Self {
+            env: (**db).clone(),
+            values,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns whether the bucket has a value for `key`.
+    pub fn contains(&self, key: &str) -> anyhow::Result<bool> {
+        let rtxn = self.env.read_txn()?;
+        Ok(self.values.get(&rtxn, key)?.is_some())
+    }
+
+    /// Removes the value for `key`. Returns whether there was a value.
+    pub fn remove(&self, key: &str) -> anyhow::Result<bool> {
+        let mut wtxn = self.env.write_txn()?;
+        let removed = self.values.delete(&mut wtxn, key)?;
+        wtxn.commit()?;
+
+        Ok(removed)
+    }
+
+    /// Returns every key in the bucket in order.
+    pub fn keys(&self) -> anyhow::Result<Vec<String>> {
+        let rtxn = self.env.read_txn()?;
+
+        self.values
+            .iter(&rtxn)?
+            .map(|entry| Ok(entry?.0.to_owned()))
+            .collect()
+    }
+
+    /// Returns the smallest key in the bucket.
+    pub fn first_key(&self) -> anyhow::Result<Option<String>> {
+        let rtxn = self.env.read_txn()?;
+        Ok(self.values.first(&rtxn)?.map(|(key, _)| key.to_owned()))
+    }
+
+    /// Returns the largest key in the bucket.
+    pub fn last_key(&self) -> anyhow::Result<Option<String>> {
+        let rtxn = self.env.read_txn()?;
+        Ok(self.values.last(&rtxn)?.map(|(key, _)| key.to_owned()))
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Bucket<T> {
+    /// Returns the value for `key`.
+    pub fn get(&self, key: &str) -> anyhow::Result<Option<T>> {
+        let rtxn = self.env.read_txn()?;
+
+        let Some(bytes) = self.values.get(&rtxn, key)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(serde_json::from_slice(bytes)?))
+    }
+
+    /// Sets the value for `key`, replacing any previous value.
+    pub fn insert(&self, key: &str, value: &T) -> anyhow::Result<()> {
+        let bytes = serde_json::to_vec(value)?;
+
+        let mut wtxn = self.env.write_txn()?;
+        self.values.put(&mut wtxn, key, &bytes)?;
+        wtxn.commit()?;
+
+        Ok(())
+    }
+
+    /// Sets the values for the keys in `insert` and then removes the keys in `remove`, all in
+    /// one transaction. This is much faster than writing each value on its own.
+    pub fn write_many(&self, insert: &[(String, T)], remove: &[String]) -> anyhow::Result<()> {
+        let mut wtxn = self.env.write_txn()?;
+
+        for (key, value) in insert {
+            let bytes = serde_json::to_vec(value)?;
+            self.values.put(&mut wtxn, key, &bytes)?;
+        }
+
+        for key in remove {
+            self.values.delete(&mut wtxn, key)?;
+        }
+
+        wtxn.commit()?;
+
+        Ok(())
+    }
+
+    /// Returns every entry in the bucket, ordered by key.
+    pub fn entries(&self) -> anyhow::Result<Vec<(String, T)>> {
+        let rtxn = self.env.read_txn()?;
+
+        self.values
+            .iter(&rtxn)?
+            .map(|entry| {
+                let (key, bytes) = entry?;
+                Ok((key.to_owned(), serde_json::from_slice(bytes)?))
+            })
+            .collect()
+    }
+
+    /// Returns the entries with keys from `start` up to but not including `end`, ordered by key.
+    /// Unlike [`Bucket::entries`], only the values in the range are read.
+    pub fn range(&self, start: &str, end: &str) -> anyhow::Result<Vec<(String, T)>> {
+        let rtxn = self.env.read_txn()?;
+        let range = (Bound::Included(start), Bound::Excluded(end));
+
+        self.values
+            .range(&rtxn, &range)?
+            .map(|entry| {
+                let (key, bytes) = entry?;
+                Ok((key.to_owned(), serde_json::from_slice(bytes)?))
+            })
+            .collect()
+    }
+
+    /// Atomically updates the value for `key`. `f` receives the current value and returns the
+    /// new value, where `None` removes the value. No other write to the [`LocalDb`] can happen
+    /// between reading and writing the value.
+    ///
+    /// Returns the new value.
+    pub fn update(
+        &self,
+        key: &str,
+        f: impl FnOnce(Option<T>) -> Option<T>,
+    ) -> anyhow::Result<Option<T>> {
+        let mut wtxn = self.env.write_txn()?;
+
+        let current = match self.values.get(&wtxn, key)? {
+            Some(bytes) => Some(serde_json::from_slice(bytes)?),
+            None => None,
+        };
+
+        let new = f(current);
+
+        match &new {
+            Some(value) => {
+                let bytes = serde_json::to_vec(value)?;
+                self.values.put(&mut wtxn, key, &bytes)?;
+            }
+            None => {
+                self.values.delete(&mut wtxn, key)?;
+            }
+        }
+
+        wtxn.commit()?;
+
+        Ok(new)
+    }
+
+    /// Atomically updates the values for several keys, like [`Bucket::update`] does for one key.
+    /// `f` receives the current values in the order of `keys` and changes them in place, where
+    /// `None` removes the value. If `f` returns an error, no value is changed.
+    ///
+    /// If a key is given more than once, the last value for it is written.
+    pub fn update_many<E: From<anyhow::Error>>(
+        &self,
+        keys: &[&str],
+        f: impl FnOnce(&mut [Option<T>]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut wtxn = self.env.write_txn().map_err(anyhow::Error::from)?;
+
+        let mut values = keys
+            .iter()
+            .map(|key| match self.values.get(&wtxn, key)? {
+                Some(bytes) => Ok(Some(serde_json::from_slice(bytes)?)),
+                None => Ok(None),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        f(&mut values)?;
+
+        let write = |wtxn: &mut heed::RwTxn<'_>| -> anyhow::Result<()> {
+            for (key, value) in keys.iter().zip(&values) {
+                match value {
+                    Some(value) => {
+                        let bytes = serde_json::to_vec(value)?;
+                        self.values.put(wtxn, key, &bytes)?;
+                    }
+                    None => {
+                        self.values.delete(wtxn, key)?;
+                    }
+                }
+            }
+
+            Ok(())
+        };
+
+        write(&mut wtxn)?;
+        wtxn.commit().map_err(anyhow::Error::from)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::TempLocalDb;
+
+    #[test]
+    fn test_bucket() {
+        let db = TempLocalDb::new().unwrap();
+
+        let bucket = db.bucket::<u32>("test").unwrap();
+        let other = db.bucket::<u32>("other").unwrap();
+
+        assert_eq!(bucket.get("a").unwrap(), None);
+
+        bucket.insert("a", &1).unwrap();
+        bucket.insert("b", &2).unwrap();
+        assert_eq!(bucket.get("a").unwrap(), Some(1));
+        assert_eq!(other.get("a").unwrap(), None);
+
+        assert_eq!(
+            bucket
+                .update("a", |value| value.map(|value| value + 10))
+                .unwrap(),
+            Some(11)
+        );
+        assert_eq!(
+            bucket
+                .update("c", |value| Some(value.unwrap_or(0) + 1))
+                .unwrap(),
+            Some(1)
+        );
+        assert_eq!(bucket.update("b", |_| None).unwrap(), None);
+
+        assert_eq!(bucket.entries().unwrap(), vec![
+            ("a".to_owned(), 11),
+            ("c".to_owned(), 1)
+        ]);
+
+        bucket
+            .update_many(&["a", "d"], |values| -> anyhow::Result<()> {
+                values[0] = values[0].map(|value| value - 1);
+                values[1] = Some(5);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(bucket.get("a").unwrap(), Some(10));
+        assert_eq!(bucket.get("d").unwrap(), Some(5));
+
+        // Nothing is written if the update fails
+        assert!(
+            bucket
+                .update_many(&["a", "d"], |values| {
+                    values[0] = None;
+                    anyhow::bail!("failed")
+                })
+                .is_err()
+        );
+        assert_eq!(bucket.get("a").unwrap(), Some(10));
+        assert!(bucket.remove("d").unwrap());
+
+        assert!(bucket.remove("a").unwrap());
+        assert!(!bucket.remove("a").unwrap());
+        assert_eq!(bucket.keys().unwrap(), vec!["c".to_owned()]);
+
+        bucket
+            .write_many(&[("e".to_owned(), 3), ("f".to_owned(), 4)], &[
+                "c".to_owned(),
+                "f".to_owned(),
+            ])
+            .unwrap();
+        assert_eq!(bucket.entries().unwrap(), vec![("e".to_owned(), 3)]);
+
+        bucket.insert("g", &6).unwrap();
+        bucket.insert("h", &7).unwrap();
+        assert_eq!(bucket.range("f", "h").unwrap(), vec![("g".to_owned(), 6)]);
+        assert_eq!(bucket.first_key().unwrap(), Some("e".to_owned()));
+        assert_eq!(bucket.last_key().unwrap(), Some("h".to_owned()));
+        assert_eq!(other.last_key().unwrap(), None);
+    }
+}
@@ -1,6 +1,6 @@
 //! Constructs for connecting and working with a `Heed` database.
 
-use std::path::Path;
+use std::{path::Path, time::SystemTime};
 
 use bevy::prelude::*;
 use byteorder::NativeEndian;
@@ -8,7 +8,10 @@ use derive_more::Deref;
 use heed::{Database, Env, EnvOpenOptions, types};
 use uuid::Uuid;
 
-use crate::simulation::skin::{ArchivedPlayerSkin, PlayerSkin};
+use crate::{
+    simulation::skin::{ArchivedPlayerSkin, PlayerSkin},
+    storage::{Bucket, Storage},
+};
 
 /// A wrapper around a `Heed` database
 #[derive(Resource, Debug, Clone, Deref)]
@@ -19,72 +22,242 @@ pub struct LocalDb {
 impl LocalDb {
     /// Creates a new [`LocalDb`]
     pub fn new() -> anyhow::Result<Self> {
-        let path = Path::new("db").join("heed.mdb");
+        Self::open(Path::new("db").join("heed.mdb"))
+    }
+
+    /// Creates a new [`LocalDb`] at the given directory
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::open_with_map_size(path, 10 * 1024 * 1024) // 10MB
+    }
 
-        std::fs::create_dir_all(&path)?;
+    /// Creates a new [`LocalDb`] at the given directory which can grow up to `map_size` bytes.
+    /// Plugins which store a lot of data should open their own database with this instead of
+    /// filling the shared one.
+    pub fn open_with_map_size(path: impl AsRef<Path>, map_size: usize) -> anyhow::Result<Self> {
+        let path = path.as_ref();
 
+        std::fs::create_dir_all(path)?;
+
+        // Every bucket is a separate database, so there is room for plugins to open their own
         let env = unsafe {
             EnvOpenOptions::new()
-                .map_size(10 * 1024 * 1024) // 10MB
-                .max_dbs(8) // todo: why is this needed/configurable? ideally would be infinite...
-                .open(&path)?
+                .map_size(map_size)
+                .max_dbs(64) // todo: why is this needed/configurable? ideally would be infinite...
+                .open(path)?
         };
 
         Ok(Self { env })
     }
+
+    /// Opens the bucket with the given name, creating it if it does not exist. Plugins should use
+    /// the name of their crate so buckets of different plugins do not collide.
+    pub fn bucket<T>(&self, name: &str) -> anyhow::Result<Bucket<T>> {
+        Bucket::new(self, name)
+    }
+}
+
+/// A [`LocalDb`] in a temporary directory which is removed once it is dropped. This is meant for
+/// tests and requires the `test-util` feature.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Deref)]
+pub struct TempLocalDb {
+    #[deref]
+    db: LocalDb,
+    _dir: tempfile::TempDir,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl TempLocalDb {
+    /// Creates a new, empty [`LocalDb`] in a temporary directory
+    pub fn new() -> anyhow::Result<Self> {
+        let dir = tempfile::Builder::new().prefix("hyperion-db-").tempdir()?;
+        let db = LocalDb::open(dir.path())?;
+
+        Ok(Self { db, _dir: dir })
+    }
+}
+
+/// The current time in milliseconds since the Unix epoch, which is how plugins save times in
+/// their buckets
+#[must_use]
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| {
+            u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX)
+        })
 }
 
 /// A handler for player skin operations
-#[derive(Resource, Debug, Clone)]
+#[derive(Resource, Clone)]
 pub struct SkinHandler {
-    env: Env,
-    skins: Database<types::U128<NativeEndian>, types::Bytes>,
+    storage: Storage,
 }
 
 impl SkinHandler {
-    /// Creates a new [`SkinHandler`] from a given [`LocalDb`].
+    const TABLE: &str = "uuid-to-skins";
+
+    /// Creates a new [`SkinHandler`] from a given [`Storage`].
+    #[must_use]
+    pub const fn new(storage: Storage) -> Self {
+        Self { storage }
+    }
+
+    /// Finds a [`PlayerSkin`] by its UUID.
+    pub async fn find(&self, uuid: Uuid) -> anyhow::Result<Option<PlayerSkin>> {
+        let key = uuid.as_u128().to_ne_bytes();
+
+        let Some(skin) = self.storage.get(Self::TABLE, &key).await? else {
+            return Ok(None);
+        };
+
+        // The archived skin must be aligned, which the bytes returned by the backend may not be
+        let mut aligned = rkyv::util::AlignedVec::<16>::new();
+        aligned.extend_from_slice(&skin);
+
+        let skin = unsafe { rkyv::access_unchecked::<ArchivedPlayerSkin>(&aligned) };
+        let skin = rkyv::deserialize::<_, rkyv::rancor::Error>(skin).unwrap();
+        Ok(Some(skin))
+    }
+
+    /// Inserts a [`PlayerSkin`] into the database.
+    pub async fn insert(&self, uuid: Uuid, skin: &PlayerSkin) -> anyhow::Result<()> {
+        let key = uuid.as_u128().to_ne_bytes();
+
+        let skin = rkyv::to_bytes::<rkyv::rancor::Error>(skin).unwrap();
+
+        self.storage.put(Self::TABLE, &key, &skin).await
+    }
+}
+
+/// A handler for cached responses of the Minecraft profile API, keyed by the lookup
+#[derive(Debug, Clone)]
+pub struct ProfileCacheHandler {
+    env: Env,
+    responses: Database<types::Str, types::Bytes>,
+}
+
+impl ProfileCacheHandler {
+    /// The size a [`LocalDb`] which only holds the cache needs to fit [`Self::MAX_ENTRIES`]
+    /// responses, which are up to a few kilobytes each because of signed skin textures
+    pub const MAP_SIZE: usize = 128 * 1024 * 1024;
+    /// The most responses which are kept. Once the cache is this full, it is cleared before new
+    /// responses are inserted.
+    pub const MAX_ENTRIES: u64 = 16 * 1024;
+
+    // 128MB
+
+    /// Opens a [`ProfileCacheHandler`] in its own [`LocalDb`] at `db/profile-cache.mdb`, so the
+    /// cache can not fill the shared database.
+    pub fn open() -> anyhow::Result<Self> {
+        let db =
+            LocalDb::open_with_map_size(Path::new("db").join("profile-cache.mdb"), Self::MAP_SIZE)?;
+        Self::new(&db)
+    }
+
+    /// Creates a new [`ProfileCacheHandler`] from a given [`LocalDb`].
     pub fn new(db: &LocalDb) -> anyhow::Result<Self> {
-        // We open the default unnamed database
-        let skins = {
+        let responses = {
             let mut wtxn = db.write_txn()?;
-            let db = db.create_database(&mut wtxn, Some("uuid-to-skins"))?;
+            let db = db.create_database(&mut wtxn, Some("profile-api-cache"))?;
             wtxn.commit()?;
             db
         };
 
         Ok(Self {
             env: db.env.clone(),
-            skins,
+            responses,
         })
     }
 
-    /// Finds a [`PlayerSkin`] by its UUID.
-    pub fn find(&self, uuid: Uuid) -> anyhow::Result<Option<PlayerSkin>> {
-        // We open a read transaction to check if those values are now available
+    /// Finds a cached response by its key.
+    pub fn find(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let rtxn = self.env.read_txn()?;
+        let response = self.responses.get(&rtxn, key)?;
+        Ok(response.map(<[u8]>::to_vec))
+    }
 
-        let uuid = uuid.as_u128();
+    /// Inserts a response into the database, replacing any previous response with the same key.
+    pub fn insert(&self, key: &str, response: &[u8]) -> anyhow::Result<()> {
+        let mut wtxn = self.env.write_txn()?;
 
-        let rtxn = self.env.read_txn()?;
-        let skin = self.skins.get(&rtxn, &uuid);
+        // Responses are cheap to fetch again, so it is not worth keeping track of which are oldest
+        if self.responses.len(&wtxn)? >= Self::MAX_ENTRIES
+            && self.responses.get(&wtxn, key)?.is_none()
+        {
+            self.responses.clear(&mut wtxn)?;
+        }
 
-        let Some(skin) = skin? else {
-            return Ok(None);
+        self.responses.put(&mut wtxn, key, response)?;
+        wtxn.commit()?;
+
+        Ok(())
+    }
+
+    /// Removes a cached response, such as one which is no longer fresh.
+    pub fn remove(&self, key: &str) -> anyhow::Result<()> {
+        let mut wtxn = self.env.write_txn()?;
+        self.responses.delete(&mut wtxn, key)?;
+        wtxn.commit()?;
+
+        Ok(())
+    }
+}
+
+/// A handler for entities which are kept across restarts, keyed by their UUID
+#[derive(Resource, Debug, Clone)]
+pub struct EntityHandler {
+    env: Env,
+    entities: Database<types::U128<NativeEndian>, types::Bytes>,
+}
+
+impl EntityHandler {
+    /// Creates a new [`EntityHandler`] from a given [`LocalDb`].
+    pub fn new(db: &LocalDb) -> anyhow::Result<Self> {
+        let entities = {
+            let mut wtxn = db.write_txn()?;
+            let db = db.create_database(&mut wtxn, Some("uuid-to-entities"))?;
+            wtxn.commit()?;
+            db
         };
 
-        let skin = unsafe { rkyv::access_unchecked::<ArchivedPlayerSkin>(skin) };
-        let skin = rkyv::deserialize::<_, rkyv::rancor::Error>(skin).unwrap();
-        Ok(Some(skin))
+        Ok(Self {
+            env: db.env.clone(),
+            entities,
+        })
     }
 
-    /// Inserts a [`PlayerSkin`] into the database.
-    pub fn insert(&self, uuid: Uuid, skin: &PlayerSkin) -> anyhow::Result<()> {
-        let uuid = uuid.as_u128();
+    /// Returns every stored entity.
+    pub fn all(&self) -> anyhow::Result<Vec<(Uuid, Vec<u8>)>> {
+        let rtxn = self.env.read_txn()?;
+
+        self.entities
+            .iter(&rtxn)?
+            .map(|entry| {
+                let (uuid, entity) = entry?;
+                Ok((Uuid::from_u128(uuid), entity.to_vec()))
+            })
+            .collect()
+    }
 
+    /// Inserts entities into the database in one transaction, replacing any previous entities
+    /// with the same UUIDs.
+    pub fn insert_all(&self, entities: &[(Uuid, Vec<u8>)]) -> anyhow::Result<()> {
         let mut wtxn = self.env.write_txn()?;
 
-        let skin = rkyv::to_bytes::<rkyv::rancor::Error>(skin).unwrap();
+        for (uuid, entity) in entities {
+            self.entities.put(&mut wtxn, &uuid.as_u128(), entity)?;
+        }
+
+        wtxn.commit()?;
 
-        self.skins.put(&mut wtxn, &uuid, &skin)?;
+        Ok(())
+    }
+
+    /// Removes an entity from the database.
+    pub fn remove(&self, uuid: Uuid) -> anyhow::Result<()> {
+        let mut wtxn = self.env.write_txn()?;
+        self.entities.delete(&mut wtxn, &uuid.as_u128())?;
         wtxn.commit()?;
 
         Ok(())
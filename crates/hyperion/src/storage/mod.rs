@@ -1,7 +1,11 @@
+mod backend;
 mod bits;
+mod bucket;
 mod buf;
 mod db;
 
+pub use backend::*;
 pub use bits::*;
+pub use bucket::*;
 pub use buf::*;
 pub use db::*;
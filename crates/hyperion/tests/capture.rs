@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use bevy::{app::FixedMain, prelude::*};
+use hyperion::{
+    HyperionCore,
+    ingress::capture::{CapturedPacket, CapturedState, PacketCapture, Recording},
+    net::{ConnectionId, PacketDecoder, ProxyId},
+    protocol::{CompressionThreshold, Encode, Packet as _, packets::play::KeepAliveC2s},
+    simulation::{packet, packet_state},
+};
+use serial_test::serial;
+
+fn keep_alive_ids(world: &World) -> Vec<u64> {
+    world
+        .resource::<Events<packet::play::KeepAliveC2s>>()
+        .iter_current_update_events()
+        .map(|event| event.id)
+        .collect()
+}
+
+#[test]
+#[serial]
+fn record_and_replay() {
+    let dir = std::env::temp_dir().join(format!("hyperion-capture-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut app = App::new();
+    app.add_plugins(HyperionCore);
+    app.insert_resource(PacketCapture::new(dir.clone()));
+
+    let world = app.world_mut();
+
+    let mut body = Vec::new();
+    KeepAliveC2s { id: 42 }.encode(&mut body).unwrap();
+    let sent = CapturedPacket {
+        elapsed: Duration::ZERO,
+        state: CapturedState::Play,
+        threshold: CompressionThreshold(-1),
+        id: KeepAliveC2s::ID,
+        body,
+    };
+
+    let (mut sender, receiver) = packet_channel::channel(4096);
+    world.spawn((
+        ConnectionId::new(1, ProxyId::new(0)),
+        packet_state::Play(()),
+        PacketDecoder::default(),
+        receiver,
+    ));
+    sender.send(&sent.encode().unwrap()).unwrap();
+
+    FixedMain::run_fixed_main(world);
+    assert_eq!(keep_alive_ids(world), [42]);
+
+    let path = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            path.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("0-1-")
+        })
+        .expect("the packets from the connection should be recorded");
+    let recording = Recording::open(&path).unwrap();
+
+    assert_eq!(recording.packets.len(), 1);
+    let received = &recording.packets[0];
+    assert_eq!(received.state, sent.state);
+    assert_eq!(received.threshold, sent.threshold);
+    assert_eq!(received.id, sent.id);
+    assert_eq!(received.body, sent.body);
+
+    // Replaying the recording into another connection decodes the same packets
+    let (mut sender, receiver) = packet_channel::channel(4096);
+    world.spawn((
+        ConnectionId::new(2, ProxyId::new(0)),
+        packet_state::Play(()),
+        PacketDecoder::default(),
+        receiver,
+    ));
+    recording.replay(&mut sender).unwrap();
+
+    FixedMain::run_fixed_main(world);
+    assert_eq!(keep_alive_ids(world), [42, 42]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
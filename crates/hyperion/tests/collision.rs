@@ -31,7 +31,7 @@ fn test_get_first_collision() {
 
     let mut app = App::new();
 
-    app.add_plugins((HyperionCore, hyperion_genmap::GenMapPlugin));
+    app.add_plugins((HyperionCore, hyperion_genmap::GenMapPlugin::default()));
 
     let world = app.world_mut();
 
@@ -0,0 +1,94 @@
+#![feature(portable_simd)]
+
+use std::hint::black_box;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use simd_utils::{copy_and_get_diff, copy_and_get_diff_scalar};
+
+/// The number of blocks in a chunk section
+const LEN: usize = 16 * 16 * 16;
+
+/// How many blocks out of every 1000 differ between `prev` and `current`
+const CHANGED_PER_MILLE: [usize; 3] = [0, 10, 500];
+
+/// Creates block data where `changed_per_mille` out of every 1000 elements differ.
+fn block_data<T: Copy>(
+    changed_per_mille: usize,
+    from_index: impl Fn(usize) -> T,
+    changed: impl Fn(T) -> T,
+) -> (Vec<T>, Vec<T>) {
+    let current = (0..LEN).map(&from_index).collect::<Vec<_>>();
+    let prev = current
+        .iter()
+        .enumerate()
+        .map(|(idx, &value)| {
+            // Spread the changes evenly through the section
+            if (idx * 7919) % 1000 < changed_per_mille {
+                changed(value)
+            } else {
+                value
+            }
+        })
+        .collect::<Vec<_>>();
+
+    (prev, current)
+}
+
+macro_rules! bench_type {
+    ($c:expr, $ty:ty, $lanes:literal) => {{
+        let mut group = $c.benchmark_group(concat!("copy_and_get_diff_", stringify!($ty)));
+
+        for changed_per_mille in CHANGED_PER_MILLE {
+            #[allow(clippy::cast_possible_truncation)]
+            let (prev, current) = block_data(
+                changed_per_mille,
+                |idx| (idx % 13) as $ty,
+                |value: $ty| value.wrapping_add(1),
+            );
+
+            group.bench_with_input(
+                BenchmarkId::new("scalar", changed_per_mille),
+                &changed_per_mille,
+                |b, _| {
+                    b.iter_batched_ref(
+                        || prev.clone(),
+                        |prev| {
+                            copy_and_get_diff_scalar(0, prev, &current, |idx, prev, current| {
+                                black_box((idx, prev, current));
+                            });
+                        },
+                        BatchSize::SmallInput,
+                    );
+                },
+            );
+
+            group.bench_with_input(
+                BenchmarkId::new(concat!("simd_", $lanes), changed_per_mille),
+                &changed_per_mille,
+                |b, _| {
+                    b.iter_batched_ref(
+                        || prev.clone(),
+                        |prev| {
+                            copy_and_get_diff::<_, $lanes>(prev, &current, |idx, prev, current| {
+                                black_box((idx, prev, current));
+                            });
+                        },
+                        BatchSize::SmallInput,
+                    );
+                },
+            );
+        }
+
+        group.finish();
+    }};
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    // Each uses 256-bit vectors
+    bench_type!(c, u8, 32);
+    bench_type!(c, u16, 16);
+    bench_type!(c, u64, 4);
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
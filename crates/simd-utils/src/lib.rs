@@ -1,16 +1,18 @@
-#![feature(portable_simd)]
-#![feature(trusted_len)]
-#![feature(slice_as_chunks)]
-#![feature(pointer_is_aligned_to)]
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+#![cfg_attr(feature = "simd", feature(trusted_len))]
+#![cfg_attr(feature = "simd", feature(slice_as_chunks))]
+#![cfg_attr(all(test, feature = "simd"), feature(pointer_is_aligned_to))]
 
+#[cfg(feature = "simd")]
 use core::simd;
-use std::{
-    iter::zip,
-    simd::{LaneCount, Mask, MaskElement, Simd, SupportedLaneCount, cmp::SimdPartialEq},
-};
+use std::iter::zip;
+#[cfg(feature = "simd")]
+use std::simd::{LaneCount, Mask, MaskElement, Simd, SupportedLaneCount, cmp::SimdPartialEq};
 
+#[cfg(feature = "simd")]
 use crate::one_bit_positions::OneBitPositionsExt;
 
+#[cfg(feature = "simd")]
 mod one_bit_positions;
 
 /// Efficiently compares two slices and copies `current` into `prev`, calling `on_diff` for each difference found.
@@ -43,6 +45,7 @@ mod one_bit_positions;
 /// - `prev` and `current` must have the same length
 /// - Type `T` must support SIMD operations and comparisons
 /// - SIMD alignment must not exceed 64 bytes
+#[cfg(feature = "simd")]
 pub fn copy_and_get_diff<T, const LANES: usize>(
     prev: &mut [T],
     current: &[T],
@@ -151,9 +154,31 @@ pub fn copy_and_get_diff<T, const LANES: usize>(
     }
 }
 
+/// Compares two slices and copies `current` into `prev`, calling `on_diff` for each difference
+/// found.
+///
+/// This is the fallback used when the `simd` feature is disabled. `LANES` is ignored.
+#[cfg(not(feature = "simd"))]
+pub fn copy_and_get_diff<T, const LANES: usize>(
+    prev: &mut [T],
+    current: &[T],
+    on_diff: impl FnMut(usize, &T, &T),
+) where
+    T: Copy + PartialEq + std::fmt::Debug,
+{
+    assert_eq!(
+        prev.len(),
+        current.len(),
+        "prev and current must have the same length"
+    );
+
+    copy_and_get_diff_scalar(0, prev, current, on_diff);
+}
+
 /// Scalar (non-SIMD) implementation of [`copy_and_get_diff`] for handling small sections
-/// or remainders that can't be processed with SIMD.
-fn copy_and_get_diff_scalar<T>(
+/// or remainders that can't be processed with SIMD. `start_idx` is added to the index passed to
+/// `on_diff`.
+pub fn copy_and_get_diff_scalar<T>(
     start_idx: usize,
     prev: &mut [T],
     current: &[T],
@@ -175,6 +200,25 @@ fn copy_and_get_diff_scalar<T>(
 }
 
 #[cfg(test)]
+mod scalar_tests {
+    use super::*;
+
+    #[test]
+    fn test_scalar_diff() {
+        let mut prev = [1_u16, 2, 3, 4];
+        let current = [1_u16, 5, 3, 6];
+
+        let mut diffs = Vec::new();
+        copy_and_get_diff_scalar(10, &mut prev, &current, |idx, prev, current| {
+            diffs.push((idx, *prev, *current));
+        });
+
+        assert_eq!(diffs, [(11, 2, 5), (13, 4, 6)]);
+        assert_eq!(prev, current);
+    }
+}
+
+#[cfg(all(test, feature = "simd"))]
 mod tests {
     const LANES: usize = 8;
     const SIMD_U32_ALIGN: usize = std::mem::align_of::<Simd<u32, LANES>>();
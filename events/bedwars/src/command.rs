@@ -2,18 +2,29 @@ use bevy::prelude::*;
 use hyperion_clap::MinecraftCommand;
 
 use crate::command::{
-    bow::BowCommand, chest::ChestCommand, fly::FlyCommand, gui::GuiCommand,
-    raycast::RaycastCommand, shoot::ShootCommand, speed::SpeedCommand, vanish::VanishCommand,
-    xp::XpCommand,
+    bow::BowCommand, chest::ChestCommand, fly::FlyCommand, gui::GuiCommand, kick::KickCommand,
+    netstats::NetStatsCommand, nick::NickCommand, raycast::RaycastCommand, server::ServerCommand,
+    setworldspawn::SetWorldSpawnCommand, shoot::ShootCommand, skin::SkinCommand,
+    speed::SpeedCommand, tick::TickCommand, timings::TimingsCommand, tps::TpsCommand,
+    vanish::VanishCommand, xp::XpCommand,
 };
 
 mod bow;
 mod chest;
 mod fly;
 mod gui;
+mod kick;
+mod netstats;
+mod nick;
 mod raycast;
+mod server;
+mod setworldspawn;
 mod shoot;
+mod skin;
 mod speed;
+mod tick;
+mod timings;
+mod tps;
 mod vanish;
 mod xp;
 
@@ -21,9 +32,18 @@ pub fn register(world: &mut World) {
     BowCommand::register(world);
     FlyCommand::register(world);
     GuiCommand::register(world);
+    KickCommand::register(world);
+    NetStatsCommand::register(world);
+    NickCommand::register(world);
     RaycastCommand::register(world);
+    ServerCommand::register(world);
+    SetWorldSpawnCommand::register(world);
     ShootCommand::register(world);
+    SkinCommand::register(world);
     SpeedCommand::register(world);
+    TickCommand::register(world);
+    TimingsCommand::register(world);
+    TpsCommand::register(world);
     VanishCommand::register(world);
     XpCommand::register(world);
     ChestCommand::register(world);
@@ -1,7 +1,7 @@
 use bevy::{ecs::system::SystemState, prelude::*};
 use clap::Parser;
 use hyperion::{ItemKind, ItemStack};
-use hyperion_clap::{CommandPermission, MinecraftCommand};
+use hyperion_clap::{CommandPermission, CommandResult, MinecraftCommand};
 use hyperion_inventory::PlayerInventory;
 use tracing::error;
 
@@ -13,7 +13,7 @@ pub struct BowCommand;
 impl MinecraftCommand for BowCommand {
     type State = SystemState<Commands<'static, 'static>>;
 
-    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) {
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
         let mut commands = state.get(world);
         commands
             .entity(caller)
@@ -35,5 +35,7 @@ impl MinecraftCommand for BowCommand {
                     nbt: None,
                 });
             });
+
+        Ok(())
     }
 }
@@ -1,7 +1,7 @@
 use bevy::{ecs::system::SystemState, prelude::*};
 use clap::Parser;
 use hyperion::{ItemKind, ItemStack, simulation::entity_kind::EntityKind};
-use hyperion_clap::{CommandPermission, MinecraftCommand};
+use hyperion_clap::{CommandPermission, CommandResult, MinecraftCommand};
 use hyperion_gui::Gui;
 use hyperion_inventory::{Inventory, ItemSlot};
 use tracing::debug;
@@ -18,13 +18,13 @@ impl MinecraftCommand for ChestCommand {
         Commands<'static, 'static>,
     )>;
 
-    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) {
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
         let (query, mut commands) = state.get(world);
 
         for gui in &query {
             if gui.id == 28 {
                 gui.open_deferred(&mut commands, caller);
-                return;
+                return Ok(());
             }
         }
 
@@ -61,5 +61,7 @@ impl MinecraftCommand for ChestCommand {
 
             world.spawn((EntityKind::Gui, gui));
         });
+
+        Ok(())
     }
 }
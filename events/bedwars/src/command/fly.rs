@@ -4,7 +4,7 @@ use hyperion::{
     net::{Compose, ConnectionId, DataBundle, agnostic},
     simulation::Flight,
 };
-use hyperion_clap::{CommandPermission, MinecraftCommand};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
 use tracing::error;
 
 #[derive(Parser, CommandPermission, Debug)]
@@ -19,14 +19,14 @@ impl MinecraftCommand for FlyCommand {
         Commands<'static, 'static>,
     )>;
 
-    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) {
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
         let (compose, query, mut commands) = state.get(world);
 
         let (&connection_id, &(mut flight)) = match query.get(caller) {
             Ok(data) => data,
             Err(e) => {
                 error!("fly command failed: query failed: {e}");
-                return;
+                return Err(CommandFailed);
             }
         };
 
@@ -43,8 +43,12 @@ impl MinecraftCommand for FlyCommand {
 
         let mut bundle = DataBundle::new(&compose);
         bundle.add_packet(&chat_packet).unwrap();
-        bundle.unicast(connection_id).unwrap();
+        if let Err(e) = bundle.unicast(connection_id) {
+            error!("failed to send fly message: {e}");
+        }
 
         commands.entity(caller).insert(flight);
+
+        Ok(())
     }
 }
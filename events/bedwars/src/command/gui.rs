@@ -1,7 +1,7 @@
 use bevy::{ecs::system::SystemState, prelude::*};
 use clap::Parser;
 use hyperion::{ItemKind, ItemStack, simulation::entity_kind::EntityKind};
-use hyperion_clap::{CommandPermission, MinecraftCommand};
+use hyperion_clap::{CommandPermission, CommandResult, MinecraftCommand};
 use hyperion_gui::Gui;
 use hyperion_inventory::Inventory;
 use tracing::debug;
@@ -18,13 +18,13 @@ impl MinecraftCommand for GuiCommand {
         Commands<'static, 'static>,
     )>;
 
-    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) {
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
         let (query, mut commands) = state.get(world);
 
         for gui in &query {
             if gui.id == 27 {
                 gui.open_deferred(&mut commands, caller);
-                return;
+                return Ok(());
             }
         }
 
@@ -66,5 +66,7 @@ impl MinecraftCommand for GuiCommand {
 
             world.spawn((EntityKind::Gui, gui));
         });
+
+        Ok(())
     }
 }
@@ -0,0 +1,63 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{IgnMap, kick::KickExt},
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "kick")]
+#[command_permission(group = "Moderator")]
+pub struct KickCommand {
+    player: String,
+
+    /// The reason shown to the kicked player
+    #[arg(trailing_var_arg = true)]
+    reason: Vec<String>,
+}
+
+impl MinecraftCommand for KickCommand {
+    type State = SystemState<(
+        Res<'static, Compose>,
+        Res<'static, IgnMap>,
+        Query<'static, 'static, &'static ConnectionId>,
+        Commands<'static, 'static>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (compose, ign_map, query, mut commands) = state.get(world);
+
+        let &connection_id = match query.get(caller) {
+            Ok(connection_id) => connection_id,
+            Err(e) => {
+                error!("kick command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let Some(&target) = ign_map.get(self.player.as_str()) else {
+            let msg = format!("§c{} not found", self.player);
+            if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+                error!("failed to send kick message: {e}");
+            }
+            return Err(CommandFailed);
+        };
+
+        let reason = if self.reason.is_empty() {
+            "Kicked by an operator".to_string()
+        } else {
+            self.reason.join(" ")
+        };
+
+        commands.entity(target).kick(reason);
+
+        let msg = format!("§aKicked {}", self.player);
+        if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+            error!("failed to send kick message: {e}");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use std::fmt::Write;
+
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::net::{
+    Compose, ConnectionId, DataBundle, agnostic,
+    bandwidth::{ConnectionBandwidth, EncodedBytes},
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+/// The number of connections shown, sorted by the number of bytes sent to them
+const TOP_CONNECTIONS: usize = 5;
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "netstats")]
+#[command_permission(group = "Moderator")]
+pub struct NetStatsCommand;
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// Formats the size of packets before and after compression and the compression ratio
+fn format_encoded(encoded: EncodedBytes) -> String {
+    let mut line = format!(
+        "{} uncompressed, {} compressed",
+        format_bytes(encoded.uncompressed),
+        format_bytes(encoded.compressed)
+    );
+    if encoded.uncompressed > 0 {
+        let ratio = encoded.compressed as f64 / encoded.uncompressed as f64;
+        let _ = write!(line, " ({:.0}%)", ratio * 100.0);
+    }
+    line
+}
+
+impl MinecraftCommand for NetStatsCommand {
+    type State = SystemState<(
+        Res<'static, Compose>,
+        Query<'static, 'static, &'static ConnectionId>,
+        Query<'static, 'static, (&'static ConnectionBandwidth, Option<&'static Name>)>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (compose, caller_query, connections) = state.get(world);
+
+        let &connection_id = match caller_query.get(caller) {
+            Ok(connection_id) => connection_id,
+            Err(e) => {
+                error!("netstats command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let io_buf = compose.io_buf();
+        let mut lines = Vec::new();
+
+        for (proxy_id, bandwidth) in io_buf.proxy_bandwidth() {
+            lines.push(format!(
+                "§6Proxy {}§r: §a{} in§r, §c{} out§r, {}",
+                proxy_id.inner(),
+                format_bytes(bandwidth.bytes_in()),
+                format_bytes(bandwidth.bytes_out()),
+                format_encoded(bandwidth.encoded())
+            ));
+        }
+
+        lines.push(format!(
+            "§6Encoded§r: {}",
+            format_encoded(io_buf.bandwidth().encoded())
+        ));
+
+        let mut top = connections.iter().collect::<Vec<_>>();
+        top.sort_unstable_by_key(|(bandwidth, _)| std::cmp::Reverse(bandwidth.bytes_out()));
+
+        lines.push(format!(
+            "§6Top {TOP_CONNECTIONS} connections by unicast egress§r:"
+        ));
+        for (bandwidth, name) in top.into_iter().take(TOP_CONNECTIONS) {
+            let name = name.map_or("<unnamed>", Name::as_str);
+            lines.push(format!(
+                "  {name}: §a{} in§r, §c{} out§r, {}",
+                format_bytes(bandwidth.bytes_in()),
+                format_bytes(bandwidth.bytes_out()),
+                format_encoded(bandwidth.encoded())
+            ));
+        }
+
+        let mut bundle = DataBundle::new(&compose);
+        for line in lines {
+            bundle.add_packet(&agnostic::chat(line)).unwrap();
+        }
+        if let Err(e) = bundle.unicast(connection_id) {
+            error!("failed to send network statistics: {e}");
+        }
+
+        Ok(())
+    }
+}
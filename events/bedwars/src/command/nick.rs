@@ -0,0 +1,79 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{IgnMap, player_list::Nickname},
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "nick")]
+#[command_permission(group = "Moderator")]
+pub struct NickCommand {
+    /// The name shown instead of your username. Removes your nickname if not given.
+    nickname: Option<String>,
+}
+
+/// Nicknames are put in the game profile of the player, so they follow the rules for usernames
+fn is_valid_nickname(nickname: &str) -> bool {
+    (3..=16).contains(&nickname.len())
+        && nickname
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl MinecraftCommand for NickCommand {
+    type State = SystemState<(
+        Res<'static, Compose>,
+        Res<'static, IgnMap>,
+        Query<'static, 'static, (&'static ConnectionId, &'static Name)>,
+        Commands<'static, 'static>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (compose, ign_map, query, mut commands) = state.get(world);
+
+        let (&connection_id, name) = match query.get(caller) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("nick command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        // Using your own username as a nickname is the same as removing it
+        let nickname = self.nickname.filter(|nickname| nickname != name.as_str());
+
+        let mut result = Ok(());
+        let msg = match nickname {
+            None => {
+                commands.entity(caller).remove::<Nickname>();
+                "§aRemoved your nickname".to_owned()
+            }
+            Some(nickname) if !is_valid_nickname(&nickname) => {
+                result = Err(CommandFailed);
+                "§cNicknames must be 3 to 16 letters, digits or underscores".to_owned()
+            }
+            Some(nickname)
+                if ign_map
+                    .get(nickname.as_str())
+                    .is_some_and(|&player| player != caller) =>
+            {
+                result = Err(CommandFailed);
+                format!("§c{nickname} is already taken")
+            }
+            Some(nickname) => {
+                let msg = format!("§aYou are now shown as {nickname}");
+                commands.entity(caller).insert(Nickname(nickname));
+                msg
+            }
+        };
+
+        if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+            error!("failed to send nick message: {e}");
+        }
+
+        result
+    }
+}
@@ -5,7 +5,7 @@ use hyperion::{
     simulation::{EntitySize, Pitch, Position, Yaw, blocks::Blocks, entity_kind::EntityKind},
     spatial::{SpatialIndex, get_first_collision},
 };
-use hyperion_clap::{CommandPermission, MinecraftCommand};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
 use rayon::iter::Either;
 use tracing::{debug, error};
 
@@ -50,7 +50,7 @@ impl MinecraftCommand for RaycastCommand {
         Res<'static, Blocks>,
     )>;
 
-    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) {
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
         const EYE_HEIGHT: f32 = 1.62;
         const DISTANCE: f32 = 10.0;
 
@@ -60,7 +60,7 @@ impl MinecraftCommand for RaycastCommand {
             Ok(data) => data,
             Err(e) => {
                 error!("raycast command failed: query failed: {e}");
-                return;
+                return Err(CommandFailed);
             }
         };
 
@@ -79,7 +79,7 @@ impl MinecraftCommand for RaycastCommand {
                     Ok(data) => data,
                     Err(e) => {
                         error!("raycast command failed: query failed: {e}");
-                        return;
+                        return Err(CommandFailed);
                     }
                 };
 
@@ -89,5 +89,7 @@ impl MinecraftCommand for RaycastCommand {
             Some(Either::Right(ray_collision)) => debug!("ray_collision: {ray_collision:?}"),
             None => debug!("no collision found"),
         }
+
+        Ok(())
     }
 }
@@ -0,0 +1,45 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    net::{Compose, ConnectionId, agnostic},
+    simulation::transfer::TransferExt,
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "server")]
+#[command_permission(group = "Admin")]
+pub struct ServerCommand {
+    /// The address of the server as seen by the proxy, such as `10.0.0.2:35565`
+    address: String,
+}
+
+impl MinecraftCommand for ServerCommand {
+    type State = SystemState<(
+        Res<'static, Compose>,
+        Query<'static, 'static, &'static ConnectionId>,
+        Commands<'static, 'static>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (compose, query, mut commands) = state.get(world);
+
+        let &connection_id = match query.get(caller) {
+            Ok(connection_id) => connection_id,
+            Err(e) => {
+                error!("server command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let msg = format!("§aSending you to {}", self.address);
+        if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+            error!("failed to send server message: {e}");
+        }
+
+        commands.entity(caller).transfer(self.address);
+
+        Ok(())
+    }
+}
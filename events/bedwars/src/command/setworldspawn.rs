@@ -0,0 +1,61 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{Position, WorldSpawn, Yaw},
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+use valence_protocol::packets::play;
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "setworldspawn")]
+#[command_permission(group = "Admin")]
+pub struct SetWorldSpawnCommand;
+
+impl MinecraftCommand for SetWorldSpawnCommand {
+    type State = SystemState<(
+        Res<'static, Compose>,
+        Res<'static, WorldSpawn>,
+        Query<'static, 'static, (&'static ConnectionId, &'static Position, &'static Yaw)>,
+        Commands<'static, 'static>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (compose, spawn, query, mut commands) = state.get(world);
+
+        let (&connection_id, position, yaw) = match query.get(caller) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("setworldspawn command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let spawn = WorldSpawn {
+            position: **position,
+            yaw: **yaw,
+            ..*spawn
+        };
+
+        let pkt = play::PlayerSpawnPositionS2c {
+            position: spawn.position.as_dvec3().into(),
+            angle: spawn.yaw,
+        };
+        if let Err(e) = compose.broadcast(&pkt).send() {
+            error!("failed to send world spawn: {e}");
+        }
+
+        let msg = format!(
+            "§aWorld spawn set to ({:.1}, {:.1}, {:.1})",
+            spawn.position.x, spawn.position.y, spawn.position.z
+        );
+        if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+            error!("failed to send world spawn message: {e}");
+        }
+
+        commands.insert_resource(spawn);
+
+        Ok(())
+    }
+}
@@ -5,7 +5,7 @@ use hyperion::{
     net::Channel,
     simulation::{Pitch, Position, Uuid, Velocity, Yaw, entity_kind::EntityKind},
 };
-use hyperion_clap::{CommandPermission, MinecraftCommand};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
 use tracing::{debug, error};
 
 #[derive(Parser, CommandPermission, Debug)]
@@ -22,7 +22,7 @@ impl MinecraftCommand for ShootCommand {
         Commands<'static, 'static>,
     )>;
 
-    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) {
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
         const EYE_HEIGHT: f32 = 1.62;
         const BASE_VELOCITY: f32 = 3.0; // Base velocity multiplier for arrows
 
@@ -32,7 +32,7 @@ impl MinecraftCommand for ShootCommand {
             Ok(data) => data,
             Err(e) => {
                 error!("shoot command failed: query failed: {e}");
-                return;
+                return Err(CommandFailed);
             }
         };
 
@@ -67,5 +67,7 @@ impl MinecraftCommand for ShootCommand {
             Pitch::new(**pitch),
             Channel,
         ));
+
+        Ok(())
     }
 }
@@ -0,0 +1,72 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    command_channel::CommandChannel,
+    net::{Compose, ConnectionId, agnostic},
+    runtime::AsyncRuntime,
+    simulation::{
+        event,
+        skin::{PlayerSkin, SkinSource},
+    },
+    storage::SkinHandler,
+    util::{mineskin::MineSkinClient, mojang::MojangClient},
+};
+use hyperion_clap::{CommandPermission, CommandResult, MinecraftCommand};
+use tracing::{error, warn};
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "skin")]
+#[command_permission(group = "Normal")]
+pub struct SkinCommand {
+    /// The username of a player or the URL of a skin texture
+    skin: String,
+}
+
+impl MinecraftCommand for SkinCommand {
+    type State = SystemState<(
+        Res<'static, AsyncRuntime>,
+        Res<'static, MojangClient>,
+        Res<'static, MineSkinClient>,
+        Res<'static, SkinHandler>,
+        Res<'static, CommandChannel>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (runtime, mojang, mineskin, skins, command_channel) = state.get(world);
+
+        let source = SkinSource::parse(&self.skin);
+        let mojang = mojang.clone();
+        let mineskin = mineskin.clone();
+        let skins = skins.clone();
+        let command_channel = command_channel.clone();
+
+        runtime.spawn(async move {
+            let result = PlayerSkin::from_source(&source, &mojang, &mineskin, &skins).await;
+
+            command_channel.push(move |world: &mut World| {
+                let message = match result {
+                    Ok(Some(skin)) => {
+                        world.send_event(event::SetSkin { skin, by: caller });
+                        return;
+                    }
+                    Ok(None) => format!("§c{source} does not have a skin"),
+                    Err(e) => {
+                        warn!("skin command failed: failed to get skin from {source}: {e}");
+                        format!("§cFailed to get the skin of {source}")
+                    }
+                };
+
+                let Some(&connection_id) = world.get::<ConnectionId>(caller) else {
+                    return;
+                };
+
+                let compose = world.resource::<Compose>();
+                if let Err(e) = compose.unicast(&agnostic::chat(message), connection_id) {
+                    error!("failed to send skin message: {e}");
+                }
+            });
+        });
+
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@ use hyperion::{
     net::{Compose, ConnectionId, agnostic},
     simulation::FlyingSpeed,
 };
-use hyperion_clap::{CommandPermission, MinecraftCommand};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
 use tracing::error;
 
 #[derive(Parser, CommandPermission, Debug)]
@@ -21,23 +21,27 @@ impl MinecraftCommand for SpeedCommand {
         Commands<'static, 'static>,
     )>;
 
-    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) {
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
         let (query, compose, mut commands) = state.get(world);
 
         let &connection_id = match query.get(caller) {
             Ok(connection_id) => connection_id,
             Err(e) => {
                 error!("speed command failed: query failed: {e}");
-                return;
+                return Err(CommandFailed);
             }
         };
 
         let msg = format!("Setting speed to {}", self.amount);
         let chat = agnostic::chat(msg);
-        compose.unicast(&chat, connection_id).unwrap();
+        if let Err(e) = compose.unicast(&chat, connection_id) {
+            error!("failed to send speed message: {e}");
+        }
 
         commands
             .entity(caller)
             .insert(FlyingSpeed::new(self.amount));
+
+        Ok(())
     }
 }
@@ -0,0 +1,101 @@
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    net::{Compose, ConnectionId, agnostic},
+    tick_rate::{MAX_TICK_RATE, MIN_TICK_RATE, TickRate},
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "tick")]
+#[command_permission(group = "Admin")]
+pub enum TickCommand {
+    /// Shows the tick rate and whether the game is frozen
+    Query,
+    /// Stops the game from advancing while players stay connected
+    Freeze,
+    Unfreeze,
+    /// Runs a number of ticks while frozen
+    Step {
+        #[arg(default_value_t = 1)]
+        ticks: u32,
+    },
+    /// Sets the number of ticks per second
+    Rate {
+        rate: f64,
+    },
+}
+
+impl MinecraftCommand for TickCommand {
+    type State = SystemState<(
+        Res<'static, Compose>,
+        Res<'static, TickRate>,
+        Query<'static, 'static, &'static ConnectionId>,
+        Commands<'static, 'static>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (compose, tick_rate, query, mut commands) = state.get(world);
+
+        let &connection_id = match query.get(caller) {
+            Ok(connection_id) => connection_id,
+            Err(e) => {
+                error!("tick command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let mut updated = tick_rate.clone();
+
+        let mut result = Ok(());
+        let msg = match self {
+            Self::Query => {
+                let state = if tick_rate.is_frozen() {
+                    "frozen"
+                } else {
+                    "running"
+                };
+                format!(
+                    "§6The game is {state} at {:.1} ticks per second",
+                    tick_rate.rate()
+                )
+            }
+            Self::Freeze => {
+                updated.set_frozen(true);
+                "§aThe game is frozen".to_string()
+            }
+            Self::Unfreeze => {
+                updated.set_frozen(false);
+                "§aThe game is running".to_string()
+            }
+            Self::Step { ticks } => {
+                if updated.step(ticks) {
+                    format!("§aStepping {ticks} ticks")
+                } else {
+                    result = Err(CommandFailed);
+                    "§cThe game must be frozen to step".to_string()
+                }
+            }
+            Self::Rate { rate } => {
+                if (MIN_TICK_RATE..=MAX_TICK_RATE).contains(&rate) {
+                    updated.set_rate(rate);
+                    format!("§aTick rate set to {rate:.1}")
+                } else {
+                    result = Err(CommandFailed);
+                    format!("§cThe tick rate must be between {MIN_TICK_RATE} and {MAX_TICK_RATE}")
+                }
+            }
+        };
+
+        if let Err(e) = compose.unicast(&agnostic::chat(msg), connection_id) {
+            error!("failed to send tick message: {e}");
+        }
+
+        if updated != *tick_rate {
+            commands.insert_resource(updated);
+        }
+
+        result
+    }
+}
@@ -0,0 +1,92 @@
+use std::path::Path;
+
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    net::{Compose, ConnectionId, DataBundle, agnostic},
+    timings::SystemTimings,
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+/// The file which the timings are written to by `/timings --dump`
+const DUMP_PATH: &str = "timings.json";
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "timings")]
+#[command_permission(group = "Moderator")]
+pub struct TimingsCommand {
+    /// The number of systems to show
+    #[arg(default_value_t = 10)]
+    count: usize,
+
+    /// Write the timings of all systems to a json file
+    #[arg(long)]
+    dump: bool,
+
+    /// Clear the collected timings
+    #[arg(long)]
+    reset: bool,
+}
+
+impl MinecraftCommand for TimingsCommand {
+    type State = SystemState<(
+        Res<'static, Compose>,
+        Query<'static, 'static, &'static ConnectionId>,
+        Commands<'static, 'static>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (compose, query, mut commands) = state.get(world);
+
+        let &connection_id = match query.get(caller) {
+            Ok(connection_id) => connection_id,
+            Err(e) => {
+                error!("timings command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let timings = world.resource::<SystemTimings>();
+        let mut lines = Vec::new();
+        let mut result = Ok(());
+
+        if self.reset {
+            // The world is borrowed immutably while executing commands
+            commands.queue(|world: &mut World| {
+                world.resource_mut::<SystemTimings>().reset();
+            });
+            lines.push("§aTimings have been reset".to_string());
+        } else if self.dump {
+            match timings.dump(Path::new(DUMP_PATH)) {
+                Ok(()) => lines.push(format!("§aTimings written to {DUMP_PATH}")),
+                Err(e) => {
+                    error!("failed to write timings: {e:?}");
+                    lines.push("§cFailed to write timings".to_string());
+                    result = Err(CommandFailed);
+                }
+            }
+        } else {
+            lines.push(format!(
+                "§6Slowest {} systems§r (mean / p95 / p99 / max ms per tick):",
+                self.count
+            ));
+            for timing in timings.report().into_iter().take(self.count) {
+                lines.push(format!(
+                    "  {}: §a{:.3}§r / §e{:.3}§r / §6{:.3}§r / §c{:.3}",
+                    timing.name, timing.mean, timing.p95, timing.p99, timing.max
+                ));
+            }
+        }
+
+        let mut bundle = DataBundle::new(&compose);
+        for line in lines {
+            bundle.add_packet(&agnostic::chat(line)).unwrap();
+        }
+        if let Err(e) = bundle.unicast(connection_id) {
+            error!("failed to send timings: {e}");
+        }
+
+        result
+    }
+}
@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemState, prelude::*};
+use clap::Parser;
+use hyperion::{
+    net::{Compose, ConnectionId, DataBundle, agnostic},
+    tick_health::{TickAverage, TickHealth},
+    tick_rate::TickRate,
+};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
+use tracing::error;
+
+const WINDOWS: [(&str, Duration); 3] = [
+    ("1m", Duration::from_secs(60)),
+    ("5m", Duration::from_secs(5 * 60)),
+    ("15m", Duration::from_secs(15 * 60)),
+];
+
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "tps")]
+#[command_permission(group = "Normal")]
+pub struct TpsCommand;
+
+fn tps_color(tps: f64, rate: f64) -> &'static str {
+    if tps >= rate * 0.9 {
+        "§a"
+    } else if tps >= rate * 0.75 {
+        "§e"
+    } else {
+        "§c"
+    }
+}
+
+fn mspt_color(mspt: f64, budget: f64) -> &'static str {
+    if mspt <= budget * 0.8 {
+        "§a"
+    } else if mspt <= budget {
+        "§e"
+    } else {
+        "§c"
+    }
+}
+
+impl MinecraftCommand for TpsCommand {
+    type State = SystemState<(
+        Res<'static, Compose>,
+        Res<'static, TickHealth>,
+        Res<'static, TickRate>,
+        Query<'static, 'static, &'static ConnectionId>,
+    )>;
+
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
+        let (compose, health, tick_rate, query) = state.get(world);
+
+        let &connection_id = match query.get(caller) {
+            Ok(connection_id) => connection_id,
+            Err(e) => {
+                error!("tps command failed: query failed: {e}");
+                return Err(CommandFailed);
+            }
+        };
+
+        let averages = WINDOWS.map(|(_, window)| health.average(window));
+
+        let rate = tick_rate.rate();
+        let budget = health.budget().as_secs_f64() * 1000.0;
+
+        let format = |value: fn(&TickAverage) -> f64, color: &dyn Fn(f64) -> &'static str| {
+            averages
+                .iter()
+                .map(|average| {
+                    average.as_ref().map_or_else(
+                        || "-".to_string(),
+                        |average| {
+                            let value = value(average);
+                            format!("{}{value:.2}§r", color(value))
+                        },
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let windows = WINDOWS.map(|(name, _)| name).join(", ");
+
+        let mut lines = vec![
+            format!(
+                "§6TPS from last {windows}§r: {}",
+                format(|average| average.tps, &|tps| tps_color(tps, rate))
+            ),
+            format!(
+                "§6MSPT from last {windows}§r: {}",
+                format(|average| average.mspt, &|mspt| mspt_color(mspt, budget))
+            ),
+        ];
+
+        if tick_rate.is_frozen() {
+            lines.push("§bThe game is frozen".to_string());
+        }
+
+        if health.is_overloaded() {
+            lines.push(format!(
+                "§cThe server is overloaded ({} consecutive ticks over {} ms)",
+                health.consecutive_overloaded(),
+                health.budget().as_millis()
+            ));
+        }
+
+        let mut bundle = DataBundle::new(&compose);
+        for line in lines {
+            bundle.add_packet(&agnostic::chat(line)).unwrap();
+        }
+        if let Err(e) = bundle.unicast(connection_id) {
+            error!("failed to send tps: {e}");
+        }
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use bevy::{ecs::system::SystemState, prelude::*};
 use clap::Parser;
 use hyperion::net::{Compose, ConnectionId};
-use hyperion_clap::{CommandPermission, MinecraftCommand};
+use hyperion_clap::{CommandFailed, CommandPermission, CommandResult, MinecraftCommand};
 use tracing::error;
 
 use crate::plugin::vanish::Vanished;
@@ -26,14 +26,14 @@ impl MinecraftCommand for VanishCommand {
         Commands<'static, 'static>,
     )>;
 
-    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) {
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
         let (query, compose, mut commands) = state.get(world);
 
         let (&connection_id, name, vanished) = match query.get(caller) {
             Ok(data) => data,
             Err(e) => {
                 error!("vanish command failed: query failed: {e}");
-                return;
+                return Err(CommandFailed);
             }
         };
 
@@ -45,6 +45,10 @@ impl MinecraftCommand for VanishCommand {
             "§7[Admin] §f{name} §7is now {}",
             if is_vanished { "vanished" } else { "visible" }
         ));
-        compose.unicast(&packet, connection_id).unwrap();
+        if let Err(e) = compose.unicast(&packet, connection_id) {
+            error!("failed to send vanish packet: {e}");
+        }
+
+        Ok(())
     }
 }
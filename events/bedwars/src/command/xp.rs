@@ -1,7 +1,7 @@
 use bevy::{ecs::system::SystemState, prelude::*};
 use clap::Parser;
 use hyperion::simulation::Xp;
-use hyperion_clap::{CommandPermission, MinecraftCommand};
+use hyperion_clap::{CommandPermission, CommandResult, MinecraftCommand};
 
 #[derive(Parser, CommandPermission, Debug)]
 #[command(name = "xp")]
@@ -13,10 +13,12 @@ pub struct XpCommand {
 impl MinecraftCommand for XpCommand {
     type State = SystemState<Commands<'static, 'static>>;
 
-    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) {
+    fn execute(self, world: &World, state: &mut Self::State, caller: Entity) -> CommandResult {
         let mut commands = state.get(world);
         commands.entity(caller).insert(Xp {
             amount: self.amount,
         });
+
+        Ok(())
     }
 }
@@ -6,15 +6,21 @@
 use std::net::SocketAddr;
 
 use bevy::prelude::*;
-use hyperion::{Crypto, Endpoint, HyperionCore, simulation::packet_state, spatial::Spatial};
+use hyperion::{
+    CryptoPaths, Endpoint, HyperionCore, ItemKind, ItemStack, Particle, simulation::packet_state,
+    spatial::Spatial,
+};
+use hyperion_cosmetics::{Cosmetic, CosmeticEffect, Cosmetics};
 use hyperion_proxy_module::SetProxyAddress;
+use hyperion_quests::{Objective, Quest, Quests};
+use hyperion_shop::{Shop, ShopEntry, Shops};
 use valence_text::IntoText;
 
 use crate::{
     plugin::{
-        attack::AttackPlugin, block::BlockPlugin, bow::BowPlugin, chat::ChatPlugin,
-        damage::DamagePlugin, regeneration::RegenerationPlugin, spawn::SpawnPlugin,
-        stats::StatsPlugin, vanish::VanishPlugin,
+        attack::AttackPlugin, block::BlockPlugin, chat::ChatPlugin, damage::DamagePlugin,
+        regeneration::RegenerationPlugin, spawn::SpawnPlugin, stats::StatsPlugin,
+        vanish::VanishPlugin,
     },
     skin::SkinPlugin,
 };
@@ -107,6 +113,85 @@ fn initialize_player(
         .insert((Spatial, Team::Red));
 }
 
+fn define_cosmetics(cosmetics: &mut Cosmetics) {
+    cosmetics.define(
+        "flame_trail",
+        Cosmetic::new("Flame Trail", CosmeticEffect::Trail {
+            particle: Particle::Flame,
+            count: 2,
+        }),
+    );
+    cosmetics.define(
+        "heart_trail",
+        Cosmetic::new("Heart Trail", CosmeticEffect::Trail {
+            particle: Particle::Heart,
+            count: 1,
+        }),
+    );
+    cosmetics.define(
+        "totem_burst",
+        Cosmetic::new("Totem Burst", CosmeticEffect::KillEffect {
+            particle: Particle::TotemOfUndying,
+            count: 50,
+        }),
+    );
+    cosmetics.define(
+        "explosion",
+        Cosmetic::new("Explosion", CosmeticEffect::KillEffect {
+            particle: Particle::Explosion,
+            count: 3,
+        }),
+    );
+    cosmetics.define(
+        "champion",
+        Cosmetic::new(
+            "Champion",
+            CosmeticEffect::ChatPrefix("§6[Champion] ".to_owned()),
+        ),
+    );
+}
+
+fn define_quests(quests: &mut Quests) {
+    quests.define(
+        "first_blood",
+        Quest::new("First Blood", Objective::KillPlayers { count: 1 }).with_reward(ItemStack::new(
+            ItemKind::GoldenApple,
+            1,
+            None,
+        )),
+    );
+    quests.define(
+        "hunter",
+        Quest::new("Hunter", Objective::KillPlayers { count: 10 }).with_reward(ItemStack::new(
+            ItemKind::DiamondSword,
+            1,
+            None,
+        )),
+    );
+    quests.define(
+        "miner",
+        Quest::new("Miner", Objective::BreakBlocks { count: 64 }).with_reward(ItemStack::new(
+            ItemKind::IronPickaxe,
+            1,
+            None,
+        )),
+    );
+}
+
+fn define_shops(shops: &mut Shops) {
+    shops.define(
+        "bedwars",
+        Shop::new("Item Shop")
+            .with_entry(ShopEntry::new(ItemKind::WhiteWool, 16).buy(4).sell(2))
+            .with_entry(ShopEntry::new(ItemKind::EndStone, 12).buy(24).sell(12))
+            .with_entry(ShopEntry::new(ItemKind::StoneSword, 1).buy(10))
+            .with_entry(ShopEntry::new(ItemKind::IronSword, 1).buy(70))
+            .with_entry(ShopEntry::new(ItemKind::Shears, 1).buy(20))
+            .with_entry(ShopEntry::new(ItemKind::GoldenApple, 1).buy(30))
+            .with_entry(ShopEntry::new(ItemKind::Arrow, 8).buy(2)),
+    );
+}
+
 #[derive(Component)]
 pub struct BedwarsPlugin;
 
@@ -116,7 +201,6 @@ impl Plugin for BedwarsPlugin {
             (
                 AttackPlugin,
                 BlockPlugin,
-                BowPlugin,
                 ChatPlugin,
                 DamagePlugin,
                 RegenerationPlugin,
@@ -125,23 +209,51 @@ impl Plugin for BedwarsPlugin {
                 StatsPlugin,
                 VanishPlugin,
             ),
-            hyperion_clap::ClapCommandPlugin,
-            hyperion_genmap::GenMapPlugin,
-            hyperion_item::ItemPlugin,
-            hyperion_permission::PermissionPlugin,
-            hyperion_proxy_module::HyperionProxyPlugin,
+            (
+                hyperion_admin::AdminPlugin,
+                hyperion_audit::AuditPlugin::default(),
+                hyperion_backup::BackupPlugin::default(),
+                hyperion_blocklog::BlockLogPlugin::default(),
+                hyperion_bow::BowPlugin,
+                hyperion_bridge::BridgePlugin,
+                hyperion_clap::ClapCommandPlugin,
+                hyperion_combat_tag::CombatTagPlugin::default(),
+                hyperion_cosmetics::CosmeticsPlugin,
+                hyperion_economy::EconomyPlugin::default(),
+                hyperion_fishing::FishingPlugin,
+                hyperion_genmap::GenMapPlugin::default(),
+                hyperion_gui::GuiPlugin,
+            ),
+            (
+                hyperion_item::ItemPlugin,
+                hyperion_kits::KitsPlugin,
+                hyperion_permission::PermissionPlugin,
+                hyperion_protect::ProtectPlugin,
+                hyperion_proxy_module::HyperionProxyPlugin,
+                hyperion_quests::QuestsPlugin,
+                hyperion_shop::ShopPlugin::default(),
+                hyperion_voice::VoicePlugin::default(),
+                hyperion_warps::WarpsPlugin::default(),
+                hyperion_worldedit::WorldEditPlugin,
+            ),
         ));
         app.add_observer(initialize_player);
 
+        define_cosmetics(&mut app.world_mut().resource_mut::<Cosmetics>());
+        define_quests(&mut app.world_mut().resource_mut::<Quests>());
+        define_shops(&mut app.world_mut().resource_mut::<Shops>());
         command::register(app.world_mut());
     }
 }
 
-pub fn init_game(address: SocketAddr, crypto: Crypto) -> anyhow::Result<()> {
+pub fn init_game(address: SocketAddr, crypto_paths: CryptoPaths) -> anyhow::Result<()> {
     let mut app = App::new();
 
+    let crypto = crypto_paths.load()?;
+
     app.insert_resource(Endpoint::from(address));
     app.insert_resource(crypto);
+    app.insert_resource(crypto_paths);
     app.add_plugins((HyperionCore, BedwarsPlugin));
     app.world_mut().trigger(SetProxyAddress {
         server: address.to_string(),
@@ -2,7 +2,7 @@ use std::{net::SocketAddr, path::PathBuf};
 
 use bedwars::init_game;
 use clap::Parser;
-use hyperion::Crypto;
+use hyperion::CryptoPaths;
 use serde::Deserialize;
 use tracing_subscriber::{EnvFilter, Registry, layer::SubscriberExt};
 // use tracing_tracy::TracyLayer;
@@ -83,7 +83,11 @@ fn main() {
 
     let address = format!("{ip}:{port}", ip = args.ip, port = args.port);
     let address = address.parse::<SocketAddr>().unwrap();
-    let crypto = Crypto::new(&args.root_ca_cert, &args.cert, &args.private_key).unwrap();
+    let crypto_paths = CryptoPaths {
+        root_ca_cert: args.root_ca_cert,
+        cert: args.cert,
+        key: args.private_key,
+    };
 
-    init_game(address, crypto).unwrap();
+    init_game(address, crypto_paths).unwrap();
 }
@@ -1,6 +1,5 @@
 pub mod attack;
 pub mod block;
-pub mod bow;
 pub mod chat;
 pub mod damage;
 pub mod regeneration;
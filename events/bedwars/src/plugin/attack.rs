@@ -1,25 +1,26 @@
-use std::borrow::Cow;
-
 use bevy::prelude::*;
 use derive_more::with_trait::Add;
 use hyperion::{
-    BlockKind, ingress,
-    net::{Compose, ConnectionId, agnostic},
+    BlockKind,
+    config::Config,
+    ingress,
+    net::{Compose, ConnectionId, Priority, agnostic},
     runtime::AsyncRuntime,
     simulation::{
-        PendingTeleportation, Position, Velocity, Yaw, blocks::Blocks, event,
-        metadata::living_entity::Health, packet::play, packet_state,
+        PendingTeleportation, Position, Velocity, WorldSpawn, Yaw, blocks::Blocks, event,
+        fire::Burning, metadata::living_entity::Health, packet::play, packet_state,
+        totem::try_use_totem,
     },
+    timings::timed,
 };
 use hyperion_inventory::PlayerInventory;
-use hyperion_utils::{EntityExt, Prev};
+use hyperion_utils::EntityExt;
 use tracing::error;
 use valence_protocol::{
-    ItemKind, ItemStack, Particle, VarInt, ident,
+    ItemKind, ItemStack, VarInt,
     math::{DVec3, Vec3},
     packets::play::{
-        DamageTiltS2c, DeathMessageS2c, EntityDamageS2c, GameMessageS2c, ParticleS2c,
-        client_status_c2s::ClientStatusC2s, player_interact_entity_c2s::EntityInteraction,
+        DamageTiltS2c, DeathMessageS2c, GameMessageS2c, client_status_c2s::ClientStatusC2s,
     },
     text::IntoText,
 };
@@ -29,6 +30,9 @@ use crate::Team;
 
 pub struct AttackPlugin;
 
+/// The id of `minecraft:player_attack` in the `minecraft:damage_type` registry
+const PLAYER_ATTACK: i32 = 31;
+
 #[derive(Component, Default, Copy, Clone, Debug)]
 pub struct ImmuneUntil {
     tick: i64,
@@ -57,22 +61,6 @@ const fn check_and_update_immunity(tick: i64, immune_until: &mut ImmuneUntil) ->
     false
 }
 
-fn is_critical_hit(prev_position: Prev<Position>, position: Position) -> bool {
-    // TODO: Do not allow critical hits if the player is on a ladder, vine, or water. None of
-    // these special blocks are currently on the map.
-    let position_delta_y = position.y - prev_position.y;
-    position_delta_y < 0.0
-}
-
-fn total_combat_stats(
-    critical_hit: bool,
-    inventory: &PlayerInventory,
-    stats: CombatStats,
-) -> CombatStats {
-    let inventory_combat_stats = calculate_stats(inventory, critical_hit);
-    stats + inventory_combat_stats
-}
-
 fn initialize_player(
     trigger: Trigger<'_, OnAdd, packet_state::Play>,
     mut commands: Commands<'_, '_>,
@@ -82,77 +70,8 @@ fn initialize_player(
         .insert((ImmuneUntil::default(), CombatStats::default()));
 }
 
-fn handle_melee_attacks(
-    mut packets: EventReader<'_, '_, play::PlayerInteractEntity>,
-    origin_query: Query<'_, '_, (&Position, &PlayerInventory, &CombatStats)>,
-    target_query: Query<'_, '_, (&Prev<Position>, &Position)>,
-    mut world_and_writer: ParamSet<'_, '_, (&World, EventWriter<'_, event::AttackEntity>)>,
-) {
-    for packet in packets.read() {
-        if packet.interact != EntityInteraction::Attack {
-            continue;
-        }
-
-        // Player who is attacking the target
-        let origin = packet.sender();
-
-        // Player who is being attacked by the attacker
-        let target = match Entity::from_minecraft_id(packet.entity_id.0, world_and_writer.p0()) {
-            Ok(target) => target,
-            Err(e) => {
-                error!("handle melee attack failed: target id is invalid: {e}");
-                continue;
-            }
-        };
-
-        let (&origin_pos, origin_inventory, &origin_stats) = match origin_query.get(origin) {
-            Ok(data) => data,
-            Err(e) => {
-                error!("handle melee attack failed: query failed: {e}");
-                continue;
-            }
-        };
-
-        let (&target_prev_pos, &target_pos) = match target_query.get(target) {
-            Ok(data) => data,
-            Err(e) => {
-                error!("handle melee attack failed: query failed: {e}");
-                continue;
-            }
-        };
-
-        let is_critical_hit = is_critical_hit(target_prev_pos, target_pos);
-        let combat_stats = total_combat_stats(is_critical_hit, origin_inventory, origin_stats);
-
-        let damage_after_armor =
-            get_damage_left(1.0, combat_stats.armor, combat_stats.armor_toughness);
-        let damage_after_protection =
-            get_inflicted_damage(damage_after_armor, combat_stats.protection);
-
-        world_and_writer.p1().write(event::AttackEntity {
-            origin,
-            target,
-            direction: (target_pos - origin_pos).normalize(),
-            damage: damage_after_protection,
-            sound: if is_critical_hit {
-                ident!("minecraft:entity.player.attack.crit")
-            } else {
-                ident!("minecraft:entity.player.attack.knockback")
-            },
-            particles: is_critical_hit.then(|| ParticleS2c {
-                particle: Cow::Owned(Particle::Crit),
-                long_distance: true,
-                position: target_pos.as_dvec3() + DVec3::new(0.0, 1.0, 0.0),
-                max_speed: 0.5,
-                count: 100,
-                offset: Vec3::new(0.5, 0.5, 0.5),
-            }),
-        });
-    }
-}
-
 fn handle_attacks(
-    mut events: EventReader<'_, '_, event::AttackEntity>,
+    mut events: EventReader<'_, '_, event::Cancellable<event::AttackEntity>>,
     compose: Res<'_, Compose>,
     mut origin_query: Query<'_, '_, (&Team, &Name, &ConnectionId)>,
     mut target_query: Query<
@@ -166,13 +85,16 @@ fn handle_attacks(
             &mut ImmuneUntil,
             &mut Health,
             &mut Velocity,
+            Option<&mut PlayerInventory>,
+            Option<&CombatStats>,
         ),
     >,
+    mut commands: Commands<'_, '_>,
 ) {
     let current_tick = compose.global().tick;
 
     for event in events.read() {
-        if event.damage <= 0.0 {
+        if event.is_cancelled() || event.damage <= 0.0 {
             continue;
         }
 
@@ -193,6 +115,8 @@ fn handle_attacks(
             mut target_immune_until,
             mut target_health,
             mut target_velocity,
+            mut target_inventory,
+            target_stats,
         ) = match target_query.get_mut(event.target) {
             Ok(data) => data,
             Err(e) => {
@@ -208,7 +132,9 @@ fn handle_attacks(
                 overlay: false,
             };
 
-            compose.unicast(&pkt_msg, origin_connection).unwrap();
+            if let Err(e) = compose.unicast(&pkt_msg, origin_connection) {
+                error!("failed to send attack message: {e}");
+            }
 
             continue;
         }
@@ -225,15 +151,20 @@ fn handle_attacks(
             .seed(fastrand::i64(..))
             .build();
 
-        compose.broadcast(&sound).send().unwrap();
+        if let Err(e) = compose.broadcast(&sound).send() {
+            error!("failed to send attack sound: {e}");
+        }
 
         // Broadcast particles
         if let Some(particles) = &event.particles {
-            compose
+            if let Err(e) = compose
                 .broadcast(particles)
                 .exclude(origin_connection)
+                .priority(Priority::Bulk)
                 .send()
-                .unwrap();
+            {
+                error!("failed to send attack particles: {e}");
+            }
         }
 
         let delta_x: f64 = f64::from(event.direction.x);
@@ -249,19 +180,44 @@ fn handle_attacks(
                 .mul_add(57.295_776_367_187_5_f64, -f64::from(*target_yaw)) as f32,
         };
 
-        compose.unicast(&pkt_hurt, target_connection).unwrap();
+        if let Err(e) = compose.unicast(&pkt_hurt, target_connection) {
+            error!("failed to send hurt packet: {e}");
+        }
 
-        target_health.damage(event.damage);
+        let armor = target_stats.copied().unwrap_or_default()
+            + target_inventory
+                .as_deref()
+                .map(calculate_stats)
+                .unwrap_or_default();
+        let damage_after_armor = get_damage_left(event.damage, armor.armor, armor.armor_toughness);
+        let damage_after_protection = get_inflicted_damage(damage_after_armor, armor.protection);
+
+        target_health.damage(damage_after_protection);
+
+        if let Some(inventory) = target_inventory.as_deref_mut() {
+            try_use_totem(
+                &compose,
+                &mut commands,
+                event.target,
+                &mut target_health,
+                inventory,
+            );
+        }
 
         if target_health.is_dead() {
+            commands.send_event(event::PlayerDeath {
+                victim: event.target,
+                killer: Some(event.origin),
+            });
+
             // Even if enable_respawn_screen is false, the client needs this to send ClientCommandC2s and initiate its respawn
             let pkt_death_screen = DeathMessageS2c {
                 player_id: VarInt(event.target.minecraft_id()),
                 message: format!("You were killed by {origin_name}").into_cow_text(),
             };
-            compose
-                .unicast(&pkt_death_screen, target_connection)
-                .unwrap();
+            if let Err(e) = compose.unicast(&pkt_death_screen, target_connection) {
+                error!("failed to send death screen: {e}");
+            }
         } else {
             // Calculate velocity change based on attack direction
             let knockback_xz = 8.0;
@@ -274,17 +230,27 @@ fn handle_attacks(
             );
 
             target_velocity.0 += new_vel;
+
+            // Knockback from enchantments such as Punch
+            let extra = event.direction.with_y(0.0).normalize_or_zero() * event.extra_knockback;
+            if extra != Vec3::ZERO {
+                target_velocity.0 += extra.with_y(0.1);
+            }
+
+            if event.fire_ticks > 0 {
+                commands.entity(event.target).insert(Burning {
+                    ticks: event.fire_ticks,
+                });
+            }
         }
 
-        // EntityDamageS2c: display red outline when taking damage (play arrow hit sound?)
-        let pkt_damage_event = EntityDamageS2c {
-            entity_id: VarInt(event.target.minecraft_id()),
-            source_cause_id: VarInt(event.origin.minecraft_id() + 1), // this is an OptVarint
-            source_direct_id: VarInt(event.origin.minecraft_id() + 1), /* if hit by a projectile, it should be the projectile's entity id */
-            source_type_id: VarInt(31),                                // 31 = player_attack
-            source_pos: None,
-        };
-        compose.broadcast(&pkt_damage_event).send().unwrap();
+        commands.send_event(event::Hurt {
+            target: event.target,
+            damage_type: PLAYER_ATTACK,
+            source_cause: Some(event.origin),
+            // If hit by a projectile, this should be the projectile
+            source_direct: Some(event.origin),
+        });
     }
 }
 
@@ -292,6 +258,8 @@ fn handle_respawn(
     mut packets: EventReader<'_, '_, play::ClientStatus>,
     query: Query<'_, '_, &Team>,
     candidates_query: Query<'_, '_, (Entity, &Position, &Team)>,
+    spawn: Res<'_, WorldSpawn>,
+    config: Res<'_, Config>,
     mut blocks: ResMut<'_, Blocks>,
     runtime: Res<'_, AsyncRuntime>,
     mut commands: Commands<'_, '_>,
@@ -322,7 +290,7 @@ fn handle_respawn(
             get_respawn_pos(&blocks, &random_mate).as_vec3()
         } else {
             // There are no other teammates, so spawn the player in a random location
-            find_spawn_position(&mut blocks, &runtime, &avoid_blocks())
+            find_spawn_position(&spawn, &config, &mut blocks, &runtime, &avoid_blocks())
         };
 
         commands
@@ -340,8 +308,8 @@ impl Plugin for AttackPlugin {
         app.add_systems(
             FixedUpdate,
             (
-                (handle_melee_attacks, handle_attacks).chain(),
-                handle_respawn,
+                timed(handle_attacks).after(event::CancelEvents),
+                timed(handle_respawn),
             )
                 .after(ingress::decode::play),
         );
@@ -387,18 +355,6 @@ fn get_inflicted_damage(damage: f32, protection: f32) -> f32 {
     damage * (1.0 - f / 25.0)
 }
 
-const fn calculate_damage(item: &ItemStack) -> f32 {
-    match item.item {
-        ItemKind::WoodenSword | ItemKind::GoldenSword => 4.0,
-        ItemKind::StoneSword => 5.0,
-        ItemKind::IronSword => 6.0,
-        ItemKind::DiamondSword => 7.0,
-        ItemKind::NetheriteSword => 8.0,
-        ItemKind::WoodenPickaxe => 2.0,
-        _ => 1.0,
-    }
-}
-
 const fn calculate_armor(item: &ItemStack) -> f32 {
     match item.item {
         ItemKind::LeatherHelmet
@@ -440,10 +396,7 @@ const fn calculate_toughness(item: &ItemStack) -> f32 {
 }
 
 // TODO: split this up into separate functions
-fn calculate_stats(inventory: &PlayerInventory, critical_hit: bool) -> CombatStats {
-    let hand = inventory.get_cursor();
-    let multiplier = if critical_hit { 1.5 } else { 1.0 };
-    let damage = calculate_damage(&hand.stack) * multiplier;
+fn calculate_stats(inventory: &PlayerInventory) -> CombatStats {
     let armor = calculate_armor(&inventory.get_helmet().stack)
         + calculate_armor(&inventory.get_chestplate().stack)
         + calculate_armor(&inventory.get_leggings().stack)
@@ -457,7 +410,7 @@ fn calculate_stats(inventory: &PlayerInventory, critical_hit: bool) -> CombatSta
     CombatStats {
         armor,
         armor_toughness,
-        damage,
+        damage: 0.0,
         // TODO
         protection: 0.0,
     }
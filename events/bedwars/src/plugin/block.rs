@@ -6,6 +6,7 @@ use hyperion::{
         blocks::{Blocks, EntityAndSequence},
         event,
     },
+    timings::timed,
     valence_protocol::{
         BlockPos,
         block::{PropName, PropValue},
@@ -13,10 +14,11 @@ use hyperion::{
         packets::play,
     },
 };
+use hyperion_blocklog::RecordBlockChanges;
 use tracing::error;
 
 fn handle_destroyed_blocks(
-    mut events: EventReader<'_, '_, event::DestroyBlock>,
+    mut events: EventReader<'_, '_, event::Cancellable<event::DestroyBlock>>,
     compose: Res<'_, Compose>,
     mut blocks: ResMut<'_, Blocks>,
     query: Query<'_, '_, &ConnectionId>,
@@ -43,24 +45,51 @@ fn handle_destroyed_blocks(
             block_id: current,
         };
 
-        compose.unicast(&pkt, connection_id).unwrap();
+        if let Err(e) = compose.unicast(&pkt, connection_id) {
+            error!("failed to send block update: {e}");
+        }
     }
 }
 
 fn handle_placed_blocks(
-    mut events: EventReader<'_, '_, event::PlaceBlock>,
+    mut events: EventReader<'_, '_, event::Cancellable<event::PlaceBlock>>,
     mut blocks: ResMut<'_, Blocks>,
     compose: Res<'_, Compose>,
     query: Query<'_, '_, &ConnectionId>,
 ) {
-    for event::PlaceBlock {
-        position,
-        block,
-        from,
-        sequence,
-    } in events.read()
-    {
-        let &connection_id = match query.get(*from) {
+    for event in events.read() {
+        let event::PlaceBlock {
+            position,
+            block,
+            from,
+            sequence,
+        } = **event;
+
+        if event.is_cancelled() {
+            // The client predicted that the block would be placed, so it is told which block is
+            // actually there when the sequence is confirmed
+            blocks
+                .to_confirm
+                .push(EntityAndSequence::new(from, sequence));
+
+            let Ok(&connection_id) = query.get(from) else {
+                continue;
+            };
+
+            if let Some(current) = blocks.get_block(position) {
+                let pkt = play::BlockUpdateS2c {
+                    position: BlockPos::new(position.x, position.y, position.z),
+                    block_id: current,
+                };
+                if let Err(e) = compose.unicast(&pkt, connection_id) {
+                    error!("failed to send block update: {e}");
+                }
+            }
+
+            continue;
+        }
+
+        let &connection_id = match query.get(from) {
             Ok(data) => data,
             Err(e) => {
                 error!("failed to handle placed blocks: query failed: {e}");
@@ -71,31 +100,41 @@ fn handle_placed_blocks(
         if block.collision_shapes().is_empty() {
             blocks
                 .to_confirm
-                .push(EntityAndSequence::new(*from, *sequence));
+                .push(EntityAndSequence::new(from, sequence));
 
             // so we send update to player
 
             let msg = chat!("§cYou can't place this block");
 
-            compose.unicast(&msg, connection_id).unwrap();
+            if let Err(e) = compose.unicast(&msg, connection_id) {
+                error!("failed to send block message: {e}");
+            }
 
             continue;
         }
 
-        blocks.set_block(*position, *block).unwrap();
+        blocks.set_block(position, block).unwrap();
 
         blocks.to_confirm.push(EntityAndSequence {
-            entity: *from,
-            sequence: *sequence,
+            entity: from,
+            sequence,
         });
     }
 }
 
 fn handle_toggled_doors(
-    mut events: EventReader<'_, '_, event::ToggleDoor>,
+    mut events: EventReader<'_, '_, event::Cancellable<event::ToggleDoor>>,
     mut blocks: ResMut<'_, Blocks>,
 ) {
     for event in events.read() {
+        if event.is_cancelled() {
+            blocks.to_confirm.push(EntityAndSequence {
+                entity: event.from,
+                sequence: event.sequence,
+            });
+            continue;
+        }
+
         let position = event.position;
 
         // The block is fetched again instead of sending the expected block state
@@ -158,10 +197,12 @@ impl Plugin for BlockPlugin {
         app.add_systems(
             FixedUpdate,
             (
-                handle_destroyed_blocks,
-                handle_placed_blocks,
-                handle_toggled_doors,
-            ),
+                timed(handle_destroyed_blocks),
+                timed(handle_placed_blocks),
+                timed(handle_toggled_doors),
+            )
+                .after(event::CancelEvents)
+                .after(RecordBlockChanges),
         );
     }
 }
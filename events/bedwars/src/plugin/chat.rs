@@ -1,11 +1,17 @@
 use bevy::prelude::*;
 use hyperion::{
     ingress,
-    net::{Compose, ConnectionId},
-    simulation::{Position, packet, packet_state},
+    net::{Compose, ConnectionId, agnostic},
+    simulation::{
+        ChatPrefix, Position,
+        event::ChatSent,
+        packet, packet_state,
+        player_list::{Nickname, profile_name},
+    },
+    timings::timed,
     valence_protocol::{
         packets::play,
-        text::{Color, IntoText, Text},
+        text::{IntoText, Text},
     },
 };
 use tracing::error;
@@ -31,19 +37,33 @@ pub fn initialize_cooldown(
 
 pub fn handle_chat_messages(
     mut packets: EventReader<'_, '_, packet::play::ChatMessage>,
+    mut sent: EventWriter<'_, ChatSent>,
     compose: Res<'_, Compose>,
-    mut query: Query<'_, '_, (&Name, &Position, &mut ChatCooldown, &ConnectionId, &Team)>,
+    mut query: Query<
+        '_,
+        '_,
+        (
+            &Name,
+            &Position,
+            &mut ChatCooldown,
+            &ConnectionId,
+            &Team,
+            Option<&ChatPrefix>,
+            Option<&Nickname>,
+        ),
+    >,
 ) {
     let current_tick = compose.global().tick;
 
     for packet in packets.read() {
-        let (name, position, mut cooldown, io, team) = match query.get_mut(packet.sender()) {
-            Ok(data) => data,
-            Err(e) => {
-                error!("could not process chat message: query failed: {e}");
-                continue;
-            }
-        };
+        let (name, position, mut cooldown, io, team, prefix, nickname) =
+            match query.get_mut(packet.sender()) {
+                Ok(data) => data,
+                Err(e) => {
+                    error!("could not process chat message: query failed: {e}");
+                    continue;
+                }
+            };
 
         // Check if player is still on cooldown
         if cooldown.expires > current_tick {
@@ -59,25 +79,33 @@ pub fn handle_chat_messages(
                 overlay: false,
             };
 
-            compose.unicast(&packet, *io).unwrap();
+            if let Err(e) = compose.unicast(&packet, *io) {
+                error!("failed to send chat cooldown message: {e}");
+            }
             continue;
         }
 
         cooldown.expires = current_tick + CHAT_COOLDOWN_TICKS;
 
-        let chat = Text::default()
-            + "<".color(Color::DARK_GRAY)
-            + name.as_str().to_owned().color(*team)
-            + "> ".color(Color::DARK_GRAY)
-            + (**packet.message).to_owned();
-        let packet = play::GameMessageS2c {
-            chat: chat.into(),
-            overlay: false,
-        };
+        let mut sender = Text::default();
+        if let Some(prefix) = prefix {
+            sender += prefix.0.clone();
+        }
+        sender += profile_name(name, nickname).to_owned().color(*team);
+
+        let message: &str = &packet.message;
+        let chat = agnostic::player_chat(sender, message);
 
         let center = position.to_chunk();
 
-        compose.broadcast_local(&packet, center).send().unwrap();
+        if let Err(e) = compose.broadcast_local(&chat, center).send() {
+            error!("failed to send chat message: {e}");
+        }
+
+        sent.write(ChatSent {
+            sender: packet.sender(),
+            message: message.to_owned(),
+        });
     }
 }
 
@@ -88,7 +116,7 @@ impl Plugin for ChatPlugin {
         app.add_observer(initialize_cooldown);
         app.add_systems(
             FixedUpdate,
-            handle_chat_messages.after(ingress::decode::play),
+            timed(handle_chat_messages).after(ingress::decode::play),
         );
     }
 }
@@ -1,48 +1,130 @@
 use bevy::prelude::*;
 use hyperion::{
     net::{Compose, ConnectionId, agnostic},
-    simulation::{Position, event::HitGroundEvent, metadata::living_entity::Health},
+    simulation::{
+        Position,
+        event::{HitGroundEvent, Hurt, PlayerDeath},
+        metadata::living_entity::Health,
+        totem::try_use_totem,
+    },
+    timings::timed,
 };
+use hyperion_inventory::PlayerInventory;
 use hyperion_utils::EntityExt;
 use tracing::error;
 use valence_protocol::{VarInt, packets::play, text::IntoText};
 use valence_server::ident;
 
-fn apply_natural_damages(
+/// Players take void damage 64 blocks below the bottom of the world, which matches vanilla
+const VOID_Y: f32 = -128.0;
+
+/// The damage taken every tick in the void
+const VOID_DAMAGE: f32 = 4.0;
+
+/// Damage types from the `minecraft:damage_type` registry
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DamageType {
+    Fall,
+    OutOfWorld,
+}
+
+impl DamageType {
+    const fn id(self) -> i32 {
+        match self {
+            Self::Fall => 8,
+            Self::OutOfWorld => 29,
+        }
+    }
+
+    /// Void damage cannot be prevented by a totem of undying in vanilla
+    const fn bypasses_totems(self) -> bool {
+        matches!(self, Self::OutOfWorld)
+    }
+}
+
+/// Damage dealt to a player which was not caused by another entity
+#[derive(Copy, Clone, Debug)]
+pub struct Damage<'a> {
+    pub amount: f32,
+    pub damage_type: DamageType,
+    /// Shown on the death screen if the player dies
+    pub death_message: &'a str,
+}
+
+/// Damages a player, showing the damage to them and sending the death screen if they die. A
+/// totem of undying in `inventory` saves the player from dying unless the damage bypasses totems.
+pub fn damage_player(
+    compose: &Compose,
+    commands: &mut Commands<'_, '_>,
+    player: Entity,
+    connection_id: ConnectionId,
+    health: &mut Health,
+    inventory: Option<&mut PlayerInventory>,
+    damage: &Damage<'_>,
+) {
+    if damage.amount <= 0. || health.is_dead() {
+        return;
+    }
+
+    health.damage(damage.amount);
+
+    commands.send_event(Hurt {
+        target: player,
+        damage_type: damage.damage_type.id(),
+        source_cause: None,
+        source_direct: None,
+    });
+
+    if !damage.damage_type.bypasses_totems()
+        && let Some(inventory) = inventory
+    {
+        try_use_totem(compose, commands, player, health, inventory);
+    }
+
+    if health.is_dead() {
+        commands.send_event(PlayerDeath {
+            victim: player,
+            killer: None,
+        });
+
+        let pkt_death_screen = play::DeathMessageS2c {
+            player_id: VarInt(player.minecraft_id()),
+            message: damage.death_message.to_string().into_cow_text(),
+        };
+        if let Err(e) = compose.unicast(&pkt_death_screen, connection_id) {
+            error!("failed to send death screen: {e}");
+        }
+    }
+}
+
+fn apply_fall_damage(
     mut events: EventReader<'_, '_, HitGroundEvent>,
-    mut query: Query<'_, '_, (&mut Health, &ConnectionId, &Position)>,
+    mut query: Query<
+        '_,
+        '_,
+        (
+            &mut Health,
+            &ConnectionId,
+            &Position,
+            Option<&mut PlayerInventory>,
+        ),
+    >,
     compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
 ) {
     for event in events.read() {
-        if event.fall_distance <= 3. {
-            continue;
-        }
-
-        // TODO account for armor/effects and gamemode
-        let damage = event.fall_distance.floor() - 3.;
-
-        if damage <= 0. {
+        if event.damage <= 0. {
             continue;
         }
 
-        let (mut health, &connection_id, position) = match query.get_mut(event.client) {
+        let (mut health, &connection_id, position, inventory) = match query.get_mut(event.client) {
             Ok(data) => data,
             Err(e) => {
-                error!("failed to apply natural damages: query failed: {e}");
+                error!("failed to apply fall damage: query failed: {e}");
                 continue;
             }
         };
 
-        health.damage(damage);
-
-        let pkt_damage_event = play::EntityDamageS2c {
-            entity_id: VarInt(event.client.minecraft_id()),
-            source_cause_id: VarInt(0),
-            source_direct_id: VarInt(0),
-            source_type_id: VarInt(10), // 10 = fall damage
-            source_pos: Option::None,
-        };
-
         let sound = agnostic::sound(
             if event.fall_distance > 7. {
                 ident!("minecraft:entity.player.big_fall")
@@ -56,25 +138,55 @@ fn apply_natural_damages(
         .seed(fastrand::i64(..))
         .build();
 
-        compose.unicast(&pkt_damage_event, connection_id).unwrap();
-        compose
-            .broadcast_local(&sound, position.to_chunk())
-            .send()
-            .unwrap();
-
-        if health.is_dead() {
-            let pkt_death_screen = play::DeathMessageS2c {
-                player_id: VarInt(event.client.minecraft_id()),
-                message: (if event.fall_distance < 5.0 {
-                    "You hit the ground too hard"
-                } else {
-                    "You fell from a high place"
-                })
-                .to_string()
-                .into_cow_text(),
-            };
-            compose.unicast(&pkt_death_screen, connection_id).unwrap();
+        if let Err(e) = compose.broadcast_local(&sound, position.to_chunk()).send() {
+            error!("failed to send fall damage sound: {e}");
+        }
+
+        let death_message = if event.fall_distance < 5.0 {
+            "You hit the ground too hard"
+        } else {
+            "You fell from a high place"
+        };
+
+        damage_player(
+            &compose,
+            &mut commands,
+            event.client,
+            connection_id,
+            &mut health,
+            inventory.map(Mut::into_inner),
+            &Damage {
+                amount: event.damage,
+                damage_type: DamageType::Fall,
+                death_message,
+            },
+        );
+    }
+}
+
+fn apply_void_damage(
+    mut query: Query<'_, '_, (Entity, &mut Health, &ConnectionId, &Position)>,
+    compose: Res<'_, Compose>,
+    mut commands: Commands<'_, '_>,
+) {
+    for (player, mut health, &connection_id, position) in &mut query {
+        if position.y >= VOID_Y {
+            continue;
         }
+
+        damage_player(
+            &compose,
+            &mut commands,
+            player,
+            connection_id,
+            &mut health,
+            None,
+            &Damage {
+                amount: VOID_DAMAGE,
+                damage_type: DamageType::OutOfWorld,
+                death_message: "You fell out of the world",
+            },
+        );
     }
 }
 
@@ -82,6 +194,9 @@ pub struct DamagePlugin;
 
 impl Plugin for DamagePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(FixedUpdate, apply_natural_damages);
+        app.add_systems(
+            FixedUpdate,
+            (timed(apply_fall_damage), timed(apply_void_damage)),
+        );
     }
 }
@@ -2,6 +2,7 @@ use bevy::prelude::*;
 use hyperion::{
     net::Compose,
     simulation::{metadata::living_entity::Health, packet_state},
+    tick_rate::AdvanceTick,
 };
 use hyperion_utils::Prev;
 
@@ -58,6 +59,6 @@ fn regenerate(
 impl Plugin for RegenerationPlugin {
     fn build(&self, app: &mut App) {
         app.add_observer(initialize_player);
-        app.add_systems(FixedPostUpdate, regenerate);
+        app.add_systems(FixedPostUpdate, regenerate.in_set(AdvanceTick));
     }
 }
@@ -1,34 +1,50 @@
 use bevy::prelude::*;
 use hyperion::{
     InitializePlayerPosition,
+    config::Config,
+    glam::I16Vec2,
     runtime::AsyncRuntime,
-    simulation::{Position, blocks::Blocks},
+    simulation::{Position, WorldSpawn, blocks::Blocks},
     valence_protocol::{
-        BlockKind,
+        BlockKind, BlockState,
         math::{IVec2, IVec3, Vec3},
     },
 };
+use roaring::RoaringBitmap;
+use tracing::info;
 
-const RADIUS: i32 = 0;
 const SPAWN_MIN_Y: i16 = 3;
 const SPAWN_MAX_Y: i16 = 100;
 
-fn position_in_radius() -> IVec2 {
-    let x = fastrand::i32(-RADIUS..=RADIUS);
-    let z = fastrand::i32(-RADIUS..=RADIUS);
+/// The spawn area shrunk to fit inside the world border, which is centered on the origin. Any
+/// block of the chosen chunk may become the spawn position, so a chunk of room is kept to the
+/// border.
+fn spawn_area(spawn: &WorldSpawn, config: &Config) -> WorldSpawn {
+    let Some(diameter) = config.border_diameter else {
+        return *spawn;
+    };
+
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "the border is far smaller than i32::MAX"
+    )]
+    let border_radius = (diameter / 2.0).floor() as i32;
+
+    let center = spawn.position.as_ivec3();
+    let room = border_radius - center.x.abs().max(center.z.abs()) - 16;
 
-    IVec2::new(x, z)
+    WorldSpawn {
+        radius: spawn.radius.min(room.max(0)),
+        ..*spawn
+    }
 }
 
-fn random_chunk_in_radius() -> I16Vec2 {
-    let pos: IVec2 = position_in_radius() >> 4;
+fn random_chunk_in_radius(spawn: &WorldSpawn) -> I16Vec2 {
+    let center = spawn.position.as_ivec3();
+    let pos = (IVec2::new(center.x, center.z) + spawn.random_offset()) >> 4;
     pos.as_i16vec2()
 }
 
-use hyperion::{glam::I16Vec2, valence_protocol::BlockState};
-use roaring::RoaringBitmap;
-use tracing::info;
-
 pub fn avoid_blocks() -> RoaringBitmap {
     let mut blocks = RoaringBitmap::new();
     let spawnable = [BlockKind::Lava];
@@ -47,11 +63,18 @@ impl Plugin for SpawnPlugin {
 
         app.add_observer(
             move |trigger: Trigger<'_, InitializePlayerPosition>,
+                  spawn: Res<'_, WorldSpawn>,
+                  config: Res<'_, Config>,
                   mut blocks: ResMut<'_, Blocks>,
                   runtime: Res<'_, AsyncRuntime>,
                   mut commands: Commands<'_, '_>| {
-                let position =
-                    Position::from(find_spawn_position(&mut blocks, &runtime, &avoid_blocks));
+                let position = Position::from(find_spawn_position(
+                    &spawn,
+                    &config,
+                    &mut blocks,
+                    &runtime,
+                    &avoid_blocks,
+                ));
                 let target = trigger.event().0;
                 commands.entity(target).insert(position);
             },
@@ -59,22 +82,27 @@ impl Plugin for SpawnPlugin {
     }
 }
 
+/// Finds a safe position in a random chunk around the world spawn and inside the world border,
+/// falling back to the world spawn itself
 pub fn find_spawn_position(
+    spawn: &WorldSpawn,
+    config: &Config,
     blocks: &mut Blocks,
     runtime: &AsyncRuntime,
     avoid_blocks: &RoaringBitmap,
 ) -> Vec3 {
     const MAX_TRIES: usize = 3;
-    const FALLBACK_POSITION: Vec3 = Vec3::new(0.0, 120.0, 0.0);
+
+    let area = spawn_area(spawn, config);
 
     for _ in 0..MAX_TRIES {
-        let chunk = random_chunk_in_radius();
+        let chunk = random_chunk_in_radius(&area);
         if let Some(pos) = try_chunk_for_spawn(chunk, blocks, runtime, avoid_blocks) {
             return pos;
         }
     }
 
-    FALLBACK_POSITION
+    spawn.position
 }
 
 fn try_chunk_for_spawn(
@@ -5,7 +5,7 @@ use hyperion::{
     net::Compose,
     valence_protocol::{packets::play, text::IntoText},
 };
-use tracing::info_span;
+use tracing::{error, info_span};
 
 #[derive(Resource)]
 struct UpdateStart(Instant);
@@ -82,7 +82,9 @@ impl Plugin for StatsPlugin {
                     footer: footer.into_cow_text(),
                 };
 
-                compose.broadcast(&pkt).send().unwrap();
+                if let Err(e) = compose.broadcast(&pkt).send() {
+                    error!("failed to send tab list: {e}");
+                }
             },
         );
     }
@@ -1,11 +1,6 @@
 use bevy::prelude::*;
-use hyperion::{
-    net::Compose,
-    simulation::{Uuid, metadata::entity::EntityFlags},
-};
+use hyperion::{egress::player_join::Hidden, simulation::metadata::entity::EntityFlags};
 use tracing::error;
-use valence_protocol::packets::play::{self, player_list_s2c::PlayerListActions};
-use valence_server::GameMode;
 
 pub struct VanishPlugin;
 
@@ -26,10 +21,12 @@ impl Vanished {
 
 fn update_vanish(
     trigger: Trigger<'_, OnInsert, Vanished>,
-    compose: Res<'_, Compose>,
-    mut query: Query<'_, '_, (&Vanished, &Uuid, &mut EntityFlags)>,
+    mut query: Query<'_, '_, (&Vanished, &mut EntityFlags)>,
+    mut commands: Commands<'_, '_>,
 ) {
-    let (vanished, uuid, mut flags) = match query.get_mut(trigger.target()) {
+    let player = trigger.target();
+
+    let (vanished, mut flags) = match query.get_mut(player) {
         Ok(data) => data,
         Err(e) => {
             error!("failed to update vanish: query failed: {e}");
@@ -38,40 +35,12 @@ fn update_vanish(
     };
 
     if vanished.is_vanished() {
-        // Remove from player list and make them invisible
-        let remove_packet = play::PlayerListS2c {
-            actions: PlayerListActions::new()
-                .with_update_listed(true)
-                .with_update_game_mode(true),
-            entries: vec![play::player_list_s2c::PlayerListEntry {
-                player_uuid: uuid.0,
-                listed: false,
-                game_mode: GameMode::Survival,
-                ..Default::default()
-            }]
-            .into(),
-        };
-        compose.broadcast(&remove_packet).send().unwrap();
-
-        // Set entity flags to make them invisible
+        // Remove from player lists and make them invisible
+        commands.entity(player).insert(Hidden);
         *flags |= EntityFlags::INVISIBLE;
     } else {
-        // Add back to player list and make them visible
-        let add_packet = play::PlayerListS2c {
-            actions: PlayerListActions::new()
-                .with_update_listed(true)
-                .with_update_game_mode(true),
-            entries: vec![play::player_list_s2c::PlayerListEntry {
-                player_uuid: uuid.0,
-                listed: true,
-                game_mode: GameMode::Survival,
-                ..Default::default()
-            }]
-            .into(),
-        };
-        compose.broadcast(&add_packet).send().unwrap();
-
-        // Clear invisible flag
+        // Add back to player lists and make them visible
+        commands.entity(player).remove::<Hidden>();
         *flags &= !EntityFlags::INVISIBLE;
     }
 }
@@ -2,35 +2,96 @@ use std::borrow::Cow;
 
 use bevy::prelude::*;
 use hyperion::{
-    egress::player_join::{PlayerListActions, PlayerListEntry, PlayerListS2c},
-    net::{Compose, ConnectionId, DataBundle},
-    simulation::event,
+    egress::{
+        metadata::show_all,
+        player_join::{PlayerListActions, PlayerListEntry, PlayerListS2c},
+    },
+    net::{Compose, ConnectionId},
+    simulation::{
+        PendingTeleportation, Pitch, Position, Yaw,
+        afk::Afk,
+        event,
+        player_list::{DisplayName, Nickname, list_name, profile_name},
+    },
+    timings::timed,
     valence_ident::ident,
 };
+use hyperion_inventory::PlayerInventory;
 use hyperion_utils::EntityExt;
 use tracing::error;
-use valence_bytes::Utf8Bytes;
+use valence_bytes::{CowUtf8Bytes, Utf8Bytes};
 use valence_protocol::{
-    GameMode, VarInt,
+    ByteAngle, GameMode, VarInt,
     game_mode::OptGameMode,
-    packets::play::{EntitiesDestroyS2c, PlayerRemoveS2c, PlayerRespawnS2c},
+    packets::play::{
+        EntitiesDestroyS2c, EntityEquipmentUpdateS2c, PlayerRemoveS2c, PlayerRespawnS2c,
+        PlayerSpawnS2c, entity_equipment_update_s2c::EquipmentEntry,
+    },
 };
 
 pub struct SkinPlugin;
 
 impl Plugin for SkinPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(FixedUpdate, on_set_skin);
+        app.add_systems(FixedUpdate, timed(on_set_skin));
     }
 }
 
+/// The equipment shown to other players, which is lost when the player is spawned again
+fn equipment(inventory: &PlayerInventory) -> Vec<EquipmentEntry> {
+    [
+        (0, inventory.get_cursor()),
+        (1, inventory.get_offhand()),
+        (2, inventory.get_boots()),
+        (3, inventory.get_leggings()),
+        (4, inventory.get_chestplate()),
+        (5, inventory.get_helmet()),
+    ]
+    .into_iter()
+    .map(|(slot, item)| EquipmentEntry {
+        slot,
+        item: item.stack.clone(),
+    })
+    .collect()
+}
+
+/// Clients only load the skin of a player when the player is spawned, so the player list entry of
+/// the player is replaced for everyone, the player is spawned again for other players and the
+/// player is respawned for themselves.
 fn on_set_skin(
     mut events: EventReader<'_, '_, event::SetSkin>,
     compose: Res<'_, Compose>,
-    query: Query<'_, '_, (&ConnectionId, &hyperion::simulation::Uuid)>,
+    query: Query<
+        '_,
+        '_,
+        (
+            &ConnectionId,
+            &hyperion::simulation::Uuid,
+            &Name,
+            &Position,
+            &Yaw,
+            &Pitch,
+            &PlayerInventory,
+            Option<&Nickname>,
+            Option<&DisplayName>,
+            Has<Afk>,
+        ),
+    >,
+    mut commands: Commands<'_, '_>,
 ) {
     for event in events.read() {
-        let (&connection_id, uuid) = match query.get(event.by) {
+        let (
+            &connection_id,
+            uuid,
+            name,
+            position,
+            yaw,
+            pitch,
+            inventory,
+            nickname,
+            display_name,
+            afk,
+        ) = match query.get(event.by) {
             Ok(data) => data,
             Err(e) => {
                 error!("failed to set skin: query failed: {e}");
@@ -39,20 +100,10 @@ fn on_set_skin(
         };
 
         let minecraft_id = event.by.minecraft_id();
-        let mut bundle = DataBundle::new(&compose);
-        // Remove player info
-        bundle
-            .add_packet(&PlayerRemoveS2c {
-                uuids: Cow::Borrowed(&[**uuid]),
-            })
-            .unwrap();
 
-        // Destroy player entity
-        bundle
-            .add_packet(&EntitiesDestroyS2c {
-                entity_ids: Cow::Borrowed(&[VarInt(minecraft_id)]),
-            })
-            .unwrap();
+        commands
+            .entity(event.by)
+            .insert((event.skin.clone(), PendingTeleportation::new(**position)));
 
         // todo: in future, do not clone
         let property = valence_protocol::profile::Property::<Utf8Bytes> {
@@ -63,39 +114,111 @@ fn on_set_skin(
 
         let property = &[property];
 
-        // Add player back with new skin
-        bundle
-            .add_packet(&PlayerListS2c {
-                actions: PlayerListActions::default().with_add_player(true),
+        // Replace the player list entry of the player for everyone
+        if let Err(e) = compose
+            .broadcast(&PlayerRemoveS2c {
+                uuids: Cow::Borrowed(&[**uuid]),
+            })
+            .send()
+        {
+            error!("failed to send skin update: {e}");
+        }
+
+        if let Err(e) = compose
+            .broadcast(&PlayerListS2c {
+                actions: PlayerListActions::default()
+                    .with_add_player(true)
+                    .with_update_listed(true)
+                    .with_update_display_name(true),
                 entries: Cow::Borrowed(&[PlayerListEntry {
                     player_uuid: **uuid,
-                    username: "Player".into(),
+                    username: CowUtf8Bytes::Borrowed(profile_name(name, nickname)),
                     properties: Cow::Borrowed(property),
                     chat_data: None,
                     listed: true,
                     ping: 20,
                     game_mode: GameMode::Survival,
-                    display_name: None,
+                    display_name: Some(Cow::Owned(list_name(name, nickname, display_name, afk))),
                 }]),
             })
-            .unwrap();
-
-        // // Respawn player
-        bundle
-            .add_packet(&PlayerRespawnS2c {
-                dimension_type_name: ident!("minecraft:overworld"),
-                dimension_name: ident!("minecraft:overworld"),
-                hashed_seed: 0,
-                game_mode: GameMode::Survival,
-                previous_game_mode: OptGameMode::default(),
-                is_debug: false,
-                is_flat: false,
-                copy_metadata: false,
-                last_death_location: None,
-                portal_cooldown: VarInt::default(),
-            })
-            .unwrap();
+            .send()
+        {
+            error!("failed to send skin update: {e}");
+        }
+
+        // Spawn the player again for other players
+        let entity_id = VarInt(minecraft_id);
+        let channel = event.by.into();
+
+        if let Err(e) = compose
+            .broadcast_channel(
+                &EntitiesDestroyS2c {
+                    entity_ids: Cow::Borrowed(&[entity_id]),
+                },
+                channel,
+            )
+            .exclude(connection_id)
+            .send()
+        {
+            error!("failed to send skin update: {e}");
+        }
+
+        if let Err(e) = compose
+            .broadcast_channel(
+                &PlayerSpawnS2c {
+                    entity_id,
+                    player_uuid: **uuid,
+                    position: position.as_dvec3(),
+                    yaw: ByteAngle::from_degrees(**yaw),
+                    pitch: ByteAngle::from_degrees(**pitch),
+                },
+                channel,
+            )
+            .exclude(connection_id)
+            .send()
+        {
+            error!("failed to send skin update: {e}");
+        }
+
+        if let Err(e) = compose
+            .broadcast_channel(&show_all(minecraft_id), channel)
+            .exclude(connection_id)
+            .send()
+        {
+            error!("failed to send skin update: {e}");
+        }
+
+        if let Err(e) = compose
+            .broadcast_channel(
+                &EntityEquipmentUpdateS2c {
+                    entity_id,
+                    equipment: equipment(inventory),
+                },
+                channel,
+            )
+            .exclude(connection_id)
+            .send()
+        {
+            error!("failed to send skin update: {e}");
+        }
+
+        // Respawn the player so they see their own skin. The player is teleported back to their
+        // position afterwards.
+        let pkt = PlayerRespawnS2c {
+            dimension_type_name: ident!("minecraft:overworld"),
+            dimension_name: ident!("minecraft:overworld"),
+            hashed_seed: 0,
+            game_mode: GameMode::Survival,
+            previous_game_mode: OptGameMode::default(),
+            is_debug: false,
+            is_flat: false,
+            copy_metadata: true,
+            last_death_location: None,
+            portal_cooldown: VarInt::default(),
+        };
 
-        bundle.unicast(connection_id).unwrap();
+        if let Err(e) = compose.unicast(&pkt, connection_id) {
+            error!("failed to send skin update: {e}");
+        }
     }
 }
@@ -12,15 +12,15 @@ use valence_protocol::{
 };
 
 /// 1.20.1
-const PROTOCOL_VERSION: i32 = 763;
+pub const PROTOCOL_VERSION: i32 = 763;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct ServerAddress {
-    host: String,
-    port: u16,
+    pub host: String,
+    pub port: u16,
 }
 
-fn parse_address(address: &str) -> eyre::Result<ServerAddress> {
+pub fn parse_address(address: &str) -> eyre::Result<ServerAddress> {
     static ADDRESS_REGEX: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
         regex::Regex::new(r"^(?P<host>[^:]+)(?::(?P<port>\d+))?$").unwrap()
     });
@@ -0,0 +1,118 @@
+//! Faults which the bot injects into its connection.
+//!
+//! Random numbers come from the Antithesis SDK, so Antithesis can explore which faults happen and
+//! when. Outside of Antithesis they fall back to ordinary random numbers.
+
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use valence_protocol::{CompressionThreshold, Encode, Packet, VarInt, packets::play};
+
+/// How often each fault is injected
+#[derive(Copy, Clone, Debug)]
+pub struct Faults {
+    /// Chance of closing the connection on each tick
+    pub disconnect_probability: f64,
+    /// Chance of delaying the packets sent after a packet
+    pub delay_probability: f64,
+    /// The longest delay in ticks
+    pub max_delay_ticks: u64,
+    /// Chance of sending a malformed packet on each tick
+    pub malformed_probability: f64,
+}
+
+/// Returns `true` with the given probability
+#[must_use]
+pub fn chance(probability: f64) -> bool {
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "precision is not needed for a probability"
+    )]
+    let value = antithesis_sdk::random::get_random() as f64 / u64::MAX as f64;
+    value < probability
+}
+
+/// Returns a random number in `0..n`
+#[must_use]
+pub fn random_below(n: u64) -> u64 {
+    antithesis_sdk::random::get_random() % n.max(1)
+}
+
+/// Packets which are waiting to be sent. A delay holds back every packet after it, like a slow
+/// network would, so packets are always sent in order.
+#[derive(Debug, Default)]
+pub struct Outgoing {
+    queue: VecDeque<(u64, Bytes)>,
+    /// No packet is sent before this tick
+    delayed_until: u64,
+}
+
+impl Outgoing {
+    /// Queues a packet and returns the tick it will be sent on
+    pub fn push(&mut self, tick: u64, packet: Bytes, faults: &Faults) -> u64 {
+        if chance(faults.delay_probability) {
+            let delay = 1 + random_below(faults.max_delay_ticks);
+            self.delayed_until = self.delayed_until.max(tick + delay);
+            antithesis_sdk::assert_reachable!("delayed packets sent to the server");
+        }
+
+        let send_tick = self.delayed_until.max(tick);
+        self.queue.push_back((send_tick, packet));
+        send_tick
+    }
+
+    /// Takes the packets which should be sent by `tick`
+    pub fn take_ready(&mut self, tick: u64) -> Vec<u8> {
+        let mut ready = Vec::new();
+        while let Some((send_tick, packet)) = self.queue.front() {
+            if *send_tick > tick {
+                break;
+            }
+            ready.extend_from_slice(packet);
+            self.queue.pop_front();
+        }
+        ready
+    }
+}
+
+/// Packet ids which are sent with garbage bodies
+const MALFORMED_IDS: &[i32] = &[
+    play::TeleportConfirmC2s::ID,
+    play::KeepAliveC2s::ID,
+    play::ChatMessageC2s::ID,
+    play::ClickSlotC2s::ID,
+    play::CustomPayloadC2s::ID,
+    play::PlayerInteractEntityC2s::ID,
+    play::PlayerInteractBlockC2s::ID,
+    play::PositionAndOnGroundC2s::ID,
+    play::UpdateSelectedSlotC2s::ID,
+];
+
+/// The longest body of a malformed packet
+const MAX_MALFORMED_LEN: u64 = 64;
+
+/// Creates a packet with a real packet id and a random body. The length prefix is correct, so the
+/// server can frame the packet and has to reject its body.
+pub fn malformed_packet(threshold: CompressionThreshold) -> eyre::Result<Bytes> {
+    let index = usize::try_from(random_below(MALFORMED_IDS.len() as u64))?;
+    let id = MALFORMED_IDS[index];
+
+    let mut data = Vec::new();
+    VarInt(id).encode(&mut data)?;
+    for _ in 0..random_below(MAX_MALFORMED_LEN + 1) {
+        data.push(antithesis_sdk::random::get_random().to_le_bytes()[0]);
+    }
+
+    // The packet is kept below the compression threshold so that it can be sent uncompressed
+    let mut packet = Vec::new();
+    if let Ok(threshold) = usize::try_from(threshold.0) {
+        data.truncate(threshold.saturating_sub(1).max(VarInt(id).written_size()));
+        VarInt(0).encode(&mut packet)?;
+    }
+    packet.extend_from_slice(&data);
+
+    let mut frame = Vec::new();
+    VarInt(i32::try_from(packet.len())?).encode(&mut frame)?;
+    frame.extend_from_slice(&packet);
+    Ok(Bytes::from(frame))
+}
@@ -0,0 +1,209 @@
+//! Protocol invariants which the server must uphold no matter which faults are injected.
+//!
+//! Each check reports to Antithesis with `assert_always!` and also returns whether the invariant
+//! held, so that the checks can be tested without an Antithesis environment.
+
+use std::collections::{HashMap, HashSet};
+
+use antithesis_sdk::serde_json::json;
+
+/// A teleport which the server asked the bot to confirm
+#[derive(Copy, Clone, Debug)]
+struct Teleport {
+    id: i32,
+    received_tick: u64,
+    confirmed_tick: Option<u64>,
+}
+
+/// Tracks what the server has sent to one connection
+#[derive(Debug)]
+pub struct Invariants {
+    /// Teleports which are confirmed within this many ticks must not be re-sent
+    teleport_confirm_ticks: u64,
+    last_teleport: Option<Teleport>,
+    /// The last state id for each window
+    state_ids: HashMap<u8, i32>,
+    /// The entities which have been spawned and not removed
+    entities: HashSet<i32>,
+    own_entity_id: Option<i32>,
+}
+
+impl Invariants {
+    #[must_use]
+    pub fn new(teleport_confirm_ticks: u64) -> Self {
+        Self {
+            teleport_confirm_ticks,
+            last_teleport: None,
+            state_ids: HashMap::new(),
+            entities: HashSet::new(),
+            own_entity_id: None,
+        }
+    }
+
+    /// The server sent a teleport. The server re-sends a teleport with the same id until it is
+    /// confirmed, so receiving the id again after a timely confirmation means that the
+    /// confirmation was lost.
+    pub fn teleport_received(&mut self, id: i32, tick: u64) -> bool {
+        let mut holds = true;
+
+        if let Some(last) = self.last_teleport
+            && last.id == id
+        {
+            let confirmed_in_time = last.confirmed_tick.is_some_and(|confirmed| {
+                confirmed - last.received_tick <= self.teleport_confirm_ticks
+            });
+
+            if !confirmed_in_time {
+                antithesis_sdk::assert_reachable!("the server re-sent an unconfirmed teleport");
+            }
+
+            holds = !confirmed_in_time;
+            antithesis_sdk::assert_always!(
+                holds,
+                "teleports confirmed in time are not re-sent",
+                &json!({
+                    "id": id,
+                    "received_tick": last.received_tick,
+                    "confirmed_tick": last.confirmed_tick,
+                    "resent_tick": tick,
+                })
+            );
+        }
+
+        // A re-send has to be confirmed again, so it is tracked like a new teleport
+        self.last_teleport = Some(Teleport {
+            id,
+            received_tick: tick,
+            confirmed_tick: None,
+        });
+
+        holds
+    }
+
+    /// The bot sent the confirmation for a teleport to the server
+    pub fn teleport_confirmed(&mut self, id: i32, tick: u64) {
+        if let Some(teleport) = &mut self.last_teleport
+            && teleport.id == id
+        {
+            teleport.confirmed_tick = Some(tick);
+        }
+    }
+
+    /// The server opened a window, which starts its state ids over
+    pub fn window_opened(&mut self, window_id: u8) {
+        self.state_ids.remove(&window_id);
+    }
+
+    /// The server sent the contents or a slot of a window. State ids wrap around, so they only
+    /// need to be ahead of the previous state id.
+    pub fn state_id_received(&mut self, window_id: u8, state_id: i32) -> bool {
+        let holds = self
+            .state_ids
+            .insert(window_id, state_id)
+            .is_none_or(|previous| state_id.wrapping_sub(previous) >= 0);
+
+        antithesis_sdk::assert_always!(
+            holds,
+            "inventory state ids never go backwards",
+            &json!({
+                "window_id": window_id,
+                "state_id": state_id,
+            })
+        );
+
+        holds
+    }
+
+    /// The server joined the bot to the world as the entity `entity_id`
+    pub fn joined(&mut self, entity_id: i32) {
+        self.own_entity_id = Some(entity_id);
+    }
+
+    /// The server spawned an entity
+    pub fn entity_spawned(&mut self, entity_id: i32) -> bool {
+        let holds = self.own_entity_id != Some(entity_id) && self.entities.insert(entity_id);
+
+        antithesis_sdk::assert_always!(
+            holds,
+            "spawned entity ids are unique",
+            &json!({
+                "entity_id": entity_id,
+                "own_entity_id": self.own_entity_id,
+            })
+        );
+
+        holds
+    }
+
+    /// The server removed entities
+    pub fn entities_removed(&mut self, entity_ids: impl IntoIterator<Item = i32>) {
+        for entity_id in entity_ids {
+            self.entities.remove(&entity_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_teleport_resent_after_timely_confirm() {
+        let mut invariants = Invariants::new(10);
+
+        assert!(invariants.teleport_received(1, 0));
+        invariants.teleport_confirmed(1, 5);
+        assert!(!invariants.teleport_received(1, 25));
+    }
+
+    #[test]
+    fn test_teleport_resent_without_confirm() {
+        let mut invariants = Invariants::new(10);
+
+        assert!(invariants.teleport_received(1, 0));
+        assert!(invariants.teleport_received(1, 21));
+        invariants.teleport_confirmed(1, 23);
+        assert!(invariants.teleport_received(2, 40));
+    }
+
+    #[test]
+    fn test_teleport_to_same_position() {
+        let mut invariants = Invariants::new(10);
+
+        // A new teleport is not a re-send, even if it goes to the same position
+        assert!(invariants.teleport_received(1, 0));
+        invariants.teleport_confirmed(1, 5);
+        assert!(invariants.teleport_received(2, 25));
+    }
+
+    #[test]
+    fn test_state_ids() {
+        let mut invariants = Invariants::new(10);
+
+        assert!(invariants.state_id_received(0, 1));
+        assert!(invariants.state_id_received(0, 1));
+        assert!(invariants.state_id_received(0, 2));
+        assert!(!invariants.state_id_received(0, 1));
+
+        assert!(invariants.state_id_received(0, i32::MAX));
+        assert!(invariants.state_id_received(0, i32::MIN));
+
+        invariants.window_opened(1);
+        assert!(invariants.state_id_received(1, 5));
+        invariants.window_opened(1);
+        assert!(invariants.state_id_received(1, 0));
+    }
+
+    #[test]
+    fn test_entity_ids() {
+        let mut invariants = Invariants::new(10);
+        invariants.joined(1);
+
+        assert!(!invariants.entity_spawned(1));
+        assert!(invariants.entity_spawned(2));
+        assert!(!invariants.entity_spawned(2));
+
+        invariants.entities_removed([2]);
+        assert!(invariants.entity_spawned(2));
+    }
+}
@@ -1,7 +1,15 @@
+#![feature(let_chains)]
+
 use antithesis_sdk::serde_json::json;
 use serde::Deserialize;
+use tokio::task::JoinSet;
+
+use crate::{faults::Faults, play::Scenario};
 
 mod bot;
+mod faults;
+mod invariants;
+mod play;
 
 #[derive(Deserialize, Debug)]
 pub struct LaunchArguments {
@@ -9,12 +17,62 @@ pub struct LaunchArguments {
 
     #[serde(default = "default_bot_count")]
     bot_count: u32,
+
+    /// How many ticks each bot plays for
+    #[serde(default = "default_ticks")]
+    ticks: u64,
+
+    /// Teleports confirmed within this many ticks must not be re-sent by the server
+    #[serde(default = "default_teleport_confirm_ticks")]
+    teleport_confirm_ticks: u64,
+
+    /// Chance of a bot disconnecting on each tick
+    #[serde(default = "default_disconnect_probability")]
+    disconnect_probability: f64,
+
+    /// Chance of a bot delaying the packets after each packet it sends
+    #[serde(default = "default_delay_probability")]
+    delay_probability: f64,
+
+    /// The longest delay in ticks
+    #[serde(default = "default_max_delay_ticks")]
+    max_delay_ticks: u64,
+
+    /// Chance of a bot sending a malformed packet on each tick
+    #[serde(default = "default_malformed_probability")]
+    malformed_probability: f64,
 }
 
 const fn default_bot_count() -> u32 {
     1
 }
 
+const fn default_ticks() -> u64 {
+    // One minute
+    1200
+}
+
+const fn default_teleport_confirm_ticks() -> u64 {
+    // Half of the time the server waits before re-sending a teleport
+    10
+}
+
+const fn default_disconnect_probability() -> f64 {
+    0.001
+}
+
+const fn default_delay_probability() -> f64 {
+    0.01
+}
+
+const fn default_max_delay_ticks() -> u64 {
+    40
+}
+
+const fn default_malformed_probability() -> f64 {
+    0.001
+}
+
 pub async fn start(args: LaunchArguments) -> eyre::Result<()> {
     const UNUSUALLY_HIGH_BOT_THRESHOLD: u32 = 1_000;
 
@@ -28,7 +86,16 @@ pub async fn start(args: LaunchArguments) -> eyre::Result<()> {
 
     tracing::info!("args = {args:?}");
 
-    let LaunchArguments { address, bot_count } = args;
+    let LaunchArguments {
+        address,
+        bot_count,
+        ticks,
+        teleport_confirm_ticks,
+        disconnect_probability,
+        delay_probability,
+        max_delay_ticks,
+        malformed_probability,
+    } = args;
 
     if bot_count > UNUSUALLY_HIGH_BOT_THRESHOLD {
         tracing::warn!("bot_count {bot_count} is unusually high. This may cause issues.");
@@ -38,5 +105,26 @@ pub async fn start(args: LaunchArguments) -> eyre::Result<()> {
         bot::launch(&address).await?;
     }
 
+    let scenario = Scenario {
+        ticks,
+        teleport_confirm_ticks,
+        faults: Faults {
+            disconnect_probability,
+            delay_probability,
+            max_delay_ticks,
+            malformed_probability,
+        },
+    };
+
+    let mut bots = JoinSet::new();
+    for id in 0..bot_count {
+        let address = address.clone();
+        bots.spawn(async move { play::run(&address, &format!("Bot_{id}"), scenario).await });
+    }
+
+    while let Some(result) = bots.join_next().await {
+        result??;
+    }
+
     Ok(())
 }
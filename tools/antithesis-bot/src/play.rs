@@ -0,0 +1,304 @@
+//! A bot which joins the server, checks [`Invariants`] on everything the server sends and injects
+//! [`Faults`] into its connection.
+
+use std::time::Duration;
+
+use antithesis_sdk::serde_json::json;
+use bytes::{Bytes, BytesMut};
+use eyre::{Context, eyre};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tracing::info;
+use valence_protocol::{
+    Bounded, CompressionThreshold, DecodeBytes, Encode, Packet, PacketDecoder, PacketEncoder,
+    VarInt,
+    decode::PacketFrame,
+    packets::{
+        handshaking::{HandshakeC2s, handshake_c2s::HandshakeNextState},
+        login, play,
+    },
+};
+
+use crate::{
+    bot::{PROTOCOL_VERSION, parse_address},
+    faults::{self, Faults, Outgoing},
+    invariants::Invariants,
+};
+
+const TICK: Duration = Duration::from_millis(50);
+
+/// The server must finish logging in a player within this many ticks
+const LOGIN_TIMEOUT_TICKS: u64 = 200;
+
+/// What a scenario does and for how long
+#[derive(Copy, Clone, Debug)]
+pub struct Scenario {
+    /// How many ticks each bot plays for, across all of its connections
+    pub ticks: u64,
+    /// Teleports confirmed within this many ticks must not be re-sent by the server
+    pub teleport_confirm_ticks: u64,
+    pub faults: Faults,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    Login,
+    Play,
+}
+
+/// Why a connection ended
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum End {
+    /// The scenario ran for all of its ticks
+    Finished,
+    /// The bot closed the connection as a fault
+    Disconnected,
+    /// The server closed the connection
+    Closed,
+}
+
+/// Plays `scenario` as `name`, reconnecting whenever the connection ends early
+pub async fn run(address: &str, name: &str, scenario: Scenario) -> eyre::Result<()> {
+    let mut tick = 0;
+    let mut faulted = false;
+
+    while tick < scenario.ticks {
+        let stream = TcpStream::connect(address).await;
+        if faulted {
+            antithesis_sdk::assert_always!(
+                stream.is_ok(),
+                "the server accepts connections after faults",
+                &json!({ "name": name, "tick": tick })
+            );
+        }
+        let stream = stream.wrap_err_with(|| format!("Failed to connect to {address}"))?;
+
+        let mut session = Session::new(name, scenario, tick);
+        let end = session.play(stream, address).await?;
+        tick = session.tick;
+
+        info!("{name} ended its connection at tick {tick}: {end:?}");
+        faulted = end != End::Finished;
+    }
+
+    antithesis_sdk::assert_reachable!("a bot played its whole scenario");
+    Ok(())
+}
+
+/// One connection to the server
+struct Session<'a> {
+    name: &'a str,
+    scenario: Scenario,
+    tick: u64,
+    connected_tick: u64,
+    state: State,
+    encoder: PacketEncoder,
+    decoder: PacketDecoder,
+    threshold: CompressionThreshold,
+    outgoing: Outgoing,
+    invariants: Invariants,
+}
+
+impl<'a> Session<'a> {
+    fn new(name: &'a str, scenario: Scenario, tick: u64) -> Self {
+        Self {
+            name,
+            scenario,
+            tick,
+            connected_tick: tick,
+            state: State::Login,
+            encoder: PacketEncoder::new(),
+            decoder: PacketDecoder::new(),
+            threshold: CompressionThreshold(-1),
+            outgoing: Outgoing::default(),
+            invariants: Invariants::new(scenario.teleport_confirm_ticks),
+        }
+    }
+
+    async fn play(&mut self, stream: TcpStream, address: &str) -> eyre::Result<End> {
+        let server_addr = parse_address(address)?;
+        let (mut reader, mut writer) = stream.into_split();
+
+        // The login packets are never delayed, so every connection at least starts to log in
+        self.encoder
+            .append_packet(&HandshakeC2s {
+                protocol_version: VarInt(PROTOCOL_VERSION),
+                server_address: Bounded(&server_addr.host),
+                server_port: server_addr.port,
+                next_state: HandshakeNextState::Login,
+            })
+            .map_err(|e| eyre!("failed to encode handshake packet: {e}"))?;
+        self.encoder
+            .append_packet(&login::LoginHelloC2s {
+                username: Bounded(self.name),
+                profile_id: None,
+            })
+            .map_err(|e| eyre!("failed to encode login packet: {e}"))?;
+        writer
+            .write_all(&self.encoder.take())
+            .await
+            .wrap_err("failed to write login packets")?;
+
+        let mut interval = tokio::time::interval(TICK);
+        let mut buf = BytesMut::with_capacity(1024);
+
+        loop {
+            tokio::select! {
+                read = reader.read_buf(&mut buf) => {
+                    if read.wrap_err("failed to read from the server")? == 0 {
+                        return Ok(End::Closed);
+                    }
+
+                    self.decoder.queue_bytes(buf.split());
+                    while let Some(frame) = self
+                        .decoder
+                        .try_next_packet()
+                        .map_err(|e| eyre!("failed to decode packet: {e}"))?
+                    {
+                        if let Some(end) = self.handle(frame)? {
+                            return Ok(end);
+                        }
+                    }
+                }
+                _ = interval.tick() => {
+                    if let Some(end) = self.on_tick()? {
+                        return Ok(end);
+                    }
+
+                    let ready = self.outgoing.take_ready(self.tick);
+                    if !ready.is_empty() {
+                        writer
+                            .write_all(&ready)
+                            .await
+                            .wrap_err("failed to write packets")?;
+                    }
+                }
+            }
+        }
+    }
+
+    fn on_tick(&mut self) -> eyre::Result<Option<End>> {
+        self.tick += 1;
+
+        if self.tick >= self.scenario.ticks {
+            return Ok(Some(End::Finished));
+        }
+
+        let faults = self.scenario.faults;
+        match self.state {
+            State::Login => {
+                let waited = self.tick - self.connected_tick;
+                antithesis_sdk::assert_always!(
+                    waited <= LOGIN_TIMEOUT_TICKS,
+                    "the server logs in players in time",
+                    &json!({ "name": self.name, "waited": waited })
+                );
+            }
+            State::Play => {
+                if faults::chance(faults.disconnect_probability) {
+                    antithesis_sdk::assert_reachable!("disconnected from the server as a fault");
+                    return Ok(Some(End::Disconnected));
+                }
+
+                if faults::chance(faults.malformed_probability) {
+                    antithesis_sdk::assert_reachable!("sent a malformed packet to the server");
+                    let packet = faults::malformed_packet(self.threshold)?;
+                    self.outgoing.push(self.tick, packet, &faults);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Queues a packet and returns the tick it will be sent on
+    fn send<P: Packet + Encode>(&mut self, packet: &P) -> eyre::Result<u64> {
+        self.encoder
+            .append_packet(packet)
+            .map_err(|e| eyre!("failed to encode {}: {e}", P::NAME))?;
+        let packet = self.encoder.take().freeze();
+        Ok(self.outgoing.push(self.tick, packet, &self.scenario.faults))
+    }
+
+    fn handle(&mut self, frame: PacketFrame) -> eyre::Result<Option<End>> {
+        let mut body = frame.body.freeze();
+
+        match self.state {
+            State::Login => match frame.id {
+                login::LoginCompressionS2c::ID => {
+                    let packet = decode::<login::LoginCompressionS2c>(&mut body)?;
+                    self.threshold = CompressionThreshold(packet.threshold.0);
+                    self.encoder.set_compression(self.threshold);
+                    self.decoder.set_compression(self.threshold);
+                }
+                login::LoginSuccessS2c::ID => {
+                    antithesis_sdk::assert_reachable!("logged in to the server");
+                    self.state = State::Play;
+                }
+                login::LoginDisconnectS2c::ID => return Ok(Some(End::Closed)),
+                _ => {}
+            },
+            State::Play => match frame.id {
+                play::GameJoinS2c::ID => {
+                    let entity_id = decode::<i32>(&mut body)?;
+                    self.invariants.joined(entity_id);
+                }
+                play::PlayerPositionLookS2c::ID => {
+                    let packet = decode::<play::PlayerPositionLookS2c>(&mut body)?;
+                    let id = packet.teleport_id.0;
+                    self.invariants.teleport_received(id, self.tick);
+
+                    let sent = self.send(&play::TeleportConfirmC2s {
+                        teleport_id: packet.teleport_id,
+                    })?;
+                    self.invariants.teleport_confirmed(id, sent);
+                }
+                play::KeepAliveS2c::ID => {
+                    let packet = decode::<play::KeepAliveS2c>(&mut body)?;
+                    self.send(&play::KeepAliveC2s { id: packet.id })?;
+                }
+                play::DisconnectS2c::ID => return Ok(Some(End::Closed)),
+                play::EntitySpawnS2c::ID
+                | play::PlayerSpawnS2c::ID
+                | play::ExperienceOrbSpawnS2c::ID => {
+                    let entity_id = decode::<VarInt>(&mut body)?.0;
+                    self.invariants.entity_spawned(entity_id);
+                }
+                play::EntitiesDestroyS2c::ID => {
+                    let count = decode::<VarInt>(&mut body)?.0;
+                    let entity_ids = (0..count)
+                        .map(|_| decode::<VarInt>(&mut body).map(|id| id.0))
+                        .collect::<eyre::Result<Vec<_>>>()?;
+                    self.invariants.entities_removed(entity_ids);
+                }
+                play::OpenScreenS2c::ID => {
+                    let window_id = decode::<VarInt>(&mut body)?.0;
+                    self.invariants.window_opened(u8::try_from(window_id)?);
+                }
+                play::InventoryS2c::ID => {
+                    let window_id = decode::<u8>(&mut body)?;
+                    let state_id = decode::<VarInt>(&mut body)?.0;
+                    self.invariants.state_id_received(window_id, state_id);
+                }
+                play::ScreenHandlerSlotUpdateS2c::ID => {
+                    // Negative window ids update the cursor or the inventory without a state id
+                    if let Ok(window_id) = u8::try_from(decode::<i8>(&mut body)?) {
+                        let state_id = decode::<VarInt>(&mut body)?.0;
+                        self.invariants.state_id_received(window_id, state_id);
+                    }
+                }
+                _ => {}
+            },
+        }
+
+        Ok(None)
+    }
+}
+
+/// Decodes the start of a packet body. Only the fields which are checked are decoded, so the rest
+/// of the body is ignored.
+fn decode<T: DecodeBytes>(body: &mut Bytes) -> eyre::Result<T> {
+    T::decode_bytes(body).map_err(|e| eyre!("failed to decode packet: {e}"))
+}
@@ -0,0 +1,242 @@
+//! Scripted behaviors which bots run every tick after they have spawned.
+//!
+//! Load tests which only connect bots mostly measure the cost of idle players. Behaviors make the
+//! bots exercise the expensive parts of the server instead, such as block updates, inventories,
+//! chat and combat. They are selected with `BOT_BEHAVIORS`, a comma-separated list such as
+//! `walk-path,build,chat`.
+
+use std::{f64::consts::TAU, str::FromStr};
+
+use anyhow::bail;
+use rand::{Rng, seq::IndexedRandom};
+
+use crate::{Bot, Compression, states::play};
+
+const MESSAGES: &[&str] = &["This is a chat message!", "Wow", "Server = on?"];
+
+/// How far a bot walks along its path each tick, which is about the walking speed of a player
+const WALK_SPEED: f64 = 0.2;
+
+/// How far away a bot can attack another player from
+const ATTACK_RANGE: f64 = 3.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Behavior {
+    /// Stays where it spawned
+    Idle,
+    /// Moves randomly every tick
+    Wander,
+    /// Walks in a circle around where it spawned
+    WalkPath,
+    /// Randomly swings, sneaks, sprints or changes the held slot
+    RandomActions,
+    /// Breaks the block below it
+    BreakBlocks,
+    /// Places a block on top of the block below it
+    PlaceBlocks,
+    /// Builds by breaking and placing blocks below it in turn
+    Build,
+    /// Clicks slots in its inventory and closes it
+    Inventory,
+    /// Sends chat messages
+    Chat,
+    /// Walks towards the nearest player and attacks them
+    AttackNearest,
+}
+
+impl FromStr for Behavior {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "idle" => Self::Idle,
+            "wander" => Self::Wander,
+            "walk-path" => Self::WalkPath,
+            "random-actions" => Self::RandomActions,
+            "break-blocks" => Self::BreakBlocks,
+            "place-blocks" => Self::PlaceBlocks,
+            "build" => Self::Build,
+            "inventory" => Self::Inventory,
+            "chat" => Self::Chat,
+            "attack" => Self::AttackNearest,
+            _ => bail!(
+                "unknown behavior `{s}`, expected one of idle, wander, walk-path, random-actions, \
+                 break-blocks, place-blocks, build, inventory, chat or attack"
+            ),
+        })
+    }
+}
+
+/// The behaviors every bot runs and how often they act
+#[derive(Clone, Debug)]
+pub struct BehaviorConfig {
+    pub behaviors: Vec<Behavior>,
+    /// Ticks between chat messages
+    pub chat_interval: u32,
+    /// Ticks between other actions, such as breaking blocks or attacking
+    pub action_interval: u32,
+    /// The radius of the circle which bots walk in
+    pub path_radius: f64,
+}
+
+impl BehaviorConfig {
+    /// Parses a comma-separated list of behaviors
+    pub fn parse_behaviors(list: &str) -> anyhow::Result<Vec<Behavior>> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|behavior| !behavior.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+
+    /// Runs every behavior for one tick
+    pub fn run(&self, bot: &mut Bot, tick: u32, compression: &mut Compression) {
+        // Bots are offset by their id so that they do not all act on the same tick
+        let tick = tick.wrapping_add(bot.id);
+        let acts = tick % self.action_interval.max(1) == 0;
+
+        for behavior in &self.behaviors {
+            match behavior {
+                Behavior::Wander => {
+                    bot.x += rand::random::<f64>().mul_add(1.0, -0.5);
+                    bot.z += rand::random::<f64>().mul_add(1.0, -0.5);
+                    bot.send_packet(play::write_current_pos(bot), compression);
+                }
+                Behavior::WalkPath => self.walk_path(bot, compression),
+                Behavior::RandomActions if acts => random_action(bot, compression),
+                Behavior::BreakBlocks if acts => break_block(bot, compression),
+                Behavior::PlaceBlocks if acts => place_block(bot, compression),
+                Behavior::Build if acts => {
+                    if (tick / self.action_interval.max(1)) % 2 == 0 {
+                        break_block(bot, compression);
+                    } else {
+                        place_block(bot, compression);
+                    }
+                }
+                Behavior::Inventory if acts => {
+                    let slot = rand::rng().random_range(9..45);
+                    bot.send_packet(play::write_click_slot(0, slot), compression);
+                    bot.send_packet(play::write_close_window(0), compression);
+                }
+                Behavior::Chat if tick % self.chat_interval.max(1) == 0 => {
+                    bot.send_packet(
+                        play::write_chat_message(MESSAGES.choose(&mut rand::rng()).unwrap()),
+                        compression,
+                    );
+                }
+                Behavior::AttackNearest => attack_nearest(bot, acts, compression),
+                // Idle bots and behaviors which do not act on this tick do nothing
+                _ => {}
+            }
+        }
+    }
+
+    fn walk_path(&self, bot: &mut Bot, compression: &mut Compression) {
+        let (center_x, center_z) = *bot.path_center.get_or_insert((bot.x, bot.z));
+
+        let radius = self.path_radius.max(WALK_SPEED);
+        bot.path_angle = (bot.path_angle + WALK_SPEED / radius) % TAU;
+
+        bot.x = bot.path_angle.cos().mul_add(radius, center_x);
+        bot.z = bot.path_angle.sin().mul_add(radius, center_z);
+        bot.send_packet(play::write_current_pos(bot), compression);
+    }
+}
+
+fn random_action(bot: &mut Bot, compression: &mut Compression) {
+    match rand::rng().random_range(0..=4u8) {
+        0 => {
+            // Send chat
+            bot.send_packet(
+                play::write_chat_message(MESSAGES.choose(&mut rand::rng()).unwrap()),
+                compression,
+            );
+        }
+        1 => {
+            // Punch animation
+            bot.send_packet(play::write_animation(rand::random()), compression);
+        }
+        2 => {
+            // Sneak
+            bot.send_packet(
+                play::write_entity_action(bot.entity_id, u32::from(rand::random::<bool>()), 0),
+                compression,
+            );
+        }
+        3 => {
+            // Sprint
+            bot.send_packet(
+                play::write_entity_action(bot.entity_id, if rand::random() { 3 } else { 4 }, 0),
+                compression,
+            );
+        }
+        4 => {
+            // Held item
+            bot.send_packet(
+                play::write_held_slot(rand::rng().random_range(0..9)),
+                compression,
+            );
+        }
+        _ => {}
+    }
+}
+
+/// The block which the bot is standing on
+fn block_below(bot: &Bot) -> (i32, i32, i32) {
+    (
+        bot.x.floor() as i32,
+        bot.y.floor() as i32 - 1,
+        bot.z.floor() as i32,
+    )
+}
+
+fn break_block(bot: &mut Bot, compression: &mut Compression) {
+    let (x, y, z) = block_below(bot);
+
+    // Started and finished digging, which is what the client sends in creative mode
+    for status in [0, 2] {
+        bot.sequence += 1;
+        let packet = play::write_player_action(status, x, y, z, 1, bot.sequence);
+        bot.send_packet(packet, compression);
+    }
+    bot.send_packet(play::write_animation(false), compression);
+}
+
+fn place_block(bot: &mut Bot, compression: &mut Compression) {
+    let (x, y, z) = block_below(bot);
+
+    bot.sequence += 1;
+    let packet = play::write_use_item_on(x, y, z, 1, bot.sequence);
+    bot.send_packet(packet, compression);
+    bot.send_packet(play::write_animation(false), compression);
+}
+
+fn attack_nearest(bot: &mut Bot, acts: bool, compression: &mut Compression) {
+    let Some((&target, &(x, y, z))) = bot
+        .players
+        .iter()
+        .min_by(|(_, a), (_, b)| distance_squared(bot, **a).total_cmp(&distance_squared(bot, **b)))
+    else {
+        return;
+    };
+
+    let (dx, dz) = (x - bot.x, z - bot.z);
+    let distance = dx.hypot(dz);
+    let yaw = (-dx).atan2(dz).to_degrees() as f32;
+
+    if distance > ATTACK_RANGE {
+        let step = WALK_SPEED / distance;
+        bot.x = dx.mul_add(step, bot.x);
+        bot.z = dz.mul_add(step, bot.z);
+        bot.send_packet(play::write_pos(bot.x, bot.y, bot.z, yaw, 0.0), compression);
+    } else if acts && (y - bot.y).abs() <= ATTACK_RANGE {
+        bot.send_packet(play::write_pos(bot.x, bot.y, bot.z, yaw, 0.0), compression);
+        bot.send_packet(play::write_attack(target), compression);
+        bot.send_packet(play::write_animation(false), compression);
+    }
+}
+
+fn distance_squared(bot: &Bot, (x, y, z): (f64, f64, f64)) -> f64 {
+    let (dx, dy, dz) = (x - bot.x, y - bot.y, z - bot.z);
+    dx.mul_add(dx, dy.mul_add(dy, dz * dz))
+}
@@ -22,30 +22,24 @@ use libdeflater::{CompressionLvl, Compressor, Decompressor};
 #[cfg(unix)]
 use mio::net::UnixStream;
 use mio::{Events, Interest, Poll, Registry, Token, event, net::TcpStream};
-use rand::{Rng, seq::IndexedRandom};
 
-use crate::{
-    packet_utils::Buf,
-    states::{login, play},
-};
+pub use crate::behavior::{Behavior, BehaviorConfig};
+use crate::{packet_utils::Buf, states::login};
 
+mod behavior;
 mod net;
 mod packet_processors;
 mod packet_utils;
 mod states;
 
-const SHOULD_MOVE: bool = true;
-
 const PROTOCOL_VERSION: u32 = 763;
 
-const MESSAGES: &[&str] = &["This is a chat message!", "Wow", "Server = on?"];
-
 pub struct BotManager {
     bot_on: Arc<AtomicU32>,
     addrs: Address,
     bots_per_tick: u32,
     tick_counter: u32,
-    action_tick: u32,
+    behavior: BehaviorConfig,
     map: HashMap<Token, Bot>,
     packet_buf: Buf,
     uncompressed_buf: Buf,
@@ -57,7 +51,12 @@ pub struct BotManager {
 }
 
 impl BotManager {
-    pub fn create(count: u32, addrs: Address, bot_on: Arc<AtomicU32>) -> anyhow::Result<Self> {
+    pub fn create(
+        count: u32,
+        addrs: Address,
+        bot_on: Arc<AtomicU32>,
+        behavior: BehaviorConfig,
+    ) -> anyhow::Result<Self> {
         let poll = Poll::new().expect("could not unwrap poll");
         // todo check used cap
         let events = Events::with_capacity((count * 5) as usize);
@@ -76,14 +75,13 @@ impl BotManager {
         let dur = Duration::from_millis(50);
 
         let tick_counter = 0;
-        let action_tick = 4;
 
         Ok(Self {
             bot_on,
             addrs,
             bots_per_tick,
             tick_counter,
-            action_tick,
+            behavior,
             map,
             packet_buf,
             uncompressed_buf,
@@ -151,6 +149,10 @@ impl BotManager {
                     z: 0.0,
                     buffering_buf: Buf::with_length(200),
                     joined: false,
+                    sequence: 0,
+                    players: HashMap::new(),
+                    path_center: None,
+                    path_angle: 0.0,
                 };
                 registry
                     .register(
@@ -198,61 +200,9 @@ impl BotManager {
         let mut to_remove = Vec::new();
 
         for bot in self.map.values_mut() {
-            if SHOULD_MOVE && bot.teleported {
-                bot.x += rand::random::<f64>().mul_add(1.0, -0.5);
-                bot.z += rand::random::<f64>().mul_add(1.0, -0.5);
-                bot.send_packet(play::write_current_pos(bot), &mut self.compression);
-
-                if (self.tick_counter + bot.id) % self.action_tick == 0 {
-                    match rand::rng().random_range(0..=4u8) {
-                        0 => {
-                            // Send chat
-                            bot.send_packet(
-                                play::write_chat_message(
-                                    MESSAGES.choose(&mut rand::rng()).unwrap(),
-                                ),
-                                &mut self.compression,
-                            );
-                        }
-                        1 => {
-                            // Punch animation
-                            bot.send_packet(
-                                play::write_animation(rand::random()),
-                                &mut self.compression,
-                            );
-                        }
-                        2 => {
-                            // Sneak
-                            bot.send_packet(
-                                play::write_entity_action(
-                                    bot.entity_id,
-                                    u32::from(rand::random::<bool>()),
-                                    0,
-                                ),
-                                &mut self.compression,
-                            );
-                        }
-                        3 => {
-                            // Sprint
-                            bot.send_packet(
-                                play::write_entity_action(
-                                    bot.entity_id,
-                                    if rand::random() { 3 } else { 4 },
-                                    0,
-                                ),
-                                &mut self.compression,
-                            );
-                        }
-                        4 => {
-                            // Held item
-                            bot.send_packet(
-                                play::write_held_slot(rand::rng().random_range(0..9)),
-                                &mut self.compression,
-                            );
-                        }
-                        _ => {}
-                    }
-                }
+            if bot.teleported {
+                self.behavior
+                    .run(bot, self.tick_counter, &mut self.compression);
             }
 
             if bot.kicked {
@@ -380,6 +330,12 @@ pub struct Bot {
     pub z: f64,
     pub buffering_buf: Buf,
     pub joined: bool,
+    /// The sequence number of the last block action
+    pub sequence: u32,
+    /// The positions of other players by entity id
+    pub players: HashMap<u32, (f64, f64, f64)>,
+    pub path_center: Option<(f64, f64)>,
+    pub path_angle: f64,
 }
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -6,7 +6,7 @@ use std::{
     sync::{Arc, atomic::AtomicU32},
 };
 
-use rust_mc_bot::{Address, BotManager};
+use rust_mc_bot::{Address, BehaviorConfig, BotManager};
 use serde::Deserialize;
 
 const UDS_PREFIX: &str = "unix://";
@@ -25,6 +25,23 @@ struct Config {
     /// Number of threads to use
     #[serde(default = "default_threads")]
     threads: usize,
+
+    /// Comma-separated behaviors which every bot runs: idle, wander, walk-path, random-actions,
+    /// break-blocks, place-blocks, build, inventory, chat or attack
+    #[serde(default = "default_behaviors")]
+    behaviors: String,
+
+    /// Ticks between chat messages sent by the chat behavior
+    #[serde(default = "default_chat_interval")]
+    chat_interval: u32,
+
+    /// Ticks between the actions of other behaviors
+    #[serde(default = "default_action_interval")]
+    action_interval: u32,
+
+    /// Radius in blocks of the circle which the walk-path behavior walks in
+    #[serde(default = "default_path_radius")]
+    path_radius: f64,
 }
 
 fn default_server() -> String {
@@ -39,6 +56,22 @@ fn default_threads() -> usize {
     1_usize.max(num_cpus::get())
 }
 
+fn default_behaviors() -> String {
+    "wander,random-actions".to_string()
+}
+
+const fn default_chat_interval() -> u32 {
+    20
+}
+
+const fn default_action_interval() -> u32 {
+    4
+}
+
+const fn default_path_radius() -> f64 {
+    8.0
+}
+
 fn main() {
     dotenvy::dotenv().ok();
     tracing_subscriber::fmt::init();
@@ -55,7 +88,8 @@ fn main() {
                 e
             );
             tracing::info!(
-                "Configure using BOT_SERVER, BOT_BOT_COUNT, and BOT_THREADS environment variables"
+                "Configure using BOT_SERVER, BOT_BOT_COUNT, BOT_THREADS, BOT_BEHAVIORS, \
+                 BOT_CHAT_INTERVAL, BOT_ACTION_INTERVAL, and BOT_PATH_RADIUS environment variables"
             );
             tracing::info!(
                 "Default values: BOT_SERVER={}, BOT_BOT_COUNT={}, BOT_THREADS={}",
@@ -67,6 +101,22 @@ fn main() {
         }
     };
 
+    let behaviors = match BehaviorConfig::parse_behaviors(&config.behaviors) {
+        Ok(behaviors) => behaviors,
+        Err(e) => {
+            tracing::error!("Invalid BOT_BEHAVIORS: {e}");
+            return;
+        }
+    };
+    tracing::info!("behaviors: {behaviors:?}");
+
+    let behavior = BehaviorConfig {
+        behaviors,
+        chat_interval: config.chat_interval,
+        action_interval: config.action_interval,
+        path_radius: config.path_radius,
+    };
+
     let addrs: Address = if config.server.starts_with(UDS_PREFIX) {
         #[cfg(unix)]
         {
@@ -104,8 +154,10 @@ fn main() {
         for _ in 0..config.threads {
             let addrs = addrs.clone();
             let bot_on = bot_on.clone();
+            let behavior = behavior.clone();
             threads.push(std::thread::spawn(move || {
-                let mut manager = BotManager::create(config.bot_count, addrs, bot_on).unwrap();
+                let mut manager =
+                    BotManager::create(config.bot_count, addrs, bot_on, behavior).unwrap();
                 manager.game_loop();
             }));
         }
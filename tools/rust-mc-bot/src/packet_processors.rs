@@ -35,6 +35,10 @@ pub fn lookup_packet(state: u8, packet: u8) -> Option<Packet> {
                 0x28 => return Some(play::process_join_game),         // JOIN_GAME
                 0x1A => return Some(play::process_kick),              // DISCONNECT
                 0x3C => return Some(play::process_teleport),          // PLAYER_POSITION_AND_LOOK
+                0x03 => return Some(play::process_spawn_player),      // SPAWN_PLAYER
+                0x2B | 0x2C => return Some(play::process_entity_move), /* ENTITY_POSITION(_AND_ROTATION) */
+                0x68 => return Some(play::process_entity_teleport),    // ENTITY_TELEPORT
+                0x3E => return Some(play::process_remove_entities),    // REMOVE_ENTITIES
                 _ => {}
             }
         }
@@ -40,6 +40,44 @@ pub fn process_teleport(buffer: &mut Buf, bot: &mut Bot, compression: &mut Compr
     bot.teleported = true;
 }
 
+pub fn process_spawn_player(buffer: &mut Buf, bot: &mut Bot, _compression: &mut Compression) {
+    let entity_id = buffer.read_var_u32().0;
+    let _uuid = buffer.read_u128();
+    let x = buffer.read_f64();
+    let y = buffer.read_f64();
+    let z = buffer.read_f64();
+    bot.players.insert(entity_id, (x, y, z));
+}
+
+pub fn process_entity_move(buffer: &mut Buf, bot: &mut Bot, _compression: &mut Compression) {
+    // Relative moves are in 1/4096ths of a block
+    let entity_id = buffer.read_var_u32().0;
+    let dx = f64::from(buffer.read_u16() as i16) / 4096.0;
+    let dy = f64::from(buffer.read_u16() as i16) / 4096.0;
+    let dz = f64::from(buffer.read_u16() as i16) / 4096.0;
+    if let Some((x, y, z)) = bot.players.get_mut(&entity_id) {
+        *x += dx;
+        *y += dy;
+        *z += dz;
+    }
+}
+
+pub fn process_entity_teleport(buffer: &mut Buf, bot: &mut Bot, _compression: &mut Compression) {
+    let entity_id = buffer.read_var_u32().0;
+    let x = buffer.read_f64();
+    let y = buffer.read_f64();
+    let z = buffer.read_f64();
+    if let Some(position) = bot.players.get_mut(&entity_id) {
+        *position = (x, y, z);
+    }
+}
+
+pub fn process_remove_entities(buffer: &mut Buf, bot: &mut Bot, _compression: &mut Compression) {
+    for entity_id in buffer.read_var_u32_slice() {
+        bot.players.remove(&entity_id);
+    }
+}
+
 pub fn write_chat_message(message: &str) -> Buf {
     // ClientChatMessagePacket
     let mut buf = Buf::new();
@@ -89,6 +127,74 @@ pub fn write_held_slot(slot: u16) -> Buf {
     buf
 }
 
+pub fn write_player_action(status: u32, x: i32, y: i32, z: i32, face: u8, sequence: u32) -> Buf {
+    // ClientPlayerActionPacket
+    let mut buf = Buf::new();
+    buf.write_packet_id(0x1D);
+
+    buf.write_var_u32(status);
+    buf.write_block_position(x, y, z);
+    buf.write_u8(face);
+    buf.write_var_u32(sequence);
+
+    buf
+}
+
+pub fn write_use_item_on(x: i32, y: i32, z: i32, face: u32, sequence: u32) -> Buf {
+    // ClientUseItemOnPacket
+    let mut buf = Buf::new();
+    buf.write_packet_id(0x31);
+
+    buf.write_var_u32(0); // main hand
+    buf.write_block_position(x, y, z);
+    buf.write_var_u32(face);
+    buf.write_f32(0.5);
+    buf.write_f32(1.0);
+    buf.write_f32(0.5);
+    buf.write_bool(false); // inside block
+    buf.write_var_u32(sequence);
+
+    buf
+}
+
+pub fn write_click_slot(window_id: u8, slot: u16) -> Buf {
+    // ClientClickContainerPacket
+    let mut buf = Buf::new();
+    buf.write_packet_id(0x0B);
+
+    buf.write_u8(window_id);
+    buf.write_var_u32(0); // state id
+    buf.write_u16(slot);
+    buf.write_u8(0); // left click
+    buf.write_var_u32(0); // pickup
+    buf.write_var_u32(0); // changed slots
+    buf.write_bool(false); // carried item
+
+    buf
+}
+
+pub fn write_close_window(window_id: u8) -> Buf {
+    // ClientCloseContainerPacket
+    let mut buf = Buf::new();
+    buf.write_packet_id(0x0C);
+
+    buf.write_u8(window_id);
+
+    buf
+}
+
+pub fn write_attack(entity_id: u32) -> Buf {
+    // ClientInteractPacket
+    let mut buf = Buf::new();
+    buf.write_packet_id(0x10);
+
+    buf.write_var_u32(entity_id);
+    buf.write_var_u32(1); // attack
+    buf.write_bool(false); // sneaking
+
+    buf
+}
+
 pub fn write_tele_confirm(id: u32) -> Buf {
     // ClientTeleportConfirmPacket
     let mut buf = Buf::new();